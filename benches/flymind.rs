@@ -0,0 +1,136 @@
+//! Baseline timings for loading, graph algorithms, and simulation.
+//!
+//! Each benchmark id carries an engine label ("serial/f64" today) so alternative
+//! engines can be added to the same groups and compared side by side. Synthetic
+//! inputs come from `celegans_connectome::synthetic` with fixed seeds.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use celegans_connectome::{synthetic, Network};
+
+const ENGINE: &str = "serial/f64";
+const SEED: u64 = 0x5EED;
+
+fn connectome() -> Network {
+    Network::from_connect_csv("NeuronConnect.csv").expect("NeuronConnect.csv in crate root")
+}
+
+fn loading(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+
+    let bytes = std::fs::read("NeuronConnect.csv").expect("NeuronConnect.csv in crate root");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_with_input(
+        BenchmarkId::new(ENGINE, "NeuronConnect"),
+        &bytes,
+        |b, bytes| b.iter(|| Network::from_connect_reader(black_box(bytes.as_slice())).unwrap()),
+    );
+
+    let generated = synthetic::edge_list_csv(20_000, 1_000_000, SEED);
+    group.throughput(Throughput::Bytes(generated.len() as u64));
+    group.bench_with_input(
+        BenchmarkId::new(ENGINE, "generated-1M"),
+        &generated,
+        |b, bytes| b.iter(|| Network::from_connect_reader(black_box(bytes.as_slice())).unwrap()),
+    );
+    group.finish();
+
+    // Every name of the 1M-row load looked up by borrowed `&str`.
+    let loaded = Network::from_connect_reader(generated.as_slice()).unwrap();
+    let names: Vec<String> = loaded
+        .neurons
+        .iter()
+        .map(|n| n.name().to_string())
+        .collect();
+    let mut group = c.benchmark_group("id_of");
+    group.throughput(Throughput::Elements(names.len() as u64));
+    group.bench_with_input(
+        BenchmarkId::new(ENGINE, "generated-1M"),
+        &names,
+        |b, names| {
+            b.iter(|| {
+                names
+                    .iter()
+                    .filter_map(|name| loaded.id_of(black_box(name)))
+                    .count()
+            })
+        },
+    );
+    group.finish();
+}
+
+fn graph(c: &mut Criterion) {
+    let network = connectome();
+    let synthetic_net = synthetic::random_network(20_000, 400_000, SEED);
+
+    let mut group = c.benchmark_group("csr");
+    for (label, net) in [
+        ("NeuronConnect", &network),
+        ("synthetic-20k", &synthetic_net),
+    ] {
+        group.bench_with_input(BenchmarkId::new(ENGINE, label), net, |b, net| {
+            b.iter(|| black_box(net.build_csr()))
+        });
+    }
+    group.finish();
+
+    let from = network.id_of("ASHL").expect("ASHL present");
+    let to = network.id_of("AVAL").expect("AVAL present");
+    let mut group = c.benchmark_group("shortest_path");
+    group.bench_with_input(BenchmarkId::new(ENGINE, "ASHL-AVAL"), &network, |b, net| {
+        b.iter(|| black_box(net.shortest_path(from, to)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("node_betweenness");
+    group.sample_size(10);
+    group.bench_with_input(
+        BenchmarkId::new(ENGINE, "NeuronConnect"),
+        &network,
+        |b, net| b.iter(|| black_box(net.node_betweenness())),
+    );
+    group.finish();
+
+    let mut group = c.benchmark_group("pagerank");
+    for (label, net) in [
+        ("NeuronConnect", &network),
+        ("synthetic-20k", &synthetic_net),
+    ] {
+        group.bench_with_input(BenchmarkId::new(ENGINE, label), net, |b, net| {
+            b.iter(|| black_box(net.pagerank(0.85, 1e-10)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("edge_betweenness");
+    group.sample_size(10);
+    group.bench_with_input(
+        BenchmarkId::new(ENGINE, "NeuronConnect"),
+        &network,
+        |b, net| b.iter(|| black_box(net.edge_betweenness())),
+    );
+    group.finish();
+}
+
+fn simulation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_step");
+    let inputs = [
+        ("NeuronConnect", connectome()),
+        (
+            "synthetic-20k",
+            synthetic::random_network(20_000, 400_000, SEED),
+        ),
+    ];
+    for (label, mut net) in inputs {
+        group.bench_function(BenchmarkId::new(ENGINE, label), |b| {
+            b.iter(|| net.update_step(black_box(0.1)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, loading, graph, simulation);
+criterion_main!(benches);
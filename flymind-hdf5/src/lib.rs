@@ -0,0 +1,254 @@
+//! HDF5 output for `celegans_connectome` simulation results.
+//!
+//! One file holds the spikes, the recorded potentials, the configuration and
+//! the network the run used, so it can be analysed without the inputs; see
+//! the README for the layout.
+
+use std::path::Path;
+
+use hdf5::types::VarLenUnicode;
+use hdf5::{File, Group, H5Type};
+use ndarray::{s, Array2};
+
+use celegans_connectome::{Network, SimulationConfig, SimulationResult};
+
+/// Rows of the voltage matrix per chunk.
+const VOLTAGE_CHUNK_ROWS: usize = 1024;
+const DEFLATE_LEVEL: u8 = 4;
+
+pub trait ToHdf5 {
+    /// Write to a new file at `path`, replacing any existing one. `network` is
+    /// the one the result came from; `config` is stored if given.
+    fn to_hdf5<P: AsRef<Path>>(
+        &self,
+        path: P,
+        network: &Network,
+        config: Option<&SimulationConfig>,
+    ) -> hdf5::Result<()>;
+}
+
+impl ToHdf5 for SimulationResult {
+    fn to_hdf5<P: AsRef<Path>>(
+        &self,
+        path: P,
+        network: &Network,
+        config: Option<&SimulationConfig>,
+    ) -> hdf5::Result<()> {
+        let file = File::create(path)?;
+        write_spikes(&file.create_group("spikes")?, self)?;
+        write_voltages(&file.create_group("voltages")?, self, network)?;
+        let config_group = file.create_group("config")?;
+        if let Some(config) = config {
+            let toml = config
+                .to_toml_string()
+                .map_err(|err| hdf5::Error::from(err.to_string()))?;
+            config_group
+                .new_attr::<VarLenUnicode>()
+                .create("toml")?
+                .write_scalar(&unicode(&toml)?)?;
+        }
+        write_network(&file.create_group("network")?, network)?;
+        Ok(())
+    }
+}
+
+fn unicode(s: &str) -> hdf5::Result<VarLenUnicode> {
+    s.parse()
+        .map_err(|err| hdf5::Error::from(format!("{s:?}: {err}")))
+}
+
+fn unicode_all<'a>(items: impl Iterator<Item = &'a str>) -> hdf5::Result<Vec<VarLenUnicode>> {
+    items.map(unicode).collect()
+}
+
+fn dataset<T: H5Type>(group: &Group, name: &str, data: &[T]) -> hdf5::Result<()> {
+    group.new_dataset_builder().with_data(data).create(name)?;
+    Ok(())
+}
+
+fn write_spikes(group: &Group, result: &SimulationResult) -> hdf5::Result<()> {
+    let mut spikes: Vec<(usize, u32)> = result
+        .spike_trains
+        .iter()
+        .enumerate()
+        .flat_map(|(id, train)| train.iter().map(move |step| (step, id as u32)))
+        .collect();
+    spikes.sort_unstable();
+    let ids: Vec<u32> = spikes.iter().map(|&(_, id)| id).collect();
+    let times: Vec<f64> = spikes
+        .iter()
+        .map(|&(step, _)| step as f64 * result.dt)
+        .collect();
+    dataset(group, "neuron_ids", &ids)?;
+    dataset(group, "times", &times)
+}
+
+fn write_voltages(group: &Group, result: &SimulationResult, network: &Network) -> hdf5::Result<()> {
+    let mut watched: Vec<usize> = result.potentials.keys().copied().collect();
+    watched.sort_unstable();
+    let rows = watched
+        .iter()
+        .map(|id| result.potentials[id].len())
+        .min()
+        .unwrap_or(0);
+
+    let shape = (rows, watched.len());
+    let data = if rows == 0 || watched.is_empty() {
+        group.new_dataset::<f64>().shape(shape).create("data")?
+    } else {
+        group
+            .new_dataset::<f64>()
+            .shape(shape)
+            .chunk((rows.min(VOLTAGE_CHUNK_ROWS), watched.len()))
+            .deflate(DEFLATE_LEVEL)
+            .create("data")?
+    };
+    // One chunk of rows at a time, so a long run is never copied whole.
+    for start in (0..rows).step_by(VOLTAGE_CHUNK_ROWS) {
+        let end = (start + VOLTAGE_CHUNK_ROWS).min(rows);
+        let block = Array2::from_shape_fn((end - start, watched.len()), |(t, k)| {
+            result.potentials[&watched[k]][start + t]
+        });
+        data.write_slice(&block, s![start..end, ..])?;
+    }
+    let names = unicode_all(watched.iter().map(|&id| network.neurons[id].name()))?;
+    data.new_attr_builder()
+        .with_data(names.as_slice())
+        .create("neuron_names")?;
+    data.new_attr::<u64>()
+        .create("stride")?
+        .write_scalar(&(result.potential_stride as u64))?;
+    data.new_attr::<f64>()
+        .create("dt")?
+        .write_scalar(&result.dt)?;
+    Ok(())
+}
+
+fn write_network(group: &Group, network: &Network) -> hdf5::Result<()> {
+    let names = unicode_all(network.neurons.iter().map(|n| n.name()))?;
+    let types = unicode_all(network.neurons.iter().map(|n| n.neuron_type.label()))?;
+    dataset(group, "names", &names)?;
+    dataset(group, "types", &types)?;
+
+    let edges = group.create_group("edges")?;
+    let from: Vec<u32> = network
+        .connections
+        .iter()
+        .map(|c| c.from_id as u32)
+        .collect();
+    let to: Vec<u32> = network.connections.iter().map(|c| c.to_id as u32).collect();
+    let weight: Vec<f64> = network.connections.iter().map(|c| c.weight).collect();
+    let synapse = unicode_all(network.connections.iter().map(|c| c.synapse_type.code()))?;
+    dataset(&edges, "from", &from)?;
+    dataset(&edges, "to", &to)?;
+    dataset(&edges, "synapse", &synapse)?;
+    dataset(&edges, "weight", &weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use celegans_connectome::NeuronType;
+
+    /// ALML driven for the whole run, with ALML and AVDL recorded at every
+    /// step: more rows than one voltage chunk holds.
+    const CONFIG: &str = r#"duration = 1250.0
+dt = 0.5
+
+[[stimulus]]
+neuron = "ALML"
+duration = 1250.0
+amplitude = 40.0
+
+[recorders.voltages]
+neurons = ["AVDL", "ALML"]
+"#;
+
+    fn strings(group: &Group, name: &str) -> Vec<String> {
+        group
+            .dataset(name)
+            .unwrap()
+            .read_raw::<VarLenUnicode>()
+            .unwrap()
+            .iter()
+            .map(|s| s.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn a_written_run_reads_back() {
+        let mut network = Network::example_touch_circuit();
+        let config = SimulationConfig::from_toml_str(CONFIG).unwrap();
+        let result = config.run(&mut network).unwrap();
+        let path = std::env::temp_dir().join(format!("flymind-hdf5-{}.h5", std::process::id()));
+        result.to_hdf5(&path, &network, Some(&config)).unwrap();
+        let file = File::open(&path).unwrap();
+
+        let group = file.group("network").unwrap();
+        let names: Vec<&str> = network.neurons.iter().map(|n| n.name()).collect();
+        assert_eq!(strings(&group, "names"), names);
+        let types = strings(&group, "types");
+        assert_eq!(types.len(), names.len());
+        for (label, neuron) in types.iter().zip(&network.neurons) {
+            assert_eq!(NeuronType::from_label(label), Some(neuron.neuron_type));
+        }
+
+        let edges = file.group("network/edges").unwrap();
+        let from = edges.dataset("from").unwrap().read_raw::<u32>().unwrap();
+        let to = edges.dataset("to").unwrap().read_raw::<u32>().unwrap();
+        let weight = edges.dataset("weight").unwrap().read_raw::<f64>().unwrap();
+        let synapse = strings(&edges, "synapse");
+        assert_eq!(from.len(), network.connections.len());
+        for (i, conn) in network.connections.iter().enumerate() {
+            assert_eq!(
+                (
+                    from[i] as usize,
+                    to[i] as usize,
+                    weight[i],
+                    synapse[i].as_str()
+                ),
+                (
+                    conn.from_id,
+                    conn.to_id,
+                    conn.weight,
+                    conn.synapse_type.code()
+                )
+            );
+        }
+
+        let data = file.dataset("voltages/data").unwrap();
+        let rows = result.steps;
+        assert!(rows > VOLTAGE_CHUNK_ROWS);
+        assert_eq!(data.shape(), [rows, 2]);
+        let recorded: Vec<String> = data
+            .attr("neuron_names")
+            .unwrap()
+            .read_raw::<VarLenUnicode>()
+            .unwrap()
+            .iter()
+            .map(|s| s.as_str().to_string())
+            .collect();
+        // Columns are in id order, whatever order the config listed them.
+        assert_eq!(recorded, ["ALML", "AVDL"]);
+        assert_eq!(
+            data.attr("stride").unwrap().read_scalar::<u64>().unwrap(),
+            1
+        );
+        assert_eq!(data.attr("dt").unwrap().read_scalar::<f64>().unwrap(), 0.5);
+        let matrix = data.read_2d::<f64>().unwrap();
+        let alml = network.id_of("ALML").unwrap();
+        for t in [0, VOLTAGE_CHUNK_ROWS - 1, VOLTAGE_CHUNK_ROWS, rows - 1] {
+            assert_eq!(matrix[[t, 0]], result.potentials[&alml][t], "row {t}");
+        }
+
+        let times = file
+            .dataset("spikes/times")
+            .unwrap()
+            .read_raw::<f64>()
+            .unwrap();
+        let total: usize = result.spike_trains.iter().map(|train| train.len()).sum();
+        assert_eq!(times.len(), total);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+//! Parquet export for `celegans_connectome` spikes and connections.
+//!
+//! Repeated strings (neuron names, types, synapse labels) are dictionary
+//! encoded, so they cost a small integer per row on disk and in Arrow.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, DictionaryArray, Float64Array, UInt32Array};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::Result;
+use parquet::file::properties::WriterProperties;
+
+use celegans_connectome::{Network, SimulationResult};
+
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    /// Rows per row group; smaller groups let readers skip more of a long
+    /// recording, larger ones compress better.
+    pub row_group_size: usize,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        Self {
+            row_group_size: 1 << 20,
+        }
+    }
+}
+
+pub trait SpikesToParquet {
+    /// Write one row per spike, in time order. `network` is the one the
+    /// result came from and supplies names and types.
+    fn spikes_to_parquet<P: AsRef<Path>>(
+        &self,
+        path: P,
+        network: &Network,
+        options: &ParquetOptions,
+    ) -> Result<()>;
+}
+
+pub trait ConnectionsToParquet {
+    /// Write one row per connection, in connection order.
+    fn connections_to_parquet<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ParquetOptions,
+    ) -> Result<()>;
+}
+
+fn dictionary_field(name: &str, nullable: bool) -> Field {
+    let data_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+    Field::new(name, data_type, nullable)
+}
+
+fn write<P: AsRef<Path>>(
+    path: P,
+    schema: Schema,
+    columns: Vec<ArrayRef>,
+    options: &ParquetOptions,
+) -> Result<()> {
+    let schema = Arc::new(schema);
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(options.row_group_size.max(1))
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+impl SpikesToParquet for SimulationResult {
+    fn spikes_to_parquet<P: AsRef<Path>>(
+        &self,
+        path: P,
+        network: &Network,
+        options: &ParquetOptions,
+    ) -> Result<()> {
+        let mut spikes: Vec<(usize, usize)> = self
+            .spike_trains
+            .iter()
+            .enumerate()
+            .flat_map(|(id, train)| train.iter().map(move |step| (step, id)))
+            .collect();
+        spikes.sort_unstable();
+
+        let time: Float64Array = spikes
+            .iter()
+            .map(|&(step, _)| step as f64 * self.dt)
+            .collect();
+        let ids: UInt32Array = spikes.iter().map(|&(_, id)| id as u32).collect();
+        let names: DictionaryArray<Int32Type> = spikes
+            .iter()
+            .map(|&(_, id)| network.neurons[id].name())
+            .collect();
+        let types: DictionaryArray<Int32Type> = spikes
+            .iter()
+            .map(|&(_, id)| network.neurons[id].neuron_type.label())
+            .collect();
+
+        let schema = Schema::new(vec![
+            Field::new("time", DataType::Float64, false),
+            Field::new("neuron_id", DataType::UInt32, false),
+            dictionary_field("neuron_name", false),
+            dictionary_field("neuron_type", false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(time),
+            Arc::new(ids),
+            Arc::new(names),
+            Arc::new(types),
+        ];
+        write(path, schema, columns, options)
+    }
+}
+
+impl ConnectionsToParquet for Network {
+    fn connections_to_parquet<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ParquetOptions,
+    ) -> Result<()> {
+        let name = |id: usize| self.neurons[id].name();
+        let from: DictionaryArray<Int32Type> =
+            self.connections.iter().map(|c| name(c.from_id)).collect();
+        let to: DictionaryArray<Int32Type> =
+            self.connections.iter().map(|c| name(c.to_id)).collect();
+        let synapse: DictionaryArray<Int32Type> = self
+            .connections
+            .iter()
+            .map(|c| c.synapse_type.label())
+            .collect();
+        let subtype: DictionaryArray<Int32Type> = self
+            .connections
+            .iter()
+            .map(|c| c.synapse_type.subtype().map(|s| s.label()))
+            .collect();
+        let weight: Float64Array = self.connections.iter().map(|c| c.weight).collect();
+
+        let schema = Schema::new(vec![
+            dictionary_field("from_name", false),
+            dictionary_field("to_name", false),
+            dictionary_field("synapse_type", false),
+            dictionary_field("subtype", true),
+            Field::new("weight", DataType::Float64, false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(from),
+            Arc::new(to),
+            Arc::new(synapse),
+            Arc::new(subtype),
+            Arc::new(weight),
+        ];
+        write(path, schema, columns, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray, StringArray};
+    use arrow::datatypes::{Float64Type, UInt32Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet::basic::Encoding;
+
+    use celegans_connectome::SimulationConfig;
+
+    /// ALML driven for 150 ms, 100 spikes in all.
+    const CONFIG: &str = r#"duration = 200.0
+dt = 0.5
+
+[[stimulus]]
+neuron = "ALML"
+start = 10.0
+duration = 150.0
+amplitude = 40.0
+"#;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flymind-{name}-{}.parquet", std::process::id()))
+    }
+
+    /// Every row of the file at `path`, and its row-group count.
+    fn read(path: &Path) -> (RecordBatch, usize) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let metadata = builder.metadata().clone();
+        for group in metadata.row_groups() {
+            for column in group.columns() {
+                let dictionary = column.encodings().contains(&Encoding::RLE_DICTIONARY);
+                let name = column.column_descr().name();
+                // Strings are dictionary encoded on disk; numbers may be too.
+                if !["time", "neuron_id", "weight"].contains(&name) {
+                    assert!(dictionary, "{name} is not dictionary encoded");
+                }
+            }
+        }
+        let schema = builder.schema().clone();
+        let batches: Vec<RecordBatch> = builder.build().unwrap().map(Result::unwrap).collect();
+        let batch = arrow::compute::concat_batches(&schema, &batches).unwrap();
+        (batch, metadata.num_row_groups())
+    }
+
+    /// The strings of the dictionary column `name`, `None` where null.
+    fn strings(batch: &RecordBatch, name: &str) -> Vec<Option<String>> {
+        let column = batch.column_by_name(name).unwrap();
+        assert!(
+            matches!(column.data_type(), DataType::Dictionary(..)),
+            "{name}"
+        );
+        let dictionary = column.as_dictionary::<Int32Type>();
+        let values = dictionary.downcast_dict::<StringArray>().unwrap();
+        values.into_iter().map(|s| s.map(str::to_string)).collect()
+    }
+
+    #[test]
+    fn spikes_round_trip_in_small_row_groups() {
+        let mut network = Network::example_touch_circuit();
+        let config = SimulationConfig::from_toml_str(CONFIG).unwrap();
+        let result = config.run(&mut network).unwrap();
+        let mut expected: Vec<(usize, usize)> = result
+            .spike_trains
+            .iter()
+            .enumerate()
+            .flat_map(|(id, train)| train.iter().map(move |step| (step, id)))
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(expected.len(), 100);
+
+        let path = temp_path("spikes");
+        let options = ParquetOptions { row_group_size: 32 };
+        result.spikes_to_parquet(&path, &network, &options).unwrap();
+        let (batch, row_groups) = read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batch.num_rows(), expected.len());
+        assert_eq!(row_groups, 4);
+        let time = batch
+            .column_by_name("time")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        let ids = batch
+            .column_by_name("neuron_id")
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let names = strings(&batch, "neuron_name");
+        let types = strings(&batch, "neuron_type");
+        for (row, &(step, id)) in expected.iter().enumerate() {
+            let neuron = &network.neurons[id];
+            assert_eq!(time.value(row), step as f64 * result.dt);
+            assert_eq!(ids.value(row) as usize, id);
+            assert_eq!(names[row].as_deref(), Some(neuron.name()));
+            assert_eq!(types[row].as_deref(), Some(neuron.neuron_type.label()));
+        }
+    }
+
+    #[test]
+    fn connections_round_trip_in_small_row_groups() {
+        let network = Network::example_touch_circuit();
+        let path = temp_path("connections");
+        let options = ParquetOptions {
+            row_group_size: 100,
+        };
+        network.connections_to_parquet(&path, &options).unwrap();
+        let (batch, row_groups) = read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let rows = network.connections.len();
+        assert_eq!(batch.num_rows(), rows);
+        assert_eq!(row_groups, rows.div_ceil(100));
+        let from = strings(&batch, "from_name");
+        let to = strings(&batch, "to_name");
+        let synapse = strings(&batch, "synapse_type");
+        let subtype = strings(&batch, "subtype");
+        let weight = batch
+            .column_by_name("weight")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        assert!(subtype.iter().any(Option::is_none));
+        for (row, conn) in network.connections.iter().enumerate() {
+            assert_eq!(
+                from[row].as_deref(),
+                Some(network.neurons[conn.from_id].name())
+            );
+            assert_eq!(to[row].as_deref(), Some(network.neurons[conn.to_id].name()));
+            assert_eq!(synapse[row].as_deref(), Some(conn.synapse_type.label()));
+            assert_eq!(
+                subtype[row].as_deref(),
+                conn.synapse_type.subtype().map(|s| s.label())
+            );
+            assert_eq!(weight.value(row), conn.weight);
+            assert!(weight.is_valid(row));
+        }
+    }
+}
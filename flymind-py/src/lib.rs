@@ -0,0 +1,276 @@
+//! Python bindings for `celegans_connectome`.
+//!
+//! `flymind.Network` owns a Rust `Network`; results come back as plain lists
+//! and dicts so they drop straight into numpy or pandas. Library errors are
+//! raised as `flymind.FlymindError` carrying the Rust message.
+
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use celegans_connectome::{
+    Direction, Network as CoreNetwork, PathOptions, Stimulus, StimulusShape, SynapseClass,
+};
+
+create_exception!(
+    flymind,
+    FlymindError,
+    PyException,
+    "An error from the flymind library."
+);
+
+fn core_err(err: celegans_connectome::FlymindError) -> PyErr {
+    FlymindError::new_err(err.to_string())
+}
+
+/// A loaded connectome.
+#[pyclass(name = "Network", module = "flymind")]
+pub struct Network {
+    inner: CoreNetwork,
+}
+
+impl Network {
+    fn id(&self, name: &str) -> PyResult<usize> {
+        self.inner
+            .id_of(name)
+            .ok_or_else(|| PyKeyError::new_err(format!("unknown neuron {name:?}")))
+    }
+
+    fn name(&self, id: usize) -> String {
+        self.inner.neurons[id].name().to_string()
+    }
+}
+
+fn parse_direction(direction: &str) -> PyResult<Direction> {
+    match direction {
+        "out" => Ok(Direction::Out),
+        "in" => Ok(Direction::In),
+        "both" => Ok(Direction::Both),
+        other => Err(PyValueError::new_err(format!(
+            "direction must be 'out', 'in' or 'both', got {other:?}"
+        ))),
+    }
+}
+
+fn parse_class(class: &str) -> PyResult<SynapseClass> {
+    match class {
+        "chem" | "chemical" => Ok(SynapseClass::Chemical),
+        "gap" | "gap_junction" => Ok(SynapseClass::GapJunction),
+        "nmj" => Ok(SynapseClass::Nmj),
+        other => Err(PyValueError::new_err(format!(
+            "synapse type must be 'chem', 'gap' or 'nmj', got {other:?}"
+        ))),
+    }
+}
+
+/// Read one stimulus dict: `neuron`, `start_step`, `duration_steps` and
+/// `amplitude`, plus `period_steps` and `width_steps` for a pulse train or
+/// `end_amplitude` for a ramp.
+fn parse_stimulus(network: &Network, spec: &Bound<'_, PyDict>) -> PyResult<Stimulus> {
+    fn get<'py, T: FromPyObject<'py>>(spec: &Bound<'py, PyDict>, key: &str) -> PyResult<Option<T>> {
+        spec.get_item(key)?.map(|value| value.extract()).transpose()
+    }
+    fn required<'py, T: FromPyObject<'py>>(spec: &Bound<'py, PyDict>, key: &str) -> PyResult<T> {
+        get(spec, key)?.ok_or_else(|| PyKeyError::new_err(format!("stimulus needs {key:?}")))
+    }
+
+    let neuron: String = required(spec, "neuron")?;
+    let mut stimulus = Stimulus::new(
+        network.id(&neuron)?,
+        get(spec, "start_step")?.unwrap_or(0),
+        required(spec, "duration_steps")?,
+        required(spec, "amplitude")?,
+    );
+    let period: Option<usize> = get(spec, "period_steps")?;
+    let width: Option<usize> = get(spec, "width_steps")?;
+    let end_amplitude: Option<f64> = get(spec, "end_amplitude")?;
+    stimulus.shape =
+        match (period, width, end_amplitude) {
+            (None, None, None) => StimulusShape::Step,
+            (Some(period_steps), Some(width_steps), None) => StimulusShape::Pulse {
+                period_steps: period_steps.max(1),
+                width_steps,
+            },
+            (None, None, Some(end_amplitude)) => StimulusShape::Ramp { end_amplitude },
+            _ => return Err(PyValueError::new_err(
+                "a stimulus takes period_steps and width_steps together, or end_amplitude alone",
+            )),
+        };
+    Ok(stimulus)
+}
+
+#[pymethods]
+impl Network {
+    /// Load a NeuronConnect.csv-style edge list.
+    #[staticmethod]
+    fn from_csv(path: PathBuf) -> PyResult<Self> {
+        let inner = CoreNetwork::from_connect_csv(&path).map_err(core_err)?;
+        Ok(Self { inner })
+    }
+
+    /// Apply a `name, type[, region]` CSV; returns the names matching no neuron.
+    fn apply_metadata(&mut self, path: PathBuf) -> PyResult<Vec<String>> {
+        self.inner.apply_metadata_csv(&path).map_err(core_err)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.neurons.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Network({} neurons, {} connections)",
+            self.inner.neurons.len(),
+            self.inner.connections.len()
+        )
+    }
+
+    #[getter]
+    fn neuron_names(&self) -> Vec<String> {
+        self.inner
+            .neurons
+            .iter()
+            .map(|n| n.name().to_string())
+            .collect()
+    }
+
+    #[getter]
+    fn connection_count(&self) -> usize {
+        self.inner.connections.len()
+    }
+
+    /// The structural summary as a nested dict.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let json = serde_json::to_string(&self.inner.stats())
+            .map_err(|err| FlymindError::new_err(err.to_string()))?;
+        py.import_bound("json")?.call_method1("loads", (json,))
+    }
+
+    /// `(partner, synapse, weight)` for each connection of `name`, heaviest first.
+    #[pyo3(signature = (name, direction = "out"))]
+    fn neighbors(&self, name: &str, direction: &str) -> PyResult<Vec<(String, String, f64)>> {
+        let id = self.id(name)?;
+        let mut rows: Vec<(String, String, f64)> = self
+            .inner
+            .connections_of(id, parse_direction(direction)?)
+            .into_iter()
+            .map(|c| {
+                let conn = &self.inner.connections[c];
+                let partner = if conn.from_id == id {
+                    conn.to_id
+                } else {
+                    conn.from_id
+                };
+                (
+                    self.name(partner),
+                    conn.synapse_type.label().to_string(),
+                    conn.weight,
+                )
+            })
+            .collect();
+        rows.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        Ok(rows)
+    }
+
+    /// Fewest-hop path as a list of names, or `None` when unreachable.
+    #[pyo3(signature = (source, target, synapse_types = None, max_hops = None))]
+    fn shortest_path(
+        &self,
+        source: &str,
+        target: &str,
+        synapse_types: Option<Vec<String>>,
+        max_hops: Option<usize>,
+    ) -> PyResult<Option<Vec<String>>> {
+        let mut opts = PathOptions {
+            max_hops,
+            ..PathOptions::default()
+        };
+        if let Some(types) = synapse_types {
+            opts.classes = types
+                .iter()
+                .map(String::as_str)
+                .map(parse_class)
+                .collect::<PyResult<_>>()?;
+        }
+        let path = self
+            .inner
+            .shortest_path_with(self.id(source)?, self.id(target)?, &opts);
+        Ok(path.map(|path| path.neurons.into_iter().map(|id| self.name(id)).collect()))
+    }
+
+    /// The network induced by the named neurons.
+    fn subnetwork(&self, names: Vec<String>) -> PyResult<Network> {
+        let ids = names
+            .iter()
+            .map(|n| self.id(n))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Network {
+            inner: self.inner.subnetwork(&ids),
+        })
+    }
+
+    /// Sensory and motor neurons plus the interneurons linking them within
+    /// `max_depth` chemical hops.
+    fn sensorimotor_subnetwork(&self, max_depth: usize) -> Network {
+        Network {
+            inner: self.inner.sensorimotor_subnetwork(max_depth),
+        }
+    }
+
+    /// Run the LIF model from rest for `steps` steps of `dt`.
+    ///
+    /// Returns a dict with `steps`, `dt`, `spikes` (name to spike steps) and
+    /// `potentials` (name to one sample per step, for the `watch` neurons).
+    #[pyo3(signature = (steps, dt, stimuli = None, watch = None, seed = 0))]
+    fn simulate<'py>(
+        &mut self,
+        py: Python<'py>,
+        steps: usize,
+        dt: f64,
+        stimuli: Option<Vec<Bound<'py, PyDict>>>,
+        watch: Option<Vec<String>>,
+        seed: u64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let stimuli = stimuli
+            .unwrap_or_default()
+            .iter()
+            .map(|spec| parse_stimulus(self, spec))
+            .collect::<PyResult<Vec<_>>>()?;
+        let watch = watch
+            .unwrap_or_default()
+            .iter()
+            .map(|n| self.id(n))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.inner.check_dt(dt).map_err(core_err)?;
+
+        self.inner.reset_state();
+        self.inner.seed(seed);
+        self.inner.stimuli = stimuli;
+        let result = py.allow_threads(|| self.inner.run_simulation_recording(steps, dt, &watch));
+
+        let spikes = PyDict::new_bound(py);
+        for (id, train) in result.spike_trains.iter().enumerate() {
+            spikes.set_item(self.name(id), train.to_vec())?;
+        }
+        let potentials = PyDict::new_bound(py);
+        for (id, trace) in &result.potentials {
+            potentials.set_item(self.name(*id), trace)?;
+        }
+        let out = PyDict::new_bound(py);
+        out.set_item("steps", result.steps)?;
+        out.set_item("dt", result.dt)?;
+        out.set_item("spikes", spikes)?;
+        out.set_item("potentials", potentials)?;
+        Ok(out)
+    }
+}
+
+#[pymodule]
+fn flymind(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Network>()?;
+    m.add("FlymindError", m.py().get_type_bound::<FlymindError>())?;
+    Ok(())
+}
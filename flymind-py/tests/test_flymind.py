@@ -0,0 +1,137 @@
+"""Tests for the flymind bindings; run with `pytest` after installing the wheel."""
+
+from pathlib import Path
+
+import pytest
+
+import flymind
+from flymind import Network
+
+CONNECTOME = Path(__file__).resolve().parents[2] / "NeuronConnect.csv"
+
+
+@pytest.fixture(scope="module")
+def net():
+    return Network.from_csv(str(CONNECTOME))
+
+
+@pytest.fixture
+def small(tmp_path):
+    path = tmp_path / "small.csv"
+    path.write_text(
+        "Neuron 1,Neuron 2,Type,Nbr\n"
+        "A,B,Sp,3\n"
+        "B,A,R,3\n"
+        "B,C,S,2\n"
+        "C,B,R,2\n"
+        "A,C,EJ,1\n"
+        "C,A,EJ,1\n"
+    )
+    return Network.from_csv(str(path))
+
+
+def test_loads_the_connectome(net):
+    assert len(net) == 283
+    assert net.connection_count == 6417
+    assert len(net.neuron_names) == 283
+    assert "AVAL" in net.neuron_names
+    assert repr(net) == "Network(283 neurons, 6417 connections)"
+
+
+def test_shortest_path_ashl_to_aval(net):
+    assert net.shortest_path("ASHL", "AVAL") == ["ASHL", "AVAL"]
+
+
+def test_shortest_path_options(small):
+    assert small.shortest_path("A", "C") == ["A", "B", "C"]
+    assert small.shortest_path("A", "C", synapse_types=["gap"]) == ["A", "C"]
+    assert small.shortest_path("A", "C", max_hops=1) is None
+    assert small.shortest_path("C", "A") is None
+    with pytest.raises(ValueError):
+        small.shortest_path("A", "C", synapse_types=["telepathy"])
+
+
+def test_neighbors_are_heaviest_first(small):
+    out = small.neighbors("B")
+    assert [partner for partner, _, _ in out] == ["A", "C"]
+    weights = [weight for _, _, weight in out]
+    assert weights == sorted(weights, reverse=True)
+    assert ("A", "chemical_send", 3.0) in small.neighbors("B", direction="in")
+    with pytest.raises(ValueError):
+        small.neighbors("B", direction="sideways")
+
+
+def test_stats_is_a_plain_dict(net):
+    stats = net.stats()
+    assert stats["neuron_count"] == 283
+    assert stats["connection_count"] == 6417
+    assert 0.0 <= stats["reciprocity"] <= 1.0
+    assert sum(stats["component_sizes"]) == 283
+
+
+def test_subnetwork_keeps_only_the_named_neurons(net):
+    sub = net.subnetwork(["ASHL", "ASHR", "AVAL", "AVAR"])
+    assert sorted(sub.neuron_names) == ["ASHL", "ASHR", "AVAL", "AVAR"]
+    assert sub.connection_count == 14
+    assert sub.shortest_path("ASHL", "AVAL") == ["ASHL", "AVAL"]
+
+
+def test_simulate_returns_spikes_and_watched_potentials(net):
+    run = net.simulate(
+        steps=2000,
+        dt=0.5,
+        stimuli=[{"neuron": "ASHL", "start_step": 100, "duration_steps": 500, "amplitude": 2.0}],
+        watch=["AVAL"],
+        seed=1,
+    )
+    assert run["steps"] == 2000
+    assert run["dt"] == 0.5
+    assert set(run["spikes"]) == set(net.neuron_names)
+    assert len(run["spikes"]["ASHL"]) > 0
+    assert all(isinstance(step, int) for step in run["spikes"]["ASHL"])
+    assert list(run["potentials"]) == ["AVAL"]
+    assert len(run["potentials"]["AVAL"]) == 2000
+
+
+def test_simulate_is_reproducible_for_a_seed(net):
+    spec = {"neuron": "ALML", "duration_steps": 400, "amplitude": 3.0}
+    first = net.simulate(steps=500, dt=0.5, stimuli=[spec], seed=7)
+    second = net.simulate(steps=500, dt=0.5, stimuli=[spec], seed=7)
+    assert first["spikes"] == second["spikes"]
+
+
+def test_stimulus_shapes(net):
+    pulse = {
+        "neuron": "ALML",
+        "duration_steps": 400,
+        "amplitude": 3.0,
+        "period_steps": 50,
+        "width_steps": 10,
+    }
+    ramp = {"neuron": "ALML", "duration_steps": 400, "amplitude": 0.0, "end_amplitude": 3.0}
+    for spec in (pulse, ramp):
+        assert net.simulate(steps=400, dt=0.5, stimuli=[spec])["steps"] == 400
+    with pytest.raises(ValueError):
+        net.simulate(steps=10, dt=0.5, stimuli=[dict(pulse, end_amplitude=1.0)])
+    with pytest.raises(KeyError):
+        net.simulate(steps=10, dt=0.5, stimuli=[{"neuron": "ALML", "amplitude": 1.0}])
+
+
+def test_unknown_names_raise_key_error(net):
+    with pytest.raises(KeyError):
+        net.shortest_path("ASHL", "NOPE")
+    with pytest.raises(KeyError):
+        net.subnetwork(["NOPE"])
+    with pytest.raises(KeyError):
+        net.simulate(steps=10, dt=0.5, watch=["NOPE"])
+
+
+def test_library_errors_raise_flymind_error(tmp_path, net):
+    with pytest.raises(flymind.FlymindError):
+        Network.from_csv(str(tmp_path / "missing.csv"))
+    bad = tmp_path / "bad.csv"
+    bad.write_text("Neuron 1,Neuron 2,Type,Nbr\nA,B,Sp,-3\n")
+    with pytest.raises(flymind.FlymindError, match="Nbr"):
+        Network.from_csv(str(bad))
+    with pytest.raises(flymind.FlymindError):
+        net.simulate(steps=10, dt=0.0)
@@ -0,0 +1,75 @@
+#ifndef FLYMIND_H
+#define FLYMIND_H
+
+/* C ABI of celegans_connectome, built with `cargo build --release --features ffi`.
+ * Generated by cbindgen from src/ffi.rs; see cbindgen.toml. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* Loaded network. */
+typedef struct flymind_network_t flymind_network_t;
+
+/* A network being simulated. */
+typedef struct flymind_sim_t flymind_sim_t;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last failed call on this thread, or null if none has
+// failed. Valid until the next failing call on the same thread.
+const char *flymind_last_error_message(void);
+
+// Load a `NeuronConnect.csv` edge list from a NUL-terminated path.
+flymind_network_t *flymind_network_load_path(const char *path);
+
+// Load an edge list from `len` bytes of CSV text.
+flymind_network_t *flymind_network_load_buffer(const uint8_t *data, size_t len);
+
+void flymind_network_free(flymind_network_t *network);
+
+ptrdiff_t flymind_network_neuron_count(const flymind_network_t *network);
+
+ptrdiff_t flymind_network_connection_count(const flymind_network_t *network);
+
+// Id of the neuron named by NUL-terminated `name`, or -1 if there is none.
+ptrdiff_t flymind_network_neuron_id(const flymind_network_t *network, const char *name);
+
+// Copy the name of neuron `id`, NUL-terminated, into `buf` of `buf_len`
+// bytes. Returns the name's length without the NUL; nothing is written
+// unless `buf_len` exceeds it.
+ptrdiff_t flymind_network_neuron_name(const flymind_network_t *network,
+                                      size_t id,
+                                      char *buf,
+                                      size_t buf_len);
+
+// Start a simulation of a copy of `network` from rest with step `dt`, using
+// the default LIF parameters.
+flymind_sim_t *flymind_sim_new(const flymind_network_t *network, double dt);
+
+void flymind_sim_free(flymind_sim_t *sim);
+
+// Advance `steps` steps, injecting `currents[i]` into neuron `i` throughout.
+// `currents` may be null (no injection) or must hold one value per neuron,
+// with `currents_len` saying how many. Returns 0, or -1 on error.
+int flymind_sim_run(flymind_sim_t *sim, size_t steps, const double *currents, size_t currents_len);
+
+// Simulated time so far, steps taken times `dt` and so in the unit of `dt`,
+// or a negative value on error.
+double flymind_sim_time(flymind_sim_t *sim);
+
+// Spikes since the last retrieval, in time order: `times[k]` is when neuron
+// `neurons[k]` fired, in the unit of `dt`. Returns how many are pending. If
+// both arrays are non-null and `capacity` is at least that many, they are
+// filled and the spikes are cleared; otherwise nothing is written.
+ptrdiff_t flymind_sim_take_spikes(flymind_sim_t *sim,
+                                  double *times,
+                                  size_t *neurons,
+                                  size_t capacity);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FLYMIND_H */
@@ -0,0 +1,251 @@
+//! Free-form key-value data attached to neurons and connections, such as
+//! lineage or experimental flags, without adding fields to `Neuron` or
+//! `Connection`.
+//!
+//! Annotations are stored per id, so a network without any holds two empty
+//! maps. Connection annotations follow connection indices: `retain_connections`
+//! and `subnetwork` move them along, but edits made directly to
+//! `Network::connections` leave them where they were. They are saved and read
+//! back with the rest of the structure by `Network::save_structure`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::Network;
+
+/// One annotation value. Serialized as the plain JSON value, so whole numbers
+/// read back as `I64` and other numbers as `F64`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AttrValue {
+    Bool(bool),
+    I64(i64),
+    F64(f64),
+    Str(String),
+}
+
+impl AttrValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttrValue::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The number held, with integers converted.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            AttrValue::F64(value) => Some(value),
+            AttrValue::I64(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            AttrValue::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            AttrValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// GraphML `attr.type` of the value.
+    pub(crate) fn graphml_type(&self) -> &'static str {
+        match self {
+            AttrValue::Bool(_) => "boolean",
+            AttrValue::I64(_) => "long",
+            AttrValue::F64(_) => "double",
+            AttrValue::Str(_) => "string",
+        }
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::Bool(value) => write!(f, "{value}"),
+            AttrValue::I64(value) => write!(f, "{value}"),
+            AttrValue::F64(value) => write!(f, "{value}"),
+            AttrValue::Str(value) => f.write_str(value),
+        }
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(value: bool) -> Self {
+        AttrValue::Bool(value)
+    }
+}
+
+impl From<i64> for AttrValue {
+    fn from(value: i64) -> Self {
+        AttrValue::I64(value)
+    }
+}
+
+impl From<f64> for AttrValue {
+    fn from(value: f64) -> Self {
+        AttrValue::F64(value)
+    }
+}
+
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> Self {
+        AttrValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(value: String) -> Self {
+        AttrValue::Str(value)
+    }
+}
+
+/// The annotations of a network: for each annotated neuron id (or connection
+/// index), its values by key. Serializes to JSON as-is, ids becoming string
+/// keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Annotations {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub neurons: HashMap<usize, BTreeMap<String, AttrValue>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub connections: HashMap<usize, BTreeMap<String, AttrValue>>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.neurons.is_empty() && self.connections.is_empty()
+    }
+
+    /// Move neuron annotations to `new_id(old)`, dropping those of neurons
+    /// that map to `None`. Where several neurons map to one id, each key keeps
+    /// the value of the lowest old id.
+    pub(crate) fn remap_neurons(&mut self, new_id: impl Fn(usize) -> Option<usize>) {
+        let mut old: Vec<_> = std::mem::take(&mut self.neurons).into_iter().collect();
+        old.sort_unstable_by_key(|&(id, _)| id);
+        for (id, attrs) in old {
+            if let Some(id) = new_id(id) {
+                let merged = self.neurons.entry(id).or_default();
+                for (key, value) in attrs {
+                    merged.entry(key).or_insert(value);
+                }
+            }
+        }
+    }
+
+    /// Move connection annotations to `new_index[old]`, dropping those that
+    /// map to `None` or past its end.
+    pub(crate) fn remap_connections(&mut self, new_index: &[Option<usize>]) {
+        self.connections = std::mem::take(&mut self.connections)
+            .into_iter()
+            .filter_map(|(index, attrs)| Some((new_index.get(index).copied().flatten()?, attrs)))
+            .collect();
+    }
+}
+
+impl Network {
+    /// Set `key` on neuron `id`, returning the value it replaces.
+    pub fn set_neuron_attr(
+        &mut self,
+        id: usize,
+        key: &str,
+        value: impl Into<AttrValue>,
+    ) -> Option<AttrValue> {
+        set(&mut self.annotations.neurons, id, key, value.into())
+    }
+
+    pub fn get_neuron_attr(&self, id: usize, key: &str) -> Option<&AttrValue> {
+        self.annotations.neurons.get(&id)?.get(key)
+    }
+
+    pub fn remove_neuron_attr(&mut self, id: usize, key: &str) -> Option<AttrValue> {
+        remove(&mut self.annotations.neurons, id, key)
+    }
+
+    /// Every annotation of neuron `id`, by key; empty if it has none.
+    pub fn neuron_attrs(&self, id: usize) -> impl Iterator<Item = (&str, &AttrValue)> + '_ {
+        attrs_of(&self.annotations.neurons, id)
+    }
+
+    /// Set `key` on connection `index`, returning the value it replaces.
+    pub fn set_connection_attr(
+        &mut self,
+        index: usize,
+        key: &str,
+        value: impl Into<AttrValue>,
+    ) -> Option<AttrValue> {
+        set(&mut self.annotations.connections, index, key, value.into())
+    }
+
+    pub fn get_connection_attr(&self, index: usize, key: &str) -> Option<&AttrValue> {
+        self.annotations.connections.get(&index)?.get(key)
+    }
+
+    pub fn remove_connection_attr(&mut self, index: usize, key: &str) -> Option<AttrValue> {
+        remove(&mut self.annotations.connections, index, key)
+    }
+
+    pub fn connection_attrs(&self, index: usize) -> impl Iterator<Item = (&str, &AttrValue)> + '_ {
+        attrs_of(&self.annotations.connections, index)
+    }
+
+    /// Ids of the neurons whose `key` annotation satisfies `predicate`,
+    /// ascending; neurons without `key` are never selected.
+    pub fn neurons_with_attr<F: Fn(&AttrValue) -> bool>(
+        &self,
+        key: &str,
+        predicate: F,
+    ) -> Vec<usize> {
+        select(&self.annotations.neurons, key, predicate)
+    }
+
+    /// Indices of the connections whose `key` annotation satisfies
+    /// `predicate`, ascending.
+    pub fn connections_with_attr<F: Fn(&AttrValue) -> bool>(
+        &self,
+        key: &str,
+        predicate: F,
+    ) -> Vec<usize> {
+        select(&self.annotations.connections, key, predicate)
+    }
+}
+
+type AttrMap = HashMap<usize, BTreeMap<String, AttrValue>>;
+
+fn set(map: &mut AttrMap, id: usize, key: &str, value: AttrValue) -> Option<AttrValue> {
+    map.entry(id).or_default().insert(key.to_string(), value)
+}
+
+fn remove(map: &mut AttrMap, id: usize, key: &str) -> Option<AttrValue> {
+    let attrs = map.get_mut(&id)?;
+    let removed = attrs.remove(key);
+    if attrs.is_empty() {
+        map.remove(&id);
+    }
+    removed
+}
+
+fn attrs_of(map: &AttrMap, id: usize) -> impl Iterator<Item = (&str, &AttrValue)> + '_ {
+    map.get(&id)
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| (key.as_str(), value))
+}
+
+fn select<F: Fn(&AttrValue) -> bool>(map: &AttrMap, key: &str, predicate: F) -> Vec<usize> {
+    let mut ids: Vec<usize> = map
+        .iter()
+        .filter(|(_, attrs)| attrs.get(key).is_some_and(&predicate))
+        .map(|(&id, _)| id)
+        .collect();
+    ids.sort_unstable();
+    ids
+}
@@ -0,0 +1,354 @@
+//! Network descriptions for running the connectome in other simulators.
+//!
+//! A bundle is a directory of CSV tables plus a generated Python script that
+//! loads them. Only `ChemicalSend` and gap-junction rows become synapses;
+//! `ChemicalReceive` rows duplicate the sends and NMJ rows target muscle.
+//! Modulatory sends are left out, as neither simulator has an equivalent.
+//! Chemical weights are the signed potential jump of the flymind LIF model
+//! (`chemical_gain * weight`, negative when inhibitory) and gap-junction
+//! weights the conductance per ms into the postsynaptic neuron
+//! (`gap_gain * weight`), both with the postsynaptic neuron's parameters.
+//! Delays and refractory periods are in simulation steps, one step of delay
+//! for every chemical synapse as in `update_step`; the scripts run at the `dt`
+//! the bundle was written for.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::network::{ChemicalSubtype, Network, NeuronType, SynapseType};
+use crate::simulation::{LifParams, TimeUnit};
+
+/// Simulator a bundle is written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimTarget {
+    /// `neurons.csv`, `chemical_synapses.csv` (`i,j,w,delay,kind`),
+    /// `gap_junctions.csv` (`i,j,w`) and `brian2_model.py`.
+    Brian2,
+    /// SONATA-style `nodes.csv`, `node_types.csv`, `chemical_edges.csv`,
+    /// `gap_edges.csv` and `edge_types.csv`, plus `build_sonata.py`, which turns
+    /// them into the HDF5 files and circuit config NEST reads.
+    NestSonata,
+}
+
+const BRIAN2_SCRIPT: &str = include_str!("bundle/brian2_model.py");
+const SONATA_SCRIPT: &str = include_str!("bundle/build_sonata.py");
+
+/// SONATA population holding every neuron.
+const POPULATION: &str = "celegans";
+const EXCITATORY_EDGE_TYPE: usize = 100;
+const INHIBITORY_EDGE_TYPE: usize = 101;
+const GAP_EDGE_TYPE: usize = 200;
+
+/// One exported synapse, `from -> to`.
+struct Synapse {
+    from: usize,
+    to: usize,
+    weight: f64,
+    /// In steps.
+    delay: usize,
+    inhibitory: bool,
+}
+
+impl Network {
+    /// Write a bundle for `target` into `dir`, creating it if needed, and return
+    /// the paths written. Neuron indices in every table are neuron ids. `dt` is
+    /// the step of the run the bundle should reproduce, in `lif.time_unit` as
+    /// for `run_simulation`; it must be positive.
+    pub fn to_simulator_bundle<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        target: SimTarget,
+        dt: f64,
+    ) -> io::Result<Vec<PathBuf>> {
+        if !(dt.is_finite() && dt > 0.0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("bundle time step must be positive, got {dt}"),
+            ));
+        }
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let dt_ms = self.lif.time_unit.convert(dt, TimeUnit::Milliseconds);
+        match target {
+            SimTarget::Brian2 => self.write_brian2(dir, dt_ms),
+            SimTarget::NestSonata => self.write_sonata(dir, dt_ms),
+        }
+    }
+
+    /// Chemical sends and gap junctions with their model weights.
+    fn bundle_synapses(&self) -> (Vec<Synapse>, Vec<Synapse>) {
+        let mut chemical = Vec::new();
+        let mut gap = Vec::new();
+        for conn in &self.connections {
+            let params = self.lif_for(conn.to_id);
+            match conn.synapse_type {
+                SynapseType::ChemicalSend(subtype) if !subtype.is_modulatory() => {
+                    let inhibitory = subtype == ChemicalSubtype::Inhibitory;
+                    let sign = subtype.sign();
+                    chemical.push(Synapse {
+                        from: conn.from_id,
+                        to: conn.to_id,
+                        weight: sign * params.chemical_gain * conn.weight,
+                        delay: 1,
+                        inhibitory,
+                    });
+                }
+                SynapseType::GapJunction => gap.push(Synapse {
+                    from: conn.from_id,
+                    to: conn.to_id,
+                    weight: params.gap_gain * conn.weight / per_ms(params),
+                    delay: 0,
+                    inhibitory: false,
+                }),
+                SynapseType::ChemicalSend(_)
+                | SynapseType::ChemicalReceive(_)
+                | SynapseType::NMJ
+                | SynapseType::Extrasynaptic(_) => {}
+            }
+        }
+        (chemical, gap)
+    }
+
+    fn write_brian2(&self, dir: &Path, dt_ms: f64) -> io::Result<Vec<PathBuf>> {
+        let (chemical, gap) = self.bundle_synapses();
+
+        let neurons = dir.join("neurons.csv");
+        let mut wtr = csv::Writer::from_path(&neurons)?;
+        wtr.write_record([
+            "i",
+            "name",
+            "type",
+            "tau_ms",
+            "threshold",
+            "reset",
+            "refractory_steps",
+        ])?;
+        for neuron in &self.neurons {
+            let params = self.lif_for(neuron.id);
+            wtr.write_record([
+                &neuron.id.to_string(),
+                neuron.name(),
+                neuron.neuron_type.label(),
+                &tau_ms(params).to_string(),
+                &params.threshold.to_string(),
+                &params.reset_potential.to_string(),
+                &params.refractory_steps.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+
+        let chemical_path = dir.join("chemical_synapses.csv");
+        let mut wtr = csv::Writer::from_path(&chemical_path)?;
+        wtr.write_record(["i", "j", "w", "delay", "kind"])?;
+        for syn in &chemical {
+            let kind = if syn.inhibitory { "inh" } else { "exc" };
+            wtr.write_record([
+                &syn.from.to_string(),
+                &syn.to.to_string(),
+                &syn.weight.to_string(),
+                &syn.delay.to_string(),
+                kind,
+            ])?;
+        }
+        wtr.flush()?;
+
+        let gap_path = dir.join("gap_junctions.csv");
+        let mut wtr = csv::Writer::from_path(&gap_path)?;
+        wtr.write_record(["i", "j", "w"])?;
+        for syn in &gap {
+            wtr.write_record([
+                &syn.from.to_string(),
+                &syn.to.to_string(),
+                &syn.weight.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+
+        let script = dir.join("brian2_model.py");
+        fs::write(
+            &script,
+            BRIAN2_SCRIPT
+                .replace("@DT_MS@", &dt_ms.to_string())
+                .replace("@NEURONS@", "neurons.csv")
+                .replace("@CHEMICAL@", "chemical_synapses.csv")
+                .replace("@GAP@", "gap_junctions.csv"),
+        )?;
+        Ok(vec![neurons, chemical_path, gap_path, script])
+    }
+
+    fn write_sonata(&self, dir: &Path, dt_ms: f64) -> io::Result<Vec<PathBuf>> {
+        let (chemical, gap) = self.bundle_synapses();
+
+        let node_types = dir.join("node_types.csv");
+        let mut wtr = space_separated(&node_types)?;
+        wtr.write_record(["node_type_id", "model_type", "model_template", "pop_name"])?;
+        for (k, neuron_type) in NEURON_TYPES.iter().enumerate() {
+            wtr.write_record([
+                &(k + 1).to_string(),
+                "point_neuron",
+                "nest:iaf_psc_delta",
+                neuron_type.label(),
+            ])?;
+        }
+        wtr.flush()?;
+
+        let nodes = dir.join("nodes.csv");
+        let mut wtr = csv::Writer::from_path(&nodes)?;
+        wtr.write_record([
+            "node_id",
+            "node_type_id",
+            "name",
+            "tau_m",
+            "E_L",
+            "V_th",
+            "V_reset",
+            "refractory_steps",
+        ])?;
+        for neuron in &self.neurons {
+            let params = self.lif_for(neuron.id);
+            let type_id = NEURON_TYPES
+                .iter()
+                .position(|&t| t == neuron.neuron_type)
+                .map_or(0, |k| k + 1);
+            wtr.write_record([
+                &neuron.id.to_string(),
+                &type_id.to_string(),
+                neuron.name(),
+                &tau_ms(params).to_string(),
+                &params.resting_potential.to_string(),
+                &params.absolute_threshold().to_string(),
+                &params.absolute_reset().to_string(),
+                &params.refractory_steps.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+
+        let edge_types = dir.join("edge_types.csv");
+        let mut wtr = space_separated(&edge_types)?;
+        wtr.write_record(["edge_type_id", "model_template", "kind"])?;
+        wtr.write_record([&EXCITATORY_EDGE_TYPE.to_string(), "static_synapse", "exc"])?;
+        wtr.write_record([&INHIBITORY_EDGE_TYPE.to_string(), "static_synapse", "inh"])?;
+        wtr.write_record([&GAP_EDGE_TYPE.to_string(), "gap_junction", "gap"])?;
+        wtr.flush()?;
+
+        let chemical_path = dir.join("chemical_edges.csv");
+        write_sonata_edges(&chemical_path, &chemical, |syn| {
+            if syn.inhibitory {
+                INHIBITORY_EDGE_TYPE
+            } else {
+                EXCITATORY_EDGE_TYPE
+            }
+        })?;
+        let gap_path = dir.join("gap_edges.csv");
+        write_sonata_edges(&gap_path, &gap, |_| GAP_EDGE_TYPE)?;
+
+        let script = dir.join("build_sonata.py");
+        fs::write(
+            &script,
+            SONATA_SCRIPT
+                .replace("@POPULATION@", POPULATION)
+                .replace("@DT_MS@", &dt_ms.to_string())
+                .replace("@NODES@", "nodes.csv")
+                .replace("@NODE_TYPES@", "node_types.csv")
+                .replace("@EDGE_TYPES@", "edge_types.csv")
+                .replace("@CHEMICAL@", "chemical_edges.csv")
+                .replace("@GAP@", "gap_edges.csv"),
+        )?;
+        Ok(vec![
+            node_types,
+            nodes,
+            edge_types,
+            chemical_path,
+            gap_path,
+            script,
+        ])
+    }
+}
+
+/// Node types of a SONATA bundle, numbered from 1 in this order.
+const NEURON_TYPES: [NeuronType; 4] = [
+    NeuronType::Sensory,
+    NeuronType::Interneuron,
+    NeuronType::Motor,
+    NeuronType::Other,
+];
+
+/// SONATA type tables are space-separated.
+fn space_separated(path: &Path) -> io::Result<csv::Writer<fs::File>> {
+    Ok(csv::WriterBuilder::new().delimiter(b' ').from_path(path)?)
+}
+
+fn write_sonata_edges(
+    path: &Path,
+    synapses: &[Synapse],
+    edge_type: impl Fn(&Synapse) -> usize,
+) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_path(path)?;
+    wtr.write_record([
+        "source_node_id",
+        "target_node_id",
+        "edge_type_id",
+        "syn_weight",
+        "delay",
+    ])?;
+    for syn in synapses {
+        wtr.write_record([
+            &syn.from.to_string(),
+            &syn.to.to_string(),
+            &edge_type(syn).to_string(),
+            &syn.weight.to_string(),
+            &syn.delay.to_string(),
+        ])?;
+    }
+    wtr.flush()
+}
+
+fn tau_ms(params: &LifParams) -> f64 {
+    params.time_unit.convert(params.tau, TimeUnit::Milliseconds)
+}
+
+/// Milliseconds in one unit of `params.time_unit`.
+fn per_ms(params: &LifParams) -> f64 {
+    params.time_unit.convert(1.0, TimeUnit::Milliseconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("flymind-bundle-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn scripts_run_at_the_requested_step() {
+        let mut network = Network::example_touch_circuit();
+        let dir = bundle_dir("dt");
+        network
+            .to_simulator_bundle(&dir, SimTarget::Brian2, 0.25)
+            .unwrap();
+        let brian2 = fs::read_to_string(dir.join("brian2_model.py")).unwrap();
+        assert!(brian2.contains("defaultclock.dt = 0.25 * ms"));
+
+        network.lif.time_unit = TimeUnit::Seconds;
+        network
+            .to_simulator_bundle(&dir, SimTarget::NestSonata, 0.0005)
+            .unwrap();
+        let sonata = fs::read_to_string(dir.join("build_sonata.py")).unwrap();
+        assert!(sonata.contains("\nDT = 0.5\n"));
+        assert!(!sonata.contains('@'));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_non_positive_step_is_rejected() {
+        let network = Network::example_touch_circuit();
+        let dir = bundle_dir("zero");
+        let err = network
+            .to_simulator_bundle(&dir, SimTarget::Brian2, 0.0)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,58 @@
+"""Load the flymind connectome bundle in this directory into Brian2.
+
+Generated by flymind; edit freely. Potentials are dimensionless, as in the
+flymind LIF model, and measured from its resting potential: a chemical spike
+adds `w` to the postsynaptic potential one step later and a gap junction row
+conducts `w * (v_pre - v_post)` per ms into its postsynaptic neuron.
+"""
+import csv
+import os
+
+from brian2 import NeuronGroup, Synapses, defaultclock, ms, run
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def read(name):
+    with open(os.path.join(HERE, name), newline="") as f:
+        return list(csv.DictReader(f))
+
+
+neurons = read("@NEURONS@")
+chemical = read("@CHEMICAL@")
+gap = read("@GAP@")
+
+defaultclock.dt = @DT_MS@ * ms
+
+group = NeuronGroup(
+    len(neurons),
+    """
+    dv/dt = -v / tau + (I_gap + I_ext) / ms : 1 (unless refractory)
+    I_gap : 1
+    I_ext : 1
+    tau : second (constant)
+    threshold : 1 (constant)
+    reset : 1 (constant)
+    t_ref : second (constant)
+    """,
+    threshold="v >= threshold",
+    reset="v = reset",
+    refractory="t_ref",
+    method="euler",
+)
+group.tau = [float(n["tau_ms"]) for n in neurons] * ms
+group.threshold = [float(n["threshold"]) for n in neurons]
+group.reset = [float(n["reset"]) for n in neurons]
+group.t_ref = [int(n["refractory_steps"]) for n in neurons] * defaultclock.dt
+
+chem = Synapses(group, group, "w : 1", on_pre="v_post += w")
+chem.connect(i=[int(s["i"]) for s in chemical], j=[int(s["j"]) for s in chemical])
+chem.w = [float(s["w"]) for s in chemical]
+chem.delay = [int(s["delay"]) for s in chemical] * defaultclock.dt
+
+electrical = Synapses(group, group, "w : 1\nI_gap_post = w * (v_pre - v_post) : 1 (summed)")
+electrical.connect(i=[int(s["i"]) for s in gap], j=[int(s["j"]) for s in gap])
+electrical.w = [float(s["w"]) for s in gap]
+
+if __name__ == "__main__":
+    run(100 * ms)
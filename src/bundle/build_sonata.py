@@ -0,0 +1,67 @@
+"""Build SONATA nodes.h5 / edges.h5 and a circuit config from the flymind
+CSV tables in this directory, for NEST's SONATA loader.
+
+Generated by flymind; needs h5py and numpy.
+"""
+import csv
+import json
+import os
+
+import h5py
+import numpy as np
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+POPULATION = "@POPULATION@"
+# Simulation resolution in ms; refractory periods and delays are in steps of it.
+DT = @DT_MS@
+
+
+def read(name):
+    with open(os.path.join(HERE, name), newline="") as f:
+        return list(csv.DictReader(f))
+
+
+nodes = read("@NODES@")
+with h5py.File(os.path.join(HERE, "nodes.h5"), "w") as f:
+    pop = f.create_group(f"nodes/{POPULATION}")
+    pop["node_id"] = np.array([int(n["node_id"]) for n in nodes], dtype=np.uint64)
+    pop["node_type_id"] = np.array([int(n["node_type_id"]) for n in nodes], dtype=np.uint64)
+    group = pop.create_group("0")
+    group["name"] = np.array([n["name"] for n in nodes], dtype=h5py.string_dtype())
+    for key in ("tau_m", "E_L", "V_th", "V_reset"):
+        group[key] = np.array([float(n[key]) for n in nodes])
+    group["t_ref"] = np.array([int(n["refractory_steps"]) * DT for n in nodes])
+
+edge_files = []
+for table in ("@CHEMICAL@", "@GAP@"):
+    edges = read(table)
+    name = os.path.splitext(table)[0]
+    path = os.path.join(HERE, f"{name}.h5")
+    with h5py.File(path, "w") as f:
+        pop = f.create_group(f"edges/{name}")
+        source = pop.create_dataset(
+            "source_node_id", data=np.array([int(e["source_node_id"]) for e in edges], dtype=np.uint64)
+        )
+        source.attrs["node_population"] = POPULATION
+        target = pop.create_dataset(
+            "target_node_id", data=np.array([int(e["target_node_id"]) for e in edges], dtype=np.uint64)
+        )
+        target.attrs["node_population"] = POPULATION
+        pop["edge_type_id"] = np.array([int(e["edge_type_id"]) for e in edges], dtype=np.uint64)
+        pop["0/syn_weight"] = np.array([float(e["syn_weight"]) for e in edges])
+        pop["0/delay"] = np.array([int(e["delay"]) * DT for e in edges])
+    edge_files.append({"edges_file": path, "edge_types_file": os.path.join(HERE, "@EDGE_TYPES@")})
+
+config = {
+    "networks": {
+        "nodes": [
+            {
+                "nodes_file": os.path.join(HERE, "nodes.h5"),
+                "node_types_file": os.path.join(HERE, "@NODE_TYPES@"),
+            }
+        ],
+        "edges": edge_files,
+    }
+}
+with open(os.path.join(HERE, "circuit_config.json"), "w") as f:
+    json.dump(config, f, indent=2)
@@ -0,0 +1,155 @@
+//! The canonical 302-neuron roster of the adult hermaphrodite and name checks.
+
+use crate::network::Network;
+
+/// Every neuron name in the adult hermaphrodite, sorted (282 somatic + 20 pharyngeal).
+pub const CANONICAL_NEURONS: [&str; 302] = [
+    "ADAL", "ADAR", "ADEL", "ADER", "ADFL", "ADFR", "ADLL", "ADLR", "AFDL", "AFDR", "AIAL", "AIAR",
+    "AIBL", "AIBR", "AIML", "AIMR", "AINL", "AINR", "AIYL", "AIYR", "AIZL", "AIZR", "ALA", "ALML",
+    "ALMR", "ALNL", "ALNR", "AQR", "AS01", "AS02", "AS03", "AS04", "AS05", "AS06", "AS07", "AS08",
+    "AS09", "AS10", "AS11", "ASEL", "ASER", "ASGL", "ASGR", "ASHL", "ASHR", "ASIL", "ASIR", "ASJL",
+    "ASJR", "ASKL", "ASKR", "AUAL", "AUAR", "AVAL", "AVAR", "AVBL", "AVBR", "AVDL", "AVDR", "AVEL",
+    "AVER", "AVFL", "AVFR", "AVG", "AVHL", "AVHR", "AVJL", "AVJR", "AVKL", "AVKR", "AVL", "AVM",
+    "AWAL", "AWAR", "AWBL", "AWBR", "AWCL", "AWCR", "BAGL", "BAGR", "BDUL", "BDUR", "CANL", "CANR",
+    "CEPDL", "CEPDR", "CEPVL", "CEPVR", "DA01", "DA02", "DA03", "DA04", "DA05", "DA06", "DA07",
+    "DA08", "DA09", "DB01", "DB02", "DB03", "DB04", "DB05", "DB06", "DB07", "DD01", "DD02", "DD03",
+    "DD04", "DD05", "DD06", "DVA", "DVB", "DVC", "FLPL", "FLPR", "HSNL", "HSNR", "I1L", "I1R",
+    "I2L", "I2R", "I3", "I4", "I5", "I6", "IL1DL", "IL1DR", "IL1L", "IL1R", "IL1VL", "IL1VR",
+    "IL2DL", "IL2DR", "IL2L", "IL2R", "IL2VL", "IL2VR", "LUAL", "LUAR", "M1", "M2L", "M2R", "M3L",
+    "M3R", "M4", "M5", "MCL", "MCR", "MI", "NSML", "NSMR", "OLLL", "OLLR", "OLQDL", "OLQDR",
+    "OLQVL", "OLQVR", "PDA", "PDB", "PDEL", "PDER", "PHAL", "PHAR", "PHBL", "PHBR", "PHCL", "PHCR",
+    "PLML", "PLMR", "PLNL", "PLNR", "PQR", "PVCL", "PVCR", "PVDL", "PVDR", "PVM", "PVNL", "PVNR",
+    "PVPL", "PVPR", "PVQL", "PVQR", "PVR", "PVT", "PVWL", "PVWR", "RIAL", "RIAR", "RIBL", "RIBR",
+    "RICL", "RICR", "RID", "RIFL", "RIFR", "RIGL", "RIGR", "RIH", "RIML", "RIMR", "RIPL", "RIPR",
+    "RIR", "RIS", "RIVL", "RIVR", "RMDDL", "RMDDR", "RMDL", "RMDR", "RMDVL", "RMDVR", "RMED",
+    "RMEL", "RMER", "RMEV", "RMFL", "RMFR", "RMGL", "RMGR", "RMHL", "RMHR", "SAADL", "SAADR",
+    "SAAVL", "SAAVR", "SABD", "SABVL", "SABVR", "SDQL", "SDQR", "SIADL", "SIADR", "SIAVL", "SIAVR",
+    "SIBDL", "SIBDR", "SIBVL", "SIBVR", "SMBDL", "SMBDR", "SMBVL", "SMBVR", "SMDDL", "SMDDR",
+    "SMDVL", "SMDVR", "URADL", "URADR", "URAVL", "URAVR", "URBL", "URBR", "URXL", "URXR", "URYDL",
+    "URYDR", "URYVL", "URYVR", "VA01", "VA02", "VA03", "VA04", "VA05", "VA06", "VA07", "VA08",
+    "VA09", "VA10", "VA11", "VA12", "VB01", "VB02", "VB03", "VB04", "VB05", "VB06", "VB07", "VB08",
+    "VB09", "VB10", "VB11", "VC01", "VC02", "VC03", "VC04", "VC05", "VC06", "VD01", "VD02", "VD03",
+    "VD04", "VD05", "VD06", "VD07", "VD08", "VD09", "VD10", "VD11", "VD12", "VD13",
+];
+
+impl Network {
+    /// `Network::with_neuron_roster` of `CANONICAL_NEURONS`: all 302 neurons,
+    /// in alphabetical id order, whether or not a file connects them.
+    pub fn with_canonical_roster() -> Self {
+        Network::with_neuron_roster(&CANONICAL_NEURONS)
+    }
+}
+
+/// `name` upper-cased with everything but ASCII letters and digits removed, so
+/// `"aval "`, `"AVAL."` and `"AVAL"` all become `"AVAL"`.
+pub fn canonicalize_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+pub fn is_canonical(name: &str) -> bool {
+    CANONICAL_NEURONS.binary_search(&name).is_ok()
+}
+
+/// Canonical names within edit distance 2 of `name` (case-insensitive), closest first.
+pub fn near_misses(name: &str) -> Vec<&'static str> {
+    closest_names(name, CANONICAL_NEURONS.iter().copied(), 2)
+}
+
+/// Names from `candidates` within `max_distance` edits of `name`, ignoring case,
+/// ordered by distance and then alphabetically. An exact match is not included.
+pub fn closest_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    max_distance: usize,
+) -> Vec<&'a str> {
+    let target = name.trim().to_ascii_uppercase();
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| {
+            (
+                edit_distance(&target, &candidate.to_ascii_uppercase()),
+                candidate,
+            )
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect();
+    scored.sort_unstable();
+    scored.dedup();
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// Levenshtein distance over bytes (neuron names are ASCII).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::LoaderConfig;
+
+    /// One neuron spelled three ways, each synapsing onto a different partner.
+    const SPELLINGS: &str =
+        "Neuron 1,Neuron 2,Type,Nbr\nAVAL,VA08,S,1\naval,DA05,S,2\nAVAL ,AVAR,S,3\n";
+
+    #[test]
+    fn spelling_variants_canonicalize_to_one_name() {
+        for name in ["AVAL", "aval", "AVAL "] {
+            assert_eq!(canonicalize_name(name), "AVAL");
+            assert!(is_canonical(&canonicalize_name(name)));
+        }
+        assert!(!is_canonical("aval"));
+    }
+
+    #[test]
+    fn spelling_variants_load_as_one_neuron() {
+        let config = LoaderConfig {
+            normalize_names: true,
+            ..LoaderConfig::default()
+        };
+        let (network, _) =
+            Network::load_connect_reader(SPELLINGS.as_bytes(), &config, None).unwrap();
+        let aval = network.id_of("AVAL").unwrap();
+        assert_eq!(network.neurons.len(), 4);
+        assert_eq!(network.id_of("aval"), None);
+        assert_eq!(network.outgoing_map[&aval].len(), 3);
+    }
+
+    #[test]
+    fn spelling_variants_merge_into_one_neuron() {
+        let (mut network, _) =
+            Network::load_connect_reader(SPELLINGS.as_bytes(), &LoaderConfig::default(), None)
+                .unwrap();
+        assert_eq!(network.neurons.len(), 6);
+
+        let merges = network.merge_duplicate_neurons();
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].name, "AVAL");
+        assert_eq!(merges[0].merged, ["AVAL", "aval", "AVAL "]);
+        assert_eq!(network.neurons.len(), 4);
+        let aval = network.id_of("AVAL").unwrap();
+        let weight: f64 = network
+            .connections
+            .iter()
+            .filter(|conn| conn.from_id == aval)
+            .map(|conn| conn.weight)
+            .sum();
+        assert_eq!(weight, 6.0);
+    }
+}
@@ -0,0 +1,314 @@
+//! Shortest-path centrality (Brandes' algorithm).
+//!
+//! Paths are fewest-hop routes over chemical sends (presynaptic to
+//! postsynaptic) and gap junctions (either direction), so a gap junction used
+//! from either end credits the same connection. Parallel connections between a
+//! pair collapse to the heaviest, as in `Network::traversal_steps`; the others
+//! carry no paths. Counts are over ordered (source, target) pairs and are not
+//! normalised.
+//!
+//! `Network::pagerank` and `Network::eigenvector_centrality` score influence
+//! by power iteration over the weighted chemical graph instead, a neuron
+//! counting for more the more weight it takes from high-scoring neurons.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::network::{Network, NeuronType, SynapseType};
+use crate::paths::{PathOptions, Step, SynapseClass};
+use crate::walk::MAX_POWER_ITERATIONS;
+
+/// One entry of `Network::top_edge_betweenness`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeBetweenness {
+    /// Index into `connections`.
+    pub connection: usize,
+    pub from: String,
+    pub to: String,
+    pub synapse_type: SynapseType,
+    pub betweenness: f64,
+}
+
+/// Scores from power iteration, with how the iteration ended.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerIteration {
+    /// Score of every neuron, keyed by id, summing to 1.
+    pub scores: HashMap<usize, f64>,
+    pub iterations: usize,
+    /// L1 change of the scores on the last iteration.
+    pub residual: f64,
+    /// Whether `residual` fell below the tolerance within
+    /// `MAX_POWER_ITERATIONS`.
+    pub converged: bool,
+}
+
+impl PowerIteration {
+    fn new(scores: Vec<f64>, iterations: usize, residual: f64, tol: f64) -> Self {
+        Self {
+            scores: scores.into_iter().enumerate().collect(),
+            iterations,
+            residual,
+            converged: residual < tol,
+        }
+    }
+}
+
+impl Network {
+    /// Shortest paths between all ordered pairs passing through each neuron
+    /// (endpoints excluded), keyed by neuron id.
+    pub fn node_betweenness(&self) -> HashMap<usize, f64> {
+        let all: Vec<usize> = (0..self.neurons.len()).collect();
+        let (node, _) = self.brandes(&self.centrality_steps(), None, &all, &all);
+        node.into_iter().enumerate().collect()
+    }
+
+    /// Shortest paths between all ordered pairs that traverse each connection,
+    /// keyed by connection index. Every traversable connection has an entry.
+    pub fn edge_betweenness(&self) -> HashMap<usize, f64> {
+        let all: Vec<usize> = (0..self.neurons.len()).collect();
+        self.edge_betweenness_between(&all, &all)
+    }
+
+    /// Like `edge_betweenness`, counting only paths that start in `sources` and
+    /// end in `targets`. Ids out of range are ignored.
+    pub fn edge_betweenness_between(
+        &self,
+        sources: &[usize],
+        targets: &[usize],
+    ) -> HashMap<usize, f64> {
+        let steps = self.centrality_steps();
+        let (_, edge) = self.brandes(&steps, None, sources, targets);
+        steps
+            .iter()
+            .flatten()
+            .map(|&(_, conn)| (conn, edge[conn]))
+            .collect()
+    }
+
+    /// Edge betweenness over the connections `opts` follows, indexed like
+    /// `connections`; connections never traversed score 0.0. Paths longer than
+    /// `max_hops` are not counted.
+    pub fn edge_betweenness_with(&self, opts: &PathOptions) -> Vec<f64> {
+        let all: Vec<usize> = (0..self.neurons.len()).collect();
+        let (_, edge) = self.brandes(&self.traversal_steps(opts), opts.max_hops, &all, &all);
+        edge
+    }
+
+    /// The `k` connections with the highest `edge_betweenness_with`, highest
+    /// first (lowest index on ties), leaving out those no path crosses.
+    pub fn top_edge_betweenness(&self, k: usize, opts: &PathOptions) -> Vec<EdgeBetweenness> {
+        let scores = self.edge_betweenness_with(opts);
+        let mut ranked: Vec<(usize, f64)> = scores
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, score)| score > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(k)
+            .map(|(index, betweenness)| {
+                let conn = &self.connections[index];
+                EdgeBetweenness {
+                    connection: index,
+                    from: self.neurons[conn.from_id].name().to_string(),
+                    to: self.neurons[conn.to_id].name().to_string(),
+                    synapse_type: conn.synapse_type,
+                    betweenness,
+                }
+            })
+            .collect()
+    }
+
+    /// Edge betweenness over paths from sensory neurons to motor neurons.
+    pub fn sensory_motor_edge_betweenness(&self) -> HashMap<usize, f64> {
+        let of_type = |t: NeuronType| -> Vec<usize> {
+            self.neurons
+                .iter()
+                .filter(|n| n.neuron_type == t)
+                .map(|n| n.id)
+                .collect()
+        };
+        self.edge_betweenness_between(&of_type(NeuronType::Sensory), &of_type(NeuronType::Motor))
+    }
+
+    /// Chemical sends and gap junctions, as described in the module docs.
+    fn centrality_steps(&self) -> Vec<Vec<Step>> {
+        self.traversal_steps(&PathOptions {
+            classes: vec![SynapseClass::Chemical, SynapseClass::GapJunction],
+            ..PathOptions::default()
+        })
+    }
+
+    /// Node scores indexed by neuron id and edge scores indexed by connection.
+    fn brandes(
+        &self,
+        steps: &[Vec<Step>],
+        max_hops: Option<usize>,
+        sources: &[usize],
+        targets: &[usize],
+    ) -> (Vec<f64>, Vec<f64>) {
+        let n = self.neurons.len();
+        let max_hops = max_hops.unwrap_or(usize::MAX);
+        let mut node = vec![0.0; n];
+        let mut edge = vec![0.0; self.connections.len()];
+
+        let mut is_target = vec![false; n];
+        for &t in targets.iter().filter(|&&t| t < n) {
+            is_target[t] = true;
+        }
+
+        let mut sigma = vec![0.0f64; n];
+        let mut dist = vec![usize::MAX; n];
+        let mut delta = vec![0.0; n];
+        let mut preds: Vec<Vec<Step>> = vec![Vec::new(); n];
+        let mut order: Vec<usize> = Vec::with_capacity(n);
+        let mut queue = VecDeque::new();
+
+        for &s in sources.iter().filter(|&&s| s < n) {
+            for v in order.drain(..) {
+                sigma[v] = 0.0;
+                dist[v] = usize::MAX;
+                delta[v] = 0.0;
+                preds[v].clear();
+            }
+            sigma[s] = 1.0;
+            dist[s] = 0;
+            queue.push_back(s);
+            while let Some(v) = queue.pop_front() {
+                order.push(v);
+                if dist[v] >= max_hops {
+                    continue;
+                }
+                for &(w, conn) in &steps[v] {
+                    if dist[w] == usize::MAX {
+                        dist[w] = dist[v] + 1;
+                        queue.push_back(w);
+                    }
+                    if dist[w] == dist[v] + 1 {
+                        sigma[w] += sigma[v];
+                        preds[w].push((v, conn));
+                    }
+                }
+            }
+
+            for &w in order.iter().rev() {
+                let terminal = if is_target[w] && w != s { 1.0 } else { 0.0 };
+                for &(v, conn) in &preds[w] {
+                    let share = sigma[v] / sigma[w] * (terminal + delta[w]);
+                    edge[conn] += share;
+                    delta[v] += share;
+                }
+                if w != s {
+                    node[w] += delta[w];
+                }
+            }
+        }
+        (node, edge)
+    }
+}
+
+impl Network {
+    /// PageRank over the chemical sends, each neuron passing its rank on in
+    /// proportion to weight (as `random_walk_stationary`, with `teleport` of
+    /// `1 - damping`): a dangling neuron, without positive sends, spreads
+    /// its rank over every neuron. Iterates until the scores change by less
+    /// than `tol` in L1; see `pagerank_report` for whether they did.
+    pub fn pagerank(&self, damping: f64, tol: f64) -> HashMap<usize, f64> {
+        self.pagerank_report(damping, tol).scores
+    }
+
+    /// `pagerank`, with the iterations it took and its final residual.
+    pub fn pagerank_report(&self, damping: f64, tol: f64) -> PowerIteration {
+        let (scores, iterations, residual) = self.stationary_iteration(1.0 - damping, tol);
+        PowerIteration::new(scores, iterations, residual, tol)
+    }
+
+    /// Eigenvector centrality of the chemical graph: the leading eigenvector
+    /// of the summed positive send weights, where a neuron scores by the
+    /// weighted scores of the neurons sending to it, scaled to sum to 1.
+    /// Neurons no cycle of sends reaches have 0 in the eigenvector and come
+    /// out at or near it. See `eigenvector_centrality_report` for
+    /// convergence.
+    pub fn eigenvector_centrality(&self, tol: f64) -> HashMap<usize, f64> {
+        self.eigenvector_centrality_report(tol).scores
+    }
+
+    /// `eigenvector_centrality`, with the iterations it took and its final
+    /// residual. Each step adds the scores to the weighted sum of their
+    /// inputs before rescaling, which keeps the eigenvector but makes the
+    /// iteration converge on graphs whose cycles share a period.
+    pub fn eigenvector_centrality_report(&self, tol: f64) -> PowerIteration {
+        let n = self.neurons.len();
+        if n == 0 {
+            return PowerIteration::new(Vec::new(), 0, 0.0, tol);
+        }
+        let mut sends: HashMap<(usize, usize), f64> = HashMap::new();
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+                && conn.weight > 0.0
+                && self.counts(conn)
+            {
+                *sends.entry((conn.from_id, conn.to_id)).or_insert(0.0) += conn.weight;
+            }
+        }
+        let mut sends: Vec<((usize, usize), f64)> = sends.into_iter().collect();
+        sends.sort_by_key(|&(pair, _)| pair);
+
+        let mut score = vec![1.0 / n as f64; n];
+        let mut next = vec![0.0; n];
+        let mut iterations = 0;
+        let mut residual = f64::INFINITY;
+        while iterations < MAX_POWER_ITERATIONS {
+            next.copy_from_slice(&score);
+            for &((from, to), weight) in &sends {
+                next[to] += weight * score[from];
+            }
+            let total: f64 = next.iter().sum();
+            for value in &mut next {
+                *value /= total;
+            }
+            residual = score.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+            std::mem::swap(&mut score, &mut next);
+            iterations += 1;
+            if residual < tol {
+                break;
+            }
+        }
+        PowerIteration::new(score, iterations, residual, tol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    #[test]
+    fn pagerank_spreads_dangling_rank_and_sums_to_one() {
+        // B has no sends, so its rank goes back out over A and B alike:
+        // A = (1 - d) / 2 + d * B / 2 with A + B = 1.
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 1, EXCITATORY, 2.0);
+        let report = network.pagerank_report(0.85, 1e-12);
+        assert!(report.converged);
+        assert!((report.scores.values().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!((report.scores[&0] - 0.5 / 1.425).abs() < 1e-9);
+        assert!(report.scores[&1] > report.scores[&0]);
+    }
+
+    #[test]
+    fn eigenvector_centrality_settles_on_a_periodic_cycle() {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C"]);
+        for (from, to) in [(0, 1), (1, 2), (2, 0)] {
+            network.add_connection(from, to, EXCITATORY, 1.0);
+        }
+        let report = network.eigenvector_centrality_report(1e-12);
+        assert!(report.converged);
+        for id in 0..3 {
+            assert!((report.scores[&id] - 1.0 / 3.0).abs() < 1e-9);
+        }
+        assert!(Network::new().eigenvector_centrality(1e-9).is_empty());
+    }
+}
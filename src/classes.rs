@@ -0,0 +1,348 @@
+//! Neuron classes: AVAL and AVAR are both AVA, VB01 to VB11 are all VB.
+//!
+//! Names are reduced by the usual rules. Motor neurons numbered along the
+//! ventral cord lose their number. A trailing L or R is dropped, and then a D or
+//! V for the classes that also come in dorsal and ventral members (CEPDL ->
+//! CEP, RMED -> RME). Unpaired neurons whose names merely end in L or R (AVL,
+//! PVR, AQR, ...) are listed as exceptions.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::network::{Connection, Network, NeuronType, Region, SynapseType};
+
+/// Classes whose members are numbered (`VB01`, `VB2`, ...) rather than sided.
+pub const NUMBERED_CLASSES: [&str; 8] = ["AS", "DA", "DB", "DD", "VA", "VB", "VC", "VD"];
+
+/// Classes with dorsal and ventral members, named `<class>D[L|R]` and
+/// `<class>V[L|R]`.
+pub const DORSOVENTRAL_CLASSES: [&str; 14] = [
+    "CEP", "IL1", "IL2", "OLQ", "RMD", "RME", "SAA", "SAB", "SIA", "SIB", "SMB", "SMD", "URA",
+    "URY",
+];
+
+/// Single neurons whose names end in L or R without being one side of a pair.
+pub const UNPAIRED_LR: [&str; 5] = ["AQR", "AVL", "PQR", "PVR", "RIR"];
+
+/// The class of the neuron called `name` (case-insensitive, surrounding
+/// whitespace ignored). Names that match no rule are their own class.
+pub fn neuron_class(name: &str) -> String {
+    let name = name.trim().to_ascii_uppercase();
+    for prefix in NUMBERED_CLASSES {
+        if let Some(number) = name.strip_prefix(prefix) {
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                return prefix.to_string();
+            }
+        }
+    }
+    if UNPAIRED_LR.contains(&name.as_str()) {
+        return name;
+    }
+
+    let mut stem = name.as_str();
+    if stem.len() > 2 && (stem.ends_with('L') || stem.ends_with('R')) {
+        stem = &stem[..stem.len() - 1];
+    }
+    if let Some(class) = stem
+        .strip_suffix('D')
+        .or_else(|| stem.strip_suffix('V'))
+        .filter(|class| DORSOVENTRAL_CLASSES.contains(class))
+    {
+        stem = class;
+    }
+    stem.to_string()
+}
+
+impl Network {
+    /// The ids of each class's members by `neuron_class`, ascending.
+    pub fn functional_classes(&self) -> HashMap<String, Vec<usize>> {
+        let mut classes: HashMap<String, Vec<usize>> = HashMap::new();
+        for neuron in &self.neurons {
+            classes
+                .entry(neuron_class(neuron.name()))
+                .or_default()
+                .push(neuron.id);
+        }
+        classes
+    }
+
+    /// One neuron per class, with class names in order of each class's lowest
+    /// member id, and the class of every original neuron by name.
+    ///
+    /// Connections with the same class endpoints and synapse type are merged,
+    /// their weights and contacts summed, so total weight is unchanged; a
+    /// connection within a class (AVAL -> AVAR) becomes a self-connection.
+    /// Each class takes the first known type, region and transmitter of its
+    /// members and their mean soma position. Muscles and simulation settings are not copied.
+    pub fn collapse_to_classes(&self) -> (Network, BTreeMap<String, String>) {
+        let mut classes = BTreeMap::new();
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut class_of = Vec::with_capacity(self.neurons.len());
+        for neuron in &self.neurons {
+            let class = neuron_class(neuron.name());
+            let id = *index.entry(class.clone()).or_insert_with(|| {
+                names.push(class.clone());
+                names.len() - 1
+            });
+            classes.insert(neuron.name().to_string(), class);
+            class_of.push(id);
+        }
+        (self.merge_into(&class_of, &names, true), classes)
+    }
+
+    /// A coarse-grained copy with one supernode per group of neuron ids, e.g.
+    /// from `functional_classes` or a community detection. Neurons in no
+    /// group stay nodes of their own; a neuron in several groups joins the
+    /// first, and ids past the last neuron are ignored.
+    ///
+    /// Supernodes are numbered in order of their lowest member id. One whose
+    /// members share a `neuron_class` is named after it, any other after its
+    /// lowest member with the number of others (`AVAL+3`), and a name already
+    /// taken gets the supernode's id appended. Types, regions, positions and
+    /// connections merge as in `collapse_to_classes`, except that connections
+    /// within a group are dropped; `contract_with_self_loops` keeps them.
+    pub fn contract(&self, groups: &[Vec<usize>]) -> Network {
+        self.contract_groups(groups, false)
+    }
+
+    /// `contract`, turning connections within a group into self-connections
+    /// of its supernode.
+    pub fn contract_with_self_loops(&self, groups: &[Vec<usize>]) -> Network {
+        self.contract_groups(groups, true)
+    }
+
+    fn contract_groups(&self, groups: &[Vec<usize>], self_loops: bool) -> Network {
+        let n = self.neurons.len();
+        let mut group_of: Vec<Option<usize>> = vec![None; n];
+        for (group, members) in groups.iter().enumerate() {
+            for &id in members.iter().filter(|&&id| id < n) {
+                group_of[id].get_or_insert(group);
+            }
+        }
+        let mut node_of_group: Vec<Option<usize>> = vec![None; groups.len()];
+        let mut members: Vec<Vec<usize>> = Vec::new();
+        let mut node_of = Vec::with_capacity(n);
+        for (id, group) in group_of.iter().enumerate() {
+            let node = match group {
+                Some(group) => *node_of_group[*group].get_or_insert_with(|| {
+                    members.push(Vec::new());
+                    members.len() - 1
+                }),
+                None => {
+                    members.push(Vec::new());
+                    members.len() - 1
+                }
+            };
+            members[node].push(id);
+            node_of.push(node);
+        }
+
+        let mut names: Vec<String> = Vec::with_capacity(members.len());
+        for (node, ids) in members.iter().enumerate() {
+            let first = self.neurons[ids[0]].name();
+            let class = neuron_class(first);
+            let mut name = if ids.len() == 1 {
+                first.to_string()
+            } else if ids
+                .iter()
+                .all(|&id| neuron_class(self.neurons[id].name()) == class)
+            {
+                class
+            } else {
+                format!("{first}+{}", ids.len() - 1)
+            };
+            if names.contains(&name) {
+                name = format!("{name}_{node}");
+            }
+            names.push(name);
+        }
+        self.merge_into(&node_of, &names, self_loops)
+    }
+
+    /// A network of one neuron per `names` entry, neuron `id` of this one
+    /// merged into `node_of[id]`. Each merged neuron takes the first known
+    /// type, region and transmitter of its members and their mean soma
+    /// position. Connections with the same merged endpoints and synapse type
+    /// are summed; those between members of one node become self-connections
+    /// if `internal` is set and are dropped otherwise.
+    fn merge_into(&self, node_of: &[usize], names: &[String], internal: bool) -> Network {
+        let mut merged_network = Network::new();
+        for name in names {
+            merged_network.add_neuron(name, NeuronType::Other, Region::Unknown, 0.0);
+        }
+        let mut positions: Vec<(f64, usize)> = vec![(0.0, 0); names.len()];
+        for neuron in &self.neurons {
+            let id = node_of[neuron.id];
+            let merged = &mut merged_network.neurons[id];
+            if merged.neuron_type == NeuronType::Other {
+                merged.neuron_type = neuron.neuron_type;
+                merged.type_confidence = neuron.type_confidence;
+            }
+            if merged.region == Region::Unknown {
+                merged.region = neuron.region;
+            }
+            if merged.transmitter.is_none() {
+                merged.transmitter.clone_from(&neuron.transmitter);
+            }
+            positions[id].0 += neuron.soma_position;
+            positions[id].1 += 1;
+        }
+        for (neuron, (sum, count)) in merged_network.neurons.iter_mut().zip(positions) {
+            neuron.soma_position = sum / count.max(1) as f64;
+        }
+
+        let mut merged: HashMap<(usize, usize, SynapseType), usize> = HashMap::new();
+        for conn in &self.connections {
+            let key = (
+                node_of[conn.from_id],
+                node_of[conn.to_id],
+                conn.synapse_type,
+            );
+            if key.0 == key.1 && !internal {
+                continue;
+            }
+            match merged.get(&key) {
+                Some(&index) => {
+                    let existing = &mut merged_network.connections[index];
+                    existing.weight += conn.weight;
+                    existing.contacts += conn.contacts;
+                }
+                None => {
+                    merged.insert(key, merged_network.connections.len());
+                    merged_network.connections.push(
+                        Connection::new(key.0, key.1, key.2, conn.weight)
+                            .with_contacts(conn.contacts),
+                    );
+                }
+            }
+        }
+        merged_network.rebuild_indices();
+        merged_network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const SEND: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    fn network() -> Network {
+        let mut network = Network::new();
+        for (name, position) in [("AVAL", 1.0), ("AVAR", 3.0), ("ASHL", 0.0), ("DA01", 5.0)] {
+            network.add_neuron(name, NeuronType::Other, Region::Unknown, position);
+        }
+        network.add_connection(0, 1, SEND, 1.0);
+        network.add_connection(1, 0, SEND, 2.0);
+        network.add_connection(0, 3, SEND, 3.0);
+        network.add_connection(1, 3, SEND, 4.0);
+        network.add_connection(2, 3, SEND, 5.0);
+        network
+    }
+
+    /// `(from, to, weight)` of each connection, by endpoint ids.
+    fn weights(network: &Network) -> Vec<(&str, &str, f64)> {
+        let mut connections: Vec<&Connection> = network.connections.iter().collect();
+        connections.sort_by_key(|c| (c.from_id, c.to_id));
+        connections
+            .iter()
+            .map(|c| {
+                (
+                    network.neurons[c.from_id].name(),
+                    network.neurons[c.to_id].name(),
+                    c.weight,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_become_supernodes() {
+        let network = network();
+        let contracted = network.contract(&[vec![0, 1], vec![2, 3]]);
+        let names: Vec<&str> = contracted.neurons.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["AVA", "ASHL+1"]);
+        assert_eq!(contracted.neurons[0].soma_position, 2.0);
+        assert_eq!(weights(&contracted), [("AVA", "ASHL+1", 7.0)]);
+
+        let looped = network.contract_with_self_loops(&[vec![0, 1], vec![2, 3]]);
+        assert_eq!(
+            weights(&looped),
+            [
+                ("AVA", "AVA", 3.0),
+                ("AVA", "ASHL+1", 7.0),
+                ("ASHL+1", "ASHL+1", 5.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn ungrouped_neurons_stay_and_the_first_group_wins() {
+        let network = network();
+        let contracted = network.contract(&[vec![0, 1, 9], vec![1, 3]]);
+        let names: Vec<&str> = contracted.neurons.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["AVA", "ASHL", "DA01"]);
+        assert_eq!(
+            weights(&contracted),
+            [("AVA", "DA01", 7.0), ("ASHL", "DA01", 5.0)]
+        );
+    }
+
+    #[test]
+    fn class_collapse_is_unchanged() {
+        let (classes, _) = network().collapse_to_classes();
+        assert_eq!(
+            weights(&classes),
+            [("AVA", "AVA", 3.0), ("AVA", "DA", 7.0), ("ASH", "DA", 5.0)]
+        );
+    }
+
+    #[test]
+    fn tricky_names_reduce_to_their_class() {
+        for (name, class) in [
+            ("DD01", "DD"),
+            ("DD6", "DD"),
+            ("VD13", "VD"),
+            ("vd05 ", "VD"),
+            ("VC06", "VC"),
+            ("AS10", "AS"),
+            ("CEPDL", "CEP"),
+            ("CEPVR", "CEP"),
+            ("RMED", "RME"),
+            ("RMEV", "RME"),
+            ("RMEL", "RME"),
+            ("SABD", "SAB"),
+            ("SABVL", "SAB"),
+            ("SMDDR", "SMD"),
+            ("IL1VL", "IL1"),
+            ("RMDL", "RMD"),
+            ("RMDDL", "RMD"),
+            ("AVDL", "AVD"),
+            ("RID", "RID"),
+            ("I1L", "I1"),
+            ("AVL", "AVL"),
+            ("PVR", "PVR"),
+            ("AQR", "AQR"),
+            ("DVA", "DVA"),
+        ] {
+            assert_eq!(neuron_class(name), class, "{name}");
+        }
+    }
+
+    #[test]
+    fn class_collapse_conserves_total_weight() {
+        let network = Network::example_touch_circuit();
+        let (classes, class_of) = network.collapse_to_classes();
+        let total = |network: &Network| network.connections.iter().map(|c| c.weight).sum::<f64>();
+        assert!((total(&classes) - total(&network)).abs() < 1e-9);
+        assert_eq!(class_of.len(), network.neurons.len());
+        assert_eq!(class_of["AVAL"], "AVA");
+        assert!(classes.neurons.len() < network.neurons.len());
+        // White et al. (1986) group the 302 neurons into 118 classes.
+        assert_eq!(
+            Network::with_canonical_roster().functional_classes().len(),
+            118
+        );
+    }
+}
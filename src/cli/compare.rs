@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use super::{CliResult, LoadOptions};
+
+/// Compare two connectomes aligned by neuron name
+#[derive(Debug, Args)]
+pub struct CompareArgs {
+    /// The "before" edge list
+    pub first: PathBuf,
+
+    /// The "after" edge list
+    pub second: PathBuf,
+
+    #[command(flatten)]
+    pub options: LoadOptions,
+
+    /// Emit the report as JSON instead of Markdown
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn run(args: &CompareArgs) -> CliResult {
+    let first = args.options.load(&args.first)?;
+    let second = args.options.load(&args.second)?;
+    let report = first.compare(&second);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+
+use celegans_connectome::{ExportFormat, LayoutOptions, Region, Selection};
+
+use super::{resolve_neuron, CliResult, GraphArg, LoadArgs, SynapseArg};
+
+/// Write the network, or a filtered part of it, in a graph or matrix format
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    #[arg(long, value_enum)]
+    pub format: FormatArg,
+
+    /// Output file, or "-" for stdout
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Keep only these neurons (comma-separated names)
+    #[arg(long, value_delimiter = ',')]
+    pub subset_names: Vec<String>,
+
+    /// Keep only neurons in this region
+    #[arg(long, value_parser = parse_region)]
+    pub region: Option<Region>,
+
+    /// Keep only neurons matching this selection, e.g.
+    /// "type:interneuron & outdeg>20"
+    #[arg(long, value_name = "EXPR")]
+    pub select: Option<Selection>,
+
+    /// Drop connections lighter than this
+    #[arg(long)]
+    pub min_weight: Option<f64>,
+
+    /// Keep only this graph; gap junctions are merged and symmetrized
+    #[arg(long, value_enum)]
+    pub graph: Option<GraphArg>,
+
+    /// Keep only connections of these classes
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub synapse_types: Vec<SynapseArg>,
+
+    /// Tag edges feedforward, feedback, lateral, unranked or excluded by
+    /// sensory depth (dot and graphml only)
+    #[arg(long)]
+    pub flow: bool,
+
+    /// Add x/y node coordinates from this layout (graphml and cytoscape only)
+    #[arg(long, value_enum, conflicts_with = "flow")]
+    pub layout: Option<LayoutArg>,
+
+    /// Seed of the force layout's starting positions
+    #[arg(long, default_value_t = 0, requires = "layout")]
+    pub layout_seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LayoutArg {
+    /// Force-directed, x starting from soma position
+    Force,
+    /// Normalized Laplacian eigenmap
+    Spectral,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FormatArg {
+    Gml,
+    Dot,
+    #[value(name = "graphml")]
+    GraphMl,
+    Gexf,
+    Json,
+    EdgeCsv,
+    Adjacency,
+    Mtx,
+    Cytoscape,
+}
+
+impl FormatArg {
+    fn format(self) -> ExportFormat {
+        match self {
+            FormatArg::Gml => ExportFormat::Gml,
+            FormatArg::Dot => ExportFormat::Dot,
+            FormatArg::GraphMl => ExportFormat::GraphMl,
+            FormatArg::Gexf => ExportFormat::Gexf,
+            FormatArg::Json => ExportFormat::Json,
+            FormatArg::EdgeCsv => ExportFormat::EdgeCsv,
+            FormatArg::Adjacency => ExportFormat::Adjacency,
+            FormatArg::Mtx => ExportFormat::Mtx,
+            FormatArg::Cytoscape => ExportFormat::Cytoscape,
+        }
+    }
+}
+
+fn parse_region(label: &str) -> Result<Region, String> {
+    Region::from_label(label)
+        .ok_or_else(|| format!("unknown region {label:?}; expected head, midbody or tail"))
+}
+
+pub fn run(args: &ExportArgs) -> CliResult {
+    let mut network = args.load.load()?;
+    if let Some(graph) = args.graph {
+        network = network.graph_view(graph.kind());
+    }
+
+    if !args.subset_names.is_empty() || args.region.is_some() || args.select.is_some() {
+        let mut ids: Vec<usize> = if args.subset_names.is_empty() {
+            (0..network.neurons.len()).collect()
+        } else {
+            args.subset_names
+                .iter()
+                .map(|name| resolve_neuron(&network, name.trim()))
+                .collect::<Result<_, _>>()?
+        };
+        if let Some(region) = args.region {
+            ids.retain(|&id| network.neurons[id].region == region);
+        }
+        if let Some(selection) = &args.select {
+            ids.retain(|&id| selection.matches(&network, id));
+        }
+        network = network.subnetwork(&ids);
+    }
+    if let Some(min_weight) = args.min_weight {
+        network.prune(min_weight);
+    }
+    if !args.synapse_types.is_empty() {
+        network.retain_connections(|conn| {
+            args.synapse_types
+                .iter()
+                .any(|class| class.matches(conn.synapse_type))
+        });
+    }
+
+    let format = args.format.format();
+    if args.flow && !matches!(format, ExportFormat::Dot | ExportFormat::GraphMl) {
+        return Err(format!("--flow needs dot or graphml, not {}", format.name()).into());
+    }
+    if args.layout.is_some() && !matches!(format, ExportFormat::GraphMl | ExportFormat::Cytoscape) {
+        return Err(format!("--layout needs graphml or cytoscape, not {}", format.name()).into());
+    }
+    let layout = args.layout.map(|layout| match layout {
+        LayoutArg::Force => network.layout_force_directed(&LayoutOptions {
+            seed: args.layout_seed,
+            soma_x: true,
+            ..LayoutOptions::default()
+        }),
+        LayoutArg::Spectral => network.layout_spectral(true),
+    });
+    let write = |writer: &mut dyn io::Write| match (args.flow, &layout, format) {
+        (true, _, ExportFormat::Dot) => network.write_dot_with_flow(writer),
+        (true, _, ExportFormat::GraphMl) => network.write_graphml_with_flow(writer),
+        (_, Some(layout), ExportFormat::GraphMl) => {
+            network.write_graphml_with_layout(writer, layout)
+        }
+        (_, Some(layout), ExportFormat::Cytoscape) => {
+            network.write_cytoscape_json_with_layout(writer, layout)
+        }
+        _ => network.export(format, writer),
+    };
+    if args.out.as_os_str() == "-" {
+        write(&mut io::stdout().lock())?;
+    } else {
+        write(&mut File::create(&args.out)?)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,382 @@
+//! Subcommands of the `flymind` binary.
+
+pub mod compare;
+pub mod export;
+pub mod path;
+pub mod query;
+pub mod repl;
+pub mod simulate;
+pub mod stats;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use celegans_connectome::canonical;
+use celegans_connectome::{
+    ExtrasynapticEdges, FlymindError, GapJunctionMode, GraphKind, LoadProgress, LoaderConfig,
+    MergeMode, NbrTransform, Network, PolyadicScaling, SelfConnections, SynapseClass, SynapseCode,
+    SynapseType, UnknownCodePolicy,
+};
+
+/// Outcome of a subcommand: its exit code, or an error printed by `main`.
+pub type CliResult = Result<ExitCode, Box<dyn Error>>;
+
+/// The connectome argument shared by most subcommands.
+#[derive(Debug, Args)]
+pub struct LoadArgs {
+    /// Edge list in the NeuronConnect.csv format
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub options: LoadOptions,
+}
+
+impl LoadArgs {
+    pub fn load(&self) -> Result<Network, FlymindError> {
+        self.options.load(&self.file)
+    }
+}
+
+/// Loader options shared by every subcommand that reads a connectome.
+#[derive(Debug, Args)]
+pub struct LoadOptions {
+    /// Field delimiter (use "\t" for tab-separated files)
+    #[arg(long, default_value = ",")]
+    pub delimiter: String,
+
+    /// How repeated (from, to, type) rows are stored
+    #[arg(long, value_enum, default_value_t = MergeArg::Keep)]
+    pub merge: MergeArg,
+
+    /// Fail on unknown synapse codes
+    #[arg(long, conflicts_with = "lenient")]
+    pub strict: bool,
+
+    /// Skip rows with unknown synapse codes instead of loading them as chemical sends
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Load rows of Type CODE as the built-in code BUILTIN (S, Sp, R, Rp, EJ or
+    /// NMJ), e.g. GJ=EJ, polyadic if BUILTIN is; may be repeated
+    #[arg(long, value_name = "CODE=BUILTIN")]
+    pub synapse_code: Vec<String>,
+
+    /// Keep differing gap-junction weights per direction (rectifying junctions)
+    #[arg(long)]
+    pub asymmetric_gap_junctions: bool,
+
+    /// Treat names differing only in case, whitespace or punctuation as one neuron
+    #[arg(long)]
+    pub normalize_names: bool,
+
+    /// Share each polyadic (Sp/Rp) contact among the partners of its release
+    /// site: a partner count per site, or "estimate" to estimate it per neuron
+    #[arg(long, value_name = "PARTNERS")]
+    pub polyadic: Option<String>,
+
+    /// Store each connection's summed Nbr through this function as its
+    /// weight; contact counts stay as read
+    #[arg(long, value_enum, default_value_t = WeightTransformArg::Identity)]
+    pub weight_transform: WeightTransformArg,
+
+    /// Start from the 302 canonical neurons, failing on rows naming any other
+    #[arg(long)]
+    pub canonical_roster: bool,
+
+    /// Another edge list to load into the same network, e.g. gap junctions
+    /// or corrections kept apart; may be repeated
+    #[arg(long, value_name = "FILE")]
+    pub append: Vec<PathBuf>,
+
+    /// Extrasynaptic (monoamine/neuropeptide) edge list with source,
+    /// target, transmitter and optional confidence columns, overlaid after
+    /// loading
+    #[arg(long, value_name = "FILE")]
+    pub extrasynaptic: Option<PathBuf>,
+
+    /// name,type,region CSV applied after loading
+    #[arg(long)]
+    pub metadata: Option<PathBuf>,
+
+    /// CSV of type probabilities (a name column and one per type label),
+    /// applied after the metadata: each neuron takes its most likely type
+    #[arg(long, value_name = "FILE")]
+    pub type_probabilities: Option<PathBuf>,
+
+    /// Leave neurons whose most likely type is less probable than this as "other"
+    #[arg(
+        long,
+        value_name = "P",
+        default_value_t = 0.0,
+        requires = "type_probabilities"
+    )]
+    pub min_type_confidence: f64,
+
+    /// Check the loaded network and warn about likely data errors, such as
+    /// bilateral pairs whose types or regions disagree
+    #[arg(long)]
+    pub validate: bool,
+
+    /// Drop neurons left without any connection
+    #[arg(long)]
+    pub remove_isolated: bool,
+
+    /// Leave connections from a neuron onto itself out of analyses and runs
+    #[arg(long)]
+    pub ignore_self_connections: bool,
+
+    /// Count extrasynaptic edges in degrees, matrices and other measures of
+    /// the whole network, not only in --graph extrasynaptic|multiplex views
+    #[arg(long, requires = "extrasynaptic")]
+    pub count_extrasynaptic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergeArg {
+    Keep,
+    Sum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WeightTransformArg {
+    Identity,
+    Log1p,
+    Sqrt,
+}
+
+impl LoadOptions {
+    pub fn config(&self) -> Result<LoaderConfig, String> {
+        let delimiter = match self.delimiter.as_str() {
+            "\\t" | "tab" => b'\t',
+            d if d.len() == 1 => d.as_bytes()[0],
+            d => return Err(format!("delimiter must be a single byte, got {d:?}")),
+        };
+        let mut synapse_code_map = HashMap::new();
+        for entry in &self.synapse_code {
+            let mapped = entry
+                .split_once('=')
+                .and_then(|(code, builtin)| Some((code, SynapseCode::builtin(builtin)?)));
+            let Some((code, synapse_code)) = mapped else {
+                return Err(format!(
+                    "--synapse-code must be CODE=BUILTIN with a built-in code such as EJ, \
+                     got {entry:?}"
+                ));
+            };
+            synapse_code_map.insert(code.to_string(), synapse_code);
+        }
+        Ok(LoaderConfig {
+            delimiter,
+            synapse_code_map,
+            merge_mode: match self.merge {
+                MergeArg::Keep => MergeMode::KeepAll,
+                MergeArg::Sum => MergeMode::Sum,
+            },
+            unknown_code_policy: if self.strict {
+                UnknownCodePolicy::Error
+            } else if self.lenient {
+                UnknownCodePolicy::Skip
+            } else {
+                UnknownCodePolicy::Permissive
+            },
+            gap_junctions: if self.asymmetric_gap_junctions {
+                GapJunctionMode::Asymmetric
+            } else {
+                GapJunctionMode::Symmetric
+            },
+            normalize_names: self.normalize_names,
+            weight_transform: match self.weight_transform {
+                WeightTransformArg::Identity => NbrTransform::Identity,
+                WeightTransformArg::Log1p => NbrTransform::Log1p,
+                WeightTransformArg::Sqrt => NbrTransform::Sqrt,
+            },
+            polyadic: match self.polyadic.as_deref() {
+                None => PolyadicScaling::Off,
+                Some("estimate") => PolyadicScaling::Estimated,
+                Some(partners) => match partners.parse::<f64>() {
+                    Ok(partners) if partners >= 1.0 => PolyadicScaling::PerSite(partners),
+                    _ => {
+                        return Err(format!(
+                            "--polyadic must be \"estimate\" or a partner count of at least 1, \
+                             got {partners:?}"
+                        ))
+                    }
+                },
+            },
+            ..LoaderConfig::default()
+        })
+    }
+
+    /// Load the network, printing load warnings (and progress on a terminal) to stderr.
+    pub fn load(&self, file: &Path) -> Result<Network, FlymindError> {
+        let config = self
+            .config()
+            .map_err(|msg| FlymindError::Io(io::Error::new(io::ErrorKind::InvalidInput, msg)))?;
+
+        let bar = io::stderr().is_terminal().then(|| {
+            let size = fs::metadata(file).map(|m| m.len()).ok();
+            let bar = ProgressBar::with_draw_target(size, ProgressDrawTarget::stderr_with_hz(10));
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "loading {bar:30} {bytes}/{total_bytes}  {msg}  {bytes_per_sec}  eta {eta}",
+                )
+                .expect("progress template is valid"),
+            );
+            bar
+        });
+        let mut on_progress = |p: LoadProgress| {
+            if let Some(bar) = &bar {
+                bar.set_position(p.bytes_read);
+                bar.set_message(format!("{} rows", p.rows_read));
+            }
+            ControlFlow::Continue(())
+        };
+        let loaded = if self.canonical_roster {
+            let mut network = Network::with_canonical_roster();
+            network
+                .append_from_csv_with(file, &config, Some(&mut on_progress))
+                .map(|report| (network, report))
+        } else {
+            Network::load_connect_csv(file, &config, Some(&mut on_progress))
+        };
+        if let Some(bar) = &bar {
+            bar.finish_and_clear();
+        }
+        let (mut network, report) = loaded?;
+
+        for warning in report.warnings() {
+            eprintln!("warning: {warning}");
+        }
+        for path in &self.append {
+            for warning in network.append_from_csv(path, &config)?.warnings() {
+                eprintln!("warning: {}: {warning}", path.display());
+            }
+        }
+        if let Some(path) = &self.metadata {
+            for name in network.apply_metadata_csv(path)? {
+                eprintln!("warning: metadata names unknown neuron {name:?}");
+            }
+        }
+        if let Some(path) = &self.type_probabilities {
+            let report =
+                network.apply_type_probabilities(path, &config, self.min_type_confidence)?;
+            for name in report.unknown_neurons {
+                eprintln!("warning: type probabilities name unknown neuron {name:?}");
+            }
+            if report.below_threshold > 0 {
+                eprintln!(
+                    "{} neurons left as other below type confidence {}",
+                    report.below_threshold, self.min_type_confidence
+                );
+            }
+        }
+        if let Some(path) = &self.extrasynaptic {
+            let report = network.load_extrasynaptic_csv(path, &config)?;
+            for name in report.unknown_neurons {
+                eprintln!("warning: extrasynaptic edge names unknown neuron {name:?}");
+            }
+        }
+        if self.validate {
+            for warning in network.validate().warnings() {
+                eprintln!("warning: {warning}");
+            }
+        }
+        if self.ignore_self_connections {
+            network.self_connections = SelfConnections::Ignore;
+        }
+        if self.count_extrasynaptic {
+            network.extrasynaptic_edges = ExtrasynapticEdges::Include;
+        }
+        if self.remove_isolated {
+            let removed = network.remove_isolated();
+            if !removed.is_empty() {
+                eprintln!(
+                    "removed {} isolated neurons: {}",
+                    removed.len(),
+                    removed.join(", ")
+                );
+            }
+        }
+        Ok(network)
+    }
+}
+
+/// Synapse classes accepted by filtering flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SynapseArg {
+    Chem,
+    Gap,
+    Nmj,
+    Extrasynaptic,
+}
+
+impl SynapseArg {
+    pub fn matches(self, synapse_type: SynapseType) -> bool {
+        match self {
+            SynapseArg::Chem => synapse_type.is_chemical(),
+            SynapseArg::Gap => synapse_type == SynapseType::GapJunction,
+            SynapseArg::Nmj => synapse_type == SynapseType::NMJ,
+            SynapseArg::Extrasynaptic => matches!(synapse_type, SynapseType::Extrasynaptic(_)),
+        }
+    }
+
+    pub fn class(self) -> SynapseClass {
+        match self {
+            SynapseArg::Chem => SynapseClass::Chemical,
+            SynapseArg::Gap => SynapseClass::GapJunction,
+            SynapseArg::Nmj => SynapseClass::Nmj,
+            SynapseArg::Extrasynaptic => SynapseClass::Extrasynaptic,
+        }
+    }
+}
+
+/// Connection graphs accepted by `--graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphArg {
+    Chemical,
+    Gap,
+    Combined,
+    Extrasynaptic,
+    Multiplex,
+}
+
+impl GraphArg {
+    pub fn kind(self) -> GraphKind {
+        match self {
+            GraphArg::Chemical => GraphKind::Chemical,
+            GraphArg::Gap => GraphKind::GapJunction,
+            GraphArg::Combined => GraphKind::Combined,
+            GraphArg::Extrasynaptic => GraphKind::Extrasynaptic,
+            GraphArg::Multiplex => GraphKind::Multiplex,
+        }
+    }
+}
+
+/// Look up a neuron by name, suggesting near misses when it isn't found.
+pub fn resolve_neuron(network: &Network, name: &str) -> Result<usize, String> {
+    if let Some(id) = network.id_of(name) {
+        return Ok(id);
+    }
+    let mut suggestions =
+        canonical::closest_names(name, network.neurons.iter().map(|n| n.name()), 2);
+    for candidate in canonical::near_misses(name) {
+        if !suggestions.contains(&candidate) {
+            suggestions.push(candidate);
+        }
+    }
+    suggestions.truncate(5);
+    let mut message = format!("unknown neuron {name:?}");
+    if !suggestions.is_empty() {
+        message.push_str(&format!("; did you mean {}?", suggestions.join(", ")));
+    } else if canonical::is_canonical(name) {
+        message.push_str(" (a valid neuron name, but absent from this dataset)");
+    }
+    Err(message)
+}
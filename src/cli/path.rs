@@ -0,0 +1,151 @@
+use std::process::ExitCode;
+
+use clap::Args;
+use serde::Serialize;
+
+use celegans_connectome::{Network, Path, PathOptions};
+
+use super::{resolve_neuron, CliResult, GraphArg, LoadArgs, SynapseArg};
+
+/// Exit code when the target cannot be reached, so scripts can branch on it.
+pub const UNREACHABLE: u8 = 3;
+
+/// Find the shortest path, or all short simple paths, between two neurons
+#[derive(Debug, Args)]
+pub struct PathArgs {
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    #[arg(long)]
+    pub from: String,
+
+    #[arg(long)]
+    pub to: String,
+
+    /// Prefer strong connections (cost 1/weight) instead of fewest hops
+    #[arg(long, conflicts_with = "all")]
+    pub weighted: bool,
+
+    /// Longest path considered, in hops (default 4 with --all)
+    #[arg(long)]
+    pub max_hops: Option<usize>,
+
+    /// List every simple path up to --max-hops
+    #[arg(long)]
+    pub all: bool,
+
+    /// Connection classes that may be followed
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "chem")]
+    pub via_types: Vec<SynapseArg>,
+
+    /// Follow the connections of this graph instead of --via-types
+    #[arg(long, value_enum, conflicts_with = "via_types")]
+    pub graph: Option<GraphArg>,
+
+    /// Emit paths as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Hop<'a> {
+    from: &'a str,
+    to: &'a str,
+    synapse_type: &'static str,
+    weight: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct PathView<'a> {
+    neurons: Vec<&'a str>,
+    hops: Vec<Hop<'a>>,
+}
+
+pub fn run(args: &PathArgs) -> CliResult {
+    let network = args.load.load()?;
+    let from = resolve_neuron(&network, &args.from)?;
+    let to = resolve_neuron(&network, &args.to)?;
+    let opts = PathOptions {
+        classes: match args.graph {
+            Some(graph) => graph.kind().classes(),
+            None => args.via_types.iter().map(|s| s.class()).collect(),
+        },
+        max_hops: args.max_hops,
+        ..PathOptions::default()
+    };
+
+    let paths: Vec<Path> = if args.all {
+        network.simple_paths(from, to, &opts)
+    } else if args.weighted {
+        network
+            .weighted_shortest_path(from, to, &opts)
+            .map(|(path, _)| path)
+            .into_iter()
+            .collect()
+    } else {
+        network
+            .shortest_path_with(from, to, &opts)
+            .into_iter()
+            .collect()
+    };
+
+    if paths.is_empty() {
+        if args.json {
+            println!("[]");
+        }
+        eprintln!("no path from {} to {}", args.from, args.to);
+        return Ok(ExitCode::from(UNREACHABLE));
+    }
+
+    let views: Vec<PathView> = paths.iter().map(|p| view(&network, p)).collect();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&views)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for (path, view) in paths.iter().zip(&views) {
+        println!("{}", arrow_line(&network, path));
+        if !args.all {
+            for hop in &view.hops {
+                println!(
+                    "  {:<8} -> {:<8} {:<16} {}",
+                    hop.from, hop.to, hop.synapse_type, hop.weight
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn view<'a>(network: &'a Network, path: &Path) -> PathView<'a> {
+    let neurons: Vec<&str> = path
+        .neurons
+        .iter()
+        .map(|&id| network.neurons[id].name())
+        .collect();
+    let hops = path
+        .connections
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| Hop {
+            from: neurons[i],
+            to: neurons[i + 1],
+            synapse_type: network.connections[c].synapse_type.label(),
+            weight: network.connections[c].weight,
+        })
+        .collect();
+    PathView { neurons, hops }
+}
+
+/// `ASHL -S-> AVDL -S-> AVAL`
+pub fn arrow_line(network: &Network, path: &Path) -> String {
+    let mut line = network.neurons[path.neurons[0]].name().to_string();
+    for (i, &c) in path.connections.iter().enumerate() {
+        let code = network.connections[c].synapse_type.code();
+        line.push_str(&format!(
+            " -{code}-> {}",
+            network.neurons[path.neurons[i + 1]].name()
+        ));
+    }
+    line
+}
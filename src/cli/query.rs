@@ -0,0 +1,170 @@
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use celegans_connectome::{Direction, Network, Selection};
+
+use super::{resolve_neuron, CliResult, GraphArg, LoadArgs, SynapseArg};
+
+/// List a neuron's partners, or every connection between a pair
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    /// Neuron whose connections are listed
+    #[arg(long, required_unless_present = "pair", conflicts_with = "pair")]
+    pub neuron: Option<String>,
+
+    /// Show every parallel connection between two neurons (both directions)
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    pub pair: Option<Vec<String>>,
+
+    #[arg(long, value_enum, default_value_t = DirectionArg::Out)]
+    pub direction: DirectionArg,
+
+    /// Only list connections of this class
+    #[arg(long = "type", value_enum)]
+    pub synapse: Option<SynapseArg>,
+
+    /// Query only this graph; gap junctions are merged and symmetrized
+    #[arg(long, value_enum, conflicts_with = "synapse")]
+    pub graph: Option<GraphArg>,
+
+    #[arg(long, default_value_t = 0.0)]
+    pub min_weight: f64,
+
+    /// Only list partners matching this selection, e.g. "type:motor"
+    #[arg(long, value_name = "EXPR")]
+    pub select: Option<Selection>,
+
+    #[arg(long, value_enum, default_value_t = SortArg::Weight)]
+    pub sort: SortArg,
+
+    /// Emit rows as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DirectionArg {
+    Out,
+    In,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortArg {
+    Weight,
+    Name,
+}
+
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    from: &'a str,
+    to: &'a str,
+    partner: &'a str,
+    synapse_type: &'static str,
+    subtype: Option<&'static str>,
+    weight: f64,
+}
+
+pub fn run(args: &QueryArgs) -> CliResult {
+    let mut network = args.load.load()?;
+    if let Some(graph) = args.graph {
+        network = network.graph_view(graph.kind());
+    }
+
+    let (anchor, indices) = if let Some(pair) = &args.pair {
+        let a = resolve_neuron(&network, &pair[0])?;
+        let b = resolve_neuron(&network, &pair[1])?;
+        let indices: Vec<usize> = network
+            .connections_of(a, Direction::Both)
+            .into_iter()
+            .filter(|&c| {
+                let conn = &network.connections[c];
+                (conn.from_id == a && conn.to_id == b) || (conn.from_id == b && conn.to_id == a)
+            })
+            .collect();
+        (a, indices)
+    } else {
+        let name = args.neuron.as_deref().unwrap_or_default();
+        let id = resolve_neuron(&network, name)?;
+        let direction = match args.direction {
+            DirectionArg::Out => Direction::Out,
+            DirectionArg::In => Direction::In,
+            DirectionArg::Both => Direction::Both,
+        };
+        (id, network.connections_of(id, direction))
+    };
+
+    let mut rows: Vec<Row> = indices
+        .into_iter()
+        .map(|c| &network.connections[c])
+        .filter(|conn| args.synapse.is_none_or(|s| s.matches(conn.synapse_type)))
+        .filter(|conn| conn.weight >= args.min_weight)
+        .filter_map(|conn| {
+            let partner = if conn.from_id == anchor {
+                conn.to_id
+            } else {
+                conn.from_id
+            };
+            if args
+                .select
+                .as_ref()
+                .is_some_and(|s| !s.matches(&network, partner))
+            {
+                return None;
+            }
+            Some(Row {
+                from: network.neurons[conn.from_id].name(),
+                to: network.neurons[conn.to_id].name(),
+                partner: network.neurons[partner].name(),
+                synapse_type: conn.synapse_type.label(),
+                subtype: conn.synapse_type.subtype().map(|s| s.label()),
+                weight: conn.weight,
+            })
+        })
+        .collect();
+
+    match args.sort {
+        SortArg::Weight => rows.sort_by(|a, b| {
+            b.weight
+                .total_cmp(&a.weight)
+                .then_with(|| a.partner.cmp(b.partner))
+        }),
+        SortArg::Name => rows.sort_by(|a, b| {
+            a.partner
+                .cmp(b.partner)
+                .then_with(|| b.weight.total_cmp(&a.weight))
+        }),
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+    } else {
+        print_table(&network, anchor, &rows);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_table(network: &Network, anchor: usize, rows: &[Row]) {
+    println!(
+        "{:<10}{:<6}{:<18}{:<12}{:>8}",
+        "partner", "dir", "synapse", "subtype", "weight"
+    );
+    let anchor_name = network.neurons[anchor].name();
+    for row in rows {
+        let dir = if row.from == anchor_name { "out" } else { "in" };
+        println!(
+            "{:<10}{:<6}{:<18}{:<12}{:>8}",
+            row.partner,
+            dir,
+            row.synapse_type,
+            row.subtype.unwrap_or("-"),
+            row.weight
+        );
+    }
+    println!("{} connections", rows.len());
+}
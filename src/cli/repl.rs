@@ -0,0 +1,464 @@
+//! An interactive prompt over one loaded network.
+//!
+//! `Session::execute` parses and runs a single command line and returns the text
+//! to show, so the command set can be driven without a terminal; `run` only
+//! wires it to a line editor.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::process::ExitCode;
+
+use clap::{Args, ValueEnum};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use celegans_connectome::{Direction, ExportFormat, Network, PathOptions, Region};
+
+use super::path::arrow_line;
+use super::{resolve_neuron, CliResult, LoadArgs, SynapseArg};
+
+/// Explore a network interactively
+#[derive(Debug, Args)]
+pub struct ReplArgs {
+    #[command(flatten)]
+    pub load: LoadArgs,
+}
+
+/// `(name, usage, summary)` for every command.
+const COMMANDS: [(&str, &str, &str); 9] = [
+    ("info", "info NEURON", "type, region and degree of a neuron"),
+    (
+        "neighbors",
+        "neighbors NEURON [out|in|both] [chem|gap|nmj]",
+        "partners of a neuron, heaviest first",
+    ),
+    (
+        "path",
+        "path FROM TO [chem,gap,nmj]",
+        "fewest-hop path between two neurons",
+    ),
+    ("stats", "stats", "structural summary"),
+    (
+        "top",
+        "top degree|in|out|strength|betweenness [N]",
+        "the N highest-ranked neurons (default 10)",
+    ),
+    (
+        "subnet",
+        "subnet REGION|NAME,NAME,...|all",
+        "restrict later commands to part of the network",
+    ),
+    (
+        "export",
+        "export FORMAT FILE",
+        "write the current network to FILE",
+    ),
+    (
+        "help",
+        "help [COMMAND]",
+        "list commands, or show one command's usage",
+    ),
+    ("quit", "quit", "leave the prompt (also exit or Ctrl-D)"),
+];
+
+/// What the caller should do after a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Text(String),
+    Quit,
+}
+
+/// The loaded network plus the subnetwork selected with `subnet`, if any.
+pub struct Session {
+    network: Network,
+    subnet: Option<(String, Network)>,
+}
+
+impl Session {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            subnet: None,
+        }
+    }
+
+    /// The network commands currently operate on.
+    pub fn current(&self) -> &Network {
+        self.subnet.as_ref().map_or(&self.network, |(_, net)| net)
+    }
+
+    pub fn prompt(&self) -> String {
+        match &self.subnet {
+            Some((label, _)) => format!("flymind[{label}]> "),
+            None => "flymind> ".to_string(),
+        }
+    }
+
+    /// Run one command line. Blank lines produce empty text.
+    pub fn execute(&mut self, line: &str) -> Reply {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Reply::Text(String::new());
+        };
+        let result = match command {
+            "info" => self.info(args),
+            "neighbors" => self.neighbors(args),
+            "path" => self.path(args),
+            "stats" if args.is_empty() => Ok(self.current().stats().to_string()),
+            "top" => self.top(args),
+            "subnet" => self.select_subnet(args),
+            "export" => self.export(args),
+            "help" => Ok(help(args.first().copied())),
+            "quit" | "exit" => return Reply::Quit,
+            _ if usage(command).is_some() => Err(String::new()),
+            _ => return Reply::Text(format!("unknown command {command:?}; type `help`")),
+        };
+        Reply::Text(match result {
+            Ok(text) => text,
+            Err(message) => {
+                let usage = usage(command).unwrap_or_default();
+                if message.is_empty() {
+                    format!("usage: {usage}")
+                } else {
+                    format!("{message}\nusage: {usage}")
+                }
+            }
+        })
+    }
+
+    fn info(&self, args: &[&str]) -> Result<String, String> {
+        let [name] = args else {
+            return Err(String::new());
+        };
+        let network = self.current();
+        let id = resolve_neuron(network, name)?;
+        let neuron = &network.neurons[id];
+        let mut out = String::new();
+        let _ = writeln!(out, "{} (id {id})", neuron.name());
+        let _ = writeln!(out, "  type      {}", neuron.neuron_type.label());
+        let _ = writeln!(out, "  region    {}", neuron.region.label());
+        let _ = writeln!(out, "  position  {}", neuron.soma_position);
+        let _ = writeln!(
+            out,
+            "  out       {} connections, strength {}",
+            network.out_degree(id),
+            network.out_strength(id)
+        );
+        let _ = write!(
+            out,
+            "  in        {} connections, strength {}",
+            network.in_degree(id),
+            network.in_strength(id)
+        );
+        Ok(out)
+    }
+
+    fn neighbors(&self, args: &[&str]) -> Result<String, String> {
+        let (name, rest) = args.split_first().ok_or_else(String::new)?;
+        let mut direction = Direction::Out;
+        let mut class = None;
+        for &arg in rest {
+            match arg {
+                "out" => direction = Direction::Out,
+                "in" => direction = Direction::In,
+                "both" => direction = Direction::Both,
+                other => {
+                    class = Some(
+                        SynapseArg::from_str(other, true)
+                            .map_err(|_| format!("unknown direction or type {other:?}"))?,
+                    )
+                }
+            }
+        }
+
+        let network = self.current();
+        let id = resolve_neuron(network, name)?;
+        let mut rows: Vec<(&str, &str, &str, f64)> = network
+            .connections_of(id, direction)
+            .into_iter()
+            .map(|c| &network.connections[c])
+            .filter(|conn| class.is_none_or(|s: SynapseArg| s.matches(conn.synapse_type)))
+            .map(|conn| {
+                let (partner, dir) = if conn.from_id == id {
+                    (conn.to_id, "out")
+                } else {
+                    (conn.from_id, "in")
+                };
+                (
+                    network.neurons[partner].name(),
+                    dir,
+                    conn.synapse_type.label(),
+                    conn.weight,
+                )
+            })
+            .collect();
+        if rows.is_empty() {
+            return Ok(format!("{name} has no matching connections"));
+        }
+        rows.sort_by(|a, b| b.3.total_cmp(&a.3).then_with(|| a.0.cmp(b.0)));
+
+        let mut out = format!(
+            "{:<10}{:<6}{:<18}{:>8}",
+            "partner", "dir", "synapse", "weight"
+        );
+        for (partner, dir, synapse, weight) in rows {
+            let _ = write!(out, "\n{partner:<10}{dir:<6}{synapse:<18}{weight:>8}");
+        }
+        Ok(out)
+    }
+
+    fn path(&self, args: &[&str]) -> Result<String, String> {
+        let (from, to, via) = match args {
+            [from, to] => (from, to, None),
+            [from, to, via] => (from, to, Some(via)),
+            _ => return Err(String::new()),
+        };
+        let mut opts = PathOptions::default();
+        if let Some(via) = via {
+            opts.classes = via
+                .split(',')
+                .map(|s| {
+                    SynapseArg::from_str(s, true)
+                        .map(SynapseArg::class)
+                        .map_err(|_| format!("unknown synapse type {s:?}"))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        let network = self.current();
+        let from_id = resolve_neuron(network, from)?;
+        let to_id = resolve_neuron(network, to)?;
+        Ok(match network.shortest_path_with(from_id, to_id, &opts) {
+            Some(path) => arrow_line(network, &path),
+            None => format!("no path from {from} to {to}"),
+        })
+    }
+
+    fn top(&self, args: &[&str]) -> Result<String, String> {
+        let (metric, count) = match args {
+            [metric] => (*metric, 10),
+            [metric, n] => (
+                *metric,
+                n.parse::<usize>()
+                    .map_err(|_| format!("N must be a whole number, got {n:?}"))?,
+            ),
+            _ => return Err(String::new()),
+        };
+
+        let network = self.current();
+        let ids = 0..network.neurons.len();
+        let mut scores: Vec<(usize, f64)> = match metric {
+            "degree" => ids
+                .map(|id| (id, (network.out_degree(id) + network.in_degree(id)) as f64))
+                .collect(),
+            "in" => ids.map(|id| (id, network.in_degree(id) as f64)).collect(),
+            "out" => ids.map(|id| (id, network.out_degree(id) as f64)).collect(),
+            "strength" => ids
+                .map(|id| (id, network.out_strength(id) + network.in_strength(id)))
+                .collect(),
+            "betweenness" => network.node_betweenness().into_iter().collect(),
+            other => return Err(format!("unknown metric {other:?}")),
+        };
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut out = String::new();
+        for (rank, (id, score)) in scores.into_iter().take(count).enumerate() {
+            if rank > 0 {
+                out.push('\n');
+            }
+            let _ = write!(
+                out,
+                "{:>3}. {:<10}{score}",
+                rank + 1,
+                network.neurons[id].name()
+            );
+        }
+        Ok(out)
+    }
+
+    fn select_subnet(&mut self, args: &[&str]) -> Result<String, String> {
+        let [selector] = args else {
+            return Err(String::new());
+        };
+        if *selector == "all" {
+            self.subnet = None;
+            return Ok(format!(
+                "using the full network ({} neurons)",
+                self.network.neurons.len()
+            ));
+        }
+
+        let ids: Vec<usize> = if let Some(region) = Region::from_label(selector) {
+            self.network
+                .neurons
+                .iter()
+                .filter(|n| n.region == region)
+                .map(|n| n.id)
+                .collect()
+        } else {
+            selector
+                .split(',')
+                .map(|name| resolve_neuron(&self.network, name))
+                .collect::<Result<_, _>>()?
+        };
+        if ids.is_empty() {
+            return Err(format!(
+                "no neurons in region {selector:?} (region labels come from --metadata)"
+            ));
+        }
+
+        let sub = self.network.subnetwork(&ids);
+        let text = format!(
+            "subnet {selector}: {} neurons, {} connections",
+            sub.neurons.len(),
+            sub.connections.len()
+        );
+        self.subnet = Some((selector.to_string(), sub));
+        Ok(text)
+    }
+
+    fn export(&self, args: &[&str]) -> Result<String, String> {
+        let [format, file] = args else {
+            return Err(String::new());
+        };
+        let format = ExportFormat::from_name(format).ok_or_else(|| {
+            let names: Vec<&str> = ExportFormat::ALL.iter().map(|f| f.name()).collect();
+            format!(
+                "unknown format {format:?}; expected one of {}",
+                names.join(", ")
+            )
+        })?;
+        let network = self.current();
+        File::create(file)
+            .and_then(|out| network.export(format, out))
+            .map_err(|err| format!("could not write {file}: {err}"))?;
+        Ok(format!(
+            "wrote {} neurons, {} connections to {file}",
+            network.neurons.len(),
+            network.connections.len()
+        ))
+    }
+}
+
+fn usage(command: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map(|(_, usage, _)| *usage)
+}
+
+fn help(command: Option<&str>) -> String {
+    if let Some(command) = command {
+        if let Some((_, usage, summary)) = COMMANDS.iter().find(|(name, _, _)| *name == command) {
+            return format!("{usage}\n  {summary}");
+        }
+        return format!("unknown command {command:?}");
+    }
+    let width = COMMANDS
+        .iter()
+        .map(|(_, usage, _)| usage.len())
+        .max()
+        .unwrap_or(0);
+    COMMANDS
+        .iter()
+        .map(|(_, usage, summary)| format!("{usage:<width$}  {summary}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn run(args: &ReplArgs) -> CliResult {
+    let mut session = Session::new(args.load.load()?);
+    let mut editor = DefaultEditor::new()?;
+    println!(
+        "{} neurons, {} connections. Type `help` for commands.",
+        session.current().neurons.len(),
+        session.current().connections.len()
+    );
+
+    loop {
+        match editor.readline(&session.prompt()) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                match session.execute(&line) {
+                    Reply::Text(text) if text.is_empty() => {}
+                    Reply::Text(text) => println!("{text}"),
+                    Reply::Quit => break,
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(reply: Reply) -> String {
+        match reply {
+            Reply::Text(text) => text,
+            Reply::Quit => panic!("the session quit"),
+        }
+    }
+
+    /// Run `lines` in order through one session, returning each reply's text.
+    fn script(lines: &[&str]) -> Vec<String> {
+        let mut session = Session::new(Network::example_touch_circuit());
+        lines
+            .iter()
+            .map(|line| text(session.execute(line)))
+            .collect()
+    }
+
+    #[test]
+    fn scripted_commands_share_one_session() {
+        let replies = script(&[
+            "path ALML AVAL",
+            "subnet ALML,AVDL,AVAL",
+            "path ALML AVAL",
+            "subnet all",
+            "top degree 2",
+        ]);
+        assert_eq!(replies[0], "ALML -S-> AVDR -S-> AVAL");
+        assert_eq!(
+            replies[1],
+            "subnet ALML,AVDL,AVAL: 3 neurons, 6 connections"
+        );
+        assert_eq!(replies[2], "no path from ALML to AVAL");
+        assert_eq!(replies[3], "using the full network (30 neurons)");
+        assert_eq!(replies[4], "  1. AVAL      78\n  2. AVAR      64");
+    }
+
+    #[test]
+    fn info_describes_a_neuron() {
+        let info = &script(&["info AVAL"])[0];
+        assert!(info.starts_with("AVAL (id 3)\n  type      interneuron\n"));
+        assert!(info.ends_with("in        40 connections, strength 115"));
+    }
+
+    #[test]
+    fn bad_lines_answer_with_an_error_or_usage() {
+        let replies = script(&["frobnicate now", "info", "top nope", "info NOPE", ""]);
+        assert_eq!(replies[0], "unknown command \"frobnicate\"; type `help`");
+        assert_eq!(replies[1], "usage: info NEURON");
+        assert_eq!(
+            replies[2],
+            "unknown metric \"nope\"\nusage: top degree|in|out|strength|betweenness [N]"
+        );
+        assert!(replies[3].ends_with("\nusage: info NEURON"));
+        assert_eq!(replies[4], "");
+    }
+
+    #[test]
+    fn quit_and_exit_end_the_session() {
+        let mut session = Session::new(Network::example_touch_circuit());
+        assert_eq!(session.execute("quit"), Reply::Quit);
+        assert_eq!(session.execute("  exit "), Reply::Quit);
+        assert_eq!(session.prompt(), "flymind> ");
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use clap::Args;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+
+use celegans_connectome::{
+    DiagnosticsObserver, EventVerbosity, Network, SimulationConfig, StepInfo, StepObserver,
+    TimeUnit,
+};
+
+use super::{CliResult, LoadOptions};
+
+/// Exit code after Ctrl-C ended the run early (128 + SIGINT, as shells report it).
+pub const INTERRUPTED: u8 = 130;
+
+/// Run a simulation described by a TOML config and write its recordings
+#[derive(Debug, Args)]
+pub struct SimulateArgs {
+    /// Edge list in the NeuronConnect.csv format
+    #[arg(long)]
+    pub network: PathBuf,
+
+    #[command(flatten)]
+    pub load: LoadOptions,
+
+    /// Simulation config (duration, dt, model, stimuli, recorders, plasticity)
+    #[arg(long)]
+    pub config: PathBuf,
+
+    /// Directory for spikes.csv, voltages.csv, events.jsonl, config.toml and summary.json
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Write into --out even if it already exists
+    #[arg(long)]
+    pub force: bool,
+
+    /// Without a terminal, log progress every this many simulated seconds
+    #[arg(long, default_value_t = 1.0, value_parser = parse_interval)]
+    pub log_every: f64,
+
+    /// Also write the spikes as an NWB-style units table, units.nwb.json
+    #[arg(long)]
+    pub nwb: bool,
+
+    /// Skip the divergence and sanity checks run during the simulation
+    #[arg(long)]
+    pub no_diagnostics: bool,
+}
+
+/// Progress on stderr: a bar on a terminal, periodic log lines otherwise.
+/// Also runs `diagnostics`, printing their warnings as they occur, and ends the
+/// run once `stop` is set (by Ctrl-C) or a check aborts.
+struct Progress {
+    bar: Option<ProgressBar>,
+    /// Steps between bar updates, so redrawing stays off the hot path.
+    update_every: usize,
+    /// Log-line interval in the model's time unit.
+    log_every: f64,
+    next_log: f64,
+    unit: &'static str,
+    started: Instant,
+    stop: Arc<AtomicBool>,
+    diagnostics: Option<DiagnosticsObserver>,
+}
+
+impl StepObserver for Progress {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        if self.stop.load(Ordering::Relaxed) {
+            return ControlFlow::Break(());
+        }
+        if let Some(diagnostics) = &mut self.diagnostics {
+            let flow = diagnostics.observe(info, network);
+            for warning in diagnostics.take_warnings() {
+                match &self.bar {
+                    Some(bar) => bar.println(format!("warning: {warning}")),
+                    None => eprintln!("warning: {warning}"),
+                }
+            }
+            if flow.is_break() {
+                return flow;
+            }
+        }
+        let last = info.step + 1 == info.steps;
+        if let Some(bar) = &self.bar {
+            if info.step.is_multiple_of(self.update_every) || last {
+                bar.set_position(info.step as u64 + 1);
+                bar.set_message(format!(
+                    "t = {:.3}/{:.3} {}, {} spikes",
+                    info.time(),
+                    info.total_time(),
+                    self.unit,
+                    info.total_spikes
+                ));
+            }
+        } else if info.time() >= self.next_log || last {
+            let elapsed = self.started.elapsed().as_secs_f64();
+            let rate = (info.step + 1) as f64 / elapsed.max(1e-9);
+            let remaining = (info.steps - info.step - 1) as f64 / rate;
+            eprintln!(
+                "t = {:.3}/{:.3} {}, {} spikes, {:.0} steps/s, eta {:.0}s",
+                info.time(),
+                info.total_time(),
+                self.unit,
+                info.total_spikes,
+                rate,
+                remaining
+            );
+            self.next_log = ((info.time() / self.log_every).floor() + 1.0) * self.log_every;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    steps: usize,
+    dt: f64,
+    wall_time_seconds: f64,
+    total_spikes: usize,
+    /// Spikes times the chemical sends of the neuron that fired.
+    transmissions: usize,
+    /// Summed |membrane potential| * dt over neurons and steps.
+    potential_integral: f64,
+    /// Set when Ctrl-C ended the run before `duration`.
+    interrupted: bool,
+    /// Why a diagnostics check aborted the run, if one did.
+    diverged: Option<String>,
+    spike_counts: BTreeMap<String, usize>,
+    /// Depolarization blocks of the neurons that went into block at all.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    depolarization_blocks: BTreeMap<String, usize>,
+}
+
+fn parse_interval(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(seconds),
+        Ok(_) => Err(format!("{value} is not a positive number of seconds")),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+pub fn run(args: &SimulateArgs) -> CliResult {
+    let config = SimulationConfig::from_toml_file(&args.config)?;
+    if args.out.exists() && !args.force {
+        return Err(format!(
+            "{} already exists; pass --force to overwrite",
+            args.out.display()
+        )
+        .into());
+    }
+    let mut network = args.load.load(&args.network)?;
+
+    for warning in config.configure(&mut network)? {
+        eprintln!("warning: {warning}");
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&stop);
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed))?;
+
+    let steps = config.steps();
+    let unit = network.lif.time_unit;
+    let bar = io::stderr().is_terminal().then(|| {
+        let bar = ProgressBar::with_draw_target(
+            Some(steps as u64),
+            ProgressDrawTarget::stderr_with_hz(10),
+        );
+        bar.set_style(
+            ProgressStyle::with_template("{bar:30} {pos}/{len} steps  {msg}  {per_sec}  eta {eta}")
+                .expect("progress template is valid"),
+        );
+        bar
+    });
+    let log_every = TimeUnit::Seconds.convert(args.log_every, unit);
+    let mut progress = Progress {
+        bar,
+        update_every: (steps / 1000).max(1),
+        log_every,
+        next_log: log_every,
+        unit: unit.label(),
+        started: Instant::now(),
+        stop: Arc::clone(&stop),
+        diagnostics: (!args.no_diagnostics).then(DiagnosticsObserver::default),
+    };
+    let result = config.run_configured_observed(&mut network, &mut progress);
+    let wall_time = progress.started.elapsed();
+    if let Some(bar) = &progress.bar {
+        bar.finish();
+    }
+    let interrupted = stop.load(Ordering::Relaxed) && result.steps < steps;
+    let diverged = progress.diagnostics.and_then(|d| d.into_result().err());
+    if let Some(err) = &diverged {
+        eprintln!(
+            "{err}; writing partial results for {} of {steps} steps",
+            result.steps
+        );
+    } else if interrupted {
+        eprintln!(
+            "interrupted: writing partial results for {} of {steps} steps",
+            result.steps
+        );
+    }
+
+    fs::create_dir_all(&args.out)?;
+    if config.recorders.spikes {
+        result.write_spikes_csv(
+            &network,
+            BufWriter::new(File::create(args.out.join("spikes.csv"))?),
+        )?;
+    }
+    if config.recorders.voltages.is_some() {
+        result.write_voltages_csv(
+            &network,
+            BufWriter::new(File::create(args.out.join("voltages.csv"))?),
+        )?;
+    }
+    if config.recorders.events != EventVerbosity::Off {
+        result.write_events_jsonl(
+            &network,
+            BufWriter::new(File::create(args.out.join("events.jsonl"))?),
+        )?;
+    }
+    if args.nwb {
+        result.write_nwb_json(
+            &network,
+            Some(config.seed),
+            BufWriter::new(File::create(args.out.join("units.nwb.json"))?),
+        )?;
+    }
+    fs::write(args.out.join("config.toml"), config.to_toml_string()?)?;
+
+    let summary = Summary {
+        steps: result.steps,
+        dt: result.dt,
+        wall_time_seconds: wall_time.as_secs_f64(),
+        total_spikes: result.total_spikes(),
+        transmissions: result.activity.transmissions,
+        potential_integral: result.activity.potential_integral,
+        interrupted,
+        diverged: diverged.as_ref().map(ToString::to_string),
+        spike_counts: network
+            .neurons
+            .iter()
+            .map(|n| (n.name().to_string(), result.spike_count(n.id)))
+            .collect(),
+        depolarization_blocks: network
+            .neurons
+            .iter()
+            .filter(|n| result.depolarization_blocks[n.id] > 0)
+            .map(|n| (n.name().to_string(), result.depolarization_blocks[n.id]))
+            .collect(),
+    };
+    fs::write(
+        args.out.join("summary.json"),
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+
+    println!(
+        "{} steps, {} spikes in {:.3}s -> {}",
+        summary.steps,
+        summary.total_spikes,
+        summary.wall_time_seconds,
+        args.out.display()
+    );
+    if let Some(err) = diverged {
+        return Err(err.into());
+    }
+    Ok(if interrupted {
+        ExitCode::from(INTERRUPTED)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
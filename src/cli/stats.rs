@@ -0,0 +1,101 @@
+use std::process::ExitCode;
+
+use clap::Args;
+use serde::Serialize;
+
+use celegans_connectome::{NetworkStats, OutlierMethod};
+
+use super::{CliResult, GraphArg, LoadArgs};
+
+/// Print a structural summary of a connectome
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    /// Summarize only this graph; gap junctions are merged and symmetrized
+    #[arg(long, value_enum)]
+    pub graph: Option<GraphArg>,
+
+    /// Emit the summary as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Also list connections whose weight is more than this many spreads from
+    /// the typical weight of their synapse type
+    #[arg(long, value_name = "Z")]
+    pub outliers: Option<f64>,
+
+    /// Judge outliers by median and MAD instead of mean and standard deviation
+    #[arg(long, requires = "outliers")]
+    pub robust: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    stats: &'a NetworkStats,
+    weight_outliers: &'a [Outlier<'a>],
+}
+
+#[derive(Debug, Serialize)]
+struct Outlier<'a> {
+    index: usize,
+    from: &'a str,
+    to: &'a str,
+    synapse_type: &'static str,
+    weight: f64,
+}
+
+pub fn run(args: &StatsArgs) -> CliResult {
+    let mut network = args.load.load()?;
+    if let Some(graph) = args.graph {
+        network = network.graph_view(graph.kind());
+    }
+    let stats = network.stats();
+    let outliers: Option<Vec<Outlier>> = args.outliers.map(|z| {
+        let method = if args.robust {
+            OutlierMethod::MedianMad
+        } else {
+            OutlierMethod::MeanStd
+        };
+        network
+            .weight_outliers_by(z, method)
+            .into_iter()
+            .map(|index| {
+                let conn = &network.connections[index];
+                Outlier {
+                    index,
+                    from: network.neurons[conn.from_id].name(),
+                    to: network.neurons[conn.to_id].name(),
+                    synapse_type: conn.synapse_type.label(),
+                    weight: conn.weight,
+                }
+            })
+            .collect()
+    });
+
+    if args.json {
+        match &outliers {
+            Some(outliers) => {
+                let report = Report {
+                    stats: &stats,
+                    weight_outliers: outliers,
+                };
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            None => println!("{}", serde_json::to_string_pretty(&stats)?),
+        }
+    } else {
+        println!("{stats}");
+        if let Some(outliers) = &outliers {
+            println!("\nweight outliers ({})", outliers.len());
+            for o in outliers {
+                println!(
+                    "  #{:<6} {} -> {} ({}) {}",
+                    o.index, o.from, o.to, o.synapse_type, o.weight
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,352 @@
+//! Closed-loop runs: the network moves a body through a simulated world and
+//! the world's sensory input drives the network in turn.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use crate::error::FlymindError;
+use crate::network::Network;
+use crate::rng::Rng;
+use crate::simulation::{SimulationResult, StepInfo, Stimulus};
+
+/// What the body does on a step, read from the network after the step before.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MotorReadout {
+    /// Fraction of the forward command neurons that fired.
+    pub forward: f64,
+    /// Fraction of the backward command neurons that fired.
+    pub backward: f64,
+    /// Muscle activations, indexed by `MuscleId`.
+    pub muscles: Vec<f64>,
+}
+
+/// Input from the world for the next step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensoryDrive {
+    /// `(neuron id, current)` pairs, added to the network's own stimuli for one
+    /// step.
+    pub currents: Vec<(usize, f64)>,
+}
+
+/// A world the network acts in.
+pub trait Environment {
+    /// Move the world on by `dt` under `motor` and return what is sensed there.
+    fn step(&mut self, motor: &MotorReadout, dt: f64) -> SensoryDrive;
+}
+
+/// How `run_closed_loop` reads motor output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedLoopConfig {
+    pub steps: usize,
+    pub dt: f64,
+    /// `Network::find_neurons` patterns of the forward command neurons.
+    pub forward: Vec<String>,
+    /// Patterns of the backward command neurons.
+    pub backward: Vec<String>,
+    /// Neurons whose potentials are recorded, as in `run_simulation_recording`.
+    pub watch: Vec<usize>,
+}
+
+impl Default for ClosedLoopConfig {
+    fn default() -> Self {
+        Self {
+            steps: 10_000,
+            dt: 0.1,
+            forward: vec!["AVB".to_string(), "PVC".to_string()],
+            backward: vec!["AVA".to_string(), "AVD".to_string()],
+            watch: Vec::new(),
+        }
+    }
+}
+
+/// Run `network` for `config.steps` steps, alternating with `env`: before each
+/// step the environment gets the motor readout of the previous one (nothing
+/// fired before the first) and its sensory drive is injected for that step.
+///
+/// Neurons matched by both a forward and a backward pattern count for both.
+/// A command group that matches no neuron reads as 0.
+pub fn run_closed_loop(
+    network: &mut Network,
+    env: &mut dyn Environment,
+    config: &ClosedLoopConfig,
+) -> SimulationResult {
+    let group = |patterns: &[String]| {
+        let mut ids: Vec<usize> = patterns
+            .iter()
+            .flat_map(|p| network.find_neurons(p))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let forward = group(&config.forward);
+    let backward = group(&config.backward);
+    let own_stimuli = network.stimuli.len();
+    let dt = config.dt;
+
+    let result = network.run_simulation_driven(
+        config.steps,
+        dt,
+        &config.watch,
+        1,
+        &mut |_: &StepInfo, _: &Network| ControlFlow::Continue(()),
+        &mut |network: &mut Network| {
+            let fraction = |ids: &[usize]| {
+                if ids.is_empty() {
+                    return 0.0;
+                }
+                let fired = ids
+                    .iter()
+                    .filter(|&&id| network.neurons[id].just_fired)
+                    .count();
+                fired as f64 / ids.len() as f64
+            };
+            let motor = MotorReadout {
+                forward: fraction(&forward),
+                backward: fraction(&backward),
+                muscles: network.muscle_activation(),
+            };
+            let drive = env.step(&motor, dt);
+            let step = network.current_step;
+            network.stimuli.truncate(own_stimuli);
+            network.stimuli.extend(
+                drive
+                    .currents
+                    .into_iter()
+                    .map(|(neuron, current)| Stimulus::new(neuron, step, 1, current)),
+            );
+        },
+    );
+    network.stimuli.truncate(own_stimuli);
+    result
+}
+
+/// A chemosensory neuron of a `GradientWorld` agent, whose current is
+/// `concentration_gain * c + derivative_gain * dc/dt`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChemoSensor {
+    pub neuron: usize,
+    pub concentration_gain: f64,
+    pub derivative_gain: f64,
+}
+
+/// Where a `GradientWorld` agent was after a step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryPoint {
+    pub time: f64,
+    pub x: f64,
+    pub y: f64,
+    pub heading: f64,
+    pub concentration: f64,
+}
+
+/// A point agent in a plane with a Gaussian concentration peak, a toy world
+/// for chemotaxis.
+///
+/// The agent moves along its heading at `speed` times forward minus backward
+/// drive, each smoothed over `motor_tau`, so it reverses when backward wins.
+/// Its heading diffuses at `turn_noise` radians per square-root time unit,
+/// plus `reversal_turn` times the backward drive, a crude pirouette.
+#[derive(Debug, Clone)]
+pub struct GradientWorld {
+    pub source: (f64, f64),
+    /// Standard deviation of the peak; the concentration is 1 at the source.
+    pub width: f64,
+    pub speed: f64,
+    pub motor_tau: f64,
+    pub turn_noise: f64,
+    pub reversal_turn: f64,
+    pub sensors: Vec<ChemoSensor>,
+    position: (f64, f64),
+    heading: f64,
+    forward: f64,
+    backward: f64,
+    concentration: Option<f64>,
+    time: f64,
+    rng: Rng,
+    trajectory: Vec<TrajectoryPoint>,
+}
+
+impl GradientWorld {
+    /// An agent at `start` facing `heading` (radians), with a peak of width 10
+    /// at the origin.
+    pub fn new(start: (f64, f64), heading: f64, sensors: Vec<ChemoSensor>, seed: u64) -> Self {
+        Self {
+            source: (0.0, 0.0),
+            width: 10.0,
+            speed: 0.1,
+            motor_tau: 50.0,
+            turn_noise: 0.05,
+            reversal_turn: 1.0,
+            sensors,
+            position: start,
+            heading,
+            forward: 0.0,
+            backward: 0.0,
+            concentration: None,
+            time: 0.0,
+            rng: Rng::new(seed),
+            trajectory: Vec::new(),
+        }
+    }
+
+    pub fn concentration_at(&self, x: f64, y: f64) -> f64 {
+        let (dx, dy) = (x - self.source.0, y - self.source.1);
+        (-(dx * dx + dy * dy) / (2.0 * self.width * self.width)).exp()
+    }
+
+    pub fn position(&self) -> (f64, f64) {
+        self.position
+    }
+
+    pub fn distance_to_source(&self) -> f64 {
+        let (dx, dy) = (
+            self.position.0 - self.source.0,
+            self.position.1 - self.source.1,
+        );
+        dx.hypot(dy)
+    }
+
+    /// One point per step so far.
+    pub fn trajectory(&self) -> &[TrajectoryPoint] {
+        &self.trajectory
+    }
+
+    /// Write one `time,x,y,heading,concentration` row per step.
+    pub fn write_trajectory_csv<W: Write>(&self, writer: W) -> Result<(), FlymindError> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["time", "x", "y", "heading", "concentration"])?;
+        for point in &self.trajectory {
+            wtr.write_record([
+                point.time.to_string(),
+                point.x.to_string(),
+                point.y.to_string(),
+                point.heading.to_string(),
+                point.concentration.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl Environment for GradientWorld {
+    fn step(&mut self, motor: &MotorReadout, dt: f64) -> SensoryDrive {
+        let blend = (dt / self.motor_tau).min(1.0);
+        self.forward += (motor.forward - self.forward) * blend;
+        self.backward += (motor.backward - self.backward) * blend;
+
+        let velocity = self.speed * (self.forward - self.backward);
+        self.position.0 += velocity * dt * self.heading.cos();
+        self.position.1 += velocity * dt * self.heading.sin();
+        let turning = self.turn_noise + self.reversal_turn * self.backward;
+        self.heading = (self.heading + turning * dt.sqrt() * self.rng.normal())
+            .rem_euclid(std::f64::consts::TAU);
+        self.time += dt;
+
+        let concentration = self.concentration_at(self.position.0, self.position.1);
+        let derivative = self
+            .concentration
+            .map_or(0.0, |previous| (concentration - previous) / dt);
+        self.concentration = Some(concentration);
+        self.trajectory.push(TrajectoryPoint {
+            time: self.time,
+            x: self.position.0,
+            y: self.position.1,
+            heading: self.heading,
+            concentration,
+        });
+
+        SensoryDrive {
+            currents: self
+                .sensors
+                .iter()
+                .map(|sensor| {
+                    let current = sensor.concentration_gain * concentration
+                        + sensor.derivative_gain * derivative;
+                    (sensor.neuron, current)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, NeuronType, Region, SynapseType};
+
+    /// AWC excites AIB, AIB excites AVA and AVA inhibits the tonically active
+    /// AVB; without AWC's synapse nothing ever calls a reversal. The touch
+    /// fixture has no AWC, so the loop gets its own circuit.
+    fn chemotaxis_circuit(awc_intact: bool) -> Network {
+        let mut network = Network::new();
+        let mut add = |name, neuron_type| network.add_neuron(name, neuron_type, Region::Head, 0.0);
+        let awc = add("AWCL", NeuronType::Sensory);
+        let aib = add("AIBL", NeuronType::Interneuron);
+        let ava = add("AVAL", NeuronType::Interneuron);
+        let avb = add("AVBL", NeuronType::Interneuron);
+        let excitatory = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        if awc_intact {
+            network.add_connection(awc, aib, excitatory, 20.0);
+        }
+        network.add_connection(aib, ava, excitatory, 20.0);
+        network.add_connection(
+            ava,
+            avb,
+            SynapseType::ChemicalSend(ChemicalSubtype::Inhibitory),
+            20.0,
+        );
+        network.bias_currents.insert(avb, 0.15);
+        network
+    }
+
+    /// Final distance to the peak, and the world, of an agent starting 30 from
+    /// it; AWC is an OFF cell, driven by a falling concentration.
+    fn chemotaxis_run(awc_intact: bool, seed: u64) -> (f64, GradientWorld) {
+        let mut network = chemotaxis_circuit(awc_intact);
+        let awc = network.id_of("AWCL").unwrap();
+        let sensors = vec![ChemoSensor {
+            neuron: awc,
+            concentration_gain: 0.0,
+            derivative_gain: -1000.0,
+        }];
+        let mut world = GradientWorld::new((30.0, 0.0), seed as f64 * 0.8, sensors, seed);
+        world.width = 20.0;
+        world.speed = 10.0;
+        let config = ClosedLoopConfig {
+            steps: 20_000,
+            forward: vec!["AVB".to_string()],
+            backward: vec!["AVA".to_string()],
+            ..ClosedLoopConfig::default()
+        };
+        run_closed_loop(&mut network, &mut world, &config);
+        (world.distance_to_source(), world)
+    }
+
+    #[test]
+    fn ablating_awc_loses_the_gradient() {
+        let mean_distance = |awc_intact| {
+            (0..4)
+                .map(|seed| chemotaxis_run(awc_intact, seed).0)
+                .sum::<f64>()
+                / 4.0
+        };
+        let intact = mean_distance(true);
+        let ablated = mean_distance(false);
+        assert!(intact < 30.0, "intact agents ended {intact} from the peak");
+        assert!(intact < ablated, "intact {intact}, ablated {ablated}");
+    }
+
+    #[test]
+    fn trajectory_csv_has_a_row_per_step() {
+        let (_, world) = chemotaxis_run(true, 0);
+        let mut out = Vec::new();
+        world.write_trajectory_csv(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().next(), Some("time,x,y,heading,concentration"));
+        assert_eq!(text.lines().count(), 20_001);
+        let last = world.trajectory().last().unwrap();
+        assert!((last.time - 2000.0).abs() < 1e-6);
+    }
+}
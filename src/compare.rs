@@ -0,0 +1,411 @@
+//! Comparing two reconstructions of the same connectome, e.g. different
+//! datasets or versions of one.
+//!
+//! Neurons are aligned by name. Edges are `(from, to, synapse)` triples with
+//! the weights of parallel rows summed; `ChemicalReceive` rows are left out
+//! since they repeat the sends. Only edges between neurons present in both
+//! networks are compared, so a missing neuron shows up in the neuron lists
+//! rather than as a burst of lost edges.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::functional::{pearson, spearman};
+use crate::network::{Network, SynapseType};
+
+/// Rich-club hubs of the hermaphrodite connectome (Towlson et al., 2013).
+pub const HUB_NEURONS: [&str; 11] = [
+    "AVAL", "AVAR", "AVBL", "AVBR", "AVDL", "AVDR", "AVEL", "AVER", "PVCL", "PVCR", "DVA",
+];
+
+/// Rows of each table shown by the `Display` report.
+const REPORT_ROWS: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ComparisonReport {
+    pub shared_neurons: usize,
+    pub only_in_first: Vec<String>,
+    pub only_in_second: Vec<String>,
+    /// Edge changes per synapse label.
+    pub edges: BTreeMap<String, EdgeChanges>,
+    /// Correlation of the weights of edges present in both; NaN when undefined.
+    pub weight_pearson: f64,
+    pub weight_spearman: f64,
+    /// Shared neurons whose degree changed, largest total change first.
+    pub degree_changes: Vec<DegreeChange>,
+    /// `HUB_NEURONS` present in both, largest betweenness rank change first.
+    pub hub_ranks: Vec<HubRank>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EdgeChanges {
+    pub shared: usize,
+    /// Only in the second network.
+    pub gained: usize,
+    /// Only in the first network.
+    pub lost: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DegreeChange {
+    pub neuron: String,
+    pub in_before: usize,
+    pub in_after: usize,
+    pub out_before: usize,
+    pub out_after: usize,
+}
+
+impl DegreeChange {
+    pub fn total_change(&self) -> usize {
+        self.in_before.abs_diff(self.in_after) + self.out_before.abs_diff(self.out_after)
+    }
+}
+
+/// 1-based rank by node betweenness (highest first) in each network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HubRank {
+    pub neuron: String,
+    pub rank_before: usize,
+    pub rank_after: usize,
+}
+
+type EdgeKey<'a> = (&'a str, &'a str, &'static str);
+
+impl Network {
+    /// Compare this network (the "before") with `other` (the "after").
+    pub fn compare(&self, other: &Network) -> ComparisonReport {
+        let names = |network: &Network| -> BTreeSet<String> {
+            network
+                .neurons
+                .iter()
+                .map(|n| n.name().to_string())
+                .collect()
+        };
+        let (first, second) = (names(self), names(other));
+        let shared: BTreeSet<&str> = first.intersection(&second).map(String::as_str).collect();
+
+        let mut report = ComparisonReport {
+            shared_neurons: shared.len(),
+            only_in_first: first.difference(&second).cloned().collect(),
+            only_in_second: second.difference(&first).cloned().collect(),
+            ..ComparisonReport::default()
+        };
+
+        let before = self.edge_weights(&shared);
+        let after = other.edge_weights(&shared);
+        let (mut xs, mut ys) = (Vec::new(), Vec::new());
+        for (key, &weight) in &before {
+            let changes = report.edges.entry(key.2.to_string()).or_default();
+            match after.get(key) {
+                Some(&other_weight) => {
+                    changes.shared += 1;
+                    xs.push(weight);
+                    ys.push(other_weight);
+                }
+                None => changes.lost += 1,
+            }
+        }
+        for key in after.keys().filter(|key| !before.contains_key(key)) {
+            report.edges.entry(key.2.to_string()).or_default().gained += 1;
+        }
+        report.weight_pearson = pearson(&xs, &ys);
+        report.weight_spearman = spearman(&xs, &ys);
+
+        for &name in &shared {
+            let (Some(a), Some(b)) = (self.id_of(name), other.id_of(name)) else {
+                continue;
+            };
+            let change = DegreeChange {
+                neuron: name.to_string(),
+                in_before: self.in_degree(a),
+                in_after: other.in_degree(b),
+                out_before: self.out_degree(a),
+                out_after: other.out_degree(b),
+            };
+            if change.total_change() > 0 {
+                report.degree_changes.push(change);
+            }
+        }
+        report
+            .degree_changes
+            .sort_by_key(|change| Reverse(change.total_change()));
+
+        let (ranks_before, ranks_after) = (self.betweenness_ranks(), other.betweenness_ranks());
+        report.hub_ranks = HUB_NEURONS
+            .iter()
+            .filter_map(|&hub| {
+                Some(HubRank {
+                    neuron: hub.to_string(),
+                    rank_before: *ranks_before.get(hub)?,
+                    rank_after: *ranks_after.get(hub)?,
+                })
+            })
+            .collect();
+        report
+            .hub_ranks
+            .sort_by_key(|hub| Reverse(hub.rank_before.abs_diff(hub.rank_after)));
+        report
+    }
+
+    /// A hash of the topology that does not depend on ids or row order: the
+    /// neuron names and, for every connection, its endpoints by name, synapse
+    /// type, subtype (or extrasynaptic transmitter) and weight, each sorted
+    /// before hashing. Parallel rows count separately. Networks built
+    /// differently but with the same neurons and rows hash equal; metadata,
+    /// annotations and simulation settings are left out.
+    ///
+    /// The hash is FNV-1a over a fixed encoding, so it is the same across
+    /// runs, platforms and compiler versions.
+    pub fn structural_hash(&self) -> u64 {
+        let mut names: Vec<&str> = self.neurons.iter().map(|n| n.name()).collect();
+        names.sort_unstable();
+        let mut rows: Vec<(&str, &str, &str, &str, u64)> = self
+            .connections
+            .iter()
+            .map(|conn| {
+                // -0.0 and 0.0 are the same weight.
+                let weight = if conn.weight == 0.0 { 0.0 } else { conn.weight };
+                (
+                    self.neurons[conn.from_id].name(),
+                    self.neurons[conn.to_id].name(),
+                    conn.synapse_type.label(),
+                    match conn.synapse_type {
+                        SynapseType::Extrasynaptic(modulator) => modulator.label(),
+                        other => other.subtype().map_or("", |s| s.label()),
+                    },
+                    weight.to_bits(),
+                )
+            })
+            .collect();
+        rows.sort_unstable();
+
+        let mut hash = Fnv1a::default();
+        hash.write(&(names.len() as u64).to_le_bytes());
+        for name in names {
+            hash.write_str(name);
+        }
+        hash.write(&(rows.len() as u64).to_le_bytes());
+        for (from, to, synapse, subtype, weight) in rows {
+            for text in [from, to, synapse, subtype] {
+                hash.write_str(text);
+            }
+            hash.write(&weight.to_le_bytes());
+        }
+        hash.0
+    }
+
+    /// Summed weight of each edge between two `keep` neurons.
+    fn edge_weights(&self, keep: &BTreeSet<&str>) -> BTreeMap<EdgeKey<'_>, f64> {
+        let mut weights = BTreeMap::new();
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) || !self.counts(conn) {
+                continue;
+            }
+            let from = self.neurons[conn.from_id].name();
+            let to = self.neurons[conn.to_id].name();
+            if keep.contains(from) && keep.contains(to) {
+                *weights
+                    .entry((from, to, conn.synapse_type.label()))
+                    .or_default() += conn.weight;
+            }
+        }
+        weights
+    }
+
+    fn betweenness_ranks(&self) -> HashMap<&str, usize> {
+        let betweenness = self.node_betweenness();
+        let mut order: Vec<usize> = (0..self.neurons.len()).collect();
+        order.sort_by(|&a, &b| {
+            betweenness[&b]
+                .total_cmp(&betweenness[&a])
+                .then_with(|| self.neurons[a].name().cmp(self.neurons[b].name()))
+        });
+        order
+            .into_iter()
+            .enumerate()
+            .map(|(rank, id)| (self.neurons[id].name(), rank + 1))
+            .collect()
+    }
+}
+
+/// Markdown.
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Connectome comparison\n")?;
+        writeln!(f, "## Neurons\n")?;
+        writeln!(f, "- shared: {}", self.shared_neurons)?;
+        for (label, names) in [
+            ("only in first", &self.only_in_first),
+            ("only in second", &self.only_in_second),
+        ] {
+            if names.is_empty() {
+                writeln!(f, "- {label}: none")?;
+            } else {
+                writeln!(f, "- {label} ({}): {}", names.len(), names.join(", "))?;
+            }
+        }
+
+        writeln!(f, "\n## Edges between shared neurons\n")?;
+        writeln!(f, "| synapse | shared | gained | lost |")?;
+        writeln!(f, "|---|---:|---:|---:|")?;
+        for (label, changes) in &self.edges {
+            writeln!(
+                f,
+                "| {label} | {} | {} | {} |",
+                changes.shared, changes.gained, changes.lost
+            )?;
+        }
+        writeln!(
+            f,
+            "\nWeight correlation of shared edges: Pearson {:.3}, Spearman {:.3}",
+            self.weight_pearson, self.weight_spearman
+        )?;
+
+        writeln!(f, "\n## Largest degree changes\n")?;
+        if self.degree_changes.is_empty() {
+            writeln!(f, "No shared neuron changed degree.")?;
+        } else {
+            writeln!(f, "| neuron | in | out |")?;
+            writeln!(f, "|---|---|---|")?;
+            for change in self.degree_changes.iter().take(REPORT_ROWS) {
+                writeln!(
+                    f,
+                    "| {} | {} → {} | {} → {} |",
+                    change.neuron,
+                    change.in_before,
+                    change.in_after,
+                    change.out_before,
+                    change.out_after
+                )?;
+            }
+        }
+
+        writeln!(f, "\n## Hub betweenness ranks\n")?;
+        writeln!(f, "| neuron | before | after |")?;
+        writeln!(f, "|---|---:|---:|")?;
+        for hub in &self.hub_ranks {
+            writeln!(
+                f,
+                "| {} | {} | {} |",
+                hub.neuron, hub.rank_before, hub.rank_after
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a, for hashes that must not change with the standard library.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Length-prefixed, so consecutive strings cannot run together.
+    fn write_str(&mut self, text: &str) {
+        self.write(&(text.len() as u64).to_le_bytes());
+        self.write(text.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The touch circuit without its one PVM -> AVM send, and with the
+    /// ALML -> AVDR send five times heavier.
+    fn perturbed() -> Network {
+        let mut network = Network::example_touch_circuit();
+        let id = |name: &str| network.id_of(name).unwrap();
+        let (pvm, avm, alml, avdr) = (id("PVM"), id("AVM"), id("ALML"), id("AVDR"));
+        let send = |from: usize, to: usize| {
+            move |conn: &crate::network::Connection| {
+                conn.from_id == from
+                    && conn.to_id == to
+                    && matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+            }
+        };
+        let reweighted = network
+            .connections
+            .iter()
+            .position(send(alml, avdr))
+            .unwrap();
+        network.connections[reweighted].weight *= 5.0;
+        let removed = send(pvm, avm);
+        assert_eq!(network.retain_connections(|conn| !removed(conn)), 1);
+        network
+    }
+
+    #[test]
+    fn compare_reports_the_removed_edge_and_its_degrees() {
+        let report = Network::example_touch_circuit().compare(&perturbed());
+        assert_eq!(report.shared_neurons, 30);
+        assert!(report.only_in_first.is_empty() && report.only_in_second.is_empty());
+        // The reweighted send stays shared; only the removed one is lost.
+        assert_eq!(
+            report.edges["chemical_send"],
+            EdgeChanges {
+                shared: 121,
+                gained: 0,
+                lost: 1
+            }
+        );
+        assert_eq!(
+            report.edges["gap_junction"],
+            EdgeChanges {
+                shared: 72,
+                gained: 0,
+                lost: 0
+            }
+        );
+        assert!(report.weight_pearson < 1.0 && report.weight_spearman < 1.0);
+
+        let change = |neuron: &str, degrees: [usize; 4]| DegreeChange {
+            neuron: neuron.to_string(),
+            in_before: degrees[0],
+            in_after: degrees[1],
+            out_before: degrees[2],
+            out_after: degrees[3],
+        };
+        assert_eq!(
+            report.degree_changes,
+            vec![change("AVM", [12, 11, 12, 12]), change("PVM", [2, 2, 2, 1])]
+        );
+
+        let rank = |neuron: &str, rank_before: usize, rank_after: usize| HubRank {
+            neuron: neuron.to_string(),
+            rank_before,
+            rank_after,
+        };
+        assert_eq!(
+            report.hub_ranks,
+            vec![
+                rank("PVCL", 5, 3),
+                rank("AVBR", 3, 4),
+                rank("AVDL", 4, 5),
+                rank("AVDR", 16, 15),
+                rank("AVAL", 1, 1),
+                rank("AVAR", 2, 2),
+                rank("AVBL", 7, 7),
+                rank("PVCR", 8, 8),
+            ]
+        );
+    }
+}
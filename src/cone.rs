@@ -0,0 +1,254 @@
+//! Downstream and upstream reachability: every neuron a set of sources can
+//! reach, or be reached from, under `PathOptions`.
+//!
+//! `max_hops` bounds the search depth. Upstream searches follow the same
+//! steps as `Network::traversal_steps`, reversed, so gap junctions count both
+//! ways in either direction.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use crate::error::FlymindError;
+use crate::graph::Direction;
+use crate::network::Network;
+use crate::paths::{PathOptions, Step, SynapseClass};
+
+/// The neurons reached from `sources`, each with the hop count at which it
+/// was first reached. Sources themselves are left out, even when one
+/// reaches another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InfluenceCone {
+    /// Ascending ids.
+    pub sources: Vec<usize>,
+    /// Neuron id to depth; with `Direction::Both`, the smaller of the
+    /// downstream and upstream depths.
+    pub depths: BTreeMap<usize, usize>,
+}
+
+impl InfluenceCone {
+    pub fn len(&self) -> usize {
+        self.depths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depths.is_empty()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.depths.contains_key(&id)
+    }
+
+    /// Names of the reached neurons, sorted.
+    pub fn names(&self, network: &Network) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .depths
+            .keys()
+            .map(|&id| network.neurons[id].name().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// The reached neurons, sources first, as a standalone network.
+    pub fn subnetwork(&self, network: &Network) -> Network {
+        let mut ids = self.sources.clone();
+        ids.extend(self.depths.keys());
+        network.subnetwork(&ids)
+    }
+}
+
+impl Network {
+    /// Every neuron reachable from `id` under `opts`, excluding `id` itself.
+    pub fn descendants(&self, id: usize, opts: &PathOptions) -> HashSet<usize> {
+        let steps = self.traversal_steps(opts);
+        reach(&steps, &[id], opts.max_hops).into_keys().collect()
+    }
+
+    /// Every neuron that reaches `id` under `opts`, excluding `id` itself.
+    pub fn ancestors(&self, id: usize, opts: &PathOptions) -> HashSet<usize> {
+        let steps = reversed(&self.traversal_steps(opts));
+        reach(&steps, &[id], opts.max_hops).into_keys().collect()
+    }
+
+    /// Downstream (`Out`), upstream (`In`) or both cones of the named neurons,
+    /// searched together so each neuron's depth counts from the nearest source.
+    pub fn influence_cone(
+        &self,
+        names: &[&str],
+        direction: Direction,
+        opts: &PathOptions,
+    ) -> Result<InfluenceCone, FlymindError> {
+        let mut sources = names
+            .iter()
+            .map(|&name| {
+                self.id_of(name).ok_or_else(|| FlymindError::UnknownNeuron {
+                    name: name.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        sources.sort_unstable();
+        sources.dedup();
+
+        let steps = self.traversal_steps(opts);
+        let depths = match direction {
+            Direction::Out => reach(&steps, &sources, opts.max_hops),
+            Direction::In => reach(&reversed(&steps), &sources, opts.max_hops),
+            Direction::Both => {
+                let mut depths = reach(&steps, &sources, opts.max_hops);
+                for (id, depth) in reach(&reversed(&steps), &sources, opts.max_hops) {
+                    depths
+                        .entry(id)
+                        .and_modify(|d| *d = (*d).min(depth))
+                        .or_insert(depth);
+                }
+                depths
+            }
+        };
+        Ok(InfluenceCone { sources, depths })
+    }
+
+    /// `id` and every neuron within `k` hops of it over chemical connections
+    /// and gap junctions, e.g. to pull out a local circuit with `subnetwork`.
+    /// Chemical connections are followed forward (`Out`), backward (`In`) or
+    /// either way at each hop (`Both`); gap junctions, being undirected, are
+    /// followed either way in all three. Empty for an unknown `id`.
+    pub fn neighborhood(&self, id: usize, k: usize, direction: Direction) -> HashSet<usize> {
+        if id >= self.neurons.len() {
+            return HashSet::new();
+        }
+        let opts = PathOptions {
+            classes: vec![SynapseClass::Chemical, SynapseClass::GapJunction],
+            ..PathOptions::default()
+        };
+        let forward = self.traversal_steps(&opts);
+        let steps = match direction {
+            Direction::Out => forward,
+            Direction::In => reversed(&forward),
+            Direction::Both => {
+                let mut both = reversed(&forward);
+                for (list, out) in both.iter_mut().zip(forward) {
+                    list.extend(out);
+                }
+                both
+            }
+        };
+        let mut neighborhood: HashSet<usize> = reach(&steps, &[id], Some(k)).into_keys().collect();
+        neighborhood.insert(id);
+        neighborhood
+    }
+}
+
+fn reversed(steps: &[Vec<Step>]) -> Vec<Vec<Step>> {
+    let mut reversed = vec![Vec::new(); steps.len()];
+    for (from, list) in steps.iter().enumerate() {
+        for &(to, conn) in list {
+            reversed[to].push((from, conn));
+        }
+    }
+    reversed
+}
+
+/// Breadth-first depths from `sources`, which are left out of the result.
+/// Out-of-range sources are ignored.
+fn reach(
+    steps: &[Vec<Step>],
+    sources: &[usize],
+    max_hops: Option<usize>,
+) -> BTreeMap<usize, usize> {
+    let max_hops = max_hops.unwrap_or(usize::MAX);
+    let mut depth = vec![usize::MAX; steps.len()];
+    let mut queue = VecDeque::new();
+    for &source in sources.iter().filter(|&&s| s < steps.len()) {
+        depth[source] = 0;
+        queue.push_back(source);
+    }
+    let mut reached = BTreeMap::new();
+    while let Some(current) = queue.pop_front() {
+        if depth[current] >= max_hops {
+            continue;
+        }
+        for &(next, _) in &steps[current] {
+            if depth[next] == usize::MAX {
+                depth[next] = depth[current] + 1;
+                reached.insert(next, depth[next]);
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, SynapseType};
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    /// A -> B -> C -> D, with a light branch B -> E and a gap junction C - F.
+    fn network() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C", "D", "E", "F"]);
+        for (from, to, weight) in [(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (1, 4, 0.2)] {
+            network.add_connection(from, to, EXCITATORY, weight);
+        }
+        network.add_connection(2, 5, SynapseType::GapJunction, 1.0);
+        network
+    }
+
+    fn depths(pairs: &[(usize, usize)]) -> BTreeMap<usize, usize> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn cones_record_the_depth_each_neuron_is_reached_at() {
+        let network = network();
+        let opts = PathOptions::default();
+        let down = network
+            .influence_cone(&["A"], Direction::Out, &opts)
+            .unwrap();
+        assert_eq!(down.sources, vec![0]);
+        assert_eq!(down.depths, depths(&[(1, 1), (2, 2), (3, 3), (4, 2)]));
+        assert!(!down.contains(0) && !down.contains(5));
+        assert_eq!(down.names(&network), ["B", "C", "D", "E"]);
+
+        let up = network
+            .influence_cone(&["D"], Direction::In, &opts)
+            .unwrap();
+        assert_eq!(up.depths, depths(&[(0, 3), (1, 2), (2, 1)]));
+        let both = network
+            .influence_cone(&["C", "C"], Direction::Both, &opts)
+            .unwrap();
+        assert_eq!(both.sources, vec![2]);
+        assert_eq!(both.depths, depths(&[(0, 2), (1, 1), (3, 1)]));
+
+        let near = PathOptions {
+            max_hops: Some(2),
+            ..PathOptions::default()
+        };
+        let cone = network.influence_cone(&["A"], Direction::Out, &near);
+        assert_eq!(cone.unwrap().depths, depths(&[(1, 1), (2, 2), (4, 2)]));
+        assert!(network
+            .influence_cone(&["Z"], Direction::Out, &opts)
+            .is_err());
+    }
+
+    #[test]
+    fn the_weight_threshold_prunes_the_light_branch() {
+        let network = network();
+        let heavy = PathOptions {
+            min_weight: 0.5,
+            ..PathOptions::default()
+        };
+        let cone = network
+            .influence_cone(&["A"], Direction::Out, &heavy)
+            .unwrap();
+        assert_eq!(cone.depths, depths(&[(1, 1), (2, 2), (3, 3)]));
+        assert_eq!(network.descendants(1, &heavy), HashSet::from([2, 3]));
+
+        let wired = PathOptions {
+            classes: vec![SynapseClass::Chemical, SynapseClass::GapJunction],
+            ..heavy
+        };
+        assert_eq!(network.descendants(1, &wired), HashSet::from([2, 3, 5]));
+        assert_eq!(network.ancestors(5, &wired), HashSet::from([0, 1, 2]));
+    }
+}
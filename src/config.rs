@@ -0,0 +1,817 @@
+//! TOML description of a simulation run, as read by `flymind simulate`.
+//!
+//! Times (`duration`, `dt`, stimulus `start`/`duration`/`period`/`width`) share
+//! one unit, the same one as `tau`: `model.params.time_unit`, milliseconds unless
+//! set to `"s"`. A minimal config:
+//!
+//! ```toml
+//! duration = 100.0
+//! dt = 0.1
+//!
+//! [[stimulus]]
+//! neuron = "ALML"
+//! start = 10.0
+//! duration = 50.0
+//! amplitude = 2.0
+//! ```
+//!
+//! A stimulus may name its targets with `select`, a `Selection` expression,
+//! instead of `neuron`; every matching neuron then gets the same stimulus:
+//!
+//! ```toml
+//! [[stimulus]]
+//! select = "type:sensory & region:tail"
+//! start = 10.0
+//! duration = 50.0
+//! amplitude = 2.0
+//! ```
+//!
+//! Constant currents for the whole run go under `[[model.bias]]`, each with a
+//! `neuron` (or `select`) and a `current`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::ControlFlow;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+
+use crate::error::FlymindError;
+use crate::events::EventVerbosity;
+use crate::muscle::NmjParams;
+use crate::network::Network;
+use crate::paths::SynapseClass;
+use crate::propagation::Propagation;
+use crate::select::Selection;
+use crate::simulation::{
+    DepolarizationBlock, InputNormalization, LifParams, SimulationResult, StdpParams, StepInfo,
+    StepObserver, Stimulus, StimulusShape, SynapticInput, ThresholdCrossing,
+};
+use crate::spikes::SpikeStorage;
+use crate::synapses::{ConnectionParams, PairOverride, SynapseOverride, SynapseParams};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub duration: Spanned<f64>,
+    pub dt: Spanned<f64>,
+    /// Seed for the noise generator.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub model: ModelConfig,
+    #[serde(default)]
+    pub stimulus: Vec<StimulusConfig>,
+    #[serde(default)]
+    pub recorders: RecorderConfig,
+    #[serde(default)]
+    pub plasticity: PlasticityConfig,
+    #[serde(default)]
+    pub connections: ConnectionsConfig,
+    #[serde(default)]
+    pub muscles: MuscleConfig,
+    /// Text the config was parsed from, used to turn spans into line numbers.
+    #[serde(skip)]
+    source: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelKind {
+    #[default]
+    Lif,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub kind: ModelKind,
+    /// Parameters for every neuron without an override.
+    pub params: LifParams,
+    pub overrides: Vec<NeuronOverride>,
+    /// Constant currents, as `Network::bias_currents`.
+    pub bias: Vec<BiasConfig>,
+    /// `"dense"` or `"event"`, as `Network::propagation`.
+    pub propagation: Propagation,
+    /// `"spikes"`, or `{ rate = { tau = ... } }` to couple neurons through
+    /// their filtered firing rates, as `Network::synaptic_input`.
+    pub input: SynapticInput,
+}
+
+/// A constant current into the neuron `neuron` names or every neuron `select`
+/// matches; currents given to one neuron twice add up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BiasConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neuron: Option<Spanned<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub select: Option<Spanned<String>>,
+    pub current: Spanned<f64>,
+}
+
+/// Replacement parameters for one neuron; unset fields keep the global value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NeuronOverride {
+    pub neuron: Spanned<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resting_potential: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold_crossing: Option<ThresholdCrossing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset_potential: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tau: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refractory_steps: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chemical_gain: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_gain: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_std: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_normalization: Option<InputNormalization>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_inputs: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depolarization_block: Option<DepolarizationBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modulation_gain: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modulation_tau: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extrasynaptic_gain: Option<f64>,
+}
+
+impl NeuronOverride {
+    pub fn apply(&self, base: LifParams) -> LifParams {
+        LifParams {
+            resting_potential: self.resting_potential.unwrap_or(base.resting_potential),
+            threshold: self.threshold.unwrap_or(base.threshold),
+            threshold_crossing: self.threshold_crossing.unwrap_or(base.threshold_crossing),
+            reset_potential: self.reset_potential.unwrap_or(base.reset_potential),
+            tau: self.tau.unwrap_or(base.tau),
+            refractory_steps: self.refractory_steps.unwrap_or(base.refractory_steps),
+            chemical_gain: self.chemical_gain.unwrap_or(base.chemical_gain),
+            gap_gain: self.gap_gain.unwrap_or(base.gap_gain),
+            noise_std: self.noise_std.unwrap_or(base.noise_std),
+            time_unit: base.time_unit,
+            integration: base.integration,
+            input_normalization: self.input_normalization.unwrap_or(base.input_normalization),
+            max_inputs: self.max_inputs.or(base.max_inputs),
+            depolarization_block: self.depolarization_block.or(base.depolarization_block),
+            modulation_gain: self.modulation_gain.unwrap_or(base.modulation_gain),
+            modulation_tau: self.modulation_tau.unwrap_or(base.modulation_tau),
+            extrasynaptic_gain: self.extrasynaptic_gain.unwrap_or(base.extrasynaptic_gain),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StimulusKind {
+    #[default]
+    Step,
+    /// Needs `period` and `width`.
+    Pulse,
+    /// Needs `end_amplitude`.
+    Ramp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StimulusConfig {
+    /// Exactly one of `neuron` and `select` names the targets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neuron: Option<Spanned<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub select: Option<Spanned<String>>,
+    #[serde(default)]
+    pub kind: StimulusKind,
+    #[serde(default)]
+    pub start: f64,
+    pub duration: Spanned<f64>,
+    pub amplitude: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<Spanned<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<Spanned<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_amplitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    pub spikes: bool,
+    /// `"bitset"` keeps spike trains at one bit per step, for long dense runs.
+    pub spike_storage: SpikeStorage,
+    /// `"spikes"` or `"inputs"` turns on the event log.
+    pub events: EventVerbosity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voltages: Option<VoltageRecorderConfig>,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            spikes: true,
+            spike_storage: SpikeStorage::Steps,
+            events: EventVerbosity::Off,
+            voltages: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VoltageRecorderConfig {
+    pub neurons: Vec<Spanned<String>>,
+    /// Keep one sample every `stride` steps.
+    #[serde(default = "default_stride")]
+    pub stride: Spanned<usize>,
+}
+
+fn default_stride() -> Spanned<usize> {
+    Spanned::new(0..0, 1)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlasticityConfig {
+    pub enabled: bool,
+    pub stdp: StdpParams,
+}
+
+/// Synaptic parameters, as `crate::synapses` resolves them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionsConfig {
+    /// Parameters of every connection before any override.
+    pub params: SynapseParams,
+    pub overrides: Vec<ConnectionOverride>,
+}
+
+/// Muscle readout: `tau` as `Network::muscle_tau`, `nmj` as `Network::nmj`.
+/// Both time constants are in `model.params.time_unit`. Either left out
+/// keeps the network's own.
+///
+/// ```toml
+/// [muscles]
+/// tau = 20.0
+///
+/// [muscles.nmj]
+/// gain = 0.5
+/// tau = 5.0
+/// ```
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MuscleConfig {
+    pub tau: Option<f64>,
+    pub nmj: Option<NmjParams>,
+}
+
+/// Replacement parameters for the connections chosen by exactly one of
+/// `synapse` (`"chem"`, `"gap"`, `"nmj"` or `"extrasynaptic"`), `from`/`to` (selections, either
+/// may be left out) and `connection` (an index). Unset fields keep the value
+/// of the layer below, whatever order the entries come in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synapse: Option<Spanned<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Spanned<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Spanned<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection: Option<Spanned<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_probability: Option<f64>,
+}
+
+impl ConnectionOverride {
+    pub fn params(&self) -> SynapseOverride {
+        SynapseOverride {
+            gain: self.gain,
+            w_min: self.w_min,
+            w_max: self.w_max,
+            release_probability: self.release_probability,
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn from_toml_str(source: &str) -> Result<Self, FlymindError> {
+        let mut config: SimulationConfig = toml::from_str(source).map_err(|err| {
+            let message = err.message().to_string();
+            let span = err.span().unwrap_or(0..0);
+            let key = match backticked(&message, "missing field") {
+                Some(field) => match table_at(source, span.start) {
+                    Some(table) => format!("{table}.{field}"),
+                    None => field,
+                },
+                None => key_at(source, span.start),
+            };
+            FlymindError::Config {
+                key,
+                line: err.span().map(|span| line_of(source, span.start)),
+                message,
+            }
+        })?;
+        config.source = source.to_string();
+        Ok(config)
+    }
+
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Self, FlymindError> {
+        Self::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    /// Parse the same structure written as JSON (`null` is not allowed). Errors
+    /// name the key but carry no line.
+    pub fn from_json_str(source: &str) -> Result<Self, FlymindError> {
+        let unlocated = |message: String| FlymindError::Config {
+            key: String::new(),
+            line: None,
+            message,
+        };
+        let value: toml::Table =
+            serde_json::from_str(source).map_err(|err| unlocated(err.to_string()))?;
+        let text = toml::to_string(&value).map_err(|err| unlocated(err.to_string()))?;
+        let mut config = Self::from_toml_str(&text).map_err(|err| match err {
+            FlymindError::Config { key, message, .. } => FlymindError::Config {
+                key,
+                line: None,
+                message,
+            },
+            other => other,
+        })?;
+        config.source.clear();
+        Ok(config)
+    }
+
+    /// The config with every default filled in.
+    pub fn to_toml_string(&self) -> Result<String, FlymindError> {
+        toml::to_string(self).map_err(|err| FlymindError::Config {
+            key: String::new(),
+            line: None,
+            message: err.to_string(),
+        })
+    }
+
+    pub fn steps(&self) -> usize {
+        to_steps(*self.duration.get_ref(), *self.dt.get_ref())
+    }
+
+    /// Check the config against `network` and load its parameters, stimuli and
+    /// plasticity settings into it, resetting the simulation state. Returns
+    /// warnings from `Network::check_dt`.
+    pub fn configure(&self, network: &mut Network) -> Result<Vec<String>, FlymindError> {
+        network.check_integrity()?;
+        let dt = *self.dt.get_ref();
+        if !(dt.is_finite() && dt > 0.0) {
+            return Err(self.invalid("dt", &self.dt, "must be a positive number"));
+        }
+        let duration = *self.duration.get_ref();
+        if !(duration.is_finite() && duration >= 0.0) {
+            return Err(self.invalid("duration", &self.duration, "must be zero or more"));
+        }
+
+        let mut overrides = HashMap::new();
+        for (i, entry) in self.model.overrides.iter().enumerate() {
+            let key = format!("model.overrides[{i}].neuron");
+            let id = self.neuron_id(network, &key, &entry.neuron)?;
+            overrides.insert(id, entry.apply(self.model.params));
+        }
+
+        let mut bias_currents = HashMap::new();
+        for (i, entry) in self.model.bias.iter().enumerate() {
+            let key = |field: &str| format!("model.bias[{i}].{field}");
+            if !entry.current.get_ref().is_finite() {
+                return Err(self.invalid(&key("current"), &entry.current, "must be finite"));
+            }
+            let (targets, _) =
+                self.targets(network, &key, &entry.neuron, &entry.select, &entry.current)?;
+            for id in targets {
+                *bias_currents.entry(id).or_default() += *entry.current.get_ref();
+            }
+        }
+        let connection_params = self.connection_params(network)?;
+
+        let mut stimuli = Vec::with_capacity(self.stimulus.len());
+        for (i, entry) in self.stimulus.iter().enumerate() {
+            let key = |field: &str| format!("stimulus[{i}].{field}");
+            let (targets, anchor) =
+                self.targets(network, &key, &entry.neuron, &entry.select, &entry.duration)?;
+            let shape = match entry.kind {
+                StimulusKind::Step => StimulusShape::Step,
+                StimulusKind::Pulse => {
+                    let period = self.required_time(&entry.period, &key("period"), anchor)?;
+                    let width = self.required_time(&entry.width, &key("width"), anchor)?;
+                    StimulusShape::Pulse {
+                        period_steps: to_steps(period, dt).max(1),
+                        width_steps: to_steps(width, dt),
+                    }
+                }
+                StimulusKind::Ramp => match entry.end_amplitude {
+                    Some(end_amplitude) => StimulusShape::Ramp { end_amplitude },
+                    None => {
+                        return Err(self.invalid(
+                            &key("end_amplitude"),
+                            anchor,
+                            "required for a ramp stimulus",
+                        ))
+                    }
+                },
+            };
+            if *entry.duration.get_ref() < 0.0 {
+                return Err(self.invalid(
+                    &key("duration"),
+                    &entry.duration,
+                    "must be zero or more",
+                ));
+            }
+            stimuli.extend(targets.into_iter().map(|neuron| Stimulus {
+                neuron,
+                start_step: to_steps(entry.start.max(0.0), dt),
+                duration_steps: to_steps(*entry.duration.get_ref(), dt),
+                amplitude: entry.amplitude,
+                shape,
+            }));
+        }
+
+        if let SynapticInput::Rate { tau } = self.model.input {
+            if !(tau.is_finite() && tau > 0.0) {
+                return Err(FlymindError::Config {
+                    key: "model.input.rate.tau".to_string(),
+                    line: None,
+                    message: format!("is {tau}, expected a positive number"),
+                });
+            }
+        }
+
+        let muscles = self.muscles;
+        if let Some(tau) = muscles.tau {
+            if !(tau.is_finite() && tau > 0.0) {
+                return Err(muscle_error("muscles.tau", tau, "a positive number"));
+            }
+        }
+        if let Some(nmj) = muscles.nmj {
+            if !(nmj.gain.is_finite() && nmj.gain >= 0.0) {
+                return Err(muscle_error("muscles.nmj.gain", nmj.gain, "zero or more"));
+            }
+            if !(nmj.tau.is_finite() && nmj.tau >= 0.0) {
+                return Err(muscle_error("muscles.nmj.tau", nmj.tau, "zero or more"));
+            }
+        }
+
+        if let Some(voltages) = &self.recorders.voltages {
+            if *voltages.stride.get_ref() == 0 {
+                return Err(self.invalid(
+                    "recorders.voltages.stride",
+                    &voltages.stride,
+                    "must be at least 1",
+                ));
+            }
+            for (i, name) in voltages.neurons.iter().enumerate() {
+                self.neuron_id(network, &format!("recorders.voltages.neurons[{i}]"), name)?;
+            }
+        }
+
+        network.lif = self.model.params;
+        network.lif_overrides = overrides;
+        network.reset_state();
+        network.seed(self.seed);
+        network.bias_currents = bias_currents;
+        network.connection_params = connection_params;
+        network.stimuli = stimuli;
+        network.stdp = self.plasticity.enabled.then_some(self.plasticity.stdp);
+        network.spike_storage = self.recorders.spike_storage;
+        network.propagation = self.model.propagation;
+        network.synaptic_input = self.model.input;
+        if let Some(tau) = muscles.tau {
+            network.muscle_tau = tau;
+        }
+        if let Some(nmj) = muscles.nmj {
+            network.nmj = nmj;
+        }
+        network.event_verbosity = self.recorders.events;
+        network
+            .check_dt(dt)
+            .map_err(|err| self.invalid("dt", &self.dt, &err.to_string()))
+    }
+
+    /// Configure `network` and run it for `duration`, recording as requested.
+    /// Use `configure` and `run_configured` to see dt warnings.
+    pub fn run(&self, network: &mut Network) -> Result<SimulationResult, FlymindError> {
+        self.configure(network)?;
+        Ok(self.run_configured(network))
+    }
+
+    /// Run a network already set up by `configure`.
+    pub fn run_configured(&self, network: &mut Network) -> SimulationResult {
+        self.run_configured_observed(network, &mut |_: &StepInfo, _: &Network| {
+            ControlFlow::Continue(())
+        })
+    }
+
+    /// Like `run_configured`, reporting each step to `observer`, which may stop
+    /// the run early.
+    pub fn run_configured_observed(
+        &self,
+        network: &mut Network,
+        observer: &mut dyn StepObserver,
+    ) -> SimulationResult {
+        let (watch, stride) = match &self.recorders.voltages {
+            Some(voltages) => (
+                voltages
+                    .neurons
+                    .iter()
+                    .filter_map(|name| network.id_of(name.get_ref()))
+                    .collect(),
+                *voltages.stride.get_ref(),
+            ),
+            None => (Vec::new(), 1),
+        };
+        network.run_simulation_observed(self.steps(), *self.dt.get_ref(), &watch, stride, observer)
+    }
+
+    fn connection_params(&self, network: &Network) -> Result<ConnectionParams, FlymindError> {
+        let probability = |key: String, p: Option<f64>| match p {
+            Some(p) if !(0.0..=1.0).contains(&p) => Err(FlymindError::Config {
+                key,
+                line: None,
+                message: format!("is {p}, outside [0, 1]"),
+            }),
+            _ => Ok(()),
+        };
+        probability(
+            "connections.params.release_probability".to_string(),
+            Some(self.connections.params.release_probability),
+        )?;
+        let mut params = ConnectionParams {
+            default: self.connections.params,
+            ..ConnectionParams::default()
+        };
+        for (i, entry) in self.connections.overrides.iter().enumerate() {
+            let key = |field: &str| format!("connections.overrides[{i}].{field}");
+            let layer = entry.params();
+            probability(key("release_probability"), layer.release_probability)?;
+            if let (Some(w_min), Some(w_max)) = (layer.w_min, layer.w_max) {
+                if w_min > w_max {
+                    return Err(FlymindError::Config {
+                        key: key("w_min"),
+                        line: None,
+                        message: format!("is above w_max ({w_max})"),
+                    });
+                }
+            }
+            let selection = |field: &str, expr: &Option<Spanned<String>>| {
+                expr.as_ref()
+                    .map(|expr| {
+                        Selection::parse(expr.get_ref())
+                            .map_err(|err| self.invalid(&key(field), expr, &err.to_string()))
+                    })
+                    .transpose()
+            };
+            let pair = entry.from.is_some() || entry.to.is_some();
+            match (&entry.synapse, pair, &entry.connection) {
+                (Some(synapse), false, None) => {
+                    let class = match synapse.get_ref().as_str() {
+                        "chem" => SynapseClass::Chemical,
+                        "gap" => SynapseClass::GapJunction,
+                        "nmj" => SynapseClass::Nmj,
+                        "extrasynaptic" => SynapseClass::Extrasynaptic,
+                        other => {
+                            return Err(self.invalid(
+                                &key("synapse"),
+                                synapse,
+                                &format!(
+                                    "unknown synapse class {other:?}; expected chem, gap, nmj or \
+                                     extrasynaptic"
+                                ),
+                            ))
+                        }
+                    };
+                    let merged = params.by_class.get(&class).copied().unwrap_or_default();
+                    params.by_class.insert(class, merged.merged(&layer));
+                }
+                (None, true, None) => params.by_pair.push(PairOverride {
+                    from: selection("from", &entry.from)?,
+                    to: selection("to", &entry.to)?,
+                    params: layer,
+                }),
+                (None, false, Some(index)) => {
+                    if *index.get_ref() >= network.connections.len() {
+                        return Err(self.invalid(
+                            &key("connection"),
+                            index,
+                            &format!("the network has {} connections", network.connections.len()),
+                        ));
+                    }
+                    let merged = params.by_connection.get(index.get_ref()).copied();
+                    params
+                        .by_connection
+                        .insert(*index.get_ref(), merged.unwrap_or_default().merged(&layer));
+                }
+                _ => {
+                    return Err(FlymindError::Config {
+                        key: format!("connections.overrides[{i}]"),
+                        line: None,
+                        message: "needs exactly one of `synapse`, `from`/`to` and `connection`"
+                            .to_string(),
+                    })
+                }
+            }
+        }
+        Ok(params)
+    }
+
+    /// The neurons a `neuron` or `select` key names, and the key to anchor
+    /// later errors to; `fallback` anchors the error when both are missing.
+    fn targets<'a>(
+        &self,
+        network: &Network,
+        key: &dyn Fn(&str) -> String,
+        neuron: &'a Option<Spanned<String>>,
+        select: &'a Option<Spanned<String>>,
+        fallback: &Spanned<f64>,
+    ) -> Result<(Vec<usize>, &'a Spanned<String>), FlymindError> {
+        match (neuron, select) {
+            (Some(name), None) => Ok((vec![self.neuron_id(network, &key("neuron"), name)?], name)),
+            (None, Some(expr)) => {
+                let selection = Selection::parse(expr.get_ref())
+                    .map_err(|err| self.invalid(&key("select"), expr, &err.to_string()))?;
+                let ids = network.select(&selection);
+                if ids.is_empty() {
+                    return Err(self.invalid(&key("select"), expr, "matches no neuron"));
+                }
+                Ok((ids, expr))
+            }
+            (Some(_), Some(expr)) => {
+                Err(self.invalid(&key("select"), expr, "conflicts with `neuron`"))
+            }
+            (None, None) => Err(self.invalid(
+                &key("neuron"),
+                fallback,
+                "one of `neuron` and `select` is required",
+            )),
+        }
+    }
+
+    fn neuron_id(
+        &self,
+        network: &Network,
+        key: &str,
+        name: &Spanned<String>,
+    ) -> Result<usize, FlymindError> {
+        network
+            .id_of(name.get_ref())
+            .ok_or_else(|| self.invalid(key, name, &format!("unknown neuron {:?}", name.get_ref())))
+    }
+
+    fn required_time(
+        &self,
+        value: &Option<Spanned<f64>>,
+        key: &str,
+        anchor: &Spanned<String>,
+    ) -> Result<f64, FlymindError> {
+        match value {
+            Some(v) if *v.get_ref() > 0.0 => Ok(*v.get_ref()),
+            Some(v) => Err(self.invalid(key, v, "must be positive")),
+            None => Err(self.invalid(key, anchor, "required for a pulse stimulus")),
+        }
+    }
+
+    fn invalid<T>(&self, key: &str, at: &Spanned<T>, message: &str) -> FlymindError {
+        FlymindError::Config {
+            key: key.to_string(),
+            line: span_line(&self.source, at.span()),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn to_steps(time: f64, dt: f64) -> usize {
+    (time / dt).round() as usize
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Line of a span, or `None` for a default value with no position in the source.
+fn span_line(source: &str, span: Range<usize>) -> Option<usize> {
+    (span.end > 0 && !source.is_empty()).then(|| line_of(source, span.start))
+}
+
+/// Dotted key on the line containing `offset`, qualified by the enclosing table.
+fn key_at(source: &str, offset: usize) -> String {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[line_start..].lines().next().unwrap_or("").trim();
+    if let Some(header) = table_header(line) {
+        return header;
+    }
+
+    let key = line.split('=').next().unwrap_or("").trim();
+    match table_at(source, line_start) {
+        Some(table) if !key.is_empty() => format!("{table}.{key}"),
+        Some(table) => table,
+        None => key.to_string(),
+    }
+}
+
+/// Name of the table whose header most recently precedes `offset`, if any.
+fn table_at(source: &str, offset: usize) -> Option<String> {
+    let offset = offset.min(source.len());
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    source[..line_end]
+        .lines()
+        .rev()
+        .find_map(|l| table_header(l.trim()))
+}
+
+fn table_header(line: &str) -> Option<String> {
+    let inner = line.strip_prefix('[')?.split(']').next()?;
+    Some(inner.trim_start_matches('[').trim().to_string())
+}
+
+fn backticked(message: &str, prefix: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix(prefix)?
+        .trim_start()
+        .strip_prefix('`')?;
+    Some(rest.split('`').next()?.to_string())
+}
+
+fn muscle_error(key: &str, value: f64, expected: &str) -> FlymindError {
+    FlymindError::Config {
+        key: key.to_string(),
+        line: None,
+        message: format!("is {value}, expected {expected}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configure(muscles: &str) -> Result<Network, FlymindError> {
+        let source = format!("duration = 10.0\ndt = 0.1\n{muscles}");
+        let mut network = Network::example_touch_circuit();
+        network.muscle_tau = 50.0;
+        network.nmj.gain = 2.0;
+        SimulationConfig::from_toml_str(&source)?.configure(&mut network)?;
+        Ok(network)
+    }
+
+    #[test]
+    fn muscle_settings_left_out_keep_the_network_s() {
+        let network = configure("").unwrap();
+        assert_eq!(network.muscle_tau, 50.0);
+        assert_eq!(network.nmj.gain, 2.0);
+
+        let network = configure("[muscles]\ntau = 5.0\n").unwrap();
+        assert_eq!(network.muscle_tau, 5.0);
+        assert_eq!(network.nmj.gain, 2.0);
+
+        let network = configure("[muscles.nmj]\ngain = 0.5\n").unwrap();
+        assert_eq!(network.muscle_tau, 50.0);
+        assert_eq!(
+            network.nmj,
+            NmjParams {
+                gain: 0.5,
+                tau: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn out_of_range_muscle_settings_are_rejected() {
+        for (muscles, key) in [
+            ("[muscles]\ntau = 0.0\n", "muscles.tau"),
+            ("[muscles.nmj]\ngain = -1.0\n", "muscles.nmj.gain"),
+            ("[muscles.nmj]\ntau = -1.0\n", "muscles.nmj.tau"),
+        ] {
+            assert!(
+                matches!(configure(muscles), Err(FlymindError::Config { key: k, .. }) if k == key),
+                "{muscles}"
+            );
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::network::Network;
+
+/// Compressed sparse row layout of outgoing connections.
+///
+/// The connections leaving neuron `i` are `connections[offsets[i]..offsets[i + 1]]`,
+/// in ascending connection-index order, with their targets in the parallel
+/// `targets` slice. Self-connections the network's `self_connections` ignores
+/// are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Csr {
+    pub offsets: Vec<usize>,
+    pub targets: Vec<usize>,
+    pub connections: Vec<usize>,
+}
+
+impl Csr {
+    pub fn from_network(network: &Network) -> Self {
+        let n = network.neurons.len();
+        let counted = || {
+            network
+                .connections
+                .iter()
+                .enumerate()
+                .filter(|(_, conn)| network.counts(conn))
+        };
+        let mut offsets = vec![0usize; n + 1];
+        for (_, conn) in counted() {
+            offsets[conn.from_id + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let mut cursor = offsets.clone();
+        let mut targets = vec![0usize; offsets[n]];
+        let mut connections = vec![0usize; offsets[n]];
+        for (index, conn) in counted() {
+            let slot = cursor[conn.from_id];
+            targets[slot] = conn.to_id;
+            connections[slot] = index;
+            cursor[conn.from_id] += 1;
+        }
+
+        Self {
+            offsets,
+            targets,
+            connections,
+        }
+    }
+
+    pub fn neuron_count(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn targets_of(&self, id: usize) -> &[usize] {
+        &self.targets[self.offsets[id]..self.offsets[id + 1]]
+    }
+
+    pub fn connections_of(&self, id: usize) -> &[usize] {
+        &self.connections[self.offsets[id]..self.offsets[id + 1]]
+    }
+}
+
+impl Network {
+    pub fn build_csr(&self) -> Csr {
+        Csr::from_network(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{SelfConnections, SynapseType};
+
+    #[test]
+    fn ignored_self_connections_are_left_out() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 0, SynapseType::GapJunction, 1.0);
+        network.add_connection(0, 1, SynapseType::GapJunction, 1.0);
+        network.add_connection(1, 1, SynapseType::GapJunction, 1.0);
+
+        let csr = network.build_csr();
+        assert_eq!(csr.targets_of(0), &[0, 1]);
+        assert_eq!(csr.connections_of(1), &[2]);
+
+        network.self_connections = SelfConnections::Ignore;
+        let csr = network.build_csr();
+        assert_eq!(csr.offsets, vec![0, 1, 1]);
+        assert_eq!(csr.targets_of(0), &[1]);
+        assert_eq!(csr.connections_of(0), &[1]);
+        assert!(csr.targets_of(1).is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! Sanity checks on a running simulation.
+//!
+//! `DiagnosticsObserver` is a `StepObserver` that every `check_every` steps
+//! looks for potentials that are non-finite or further than `max_abs_potential`
+//! from rest, plastic weights outside their clamps, neurons firing on more
+//! than `max_firing_fraction` of steps, and network-wide silence. Each check has a
+//! `Severity`; an aborting check stops the run and keeps a
+//! `FlymindError::Diverged` naming the first offending neuron.
+
+use std::ops::ControlFlow;
+
+use crate::error::FlymindError;
+use crate::network::{Network, SynapseType};
+use crate::simulation::{StepInfo, StepObserver};
+
+/// What a failed check does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Off,
+    /// Record a warning and keep running.
+    Warn,
+    /// Stop the run with `FlymindError::Diverged`.
+    Abort,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsOptions {
+    /// Steps between checks.
+    pub check_every: usize,
+    /// Non-finite potentials, or potentials further from rest than
+    /// `max_abs_potential`.
+    pub potentials: Severity,
+    /// Largest distance of a potential from its neuron's resting potential.
+    pub max_abs_potential: f64,
+    /// Chemical weights outside `[w_min, w_max]` while STDP is on, or non-finite.
+    pub weights: Severity,
+    /// A neuron firing on more than `max_firing_fraction` of the last `rate_window`
+    /// steps, checked at the first check once that many steps have passed.
+    pub firing_rate: Severity,
+    pub max_firing_fraction: f64,
+    pub rate_window: usize,
+    /// Warn once no neuron has fired for this many steps. Silence never aborts,
+    /// since an unstimulated network is legitimately quiet.
+    pub silence_steps: Option<usize>,
+}
+
+impl Default for DiagnosticsOptions {
+    fn default() -> Self {
+        Self {
+            check_every: 100,
+            potentials: Severity::Abort,
+            max_abs_potential: 1e6,
+            weights: Severity::Abort,
+            firing_rate: Severity::Warn,
+            max_firing_fraction: 0.9,
+            rate_window: 1000,
+            silence_steps: Some(10_000),
+        }
+    }
+}
+
+/// Runs the checks in `DiagnosticsOptions` as a step observer.
+///
+/// Warnings accumulate until taken with `take_warnings`; after an abort,
+/// `error` holds the reason and the run has stopped on that step.
+#[derive(Debug)]
+pub struct DiagnosticsObserver {
+    options: DiagnosticsOptions,
+    warnings: Vec<String>,
+    error: Option<FlymindError>,
+    /// Spikes per neuron in the current rate window.
+    window_spikes: Vec<usize>,
+    window_steps: usize,
+    last_spike: Option<usize>,
+    silence_reported: bool,
+}
+
+impl DiagnosticsObserver {
+    pub fn new(options: DiagnosticsOptions) -> Self {
+        Self {
+            options,
+            warnings: Vec::new(),
+            error: None,
+            window_spikes: Vec::new(),
+            window_steps: 0,
+            last_spike: None,
+            silence_reported: false,
+        }
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Warnings recorded since the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Why the run was aborted, if it was.
+    pub fn error(&self) -> Option<&FlymindError> {
+        self.error.as_ref()
+    }
+
+    /// The abort error, or the warnings not yet taken.
+    pub fn into_result(self) -> Result<Vec<String>, FlymindError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.warnings),
+        }
+    }
+
+    /// Apply `severity` to a failed check; returns whether the run must stop.
+    fn report(&mut self, severity: Severity, err: FlymindError) -> bool {
+        match (severity, err) {
+            (Severity::Off, _) => false,
+            (
+                Severity::Warn,
+                FlymindError::Diverged {
+                    step,
+                    neuron,
+                    what,
+                    value,
+                },
+            ) => {
+                self.warnings
+                    .push(format!("step {step}: {what} of {neuron} is {value}"));
+                false
+            }
+            (Severity::Warn, err) => {
+                self.warnings.push(err.to_string());
+                false
+            }
+            (Severity::Abort, err) => {
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn check(&mut self, step: usize, network: &Network) -> bool {
+        let diverged = |id: usize, what: &'static str, value: f64| FlymindError::Diverged {
+            step,
+            neuron: network.neurons[id].name().to_string(),
+            what,
+            value,
+        };
+
+        if self.options.potentials != Severity::Off {
+            let limit = self.options.max_abs_potential;
+            if let Some(neuron) = network.neurons.iter().find(|n| {
+                let offset = n.membrane_potential - network.lif_for(n.id).resting_potential;
+                !n.membrane_potential.is_finite() || offset.abs() > limit
+            }) {
+                let err = diverged(neuron.id, "membrane potential", neuron.membrane_potential);
+                if self.report(self.options.potentials, err) {
+                    return true;
+                }
+            }
+        }
+
+        if self.options.weights != Severity::Off {
+            let (w_min, w_max) = network
+                .stdp
+                .map_or((f64::NEG_INFINITY, f64::INFINITY), |s| (s.w_min, s.w_max));
+            if let Some(conn) = network.connections.iter().find(|conn| {
+                matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+                    && !(conn.weight.is_finite() && (w_min..=w_max).contains(&conn.weight))
+            }) {
+                let err = diverged(conn.to_id, "incoming chemical weight", conn.weight);
+                if self.report(self.options.weights, err) {
+                    return true;
+                }
+            }
+        }
+
+        if self.window_steps >= self.options.rate_window.max(1) {
+            let steps = std::mem::take(&mut self.window_steps) as f64;
+            let counts = std::mem::take(&mut self.window_spikes);
+            let ceiling = self.options.max_firing_fraction;
+            if let Some(id) = (0..counts.len()).find(|&id| counts[id] as f64 / steps > ceiling) {
+                let err = diverged(id, "firing fraction", counts[id] as f64 / steps);
+                if self.report(self.options.firing_rate, err) {
+                    return true;
+                }
+            }
+        }
+
+        if let Some(window) = self.options.silence_steps {
+            let silent = self.last_spike.map_or(step + 1, |last| step - last);
+            if silent >= window && !self.silence_reported {
+                self.silence_reported = true;
+                self.warnings.push(format!(
+                    "no neuron has fired for {silent} steps (at step {step})"
+                ));
+            }
+        }
+        false
+    }
+}
+
+impl Default for DiagnosticsObserver {
+    fn default() -> Self {
+        Self::new(DiagnosticsOptions::default())
+    }
+}
+
+impl StepObserver for DiagnosticsObserver {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        self.window_spikes.resize(network.neurons.len(), 0);
+        if info.spikes > 0 {
+            self.last_spike = Some(info.step);
+            self.silence_reported = false;
+            for (count, neuron) in self.window_spikes.iter_mut().zip(&network.neurons) {
+                *count += usize::from(neuron.just_fired);
+            }
+        }
+        self.window_steps += 1;
+
+        let last = info.step + 1 == info.steps;
+        if !(info.step + 1).is_multiple_of(self.options.check_every.max(1)) && !last {
+            return ControlFlow::Continue(());
+        }
+        if self.check(info.step, network) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{LifParams, Stimulus};
+
+    /// B never fires and is driven hard, so its potential climbs without bound.
+    fn runaway() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        let silent = LifParams {
+            threshold: f64::INFINITY,
+            ..network.lif
+        };
+        network.lif_overrides.insert(1, silent);
+        network.stimuli.push(Stimulus::new(1, 0, 1000, 100.0));
+        network
+    }
+
+    fn options(potentials: Severity) -> DiagnosticsOptions {
+        DiagnosticsOptions {
+            check_every: 1,
+            potentials,
+            max_abs_potential: 50.0,
+            ..DiagnosticsOptions::default()
+        }
+    }
+
+    #[test]
+    fn an_aborting_check_names_the_diverging_neuron() {
+        let mut network = runaway();
+        let mut diagnostics = DiagnosticsObserver::new(options(Severity::Abort));
+        let result = network.run_simulation_observed(1000, 0.1, &[], 1, &mut diagnostics);
+        assert!(result.steps < 1000);
+        match diagnostics.into_result() {
+            Err(FlymindError::Diverged {
+                step, neuron, what, ..
+            }) => {
+                assert_eq!(neuron, "B");
+                assert_eq!(what, "membrane potential");
+                assert_eq!(step + 1, result.steps);
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_warning_check_lets_the_run_finish() {
+        let mut network = runaway();
+        let mut diagnostics = DiagnosticsObserver::new(options(Severity::Warn));
+        let result = network.run_simulation_observed(1000, 0.1, &[], 1, &mut diagnostics);
+        assert_eq!(result.steps, 1000);
+        assert!(diagnostics.error().is_none());
+        let warnings = diagnostics.take_warnings();
+        assert!(warnings[0].contains("membrane potential of B is"));
+
+        let mut network = runaway();
+        let mut off = DiagnosticsObserver::new(options(Severity::Off));
+        network.run_simulation_observed(1000, 0.1, &[], 1, &mut off);
+        assert!(off.into_result().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_healthy_run_raises_nothing() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 200, 0.2));
+        let mut diagnostics = DiagnosticsObserver::new(DiagnosticsOptions {
+            check_every: 1,
+            ..DiagnosticsOptions::default()
+        });
+        let result = network.run_simulation_observed(300, 0.1, &[], 1, &mut diagnostics);
+        assert_eq!(result.steps, 300);
+        assert!(result.spike_trains.iter().any(|train| !train.is_empty()));
+        assert!(diagnostics.into_result().unwrap().is_empty());
+    }
+}
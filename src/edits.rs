@@ -0,0 +1,374 @@
+//! An undo history of structural edits, recorded while `Network::start_edit_log`
+//! is in effect.
+//!
+//! Recorded operations are `add_neuron` (and so `get_or_create_neuron`),
+//! `add_connection`, `add_connections_bulk`, `retain_connections` (and so
+//! `prune` and `remove_source`), `remove_isolated`, `merge_duplicate_neurons`,
+//! `consolidate_edges`, `transform_weights`, `set_plasticity`, `rewire` and
+//! `append_from_csv` (and so `append_from_reader`). Appends and weight changes
+//! are stored as what changed; operations that renumber, remove or rewire,
+//! and file appends, store the neurons, connections and everything indexed by
+//! them from before and after.
+//!
+//! Changes made directly to the network's fields are not recorded, and undoing
+//! past one may not give back the network as it was. Without a log, nothing
+//! is kept.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::annotations::Annotations;
+use crate::muscle::NeuromuscularJunction;
+use crate::network::{Connection, Network, Neuron};
+use crate::simulation::{LifParams, Stimulus};
+use crate::synapses::SynapseOverride;
+use crate::trace::StimulusTrace;
+
+/// Recorded edits, serializable to keep with a saved network.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EditLog {
+    done: Vec<Edit>,
+    /// Undone edits, the most recent last; cleared by a new edit.
+    undone: Vec<Edit>,
+}
+
+impl EditLog {
+    pub fn len(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.done.is_empty()
+    }
+
+    /// How many undone edits `redo` can reapply.
+    pub fn redoable(&self) -> usize {
+        self.undone.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Edit {
+    description: String,
+    change: Change,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Change {
+    /// Neurons appended to the end.
+    AddNeurons(Vec<Neuron>),
+    /// Connections appended to the end.
+    AddConnections(Vec<Connection>),
+    Weights {
+        indices: Vec<usize>,
+        before: Vec<f64>,
+        after: Vec<f64>,
+    },
+    /// Connections whose `plastic` flag was set to `plastic`.
+    Plasticity { indices: Vec<usize>, plastic: bool },
+    Replace {
+        before: Box<Structure>,
+        after: Box<Structure>,
+    },
+}
+
+/// The neurons, connections and everything indexed by them; also what
+/// `Network::save_structure` writes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Structure {
+    neurons: Vec<Neuron>,
+    connections: Vec<Connection>,
+    neuromuscular: Vec<NeuromuscularJunction>,
+    lif_overrides: HashMap<usize, LifParams>,
+    stimuli: Vec<Stimulus>,
+    stimulus_traces: Vec<StimulusTrace>,
+    bias_currents: HashMap<usize, f64>,
+    annotations: Annotations,
+    connection_overrides: HashMap<usize, SynapseOverride>,
+}
+
+impl Structure {
+    pub(crate) fn of(network: &Network) -> Self {
+        Self {
+            neurons: network.neurons.clone(),
+            connections: network.connections.clone(),
+            neuromuscular: network.neuromuscular.clone(),
+            lif_overrides: network.lif_overrides.clone(),
+            stimuli: network.stimuli.clone(),
+            stimulus_traces: network.stimulus_traces.clone(),
+            bias_currents: network.bias_currents.clone(),
+            annotations: network.annotations.clone(),
+            connection_overrides: network.connection_params.by_connection.clone(),
+        }
+    }
+
+    pub(crate) fn restore(self, network: &mut Network) {
+        network.neurons = self.neurons;
+        network.connections = self.connections;
+        network.neuromuscular = self.neuromuscular;
+        network.lif_overrides = self.lif_overrides;
+        network.stimuli = self.stimuli;
+        network.stimulus_traces = self.stimulus_traces;
+        network.bias_currents = self.bias_currents;
+        network.annotations = self.annotations;
+        network.connection_params.by_connection = self.connection_overrides;
+        network.rebuild_name_index();
+        network.rebuild_indices();
+    }
+}
+
+impl Network {
+    /// Start recording edits into a new, empty log, dropping any current one.
+    pub fn start_edit_log(&mut self) {
+        self.edit_log = Some(EditLog::default());
+    }
+
+    /// Stop recording and return the log, e.g. to save it.
+    pub fn take_edit_log(&mut self) -> Option<EditLog> {
+        self.edit_log.take()
+    }
+
+    /// Record into `log` from now on, as if its edits had been made to this
+    /// network; give it only the network it was taken from.
+    pub fn set_edit_log(&mut self, log: EditLog) {
+        self.edit_log = Some(log);
+    }
+
+    pub fn edit_log(&self) -> Option<&EditLog> {
+        self.edit_log.as_ref()
+    }
+
+    /// Descriptions of the edits `undo` can step back through, oldest first;
+    /// empty without a log.
+    pub fn edit_history(&self) -> Vec<String> {
+        self.edit_log
+            .iter()
+            .flat_map(|log| &log.done)
+            .map(|edit| edit.description.clone())
+            .collect()
+    }
+
+    /// Revert the most recent recorded edit, returning its description, or
+    /// `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<String> {
+        let edit = self.edit_log.as_mut()?.done.pop()?;
+        let description = edit.description.clone();
+        let redo = self.apply_change(edit.change, true);
+        let log = self.edit_log.get_or_insert_with(EditLog::default);
+        log.undone.push(Edit {
+            description: description.clone(),
+            change: redo,
+        });
+        Some(description)
+    }
+
+    /// Reapply the most recently undone edit, returning its description.
+    pub fn redo(&mut self) -> Option<String> {
+        let edit = self.edit_log.as_mut()?.undone.pop()?;
+        let description = edit.description.clone();
+        let undo = self.apply_change(edit.change, false);
+        let log = self.edit_log.get_or_insert_with(EditLog::default);
+        log.done.push(Edit {
+            description: description.clone(),
+            change: undo,
+        });
+        Some(description)
+    }
+
+    /// Revert `change` (or reapply it, for `!revert`), returning it for the
+    /// opposite direction.
+    fn apply_change(&mut self, change: Change, revert: bool) -> Change {
+        match change {
+            Change::AddNeurons(neurons) => {
+                if revert {
+                    let start = self.neurons.len().saturating_sub(neurons.len());
+                    self.neurons.truncate(start);
+                    self.annotations.neurons.retain(|&id, _| id < start);
+                    self.lif_overrides.retain(|&id, _| id < start);
+                } else {
+                    self.neurons.extend(neurons.iter().cloned());
+                }
+                self.rebuild_name_index();
+                Change::AddNeurons(neurons)
+            }
+            Change::AddConnections(connections) => {
+                if revert {
+                    let start = self.connections.len().saturating_sub(connections.len());
+                    self.connections.truncate(start);
+                    self.annotations
+                        .connections
+                        .retain(|&index, _| index < start);
+                    self.connection_params
+                        .by_connection
+                        .retain(|&index, _| index < start);
+                } else {
+                    self.connections.extend(connections.iter().cloned());
+                }
+                self.rebuild_indices();
+                Change::AddConnections(connections)
+            }
+            Change::Weights {
+                indices,
+                before,
+                after,
+            } => {
+                let weights = if revert { &before } else { &after };
+                for (&index, &weight) in indices.iter().zip(weights) {
+                    if let Some(conn) = self.connections.get_mut(index) {
+                        conn.weight = weight;
+                    }
+                }
+                Change::Weights {
+                    indices,
+                    before,
+                    after,
+                }
+            }
+            Change::Plasticity { indices, plastic } => {
+                for &index in &indices {
+                    if let Some(conn) = self.connections.get_mut(index) {
+                        conn.plastic = plastic != revert;
+                    }
+                }
+                Change::Plasticity { indices, plastic }
+            }
+            Change::Replace { before, after } => {
+                let state = if revert { &before } else { &after };
+                Structure::clone(state).restore(self);
+                Change::Replace { before, after }
+            }
+        }
+    }
+
+    fn record_edit(&mut self, description: String, change: Change) {
+        if let Some(log) = &mut self.edit_log {
+            log.done.push(Edit {
+                description,
+                change,
+            });
+            log.undone.clear();
+        }
+    }
+
+    pub(crate) fn record_added_neuron(&mut self, id: usize) {
+        if self.edit_log.is_some() {
+            let neuron = self.neurons[id].clone();
+            let description = format!("add neuron {}", neuron.name());
+            self.record_edit(description, Change::AddNeurons(vec![neuron]));
+        }
+    }
+
+    /// Record the connections from `start` on as appended.
+    pub(crate) fn record_added_connections(&mut self, start: usize) {
+        if self.edit_log.is_none() || start >= self.connections.len() {
+            return;
+        }
+        let added = self.connections[start..].to_vec();
+        let description = match added.as_slice() {
+            [conn] => format!(
+                "add connection {} -> {} ({}, {})",
+                self.neurons[conn.from_id].name(),
+                self.neurons[conn.to_id].name(),
+                conn.synapse_type.label(),
+                conn.weight
+            ),
+            _ => format!("add {} connections", added.len()),
+        };
+        self.record_edit(description, Change::AddConnections(added));
+    }
+
+    /// Record new weights for `indices`, given the old ones.
+    pub(crate) fn record_weights(
+        &mut self,
+        description: String,
+        indices: Vec<usize>,
+        before: Vec<f64>,
+    ) {
+        if self.edit_log.is_some() {
+            let after = indices
+                .iter()
+                .map(|&index| self.connections[index].weight)
+                .collect();
+            let change = Change::Weights {
+                indices,
+                before,
+                after,
+            };
+            self.record_edit(description, change);
+        }
+    }
+
+    pub(crate) fn record_plasticity(&mut self, indices: Vec<usize>, plastic: bool) {
+        if self.edit_log.is_some() && !indices.is_empty() {
+            let state = if plastic { "plastic" } else { "fixed" };
+            let description = format!("make {} connections {state}", indices.len());
+            self.record_edit(description, Change::Plasticity { indices, plastic });
+        }
+    }
+
+    /// Run `edit`, recording the structure before and after it under the
+    /// description `describe` gives its result, or nothing if that is `None`.
+    pub(crate) fn record_structural<T>(
+        &mut self,
+        edit: impl FnOnce(&mut Network) -> T,
+        describe: impl FnOnce(&T) -> Option<String>,
+    ) -> T {
+        let Some(log) = self.edit_log.take() else {
+            return edit(self);
+        };
+        let before = Structure::of(self);
+        let result = edit(self);
+        self.edit_log = Some(log);
+        if let Some(description) = describe(&result) {
+            let change = Change::Replace {
+                before: Box::new(before),
+                after: Box::new(Structure::of(self)),
+            };
+            self.record_edit(description, change);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::AttrValue;
+    use crate::network::{NeuronType, Region, SynapseType};
+
+    #[test]
+    fn undoing_an_append_drops_what_was_attached_to_it() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 1, SynapseType::GapJunction, 1.0);
+        network.start_edit_log();
+        let c = network.add_neuron("C", NeuronType::Other, Region::Unknown, 0.0);
+        network.add_connection(1, c, SynapseType::GapJunction, 2.0);
+        network.set_neuron_attr(c, "flag", AttrValue::from(true));
+        network.lif_overrides.insert(c, LifParams::default());
+        network.set_connection_attr(1, "flag", AttrValue::from(true));
+        network.connection_params.by_connection.insert(
+            1,
+            SynapseOverride {
+                gain: Some(2.0),
+                ..SynapseOverride::default()
+            },
+        );
+
+        network.undo();
+        assert_eq!(network.connections.len(), 1);
+        assert!(network.annotations.connections.is_empty());
+        assert!(network.connection_params.by_connection.is_empty());
+        network.undo();
+        assert_eq!(network.neurons.len(), 2);
+        assert!(network.annotations.neurons.is_empty());
+        assert!(network.lif_overrides.is_empty());
+
+        // Appending again must not inherit the stale entries.
+        network.add_neuron("D", NeuronType::Other, Region::Unknown, 0.0);
+        network.add_connection(0, 2, SynapseType::GapJunction, 1.0);
+        assert_eq!(network.get_neuron_attr(2, "flag"), None);
+        assert_eq!(network.get_connection_attr(1, "flag"), None);
+        assert_eq!(network.synapse_params(1).gain, 1.0);
+    }
+}
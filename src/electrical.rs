@@ -0,0 +1,296 @@
+//! The electrical (gap-junction) graph as a network of its own, and the
+//! chemical, wired and extrasynaptic graphs beside it.
+//!
+//! Each view keeps every neuron of its parent with the same id and name, so
+//! ids found in a view index the parent directly.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::network::{Connection, ExtrasynapticEdges, Network, Region, SynapseType};
+use crate::paths::SynapseClass;
+
+/// Which connections `Network::graph_view` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GraphKind {
+    /// Chemical sends and their mirrored receives, unchanged.
+    #[default]
+    Chemical,
+    /// Gap junctions, as in `Network::gap_junction_view`.
+    GapJunction,
+    /// Both of the above: the wired network.
+    Combined,
+    /// Extrasynaptic edges, unchanged; see `SynapseType::Extrasynaptic`.
+    Extrasynaptic,
+    /// The wired network and the extrasynaptic layer together.
+    Multiplex,
+}
+
+impl GraphKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GraphKind::Chemical => "chemical",
+            GraphKind::GapJunction => "gap",
+            GraphKind::Combined => "combined",
+            GraphKind::Extrasynaptic => "extrasynaptic",
+            GraphKind::Multiplex => "multiplex",
+        }
+    }
+
+    /// Parse a `label()` value case-insensitively; "electrical" and
+    /// "gap_junction" are accepted for `GapJunction`, "wired" for `Combined`
+    /// and "all" for `Multiplex`.
+    pub fn from_label(label: &str) -> Option<GraphKind> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "chemical" | "chem" => Some(GraphKind::Chemical),
+            "gap" | "gap_junction" | "electrical" => Some(GraphKind::GapJunction),
+            "combined" | "wired" => Some(GraphKind::Combined),
+            "extrasynaptic" | "wireless" => Some(GraphKind::Extrasynaptic),
+            "multiplex" | "all" => Some(GraphKind::Multiplex),
+            _ => None,
+        }
+    }
+
+    /// The traversal classes of the view, for `PathOptions::classes`.
+    pub fn classes(&self) -> Vec<SynapseClass> {
+        match self {
+            GraphKind::Chemical => vec![SynapseClass::Chemical],
+            GraphKind::GapJunction => vec![SynapseClass::GapJunction],
+            GraphKind::Combined => vec![SynapseClass::Chemical, SynapseClass::GapJunction],
+            GraphKind::Extrasynaptic => vec![SynapseClass::Extrasynaptic],
+            GraphKind::Multiplex => vec![
+                SynapseClass::Chemical,
+                SynapseClass::GapJunction,
+                SynapseClass::Extrasynaptic,
+            ],
+        }
+    }
+
+    /// Whether a connection of `synapse_type` belongs to the view.
+    pub fn matches(&self, synapse_type: SynapseType) -> bool {
+        let wired = synapse_type.is_chemical() || synapse_type == SynapseType::GapJunction;
+        let extrasynaptic = matches!(synapse_type, SynapseType::Extrasynaptic(_));
+        match self {
+            GraphKind::Chemical => synapse_type.is_chemical(),
+            GraphKind::GapJunction => synapse_type == SynapseType::GapJunction,
+            GraphKind::Combined => wired,
+            GraphKind::Extrasynaptic => extrasynaptic,
+            GraphKind::Multiplex => wired || extrasynaptic,
+        }
+    }
+}
+
+impl Network {
+    /// A copy of the network holding only the connections of `kind`, with
+    /// every neuron kept under its id and name. NMJ rows, muscles and
+    /// neuromuscular junctions are left out of every view, and simulation
+    /// settings are not copied.
+    ///
+    /// Gap junctions in the view are merged and symmetrized as in
+    /// `gap_junction_view`, which drops their annotations and parameter
+    /// overrides; other connections are copied as they are. A view holding
+    /// the extrasynaptic layer counts it in every measure, as
+    /// `ExtrasynapticEdges::Include`.
+    pub fn graph_view(&self, kind: GraphKind) -> Network {
+        let ids: Vec<usize> = (0..self.neurons.len()).collect();
+        let mut view = self.subnetwork(&ids);
+        if kind.classes().contains(&SynapseClass::Extrasynaptic) {
+            view.extrasynaptic_edges = ExtrasynapticEdges::Include;
+        }
+        view.muscles.clear();
+        view.neuromuscular.clear();
+        view.retain_connections(|conn| kind.matches(conn.synapse_type));
+        if kind.matches(SynapseType::GapJunction) {
+            merge_gap_junctions(&mut view);
+        }
+        view
+    }
+
+    /// The electrical network: every neuron under its id and name, and one
+    /// gap junction each way between every coupled pair.
+    ///
+    /// Parallel rows in one direction are summed, then both directions get
+    /// the mean of the two sums, a junction listed one way only being
+    /// mirrored, as `GapJunctionMode::Symmetric` does on load. Contacts are
+    /// summed the same way and the first row's source is kept.
+    pub fn gap_junction_view(&self) -> Network {
+        self.graph_view(GraphKind::GapJunction)
+    }
+
+    /// Groups of neurons coupled by gap junctions, largest first, each in
+    /// ascending id order with ties ordered by smallest member. Neurons
+    /// without a gap junction to another neuron are left out.
+    pub fn gap_junction_components(&self) -> Vec<Vec<usize>> {
+        let mut components = self.gap_junction_view().weakly_connected_components();
+        components.retain(|component| component.len() > 1);
+        components
+    }
+
+    /// Distinct gap-junction partners of each neuron, by id; self-junctions
+    /// do not count.
+    pub fn gap_junction_degrees(&self) -> Vec<usize> {
+        self.gap_junction_view()
+            .undirected_adjacency()
+            .iter()
+            .map(Vec::len)
+            .collect()
+    }
+
+    /// Number of neurons with each `gap_junction_degrees` value.
+    pub fn gap_junction_degree_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for degree in self.gap_junction_degrees() {
+            *distribution.entry(degree).or_default() += 1;
+        }
+        distribution
+    }
+
+    /// For each region with at least one gap junction inside it, the largest
+    /// group of its neurons coupled through junctions that stay within the
+    /// region, in ascending id order. Regions are listed head to tail, then
+    /// `Unknown`; ties in size go to the group with the smallest member.
+    pub fn largest_gap_clusters_by_region(&self) -> Vec<(Region, Vec<usize>)> {
+        let adjacency = self.gap_junction_view().undirected_adjacency();
+        [Region::Head, Region::MidBody, Region::Tail, Region::Unknown]
+            .into_iter()
+            .filter_map(|region| {
+                let in_region = |id: usize| self.neurons[id].region == region;
+                let mut seen = vec![false; self.neurons.len()];
+                let mut largest: Vec<usize> = Vec::new();
+                for start in (0..self.neurons.len()).filter(|&id| in_region(id)) {
+                    if seen[start] {
+                        continue;
+                    }
+                    seen[start] = true;
+                    let mut cluster = vec![start];
+                    let mut next = 0;
+                    while next < cluster.len() {
+                        for &partner in &adjacency[cluster[next]] {
+                            if in_region(partner) && !seen[partner] {
+                                seen[partner] = true;
+                                cluster.push(partner);
+                            }
+                        }
+                        next += 1;
+                    }
+                    // Starts ascend, so an earlier cluster of equal size has
+                    // the smaller member.
+                    if cluster.len() > largest.len() {
+                        largest = cluster;
+                    }
+                }
+                largest.sort_unstable();
+                (largest.len() > 1).then_some((region, largest))
+            })
+            .collect()
+    }
+}
+
+/// Replace the gap junctions of `view` with one summed connection per
+/// direction, then symmetrize them.
+fn merge_gap_junctions(view: &mut Network) {
+    let mut merged: Vec<Connection> = Vec::new();
+    let mut slot: HashMap<(usize, usize), usize> = HashMap::new();
+    for conn in &view.connections {
+        if conn.synapse_type != SynapseType::GapJunction {
+            continue;
+        }
+        match slot.get(&(conn.from_id, conn.to_id)) {
+            Some(&index) => {
+                merged[index].weight += conn.weight;
+                merged[index].contacts += conn.contacts;
+            }
+            None => {
+                slot.insert((conn.from_id, conn.to_id), merged.len());
+                merged.push(conn.clone());
+            }
+        }
+    }
+    view.retain_connections(|conn| conn.synapse_type != SynapseType::GapJunction);
+    view.connections.extend(merged);
+    crate::loader::symmetrize_gap_junctions(view);
+    view.rebuild_indices();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    #[test]
+    fn the_view_has_one_junction_each_way_per_coupled_pair() {
+        let network = Network::example_touch_circuit();
+        let pairs: BTreeSet<(usize, usize)> = network
+            .connections
+            .iter()
+            .filter(|conn| conn.synapse_type == SynapseType::GapJunction)
+            .filter(|conn| conn.from_id != conn.to_id)
+            .map(|conn| (conn.from_id.min(conn.to_id), conn.from_id.max(conn.to_id)))
+            .collect();
+        assert!(!pairs.is_empty());
+
+        let view = network.gap_junction_view();
+        assert_eq!(view.neurons.len(), network.neurons.len());
+        assert!(view
+            .connections
+            .iter()
+            .all(|conn| conn.synapse_type == SynapseType::GapJunction));
+        assert_eq!(view.connections.len(), 2 * pairs.len());
+        for conn in &view.connections {
+            let back = view
+                .connections
+                .iter()
+                .find(|other| other.from_id == conn.to_id && other.to_id == conn.from_id)
+                .unwrap();
+            assert_eq!(back.weight, conn.weight);
+        }
+
+        let degrees = network.gap_junction_degrees();
+        for (id, &degree) in degrees.iter().enumerate() {
+            let partners = pairs.iter().filter(|&&(a, b)| a == id || b == id).count();
+            assert_eq!(degree, partners, "{}", network.neurons[id].name());
+        }
+        let distribution = network.gap_junction_degree_distribution();
+        assert_eq!(distribution.values().sum::<usize>(), network.neurons.len());
+    }
+
+    #[test]
+    fn isolated_neurons_are_in_no_component() {
+        let mut network = Network::new();
+        for name in ["A", "B", "C", "D", "E"] {
+            network.get_or_create_neuron(name);
+        }
+        network.add_connection(0, 1, SynapseType::GapJunction, 1.0);
+        network.add_connection(0, 1, SynapseType::GapJunction, 2.0);
+        network.add_connection(1, 0, SynapseType::GapJunction, 1.0);
+        network.add_connection(2, 1, SynapseType::GapJunction, 4.0);
+        network.add_connection(3, 3, SynapseType::GapJunction, 1.0);
+        network.add_connection(
+            4,
+            0,
+            SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+            1.0,
+        );
+
+        let view = network.gap_junction_view();
+        let weight = |from: usize, to: usize| -> Vec<f64> {
+            view.connections
+                .iter()
+                .filter(|conn| conn.from_id == from && conn.to_id == to)
+                .map(|conn| conn.weight)
+                .collect()
+        };
+        assert_eq!(weight(0, 1), vec![2.0]);
+        assert_eq!(weight(1, 0), vec![2.0]);
+        assert_eq!(weight(1, 2), vec![4.0]);
+        assert!(weight(4, 0).is_empty());
+
+        assert_eq!(network.gap_junction_components(), vec![vec![0, 1, 2]]);
+        assert_eq!(network.gap_junction_degrees(), vec![1, 2, 1, 0, 0]);
+        assert_eq!(
+            network.gap_junction_degree_distribution(),
+            BTreeMap::from([(0, 2), (1, 2), (2, 1)])
+        );
+    }
+}
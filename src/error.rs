@@ -0,0 +1,314 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum FlymindError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Load(LoadError),
+    Integrity(IntegrityError),
+    /// A connection endpoint that is not a valid neuron index. `position` is the
+    /// offset of the edge within the batch being inserted.
+    InvalidNeuronId {
+        position: usize,
+        id: usize,
+        neuron_count: usize,
+    },
+    /// A simulation step that is not positive, or (with `max_dt` set) past the
+    /// integrator's stability limit for the fastest time constant of the model.
+    InvalidTimeStep {
+        dt: f64,
+        max_dt: Option<f64>,
+    },
+    /// An invalid simulation config. `key` is the dotted TOML key and `line`
+    /// its 1-based line, when known.
+    Config {
+        key: String,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A diagnostics check aborted a simulation at `step`: `what` of `neuron`
+    /// (the first offender, by name) had the unhealthy `value`.
+    Diverged {
+        step: usize,
+        neuron: String,
+        what: &'static str,
+        value: f64,
+    },
+    /// A `NetworkState` restored into a network whose `what` ("neurons",
+    /// "muscles" or "connections") count differs from the one it was taken from.
+    StateMismatch {
+        what: &'static str,
+        state: usize,
+        network: usize,
+    },
+    /// A neuron name that matches nothing in the network.
+    UnknownNeuron {
+        name: String,
+    },
+    /// A weight transform that would give `connection` a negative, NaN or
+    /// infinite `weight`.
+    InvalidWeight {
+        connection: usize,
+        weight: f64,
+    },
+    /// A neuron group asked for by name that has no members.
+    EmptyGroup {
+        label: String,
+    },
+    /// A result bundle whose `file` is missing, unreadable or does not match
+    /// the bundle's manifest.
+    Bundle {
+        file: String,
+        message: String,
+    },
+    /// A `SimulationBundle` replay that did not reproduce the bundle's run.
+    Replay {
+        message: String,
+    },
+    /// A `BehavioralState` whose parameters cannot be run.
+    InvalidState {
+        state: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for FlymindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlymindError::Io(err) => write!(f, "I/O error: {err}"),
+            FlymindError::Csv(err) => write!(f, "CSV error: {err}"),
+            FlymindError::Load(err) => write!(f, "load failed: {err}"),
+            FlymindError::Integrity(err) => write!(f, "inconsistent network: {err}"),
+            FlymindError::InvalidNeuronId {
+                position,
+                id,
+                neuron_count,
+            } => write!(
+                f,
+                "edge {position} references neuron {id}, but the network has {neuron_count} neurons"
+            ),
+            FlymindError::InvalidTimeStep { dt, max_dt: None } => {
+                write!(f, "time step dt = {dt} must be positive")
+            }
+            FlymindError::InvalidTimeStep {
+                dt,
+                max_dt: Some(max_dt),
+            } => write!(
+                f,
+                "time step dt = {dt} is above {max_dt}, where integration of the fastest \
+                 time constant becomes unstable"
+            ),
+            FlymindError::Config { key, line, message } => {
+                write!(f, "config")?;
+                if !key.is_empty() {
+                    write!(f, " key `{key}`")?;
+                }
+                if let Some(line) = line {
+                    write!(f, " (line {line})")?;
+                }
+                write!(f, ": {message}")
+            }
+            FlymindError::Diverged {
+                step,
+                neuron,
+                what,
+                value,
+            } => write!(
+                f,
+                "simulation diverged at step {step}: {what} of {neuron} is {value}"
+            ),
+            FlymindError::StateMismatch {
+                what,
+                state,
+                network,
+            } => write!(f, "state has {state} {what}, but the network has {network}"),
+            FlymindError::UnknownNeuron { name } => write!(f, "unknown neuron {name:?}"),
+            FlymindError::InvalidWeight { connection, weight } => write!(
+                f,
+                "weight transform gives connection {connection} weight {weight}; weights must \
+                 be finite and non-negative"
+            ),
+            FlymindError::EmptyGroup { label } => write!(f, "group {label:?} has no neurons"),
+            FlymindError::Bundle { file, message } => write!(f, "bundle file {file} {message}"),
+            FlymindError::Replay { message } => {
+                write!(f, "replay differs from its bundle: {message}")
+            }
+            FlymindError::InvalidState { state, message } => {
+                write!(f, "behavioral state {state:?} {message}")
+            }
+        }
+    }
+}
+
+impl Error for FlymindError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FlymindError::Io(err) => Some(err),
+            FlymindError::Csv(err) => Some(err),
+            FlymindError::Load(err) => Some(err),
+            FlymindError::Integrity(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FlymindError {
+    fn from(err: io::Error) -> Self {
+        FlymindError::Io(err)
+    }
+}
+
+impl From<csv::Error> for FlymindError {
+    fn from(err: csv::Error) -> Self {
+        FlymindError::Csv(err)
+    }
+}
+
+impl From<LoadError> for FlymindError {
+    fn from(err: LoadError) -> Self {
+        FlymindError::Load(err)
+    }
+}
+
+impl From<IntegrityError> for FlymindError {
+    fn from(err: IntegrityError) -> Self {
+        FlymindError::Integrity(err)
+    }
+}
+
+/// The first inconsistency `Network::check_integrity` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// Neuron `index` of `Network::neurons` has a different `id`.
+    NeuronId { index: usize, id: usize },
+    /// Connection `connection` has an endpoint `id` that is not a neuron index.
+    Endpoint {
+        connection: usize,
+        id: usize,
+        neuron_count: usize,
+    },
+    /// `map` ("outgoing_map" or "incoming_map") lists `connection` under
+    /// `neuron` when it should not, or leaves it out when it should not.
+    Adjacency {
+        map: &'static str,
+        neuron: usize,
+        connection: usize,
+    },
+    /// The name lookup does not give the first neuron called `name`.
+    NameIndex { name: String },
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::NeuronId { index, id } => {
+                write!(f, "neuron at index {index} has id {id}")
+            }
+            IntegrityError::Endpoint {
+                connection,
+                id,
+                neuron_count,
+            } => write!(
+                f,
+                "connection {connection} references neuron {id}, but the network has \
+                 {neuron_count} neurons"
+            ),
+            IntegrityError::Adjacency {
+                map,
+                neuron,
+                connection,
+            } => write!(
+                f,
+                "{map} of neuron {neuron} disagrees with connections about connection \
+                 {connection}; call rebuild_indices after editing connections"
+            ),
+            IntegrityError::NameIndex { name } => {
+                write!(
+                    f,
+                    "name lookup for {name:?} does not give the first neuron so named"
+                )
+            }
+        }
+    }
+}
+
+impl Error for IntegrityError {}
+
+/// Failures specific to reading a connectome file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The progress callback asked to stop after `rows_read` rows.
+    Cancelled { rows_read: usize },
+    /// A Type code not recognised by `LoaderConfig::synapse_type_for`, under
+    /// `UnknownCodePolicy::Error`. `line` is the 1-based line in the file.
+    UnknownSynapseCode { line: u64, code: String },
+    /// A table header without the `column` it needs.
+    MissingColumn { column: String },
+    /// A `value` in `column` that is not a number, a time no later than the
+    /// one before it, or a bad adjacency-matrix entry or row name. `line` is
+    /// the 1-based line in the file.
+    InvalidValue {
+        line: u64,
+        column: String,
+        value: String,
+    },
+    /// An adjacency matrix with `rows` rows for `columns` named neurons.
+    NotSquare { rows: usize, columns: usize },
+    /// An adjacency-matrix row with `found` weights instead of `expected`.
+    RowLength {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+    /// A row with `found` fields where at least `expected` are needed.
+    ShortRow {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+    /// A row naming a neuron outside the roster of a network built with
+    /// `Network::with_neuron_roster`.
+    NotInRoster { line: u64, name: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Cancelled { rows_read } => {
+                write!(f, "cancelled by caller after {rows_read} rows")
+            }
+            LoadError::UnknownSynapseCode { line, code } => {
+                write!(f, "line {line}: unknown synapse code {code:?}")
+            }
+            LoadError::MissingColumn { column } => write!(f, "no {column:?} column in the header"),
+            LoadError::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(f, "line {line}: invalid {column} value {value:?}"),
+            LoadError::NotSquare { rows, columns } => {
+                write!(f, "adjacency matrix has {rows} rows for {columns} neurons")
+            }
+            LoadError::RowLength {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: {found} weights, expected {expected}"),
+            LoadError::ShortRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: {found} fields, expected at least {expected}"
+            ),
+            LoadError::NotInRoster { line, name } => {
+                write!(f, "line {line}: neuron {name:?} is not in the roster")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
@@ -0,0 +1,97 @@
+//! A structured trace of what happens to each neuron during a run.
+//!
+//! Logging is off by default. With `Network::event_verbosity` raised,
+//! `update_step` appends `SimEvent`s to the network, which a run moves into
+//! `SimulationResult::events`; outside a run, `Network::take_events` collects
+//! them.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::Network;
+use crate::simulation::SimulationResult;
+
+/// How much the event log records. Each level includes the ones before it.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EventVerbosity {
+    #[default]
+    Off,
+    /// Threshold crossings, refractory periods and depolarization blocks.
+    Spikes,
+    /// Also the input of every neuron that received any, on every step.
+    Inputs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SimEvent {
+    /// `Network::current_step` when the event happened.
+    pub step: usize,
+    pub neuron: usize,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The potential crossed threshold and the neuron fired; `potential` is
+    /// the value before the reset.
+    Threshold { potential: f64 },
+    /// The neuron is held at reset for `steps` steps after this one.
+    Refractory { steps: usize },
+    /// Like `Refractory`, for a neuron going into depolarization block.
+    DepolarizationBlock { steps: usize },
+    /// Input of the step: the summed jump from chemical sends and the
+    /// continuous current from gap junctions and stimuli. Logged before the
+    /// neuron is integrated, so also for refractory neurons, which ignore it.
+    Input { jump: f64, current: f64 },
+}
+
+impl Network {
+    /// Events logged since the last run or call, oldest first.
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl SimulationResult {
+    /// Events of `neuron`, oldest first.
+    pub fn events_for(&self, neuron: usize) -> impl Iterator<Item = &SimEvent> {
+        self.events
+            .iter()
+            .filter(move |event| event.neuron == neuron)
+    }
+
+    /// Events on steps `start..end` of the network clock.
+    pub fn events_between(&self, start: usize, end: usize) -> &[SimEvent] {
+        let from = self.events.partition_point(|event| event.step < start);
+        let to = self.events.partition_point(|event| event.step < end);
+        &self.events[from..to.max(from)]
+    }
+
+    /// Write the event log as JSON lines, naming neurons from `network`:
+    /// `{"step":12,"neuron":"AVAL","kind":"threshold","potential":1.02}`.
+    pub fn write_events_jsonl<W: Write>(&self, network: &Network, mut writer: W) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            step: usize,
+            neuron: &'a str,
+            #[serde(flatten)]
+            kind: &'a EventKind,
+        }
+        for event in &self.events {
+            let line = Line {
+                step: event.step,
+                neuron: network.neurons.get(event.neuron).map_or("", |n| n.name()),
+                kind: &event.kind,
+            };
+            serde_json::to_writer(&mut writer, &line)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+}
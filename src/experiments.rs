@@ -0,0 +1,239 @@
+//! Standard stimulation protocols with pass/fail outcomes, for checking that
+//! a network and its parameters still produce textbook behaviour.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::network::Network;
+use crate::simulation::{SimulationResult, Stimulus};
+
+/// Settings of `touch_response`. Step counts are per phase, and each side is
+/// run with the same seed so the two differ only in where they are touched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchConfig {
+    pub dt: f64,
+    pub baseline_steps: usize,
+    pub stimulus_steps: usize,
+    /// Current given to every touch neuron of the stimulated side.
+    pub amplitude: f64,
+    pub seed: u64,
+    /// `Network::find_neurons` patterns of the anterior touch neurons.
+    pub anterior: Vec<String>,
+    pub posterior: Vec<String>,
+    /// Patterns of the forward command neurons.
+    pub forward: Vec<String>,
+    pub backward: Vec<String>,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        let patterns = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Self {
+            dt: 0.1,
+            baseline_steps: 2000,
+            stimulus_steps: 2000,
+            amplitude: 2.0,
+            seed: 0,
+            anterior: patterns(&["ALM", "AVM"]),
+            posterior: patterns(&["PLM", "PVM"]),
+            forward: patterns(&["AVB", "PVC"]),
+            backward: patterns(&["AVA", "AVD"]),
+        }
+    }
+}
+
+/// How the command neurons answered touch on one side.
+///
+/// The command index is `(forward - backward) / (forward + backward)` over
+/// the firing rates of the two command groups: 1 for pure forward drive, -1
+/// for pure reversal, 0 when neither group fires.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TouchResponse {
+    /// Names of the stimulated neurons.
+    pub touched: Vec<String>,
+    pub baseline_index: f64,
+    pub stimulus_index: f64,
+    /// `stimulus_index - baseline_index`.
+    pub effect: f64,
+    /// Mean firing rates of the forward and backward groups during the
+    /// stimulus, in spikes per unit time.
+    pub forward_rate: f64,
+    pub backward_rate: f64,
+    /// Whether the index moved the expected way: toward reversal for anterior
+    /// touch, toward forward for posterior.
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TouchReport {
+    pub anterior: TouchResponse,
+    pub posterior: TouchResponse,
+}
+
+impl TouchReport {
+    pub fn passed(&self) -> bool {
+        self.anterior.passed && self.posterior.passed
+    }
+}
+
+/// Touch each side of `network` in turn, as in the classic anterior and
+/// posterior touch assays: run `config.baseline_steps` without touch, then
+/// `config.stimulus_steps` with a current pulse to that side's touch neurons.
+///
+/// Each run starts from rest after `reset_state`. The network's own stimuli
+/// stay in place for both and are left as they were; neuron state is left as
+/// the posterior run ended. A side whose patterns match no neuron gets no
+/// pulse, so it only passes if the command neurons shift on their own.
+pub fn touch_response(network: &mut Network, config: &TouchConfig) -> TouchReport {
+    let group = |network: &Network, patterns: &[String]| {
+        let mut ids: Vec<usize> = patterns
+            .iter()
+            .flat_map(|p| network.find_neurons(p))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let forward = group(network, &config.forward);
+    let backward = group(network, &config.backward);
+    let anterior = group(network, &config.anterior);
+    let posterior = group(network, &config.posterior);
+
+    let mut touch = |touched: &[usize], toward_forward: bool| {
+        let own_stimuli = network.stimuli.len();
+        network.stimuli.extend(touched.iter().map(|&id| {
+            Stimulus::new(
+                id,
+                config.baseline_steps,
+                config.stimulus_steps,
+                config.amplitude,
+            )
+        }));
+        network.reset_state();
+        network.seed(config.seed);
+        let steps = config.baseline_steps + config.stimulus_steps;
+        let result = network.run_simulation(steps, config.dt);
+        network.stimuli.truncate(own_stimuli);
+
+        let baseline = 0..config.baseline_steps;
+        let stimulus = config.baseline_steps..result.steps;
+        let baseline_index = command_index(&result, &forward, &backward, baseline);
+        let stimulus_index = command_index(&result, &forward, &backward, stimulus.clone());
+        let effect = stimulus_index - baseline_index;
+        TouchResponse {
+            touched: touched
+                .iter()
+                .map(|&id| network.neurons[id].name().to_string())
+                .collect(),
+            baseline_index,
+            stimulus_index,
+            effect,
+            forward_rate: mean_rate(&result, &forward, stimulus.clone()),
+            backward_rate: mean_rate(&result, &backward, stimulus),
+            passed: if toward_forward {
+                effect > 0.0
+            } else {
+                effect < 0.0
+            },
+        }
+    };
+    TouchReport {
+        anterior: touch(&anterior, false),
+        posterior: touch(&posterior, true),
+    }
+}
+
+/// Mean firing rate of `ids` over the steps in `window`; 0 for an empty group
+/// or window.
+fn mean_rate(result: &SimulationResult, ids: &[usize], window: std::ops::Range<usize>) -> f64 {
+    let duration = window.len() as f64 * result.dt;
+    if ids.is_empty() || duration == 0.0 {
+        return 0.0;
+    }
+    let spikes: usize = ids
+        .iter()
+        .map(|&id| {
+            result.spike_trains[id]
+                .iter()
+                .filter(|step| window.contains(step))
+                .count()
+        })
+        .sum();
+    spikes as f64 / ids.len() as f64 / duration
+}
+
+fn command_index(
+    result: &SimulationResult,
+    forward: &[usize],
+    backward: &[usize],
+    window: std::ops::Range<usize>,
+) -> f64 {
+    let forward = mean_rate(result, forward, window.clone());
+    let backward = mean_rate(result, backward, window);
+    if forward + backward == 0.0 {
+        0.0
+    } else {
+        (forward - backward) / (forward + backward)
+    }
+}
+
+impl fmt::Display for TouchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Touch response\n")?;
+        writeln!(
+            f,
+            "| touch | neurons | baseline | stimulus | effect | forward | backward | result |"
+        )?;
+        writeln!(f, "|---|---|---:|---:|---:|---:|---:|---|")?;
+        for (label, response) in [("anterior", &self.anterior), ("posterior", &self.posterior)] {
+            writeln!(
+                f,
+                "| {label} | {} | {:.3} | {:.3} | {:+.3} | {:.3} | {:.3} | {} |",
+                response.touched.join(", "),
+                response.baseline_index,
+                response.stimulus_index,
+                response.effect,
+                response.forward_rate,
+                response.backward_rate,
+                if response.passed { "pass" } else { "FAIL" },
+            )?;
+        }
+        writeln!(
+            f,
+            "\nAnterior touch should move the command index toward reversal (negative \
+             effect) and posterior touch toward forward (positive effect)."
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_passes_both_sides() {
+        let mut network = Network::example_touch_circuit();
+        let report = touch_response(&mut network, &TouchConfig::default());
+        assert!(report.anterior.effect < 0.0, "{report}");
+        assert!(report.posterior.effect > 0.0, "{report}");
+        assert!(report.passed());
+        assert_eq!(report.anterior.touched, ["AVM", "ALML", "ALMR"]);
+        assert!(network.stimuli.is_empty());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["posterior"]["passed"], true);
+        assert!(!report.to_string().contains("FAIL"));
+    }
+
+    #[test]
+    fn fixture_passes_with_noise() {
+        let mut network = Network::example_touch_circuit();
+        network.lif.noise_std = 0.3;
+        let config = TouchConfig {
+            seed: 7,
+            ..TouchConfig::default()
+        };
+        assert!(touch_response(&mut network, &config).passed());
+    }
+}
@@ -0,0 +1,943 @@
+//! Writers for graph-exchange and matrix formats.
+//!
+//! Graph formats carry every connection, `ChemicalReceive` rows included, so a
+//! round trip loses nothing. Matrix formats sum weights per (from, to) pair over
+//! every type except `ChemicalReceive`, which would count each chemical synapse a
+//! second time in the reverse direction.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::annotations::AttrValue;
+use crate::flow::FlowClass;
+use crate::graph::Direction;
+use crate::network::{ExtrasynapticEdges, Network, SynapseType};
+use crate::paths::SynapseClass;
+use crate::weights::{ALL_CLASSES, WIRED_CLASSES};
+
+/// Output formats accepted by `Network::export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    Gml,
+    Dot,
+    GraphMl,
+    Gexf,
+    /// NetworkX node-link JSON.
+    Json,
+    /// The `NeuronConnect.csv` layout, readable by the loader.
+    EdgeCsv,
+    /// Dense weight matrix as CSV, rows and columns labelled by name.
+    Adjacency,
+    /// Matrix Market coordinate format, indices in neuron id order.
+    Mtx,
+    /// Cytoscape.js `elements` JSON.
+    Cytoscape,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 9] = [
+        ExportFormat::Gml,
+        ExportFormat::Dot,
+        ExportFormat::GraphMl,
+        ExportFormat::Gexf,
+        ExportFormat::Json,
+        ExportFormat::EdgeCsv,
+        ExportFormat::Adjacency,
+        ExportFormat::Mtx,
+        ExportFormat::Cytoscape,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Gml => "gml",
+            ExportFormat::Dot => "dot",
+            ExportFormat::GraphMl => "graphml",
+            ExportFormat::Gexf => "gexf",
+            ExportFormat::Json => "json",
+            ExportFormat::EdgeCsv => "edge-csv",
+            ExportFormat::Adjacency => "adjacency",
+            ExportFormat::Mtx => "mtx",
+            ExportFormat::Cytoscape => "cytoscape",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        let name = name.trim().to_ascii_lowercase();
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+impl Network {
+    pub fn export<W: Write>(&self, format: ExportFormat, writer: W) -> io::Result<()> {
+        match format {
+            ExportFormat::Gml => self.write_gml(writer),
+            ExportFormat::Dot => self.write_dot(writer),
+            ExportFormat::GraphMl => self.write_graphml(writer),
+            ExportFormat::Gexf => self.write_gexf(writer),
+            ExportFormat::Json => self.write_node_link_json(writer),
+            ExportFormat::EdgeCsv => self.write_edge_csv(writer),
+            ExportFormat::Adjacency => self.write_adjacency_csv(writer),
+            ExportFormat::Mtx => self.write_matrix_market(writer),
+            ExportFormat::Cytoscape => self.write_cytoscape_json(writer),
+        }
+    }
+
+    /// Write the network as GML, readable by NetworkX (`read_gml`), igraph and Gephi.
+    ///
+    /// Nodes carry `name`, `type`, `region` and `position`; edges carry `synapse`,
+    /// `subtype` (chemical edges only) and `weight`. Category values use the
+    /// stable `label()` strings.
+    pub fn write_gml<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, "graph [")?;
+        writeln!(out, "  directed 1")?;
+        writeln!(out, "  multigraph 1")?;
+
+        for neuron in &self.neurons {
+            writeln!(out, "  node [")?;
+            writeln!(out, "    id {}", neuron.id)?;
+            writeln!(out, "    label {}", gml_string(neuron.name()))?;
+            writeln!(out, "    name {}", gml_string(neuron.name()))?;
+            writeln!(out, "    type {}", gml_string(neuron.neuron_type.label()))?;
+            writeln!(out, "    region {}", gml_string(neuron.region.label()))?;
+            writeln!(out, "    position {}", gml_real(neuron.soma_position))?;
+            writeln!(out, "  ]")?;
+        }
+
+        for conn in &self.connections {
+            writeln!(out, "  edge [")?;
+            writeln!(out, "    source {}", conn.from_id)?;
+            writeln!(out, "    target {}", conn.to_id)?;
+            writeln!(out, "    synapse {}", gml_string(conn.synapse_type.label()))?;
+            if let Some(subtype) = conn.synapse_type.subtype() {
+                writeln!(out, "    subtype {}", gml_string(subtype.label()))?;
+            }
+            writeln!(out, "    weight {}", gml_real(conn.weight))?;
+            writeln!(out, "  ]")?;
+        }
+
+        writeln!(out, "]")?;
+        out.flush()
+    }
+
+    /// Write the network as a Graphviz digraph. Gap junctions are drawn dashed.
+    pub fn write_dot<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_dot_tagged(writer, None)
+    }
+
+    /// Like `write_dot`, giving every edge a `flow` attribute from
+    /// `edge_flow_classification`.
+    pub fn write_dot_with_flow<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_dot_tagged(writer, Some(&self.edge_flow_classification()))
+    }
+
+    fn write_dot_tagged<W: Write>(&self, writer: W, flow: Option<&[FlowClass]>) -> io::Result<()> {
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, "digraph connectome {{")?;
+        for neuron in &self.neurons {
+            writeln!(
+                out,
+                "  {} [type={}, region={}];",
+                dot_id(neuron.name()),
+                dot_id(neuron.neuron_type.label()),
+                dot_id(neuron.region.label())
+            )?;
+        }
+        for (index, conn) in self.connections.iter().enumerate() {
+            let style = if conn.synapse_type == SynapseType::GapJunction {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let flow = flow
+                .and_then(|flow| flow.get(index))
+                .map_or(String::new(), |class| {
+                    format!(", flow={}", dot_id(class.label()))
+                });
+            writeln!(
+                out,
+                "  {} -> {} [synapse={}, weight={}{style}{flow}];",
+                dot_id(self.neurons[conn.from_id].name()),
+                dot_id(self.neurons[conn.to_id].name()),
+                dot_id(conn.synapse_type.label()),
+                gml_real(conn.weight)
+            )?;
+        }
+        writeln!(out, "}}")?;
+        out.flush()
+    }
+
+    /// Write the network as GraphML with the same attributes as `write_gml`,
+    /// plus every annotation. A key whose values all share a type gets that
+    /// type (`long` and `double` together give `double`); any other is a string.
+    pub fn write_graphml<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_graphml_tagged(writer, None, None)
+    }
+
+    /// Like `write_graphml`, with a `flow` edge attribute from
+    /// `edge_flow_classification`.
+    pub fn write_graphml_with_flow<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_graphml_tagged(writer, Some(&self.edge_flow_classification()), None)
+    }
+
+    /// Like `write_graphml`, with `x` and `y` node attributes from `layout`
+    /// (indexed by neuron id, e.g. from `layout_force_directed`).
+    pub fn write_graphml_with_layout<W: Write>(
+        &self,
+        writer: W,
+        layout: &[(f64, f64)],
+    ) -> io::Result<()> {
+        self.write_graphml_tagged(writer, None, Some(layout))
+    }
+
+    fn write_graphml_tagged<W: Write>(
+        &self,
+        writer: W,
+        flow: Option<&[FlowClass]>,
+        layout: Option<&[(f64, f64)]>,
+    ) -> io::Result<()> {
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        for (id, target, ty) in [
+            ("name", "node", "string"),
+            ("type", "node", "string"),
+            ("region", "node", "string"),
+            ("position", "node", "double"),
+            ("x", "node", "double"),
+            ("y", "node", "double"),
+            ("synapse", "edge", "string"),
+            ("subtype", "edge", "string"),
+            ("weight", "edge", "double"),
+            ("flow", "edge", "string"),
+        ] {
+            let unused = match id {
+                "flow" => flow.is_none(),
+                "x" | "y" => layout.is_none(),
+                _ => false,
+            };
+            if unused {
+                continue;
+            }
+            writeln!(
+                out,
+                r#"  <key id="{id}" for="{target}" attr.name="{id}" attr.type="{ty}"/>"#
+            )?;
+        }
+        let node_keys = graphml_attr_keys(&self.annotations.neurons);
+        let edge_keys = graphml_attr_keys(&self.annotations.connections);
+        for (prefix, target, keys) in [("na", "node", &node_keys), ("ea", "edge", &edge_keys)] {
+            for (key, (index, ty)) in keys {
+                let (id, name) = (format!("{prefix}{index}"), xml_escape(key));
+                writeln!(
+                    out,
+                    r#"  <key id="{id}" for="{target}" attr.name="{name}" attr.type="{ty}"/>"#
+                )?;
+            }
+        }
+        writeln!(out, r#"  <graph id="connectome" edgedefault="directed">"#)?;
+        for neuron in &self.neurons {
+            writeln!(out, r#"    <node id="n{}">"#, neuron.id)?;
+            writeln!(
+                out,
+                r#"      <data key="name">{}</data>"#,
+                xml_escape(neuron.name())
+            )?;
+            writeln!(
+                out,
+                r#"      <data key="type">{}</data>"#,
+                neuron.neuron_type.label()
+            )?;
+            writeln!(
+                out,
+                r#"      <data key="region">{}</data>"#,
+                neuron.region.label()
+            )?;
+            writeln!(
+                out,
+                r#"      <data key="position">{}</data>"#,
+                gml_real(neuron.soma_position)
+            )?;
+            if let Some(&(x, y)) = layout.and_then(|layout| layout.get(neuron.id)) {
+                writeln!(out, r#"      <data key="x">{}</data>"#, gml_real(x))?;
+                writeln!(out, r#"      <data key="y">{}</data>"#, gml_real(y))?;
+            }
+            for (key, value) in self.neuron_attrs(neuron.id) {
+                let value = xml_escape(&value.to_string());
+                writeln!(
+                    out,
+                    r#"      <data key="na{}">{value}</data>"#,
+                    node_keys[key].0
+                )?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+        for (index, conn) in self.connections.iter().enumerate() {
+            writeln!(
+                out,
+                r#"    <edge id="e{index}" source="n{}" target="n{}">"#,
+                conn.from_id, conn.to_id
+            )?;
+            writeln!(
+                out,
+                r#"      <data key="synapse">{}</data>"#,
+                conn.synapse_type.label()
+            )?;
+            if let Some(subtype) = conn.synapse_type.subtype() {
+                writeln!(
+                    out,
+                    r#"      <data key="subtype">{}</data>"#,
+                    subtype.label()
+                )?;
+            }
+            writeln!(
+                out,
+                r#"      <data key="weight">{}</data>"#,
+                gml_real(conn.weight)
+            )?;
+            if let Some(class) = flow.and_then(|flow| flow.get(index)) {
+                writeln!(out, r#"      <data key="flow">{}</data>"#, class.label())?;
+            }
+            for (key, value) in self.connection_attrs(index) {
+                let value = xml_escape(&value.to_string());
+                writeln!(
+                    out,
+                    r#"      <data key="ea{}">{value}</data>"#,
+                    edge_keys[key].0
+                )?;
+            }
+            writeln!(out, "    </edge>")?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")?;
+        out.flush()
+    }
+
+    /// Write the network as GEXF 1.3 for Gephi.
+    pub fn write_gexf<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+        writeln!(out, r#"  <graph mode="static" defaultedgetype="directed">"#)?;
+        writeln!(out, r#"    <attributes class="node">"#)?;
+        writeln!(
+            out,
+            r#"      <attribute id="type" title="type" type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"      <attribute id="region" title="region" type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"      <attribute id="position" title="position" type="double"/>"#
+        )?;
+        writeln!(out, "    </attributes>")?;
+        writeln!(out, r#"    <attributes class="edge">"#)?;
+        writeln!(
+            out,
+            r#"      <attribute id="synapse" title="synapse" type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"      <attribute id="subtype" title="subtype" type="string"/>"#
+        )?;
+        writeln!(out, "    </attributes>")?;
+
+        writeln!(out, "    <nodes>")?;
+        for neuron in &self.neurons {
+            writeln!(
+                out,
+                r#"      <node id="{}" label="{}">"#,
+                neuron.id,
+                xml_escape(neuron.name())
+            )?;
+            writeln!(out, "        <attvalues>")?;
+            writeln!(
+                out,
+                r#"          <attvalue for="type" value="{}"/>"#,
+                neuron.neuron_type.label()
+            )?;
+            writeln!(
+                out,
+                r#"          <attvalue for="region" value="{}"/>"#,
+                neuron.region.label()
+            )?;
+            writeln!(
+                out,
+                r#"          <attvalue for="position" value="{}"/>"#,
+                gml_real(neuron.soma_position)
+            )?;
+            writeln!(out, "        </attvalues>")?;
+            writeln!(out, "      </node>")?;
+        }
+        writeln!(out, "    </nodes>")?;
+
+        writeln!(out, "    <edges>")?;
+        for (index, conn) in self.connections.iter().enumerate() {
+            writeln!(
+                out,
+                r#"      <edge id="{index}" source="{}" target="{}" weight="{}">"#,
+                conn.from_id,
+                conn.to_id,
+                gml_real(conn.weight)
+            )?;
+            writeln!(out, "        <attvalues>")?;
+            writeln!(
+                out,
+                r#"          <attvalue for="synapse" value="{}"/>"#,
+                conn.synapse_type.label()
+            )?;
+            if let Some(subtype) = conn.synapse_type.subtype() {
+                writeln!(
+                    out,
+                    r#"          <attvalue for="subtype" value="{}"/>"#,
+                    subtype.label()
+                )?;
+            }
+            writeln!(out, "        </attvalues>")?;
+            writeln!(out, "      </edge>")?;
+        }
+        writeln!(out, "    </edges>")?;
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</gexf>")?;
+        out.flush()
+    }
+
+    /// Write NetworkX node-link JSON, with nodes identified by name. Edges are
+    /// under `links`, so read it with `node_link_graph(data, edges="links")`.
+    pub fn write_node_link_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct NodeLink<'a> {
+            directed: bool,
+            multigraph: bool,
+            graph: serde_json::Map<String, serde_json::Value>,
+            nodes: Vec<NodeView<'a>>,
+            links: Vec<EdgeView<'a>>,
+        }
+
+        let doc = NodeLink {
+            directed: true,
+            multigraph: true,
+            graph: serde_json::Map::new(),
+            nodes: self.node_views(),
+            links: self.edge_views(),
+        };
+        write_json(writer, &doc)
+    }
+
+    /// Write Cytoscape.js JSON: `{"elements": {"nodes": [...], "edges": [...]}}`.
+    pub fn write_cytoscape_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_cytoscape(writer, None)
+    }
+
+    /// Like `write_cytoscape_json`, giving each node a `position` from `layout`
+    /// (indexed by neuron id, e.g. from `layout_force_directed`).
+    pub fn write_cytoscape_json_with_layout<W: Write>(
+        &self,
+        writer: W,
+        layout: &[(f64, f64)],
+    ) -> io::Result<()> {
+        self.write_cytoscape(writer, Some(layout))
+    }
+
+    fn write_cytoscape<W: Write>(
+        &self,
+        writer: W,
+        layout: Option<&[(f64, f64)]>,
+    ) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Position {
+            x: f64,
+            y: f64,
+        }
+        #[derive(Serialize)]
+        struct Data<T> {
+            data: T,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            position: Option<Position>,
+        }
+        #[derive(Serialize)]
+        struct Elements<'a> {
+            nodes: Vec<Data<NodeView<'a>>>,
+            edges: Vec<Data<EdgeView<'a>>>,
+        }
+        #[derive(Serialize)]
+        struct Doc<'a> {
+            elements: Elements<'a>,
+        }
+
+        let doc = Doc {
+            elements: Elements {
+                nodes: self
+                    .node_views()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, data)| Data {
+                        data,
+                        position: layout
+                            .and_then(|layout| layout.get(id))
+                            .map(|&(x, y)| Position { x, y }),
+                    })
+                    .collect(),
+                edges: self
+                    .edge_views()
+                    .into_iter()
+                    .map(|data| Data {
+                        data,
+                        position: None,
+                    })
+                    .collect(),
+            },
+        };
+        write_json(writer, &doc)
+    }
+
+    /// Write every connection as a `Neuron 1,Neuron 2,Type,Nbr` row.
+    pub fn write_edge_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["Neuron 1", "Neuron 2", "Type", "Nbr"])?;
+        for conn in &self.connections {
+            wtr.write_record([
+                self.neurons[conn.from_id].name(),
+                self.neurons[conn.to_id].name(),
+                conn.synapse_type.code(),
+                &conn.weight.to_string(),
+            ])?;
+        }
+        wtr.flush()
+    }
+
+    /// Write one row per neuron, in id order, with the names `write_edge_csv`
+    /// uses and its degree and strength over every connection row (so the
+    /// counts match the edge list). Neurons without any connection
+    /// (`isolated_neurons`) are written only with `include_isolated`.
+    pub fn write_neuron_table_csv<W: Write>(
+        &self,
+        writer: W,
+        include_isolated: bool,
+    ) -> io::Result<()> {
+        let isolated = if include_isolated {
+            Vec::new()
+        } else {
+            self.isolated_neurons()
+        };
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record([
+            "name",
+            "type",
+            "region",
+            "soma_position",
+            "in_degree",
+            "out_degree",
+            "in_strength",
+            "out_strength",
+        ])?;
+        for neuron in &self.neurons {
+            if isolated.binary_search(&neuron.id).is_ok() {
+                continue;
+            }
+            wtr.write_record([
+                neuron.name(),
+                neuron.neuron_type.label(),
+                neuron.region.label(),
+                &neuron.soma_position.to_string(),
+                &self.in_degree(neuron.id).to_string(),
+                &self.out_degree(neuron.id).to_string(),
+                &self.in_strength(neuron.id).to_string(),
+                &self.out_strength(neuron.id).to_string(),
+            ])?;
+        }
+        wtr.flush()
+    }
+
+    /// Summed weight from each neuron (row) to each neuron (column).
+    fn weight_matrix_entries(&self) -> BTreeMap<(usize, usize), f64> {
+        let mut entries = BTreeMap::new();
+        for conn in &self.connections {
+            if !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) && self.counts(conn) {
+                *entries.entry((conn.from_id, conn.to_id)).or_insert(0.0) += conn.weight;
+            }
+        }
+        entries
+    }
+
+    /// Dense weight matrix, `[from][to]` in neuron id order, summed as in the
+    /// matrix formats.
+    pub fn adjacency_matrix(&self) -> Vec<Vec<f64>> {
+        let n = self.neurons.len();
+        let mut matrix = vec![vec![0.0; n]; n];
+        for ((from, to), weight) in self.weight_matrix_entries() {
+            matrix[from][to] = weight;
+        }
+        matrix
+    }
+
+    /// The rows and columns of `adjacency_matrix` for `ids`, in that order:
+    /// `[i][j]` is the summed weight from `ids[i]` to `ids[j]`. Ids outside
+    /// the network give rows and columns of zeros.
+    pub fn submatrix(&self, ids: &[usize]) -> Vec<Vec<f64>> {
+        let classes: &[SynapseClass] = match self.extrasynaptic_edges {
+            ExtrasynapticEdges::Include => &ALL_CLASSES,
+            ExtrasynapticEdges::Ignore => &WIRED_CLASSES,
+        };
+        self.submatrix_of(ids, classes)
+    }
+
+    /// `submatrix`, summing only connections in `classes`, e.g. just
+    /// `GapJunction` for the electrical coupling within a class.
+    pub fn submatrix_of(&self, ids: &[usize], classes: &[SynapseClass]) -> Vec<Vec<f64>> {
+        let mut positions: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (position, &id) in ids.iter().enumerate() {
+            positions.entry(id).or_default().push(position);
+        }
+        let mut matrix = vec![vec![0.0; ids.len()]; ids.len()];
+        for (row, &id) in matrix.iter_mut().zip(ids) {
+            for index in self.connections_of(id, Direction::Out) {
+                let conn = &self.connections[index];
+                let included = conn
+                    .synapse_type
+                    .class()
+                    .is_some_and(|c| classes.contains(&c));
+                if !included || !self.counts_in_any_layer(conn) {
+                    continue;
+                }
+                for &column in positions.get(&conn.to_id).map(Vec::as_slice).unwrap_or(&[]) {
+                    row[column] += conn.weight;
+                }
+            }
+        }
+        matrix
+    }
+
+    /// `submatrix_of` with the name of each row's (and column's) neuron, empty
+    /// for ids outside the network.
+    pub fn labeled_submatrix(
+        &self,
+        ids: &[usize],
+        classes: &[SynapseClass],
+    ) -> (Vec<String>, Vec<Vec<f64>>) {
+        let names = ids
+            .iter()
+            .map(|&id| {
+                self.neurons
+                    .get(id)
+                    .map_or_else(String::new, |n| n.name().to_string())
+            })
+            .collect();
+        (names, self.submatrix_of(ids, classes))
+    }
+
+    /// Dense signed chemical weight matrix, `[from][to]` in neuron id order:
+    /// each pair's `net_drive`, excitatory sends positive and inhibitory ones
+    /// negative. Unlike `adjacency_matrix`, gap junctions and NMJs are left
+    /// out; see `to_signed_adjacency_matrix_with` to add gap junctions.
+    pub fn to_signed_adjacency_matrix(&self) -> Vec<Vec<f64>> {
+        self.to_signed_adjacency_matrix_with(false)
+    }
+
+    /// `to_signed_adjacency_matrix`, adding gap junctions as symmetric
+    /// positive entries when `gap_junctions` is set: both directions of a
+    /// pair get the mean of its two directional weights, or the one there is.
+    pub fn to_signed_adjacency_matrix_with(&self, gap_junctions: bool) -> Vec<Vec<f64>> {
+        let n = self.neurons.len();
+        let mut matrix = vec![vec![0.0; n]; n];
+        for (from, to, weight) in self.signed_adjacency_triples(gap_junctions) {
+            matrix[from][to] = weight;
+        }
+        matrix
+    }
+
+    /// The entries of `to_signed_adjacency_matrix_with` as `(from, to,
+    /// weight)`, ordered by `from` then `to`, for every pair with an
+    /// excitatory or inhibitory send (or a gap junction, if included), even
+    /// where they cancel to zero.
+    pub fn signed_adjacency_triples(&self, gap_junctions: bool) -> Vec<(usize, usize, f64)> {
+        let mut entries: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        let mut gaps: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for conn in self.connections.iter().filter(|conn| self.counts(conn)) {
+            let pair = (conn.from_id, conn.to_id);
+            match conn.synapse_type {
+                SynapseType::ChemicalSend(subtype) if !subtype.is_modulatory() => {
+                    *entries.entry(pair).or_insert(0.0) += subtype.sign() * conn.weight;
+                }
+                SynapseType::GapJunction if gap_junctions && conn.from_id != conn.to_id => {
+                    *gaps.entry(pair).or_insert(0.0) += conn.weight;
+                }
+                _ => {}
+            }
+        }
+        for (&(a, b), &forward) in &gaps {
+            let backward = gaps.get(&(b, a)).copied();
+            let weight = backward.map_or(forward, |backward| (forward + backward) / 2.0);
+            *entries.entry((a, b)).or_insert(0.0) += weight;
+            if backward.is_none() {
+                *entries.entry((b, a)).or_insert(0.0) += weight;
+            }
+        }
+        entries
+            .into_iter()
+            .map(|((from, to), weight)| (from, to, weight))
+            .collect()
+    }
+
+    /// Write the dense weight matrix: a header of names, then one row per neuron.
+    pub fn write_adjacency_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let matrix = self.adjacency_matrix();
+
+        let mut wtr = csv::Writer::from_writer(writer);
+        let mut header = vec![String::new()];
+        header.extend(self.neurons.iter().map(|n| n.name().to_string()));
+        wtr.write_record(&header)?;
+        for (neuron, row) in self.neurons.iter().zip(&matrix) {
+            let mut record = vec![neuron.name().to_string()];
+            record.extend(row.iter().map(|w| w.to_string()));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()
+    }
+
+    /// Write the weight matrix in Matrix Market coordinate form (1-based indices).
+    pub fn write_matrix_market<W: Write>(&self, writer: W) -> io::Result<()> {
+        let entries = self.weight_matrix_entries();
+        let n = self.neurons.len();
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, "%%MatrixMarket matrix coordinate real general")?;
+        writeln!(
+            out,
+            "% rows and columns are neuron ids + 1; row = presynaptic"
+        )?;
+        writeln!(out, "{n} {n} {}", entries.len())?;
+        for ((from, to), weight) in entries {
+            writeln!(out, "{} {} {weight}", from + 1, to + 1)?;
+        }
+        out.flush()
+    }
+
+    fn node_views(&self) -> Vec<NodeView<'_>> {
+        self.neurons
+            .iter()
+            .map(|n| NodeView {
+                id: n.name(),
+                neuron_type: n.neuron_type.label(),
+                region: n.region.label(),
+                position: n.soma_position,
+                attributes: self.neuron_attrs(n.id).collect(),
+            })
+            .collect()
+    }
+
+    fn edge_views(&self) -> Vec<EdgeView<'_>> {
+        self.connections
+            .iter()
+            .enumerate()
+            .map(|(index, conn)| EdgeView {
+                id: format!("e{index}"),
+                source: self.neurons[conn.from_id].name(),
+                target: self.neurons[conn.to_id].name(),
+                synapse: conn.synapse_type.label(),
+                subtype: conn.synapse_type.subtype().map(|s| s.label()),
+                weight: conn.weight,
+                attributes: self.connection_attrs(index).collect(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct NodeView<'a> {
+    id: &'a str,
+    #[serde(rename = "type")]
+    neuron_type: &'static str,
+    region: &'static str,
+    position: f64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<&'a str, &'a AttrValue>,
+}
+
+#[derive(Serialize)]
+struct EdgeView<'a> {
+    id: String,
+    source: &'a str,
+    target: &'a str,
+    synapse: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtype: Option<&'static str>,
+    weight: f64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<&'a str, &'a AttrValue>,
+}
+
+/// Every annotation key in `attrs` with its GraphML key index and type.
+fn graphml_attr_keys(
+    attrs: &HashMap<usize, BTreeMap<String, AttrValue>>,
+) -> BTreeMap<&str, (usize, &'static str)> {
+    let mut types: BTreeMap<&str, &'static str> = BTreeMap::new();
+    for (key, value) in attrs.values().flatten() {
+        let ty = value.graphml_type();
+        types
+            .entry(key)
+            .and_modify(|seen| {
+                *seen = match (*seen, ty) {
+                    (a, b) if a == b => a,
+                    ("long" | "double", "long" | "double") => "double",
+                    _ => "string",
+                }
+            })
+            .or_insert(ty);
+    }
+    types
+        .into_iter()
+        .enumerate()
+        .map(|(index, (key, ty))| (key, (index, ty)))
+        .collect()
+}
+
+fn write_json<W: Write, T: Serialize>(writer: W, value: &T) -> io::Result<()> {
+    let mut out = io::BufWriter::new(writer);
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+/// Quote a DOT identifier, escaping `"` and `\`.
+fn dot_id(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        if ch == '"' || ch == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quote a GML string. GML has no backslash escapes, so `"` and `&` (and any
+/// non-ASCII character) are written as HTML entities, which NetworkX decodes.
+fn gml_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("&quot;"),
+            '&' => quoted.push_str("&amp;"),
+            c if c.is_ascii() && !c.is_ascii_control() => quoted.push(c),
+            c => quoted.push_str(&format!("&#{};", c as u32)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// GML reals need a decimal point, and non-finite values are not valid tokens.
+fn gml_real(value: f64) -> String {
+    if value.is_finite() {
+        format!("{value:?}")
+    } else {
+        "0.0".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, NeuronType, Region};
+
+    fn network() -> Network {
+        let mut network = Network::new();
+        for name in ["A", "B", "C"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        let send = SynapseType::ChemicalSend;
+        network.add_connection(0, 1, send(ChemicalSubtype::Excitatory), 2.0);
+        network.add_connection(0, 1, send(ChemicalSubtype::Inhibitory), 0.5);
+        network.add_connection(1, 2, send(ChemicalSubtype::Inhibitory), 1.0);
+        network.add_connection(2, 0, send(ChemicalSubtype::Excitatory), 1.0);
+        network.add_connection(2, 0, send(ChemicalSubtype::Inhibitory), 1.0);
+        network.add_connection(1, 0, SynapseType::GapJunction, 1.0);
+        network.add_connection(1, 2, SynapseType::GapJunction, 2.0);
+        network.add_connection(2, 1, SynapseType::GapJunction, 4.0);
+        network
+    }
+
+    #[test]
+    fn entries_are_net_chemical_drive() {
+        let network = network();
+        assert_eq!(
+            network.signed_adjacency_triples(false),
+            vec![(0, 1, 1.5), (1, 2, -1.0), (2, 0, 0.0)]
+        );
+        let matrix = network.to_signed_adjacency_matrix();
+        assert_eq!(matrix[0], vec![0.0, 1.5, 0.0]);
+        assert_eq!(matrix[1], vec![0.0, 0.0, -1.0]);
+        for (from, to, weight) in network.signed_adjacency_triples(false) {
+            assert_eq!(network.net_drive(from, to), weight);
+        }
+    }
+
+    #[test]
+    fn gap_junctions_are_symmetric_and_positive() {
+        let triples = network().signed_adjacency_triples(true);
+        assert_eq!(
+            triples,
+            vec![
+                (0, 1, 2.5),
+                (1, 0, 1.0),
+                (1, 2, 2.0),
+                (2, 0, 0.0),
+                (2, 1, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn submatrices_follow_the_given_order() {
+        let network = Network::example_touch_circuit();
+        let full = network.adjacency_matrix();
+        let ids = [5, 2, 9, 2];
+        let sub = network.submatrix(&ids);
+        for (i, &from) in ids.iter().enumerate() {
+            for (j, &to) in ids.iter().enumerate() {
+                assert_eq!(sub[i][j], full[from][to], "{from} -> {to}");
+            }
+        }
+        let outside = network.submatrix(&[0, network.neurons.len()]);
+        assert_eq!(outside[1], vec![0.0, 0.0]);
+        assert_eq!(outside[0][1], 0.0);
+    }
+
+    #[test]
+    fn submatrices_can_keep_one_class() {
+        let network = network();
+        let (names, gaps) = network.labeled_submatrix(&[2, 1, 7], &[SynapseClass::GapJunction]);
+        assert_eq!(names, ["C", "B", ""]);
+        assert_eq!(
+            gaps,
+            vec![vec![0.0, 4.0, 0.0], vec![2.0, 0.0, 0.0], vec![0.0; 3]]
+        );
+        let chemical = network.submatrix_of(&[0, 1], &[SynapseClass::Chemical]);
+        assert_eq!(chemical, vec![vec![0.0, 2.5], vec![0.0, 0.0]]);
+    }
+}
@@ -0,0 +1,274 @@
+//! The extrasynaptic layer: monoamine and neuropeptide signalling that
+//! reaches neurons without a wired synapse, as in Bentley et al. (2016).
+//!
+//! Its edges are `SynapseType::Extrasynaptic` connections beside the wired
+//! ones, so every analysis that takes a `PathOptions` or a `GraphKind` can
+//! run on the wired network, on this layer or on both. Measures of the whole
+//! network (degrees, weight matrices, symmetry, contacts) leave the layer out
+//! unless `Network::extrasynaptic_edges` is `Include`. In runs the edges drive
+//! nothing unless `LifParams::extrasynaptic_gain` is set.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use csv::ReaderBuilder;
+
+use crate::canonical::canonicalize_name;
+use crate::error::{FlymindError, LoadError};
+use crate::loader::LoaderConfig;
+use crate::network::{Modulator, Network, SynapseType};
+
+/// What `Network::load_extrasynaptic_csv` found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtrasynapticLoadReport {
+    pub rows_read: usize,
+    pub connections_added: usize,
+    /// Source or target names that matched no neuron, in file order; their
+    /// rows are skipped.
+    pub unknown_neurons: Vec<String>,
+}
+
+/// Accepted header names (case-insensitive) of extrasynaptic file columns.
+const SOURCE_COLUMNS: [&str; 4] = ["source", "neuron1", "neuron 1", "from"];
+const TARGET_COLUMNS: [&str; 4] = ["target", "neuron2", "neuron 2", "to"];
+const TRANSMITTER_COLUMNS: [&str; 4] = ["transmitter", "monoamine", "ligand", "type"];
+const WEIGHT_COLUMNS: [&str; 2] = ["confidence", "weight"];
+
+impl Network {
+    /// Add the edges of an extrasynaptic edge list whose header names a
+    /// `source`, a `target` and a `transmitter` column and optionally a
+    /// `confidence` column (see the aliases above) to the network.
+    ///
+    /// Transmitters are read with `Modulator::from_label`; a confidence
+    /// becomes the weight, and rows without one weigh 1. Neurons must already
+    /// be in the network: rows naming others are skipped and reported. Names
+    /// go through `canonicalize_name` when `config.normalize_names` is set,
+    /// and `config.source` tags the new connections.
+    pub fn load_extrasynaptic_csv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+    ) -> Result<ExtrasynapticLoadReport, FlymindError> {
+        let file = File::open(path)?;
+        self.load_extrasynaptic_reader(BufReader::new(file), config)
+    }
+
+    /// As `load_extrasynaptic_csv`. On error nothing is added.
+    pub fn load_extrasynaptic_reader<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+    ) -> Result<ExtrasynapticLoadReport, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .delimiter(config.delimiter)
+            .from_reader(reader);
+        let header = rdr.headers()?.clone();
+        let find = |aliases: &[&str]| {
+            header
+                .iter()
+                .position(|h| aliases.iter().any(|a| h.trim().eq_ignore_ascii_case(a)))
+        };
+        let missing = |column: &str| LoadError::MissingColumn {
+            column: column.into(),
+        };
+        let source_column = find(&SOURCE_COLUMNS).ok_or_else(|| missing("source"))?;
+        let target_column = find(&TARGET_COLUMNS).ok_or_else(|| missing("target"))?;
+        let transmitter_column =
+            find(&TRANSMITTER_COLUMNS).ok_or_else(|| missing("transmitter"))?;
+        let weight_column = find(&WEIGHT_COLUMNS);
+
+        let mut report = ExtrasynapticLoadReport::default();
+        let mut edges = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            report.rows_read += 1;
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |column: usize| record.get(column).map(str::trim).filter(|v| !v.is_empty());
+            let invalid = |column: &str, value: &str| LoadError::InvalidValue {
+                line,
+                column: column.to_string(),
+                value: value.to_string(),
+            };
+            let required =
+                |column: usize, name: &str| field(column).ok_or_else(|| invalid(name, ""));
+            let source = required(source_column, "source")?;
+            let target = required(target_column, "target")?;
+            let transmitter = required(transmitter_column, "transmitter")?;
+            let modulator = Modulator::from_label(transmitter)
+                .ok_or_else(|| invalid("transmitter", transmitter))?;
+            let weight = match weight_column.and_then(field) {
+                Some(value) => value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0)
+                    .ok_or_else(|| invalid("confidence", value))?,
+                None => 1.0,
+            };
+
+            let lookup = |name: &str| {
+                if config.normalize_names {
+                    self.id_of(&canonicalize_name(name))
+                } else {
+                    self.id_of(name)
+                }
+            };
+            let (Some(from_id), Some(to_id)) = (lookup(source), lookup(target)) else {
+                for name in [source, target] {
+                    if lookup(name).is_none() {
+                        report.unknown_neurons.push(name.to_string());
+                    }
+                }
+                continue;
+            };
+            edges.push((
+                from_id,
+                to_id,
+                SynapseType::Extrasynaptic(modulator),
+                weight,
+            ));
+        }
+
+        let range = self.add_connections_bulk(edges)?;
+        let source: Option<Arc<str>> = config.source.as_deref().map(Arc::from);
+        for conn in &mut self.connections[range.clone()] {
+            conn.source.clone_from(&source);
+        }
+        report.connections_added = range.len();
+        Ok(report)
+    }
+
+    /// Indices of the extrasynaptic connections carrying `modulator`,
+    /// ascending.
+    pub fn extrasynaptic_connections(&self, modulator: Modulator) -> Vec<usize> {
+        self.connections
+            .iter()
+            .enumerate()
+            .filter(|(_, conn)| conn.synapse_type == SynapseType::Extrasynaptic(modulator))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::electrical::GraphKind;
+    use crate::network::ExtrasynapticEdges;
+    use crate::paths::{PathOptions, SynapseClass};
+    use crate::simulation::Stimulus;
+
+    const LAYER: &str = "source,target,transmitter,confidence\n\
+        RIMR,ASHL,tyramine,0.5\nALML,AVAL,serotonin,1\nAVM,AVAL,dopamine,1\n\
+        ALMR,AVAR,serotonin,1\nALMR,XYZ,serotonin,1\n";
+
+    fn layered() -> Network {
+        let mut network = Network::example_touch_circuit();
+        let report = network
+            .load_extrasynaptic_reader(LAYER.as_bytes(), &LoaderConfig::default())
+            .unwrap();
+        assert_eq!(report.connections_added, 4);
+        assert_eq!(report.unknown_neurons, vec!["XYZ".to_string()]);
+        network
+    }
+
+    fn total(matrix: &[Vec<f64>]) -> f64 {
+        matrix.iter().flatten().sum()
+    }
+
+    #[test]
+    fn measures_count_the_layer_only_when_asked() {
+        let wired = Network::example_touch_circuit();
+        let mut network = layered();
+        assert_eq!(network.adjacency_matrix(), wired.adjacency_matrix());
+        assert_eq!(network.laterality(), wired.laterality());
+        assert_eq!(network.contact_summary(), wired.contact_summary());
+        let ids: Vec<usize> = (0..network.neurons.len()).collect();
+        assert_eq!(network.submatrix(&ids), wired.submatrix(&ids));
+
+        let layer = total(
+            &network
+                .graph_view(GraphKind::Extrasynaptic)
+                .adjacency_matrix(),
+        );
+        assert_eq!(layer, 3.5);
+        let multiplex = total(&network.graph_view(GraphKind::Multiplex).adjacency_matrix());
+        let combined = total(&network.graph_view(GraphKind::Combined).adjacency_matrix());
+        assert!((multiplex - combined - layer).abs() < 1e-9);
+
+        network.extrasynaptic_edges = ExtrasynapticEdges::Include;
+        let included = total(&network.adjacency_matrix());
+        assert!((included - total(&wired.adjacency_matrix()) - layer).abs() < 1e-9);
+        assert!((total(&network.submatrix(&ids)) - included).abs() < 1e-9);
+        let laterality = network.laterality();
+        let without = wired.laterality();
+        assert_eq!(
+            laterality.ipsilateral + laterality.contralateral + laterality.midline,
+            without.ipsilateral + without.contralateral + without.midline + 4
+        );
+    }
+
+    #[test]
+    fn the_hash_tells_transmitters_apart() {
+        let serotonin = layered();
+        let mut dopamine = layered();
+        let index = dopamine.extrasynaptic_connections(Modulator::Serotonin)[0];
+        dopamine.connections[index].synapse_type = SynapseType::Extrasynaptic(Modulator::Dopamine);
+        assert_ne!(serotonin.structural_hash(), dopamine.structural_hash());
+        assert_ne!(
+            serotonin.structural_hash(),
+            Network::example_touch_circuit().structural_hash()
+        );
+    }
+
+    #[test]
+    fn paths_can_follow_the_layer_alone() {
+        let network = layered();
+        let (rimr, ashl) = (
+            network.id_of("RIMR").unwrap(),
+            network.id_of("ASHL").unwrap(),
+        );
+        let options = |kind: GraphKind| PathOptions {
+            classes: kind.classes(),
+            ..PathOptions::default()
+        };
+        assert!(network
+            .shortest_path_with(rimr, ashl, &options(GraphKind::Combined))
+            .is_none());
+        let path = network
+            .shortest_path_with(rimr, ashl, &options(GraphKind::Extrasynaptic))
+            .unwrap();
+        assert_eq!(path.neurons, vec![rimr, ashl]);
+        assert!(matches!(
+            network.connections[path.connections[0]].synapse_type,
+            SynapseType::Extrasynaptic(Modulator::Tyramine)
+        ));
+        assert!(network
+            .shortest_path_with(rimr, ashl, &options(GraphKind::Multiplex))
+            .is_some());
+        assert_eq!(
+            GraphKind::Extrasynaptic.classes(),
+            vec![SynapseClass::Extrasynaptic]
+        );
+    }
+
+    #[test]
+    fn the_gain_changes_firing_rates() {
+        let spikes = |mut network: Network, gain: f64| {
+            network.lif.extrasynaptic_gain = gain;
+            let alml = network.id_of("ALML").unwrap();
+            network.stimuli.push(Stimulus::new(alml, 10, 1500, 2.0));
+            let result = network.run_simulation(2000, 0.1);
+            let aval = network.id_of("AVAL").unwrap();
+            (result.total_spikes(), result.spike_trains[aval].len())
+        };
+        let wired = spikes(Network::example_touch_circuit(), 0.0);
+        assert_eq!(spikes(layered(), 0.0), wired);
+        let driven = spikes(layered(), 5.0);
+        assert!(driven.0 > wired.0, "{driven:?} against {wired:?}");
+        assert!(driven.1 > wired.1, "{driven:?} against {wired:?}");
+    }
+}
@@ -0,0 +1,227 @@
+//! Feedback arc sets: chemical connections whose removal leaves the chemical
+//! graph acyclic, for a feedforward view of the network.
+//!
+//! Finding a minimum set is NP-hard; this uses the Eades–Lin–Smyth ordering
+//! heuristic. Neurons are ordered by repeatedly taking sinks (to the end),
+//! sources (to the front) and otherwise the neuron with the largest surplus of
+//! outgoing over incoming edges; every connection pointing backwards in the
+//! order, self-connections included, is in the set. Ties go to the lowest id,
+//! so results are deterministic.
+
+use std::collections::BTreeMap;
+
+use crate::network::{Network, SynapseType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FasStrategy {
+    /// Surplus counts distinct partners, minimising how many neuron pairs are cut.
+    Greedy,
+    /// Surplus sums weights, so light connections are preferred for cutting.
+    #[default]
+    Weighted,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedbackArcSet {
+    /// Indices into `connections`, ascending. Parallel rows between one pair are
+    /// cut together.
+    pub removed: Vec<usize>,
+    /// Every neuron id, in an order all remaining chemical sends follow.
+    pub order: Vec<usize>,
+    /// Chemical sends the set was cut from, self-connections included.
+    pub chemical_sends: usize,
+}
+
+impl FeedbackArcSet {
+    /// How many connections are cut.
+    pub fn len(&self) -> usize {
+        self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    /// The recurrence load: the fraction of chemical sends cut, zero for a
+    /// network without any. Zero means the chemical graph is already acyclic.
+    pub fn fraction(&self) -> f64 {
+        if self.chemical_sends == 0 {
+            0.0
+        } else {
+            self.removed.len() as f64 / self.chemical_sends as f64
+        }
+    }
+}
+
+impl Network {
+    pub fn feedback_arc_set(&self, strategy: FasStrategy) -> FeedbackArcSet {
+        let n = self.neurons.len();
+        let mut pairs: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+                && conn.from_id != conn.to_id
+            {
+                *pairs.entry((conn.from_id, conn.to_id)).or_default() += conn.weight;
+            }
+        }
+        let mut successors = vec![Vec::new(); n];
+        let mut predecessors = vec![Vec::new(); n];
+        // Edge counts decide sinks and sources; `surplus` ranks the rest.
+        let mut out_count = vec![0usize; n];
+        let mut in_count = vec![0usize; n];
+        let mut surplus = vec![0.0; n];
+        for (&(from, to), &weight) in &pairs {
+            let w = match strategy {
+                FasStrategy::Greedy => 1.0,
+                FasStrategy::Weighted => weight,
+            };
+            successors[from].push((to, w));
+            predecessors[to].push((from, w));
+            out_count[from] += 1;
+            in_count[to] += 1;
+            surplus[from] += w;
+            surplus[to] -= w;
+        }
+
+        let mut active = vec![true; n];
+        let mut front = Vec::with_capacity(n);
+        let mut back = Vec::new();
+        for _ in 0..n {
+            let remaining = || (0..n).filter(|&v| active[v]);
+            let (v, at_back) = if let Some(v) = remaining().find(|&v| out_count[v] == 0) {
+                (v, true)
+            } else if let Some(v) = remaining().find(|&v| in_count[v] == 0) {
+                (v, false)
+            } else {
+                let best = remaining()
+                    .reduce(|best, v| if surplus[v] > surplus[best] { v } else { best })
+                    .expect("a neuron remains on every iteration");
+                (best, false)
+            };
+            active[v] = false;
+            for &(w, weight) in &successors[v] {
+                if active[w] {
+                    in_count[w] -= 1;
+                    surplus[w] += weight;
+                }
+            }
+            for &(u, weight) in &predecessors[v] {
+                if active[u] {
+                    out_count[u] -= 1;
+                    surplus[u] -= weight;
+                }
+            }
+            if at_back {
+                back.push(v);
+            } else {
+                front.push(v);
+            }
+        }
+        front.extend(back.into_iter().rev());
+
+        let mut position = vec![0; n];
+        for (i, &v) in front.iter().enumerate() {
+            position[v] = i;
+        }
+        let removed = self
+            .connections
+            .iter()
+            .enumerate()
+            .filter(|(_, conn)| {
+                matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+                    && position[conn.from_id] >= position[conn.to_id]
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let chemical_sends = self
+            .connections
+            .iter()
+            .filter(|conn| matches!(conn.synapse_type, SynapseType::ChemicalSend(_)))
+            .count();
+        FeedbackArcSet {
+            removed,
+            order: front,
+            chemical_sends,
+        }
+    }
+
+    /// The chemical sends left after removing a `FasStrategy::Weighted`
+    /// feedback arc set, as a network with every neuron, and the indices of the
+    /// removed connections in this one.
+    pub fn feedforward_skeleton(&self) -> (Network, Vec<usize>) {
+        let removed = self.feedback_arc_set(FasStrategy::Weighted).removed;
+        let all: Vec<usize> = (0..self.neurons.len()).collect();
+        let mut skeleton = self.subnetwork(&all);
+        let mut index = 0;
+        let mut cut = removed.iter().peekable();
+        skeleton.retain_connections(|conn| {
+            let keep = cut.next_if_eq(&&index).is_none()
+                && matches!(conn.synapse_type, SynapseType::ChemicalSend(_));
+            index += 1;
+            keep
+        });
+        (skeleton, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, NeuronType, Region};
+
+    const SEND: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    fn network(edges: &[(usize, usize, f64)]) -> Network {
+        let mut network = Network::new();
+        for name in ["A", "B", "C", "D"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        for &(from, to, weight) in edges {
+            network.add_connection(from, to, SEND, weight);
+        }
+        network
+    }
+
+    fn follows_order(network: &Network, set: &FeedbackArcSet) -> bool {
+        let mut position = vec![0; network.neurons.len()];
+        for (i, &id) in set.order.iter().enumerate() {
+            position[id] = i;
+        }
+        network
+            .connections
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !set.removed.contains(index))
+            .all(|(_, conn)| position[conn.from_id] < position[conn.to_id])
+    }
+
+    #[test]
+    fn an_acyclic_graph_carries_no_recurrence() {
+        let network = network(&[(0, 1, 1.0), (1, 2, 1.0), (0, 3, 1.0)]);
+        let set = network.feedback_arc_set(FasStrategy::Greedy);
+        assert!(set.is_empty());
+        assert_eq!(set.fraction(), 0.0);
+        assert!(follows_order(&network, &set));
+        assert_eq!(
+            Network::new()
+                .feedback_arc_set(FasStrategy::Greedy)
+                .fraction(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn a_cycle_loses_its_lightest_edge() {
+        let mut network = network(&[(0, 1, 3.0), (1, 2, 2.0), (2, 0, 0.5), (2, 3, 1.0)]);
+        let set = network.feedback_arc_set(FasStrategy::Weighted);
+        assert_eq!(set.removed, vec![2]);
+        assert_eq!((set.len(), set.chemical_sends), (1, 4));
+        assert_eq!(set.fraction(), 0.25);
+        assert!(follows_order(&network, &set));
+
+        network.add_connection(3, 3, SEND, 1.0);
+        let set = network.feedback_arc_set(FasStrategy::Weighted);
+        assert_eq!(set.removed, vec![2, 4]);
+        assert_eq!(set.fraction(), 0.4);
+    }
+}
@@ -0,0 +1,318 @@
+//! C ABI (feature `ffi`), declared in `include/flymind.h`.
+//!
+//! Networks and simulations are opaque heap handles freed by their `_free`
+//! function. Functions returning a pointer give null on failure, and those
+//! returning a count give -1; `flymind_last_error_message` then describes the
+//! failure. Variable-length results use a two-call protocol: call with a null
+//! buffer to learn the size, then again with a buffer at least that large.
+//! Panics are caught at the boundary and reported as errors.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::network::Network;
+use crate::simulation::Stimulus;
+
+/// Loaded network, `flymind_network_t` in C.
+pub struct FlymindNetwork(Network);
+
+/// A network being simulated, `flymind_sim_t` in C.
+pub struct FlymindSim {
+    network: Network,
+    dt: f64,
+    /// `(step, neuron)` spikes not yet retrieved.
+    spikes: Vec<(usize, usize)>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("NULs were replaced");
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+/// Run `body`, turning an `Err` or a panic into `fail` plus a stored message.
+fn guard<T>(fail: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_error(message);
+            fail
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_error(format!("panic: {message}"));
+            fail
+        }
+    }
+}
+
+unsafe fn network_ref<'a>(network: *const FlymindNetwork) -> Result<&'a Network, String> {
+    network
+        .as_ref()
+        .map(|n| &n.0)
+        .ok_or_else(|| "network handle is null".to_string())
+}
+
+unsafe fn sim_mut<'a>(sim: *mut FlymindSim) -> Result<&'a mut FlymindSim, String> {
+    sim.as_mut()
+        .ok_or_else(|| "simulation handle is null".to_string())
+}
+
+fn to_count(n: usize) -> isize {
+    isize::try_from(n).unwrap_or(isize::MAX)
+}
+
+/// The message of the last failed call on this thread, or null if none has
+/// failed. Valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn flymind_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Load a `NeuronConnect.csv` edge list from a NUL-terminated path.
+///
+/// # Safety
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_load_path(path: *const c_char) -> *mut FlymindNetwork {
+    guard(ptr::null_mut(), || {
+        if path.is_null() {
+            return Err("path is null".to_string());
+        }
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|_| "path is not valid UTF-8".to_string())?;
+        let network = Network::from_connect_csv(path).map_err(|err| format!("{path}: {err}"))?;
+        Ok(Box::into_raw(Box::new(FlymindNetwork(network))))
+    })
+}
+
+/// Load an edge list from `len` bytes of CSV text.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes (or be null with `len` 0).
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_load_buffer(
+    data: *const u8,
+    len: usize,
+) -> *mut FlymindNetwork {
+    guard(ptr::null_mut(), || {
+        let bytes = match (data.is_null(), len) {
+            (true, 0) => &[][..],
+            (true, _) => return Err("data is null".to_string()),
+            (false, _) => slice::from_raw_parts(data, len),
+        };
+        let network = Network::from_connect_reader(bytes).map_err(|err| err.to_string())?;
+        Ok(Box::into_raw(Box::new(FlymindNetwork(network))))
+    })
+}
+
+/// # Safety
+/// `network` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_free(network: *mut FlymindNetwork) {
+    if !network.is_null() {
+        drop(Box::from_raw(network));
+    }
+}
+
+/// # Safety
+/// `network` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_neuron_count(network: *const FlymindNetwork) -> isize {
+    guard(-1, || Ok(to_count(network_ref(network)?.neurons.len())))
+}
+
+/// # Safety
+/// `network` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_connection_count(network: *const FlymindNetwork) -> isize {
+    guard(-1, || Ok(to_count(network_ref(network)?.connections.len())))
+}
+
+/// Id of the neuron named by NUL-terminated `name`, or -1 if there is none.
+///
+/// # Safety
+/// `network` must be a live handle and `name` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_neuron_id(
+    network: *const FlymindNetwork,
+    name: *const c_char,
+) -> isize {
+    guard(-1, || {
+        let network = network_ref(network)?;
+        if name.is_null() {
+            return Err("name is null".to_string());
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+        network
+            .id_of(&name)
+            .map(to_count)
+            .ok_or_else(|| format!("unknown neuron {name:?}"))
+    })
+}
+
+/// Copy the name of neuron `id`, NUL-terminated, into `buf` of `buf_len`
+/// bytes. Returns the name's length without the NUL; nothing is written
+/// unless `buf_len` exceeds it.
+///
+/// # Safety
+/// `network` must be a live handle and `buf` null or valid for `buf_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_network_neuron_name(
+    network: *const FlymindNetwork,
+    id: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> isize {
+    guard(-1, || {
+        let network = network_ref(network)?;
+        let neuron = network.neurons.get(id).ok_or_else(|| {
+            format!(
+                "neuron id {id} out of range ({} neurons)",
+                network.neurons.len()
+            )
+        })?;
+        let name = neuron.name().as_bytes();
+        if !buf.is_null() && buf_len > name.len() {
+            ptr::copy_nonoverlapping(name.as_ptr(), buf.cast::<u8>(), name.len());
+            *buf.add(name.len()) = 0;
+        }
+        Ok(to_count(name.len()))
+    })
+}
+
+/// Start a simulation of a copy of `network` from rest with step `dt`, using
+/// the default LIF parameters.
+///
+/// # Safety
+/// `network` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_sim_new(
+    network: *const FlymindNetwork,
+    dt: f64,
+) -> *mut FlymindSim {
+    guard(ptr::null_mut(), || {
+        let source = network_ref(network)?;
+        let all: Vec<usize> = (0..source.neurons.len()).collect();
+        let network = source.subnetwork(&all);
+        network.check_dt(dt).map_err(|err| err.to_string())?;
+        Ok(Box::into_raw(Box::new(FlymindSim {
+            network,
+            dt,
+            spikes: Vec::new(),
+        })))
+    })
+}
+
+/// # Safety
+/// `sim` must be null or a handle not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_sim_free(sim: *mut FlymindSim) {
+    if !sim.is_null() {
+        drop(Box::from_raw(sim));
+    }
+}
+
+/// Advance `steps` steps, injecting `currents[i]` into neuron `i` throughout.
+/// `currents` may be null (no injection) or must hold one value per neuron,
+/// with `currents_len` saying how many. Returns 0, or -1 on error.
+///
+/// # Safety
+/// `sim` must be a live handle and `currents` null or valid for `currents_len`
+/// values.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_sim_run(
+    sim: *mut FlymindSim,
+    steps: usize,
+    currents: *const f64,
+    currents_len: usize,
+) -> c_int {
+    guard(-1, || {
+        let sim = sim_mut(sim)?;
+        let n = sim.network.neurons.len();
+        let currents = if currents.is_null() {
+            &[][..]
+        } else if currents_len == n {
+            slice::from_raw_parts(currents, currents_len)
+        } else {
+            return Err(format!("expected {n} currents, got {currents_len}"));
+        };
+
+        let start = sim.network.current_step;
+        let saved = std::mem::take(&mut sim.network.stimuli);
+        sim.network.stimuli = currents
+            .iter()
+            .enumerate()
+            .filter(|&(_, &amplitude)| amplitude != 0.0)
+            .map(|(id, &amplitude)| Stimulus::new(id, start, steps, amplitude))
+            .collect();
+        for _ in 0..steps {
+            sim.network.update_step(sim.dt);
+            let step = sim.network.current_step - 1;
+            sim.spikes.extend(
+                sim.network
+                    .neurons
+                    .iter()
+                    .filter(|neuron| neuron.just_fired)
+                    .map(|neuron| (step, neuron.id)),
+            );
+        }
+        sim.network.stimuli = saved;
+        Ok(0)
+    })
+}
+
+/// Simulated time so far, steps taken times `dt` and so in the unit of `dt`,
+/// or a negative value on error.
+///
+/// # Safety
+/// `sim` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_sim_time(sim: *mut FlymindSim) -> f64 {
+    guard(-1.0, || {
+        let sim = sim_mut(sim)?;
+        Ok(sim.network.current_step as f64 * sim.dt)
+    })
+}
+
+/// Spikes since the last retrieval, in time order: `times[k]` is when neuron
+/// `neurons[k]` fired, in the unit of `dt`. Returns how many are pending. If
+/// both arrays are non-null and `capacity` is at least that many, they are
+/// filled and the spikes are cleared; otherwise nothing is written.
+///
+/// # Safety
+/// `sim` must be a live handle; `times` and `neurons` null or valid for
+/// `capacity` values each.
+#[no_mangle]
+pub unsafe extern "C" fn flymind_sim_take_spikes(
+    sim: *mut FlymindSim,
+    times: *mut f64,
+    neurons: *mut usize,
+    capacity: usize,
+) -> isize {
+    guard(-1, || {
+        let sim = sim_mut(sim)?;
+        let pending = sim.spikes.len();
+        if !times.is_null() && !neurons.is_null() && capacity >= pending {
+            let times = slice::from_raw_parts_mut(times, pending);
+            let neurons = slice::from_raw_parts_mut(neurons, pending);
+            for (k, &(step, id)) in sim.spikes.iter().enumerate() {
+                times[k] = step as f64 * sim.dt;
+                neurons[k] = id;
+            }
+            sim.spikes.clear();
+        }
+        Ok(to_count(pending))
+    })
+}
@@ -0,0 +1,64 @@
+//! A small excerpt of the real connectome, bundled for examples, docs and
+//! quick checks that should not depend on a file on disk.
+
+use crate::network::{ChemicalSubtype, Network, SynapseType};
+
+/// Every `NeuronConnect.csv` row between the neurons of `TOUCH_CIRCUIT_TYPES`.
+const TOUCH_CIRCUIT: &str = include_str!("fixtures/touch_circuit.csv");
+const TOUCH_CIRCUIT_TYPES: &str = include_str!("fixtures/touch_circuit_types.csv");
+/// Touch receptors and the command neurons their chemical synapses inhibit,
+/// the signs of the touch circuit model of Wicks et al. (1996): anterior touch
+/// inhibits forward and posterior touch backward.
+const INHIBITED_COMMANDS: [(&[&str], &[&str]); 2] = [
+    (&["ALM", "AVM"], &["AVB", "PVC"]),
+    (&["PLM", "PVM"], &["AVA", "AVD"]),
+];
+
+impl Network {
+    /// The touch-response circuit: the six touch receptor neurons and ASH, the
+    /// command interneurons AVA, AVB, AVD and PVC with AIB and RIM, and the
+    /// first DA, DB, VA, VB, DD and VD motor neurons. 30 neurons with types and
+    /// regions set, and the 385 connections among them in the source data.
+    ///
+    /// The source data has no signs, so every chemical synapse is excitatory
+    /// except those from the touch receptors onto the opposing command neurons
+    /// (ALM and AVM onto AVB and PVC, PLM and PVM onto AVA and AVD), which are
+    /// inhibitory as in the classic circuit model. With that `touch_response`
+    /// passes on this circuit.
+    pub fn example_touch_circuit() -> Network {
+        let mut network = Network::from_connect_reader(TOUCH_CIRCUIT.as_bytes())
+            .expect("bundled touch circuit is a valid edge list");
+        let unmatched = network
+            .apply_metadata_reader(TOUCH_CIRCUIT_TYPES.as_bytes())
+            .expect("bundled touch circuit types are valid CSV");
+        debug_assert!(
+            unmatched.is_empty(),
+            "untyped fixture neurons: {unmatched:?}"
+        );
+        let in_group = |network: &Network, id: usize, prefixes: &[&str]| {
+            let name = network.neurons[id].name();
+            prefixes.iter().any(|prefix| name.starts_with(prefix))
+        };
+        for index in 0..network.connections.len() {
+            let conn = &network.connections[index];
+            let (pre, post) = match conn.synapse_type {
+                SynapseType::ChemicalSend(_) => (conn.from_id, conn.to_id),
+                SynapseType::ChemicalReceive(_) => (conn.to_id, conn.from_id),
+                _ => continue,
+            };
+            let inhibitory = INHIBITED_COMMANDS.iter().any(|(touch, commands)| {
+                in_group(&network, pre, touch) && in_group(&network, post, commands)
+            });
+            if inhibitory {
+                let conn = &mut network.connections[index];
+                conn.synapse_type = match conn.synapse_type {
+                    SynapseType::ChemicalSend(_) => {
+                        SynapseType::ChemicalSend(ChemicalSubtype::Inhibitory)
+                    }
+                    _ => SynapseType::ChemicalReceive(ChemicalSubtype::Inhibitory),
+                };
+            }
+        }
+        network
+    }
+}
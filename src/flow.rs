@@ -0,0 +1,287 @@
+//! Feedforward, feedback and lateral chemical connections relative to how many
+//! hops each neuron sits from the sensory layer.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::network::{Network, NeuronType, SynapseType};
+use crate::paths::PathOptions;
+use crate::subnetwork::hop_distances;
+
+/// Direction of a connection through the sensory-depth layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowClass {
+    /// From a shallower layer to a deeper one.
+    Feedforward,
+    /// From a deeper layer to a shallower one.
+    Feedback,
+    /// Within one layer.
+    Lateral,
+    /// An endpoint unreachable from every sensory neuron.
+    Unranked,
+    /// Not a chemical send: gap junctions, which have no direction, NMJs and
+    /// `ChemicalReceive` mirrors.
+    Excluded,
+}
+
+impl FlowClass {
+    pub const ALL: [FlowClass; 5] = [
+        FlowClass::Feedforward,
+        FlowClass::Feedback,
+        FlowClass::Lateral,
+        FlowClass::Unranked,
+        FlowClass::Excluded,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FlowClass::Feedforward => "feedforward",
+            FlowClass::Feedback => "feedback",
+            FlowClass::Lateral => "lateral",
+            FlowClass::Unranked => "unranked",
+            FlowClass::Excluded => "excluded",
+        }
+    }
+}
+
+/// Connections and weight per flow class.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlowSummary {
+    pub counts: BTreeMap<FlowClass, usize>,
+    /// Share of the total chemical send weight; `Excluded` has none.
+    pub weight_fractions: BTreeMap<FlowClass, f64>,
+}
+
+/// Counted chemical sends and their summed weight per flow class, from
+/// `Network::edge_direction_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct EdgeDirectionStats {
+    pub feedforward: usize,
+    pub lateral: usize,
+    pub feedback: usize,
+    /// Sends with an endpoint no sensory neuron reaches.
+    pub unranked: usize,
+    pub feedforward_weight: f64,
+    pub lateral_weight: f64,
+    pub feedback_weight: f64,
+    pub unranked_weight: f64,
+}
+
+impl EdgeDirectionStats {
+    /// Sends between two layered neurons.
+    pub fn ranked(&self) -> usize {
+        self.feedforward + self.lateral + self.feedback
+    }
+
+    /// Share of the ranked sends that run feedback, zero without any.
+    pub fn feedback_fraction(&self) -> f64 {
+        match self.ranked() {
+            0 => 0.0,
+            ranked => self.feedback as f64 / ranked as f64,
+        }
+    }
+
+    /// Share of the ranked sends' weight carried feedback, zero without any.
+    pub fn feedback_weight_fraction(&self) -> f64 {
+        let total = self.feedforward_weight + self.lateral_weight + self.feedback_weight;
+        if total > 0.0 {
+            self.feedback_weight / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Network {
+    /// `edge_flow_classification` tallied over the chemical sends that
+    /// `counts` accepts, so ignored self-connections are left out. The classes
+    /// are those of `flow_summary`, as counts and weights rather than
+    /// fractions, whose feedback shares can be set against those of `rewire`d
+    /// nulls.
+    pub fn edge_direction_stats(&self) -> EdgeDirectionStats {
+        let mut stats = EdgeDirectionStats::default();
+        for (conn, class) in self.connections.iter().zip(self.edge_flow_classification()) {
+            if !self.counts(conn) {
+                continue;
+            }
+            let (count, weight) = match class {
+                FlowClass::Feedforward => (&mut stats.feedforward, &mut stats.feedforward_weight),
+                FlowClass::Feedback => (&mut stats.feedback, &mut stats.feedback_weight),
+                FlowClass::Lateral => (&mut stats.lateral, &mut stats.lateral_weight),
+                FlowClass::Unranked => (&mut stats.unranked, &mut stats.unranked_weight),
+                FlowClass::Excluded => continue,
+            };
+            *count += 1;
+            *weight += conn.weight;
+        }
+        stats
+    }
+
+    /// Hops from the nearest sensory neuron along chemical sends (0 for sensory
+    /// neurons), indexed by neuron id; `None` where no sensory neuron reaches.
+    pub fn sensory_depth(&self) -> Vec<Option<usize>> {
+        let steps = self.traversal_steps(&PathOptions::default());
+        let sensory: Vec<usize> = self
+            .neurons
+            .iter()
+            .filter(|neuron| neuron.neuron_type == NeuronType::Sensory)
+            .map(|neuron| neuron.id)
+            .collect();
+        hop_distances(self.neurons.len(), &sensory, |v| {
+            steps[v].iter().map(|&(w, _)| w)
+        })
+    }
+
+    /// The flow class of every connection, parallel to `connections`.
+    pub fn edge_flow_classification(&self) -> Vec<FlowClass> {
+        let depth = self.sensory_depth();
+        self.connections
+            .iter()
+            .map(|conn| {
+                if !matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) {
+                    return FlowClass::Excluded;
+                }
+                match (depth[conn.from_id], depth[conn.to_id]) {
+                    (Some(from), Some(to)) if from < to => FlowClass::Feedforward,
+                    (Some(from), Some(to)) if from > to => FlowClass::Feedback,
+                    (Some(_), Some(_)) => FlowClass::Lateral,
+                    _ => FlowClass::Unranked,
+                }
+            })
+            .collect()
+    }
+
+    pub fn flow_summary(&self) -> FlowSummary {
+        let mut summary = FlowSummary::default();
+        let mut weights: BTreeMap<FlowClass, f64> = BTreeMap::new();
+        for class in FlowClass::ALL {
+            summary.counts.insert(class, 0);
+            if class != FlowClass::Excluded {
+                weights.insert(class, 0.0);
+            }
+        }
+        for (conn, class) in self.connections.iter().zip(self.edge_flow_classification()) {
+            *summary.counts.entry(class).or_default() += 1;
+            if let Some(weight) = weights.get_mut(&class) {
+                *weight += conn.weight;
+            }
+        }
+        let total: f64 = weights.values().sum();
+        summary.weight_fractions = weights
+            .into_iter()
+            .map(|(class, weight)| (class, if total > 0.0 { weight / total } else { 0.0 }))
+            .collect();
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, Region, SelfConnections};
+
+    const SEND: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    /// Sensory S feeds A, which feeds B; B feeds back to A, A excites
+    /// itself, and C, which nothing sensory reaches, sends to A.
+    fn network() -> Network {
+        let mut network = Network::new();
+        network.add_neuron("S", NeuronType::Sensory, Region::Head, 0.0);
+        for name in ["A", "B", "C"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        network.add_connection(0, 1, SEND, 1.0);
+        network.add_connection(1, 2, SEND, 2.0);
+        network.add_connection(2, 1, SEND, 3.0);
+        network.add_connection(1, 1, SEND, 4.0);
+        network.add_connection(3, 1, SEND, 5.0);
+        network.add_connection(1, 2, SynapseType::GapJunction, 6.0);
+        network
+    }
+
+    #[test]
+    fn sends_are_classed_by_sensory_depth() {
+        let mut network = network();
+        let stats = network.edge_direction_stats();
+        assert_eq!(
+            (
+                stats.feedforward,
+                stats.feedback,
+                stats.lateral,
+                stats.unranked
+            ),
+            (2, 1, 1, 1)
+        );
+        assert_eq!(stats.feedforward_weight, 3.0);
+        assert_eq!(stats.unranked_weight, 5.0);
+        assert_eq!(stats.feedback_fraction(), 0.25);
+        assert_eq!(stats.feedback_weight_fraction(), 0.3);
+
+        let summary = network.flow_summary();
+        for (class, count) in [
+            (FlowClass::Feedforward, stats.feedforward),
+            (FlowClass::Feedback, stats.feedback),
+            (FlowClass::Lateral, stats.lateral),
+            (FlowClass::Unranked, stats.unranked),
+        ] {
+            assert_eq!(summary.counts[&class], count, "{}", class.label());
+        }
+
+        network.self_connections = SelfConnections::Ignore;
+        assert_eq!(network.edge_direction_stats().lateral, 0);
+    }
+
+    #[test]
+    fn every_edge_of_a_three_layer_toy_is_classed() {
+        let mut network = Network::new();
+        for (name, neuron_type) in [
+            ("S1", NeuronType::Sensory),
+            ("S2", NeuronType::Sensory),
+            ("I1", NeuronType::Interneuron),
+            ("I2", NeuronType::Interneuron),
+            ("M1", NeuronType::Motor),
+            ("M2", NeuronType::Motor),
+        ] {
+            network.add_neuron(name, neuron_type, Region::Head, 0.0);
+        }
+        let edges = [
+            ("S1", "I1", SEND, FlowClass::Feedforward),
+            ("S2", "I2", SEND, FlowClass::Feedforward),
+            ("I1", "M1", SEND, FlowClass::Feedforward),
+            ("I2", "M2", SEND, FlowClass::Feedforward),
+            ("S1", "S2", SEND, FlowClass::Lateral),
+            ("I1", "I2", SEND, FlowClass::Lateral),
+            ("M2", "M1", SEND, FlowClass::Lateral),
+            ("M1", "I1", SEND, FlowClass::Feedback),
+            ("M2", "S2", SEND, FlowClass::Feedback),
+            ("I2", "S1", SynapseType::GapJunction, FlowClass::Excluded),
+        ];
+        for (from, to, synapse_type, _) in edges {
+            let (from, to) = (network.id_of(from).unwrap(), network.id_of(to).unwrap());
+            network.add_connection(from, to, synapse_type, 1.0);
+        }
+
+        assert_eq!(
+            network.sensory_depth(),
+            [Some(0), Some(0), Some(1), Some(1), Some(2), Some(2)]
+        );
+        let expected: Vec<FlowClass> = edges.iter().map(|&(_, _, _, class)| class).collect();
+        assert_eq!(network.edge_flow_classification(), expected);
+
+        let stats = network.edge_direction_stats();
+        assert_eq!(
+            (
+                stats.feedforward,
+                stats.lateral,
+                stats.feedback,
+                stats.unranked
+            ),
+            (4, 3, 2, 0)
+        );
+        let summary = network.flow_summary();
+        assert_eq!(summary.counts[&FlowClass::Excluded], 1);
+        assert_eq!(summary.weight_fractions[&FlowClass::Feedforward], 4.0 / 9.0);
+    }
+}
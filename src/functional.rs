@@ -0,0 +1,482 @@
+//! Functional connectivity from simulated activity, and how well the wiring
+//! predicts it.
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::network::Network;
+use crate::rng::Rng;
+use crate::simulation::SimulationResult;
+
+impl SimulationResult {
+    /// Pearson correlation between every pair of neurons' spike counts in bins
+    /// of `bin_steps` steps (the last bin may be shorter). Entries involving a
+    /// neuron whose count never varies are NaN.
+    pub fn coactivation_matrix(&self, bin_steps: usize) -> Vec<Vec<f64>> {
+        let counts = self.binned_counts(bin_steps);
+        let n = counts.len();
+        let mut matrix = vec![vec![f64::NAN; n]; n];
+        for i in 0..n {
+            for j in i..n {
+                let r = pearson(&counts[i], &counts[j]);
+                matrix[i][j] = r;
+                matrix[j][i] = r;
+            }
+        }
+        matrix
+    }
+
+    /// Spike-time cross-correlogram of neurons `a` and `b`: entry
+    /// `max_lag + k` counts the pairs of a spike of `a` at step `t` and a
+    /// spike of `b` at step `t + k`, for `k` from `-max_lag` to `max_lag`.
+    /// Positive lags are `b` firing after `a`, so a peak just right of the
+    /// centre suggests `a` drives `b`. Swapping `a` and `b` mirrors the result.
+    pub fn cross_correlogram(&self, a: usize, b: usize, max_lag: usize) -> Vec<usize> {
+        let mut counts = vec![0; 2 * max_lag + 1];
+        let (Some(a), Some(b)) = (self.spike_trains.get(a), self.spike_trains.get(b)) else {
+            return counts;
+        };
+        let b = b.to_vec();
+        let mut start = 0;
+        for t in a.iter() {
+            while start < b.len() && b[start] + max_lag < t {
+                start += 1;
+            }
+            for &u in b[start..].iter().take_while(|&&u| u <= t + max_lag) {
+                counts[max_lag + u - t] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Each neuron's spike count per bin of `bin_steps` steps (at least one).
+    fn binned_counts(&self, bin_steps: usize) -> Vec<Vec<f64>> {
+        self.spike_trains
+            .iter()
+            .map(|train| bin_spikes(train.iter(), self.steps, bin_steps))
+            .collect()
+    }
+}
+
+/// Count `spikes` (steps) per bin of `bin_steps` steps over `steps` steps,
+/// ignoring any at or past the end; the last bin may be shorter.
+fn bin_spikes(spikes: impl Iterator<Item = usize>, steps: usize, bin_steps: usize) -> Vec<f64> {
+    let bin_steps = bin_steps.max(1);
+    let mut row = vec![0.0; steps.div_ceil(bin_steps)];
+    for step in spikes.filter(|&step| step < steps) {
+        row[step / bin_steps] += 1.0;
+    }
+    row
+}
+
+/// Settings for `functional_connectivity`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FcOptions {
+    /// Entries involving a neuron with fewer spikes than this are NaN.
+    pub min_spikes: usize,
+    /// Width in steps of the windows spikes are jittered within for
+    /// `FcMatrix::jitter_corrected`; 0 skips the correction.
+    pub jitter_steps: usize,
+    /// Jittered copies of the run averaged for the correction.
+    pub surrogates: usize,
+    /// Seed for the jitter.
+    pub seed: u64,
+}
+
+impl Default for FcOptions {
+    fn default() -> Self {
+        Self {
+            min_spikes: 5,
+            jitter_steps: 0,
+            surrogates: 20,
+            seed: 0,
+        }
+    }
+}
+
+/// Pairwise functional connectivity of a run, from `functional_connectivity`.
+/// Every matrix is `[i][j]` by neuron id.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FcMatrix {
+    pub bin_steps: usize,
+    pub max_lag: usize,
+    /// `cross[max_lag + k]` holds, for lags `k` from `-max_lag` to `max_lag`
+    /// bins, the Pearson correlation of neuron `i`'s counts with neuron `j`'s
+    /// `k` bins later; positive lags are `i` leading `j`.
+    pub cross: Vec<Vec<Vec<f64>>>,
+    /// Zero-lag correlation minus its mean over copies of the run with each
+    /// spike moved uniformly within its jitter window, which removes what
+    /// slow co-modulation on that time scale alone would give. `None` when
+    /// `FcOptions::jitter_steps` is 0.
+    pub jitter_corrected: Option<Vec<Vec<f64>>>,
+}
+
+/// Binned spike-count cross-correlation of every pair of neurons in `result`,
+/// in bins of `bin_steps` steps at lags up to `max_lag` bins, and optionally
+/// the jitter-corrected zero-lag correlation. Entries whose correlation is
+/// undefined, or that involve a neuron below `options.min_spikes`, are NaN.
+pub fn functional_connectivity(
+    result: &SimulationResult,
+    bin_steps: usize,
+    max_lag: usize,
+    options: FcOptions,
+) -> FcMatrix {
+    let bin_steps = bin_steps.max(1);
+    let counts = result.binned_counts(bin_steps);
+    let n = counts.len();
+    let bins = counts.first().map_or(0, Vec::len);
+    let active: Vec<bool> = counts
+        .iter()
+        .map(|row| row.iter().sum::<f64>() >= options.min_spikes as f64)
+        .collect();
+
+    let mut cross = vec![vec![vec![f64::NAN; n]; n]; 2 * max_lag + 1];
+    for i in (0..n).filter(|&i| active[i]) {
+        for j in (i..n).filter(|&j| active[j]) {
+            for lag in 0..=max_lag.min(bins.saturating_sub(1)) {
+                let leading = pearson(&counts[i][..bins - lag], &counts[j][lag..]);
+                let trailing = pearson(&counts[j][..bins - lag], &counts[i][lag..]);
+                cross[max_lag + lag][i][j] = leading;
+                cross[max_lag - lag][j][i] = leading;
+                cross[max_lag + lag][j][i] = trailing;
+                cross[max_lag - lag][i][j] = trailing;
+            }
+        }
+    }
+
+    let jitter_corrected = (options.jitter_steps > 0).then(|| {
+        let zero_lag = &cross[max_lag];
+        let mut rng = Rng::new(options.seed);
+        let mut sums = vec![vec![0.0; n]; n];
+        let mut samples = vec![vec![0usize; n]; n];
+        for _ in 0..options.surrogates {
+            let jittered: Vec<Vec<f64>> = result
+                .spike_trains
+                .iter()
+                .map(|train| {
+                    let moved: Vec<usize> = train
+                        .iter()
+                        .filter(|&step| step < result.steps)
+                        .map(|step| {
+                            let start = step - step % options.jitter_steps;
+                            let width = options.jitter_steps.min(result.steps - start);
+                            start + rng.below(width)
+                        })
+                        .collect();
+                    bin_spikes(moved.into_iter(), result.steps, bin_steps)
+                })
+                .collect();
+            for i in (0..n).filter(|&i| active[i]) {
+                for j in (i..n).filter(|&j| active[j]) {
+                    let r = pearson(&jittered[i], &jittered[j]);
+                    if r.is_finite() {
+                        sums[i][j] += r;
+                        samples[i][j] += 1;
+                    }
+                }
+            }
+        }
+        let mut corrected = vec![vec![f64::NAN; n]; n];
+        for i in 0..n {
+            for j in i..n {
+                if samples[i][j] > 0 {
+                    let value = zero_lag[i][j] - sums[i][j] / samples[i][j] as f64;
+                    corrected[i][j] = value;
+                    corrected[j][i] = value;
+                }
+            }
+        }
+        corrected
+    });
+
+    FcMatrix {
+        bin_steps,
+        max_lag,
+        cross,
+        jitter_corrected,
+    }
+}
+
+impl FcMatrix {
+    /// The correlations at `lag` bins, or `None` beyond `max_lag`.
+    pub fn at_lag(&self, lag: isize) -> Option<&[Vec<f64>]> {
+        let index = self.max_lag.checked_add_signed(lag)?;
+        self.cross.get(index).map(Vec::as_slice)
+    }
+
+    pub fn zero_lag(&self) -> &[Vec<f64>] {
+        &self.cross[self.max_lag]
+    }
+
+    /// The functional weights: the jitter-corrected correlation if it was
+    /// computed, the zero-lag correlation otherwise.
+    pub fn weights(&self) -> &[Vec<f64>] {
+        self.jitter_corrected.as_deref().unwrap_or(self.zero_lag())
+    }
+
+    /// Pearson correlation between structural weight
+    /// (`Network::adjacency_matrix`) and `weights`, over ordered pairs of
+    /// distinct neurons with a non-zero structural weight and a finite
+    /// functional one; NaN if there are too few such pairs.
+    pub fn correlate_with_structure(&self, network: &Network) -> f64 {
+        let structural = network.adjacency_matrix();
+        let functional = self.weights();
+        let n = structural.len().min(functional.len());
+        let (mut weights, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i) {
+                if structural[i][j] != 0.0 && functional[i][j].is_finite() {
+                    weights.push(structural[i][j]);
+                    values.push(functional[i][j]);
+                }
+            }
+        }
+        pearson(&weights, &values)
+    }
+
+    /// Write `weights` in the layout of `Network::write_adjacency_csv`, named
+    /// by `network`'s neurons, so the two matrices line up cell for cell.
+    pub fn write_adjacency_csv<W: Write>(&self, network: &Network, writer: W) -> io::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        let mut header = vec![String::new()];
+        header.extend(network.neurons.iter().map(|n| n.name().to_string()));
+        wtr.write_record(&header)?;
+        for (neuron, row) in network.neurons.iter().zip(self.weights()) {
+            let mut record = vec![neuron.name().to_string()];
+            record.extend(row.iter().map(|w| w.to_string()));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()
+    }
+}
+
+/// How closely a functional matrix follows a structural one, over unordered
+/// neuron pairs. A pair's structural weight is the sum of both directions and
+/// its functional value the mean of both entries; pairs with a non-finite
+/// functional value are skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ConnectivityComparison {
+    pub pairs: usize,
+    /// Pairs with a non-zero structural weight.
+    pub connected_pairs: usize,
+    /// Pearson correlation between structural weight and functional value.
+    pub weight_correlation: f64,
+    /// Pearson correlation between being connected (0 or 1) and functional
+    /// value, i.e. the point-biserial correlation.
+    pub connected_correlation: f64,
+    pub mean_connected: f64,
+    pub mean_unconnected: f64,
+    /// Area under the ROC curve for separating connected from unconnected pairs
+    /// by functional value: the chance that a random connected pair scores above
+    /// a random unconnected one, ties counting half. 0.5 is chance level.
+    pub auc: f64,
+}
+
+/// Compare `functional` (e.g. `SimulationResult::coactivation_matrix`) with
+/// `structural` (e.g. `Network::adjacency_matrix`), both indexed by neuron id.
+/// Only neurons present in both are used. Undefined statistics are NaN.
+pub fn compare_connectivity(
+    structural: &[Vec<f64>],
+    functional: &[Vec<f64>],
+) -> ConnectivityComparison {
+    let n = structural.len().min(functional.len());
+    let mut weights = Vec::new();
+    let mut values = Vec::new();
+    for i in 0..n {
+        for j in i + 1..n {
+            let value = (functional[i][j] + functional[j][i]) / 2.0;
+            if value.is_finite() {
+                weights.push(structural[i][j] + structural[j][i]);
+                values.push(value);
+            }
+        }
+    }
+
+    let connected: Vec<f64> = weights
+        .iter()
+        .map(|&w| if w != 0.0 { 1.0 } else { 0.0 })
+        .collect();
+    let mean_of = |flag: f64| {
+        let picked: Vec<f64> = values
+            .iter()
+            .zip(&connected)
+            .filter(|&(_, &c)| c == flag)
+            .map(|(&v, _)| v)
+            .collect();
+        picked.iter().sum::<f64>() / picked.len() as f64
+    };
+
+    ConnectivityComparison {
+        pairs: values.len(),
+        connected_pairs: connected.iter().filter(|&&c| c == 1.0).count(),
+        weight_correlation: pearson(&weights, &values),
+        connected_correlation: pearson(&connected, &values),
+        mean_connected: mean_of(1.0),
+        mean_unconnected: mean_of(0.0),
+        auc: auc(&connected, &values),
+    }
+}
+
+impl Network {
+    /// `compare_connectivity` of this network's weights against the
+    /// co-activation of `result` in bins of `bin_steps`.
+    pub fn compare_with_activity(
+        &self,
+        result: &SimulationResult,
+        bin_steps: usize,
+    ) -> ConnectivityComparison {
+        compare_connectivity(
+            &self.adjacency_matrix(),
+            &result.coactivation_matrix(bin_steps),
+        )
+    }
+}
+
+pub(crate) fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let (mean_x, mean_y) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (&x, &y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return f64::NAN;
+    }
+    cov / (var_x * var_y).sqrt()
+}
+
+/// Pearson correlation of the ranks of `xs` and `ys`.
+pub(crate) fn spearman(xs: &[f64], ys: &[f64]) -> f64 {
+    pearson(&mean_ranks(xs), &mean_ranks(ys))
+}
+
+/// 1-based rank of each value in ascending order, tied values sharing their
+/// mean rank.
+fn mean_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let mean_rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = mean_rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Mann-Whitney estimate of the ROC area for `scores` of positive (`labels`
+/// 1.0) against negative examples, with tied scores sharing their mean rank.
+fn auc(labels: &[f64], scores: &[f64]) -> f64 {
+    let positive_rank_sum: f64 = mean_ranks(scores)
+        .iter()
+        .zip(labels)
+        .filter(|&(_, &label)| label == 1.0)
+        .map(|(&rank, _)| rank)
+        .sum();
+
+    let positives = labels.iter().filter(|&&l| l == 1.0).count() as f64;
+    let negatives = labels.len() as f64 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return f64::NAN;
+    }
+    (positive_rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, SynapseType};
+    use crate::simulation::Stimulus;
+    use crate::spikes::{SpikeStorage, SpikeTrain};
+
+    /// A and B each fire on every spike of D, whose pulses come at irregular
+    /// times; C fires on its own.
+    fn common_input_run() -> SimulationResult {
+        let mut network = Network::with_neuron_roster(&["D", "A", "B", "C"]);
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        network.add_connection(0, 1, send, 15.0);
+        network.add_connection(0, 2, send, 15.0);
+        let mut rng = Rng::new(3);
+        let mut d = 0;
+        let mut c = 7;
+        while d < 20_000 {
+            network.stimuli.push(Stimulus::new(0, d, 1, 20.0));
+            network.stimuli.push(Stimulus::new(3, c, 1, 20.0));
+            d += 40 + rng.below(200);
+            c += 40 + rng.below(200);
+        }
+        network.run_simulation(20_000, 0.1)
+    }
+
+    fn poisson_run(seed: u64, neurons: usize, steps: usize, rate: f64) -> SimulationResult {
+        let mut rng = Rng::new(seed);
+        let spike_trains = (0..neurons)
+            .map(|_| {
+                let mut train = SpikeTrain::new(SpikeStorage::Steps);
+                for step in (0..steps).filter(|_| rng.next_f64() < rate) {
+                    train.push(step);
+                }
+                train
+            })
+            .collect();
+        SimulationResult {
+            dt: 0.1,
+            steps,
+            spike_trains,
+            ..SimulationResult::default()
+        }
+    }
+
+    #[test]
+    fn common_input_gives_high_correlation() {
+        let result = common_input_run();
+        assert!(result.spike_trains[1].len() > 50);
+        let options = FcOptions {
+            jitter_steps: 500,
+            ..FcOptions::default()
+        };
+        let fc = functional_connectivity(&result, 10, 3, options);
+        assert!(fc.zero_lag()[1][2] > 0.9, "{}", fc.zero_lag()[1][2]);
+        assert!(fc.zero_lag()[1][3].abs() < 0.2);
+        assert!(fc.at_lag(2).unwrap()[1][2] < fc.zero_lag()[1][2]);
+        let corrected = fc.jitter_corrected.as_ref().unwrap();
+        assert!(corrected[1][2] > 0.8, "{}", corrected[1][2]);
+    }
+
+    #[test]
+    fn independent_poisson_neurons_are_uncorrelated() {
+        let result = poisson_run(11, 4, 40_000, 0.02);
+        let fc = functional_connectivity(&result, 10, 2, FcOptions::default());
+        for i in 0..4 {
+            for j in (0..4).filter(|&j| j != i) {
+                for lag in -2..=2 {
+                    let r = fc.at_lag(lag).unwrap()[i][j];
+                    assert!(r.abs() < 0.1, "{i} {j} at {lag}: {r}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quiet_neurons_are_masked() {
+        let mut result = poisson_run(5, 3, 5_000, 0.02);
+        result.spike_trains[2] = SpikeTrain::new(SpikeStorage::Steps);
+        result.spike_trains[2].push(100);
+        let fc = functional_connectivity(&result, 10, 1, FcOptions::default());
+        assert!(fc.zero_lag()[0][1].is_finite());
+        assert!(fc.zero_lag()[0][2].is_nan());
+        assert!(fc.at_lag(-1).unwrap()[2][0].is_nan());
+        assert!(fc.at_lag(2).is_none());
+    }
+}
@@ -0,0 +1,1065 @@
+//! Structural queries over the connection graph.
+//!
+//! The "chemical graph" is made of `ChemicalSend` connections, directed from the
+//! presynaptic to the postsynaptic neuron. `ChemicalReceive` rows record the same
+//! synapses from the other side and are left out so nothing is counted twice.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+
+use crate::network::{ChemicalSubtype, Connection, Network, NeuronType, SynapseType};
+use crate::paths::{PathOptions, Step};
+use crate::subnetwork::hop_distances;
+
+/// A longest shortest path, from `Network::diameter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diameter {
+    /// Hops along the path.
+    pub length: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// A neuron whose chemical sends break Dale's principle, from
+/// `Network::dale_violations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaleViolation {
+    pub neuron: usize,
+    /// Indices of its excitatory sends, ascending.
+    pub excitatory: Vec<usize>,
+    /// Indices of its inhibitory sends, ascending.
+    pub inhibitory: Vec<usize>,
+}
+
+/// Which side of a neuron's connections a query follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Direction {
+    #[default]
+    Out,
+    In,
+    Both,
+}
+
+impl SynapseType {
+    pub fn is_chemical(&self) -> bool {
+        matches!(
+            self,
+            SynapseType::ChemicalSend(_) | SynapseType::ChemicalReceive(_)
+        )
+    }
+}
+
+impl Network {
+    /// Distinct chemical successors of every neuron, each list sorted by id.
+    pub fn chemical_adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adjacency = vec![Vec::new(); self.neurons.len()];
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) && self.counts(conn) {
+                adjacency[conn.from_id].push(conn.to_id);
+            }
+        }
+        for list in &mut adjacency {
+            list.sort_unstable();
+            list.dedup();
+        }
+        adjacency
+    }
+
+    /// Indices of the connections touching `id` on the given side, ascending.
+    /// With `Both`, a self-connection is listed once.
+    pub fn connections_of(&self, id: usize, direction: Direction) -> Vec<usize> {
+        let out = self.outgoing_map.get(&id).map(Vec::as_slice).unwrap_or(&[]);
+        let inc = self.incoming_map.get(&id).map(Vec::as_slice).unwrap_or(&[]);
+        let mut list: Vec<usize> = match direction {
+            Direction::Out => out.to_vec(),
+            Direction::In => inc.to_vec(),
+            Direction::Both => out.iter().chain(inc).copied().collect(),
+        };
+        list.sort_unstable();
+        list.dedup();
+        list
+    }
+
+    /// Chemical sends of `subtype` across the whole network that `counts`
+    /// accepts, in index order, with subtypes as they stand now.
+    /// `ChemicalReceive` mirrors are left out so each synapse appears once;
+    /// gap junctions, NMJs and extrasynaptic edges have no subtype.
+    pub fn connections_with_subtype(
+        &self,
+        subtype: ChemicalSubtype,
+    ) -> impl Iterator<Item = &Connection> + '_ {
+        self.connections.iter().filter(move |conn| {
+            conn.synapse_type == SynapseType::ChemicalSend(subtype) && self.counts(conn)
+        })
+    }
+
+    /// Number of `connections_with_subtype`.
+    pub fn count_with_subtype(&self, subtype: ChemicalSubtype) -> usize {
+        self.connections_with_subtype(subtype).count()
+    }
+
+    /// Neurons with both excitatory and inhibitory chemical sends, ascending;
+    /// see `dale_violations`.
+    pub fn check_dale_violations(&self) -> Vec<usize> {
+        self.dale_violations().iter().map(|v| v.neuron).collect()
+    }
+
+    /// Every neuron whose chemical sends that `counts` accepts mix
+    /// excitatory and inhibitory subtypes, by id, with the sends on each
+    /// side. Dale's principle has a neuron release one transmitter at all its
+    /// synapses, so each points at mislabelled connections or a mixed
+    /// transmitter table. Modulatory sends are not compared.
+    pub fn dale_violations(&self) -> Vec<DaleViolation> {
+        (0..self.neurons.len())
+            .filter_map(|neuron| {
+                let (mut excitatory, mut inhibitory) = (Vec::new(), Vec::new());
+                for index in self.connections_of(neuron, Direction::Out) {
+                    let conn = &self.connections[index];
+                    if !self.counts(conn) {
+                        continue;
+                    }
+                    match conn.synapse_type {
+                        SynapseType::ChemicalSend(ChemicalSubtype::Excitatory) => {
+                            excitatory.push(index)
+                        }
+                        SynapseType::ChemicalSend(ChemicalSubtype::Inhibitory) => {
+                            inhibitory.push(index)
+                        }
+                        _ => {}
+                    }
+                }
+                (!excitatory.is_empty() && !inhibitory.is_empty()).then_some(DaleViolation {
+                    neuron,
+                    excitatory,
+                    inhibitory,
+                })
+            })
+            .collect()
+    }
+
+    /// Net chemical drive from `from` to `to`: the weights of their
+    /// excitatory sends minus those of their inhibitory ones, among the sends
+    /// `counts` accepts. Modulatory sends, gap junctions and NMJs add nothing,
+    /// and nor do ids outside the network.
+    pub fn net_drive(&self, from: usize, to: usize) -> f64 {
+        self.connections_of(from, Direction::Out)
+            .into_iter()
+            .map(|index| &self.connections[index])
+            .filter(|conn| conn.to_id == to && self.counts(conn))
+            .map(|conn| match conn.synapse_type {
+                SynapseType::ChemicalSend(subtype) => subtype.sign() * conn.weight,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// Number of connections leaving `id` (parallel edges count separately).
+    pub fn out_degree(&self, id: usize) -> usize {
+        self.counted(&self.outgoing_map, id).count()
+    }
+
+    /// Number of connections arriving at `id` (parallel edges count separately).
+    pub fn in_degree(&self, id: usize) -> usize {
+        self.counted(&self.incoming_map, id).count()
+    }
+
+    /// Summed weight of the connections leaving `id`.
+    pub fn out_strength(&self, id: usize) -> f64 {
+        self.counted(&self.outgoing_map, id)
+            .map(|conn| conn.weight)
+            .sum()
+    }
+
+    /// Summed weight of the connections arriving at `id`.
+    pub fn in_strength(&self, id: usize) -> f64 {
+        self.counted(&self.incoming_map, id)
+            .map(|conn| conn.weight)
+            .sum()
+    }
+
+    /// The connections `map` lists for `id` that `counts` accepts.
+    fn counted<'a>(
+        &'a self,
+        map: &'a HashMap<usize, Vec<usize>>,
+        id: usize,
+    ) -> impl Iterator<Item = &'a Connection> + 'a {
+        map.get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&c| &self.connections[c])
+            .filter(|conn| self.counts(conn))
+    }
+
+    /// Neurons with outgoing but no incoming chemical connections, ascending.
+    /// Neurons with no chemical connections at all are in neither this list nor
+    /// `pure_sinks`; those with no connections of any kind are
+    /// `isolated_neurons`.
+    pub fn pure_sources(&self) -> Vec<usize> {
+        (0..self.neurons.len())
+            .filter(|&id| self.has_chemical_send(id, true) && !self.has_chemical_send(id, false))
+            .collect()
+    }
+
+    /// Neurons with incoming but no outgoing chemical connections, ascending.
+    pub fn pure_sinks(&self) -> Vec<usize> {
+        (0..self.neurons.len())
+            .filter(|&id| self.has_chemical_send(id, false) && !self.has_chemical_send(id, true))
+            .collect()
+    }
+
+    /// Neurons with no connection of any type and no neuromuscular junction,
+    /// ascending.
+    pub fn isolated_neurons(&self) -> Vec<usize> {
+        let mut connected = vec![false; self.neurons.len()];
+        for conn in &self.connections {
+            connected[conn.from_id] = true;
+            connected[conn.to_id] = true;
+        }
+        for junction in &self.neuromuscular {
+            connected[junction.from_id] = true;
+        }
+        (0..self.neurons.len())
+            .filter(|&id| !connected[id])
+            .collect()
+    }
+
+    /// Whether `id` sends (`outgoing`) or receives at least one `ChemicalSend`.
+    fn has_chemical_send(&self, id: usize, outgoing: bool) -> bool {
+        let map = if outgoing {
+            &self.outgoing_map
+        } else {
+            &self.incoming_map
+        };
+        self.counted(map, id)
+            .any(|conn| matches!(conn.synapse_type, SynapseType::ChemicalSend(_)))
+    }
+
+    /// Weakly connected components over every connection type, largest first.
+    ///
+    /// Each component lists its neuron ids in ascending order; ties in size are
+    /// ordered by smallest member.
+    pub fn weakly_connected_components(&self) -> Vec<Vec<usize>> {
+        let n = self.neurons.len();
+        let mut parent: Vec<usize> = (0..n).collect();
+        fn find(parent: &mut [usize], mut x: usize) -> usize {
+            while parent[x] != x {
+                parent[x] = parent[parent[x]];
+                x = parent[x];
+            }
+            x
+        }
+        for conn in &self.connections {
+            let a = find(&mut parent, conn.from_id);
+            let b = find(&mut parent, conn.to_id);
+            if a != b {
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for id in 0..n {
+            let root = find(&mut parent, id);
+            groups[root].push(id);
+        }
+        let mut components: Vec<Vec<usize>> =
+            groups.into_iter().filter(|g| !g.is_empty()).collect();
+        components.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+        components
+    }
+
+    /// Strongly connected components of the chemical graph, each ascending,
+    /// in a topological order of their condensation: every chemical send
+    /// between two components runs from an earlier one to a later one. Among
+    /// components free to go next, the one with the smallest member does.
+    /// Tarjan's algorithm, linear time.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let adjacency = self.chemical_adjacency();
+        let n = adjacency.len();
+        let mut discovered = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut time = 0;
+        let mut component_of = vec![0; n];
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        for root in 0..n {
+            if discovered[root] != usize::MAX {
+                continue;
+            }
+            discovered[root] = time;
+            low[root] = time;
+            time += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            // (neuron, next successor to visit)
+            let mut calls = vec![(root, 0)];
+            while let Some(&mut (v, ref mut next)) = calls.last_mut() {
+                if let Some(&w) = adjacency[v].get(*next) {
+                    *next += 1;
+                    if discovered[w] == usize::MAX {
+                        discovered[w] = time;
+                        low[w] = time;
+                        time += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        calls.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = low[v].min(discovered[w]);
+                    }
+                    continue;
+                }
+                calls.pop();
+                if let Some(&(parent, _)) = calls.last() {
+                    low[parent] = low[parent].min(low[v]);
+                }
+                if low[v] == discovered[v] {
+                    let mut component = Vec::new();
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component_of[w] = components.len();
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    components.push(component);
+                }
+            }
+        }
+
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); components.len()];
+        for (from, targets) in adjacency.iter().enumerate() {
+            for &to in targets {
+                if component_of[from] != component_of[to] {
+                    successors[component_of[from]].push(component_of[to]);
+                }
+            }
+        }
+        let mut in_degree = vec![0usize; components.len()];
+        for list in &mut successors {
+            list.sort_unstable();
+            list.dedup();
+            for &c in list.iter() {
+                in_degree[c] += 1;
+            }
+        }
+        let mut ready: BinaryHeap<Reverse<(usize, usize)>> = (0..components.len())
+            .filter(|&c| in_degree[c] == 0)
+            .map(|c| Reverse((components[c][0], c)))
+            .collect();
+        let mut order = Vec::with_capacity(components.len());
+        while let Some(Reverse((_, c))) = ready.pop() {
+            order.push(c);
+            for &next in &successors[c] {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    ready.push(Reverse((components[next][0], next)));
+                }
+            }
+        }
+        let mut slots: Vec<Option<Vec<usize>>> = components.into_iter().map(Some).collect();
+        order
+            .into_iter()
+            .map(|c| slots[c].take().expect("each component is ordered once"))
+            .collect()
+    }
+
+    /// Every neuron in an order all chemical sends follow, from the
+    /// condensation of `strongly_connected_components`, or, if the chemical
+    /// graph has a cycle, every neuron on one: members of components with
+    /// more than one neuron and neurons with a chemical self-connection,
+    /// ascending. Remove a `feedback_arc_set` first for an order of the
+    /// rest, or use the components' order directly.
+    pub fn topological_order(&self) -> Result<Vec<usize>, Vec<usize>> {
+        let adjacency = self.chemical_adjacency();
+        let components = self.strongly_connected_components();
+        let mut cyclic: Vec<usize> = components
+            .iter()
+            .filter(|c| c.len() > 1 || adjacency[c[0]].binary_search(&c[0]).is_ok())
+            .flatten()
+            .copied()
+            .collect();
+        if cyclic.is_empty() {
+            Ok(components.into_iter().flatten().collect())
+        } else {
+            cyclic.sort_unstable();
+            Err(cyclic)
+        }
+    }
+
+    /// The longest chain of chemical sends that never returns to a strongly
+    /// connected component it has left: every neuron lies in a later
+    /// component of `strongly_connected_components` than the one before, so
+    /// the chain stays well defined however cyclic the graph is. Its hops are
+    /// the sequential stages a signal can be relayed through, a depth to set
+    /// beside `diameter`'s shortest-path width. Ties go to the chain that
+    /// ends, then runs, earliest in the components' order; empty for an
+    /// empty network.
+    pub fn longest_feedforward_path(&self) -> Vec<usize> {
+        let adjacency = self.chemical_adjacency();
+        let components = self.strongly_connected_components();
+        let component_of = component_index(&components, adjacency.len());
+        let order: Vec<usize> = components.into_iter().flatten().collect();
+        let mut length = vec![0usize; adjacency.len()];
+        let mut previous = vec![None; adjacency.len()];
+        for &from in &order {
+            for &to in &adjacency[from] {
+                if component_of[to] != component_of[from] && length[from] + 1 > length[to] {
+                    length[to] = length[from] + 1;
+                    previous[to] = Some(from);
+                }
+            }
+        }
+        let Some(&end) = order.iter().rev().max_by_key(|&&id| length[id]) else {
+            return Vec::new();
+        };
+        let mut path = vec![end];
+        while let Some(from) = previous[*path.last().expect("path starts non-empty")] {
+            path.push(from);
+        }
+        path.reverse();
+        path
+    }
+
+    /// The longest path through the condensation of the chemical graph: the
+    /// strongly connected components, each ascending, of the longest run of
+    /// components each with a chemical send into the next. Counting each
+    /// component as one stage, it is at least as long as
+    /// `longest_feedforward_path`, which must also move neuron to neuron.
+    /// Ties go as there; empty for an empty network.
+    pub fn longest_feedforward_components(&self) -> Vec<Vec<usize>> {
+        let adjacency = self.chemical_adjacency();
+        let components = self.strongly_connected_components();
+        let component_of = component_index(&components, adjacency.len());
+        let mut length = vec![0usize; components.len()];
+        let mut previous = vec![None; components.len()];
+        for (c, members) in components.iter().enumerate() {
+            for &from in members {
+                for &to in &adjacency[from] {
+                    let next = component_of[to];
+                    if next != c && length[c] + 1 > length[next] {
+                        length[next] = length[c] + 1;
+                        previous[next] = Some(c);
+                    }
+                }
+            }
+        }
+        let Some(end) = (0..components.len()).rev().max_by_key(|&c| length[c]) else {
+            return Vec::new();
+        };
+        let mut chain = vec![end];
+        while let Some(c) = previous[*chain.last().expect("chain starts non-empty")] {
+            chain.push(c);
+        }
+        let mut slots: Vec<Option<Vec<usize>>> = components.into_iter().map(Some).collect();
+        chain
+            .into_iter()
+            .rev()
+            .map(|c| slots[c].take().expect("a path visits each component once"))
+            .collect()
+    }
+
+    /// Fraction of distinct chemical pairs `a -> b` (with `a != b`) whose reverse
+    /// `b -> a` also exists. Zero when there are no such pairs.
+    pub fn reciprocity(&self) -> f64 {
+        let adjacency = self.chemical_adjacency();
+        let mut pairs = 0usize;
+        let mut reciprocated = 0usize;
+        for (a, targets) in adjacency.iter().enumerate() {
+            for &b in targets {
+                if a == b {
+                    continue;
+                }
+                pairs += 1;
+                if adjacency[b].binary_search(&a).is_ok() {
+                    reciprocated += 1;
+                }
+            }
+        }
+        if pairs == 0 {
+            0.0
+        } else {
+            reciprocated as f64 / pairs as f64
+        }
+    }
+
+    /// Fewest-hop chemical path from `from` to `to`, including both endpoints.
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        self.shortest_path_with(from, to, &PathOptions::default())
+            .map(|path| path.neurons)
+    }
+
+    /// Distinct neighbours of every neuron over all connection types, ignoring
+    /// direction and self-connections; lists sorted by id.
+    pub fn undirected_adjacency(&self) -> Vec<Vec<usize>> {
+        let mut adjacency = vec![Vec::new(); self.neurons.len()];
+        for conn in &self.connections {
+            if conn.from_id != conn.to_id {
+                adjacency[conn.from_id].push(conn.to_id);
+                adjacency[conn.to_id].push(conn.from_id);
+            }
+        }
+        for list in &mut adjacency {
+            list.sort_unstable();
+            list.dedup();
+        }
+        adjacency
+    }
+
+    /// Neuron pairs `(a, b)`, `a < b`, whose link in `undirected_adjacency` is
+    /// a bridge: removing every connection between them leaves more weakly
+    /// connected components. Ascending. Tarjan's algorithm, linear time.
+    pub fn bridges(&self) -> Vec<(usize, usize)> {
+        let adjacency = self.undirected_adjacency();
+        let n = adjacency.len();
+        let mut discovered = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut time = 0;
+        let mut bridges = Vec::new();
+        for root in 0..n {
+            if discovered[root] != usize::MAX {
+                continue;
+            }
+            discovered[root] = time;
+            low[root] = time;
+            time += 1;
+            // (neuron, its parent, next neighbour to visit)
+            let mut stack = vec![(root, usize::MAX, 0)];
+            while let Some(&mut (v, parent, ref mut next)) = stack.last_mut() {
+                if let Some(&w) = adjacency[v].get(*next) {
+                    *next += 1;
+                    if discovered[w] == usize::MAX {
+                        discovered[w] = time;
+                        low[w] = time;
+                        time += 1;
+                        stack.push((w, v, 0));
+                    } else if w != parent {
+                        low[v] = low[v].min(discovered[w]);
+                    }
+                    continue;
+                }
+                stack.pop();
+                if parent != usize::MAX {
+                    low[parent] = low[parent].min(low[v]);
+                    if low[v] > discovered[parent] {
+                        bridges.push((parent.min(v), parent.max(v)));
+                    }
+                }
+            }
+        }
+        bridges.sort_unstable();
+        bridges
+    }
+
+    /// For every neuron, how many of the neurons the chemical network reaches
+    /// from `sources` it alone connects: those no longer reached once it is
+    /// removed, itself not counted. A removed source stops being one, so a
+    /// lone source loses everything it reached. `(id, lost)` for every
+    /// neuron, most lost first, ties by id; out-of-range sources are ignored.
+    ///
+    /// Nonzero entries are the articulation points of signal flow from the
+    /// sources, candidates for an ablation with a behavioral effect. One
+    /// breadth-first search per neuron.
+    pub fn ablation_reachability_impact(&self, sources: &[usize]) -> Vec<(usize, usize)> {
+        let adjacency = self.chemical_adjacency();
+        let n = self.neurons.len();
+        let reached = |removed: usize| {
+            let sources: Vec<usize> = sources
+                .iter()
+                .copied()
+                .filter(|&s| s < n && s != removed)
+                .collect();
+            let next = |v: usize| adjacency[v].iter().copied().filter(move |&w| w != removed);
+            hop_distances(n, &sources, next)
+        };
+        let baseline = reached(usize::MAX);
+        let mut impact: Vec<(usize, usize)> = (0..n)
+            .map(|id| {
+                let after = reached(id);
+                let lost = (0..n)
+                    .filter(|&w| w != id && baseline[w].is_some() && after[w].is_none())
+                    .count();
+                (id, lost)
+            })
+            .collect();
+        impact.sort_by_key(|&(id, lost)| (Reverse(lost), id));
+        impact
+    }
+
+    /// Longest fewest-hop chemical path over all reachable ordered pairs, or
+    /// `None` if no neuron reaches another. Ties go to the smallest `from`, then
+    /// the smallest `to`.
+    pub fn diameter(&self) -> Option<Diameter> {
+        self.hop_distances().1
+    }
+
+    /// Smallest hop count within which at least `fraction` (0 to 1) of the
+    /// reachable ordered pairs of distinct neurons lie, e.g. 0.9 for the usual
+    /// 90th-percentile effective diameter. Unreachable pairs are left out;
+    /// `None` if there are no reachable pairs.
+    pub fn effective_diameter(&self, fraction: f64) -> Option<usize> {
+        let (counts, _) = self.hop_distances();
+        let total: usize = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let needed = (fraction.clamp(0.0, 1.0) * total as f64).ceil() as usize;
+        let mut within = 0;
+        for (hops, &count) in counts.iter().enumerate() {
+            within += count;
+            if within >= needed.max(1) {
+                return Some(hops);
+            }
+        }
+        Some(counts.len() - 1)
+    }
+
+    /// Fewest-hop chemical distance from every neuron (rows) to every neuron
+    /// (columns), `None` where unreachable; the diagonal is `Some(0)`. With the
+    /// `parallel` feature, rows are computed on rayon's thread pool; the result
+    /// is the same either way.
+    pub fn all_pairs_distances(&self) -> Vec<Vec<Option<usize>>> {
+        let adjacency = self.chemical_adjacency();
+        let n = self.neurons.len();
+        let row = |from: usize| hop_distances(n, &[from], |v| adjacency[v].iter().copied());
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            (0..n).into_par_iter().map(row).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            (0..n).map(row).collect()
+        }
+    }
+
+    /// Processing depth of every neuron: its fewest-hop chemical distance from
+    /// the nearest sensory neuron, so sensory neurons are layer 0. Neurons no
+    /// sensory neuron reaches get `usize::MAX`.
+    pub fn assign_layers(&self) -> HashMap<usize, usize> {
+        let adjacency = self.chemical_adjacency();
+        let sensory: Vec<usize> = self
+            .neurons
+            .iter()
+            .filter(|neuron| neuron.neuron_type == NeuronType::Sensory)
+            .map(|neuron| neuron.id)
+            .collect();
+        hop_distances(self.neurons.len(), &sensory, |v| {
+            adjacency[v].iter().copied()
+        })
+        .into_iter()
+        .enumerate()
+        .map(|(id, depth)| (id, depth.unwrap_or(usize::MAX)))
+        .collect()
+    }
+
+    /// Number of reachable ordered pairs at each hop count (index 0 is always
+    /// zero), from `all_pairs_distances`, and the pair realising the diameter.
+    fn hop_distances(&self) -> (Vec<usize>, Option<Diameter>) {
+        let mut counts = vec![0usize];
+        let mut diameter: Option<Diameter> = None;
+        for (from, row) in self.all_pairs_distances().into_iter().enumerate() {
+            for (to, hops) in row.into_iter().enumerate() {
+                let Some(hops) = hops.filter(|&h| h > 0) else {
+                    continue;
+                };
+                if counts.len() <= hops {
+                    counts.resize(hops + 1, 0);
+                }
+                counts[hops] += 1;
+                if diameter.as_ref().is_none_or(|d| hops > d.length) {
+                    diameter = Some(Diameter {
+                        length: hops,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        (counts, diameter)
+    }
+
+    /// Directed chemical cycles of at most `max_len` edges that pass through `id`.
+    ///
+    /// Each cycle starts and ends at `id` and visits no other neuron twice; a
+    /// self-connection is reported as `[id, id]`. Unlike strongly connected
+    /// components this lists the individual loops, so the cost grows quickly with
+    /// `max_len`.
+    pub fn cycles_through(&self, id: usize, max_len: usize) -> Vec<Vec<usize>> {
+        let mut cycles = Vec::new();
+        if id >= self.neurons.len() || max_len == 0 {
+            return cycles;
+        }
+
+        let adjacency = self.chemical_adjacency();
+        let mut on_path = vec![false; self.neurons.len()];
+        let mut path = vec![id];
+        on_path[id] = true;
+        extend_cycles(
+            &adjacency,
+            id,
+            max_len,
+            &mut path,
+            &mut on_path,
+            &mut cycles,
+        );
+        cycles
+    }
+
+    /// Elementary cycles of at most `max_len` edges over the connections `opts`
+    /// follows (`max_hops` is ignored), stopping after `max_cycles`.
+    ///
+    /// Each cycle is listed once, starting and ending at its lowest id, in the
+    /// same form as `cycles_through`; cycles come in order of that id, then
+    /// lexicographically. Gap junctions can be followed both ways, so each one
+    /// forms a 2-cycle when `opts` includes them.
+    pub fn find_cycles(
+        &self,
+        max_len: usize,
+        max_cycles: usize,
+        opts: &PathOptions,
+    ) -> Vec<Vec<usize>> {
+        let steps = self.traversal_steps(opts);
+        let mut search = CycleSearch {
+            steps: &steps,
+            max_len,
+            max_cycles,
+            on_path: vec![false; self.neurons.len()],
+            path: Vec::new(),
+            cycles: Vec::new(),
+        };
+        if max_len == 0 {
+            return search.cycles;
+        }
+        for start in 0..self.neurons.len() {
+            if search.cycles.len() >= max_cycles {
+                break;
+            }
+            search.path.push(start);
+            search.on_path[start] = true;
+            search.extend(start);
+            search.on_path[start] = false;
+            search.path.clear();
+        }
+        search.cycles
+    }
+
+    /// Number of cycles from `find_cycles` of each length, in edges.
+    pub fn cycle_counts(
+        &self,
+        max_len: usize,
+        max_cycles: usize,
+        opts: &PathOptions,
+    ) -> BTreeMap<usize, usize> {
+        let mut counts = BTreeMap::new();
+        for cycle in self.find_cycles(max_len, max_cycles, opts) {
+            *counts.entry(cycle.len() - 1).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Whether the chemical graph has any directed cycle, self-connections
+    /// included. Linear time, unlike enumerating them.
+    pub fn has_cycle(&self) -> bool {
+        let adjacency = self.chemical_adjacency();
+        let mut in_degree = vec![0usize; adjacency.len()];
+        for &to in adjacency.iter().flatten() {
+            in_degree[to] += 1;
+        }
+        let mut queue: VecDeque<usize> = (0..adjacency.len())
+            .filter(|&v| in_degree[v] == 0)
+            .collect();
+        let mut removed = 0;
+        while let Some(v) = queue.pop_front() {
+            removed += 1;
+            for &w in &adjacency[v] {
+                in_degree[w] -= 1;
+                if in_degree[w] == 0 {
+                    queue.push_back(w);
+                }
+            }
+        }
+        removed < adjacency.len()
+    }
+}
+
+/// Depth-first state of `Network::find_cycles`. Only neurons above the start
+/// are entered, so each cycle is found from its lowest id alone.
+struct CycleSearch<'a> {
+    steps: &'a [Vec<Step>],
+    max_len: usize,
+    max_cycles: usize,
+    on_path: Vec<bool>,
+    path: Vec<usize>,
+    cycles: Vec<Vec<usize>>,
+}
+
+impl CycleSearch<'_> {
+    fn extend(&mut self, start: usize) {
+        let last = self.path[self.path.len() - 1];
+        for &(next, _) in &self.steps[last] {
+            if self.cycles.len() >= self.max_cycles {
+                return;
+            }
+            if next == start {
+                let mut cycle = self.path.clone();
+                cycle.push(start);
+                self.cycles.push(cycle);
+            } else if next > start && !self.on_path[next] && self.path.len() < self.max_len {
+                self.on_path[next] = true;
+                self.path.push(next);
+                self.extend(start);
+                self.path.pop();
+                self.on_path[next] = false;
+            }
+        }
+    }
+}
+
+fn extend_cycles(
+    adjacency: &[Vec<usize>],
+    start: usize,
+    max_len: usize,
+    path: &mut Vec<usize>,
+    on_path: &mut [bool],
+    cycles: &mut Vec<Vec<usize>>,
+) {
+    let last = *path.last().expect("path always holds the start neuron");
+    for &next in &adjacency[last] {
+        if next == start {
+            let mut cycle = path.clone();
+            cycle.push(start);
+            cycles.push(cycle);
+        } else if !on_path[next] && path.len() < max_len {
+            on_path[next] = true;
+            path.push(next);
+            extend_cycles(adjacency, start, max_len, path, on_path, cycles);
+            path.pop();
+            on_path[next] = false;
+        }
+    }
+}
+
+/// The index in `components` of each of `n` neurons' component.
+fn component_index(components: &[Vec<usize>], n: usize) -> Vec<usize> {
+    let mut component_of = vec![0; n];
+    for (c, members) in components.iter().enumerate() {
+        for &id in members {
+            component_of[id] = c;
+        }
+    }
+    component_of
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{Modulator, Region, SelfConnections};
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+    const INHIBITORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Inhibitory);
+
+    fn network() -> Network {
+        let mut network = Network::new();
+        for name in ["A", "B", "C", "D"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        network.add_connection(0, 1, EXCITATORY, 1.0);
+        network.add_connection(0, 2, INHIBITORY, 1.0);
+        network.add_connection(0, 3, EXCITATORY, 1.0);
+        network.add_connection(1, 2, EXCITATORY, 1.0);
+        network.add_connection(
+            1,
+            3,
+            SynapseType::ChemicalSend(ChemicalSubtype::Modulatory(Modulator::Dopamine)),
+            1.0,
+        );
+        network.add_connection(
+            1,
+            2,
+            SynapseType::ChemicalReceive(ChemicalSubtype::Inhibitory),
+            1.0,
+        );
+        network.add_connection(2, 3, EXCITATORY, 1.0);
+        network.add_connection(2, 2, INHIBITORY, 1.0);
+        network
+    }
+
+    #[test]
+    fn mixed_sends_are_violations() {
+        let network = network();
+        assert_eq!(
+            network.dale_violations(),
+            vec![
+                DaleViolation {
+                    neuron: 0,
+                    excitatory: vec![0, 2],
+                    inhibitory: vec![1],
+                },
+                DaleViolation {
+                    neuron: 2,
+                    excitatory: vec![6],
+                    inhibitory: vec![7],
+                },
+            ]
+        );
+        assert_eq!(network.check_dale_violations(), vec![0, 2]);
+    }
+
+    #[test]
+    fn ignored_self_connections_are_not_compared() {
+        let mut network = network();
+        network.self_connections = SelfConnections::Ignore;
+        assert_eq!(network.check_dale_violations(), vec![0]);
+    }
+
+    #[test]
+    fn net_drive_signs_chemical_sends() {
+        let mut network = network();
+        network.add_connection(0, 1, INHIBITORY, 0.25);
+        network.add_connection(0, 1, SynapseType::GapJunction, 5.0);
+        assert_eq!(network.net_drive(0, 1), 0.75);
+        assert_eq!(network.net_drive(0, 2), -1.0);
+        assert_eq!(network.net_drive(1, 2), 1.0);
+        assert_eq!(network.net_drive(1, 3), 0.0);
+        assert_eq!(network.net_drive(1, 0), 0.0);
+        assert_eq!(network.net_drive(9, 0), 0.0);
+    }
+
+    /// Chemical sends among `n` neurons.
+    fn graph(n: usize, edges: &[(usize, usize)]) -> Network {
+        let mut network = Network::new();
+        for id in 0..n {
+            network.add_neuron(
+                &format!("N{id}"),
+                NeuronType::Interneuron,
+                Region::Head,
+                0.0,
+            );
+        }
+        for &(from, to) in edges {
+            network.add_connection(from, to, EXCITATORY, 1.0);
+        }
+        network
+    }
+
+    #[test]
+    fn components_come_in_topological_order() {
+        // {3, 4} feeds {0, 1, 2}, which feeds 5; 6 is on its own.
+        let network = graph(7, &[(0, 1), (1, 2), (2, 0), (3, 4), (4, 3), (4, 0), (2, 5)]);
+        assert_eq!(
+            network.strongly_connected_components(),
+            vec![vec![3, 4], vec![0, 1, 2], vec![5], vec![6]]
+        );
+        assert_eq!(network.topological_order(), Err(vec![0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn an_acyclic_graph_orders_every_neuron() {
+        let network = graph(5, &[(3, 1), (1, 0), (3, 0), (4, 2)]);
+        let order = network.topological_order().unwrap();
+        assert_eq!(order, vec![3, 1, 0, 4, 2]);
+        let position = |id: usize| order.iter().position(|&v| v == id).unwrap();
+        for conn in &network.connections {
+            assert!(position(conn.from_id) < position(conn.to_id));
+        }
+
+        let mut looped = network;
+        looped.add_connection(2, 2, EXCITATORY, 1.0);
+        assert_eq!(looped.topological_order(), Err(vec![2]));
+    }
+
+    #[test]
+    fn feedforward_chains_never_hop_within_a_component() {
+        // 3 feeds the cycle {0, 1, 2}, which feeds 5 and then 6; 4 is on its own.
+        let network = graph(7, &[(3, 0), (0, 1), (1, 2), (2, 0), (2, 5), (5, 6)]);
+        assert_eq!(network.longest_feedforward_path(), vec![2, 5, 6]);
+        assert_eq!(
+            network.longest_feedforward_components(),
+            vec![vec![3], vec![0, 1, 2], vec![5], vec![6]]
+        );
+        assert!(Network::new().longest_feedforward_path().is_empty());
+        assert!(Network::new().longest_feedforward_components().is_empty());
+    }
+
+    #[test]
+    fn ablating_a_chain_link_cuts_off_what_lies_beyond() {
+        // 0 -> 1 -> 2 -> 3 from source 0; 4 is never reached.
+        let mut network = graph(5, &[(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(
+            network.ablation_reachability_impact(&[0, 9]),
+            vec![(0, 3), (1, 2), (2, 1), (3, 0), (4, 0)]
+        );
+
+        // A bypass around 1 leaves 2 the only link past the source that cuts
+        // anything off.
+        network.add_connection(0, 2, EXCITATORY, 1.0);
+        assert_eq!(
+            network.ablation_reachability_impact(&[0]),
+            vec![(0, 3), (2, 1), (1, 0), (3, 0), (4, 0)]
+        );
+    }
+
+    #[test]
+    fn a_barbell_hangs_on_its_bridge() {
+        // Triangles {0, 1, 2} and {3, 4, 5}, every link both ways, joined by 2 - 3.
+        let mut edges = Vec::new();
+        for (a, b) in [(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)] {
+            edges.extend([(a, b), (b, a)]);
+        }
+        let mut network = graph(6, &edges);
+        assert_eq!(network.bridges(), vec![(2, 3)]);
+
+        let scores = network.edge_betweenness_with(&PathOptions::default());
+        let bridge = |conn: &Connection| {
+            (conn.from_id.min(conn.to_id), conn.from_id.max(conn.to_id)) == (2, 3)
+        };
+        let top = scores.iter().copied().fold(0.0, f64::max);
+        for (conn, &score) in network.connections.iter().zip(&scores) {
+            if bridge(conn) {
+                // Each of the 9 pairs across, one way.
+                assert_eq!(score, 9.0);
+            } else {
+                assert!(score < top);
+            }
+        }
+
+        assert_eq!(network.weakly_connected_components().len(), 1);
+        assert_eq!(network.retain_connections(|conn| !bridge(conn)), 2);
+        assert_eq!(
+            network.weakly_connected_components(),
+            vec![vec![0, 1, 2], vec![3, 4, 5]]
+        );
+        assert!(network.bridges().is_empty());
+    }
+
+    #[test]
+    fn overlapping_cycles_are_listed_once_each() {
+        // 0 -> 1 -> 2 -> 0 and 1 -> 2 -> 3 -> 1 share 1 -> 2; 3 loops onto itself.
+        let mut network = graph(4, &[(0, 1), (1, 2), (2, 0), (2, 3), (3, 1), (3, 3)]);
+        let opts = PathOptions::default();
+        assert_eq!(
+            network.find_cycles(3, 100, &opts),
+            vec![vec![0, 1, 2, 0], vec![1, 2, 3, 1], vec![3, 3]]
+        );
+        assert_eq!(network.find_cycles(2, 100, &opts), vec![vec![3, 3]]);
+        assert_eq!(network.find_cycles(3, 1, &opts), vec![vec![0, 1, 2, 0]]);
+        assert_eq!(
+            network.cycles_through(2, 3),
+            vec![vec![2, 0, 1, 2], vec![2, 3, 1, 2]]
+        );
+        assert_eq!(network.cycles_through(3, 1), vec![vec![3, 3]]);
+
+        network.self_connections = SelfConnections::Ignore;
+        assert_eq!(
+            network.find_cycles(3, 100, &opts),
+            vec![vec![0, 1, 2, 0], vec![1, 2, 3, 1]]
+        );
+        assert!(network.cycles_through(3, 1).is_empty());
+        assert_eq!(
+            network.cycle_counts(3, 100, &opts),
+            BTreeMap::from([(3, 2)])
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Coarse-grained connectivity between groups of neurons, e.g. how much weight
+//! runs from head sensory neurons to midbody motor neurons.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use crate::classes::neuron_class;
+use crate::network::{Network, Neuron, NeuronType, Region};
+
+const REGIONS: [Region; 4] = [Region::Head, Region::MidBody, Region::Tail, Region::Unknown];
+const NEURON_TYPES: [NeuronType; 4] = [
+    NeuronType::Sensory,
+    NeuronType::Interneuron,
+    NeuronType::Motor,
+    NeuronType::Other,
+];
+
+/// How neurons are grouped. The built-in groupings list every group, members or
+/// not, so matrices keep their shape across networks.
+pub enum GroupBy {
+    Region,
+    NeuronType,
+    /// `head/sensory`, `head/interneuron`, ...
+    RegionAndType,
+    /// Neuron classes (see `crate::classes`), in order of first appearance by
+    /// neuron id.
+    Class,
+    /// Groups named by the closure, in order of first appearance by neuron id.
+    Custom(Box<dyn Fn(&Neuron) -> String>),
+}
+
+/// Connections between groups. Row is the presynaptic group, column the
+/// postsynaptic one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupedMatrix {
+    pub labels: Vec<String>,
+    pub counts: Vec<Vec<usize>>,
+    pub weights: Vec<Vec<f64>>,
+}
+
+impl GroupBy {
+    /// All group labels and the group index of every neuron.
+    pub(crate) fn assign(&self, network: &Network) -> (Vec<String>, Vec<usize>) {
+        let fixed = |labels: Vec<String>, group: &dyn Fn(&Neuron) -> usize| {
+            let groups = network.neurons.iter().map(group).collect();
+            (labels, groups)
+        };
+        match self {
+            GroupBy::Region => fixed(
+                REGIONS.iter().map(|r| r.label().to_string()).collect(),
+                &|n| region_index(n.region),
+            ),
+            GroupBy::NeuronType => fixed(
+                NEURON_TYPES.iter().map(|t| t.label().to_string()).collect(),
+                &|n| type_index(n.neuron_type),
+            ),
+            GroupBy::RegionAndType => fixed(
+                REGIONS
+                    .iter()
+                    .flat_map(|r| {
+                        NEURON_TYPES
+                            .iter()
+                            .map(move |t| format!("{}/{}", r.label(), t.label()))
+                    })
+                    .collect(),
+                &|n| region_index(n.region) * NEURON_TYPES.len() + type_index(n.neuron_type),
+            ),
+            GroupBy::Class => by_label(network, &|n| neuron_class(n.name())),
+            GroupBy::Custom(label) => by_label(network, label),
+        }
+    }
+}
+
+/// Groups named by `label`, in order of first appearance by neuron id.
+fn by_label(network: &Network, label: &dyn Fn(&Neuron) -> String) -> (Vec<String>, Vec<usize>) {
+    let mut labels = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let groups = network
+        .neurons
+        .iter()
+        .map(|neuron| {
+            *index.entry(label(neuron)).or_insert_with_key(|key| {
+                labels.push(key.clone());
+                labels.len() - 1
+            })
+        })
+        .collect();
+    (labels, groups)
+}
+
+fn region_index(region: Region) -> usize {
+    REGIONS
+        .iter()
+        .position(|&r| r == region)
+        .unwrap_or_default()
+}
+
+fn type_index(neuron_type: NeuronType) -> usize {
+    NEURON_TYPES
+        .iter()
+        .position(|&t| t == neuron_type)
+        .unwrap_or_default()
+}
+
+impl GroupedMatrix {
+    fn zeros(labels: Vec<String>) -> Self {
+        let n = labels.len();
+        Self {
+            labels,
+            counts: vec![vec![0; n]; n],
+            weights: vec![vec![0.0; n]; n],
+        }
+    }
+
+    pub fn group_index(&self, label: &str) -> Option<usize> {
+        self.labels.iter().position(|l| l == label)
+    }
+
+    pub fn total_weight(&self) -> f64 {
+        self.weights.iter().flatten().sum()
+    }
+
+    /// One `from,to,connections,weight` row per ordered pair of groups,
+    /// including empty ones.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["from", "to", "connections", "weight"])?;
+        for (i, from) in self.labels.iter().enumerate() {
+            for (j, to) in self.labels.iter().enumerate() {
+                wtr.write_record([
+                    from,
+                    to,
+                    &self.counts[i][j].to_string(),
+                    &self.weights[i][j].to_string(),
+                ])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl Network {
+    /// Connection counts and summed weights between groups, over every row
+    /// (so `ChemicalReceive` rows count alongside their sends, as in
+    /// `NetworkStats`; use `grouped_connectivity_by_synapse` to separate them).
+    pub fn grouped_connectivity(&self, group_by: &GroupBy) -> GroupedMatrix {
+        let (labels, group) = group_by.assign(self);
+        let mut matrix = GroupedMatrix::zeros(labels);
+        for conn in self.connections.iter().filter(|conn| self.counts(conn)) {
+            let (i, j) = (group[conn.from_id], group[conn.to_id]);
+            matrix.counts[i][j] += 1;
+            matrix.weights[i][j] += conn.weight;
+        }
+        matrix
+    }
+
+    /// Like `grouped_connectivity`, with one matrix per synapse label. Every
+    /// matrix has the same groups; synapse types without rows are left out.
+    pub fn grouped_connectivity_by_synapse(
+        &self,
+        group_by: &GroupBy,
+    ) -> BTreeMap<&'static str, GroupedMatrix> {
+        let (labels, group) = group_by.assign(self);
+        let mut matrices = BTreeMap::new();
+        for conn in self.connections.iter().filter(|conn| self.counts(conn)) {
+            let matrix = matrices
+                .entry(conn.synapse_type.label())
+                .or_insert_with(|| GroupedMatrix::zeros(labels.clone()));
+            let (i, j) = (group[conn.from_id], group[conn.to_id]);
+            matrix.counts[i][j] += 1;
+            matrix.weights[i][j] += conn.weight;
+        }
+        matrices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, SelfConnections, SynapseType};
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    /// A head and a tail sensory neuron onto AVA, AVA onto a midbody motor
+    /// neuron, an AVAL-AVAR gap junction and an ignored AVAL self-loop.
+    fn network() -> Network {
+        let mut network = Network::new();
+        for (name, neuron_type, region) in [
+            ("ASHL", NeuronType::Sensory, Region::Head),
+            ("AVAL", NeuronType::Interneuron, Region::Head),
+            ("AVAR", NeuronType::Interneuron, Region::Head),
+            ("DA01", NeuronType::Motor, Region::MidBody),
+            ("PHBL", NeuronType::Sensory, Region::Tail),
+        ] {
+            network.add_neuron(name, neuron_type, region, 0.0);
+        }
+        network.add_connection(0, 1, EXCITATORY, 2.0);
+        network.add_connection(0, 2, EXCITATORY, 3.0);
+        network.add_connection(1, 3, EXCITATORY, 4.0);
+        network.add_connection(2, 3, EXCITATORY, 5.0);
+        network.add_connection(4, 2, EXCITATORY, 1.0);
+        network.add_connection(1, 2, SynapseType::GapJunction, 0.5);
+        network.add_connection(1, 1, EXCITATORY, 7.0);
+        network.self_connections = SelfConnections::Ignore;
+        network
+    }
+
+    /// `(from, to, count, weight)` of a matrix cell.
+    type Cell<'a> = (&'a str, &'a str, usize, f64);
+
+    /// Every non-empty cell, row by row.
+    fn cells(matrix: &GroupedMatrix) -> Vec<Cell<'_>> {
+        let mut cells = Vec::new();
+        for (i, from) in matrix.labels.iter().enumerate() {
+            for (j, to) in matrix.labels.iter().enumerate() {
+                if matrix.counts[i][j] > 0 {
+                    cells.push((
+                        from.as_str(),
+                        to.as_str(),
+                        matrix.counts[i][j],
+                        matrix.weights[i][j],
+                    ));
+                }
+            }
+        }
+        cells
+    }
+
+    fn side(neuron: &Neuron) -> String {
+        match neuron.name().chars().last() {
+            Some('L') => "left",
+            Some('R') => "right",
+            _ => "none",
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn each_grouping_sums_the_expected_cells() {
+        let network = network();
+        let cases: [(GroupBy, Vec<&str>, Vec<Cell>); 5] = [
+            (
+                GroupBy::Region,
+                vec!["head", "midbody", "tail", "unknown"],
+                vec![
+                    ("head", "head", 3, 5.5),
+                    ("head", "midbody", 2, 9.0),
+                    ("tail", "head", 1, 1.0),
+                ],
+            ),
+            (
+                GroupBy::NeuronType,
+                vec!["sensory", "interneuron", "motor", "other"],
+                vec![
+                    ("sensory", "interneuron", 3, 6.0),
+                    ("interneuron", "interneuron", 1, 0.5),
+                    ("interneuron", "motor", 2, 9.0),
+                ],
+            ),
+            (
+                GroupBy::RegionAndType,
+                Vec::new(),
+                vec![
+                    ("head/sensory", "head/interneuron", 2, 5.0),
+                    ("head/interneuron", "head/interneuron", 1, 0.5),
+                    ("head/interneuron", "midbody/motor", 2, 9.0),
+                    ("tail/sensory", "head/interneuron", 1, 1.0),
+                ],
+            ),
+            (
+                GroupBy::Class,
+                vec!["ASH", "AVA", "DA", "PHB"],
+                vec![
+                    ("ASH", "AVA", 2, 5.0),
+                    ("AVA", "AVA", 1, 0.5),
+                    ("AVA", "DA", 2, 9.0),
+                    ("PHB", "AVA", 1, 1.0),
+                ],
+            ),
+            (
+                GroupBy::Custom(Box::new(side)),
+                vec!["left", "right", "none"],
+                vec![
+                    ("left", "left", 1, 2.0),
+                    ("left", "right", 3, 4.5),
+                    ("left", "none", 1, 4.0),
+                    ("right", "none", 1, 5.0),
+                ],
+            ),
+        ];
+
+        let counted: f64 = network
+            .connections
+            .iter()
+            .filter(|conn| network.counts(conn))
+            .map(|conn| conn.weight)
+            .sum();
+        assert_eq!(counted, 15.5);
+        for (group_by, labels, expected) in cases {
+            let matrix = network.grouped_connectivity(&group_by);
+            if labels.is_empty() {
+                assert_eq!(matrix.labels.len(), REGIONS.len() * NEURON_TYPES.len());
+            } else {
+                assert_eq!(matrix.labels, labels);
+            }
+            assert_eq!(cells(&matrix), expected, "{:?}", matrix.labels);
+            assert_eq!(matrix.total_weight(), counted);
+        }
+    }
+
+    #[test]
+    fn synapse_matrices_add_up_to_the_whole() {
+        let network = network();
+        let by_synapse = network.grouped_connectivity_by_synapse(&GroupBy::Class);
+        assert_eq!(
+            by_synapse.keys().copied().collect::<Vec<_>>(),
+            ["chemical_send", "gap_junction"]
+        );
+        assert_eq!(cells(&by_synapse["gap_junction"]), [("AVA", "AVA", 1, 0.5)]);
+        let total: f64 = by_synapse.values().map(GroupedMatrix::total_weight).sum();
+        assert_eq!(
+            total,
+            network.grouped_connectivity(&GroupBy::Class).total_weight()
+        );
+    }
+}
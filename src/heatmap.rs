@@ -0,0 +1,224 @@
+use std::io::Write;
+
+use crate::network::Network;
+use crate::simulation::SimulationResult;
+
+/// Which per-step quantity fills the heatmap cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeatmapQuantity {
+    /// 1.0 on steps where the neuron fired, 0.0 otherwise.
+    Spike,
+    /// Recorded membrane potential; only watched neurons get a row.
+    Potential,
+    /// Spikes per unit time over the trailing `window` steps (shorter at the start of the run).
+    Rate { window: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowOrder {
+    Id,
+    /// Anterior-to-posterior by `soma_position`, ties broken by id.
+    SomaPosition,
+}
+
+/// Neuron x time matrix ready for rendering, with axis labels.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityHeatmap {
+    pub row_ids: Vec<usize>,
+    pub row_labels: Vec<String>,
+    pub times: Vec<f64>,
+    /// `values[row][column]`, one row per entry of `row_ids`.
+    pub values: Vec<Vec<f64>>,
+}
+
+impl ActivityHeatmap {
+    /// Write the matrix as CSV: a `neuron` column followed by one column per time value.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut wtr = csv::Writer::from_writer(writer);
+
+        let mut header = vec!["neuron".to_string()];
+        header.extend(self.times.iter().map(|t| t.to_string()));
+        wtr.write_record(&header)?;
+
+        for (label, row) in self.row_labels.iter().zip(&self.values) {
+            let mut record = vec![label.clone()];
+            record.extend(row.iter().map(|v| v.to_string()));
+            wtr.write_record(&record)?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl SimulationResult {
+    /// Build a heatmap of `quantity` with rows labelled by neuron name.
+    pub fn heatmap(
+        &self,
+        network: &Network,
+        quantity: HeatmapQuantity,
+        order: RowOrder,
+    ) -> ActivityHeatmap {
+        let mut row_ids: Vec<usize> = match quantity {
+            HeatmapQuantity::Potential => self.potentials.keys().copied().collect(),
+            _ => (0..self.spike_trains.len()).collect(),
+        };
+        row_ids.retain(|&id| id < network.neurons.len());
+        row_ids.sort_unstable();
+        if order == RowOrder::SomaPosition {
+            row_ids.sort_by(|&a, &b| {
+                network.neurons[a]
+                    .soma_position
+                    .total_cmp(&network.neurons[b].soma_position)
+            });
+        }
+
+        let values = row_ids
+            .iter()
+            .map(|&id| match quantity {
+                HeatmapQuantity::Spike => self.spike_row(id),
+                HeatmapQuantity::Potential => self.potential_row(id),
+                HeatmapQuantity::Rate { window } => self.rate_row(id, window.max(1)),
+            })
+            .collect();
+
+        ActivityHeatmap {
+            row_labels: row_ids
+                .iter()
+                .map(|&id| network.neurons[id].name().to_string())
+                .collect(),
+            row_ids,
+            times: (0..self.steps).map(|step| step as f64 * self.dt).collect(),
+            values,
+        }
+    }
+
+    /// Potentials expanded to one value per step, holding each strided sample.
+    /// Steps past the end of the trace are NaN, so every row is as long as
+    /// `times`.
+    fn potential_row(&self, id: usize) -> Vec<f64> {
+        let trace = &self.potentials[&id];
+        let stride = self.potential_stride.max(1);
+        (0..self.steps)
+            .map(|step| trace.get(step / stride).copied().unwrap_or(f64::NAN))
+            .collect()
+    }
+
+    fn spike_row(&self, id: usize) -> Vec<f64> {
+        let mut row = vec![0.0; self.steps];
+        for step in &self.spike_trains[id] {
+            if step < self.steps {
+                row[step] = 1.0;
+            }
+        }
+        row
+    }
+
+    fn rate_row(&self, id: usize, window: usize) -> Vec<f64> {
+        let spikes = self.spike_row(id);
+        let mut row = Vec::with_capacity(self.steps);
+        let mut in_window = 0.0;
+        for step in 0..self.steps {
+            in_window += spikes[step];
+            if step >= window {
+                in_window -= spikes[step - window];
+            }
+            let span = (step + 1).min(window) as f64 * self.dt;
+            row.push(if span > 0.0 { in_window / span } else { 0.0 });
+        }
+        row
+    }
+}
+
+/// Neuron order along both axes of `Network::render_heatmap`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MatrixOrder {
+    /// Sensory, interneuron, motor, other; by name within each type.
+    #[default]
+    TypeThenName,
+    /// Anterior to posterior by `soma_position`, ties broken by id.
+    SomaPosition,
+    /// Community by community, members in the order given. A neuron listed twice
+    /// stays where it first appears; neurons in no community follow in id order.
+    Communities(Vec<Vec<usize>>),
+    /// Neuron ids in axis order, listing every neuron exactly once.
+    Permutation(Vec<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScale {
+    #[default]
+    Linear,
+    /// `ln(1 + w)`, so light connections stay visible beside heavy ones.
+    Log,
+}
+
+/// How chemical and gap-junction weight share the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeatmapLayers {
+    /// One matrix of their sum.
+    #[default]
+    Combined,
+    /// One matrix on black, chemical weight in the red channel and gap-junction
+    /// weight in the green, each scaled to its own maximum.
+    Channels,
+    /// Chemical and gap-junction matrices side by side, each scaled to its own
+    /// maximum.
+    Panels,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatmapOptions {
+    /// Image size in pixels, labels included.
+    pub width: u32,
+    pub height: u32,
+    pub order: MatrixOrder,
+    pub scale: ColorScale,
+    pub layers: HeatmapLayers,
+    /// Name every `label_every`-th neuron along both axes; 0 draws no labels.
+    pub label_every: usize,
+}
+
+impl Default for HeatmapOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            order: MatrixOrder::default(),
+            scale: ColorScale::default(),
+            layers: HeatmapLayers::default(),
+            label_every: 10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn a_short_trace_is_padded_with_nan() {
+        let network = Network::with_neuron_roster(&["A", "B"]);
+        let result = SimulationResult {
+            dt: 0.5,
+            steps: 6,
+            potentials: HashMap::from([(0, vec![1.0, 2.0, 3.0]), (1, vec![4.0])]),
+            potential_stride: 2,
+            ..SimulationResult::default()
+        };
+        let heatmap = result.heatmap(&network, HeatmapQuantity::Potential, RowOrder::Id);
+        assert_eq!(heatmap.times.len(), 6);
+        assert_eq!(heatmap.values[0], [1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+        assert_eq!(heatmap.values[1][..2], [4.0, 4.0]);
+        assert!(heatmap.values[1][2..].iter().all(|v| v.is_nan()));
+
+        let mut csv = Vec::new();
+        heatmap.write_csv(&mut csv).unwrap();
+        let text = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.split(',').count() == 7));
+        assert_eq!(lines[2], "B,4,4,NaN,NaN,NaN,NaN");
+    }
+}
@@ -0,0 +1,312 @@
+//! Inter-spike intervals and how regular a run's spike trains are.
+//!
+//! Intervals and durations are in the run's time unit. Neurons with fewer than
+//! two spikes have no intervals and give `None` rather than NaN.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::error::FlymindError;
+use crate::grouped::GroupBy;
+use crate::network::Network;
+use crate::simulation::SimulationResult;
+
+/// Inter-spike interval statistics of one neuron.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct IsiStats {
+    /// Number of intervals, one fewer than the spikes.
+    pub n: usize,
+    pub mean: f64,
+    /// Standard deviation over mean: 0 for a periodic train, about 1 for a
+    /// Poisson one.
+    pub cv: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// `IsiStats` averaged over one group of neurons.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupIsi {
+    pub label: String,
+    pub neurons: usize,
+    /// Members with at least two spikes, which the means are over.
+    pub measured: usize,
+    pub mean_isi: Option<f64>,
+    pub mean_cv: Option<f64>,
+}
+
+/// A run of spikes each within `max_isi` of the one before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Burst {
+    pub start_step: usize,
+    pub end_step: usize,
+    pub spikes: usize,
+}
+
+/// Totals over the bursts of one neuron; the means are 0.0 without bursts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct BurstStats {
+    pub count: usize,
+    pub mean_spikes: f64,
+    pub mean_duration: f64,
+    /// Share of the neuron's spikes that fell in a burst.
+    pub burst_fraction: f64,
+}
+
+impl SimulationResult {
+    /// Intervals between consecutive spikes of `neuron`.
+    pub fn isis(&self, neuron: usize) -> Vec<f64> {
+        let steps = self
+            .spike_trains
+            .get(neuron)
+            .map(|t| t.to_vec())
+            .unwrap_or_default();
+        steps
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) as f64 * self.dt)
+            .collect()
+    }
+
+    /// Interval statistics of `neuron`, or `None` with fewer than two spikes.
+    pub fn isi_stats(&self, neuron: usize) -> Option<IsiStats> {
+        let isis = self.isis(neuron);
+        if isis.is_empty() {
+            return None;
+        }
+        let n = isis.len() as f64;
+        let mean = isis.iter().sum::<f64>() / n;
+        let variance = isis.iter().map(|isi| (isi - mean).powi(2)).sum::<f64>() / n;
+        Some(IsiStats {
+            n: isis.len(),
+            mean,
+            cv: if mean > 0.0 {
+                variance.sqrt() / mean
+            } else {
+                0.0
+            },
+            min: isis.iter().copied().fold(f64::INFINITY, f64::min),
+            max: isis.iter().copied().fold(0.0, f64::max),
+        })
+    }
+
+    /// Mean interval and CV per group of `network`'s neurons, e.g. by
+    /// `GroupBy::NeuronType` or `GroupBy::Region`, every group listed.
+    pub fn isi_summary(&self, network: &Network, group_by: &GroupBy) -> Vec<GroupIsi> {
+        let (labels, group) = group_by.assign(network);
+        let mut summary: Vec<GroupIsi> = labels
+            .into_iter()
+            .map(|label| GroupIsi {
+                label,
+                neurons: 0,
+                measured: 0,
+                mean_isi: None,
+                mean_cv: None,
+            })
+            .collect();
+        let mut sums = vec![(0.0, 0.0); summary.len()];
+        for (id, &g) in group.iter().enumerate() {
+            summary[g].neurons += 1;
+            if let Some(stats) = self.isi_stats(id) {
+                summary[g].measured += 1;
+                sums[g].0 += stats.mean;
+                sums[g].1 += stats.cv;
+            }
+        }
+        for (entry, (isi, cv)) in summary.iter_mut().zip(sums) {
+            if entry.measured > 0 {
+                let count = entry.measured as f64;
+                entry.mean_isi = Some(isi / count);
+                entry.mean_cv = Some(cv / count);
+            }
+        }
+        summary
+    }
+
+    /// Variance over mean of `neuron`'s spike count in consecutive windows of
+    /// `window_steps` steps, a trailing partial window dropped. `None` without
+    /// two full windows or without spikes in them.
+    pub fn fano_factor(&self, neuron: usize, window_steps: usize) -> Option<f64> {
+        let window_steps = window_steps.max(1);
+        let windows = self.steps / window_steps;
+        let train = self.spike_trains.get(neuron)?;
+        if windows < 2 {
+            return None;
+        }
+        let mut counts = vec![0.0; windows];
+        for step in train.iter().filter(|&step| step < windows * window_steps) {
+            counts[step / window_steps] += 1.0;
+        }
+        let mean = counts.iter().sum::<f64>() / windows as f64;
+        if mean == 0.0 {
+            return None;
+        }
+        let variance = counts.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / windows as f64;
+        Some(variance / mean)
+    }
+
+    /// Bursts of at least `min_spikes` spikes (at least two) of `neuron`,
+    /// consecutive spikes at most `max_isi` apart.
+    pub fn bursts(&self, neuron: usize, max_isi: f64, min_spikes: usize) -> Vec<Burst> {
+        let min_spikes = min_spikes.max(2);
+        let steps = self
+            .spike_trains
+            .get(neuron)
+            .map(|t| t.to_vec())
+            .unwrap_or_default();
+        let mut bursts = Vec::new();
+        let mut start = 0;
+        for i in 1..=steps.len() {
+            let joined = i < steps.len() && (steps[i] - steps[i - 1]) as f64 * self.dt <= max_isi;
+            if !joined {
+                if i - start >= min_spikes {
+                    bursts.push(Burst {
+                        start_step: steps[start],
+                        end_step: steps[i - 1],
+                        spikes: i - start,
+                    });
+                }
+                start = i;
+            }
+        }
+        bursts
+    }
+
+    /// `bursts` of `neuron`, totalled.
+    pub fn burst_stats(&self, neuron: usize, max_isi: f64, min_spikes: usize) -> BurstStats {
+        let bursts = self.bursts(neuron, max_isi, min_spikes);
+        if bursts.is_empty() {
+            return BurstStats::default();
+        }
+        let count = bursts.len() as f64;
+        let spikes: usize = bursts.iter().map(|b| b.spikes).sum();
+        let duration: f64 = bursts
+            .iter()
+            .map(|b| (b.end_step - b.start_step) as f64 * self.dt)
+            .sum();
+        BurstStats {
+            count: bursts.len(),
+            mean_spikes: spikes as f64 / count,
+            mean_duration: duration / count,
+            burst_fraction: spikes as f64 / self.spike_count(neuron) as f64,
+        }
+    }
+
+    /// Write one `neuron,spikes,intervals,mean_isi,cv,min_isi,max_isi` row per
+    /// neuron, in id order; the interval columns are empty for neurons with
+    /// fewer than two spikes.
+    pub fn write_isi_csv<W: Write>(
+        &self,
+        network: &Network,
+        writer: W,
+    ) -> Result<(), FlymindError> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record([
+            "neuron",
+            "spikes",
+            "intervals",
+            "mean_isi",
+            "cv",
+            "min_isi",
+            "max_isi",
+        ])?;
+        for id in 0..self.spike_trains.len() {
+            let name = network.neurons.get(id).map_or("", |n| n.name());
+            let mut record = vec![name.to_string(), self.spike_count(id).to_string()];
+            match self.isi_stats(id) {
+                Some(stats) => record.extend([
+                    stats.n.to_string(),
+                    stats.mean.to_string(),
+                    stats.cv.to_string(),
+                    stats.min.to_string(),
+                    stats.max.to_string(),
+                ]),
+                None => record.extend(["0", "", "", "", ""].map(String::from)),
+            }
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::spikes::{SpikeStorage, SpikeTrain};
+
+    fn result_of(steps: usize, trains: &[Vec<usize>]) -> SimulationResult {
+        let spike_trains = trains
+            .iter()
+            .map(|spikes| {
+                let mut train = SpikeTrain::new(SpikeStorage::Steps);
+                spikes.iter().for_each(|&step| train.push(step));
+                train
+            })
+            .collect();
+        SimulationResult {
+            dt: 0.5,
+            steps,
+            spike_trains,
+            ..SimulationResult::default()
+        }
+    }
+
+    #[test]
+    fn periodic_train_has_zero_cv() {
+        let result = result_of(1000, &[(0..1000).step_by(20).collect()]);
+        let stats = result.isi_stats(0).unwrap();
+        assert_eq!(stats.n, 49);
+        assert_eq!(stats.mean, 10.0);
+        assert!(stats.cv.abs() < 1e-12);
+        assert_eq!((stats.min, stats.max), (10.0, 10.0));
+        assert_eq!(result.fano_factor(0, 100), Some(0.0));
+    }
+
+    #[test]
+    fn poisson_train_has_unit_cv() {
+        let mut rng = Rng::new(17);
+        let spikes: Vec<usize> = (0..400_000).filter(|_| rng.next_f64() < 0.01).collect();
+        let result = result_of(400_000, &[spikes]);
+        let stats = result.isi_stats(0).unwrap();
+        assert!(stats.n > 3000);
+        assert!((stats.cv - 1.0).abs() < 0.05, "cv {}", stats.cv);
+        let fano = result.fano_factor(0, 1000).unwrap();
+        assert!((fano - 1.0).abs() < 0.15, "fano {fano}");
+    }
+
+    #[test]
+    fn planted_bursts_are_found() {
+        let mut spikes = Vec::new();
+        for start in [100, 500, 900] {
+            spikes.extend((0..4).map(|k| start + 2 * k));
+            spikes.push(start + 200);
+        }
+        let result = result_of(1200, &[spikes]);
+        let bursts = result.bursts(0, 1.5, 3);
+        let expected: Vec<Burst> = [100, 500, 900]
+            .into_iter()
+            .map(|start| Burst {
+                start_step: start,
+                end_step: start + 6,
+                spikes: 4,
+            })
+            .collect();
+        assert_eq!(bursts, expected);
+        let stats = result.burst_stats(0, 1.5, 3);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.mean_spikes, 4.0);
+        assert_eq!(stats.mean_duration, 3.0);
+        assert_eq!(stats.burst_fraction, 0.8);
+    }
+
+    #[test]
+    fn single_spikes_have_no_intervals() {
+        let result = result_of(100, &[vec![10], vec![]]);
+        assert_eq!(result.isi_stats(0), None);
+        assert_eq!(result.isi_stats(1), None);
+        assert_eq!(result.isi_stats(5), None);
+        assert_eq!(result.burst_stats(0, 10.0, 2), BurstStats::default());
+    }
+}
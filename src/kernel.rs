@@ -0,0 +1,457 @@
+//! The step shared by `Network::update_step` and `sweep::SimulationState`.
+//!
+//! `StepInputs` compiles a network's connections once per run: every counted
+//! input with what it needs from its connection (kind, sign, gain, release
+//! probability), grouped by target in CSR layout, and the plastic sends with
+//! their parameters. Weights and neuron parameters are read as the step runs,
+//! so STDP and state switches need no recompiling. Both steppers then build a
+//! `Drive` from it and integrate it; what differs between them (events,
+//! muscles, the wavefront, where weights live) stays with the caller.
+//!
+//! Each neuron's inputs are added in connection order, so the sums, and with
+//! them every run, come out the same however the caller walks the neurons.
+
+use crate::network::{Network, Neuron, SynapseType};
+use crate::rng::Rng;
+use crate::simulation::{Firing, InputNormalization, LifParams, StdpParams, Stimulus};
+use crate::synapses::SynapseParams;
+use crate::trace::StimulusTrace;
+
+/// A network's inputs compiled for stepping.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StepInputs {
+    /// Counted chemical sends, modulatory ones included, gap-junction rows
+    /// and extrasynaptic edges, in connection order.
+    inputs: Vec<Input>,
+    /// Slots into `inputs` of the inputs reaching neuron `i`, ascending:
+    /// `by_target[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<usize>,
+    by_target: Vec<usize>,
+    /// Distinct presynaptic partners of each neuron through fast inputs.
+    partners: Vec<usize>,
+    /// Plastic chemical sends, counted or not.
+    plastic: Vec<Plastic>,
+    /// Whether some input releases with probability below 1.
+    unreliable: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    connection: usize,
+    from: usize,
+    to: usize,
+    kind: InputKind,
+    /// -1 for inhibitory chemical sends, 1 otherwise.
+    sign: f64,
+    /// `SynapseParams::gain`.
+    gain: f64,
+    /// `SynapseParams::release_probability`.
+    release: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    Chemical,
+    Modulatory,
+    Gap,
+    Extrasynaptic,
+}
+
+impl InputKind {
+    /// Whether the input counts towards `max_inputs` and normalization.
+    fn is_fast(self) -> bool {
+        matches!(self, InputKind::Chemical | InputKind::Gap)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Plastic {
+    connection: usize,
+    from: usize,
+    to: usize,
+    synapse: Option<SynapseParams>,
+}
+
+/// `LifParams::max_inputs` and `InputNormalization` resolved against the
+/// current weights and parameters; see `Network::input_limits`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputLimits {
+    /// Which inputs survive `max_inputs`, by slot; `None` when no neuron caps.
+    capped: Option<Vec<bool>>,
+    /// Per-neuron normalization divisors; `None` when no neuron normalizes.
+    divisors: Option<Vec<f64>>,
+}
+
+/// What one step delivers to each neuron, indexed by id.
+#[derive(Debug, Clone)]
+pub(crate) struct Drive {
+    /// Instantaneous jump of the potential.
+    pub jump: Vec<f64>,
+    /// Continuous current.
+    pub current: Vec<f64>,
+    /// Raise of `Neuron::modulation`.
+    pub modulation: Vec<f64>,
+}
+
+/// How a step delivers its synaptic input.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Delivery<'a> {
+    pub limits: &'a InputLimits,
+    /// Sends that failed to release, by slot, from `release_failures`.
+    pub failed: Option<&'a [bool]>,
+    /// Whether chemical sends carry the source's rate estimate instead of
+    /// its spikes.
+    pub by_rate: bool,
+    /// Neurons to deliver to, `None` for all of them.
+    pub active: Option<&'a [bool]>,
+}
+
+impl StepInputs {
+    /// Compile `network`'s connections, with `synapses` resolved from its
+    /// `connection_params`.
+    pub(crate) fn compile(network: &Network, synapses: Option<&[SynapseParams]>) -> Self {
+        let n = network.neurons.len();
+        let synapse = |index: usize| synapses.and_then(|s| s.get(index)).copied();
+        let mut compiled = Self::default();
+        for (connection, conn) in network.connections.iter().enumerate() {
+            let params = synapse(connection);
+            if conn.plastic && matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) {
+                compiled.plastic.push(Plastic {
+                    connection,
+                    from: conn.from_id,
+                    to: conn.to_id,
+                    synapse: params,
+                });
+            }
+            if !network.counts_in_any_layer(conn) {
+                continue;
+            }
+            let (kind, sign) = match conn.synapse_type {
+                SynapseType::ChemicalSend(subtype) if subtype.is_modulatory() => {
+                    (InputKind::Modulatory, 1.0)
+                }
+                SynapseType::ChemicalSend(subtype) => (InputKind::Chemical, subtype.sign()),
+                SynapseType::GapJunction => (InputKind::Gap, 1.0),
+                SynapseType::Extrasynaptic(_) => (InputKind::Extrasynaptic, 1.0),
+                SynapseType::ChemicalReceive(_) | SynapseType::NMJ => continue,
+            };
+            let release = params.map_or(1.0, |s| s.release_probability);
+            compiled.unreliable |= release < 1.0;
+            compiled.inputs.push(Input {
+                connection,
+                from: conn.from_id,
+                to: conn.to_id,
+                kind,
+                sign,
+                gain: params.map_or(1.0, |s| s.gain),
+                release,
+            });
+        }
+
+        compiled.offsets = vec![0; n + 1];
+        for input in &compiled.inputs {
+            compiled.offsets[input.to + 1] += 1;
+        }
+        for i in 0..n {
+            compiled.offsets[i + 1] += compiled.offsets[i];
+        }
+        let mut cursor = compiled.offsets.clone();
+        compiled.by_target = vec![0; compiled.inputs.len()];
+        for (slot, input) in compiled.inputs.iter().enumerate() {
+            compiled.by_target[cursor[input.to]] = slot;
+            cursor[input.to] += 1;
+        }
+
+        let mut from = Vec::new();
+        compiled.partners = (0..n)
+            .map(|id| {
+                from.clear();
+                from.extend(
+                    compiled
+                        .incoming(id)
+                        .filter(|input| input.kind.is_fast())
+                        .map(|input| input.from),
+                );
+                from.sort_unstable();
+                from.dedup();
+                from.len()
+            })
+            .collect();
+        compiled
+    }
+
+    fn incoming(&self, id: usize) -> impl Iterator<Item = &Input> {
+        self.by_target[self.offsets[id]..self.offsets[id + 1]]
+            .iter()
+            .map(|&slot| &self.inputs[slot])
+    }
+
+    fn neuron_count(&self) -> usize {
+        self.partners.len()
+    }
+
+    /// `max_inputs` and normalization under `lif` and `weight`.
+    pub(crate) fn limits<'a>(
+        &self,
+        lif: impl Fn(usize) -> &'a LifParams,
+        weight: impl Fn(usize) -> f64,
+    ) -> InputLimits {
+        let n = self.neuron_count();
+        let caps = (0..n).any(|id| lif(id).max_inputs.is_some());
+        let capped = caps.then(|| {
+            let fast = self
+                .inputs
+                .iter()
+                .enumerate()
+                .filter(|(_, input)| input.kind.is_fast());
+            strongest_inputs(
+                fast.map(|(slot, input)| (slot, input.to, weight(input.connection))),
+                self.inputs.len(),
+                n,
+                |id| lif(id).max_inputs,
+            )
+        });
+
+        let normalizes = (0..n).any(|id| lif(id).input_normalization != InputNormalization::None);
+        let divisors = normalizes.then(|| {
+            (0..n)
+                .map(|id| {
+                    let count = self.partners[id] as f64;
+                    let value = match lif(id).input_normalization {
+                        InputNormalization::None => 1.0,
+                        InputNormalization::ByCount => count,
+                        InputNormalization::BySqrtCount => count.sqrt(),
+                        InputNormalization::ByWeight => self
+                            .incoming(id)
+                            .filter(|input| input.kind.is_fast())
+                            .map(|input| weight(input.connection).abs())
+                            .sum(),
+                    };
+                    if value > 0.0 {
+                        value
+                    } else {
+                        1.0
+                    }
+                })
+                .collect()
+        });
+        InputLimits { capped, divisors }
+    }
+
+    /// Which chemical sends fail to release this step, by slot, or `None`
+    /// when every send releases surely. One draw is taken per send from a
+    /// neuron that just fired with a release probability below 1, in
+    /// connection order, whether or not `max_inputs` then drops it.
+    pub(crate) fn release_failures(&self, neurons: &[Neuron], rng: &mut Rng) -> Option<Vec<bool>> {
+        if !self.unreliable {
+            return None;
+        }
+        let failed = self
+            .inputs
+            .iter()
+            .map(|input| {
+                let spiking = matches!(input.kind, InputKind::Chemical | InputKind::Modulatory);
+                spiking
+                    && input.release < 1.0
+                    && neurons[input.from].just_fired
+                    && rng.next_f64() >= input.release
+            })
+            .collect();
+        Some(failed)
+    }
+
+    /// The synaptic input of a step of `dt`, normalized: postsynaptic gains
+    /// from `lif`, weights by connection from `weight`.
+    pub(crate) fn drive<'a>(
+        &self,
+        neurons: &[Neuron],
+        lif: impl Fn(usize) -> &'a LifParams,
+        weight: impl Fn(usize) -> f64,
+        delivery: Delivery<'_>,
+        dt: f64,
+    ) -> Drive {
+        let n = neurons.len();
+        let mut drive = Drive {
+            jump: vec![0.0; n],
+            current: vec![0.0; n],
+            modulation: vec![0.0; n],
+        };
+        let capped = delivery.limits.capped.as_deref();
+        let targets = (0..n).filter(|&id| delivery.active.is_none_or(|active| active[id]));
+        for to in targets {
+            let params = lif(to);
+            for &slot in &self.by_target[self.offsets[to]..self.offsets[to + 1]] {
+                if capped.is_some_and(|keep| !keep[slot]) {
+                    continue;
+                }
+                let input = &self.inputs[slot];
+                let source = &neurons[input.from];
+                let weight = weight(input.connection);
+                match input.kind {
+                    InputKind::Chemical | InputKind::Modulatory => {
+                        let drive_by = if delivery.by_rate {
+                            source.rate_estimate * dt * input.release
+                        } else if source.just_fired
+                            && !delivery.failed.is_some_and(|failed| failed[slot])
+                        {
+                            1.0
+                        } else {
+                            0.0
+                        };
+                        if drive_by == 0.0 {
+                            continue;
+                        }
+                        if input.kind == InputKind::Modulatory {
+                            drive.modulation[to] +=
+                                params.modulation_gain * input.gain * weight * drive_by;
+                        } else {
+                            drive.jump[to] +=
+                                input.sign * params.chemical_gain * input.gain * weight * drive_by;
+                        }
+                    }
+                    InputKind::Gap => {
+                        let dv = source.membrane_potential - neurons[to].membrane_potential;
+                        drive.current[to] += params.gap_gain * input.gain * weight * dv;
+                    }
+                    InputKind::Extrasynaptic => {
+                        if params.extrasynaptic_gain != 0.0 {
+                            let activity = source.graded_activity(lif(input.from));
+                            drive.modulation[to] +=
+                                params.extrasynaptic_gain * input.gain * weight * activity * dt;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(divisors) = &delivery.limits.divisors {
+            for ((jump, current), divisor) in
+                drive.jump.iter_mut().zip(&mut drive.current).zip(divisors)
+            {
+                *jump /= divisor;
+                *current /= divisor;
+            }
+        }
+        drive
+    }
+
+    /// Decay the STDP traces, work out the new weights of the plastic sends
+    /// under `weight` within their bounds, and mark this step's spikes.
+    /// Returns the weights that changed, as `(connection, weight)`.
+    pub(crate) fn apply_stdp(
+        &self,
+        stdp: &StdpParams,
+        neurons: &mut [Neuron],
+        dt: f64,
+        weight: impl Fn(usize) -> f64,
+    ) -> Vec<(usize, f64)> {
+        stdp.decay_traces(neurons, dt);
+        let mut changed = Vec::new();
+        for synapse in &self.plastic {
+            let dw = stdp.weight_change(&neurons[synapse.from], &neurons[synapse.to]);
+            if dw != 0.0 {
+                let (w_min, w_max) = stdp.bounds(synapse.synapse.as_ref());
+                let old = weight(synapse.connection);
+                let new = (old + dw).max(w_min).min(w_max);
+                if new != old {
+                    changed.push((synapse.connection, new));
+                }
+            }
+        }
+        StdpParams::mark_spikes(neurons);
+        changed
+    }
+}
+
+impl Drive {
+    /// Add the current of the `stimuli` active at `step` and of `bias`, as
+    /// `(neuron, current)`.
+    pub(crate) fn add_external(
+        &mut self,
+        stimuli: &[Stimulus],
+        traces: &[StimulusTrace],
+        bias: impl IntoIterator<Item = (usize, f64)>,
+        step: usize,
+    ) {
+        let n = self.current.len();
+        for stim in stimuli {
+            if stim.neuron < n {
+                self.current[stim.neuron] += stim.current_at(step, traces);
+            }
+        }
+        for (id, bias) in bias {
+            if id < n {
+                self.current[id] += bias;
+            }
+        }
+    }
+
+    /// Raise each `active` neuron's modulation and scale its input by it.
+    pub(crate) fn modulate<'a>(
+        &mut self,
+        neurons: &mut [Neuron],
+        lif: impl Fn(usize) -> &'a LifParams,
+        dt: f64,
+        active: Option<&[bool]>,
+    ) {
+        for (i, neuron) in neurons.iter_mut().enumerate() {
+            if active.is_some_and(|active| !active[i]) {
+                continue;
+            }
+            let scale = neuron.modulate(lif(i), self.modulation[i], dt);
+            if scale != 1.0 {
+                self.jump[i] *= scale;
+                self.current[i] *= scale;
+            }
+        }
+    }
+
+    /// Integrate each `active` neuron over the step, calling `fired` with
+    /// the id and firing of every neuron that crossed threshold.
+    pub(crate) fn integrate<'a>(
+        &self,
+        neurons: &mut [Neuron],
+        lif: impl Fn(usize) -> &'a LifParams,
+        dt: f64,
+        rng: &mut Rng,
+        active: Option<&[bool]>,
+        mut fired: impl FnMut(usize, Firing),
+    ) {
+        for (i, neuron) in neurons.iter_mut().enumerate() {
+            if active.is_some_and(|active| !active[i]) {
+                continue;
+            }
+            if let Some(firing) = neuron.integrate(lif(i), self.current[i], self.jump[i], dt, rng) {
+                fired(i, firing);
+            }
+        }
+    }
+}
+
+/// Whether each of `slots` inputs is kept under a cap of `max_inputs(neuron)`
+/// per postsynaptic neuron. `inputs` gives the capped ones as `(slot, neuron,
+/// weight)`; each neuron keeps its strongest by `|weight|`, ties to the lower
+/// slot, and every slot not in `inputs` is kept.
+pub(crate) fn strongest_inputs(
+    inputs: impl IntoIterator<Item = (usize, usize, f64)>,
+    slots: usize,
+    neurons: usize,
+    max_inputs: impl Fn(usize) -> Option<usize>,
+) -> Vec<bool> {
+    let mut by_neuron: Vec<Vec<(usize, f64)>> = vec![Vec::new(); neurons];
+    for (slot, neuron, weight) in inputs {
+        by_neuron[neuron].push((slot, weight.abs()));
+    }
+    let mut keep = vec![true; slots];
+    for (neuron, mut candidates) in by_neuron.into_iter().enumerate() {
+        let Some(cap) = max_inputs(neuron) else {
+            continue;
+        };
+        if candidates.len() > cap {
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            for &(slot, _) in &candidates[cap..] {
+                keep[slot] = false;
+            }
+        }
+    }
+    keep
+}
@@ -0,0 +1,605 @@
+//! 2-D layouts, so every viewer of an export shows the same picture: a
+//! force-directed one and a spectral one.
+//!
+//! Force-directed (Fruchterman–Reingold):
+//! Linked neurons (any connection type, either direction) attract with force
+//! `d² / k` and every pair repels with `k² / d`, where `k = 1` is the ideal
+//! link length. Repulsion is approximated with a Barnes–Hut quadtree, so each
+//! iteration costs `O(n log n)` rather than `O(n²)`. Moves are capped by a
+//! temperature that cools linearly to zero; with a fixed seed and iteration
+//! count the result is identical across runs.
+//!
+//! Spectral (Laplacian eigenmap): each neuron sits at its entries in the
+//! eigenvectors of the second and third smallest eigenvalues of the graph
+//! Laplacian. Needs no seed, but the dense eigendecomposition is cubic in the
+//! size of the largest component, so it suits connectome-sized networks.
+
+use std::collections::VecDeque;
+
+use crate::network::{Network, SynapseType};
+use crate::rng::Rng;
+
+/// Nodes closer than this are treated as this far apart.
+const MIN_DISTANCE: f64 = 1e-6;
+/// Quadtree depth past which coincident neurons share a leaf.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutOptions {
+    pub iterations: usize,
+    pub seed: u64,
+    /// Start x at `soma_position`, rescaled to the layout's width, so the
+    /// anterior–posterior axis runs left to right; y still starts at random.
+    pub soma_x: bool,
+    /// Barnes–Hut opening angle: a quadtree cell whose size over distance is
+    /// below this repels as a single body. 0.0 computes every pair exactly.
+    pub theta: f64,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            iterations: 300,
+            seed: 0,
+            soma_x: false,
+            theta: 0.9,
+        }
+    }
+}
+
+/// A quadtree cell: its square, and the count and centre of its neurons.
+struct Cell {
+    x0: f64,
+    y0: f64,
+    size: f64,
+    mass: f64,
+    cx: f64,
+    cy: f64,
+    /// The neuron alone in this cell, if it is a single-neuron leaf.
+    body: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl Network {
+    /// `(x, y)` of every neuron, indexed by id, in a square of side about
+    /// `sqrt(n)`.
+    pub fn layout_force_directed(&self, opts: &LayoutOptions) -> Vec<(f64, f64)> {
+        let n = self.neurons.len();
+        let side = (n as f64).sqrt().max(1.0);
+        let mut rng = Rng::new(opts.seed);
+        let mut pos: Vec<(f64, f64)> = (0..n)
+            .map(|_| (rng.next_f64() * side, rng.next_f64() * side))
+            .collect();
+        if opts.soma_x {
+            let (lo, hi) = self
+                .neurons
+                .iter()
+                .map(|neuron| neuron.soma_position)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+                    (lo.min(p), hi.max(p))
+                });
+            if hi > lo {
+                for (p, neuron) in pos.iter_mut().zip(&self.neurons) {
+                    p.0 = (neuron.soma_position - lo) / (hi - lo) * side;
+                }
+            }
+        }
+
+        let adjacency = self.undirected_adjacency();
+        let mut disp = vec![(0.0, 0.0); n];
+        for iteration in 0..opts.iterations {
+            let temperature = side / 10.0 * (1.0 - iteration as f64 / opts.iterations as f64);
+            disp.fill((0.0, 0.0));
+
+            let cells = quadtree(&pos);
+            for (v, d) in disp.iter_mut().enumerate() {
+                repel(&cells, 0, v, pos[v], opts.theta, d);
+            }
+            for (v, list) in adjacency.iter().enumerate() {
+                for &w in list.iter().filter(|&&w| w > v) {
+                    let (dx, dy) = (pos[v].0 - pos[w].0, pos[v].1 - pos[w].1);
+                    let dist = dx.hypot(dy).max(MIN_DISTANCE);
+                    // d² / k along the unit vector.
+                    let (fx, fy) = (dx * dist, dy * dist);
+                    disp[v].0 -= fx;
+                    disp[v].1 -= fy;
+                    disp[w].0 += fx;
+                    disp[w].1 += fy;
+                }
+            }
+
+            for (p, d) in pos.iter_mut().zip(&disp) {
+                let length = d.0.hypot(d.1);
+                if length > 0.0 {
+                    let step = length.min(temperature) / length;
+                    p.0 += d.0 * step;
+                    p.1 += d.1 * step;
+                }
+            }
+        }
+        pos
+    }
+}
+
+impl Network {
+    /// Laplacian eigenmap of the symmetrized weighted graph: weights summed
+    /// over both directions of every connection except `ChemicalReceive`
+    /// mirrors, non-positive ones dropped. With `normalized`, the eigenvectors
+    /// come from the degree-normalized Laplacian, which keeps hubs from pulling
+    /// the layout around.
+    ///
+    /// Each connected component is laid out on its own, scaled to a width of
+    /// about `sqrt` of its size and placed left to right, largest first.
+    /// Eigenvector signs are fixed so the result is deterministic; single
+    /// neurons sit at their own point, and two-neuron components on a line.
+    pub fn layout_spectral(&self, normalized: bool) -> Vec<(f64, f64)> {
+        let n = self.neurons.len();
+        let mut weights: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_))
+                || conn.from_id == conn.to_id
+                || conn.weight <= 0.0
+            {
+                continue;
+            }
+            weights[conn.from_id].push((conn.to_id, conn.weight));
+            weights[conn.to_id].push((conn.from_id, conn.weight));
+        }
+
+        let mut pos = vec![(0.0, 0.0); n];
+        let mut offset = 0.0;
+        for component in weighted_components(&weights) {
+            let coords = spectral_coordinates(&component, &weights, normalized);
+            let side = (component.len() as f64).sqrt();
+            let radius = coords.iter().map(|&(x, y)| x.hypot(y)).fold(0.0, f64::max);
+            let scale = if radius > 0.0 {
+                side / 2.0 / radius
+            } else {
+                0.0
+            };
+            for (&v, &(x, y)) in component.iter().zip(&coords) {
+                pos[v] = (offset + side / 2.0 + x * scale, y * scale);
+            }
+            offset += side + 1.0;
+        }
+        pos
+    }
+}
+
+/// Components of the symmetrized graph, largest first (ties by smallest
+/// member), each ascending.
+fn weighted_components(weights: &[Vec<(usize, f64)>]) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; weights.len()];
+    let mut components = Vec::new();
+    for start in 0..weights.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut component = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some(v) = queue.pop_front() {
+            for &(w, _) in &weights[v] {
+                if !seen[w] {
+                    seen[w] = true;
+                    component.push(w);
+                    queue.push_back(w);
+                }
+            }
+        }
+        component.sort_unstable();
+        components.push(component);
+    }
+    components.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+    components
+}
+
+/// Centred eigenmap coordinates of the neurons of one component, in its order.
+fn spectral_coordinates(
+    component: &[usize],
+    weights: &[Vec<(usize, f64)>],
+    normalized: bool,
+) -> Vec<(f64, f64)> {
+    let m = component.len();
+    if m < 2 {
+        return vec![(0.0, 0.0); m];
+    }
+    let mut local = vec![usize::MAX; weights.len()];
+    for (i, &v) in component.iter().enumerate() {
+        local[v] = i;
+    }
+    let mut laplacian = vec![vec![0.0; m]; m];
+    for (i, &v) in component.iter().enumerate() {
+        for &(w, weight) in &weights[v] {
+            laplacian[i][local[w]] -= weight;
+            laplacian[i][i] += weight;
+        }
+    }
+    let degree: Vec<f64> = (0..m).map(|i| laplacian[i][i]).collect();
+    if normalized {
+        for (i, row) in laplacian.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry /= (degree[i] * degree[j]).sqrt();
+            }
+        }
+    }
+
+    let (values, vectors) = symmetric_eigen(laplacian);
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]).then(a.cmp(&b)));
+    let axis = |k: usize| -> Vec<f64> {
+        let Some(&column) = order.get(k) else {
+            return vec![0.0; m];
+        };
+        let mut axis: Vec<f64> = (0..m).map(|i| vectors[i][column]).collect();
+        if normalized {
+            // Back from the symmetric form to the random-walk eigenvectors.
+            for (x, d) in axis.iter_mut().zip(&degree) {
+                *x /= d.sqrt();
+            }
+        }
+        let largest = axis
+            .iter()
+            .copied()
+            .reduce(|best, x| {
+                if x.abs() > best.abs() + 1e-12 {
+                    x
+                } else {
+                    best
+                }
+            })
+            .unwrap_or(0.0);
+        if largest < 0.0 {
+            axis.iter_mut().for_each(|x| *x = -*x);
+        }
+        let mean = axis.iter().sum::<f64>() / m as f64;
+        axis.iter().map(|x| x - mean).collect()
+    };
+    let (x, y) = (axis(1), axis(2));
+    x.into_iter().zip(y).collect()
+}
+
+/// Eigenvalues and eigenvectors (as the columns of the second matrix) of a
+/// symmetric matrix: Householder reduction to tridiagonal form, then implicit
+/// QL iterations (the EISPACK `tred2` and `tql2` routines).
+pub(crate) fn symmetric_eigen(mut v: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = v.len();
+    let mut d: Vec<f64> = v[n - 1].clone();
+    let mut e = vec![0.0; n];
+
+    // Householder reduction.
+    for i in (1..n).rev() {
+        let scale: f64 = d[..i].iter().map(|x| x.abs()).sum();
+        let mut h = 0.0;
+        if scale == 0.0 {
+            e[i] = d[i - 1];
+            for j in 0..i {
+                d[j] = v[i - 1][j];
+                v[i][j] = 0.0;
+                v[j][i] = 0.0;
+            }
+        } else {
+            for x in &mut d[..i] {
+                *x /= scale;
+                h += *x * *x;
+            }
+            let f = d[i - 1];
+            let g = if f > 0.0 { -h.sqrt() } else { h.sqrt() };
+            e[i] = scale * g;
+            h -= f * g;
+            d[i - 1] = f - g;
+            e[..i].fill(0.0);
+            for j in 0..i {
+                let f = d[j];
+                v[j][i] = f;
+                let mut g = e[j] + v[j][j] * f;
+                for k in j + 1..i {
+                    g += v[k][j] * d[k];
+                    e[k] += v[k][j] * f;
+                }
+                e[j] = g;
+            }
+            let mut f = 0.0;
+            for j in 0..i {
+                e[j] /= h;
+                f += e[j] * d[j];
+            }
+            let hh = f / (h + h);
+            for j in 0..i {
+                e[j] -= hh * d[j];
+            }
+            for j in 0..i {
+                let (f, g) = (d[j], e[j]);
+                for k in j..i {
+                    v[k][j] -= f * e[k] + g * d[k];
+                }
+                d[j] = v[i - 1][j];
+                v[i][j] = 0.0;
+            }
+        }
+        d[i] = h;
+    }
+    // Accumulate the transformations.
+    for i in 0..n - 1 {
+        v[n - 1][i] = v[i][i];
+        v[i][i] = 1.0;
+        let h = d[i + 1];
+        if h != 0.0 {
+            for k in 0..=i {
+                d[k] = v[k][i + 1] / h;
+            }
+            for j in 0..=i {
+                let g: f64 = (0..=i).map(|k| v[k][i + 1] * v[k][j]).sum();
+                for k in 0..=i {
+                    v[k][j] -= g * d[k];
+                }
+            }
+        }
+        for row in &mut v[..=i] {
+            row[i + 1] = 0.0;
+        }
+    }
+    for j in 0..n {
+        d[j] = v[n - 1][j];
+        v[n - 1][j] = 0.0;
+    }
+    v[n - 1][n - 1] = 1.0;
+    e[0] = 0.0;
+
+    // QL iterations on the tridiagonal matrix.
+    for i in 1..n {
+        e[i - 1] = e[i];
+    }
+    e[n - 1] = 0.0;
+    let mut f = 0.0;
+    let mut tst1: f64 = 0.0;
+    let eps = f64::EPSILON;
+    for l in 0..n {
+        tst1 = tst1.max(d[l].abs() + e[l].abs());
+        let mut m = l;
+        while m < n - 1 && e[m].abs() > eps * tst1 {
+            m += 1;
+        }
+        if m > l {
+            loop {
+                let g = d[l];
+                let p = (d[l + 1] - g) / (2.0 * e[l]);
+                let r = p.hypot(1.0);
+                let r = if p < 0.0 { -r } else { r };
+                d[l] = e[l] / (p + r);
+                d[l + 1] = e[l] * (p + r);
+                let dl1 = d[l + 1];
+                let h = g - d[l];
+                for x in &mut d[l + 2..] {
+                    *x -= h;
+                }
+                f += h;
+
+                let mut p = d[m];
+                let (mut c, mut c2, mut c3) = (1.0, 1.0, 1.0);
+                let el1 = e[l + 1];
+                let (mut s, mut s2) = (0.0, 0.0);
+                for i in (l..m).rev() {
+                    c3 = c2;
+                    c2 = c;
+                    s2 = s;
+                    let g = c * e[i];
+                    let h = c * p;
+                    let r = p.hypot(e[i]);
+                    e[i + 1] = s * r;
+                    s = e[i] / r;
+                    c = p / r;
+                    p = c * d[i] - s * g;
+                    d[i + 1] = h + s * (c * g + s * d[i]);
+                    for row in v.iter_mut() {
+                        let h = row[i + 1];
+                        row[i + 1] = s * row[i] + c * h;
+                        row[i] = c * row[i] - s * h;
+                    }
+                }
+                p = -s * s2 * c3 * el1 * e[l] / dl1;
+                e[l] = s * p;
+                d[l] = c * p;
+                if e[l].abs() <= eps * tst1 {
+                    break;
+                }
+            }
+        }
+        d[l] += f;
+        e[l] = 0.0;
+    }
+    (d, v)
+}
+
+/// Barnes–Hut quadtree over `pos`; the root is cell 0.
+fn quadtree(pos: &[(f64, f64)]) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    if pos.is_empty() {
+        return cells;
+    }
+    let (mut x0, mut y0) = (f64::INFINITY, f64::INFINITY);
+    let (mut x1, mut y1) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(x, y) in pos {
+        x0 = x0.min(x);
+        y0 = y0.min(y);
+        x1 = x1.max(x);
+        y1 = y1.max(y);
+    }
+    // Slightly oversized so the extreme neurons fall strictly inside.
+    let size = (x1 - x0).max(y1 - y0) * (1.0 + 1e-9) + MIN_DISTANCE;
+    let all: Vec<usize> = (0..pos.len()).collect();
+    build(&mut cells, pos, all, (x0, y0, size), 0);
+    cells
+}
+
+fn build(
+    cells: &mut Vec<Cell>,
+    pos: &[(f64, f64)],
+    members: Vec<usize>,
+    (x0, y0, size): (f64, f64, f64),
+    depth: usize,
+) -> usize {
+    let mass = members.len() as f64;
+    let cx = members.iter().map(|&v| pos[v].0).sum::<f64>() / mass;
+    let cy = members.iter().map(|&v| pos[v].1).sum::<f64>() / mass;
+    let index = cells.len();
+    cells.push(Cell {
+        x0,
+        y0,
+        size,
+        mass,
+        cx,
+        cy,
+        body: (members.len() == 1).then(|| members[0]),
+        children: Vec::new(),
+    });
+    if members.len() == 1 || depth == MAX_DEPTH {
+        return index;
+    }
+    let half = size / 2.0;
+    let mut quadrants: [Vec<usize>; 4] = Default::default();
+    for v in members {
+        let right = pos[v].0 >= x0 + half;
+        let top = pos[v].1 >= y0 + half;
+        quadrants[usize::from(right) + 2 * usize::from(top)].push(v);
+    }
+    for (q, members) in quadrants.into_iter().enumerate() {
+        if members.is_empty() {
+            continue;
+        }
+        let origin = (x0 + half * (q % 2) as f64, y0 + half * (q / 2) as f64);
+        let child = build(cells, pos, members, (origin.0, origin.1, half), depth + 1);
+        cells[index].children.push(child);
+    }
+    index
+}
+
+/// Add to `d` the repulsion on neuron `v` at `p` from the neurons of `cell`.
+fn repel(cells: &[Cell], cell: usize, v: usize, p: (f64, f64), theta: f64, d: &mut (f64, f64)) {
+    let c = &cells[cell];
+    if c.body == Some(v) {
+        return;
+    }
+    let (dx, dy) = (p.0 - c.cx, p.1 - c.cy);
+    let dist = dx.hypot(dy);
+    let inside = p.0 >= c.x0 && p.0 < c.x0 + c.size && p.1 >= c.y0 && p.1 < c.y0 + c.size;
+    if c.children.is_empty() || (!inside && c.size < theta * dist) {
+        let mut mass = c.mass;
+        if c.body.is_none() && inside {
+            // A capped leaf holding `v` among coincident neurons.
+            mass -= 1.0;
+        }
+        let dist = dist.max(MIN_DISTANCE);
+        // mass * k² / d along the unit vector.
+        let force = mass / (dist * dist);
+        d.0 += dx * force;
+        d.1 += dy * force;
+        return;
+    }
+    for &child in &c.children {
+        repel(cells, child, v, p, theta, d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    fn network(n: usize, edges: &[(usize, usize)]) -> Network {
+        let names: Vec<String> = (0..n).map(|i| format!("N{i}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut network = Network::with_neuron_roster(&names);
+        for &(from, to) in edges {
+            network.add_connection(from, to, EXCITATORY, 1.0);
+        }
+        network
+    }
+
+    fn centroid(pos: &[(f64, f64)]) -> (f64, f64) {
+        let n = pos.len() as f64;
+        let (x, y) = pos.iter().fold((0.0, 0.0), |(x, y), p| (x + p.0, y + p.1));
+        (x / n, y / n)
+    }
+
+    fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+        (a.0 - b.0).hypot(a.1 - b.1)
+    }
+
+    #[test]
+    fn joined_cliques_lay_out_apart_and_reproducibly() {
+        let mut edges = Vec::new();
+        for clique in [0..5, 5..10] {
+            for v in clique.clone() {
+                edges.extend((v + 1..clique.end).map(|w| (v, w)));
+            }
+        }
+        edges.push((4, 5));
+        let network = network(10, &edges);
+        let opts = LayoutOptions::default();
+        let pos = network.layout_force_directed(&opts);
+        assert!(pos.iter().all(|p| p.0.is_finite() && p.1.is_finite()));
+
+        let (left, right) = (centroid(&pos[..5]), centroid(&pos[5..]));
+        let spread = pos[..5]
+            .iter()
+            .map(|&p| distance(p, left))
+            .chain(pos[5..].iter().map(|&p| distance(p, right)))
+            .fold(0.0, f64::max);
+        assert!(
+            distance(left, right) > 2.0 * spread,
+            "centroids {left:?} and {right:?}, spread {spread}"
+        );
+
+        assert_eq!(network.layout_force_directed(&opts), pos);
+        let reseeded = LayoutOptions { seed: 1, ..opts };
+        assert_ne!(network.layout_force_directed(&reseeded), pos);
+    }
+
+    #[test]
+    fn a_ring_lays_out_at_even_angles() {
+        let n = 8;
+        let ring: Vec<(usize, usize)> = (0..n).map(|v| (v, (v + 1) % n)).collect();
+        let network = network(n, &ring);
+        let step = std::f64::consts::TAU / n as f64;
+        for normalized in [false, true] {
+            let pos = network.layout_spectral(normalized);
+            let centre = centroid(&pos);
+            let radius = distance(pos[0], centre);
+            assert!(radius > 0.0);
+            for v in 0..n {
+                let (a, b) = (pos[v], pos[(v + 1) % n]);
+                assert!((distance(a, centre) - radius).abs() < 1e-9, "{normalized}");
+                let turn =
+                    (b.1 - centre.1).atan2(b.0 - centre.0) - (a.1 - centre.1).atan2(a.0 - centre.0);
+                let turn = turn.rem_euclid(std::f64::consts::TAU);
+                assert!(
+                    (turn - step).abs() < 1e-9
+                        || (turn - (std::f64::consts::TAU - step)).abs() < 1e-9,
+                    "{normalized}: {v} -> {} turns {turn}",
+                    v + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_disconnected_network_lays_out_without_nan() {
+        // A triangle, a pair, and an isolated neuron.
+        let network = network(6, &[(0, 1), (1, 2), (2, 0), (3, 4)]);
+        for normalized in [false, true] {
+            let pos = network.layout_spectral(normalized);
+            assert!(pos.iter().all(|p| p.0.is_finite() && p.1.is_finite()));
+            let right_of = |a: &[(f64, f64)], b: &[(f64, f64)]| {
+                let end = a.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+                b.iter().all(|p| p.0 > end)
+            };
+            assert!(right_of(&pos[..3], &pos[3..5]), "{normalized}");
+            assert!(right_of(&pos[3..5], &pos[5..]), "{normalized}");
+        }
+        let pos = network.layout_force_directed(&LayoutOptions::default());
+        assert!(pos.iter().all(|p| p.0.is_finite() && p.1.is_finite()));
+    }
+}
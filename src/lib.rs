@@ -0,0 +1,153 @@
+pub mod annotations;
+pub mod bundle;
+pub mod canonical;
+pub mod centrality;
+pub mod classes;
+pub mod closed_loop;
+pub mod compare;
+pub mod cone;
+pub mod config;
+pub mod csr;
+pub mod diagnostics;
+pub mod edits;
+pub mod electrical;
+pub mod error;
+pub mod events;
+pub mod experiments;
+pub mod export;
+pub mod extrasynaptic;
+pub mod feedback;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fixture;
+pub mod flow;
+pub mod functional;
+pub mod graph;
+pub mod grouped;
+pub mod heatmap;
+pub mod isi;
+mod kernel;
+pub mod layout;
+pub mod loader;
+pub mod maxflow;
+pub mod measures;
+pub mod metadata;
+pub mod motifs;
+pub mod muscle;
+pub mod network;
+pub mod nwb;
+pub mod paths;
+pub mod pca;
+#[cfg(feature = "plotting")]
+pub mod plotting;
+pub mod png;
+pub mod population;
+pub mod propagation;
+pub mod rate;
+pub mod recording;
+pub mod result_bundle;
+pub mod rewire;
+pub mod rng;
+pub mod select;
+pub mod selectivity;
+pub mod simulation;
+pub mod snapshot;
+pub mod spike_distance;
+pub mod spikes;
+pub mod states;
+pub mod stats;
+pub mod subnetwork;
+pub mod surrogate;
+pub mod sweep;
+pub mod symmetry;
+pub mod synapses;
+pub mod synthetic;
+pub mod trace;
+pub mod trials;
+pub mod walk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod weight_history;
+pub mod weights;
+
+pub use annotations::{Annotations, AttrValue};
+pub use bundle::SimTarget;
+pub use centrality::{EdgeBetweenness, PowerIteration};
+pub use closed_loop::{
+    run_closed_loop, ChemoSensor, ClosedLoopConfig, Environment, GradientWorld, MotorReadout,
+    SensoryDrive, TrajectoryPoint,
+};
+pub use compare::{ComparisonReport, DegreeChange, EdgeChanges, HubRank};
+pub use cone::InfluenceCone;
+pub use config::SimulationConfig;
+pub use csr::Csr;
+pub use diagnostics::{DiagnosticsObserver, DiagnosticsOptions, Severity};
+pub use edits::EditLog;
+pub use electrical::GraphKind;
+pub use error::{FlymindError, IntegrityError, LoadError};
+pub use events::{EventKind, EventVerbosity, SimEvent};
+pub use experiments::{touch_response, TouchConfig, TouchReport, TouchResponse};
+pub use export::ExportFormat;
+pub use extrasynaptic::ExtrasynapticLoadReport;
+pub use feedback::{FasStrategy, FeedbackArcSet};
+pub use flow::{EdgeDirectionStats, FlowClass, FlowSummary};
+pub use functional::{
+    compare_connectivity, functional_connectivity, ConnectivityComparison, FcMatrix, FcOptions,
+};
+pub use graph::{DaleViolation, Diameter, Direction};
+pub use grouped::{GroupBy, GroupedMatrix};
+pub use heatmap::{
+    ActivityHeatmap, ColorScale, HeatmapLayers, HeatmapOptions, HeatmapQuantity, MatrixOrder,
+    RowOrder,
+};
+pub use isi::{Burst, BurstStats, GroupIsi, IsiStats};
+pub use layout::LayoutOptions;
+pub use loader::{
+    GapJunctionMode, LoadProgress, LoadReport, LoaderConfig, MergeMode, NbrTransform,
+    PolyadicScaling, SynapseCode, UnknownCodePolicy,
+};
+pub use maxflow::{CapacityMode, CutEdge, FlowResult};
+pub use metadata::{NeuronTableReport, TypeProbabilityReport};
+pub use motifs::Motif;
+pub use muscle::{Muscle, MuscleId, MuscleLoadReport, NeuromuscularJunction, NmjParams, TargetId};
+pub use network::{
+    ChemicalSubtype, Connection, ConsolidationPolicy, ConsolidationReport, ExtrasynapticEdges,
+    Modulator, Network, Neuron, NeuronMerge, NeuronType, Region, SelfConnections, SynapseType,
+    ValidationReport,
+};
+pub use nwb::{NwbExport, NwbSession, NwbUnits};
+pub use paths::{Path, PathOptions, SynapseClass};
+pub use pca::{pca, PcaResult};
+#[cfg(feature = "plotting")]
+pub use plotting::{RasterOptions, TraceLayout, VoltageOptions};
+pub use population::{Aggregate, GroupActivity, PopulationRecorder};
+pub use propagation::Propagation;
+pub use rate::{Activation, Attractor, AttractorOptions, RateModel, RateParams};
+pub use recording::{RasterOrder, SpikeRecorder, VoltageRecorder};
+pub use result_bundle::{
+    BundleFile, BundleInputs, LoadedBundle, Manifest, NetworkRecord, SimulationBundle,
+};
+pub use rewire::{RewireConstraints, RewireReport};
+pub use rng::Rng;
+pub use select::{SelectError, Selection};
+pub use selectivity::Selectivity;
+pub use simulation::{
+    ActivityTotals, DepolarizationBlock, InputNormalization, IntegrationMethod, LifParams,
+    NetworkState, SimulationResult, StdpParams, StepInfo, StepIter, StepObserver, Stimulus,
+    StimulusShape, SynapticInput, ThresholdCrossing, TimeConstantSummary, TimeUnit, TraceColumns,
+};
+pub use spike_distance::{
+    van_rossum_distance, victor_purpura_distance, PopulationDistance, SpikeDistance,
+};
+pub use spikes::{SpikeIter, SpikeStorage, SpikeTrain};
+pub use states::BehavioralState;
+pub use stats::{ContactSummary, DegreeSummary, NetworkStats, OutlierMethod};
+pub use surrogate::{empirical_p_value, surrogates, SurrogateMethod, Surrogates};
+pub use sweep::{FrozenTopology, SimulationState};
+pub use symmetry::{Laterality, PairMismatch, PairSymmetry, SymmetryReport};
+pub use synapses::{ConnectionParams, PairOverride, SynapseOverride, SynapseParams};
+pub use trace::{StimulusTrace, TraceBoundary, TraceInterpolation, TraceOptions};
+pub use trials::TrialStats;
+pub use walk::{HittingEstimate, HittingTime};
+pub use weight_history::WeightRecorder;
+pub use weights::WeightTransform;
@@ -0,0 +1,1151 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use csv::ReaderBuilder;
+
+use crate::canonical::canonicalize_name;
+use crate::edits::Structure;
+use crate::error::{FlymindError, LoadError};
+use crate::network::{ChemicalSubtype, Connection, Network, SynapseType};
+
+/// Snapshot handed to the progress callback while a file is loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadProgress {
+    pub rows_read: usize,
+    /// Bytes consumed from the underlying reader so far.
+    pub bytes_read: u64,
+    pub neurons_created: usize,
+    pub connections_added: usize,
+}
+
+/// Callback invoked every `LoaderConfig::progress_interval` rows and once at the
+/// end; returning `Break` aborts the load with `LoadError::Cancelled`.
+pub type ProgressCallback<'a> = &'a mut dyn FnMut(LoadProgress) -> ControlFlow<()>;
+
+/// What the loader does with a Type code that neither
+/// `LoaderConfig::synapse_code_map` nor `SynapseType::from_code` knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownCodePolicy {
+    /// Abort with `LoadError::UnknownSynapseCode`.
+    Error,
+    /// Drop the row and count it in `LoadReport::skipped_rows`.
+    Skip,
+    /// Load the row as an excitatory chemical send.
+    #[default]
+    Permissive,
+}
+
+/// What rows of a Type code load as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SynapseCode {
+    pub synapse_type: SynapseType,
+    /// Rows are polyadic contacts, shared out as `LoaderConfig::polyadic` asks.
+    pub polyadic: bool,
+}
+
+impl SynapseCode {
+    /// A built-in code: `SynapseType::from_code`'s type, polyadic for `Sp`
+    /// and `Rp`.
+    pub fn builtin(code: &str) -> Option<Self> {
+        Some(Self {
+            synapse_type: SynapseType::from_code(code)?,
+            polyadic: matches!(code, "Sp" | "Rp"),
+        })
+    }
+}
+
+impl From<SynapseType> for SynapseCode {
+    /// A code for monadic rows of `synapse_type`.
+    fn from(synapse_type: SynapseType) -> Self {
+        Self {
+            synapse_type,
+            polyadic: false,
+        }
+    }
+}
+
+/// How repeated `(from, to, type)` rows are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// Every row becomes its own connection.
+    #[default]
+    KeepAll,
+    /// Rows repeating an earlier `(from, to, type)` add their weight to it,
+    /// as `Network::consolidate_edges` with `ConsolidationPolicy::KeepByType`
+    /// does after the fact.
+    Sum,
+}
+
+/// How the weights of polyadic chemical rows (`Sp`, and their `Rp` mirrors)
+/// are set. A polyadic release site contacts several postsynaptic neurons at
+/// once, so each contact may be given only its share of the release.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PolyadicScaling {
+    /// Every contact carries its full weight, as a monadic one would.
+    #[default]
+    Off,
+    /// Divide every polyadic contact by this many partners per release site.
+    PerSite(f64),
+    /// Divide each presynaptic neuron's polyadic contacts by its partners per
+    /// release site estimated from the data: its polyadic weight over the
+    /// largest polyadic weight onto any one partner, i.e. as many partners as
+    /// there would be if every site included that strongest partner. The edge
+    /// list does not say which contacts share a site, so this is the most
+    /// sharing the counts allow.
+    Estimated,
+}
+
+/// What the loader turns the Nbr of each connection into before storing it as
+/// the weight: the sum of its rows' Nbr, after `PolyadicScaling`, so merged
+/// rows are transformed once. `Connection::contacts` keeps the count as read
+/// either way; fallback weights for rows without an Nbr are stored as they
+/// are, outside the transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NbrTransform {
+    #[default]
+    Identity,
+    /// `ln(1 + n)`, as `WeightTransform::Log1p` after loading.
+    Log1p,
+    Sqrt,
+}
+
+impl NbrTransform {
+    pub fn apply(&self, nbr: f64) -> f64 {
+        match self {
+            NbrTransform::Identity => nbr,
+            NbrTransform::Log1p => nbr.ln_1p(),
+            NbrTransform::Sqrt => nbr.sqrt(),
+        }
+    }
+}
+
+/// How the gap-junction rows between two neurons are reconciled after loading.
+///
+/// The simulation treats each gap-junction row as a conductance into its
+/// `Neuron 2`, so the two directions of a junction are only equal if the rows
+/// say so.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GapJunctionMode {
+    /// Both directions carry the mean of the two directional weights; a junction
+    /// listed in one direction only is mirrored.
+    #[default]
+    Symmetric,
+    /// Rows are kept as given, modelling rectifying junctions.
+    Asymmetric,
+}
+
+/// Summary of a completed load.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadReport {
+    pub rows_read: usize,
+    pub connections_added: usize,
+    /// Rows dropped under `UnknownCodePolicy::Skip`.
+    pub skipped_rows: usize,
+    /// Occurrences of every unrecognised code, whatever the policy.
+    pub unknown_codes: BTreeMap<String, usize>,
+    /// Rows folded into an earlier connection under `MergeMode::Sum`.
+    pub merged_rows: usize,
+    /// How many of those rows went into each connection, by index. A merged
+    /// connection keeps the `source` of its first row, so this is where the
+    /// rest are accounted for, including rows an append summed into
+    /// connections loaded before it.
+    pub merged_into: BTreeMap<usize, usize>,
+    /// Neuron pairs whose gap-junction weights `GapJunctionMode::Symmetric`
+    /// changed or mirrored.
+    pub symmetrized_gap_junctions: usize,
+    /// Polyadic rows whose weight `PolyadicScaling` reduced.
+    pub scaled_polyadic_rows: usize,
+}
+
+impl LoadReport {
+    /// Human-readable notes about anything unusual seen while loading.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self
+            .unknown_codes
+            .iter()
+            .map(|(code, count)| format!("unknown synapse code {code:?} on {count} rows"))
+            .collect();
+        if self.skipped_rows > 0 {
+            warnings.push(format!("skipped {} rows", self.skipped_rows));
+        }
+        if self.symmetrized_gap_junctions > 0 {
+            warnings.push(format!(
+                "made {} asymmetric gap junctions symmetric",
+                self.symmetrized_gap_junctions
+            ));
+        }
+        warnings
+    }
+}
+
+/// Options controlling how an edge list is turned into a `Network`.
+#[derive(Debug, Clone)]
+pub struct LoaderConfig {
+    /// Weight used when a row has no Nbr value and its synapse type has no entry
+    /// in `default_weights`.
+    pub default_weight: f64,
+    /// Per-synapse-type fallback for rows with a missing or empty Nbr value.
+    pub default_weights: HashMap<SynapseType, f64>,
+    /// Rows between progress callbacks.
+    pub progress_interval: usize,
+    /// Type codes of a dataset's own, looked up before `SynapseCode::builtin`,
+    /// so they can add codes or change what a built-in one means.
+    pub synapse_code_map: HashMap<String, SynapseCode>,
+    pub unknown_code_policy: UnknownCodePolicy,
+    pub merge_mode: MergeMode,
+    pub gap_junctions: GapJunctionMode,
+    pub polyadic: PolyadicScaling,
+    /// Applied to each connection's Nbr once `merge_mode` has summed rows and
+    /// `polyadic` scaled them, before `gap_junctions` reconciles weights.
+    pub weight_transform: NbrTransform,
+    /// Pass names through `canonicalize_name`, so spelling variants of one
+    /// neuron load as that neuron.
+    pub normalize_names: bool,
+    pub delimiter: u8,
+    /// Tag for `Connection::source`. Unset, file loads use the file name and
+    /// reader loads leave connections untagged.
+    pub source: Option<String>,
+}
+
+impl Default for LoaderConfig {
+    fn default() -> Self {
+        Self {
+            default_weight: 1.0,
+            default_weights: HashMap::new(),
+            progress_interval: 10_000,
+            synapse_code_map: HashMap::new(),
+            unknown_code_policy: UnknownCodePolicy::default(),
+            merge_mode: MergeMode::default(),
+            gap_junctions: GapJunctionMode::default(),
+            polyadic: PolyadicScaling::default(),
+            weight_transform: NbrTransform::default(),
+            normalize_names: false,
+            delimiter: b',',
+            source: None,
+        }
+    }
+}
+
+impl LoaderConfig {
+    pub fn with_default_weight(mut self, synapse_type: SynapseType, weight: f64) -> Self {
+        self.default_weights.insert(synapse_type, weight);
+        self
+    }
+
+    /// Load rows whose Type is `code` as `synapse_code`, a `SynapseType` for
+    /// monadic rows; see `synapse_code_map`.
+    pub fn with_synapse_code(
+        mut self,
+        code: impl Into<String>,
+        synapse_code: impl Into<SynapseCode>,
+    ) -> Self {
+        self.synapse_code_map
+            .insert(code.into(), synapse_code.into());
+        self
+    }
+
+    /// What a Type code loads as: `synapse_code_map`'s entry, or else
+    /// `SynapseCode::builtin`.
+    pub fn synapse_code_for(&self, code: &str) -> Option<SynapseCode> {
+        self.synapse_code_map
+            .get(code)
+            .copied()
+            .or_else(|| SynapseCode::builtin(code))
+    }
+
+    /// The synapse type a Type code loads as; see `synapse_code_for`.
+    pub fn synapse_type_for(&self, code: &str) -> Option<SynapseType> {
+        self.synapse_code_for(code).map(|code| code.synapse_type)
+    }
+
+    pub fn default_weight_for(&self, synapse_type: SynapseType) -> f64 {
+        self.default_weights
+            .get(&synapse_type)
+            .copied()
+            .unwrap_or(self.default_weight)
+    }
+}
+
+impl Network {
+    /// Load a `Neuron 1, Neuron 2, Type, Nbr` edge list such as `NeuronConnect.csv`.
+    ///
+    /// A row with fewer than three fields, an empty name, or an Nbr that is
+    /// present but not a finite non-negative number fails the load with a
+    /// `LoadError` giving its line.
+    pub fn from_connect_csv<P: AsRef<Path>>(path: P) -> Result<Self, FlymindError> {
+        Self::from_connect_csv_with(path, &LoaderConfig::default())
+    }
+
+    pub fn from_connect_csv_with<P: AsRef<Path>>(
+        path: P,
+        config: &LoaderConfig,
+    ) -> Result<Self, FlymindError> {
+        Self::load_connect_csv(path, config, None).map(|(network, _)| network)
+    }
+
+    /// Load with a progress callback, also returning the `LoadReport`.
+    pub fn load_connect_csv<P: AsRef<Path>>(
+        path: P,
+        config: &LoaderConfig,
+        progress: Option<ProgressCallback<'_>>,
+    ) -> Result<(Self, LoadReport), FlymindError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        Self::load_tagged(
+            BufReader::new(file),
+            config,
+            progress,
+            file_source(path, config),
+        )
+    }
+
+    /// Same as `from_connect_csv`, reading from any source.
+    pub fn from_connect_reader<R: Read>(reader: R) -> Result<Self, FlymindError> {
+        Self::from_connect_reader_with(reader, &LoaderConfig::default())
+    }
+
+    pub fn from_connect_reader_with<R: Read>(
+        reader: R,
+        config: &LoaderConfig,
+    ) -> Result<Self, FlymindError> {
+        Self::load_connect_reader(reader, config, None).map(|(network, _)| network)
+    }
+
+    /// Stream rows straight into the network, reporting progress as it goes.
+    ///
+    /// Records are never collected, so memory stays proportional to the network
+    /// being built rather than to the file.
+    pub fn load_connect_reader<R: Read>(
+        reader: R,
+        config: &LoaderConfig,
+        progress: Option<ProgressCallback<'_>>,
+    ) -> Result<(Self, LoadReport), FlymindError> {
+        Self::load_tagged(reader, config, progress, config.source.clone())
+    }
+
+    /// Load another edge list into this network, e.g. a separate gap-junction
+    /// table or a file of corrections. Names already in the network reuse its
+    /// neurons and new ones are added after them, or, with `fixed_roster`
+    /// set, fail the load with `LoadError::NotInRoster`. Connections are tagged
+    /// with their own file's name, as `load_connect_csv` would tag them.
+    ///
+    /// Under `MergeMode::Sum`, a row repeating an existing `(from, to, type)`
+    /// adds to that connection, wherever it came from, and
+    /// `GapJunctionMode::Symmetric` reconciles the whole network again at the
+    /// end. `PolyadicScaling` scales only the appended rows, estimating from
+    /// them alone. The report counts only this file, and its `merged_into`
+    /// names the connections of earlier files that took rows of this one. On
+    /// error the network is left as it was and no edit is logged.
+    ///
+    /// Loading files one after another gives the network one load of them
+    /// all would, up to connection order, except for gap junctions that end
+    /// up listed in both directions across files but in one within a file:
+    /// the first file's mirror counts as a row of its own.
+    pub fn append_from_csv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+    ) -> Result<LoadReport, FlymindError> {
+        self.append_from_csv_with(path, config, None)
+    }
+
+    /// `append_from_csv`, reporting to `progress` as `load_connect_csv` does;
+    /// neurons and connections are counted with those the network had.
+    pub fn append_from_csv_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+        progress: Option<ProgressCallback<'_>>,
+    ) -> Result<LoadReport, FlymindError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let source = file_source(path, config);
+        self.append_from(BufReader::new(file), config, progress, source)
+    }
+
+    /// Same as `append_from_csv`, reading from any source, with connections
+    /// tagged by `LoaderConfig::source` only.
+    pub fn append_from_reader<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+    ) -> Result<LoadReport, FlymindError> {
+        self.append_from(reader, config, None, config.source.clone())
+    }
+
+    fn append_from<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+        progress: Option<ProgressCallback<'_>>,
+        source: Option<String>,
+    ) -> Result<LoadReport, FlymindError> {
+        let description = format!("append {}", source.as_deref().unwrap_or("edge list"));
+        self.record_structural(
+            |network| {
+                let before = Structure::of(network);
+                let report = network.append_tagged(reader, config, progress, source);
+                if report.is_err() {
+                    before.restore(network);
+                }
+                report
+            },
+            |report| report.is_ok().then_some(description),
+        )
+    }
+
+    fn load_tagged<R: Read>(
+        reader: R,
+        config: &LoaderConfig,
+        progress: Option<ProgressCallback<'_>>,
+        source: Option<String>,
+    ) -> Result<(Self, LoadReport), FlymindError> {
+        let mut network = Network::new();
+        let report = network.append_tagged(reader, config, progress, source)?;
+        Ok((network, report))
+    }
+
+    fn append_tagged<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+        mut progress: Option<ProgressCallback<'_>>,
+        source: Option<String>,
+    ) -> Result<LoadReport, FlymindError> {
+        let source: Option<Arc<str>> = source.map(Arc::from);
+        let bytes_read = Rc::new(Cell::new(0u64));
+        let reader = CountingReader {
+            inner: reader,
+            count: Rc::clone(&bytes_read),
+        };
+
+        let start = self.connections.len();
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true) // Neuron 1, Neuron 2, Type, Nbr
+            .delimiter(config.delimiter)
+            .flexible(true) // Nbr may be absent on some rows
+            .from_reader(reader);
+
+        // Reuse one record buffer so rows don't allocate; names are hashed as
+        // borrowed strs and only copied the first time a neuron is seen.
+        let mut record = csv::StringRecord::new();
+        let mut report = LoadReport::default();
+        // Rows also merge into connections the network already had.
+        let mut merged: HashMap<(usize, usize, SynapseType), usize> = HashMap::new();
+        if config.merge_mode == MergeMode::Sum {
+            for (index, conn) in self.connections.iter().enumerate() {
+                merged
+                    .entry((conn.from_id, conn.to_id, conn.synapse_type))
+                    .or_insert(index);
+            }
+        }
+        // (connection, weight) of every polyadic row, for `PolyadicScaling`,
+        // and whether the weight is an Nbr.
+        let mut polyadic: Vec<(usize, f64)> = Vec::new();
+        let mut polyadic_nbr: Vec<bool> = Vec::new();
+        // Nbr each connection took from this file, for `weight_transform`.
+        let mut nbr_weights: HashMap<usize, f64> = HashMap::new();
+        let interval = config.progress_interval.max(1);
+        while rdr.read_record(&mut record)? {
+            let line = record.position().map_or(0, |p| p.line());
+            let (Some(neuron1_name), Some(neuron2_name), Some(synapse_str)) =
+                (record.get(0), record.get(1), record.get(2))
+            else {
+                return Err(LoadError::ShortRow {
+                    line,
+                    expected: 3,
+                    found: record.len(),
+                }
+                .into());
+            };
+            for (column, name) in [("Neuron 1", neuron1_name), ("Neuron 2", neuron2_name)] {
+                if name.trim().is_empty() {
+                    return Err(LoadError::InvalidValue {
+                        line,
+                        column: column.to_string(),
+                        value: name.to_string(),
+                    }
+                    .into());
+                }
+            }
+            let nbr_str = record.get(3).unwrap_or("").trim();
+
+            report.rows_read += 1;
+
+            // Convert the Type field (e.g., EJ, Sp, R) into a SynapseType
+            let (syn_type, is_polyadic) = match config.synapse_code_for(synapse_str) {
+                Some(code) => (code.synapse_type, code.polyadic),
+                None => {
+                    *report
+                        .unknown_codes
+                        .entry(synapse_str.to_string())
+                        .or_default() += 1;
+                    match config.unknown_code_policy {
+                        UnknownCodePolicy::Error => {
+                            return Err(LoadError::UnknownSynapseCode {
+                                line,
+                                code: synapse_str.to_string(),
+                            }
+                            .into());
+                        }
+                        UnknownCodePolicy::Skip => {
+                            report.skipped_rows += 1;
+                            maybe_report(&mut progress, self, &report, interval, &bytes_read)?;
+                            continue;
+                        }
+                        UnknownCodePolicy::Permissive => (
+                            SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+                            false,
+                        ),
+                    }
+                }
+            };
+
+            // The target of an NMJ row is a muscle placeholder, not a neuron,
+            // so the roster does not cover it.
+            let muscle = syn_type == SynapseType::NMJ;
+            let (from_id, to_id) = if config.normalize_names {
+                (
+                    self.roster_neuron(&canonicalize_name(neuron1_name), line, false)?,
+                    self.roster_neuron(&canonicalize_name(neuron2_name), line, muscle)?,
+                )
+            } else {
+                (
+                    self.roster_neuron(neuron1_name, line, false)?,
+                    self.roster_neuron(neuron2_name, line, muscle)?,
+                )
+            };
+
+            // Convert Nbr to a floating-point weight, falling back to the
+            // type-specific default when it is missing.
+            let nbr = if nbr_str.is_empty() {
+                None
+            } else {
+                let nbr = nbr_str
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0)
+                    .ok_or_else(|| LoadError::InvalidValue {
+                        line,
+                        column: "Nbr".to_string(),
+                        value: nbr_str.to_string(),
+                    })?;
+                Some(nbr)
+            };
+            let weight = nbr.unwrap_or_else(|| config.default_weight_for(syn_type));
+            let contacts = nbr.map_or(0, |nbr| nbr.round() as u32);
+
+            // Endpoints come from the name index, so they are valid by
+            // construction; adjacency is built once after the last row.
+            if config.merge_mode == MergeMode::Sum {
+                let key = (from_id, to_id, syn_type);
+                if let Some(&index) = merged.get(&key) {
+                    if is_polyadic {
+                        polyadic.push((index, weight));
+                        polyadic_nbr.push(nbr.is_some());
+                    }
+                    if let Some(nbr) = nbr {
+                        *nbr_weights.entry(index).or_default() += nbr;
+                    }
+                    self.connections[index].weight += weight;
+                    self.connections[index].contacts += contacts;
+                    report.merged_rows += 1;
+                    *report.merged_into.entry(index).or_default() += 1;
+                    maybe_report(&mut progress, self, &report, interval, &bytes_read)?;
+                    continue;
+                }
+                merged.insert(key, self.connections.len());
+            }
+            if is_polyadic {
+                polyadic.push((self.connections.len(), weight));
+                polyadic_nbr.push(nbr.is_some());
+            }
+            if let Some(nbr) = nbr {
+                nbr_weights.insert(self.connections.len(), nbr);
+            }
+            let conn = Connection::new(from_id, to_id, syn_type, weight)
+                .with_contacts(contacts)
+                .with_source(source.clone());
+            self.connections.push(conn);
+            maybe_report(&mut progress, self, &report, interval, &bytes_read)?;
+        }
+
+        notify(&mut progress, self, report.rows_read, bytes_read.get())?;
+        let scaled = scale_polyadic(self, &polyadic, config.polyadic);
+        report.scaled_polyadic_rows = scaled.len();
+        if config.weight_transform != NbrTransform::Identity {
+            for (row, removed) in scaled {
+                if polyadic_nbr[row] {
+                    *nbr_weights.entry(polyadic[row].0).or_default() -= removed;
+                }
+            }
+            for (index, nbr) in nbr_weights {
+                let conn = &mut self.connections[index];
+                conn.weight = conn.weight - nbr + config.weight_transform.apply(nbr);
+            }
+        }
+        if config.gap_junctions == GapJunctionMode::Symmetric {
+            report.symmetrized_gap_junctions = symmetrize_gap_junctions(self);
+        }
+        self.rebuild_indices();
+        report.connections_added = self.connections.len() - start;
+        Ok(report)
+    }
+
+    /// The id of `name`, added unless the network has a `fixed_roster` and
+    /// `name` is not an NMJ row's `muscle` target.
+    fn roster_neuron(&mut self, name: &str, line: u64, muscle: bool) -> Result<usize, LoadError> {
+        match self.id_of(name) {
+            Some(id) => Ok(id),
+            None if self.fixed_roster && !muscle => Err(LoadError::NotInRoster {
+                line,
+                name: name.to_string(),
+            }),
+            None => Ok(self.get_or_create_neuron(name)),
+        }
+    }
+}
+
+/// `LoaderConfig::source`, else the file name of `path`.
+fn file_source(path: &Path, config: &LoaderConfig) -> Option<String> {
+    config.source.clone().or_else(|| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    })
+}
+
+/// Reduce the polyadic share `rows` of each connection as `scaling` asks,
+/// returning the position in `rows` of each row that changed and the weight
+/// taken off it. A `ChemicalReceive` row is scaled by its presynaptic neuron,
+/// its `to_id`, so it still mirrors its send.
+fn scale_polyadic(
+    network: &mut Network,
+    rows: &[(usize, f64)],
+    scaling: PolyadicScaling,
+) -> Vec<(usize, f64)> {
+    let presynaptic = |conn: &Connection| match conn.synapse_type {
+        SynapseType::ChemicalReceive(_) => conn.to_id,
+        _ => conn.from_id,
+    };
+    let partners: HashMap<usize, f64> = match scaling {
+        PolyadicScaling::Off => return Vec::new(),
+        PolyadicScaling::PerSite(partners) => rows
+            .iter()
+            .map(|&(index, _)| (presynaptic(&network.connections[index]), partners))
+            .collect(),
+        PolyadicScaling::Estimated => {
+            let mut per_pair: HashMap<(usize, usize), f64> = HashMap::new();
+            for &(index, weight) in rows {
+                let conn = &network.connections[index];
+                if matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) {
+                    *per_pair.entry((conn.from_id, conn.to_id)).or_default() += weight;
+                }
+            }
+            let mut totals: HashMap<usize, (f64, f64)> = HashMap::new();
+            for ((from, _), weight) in per_pair {
+                let (sum, max) = totals.entry(from).or_default();
+                *sum += weight;
+                *max = max.max(weight);
+            }
+            totals
+                .into_iter()
+                .filter(|&(_, (_, max))| max > 0.0)
+                .map(|(from, (sum, max))| (from, sum / max))
+                .collect()
+        }
+    };
+
+    let mut scaled = Vec::new();
+    for (row, &(index, weight)) in rows.iter().enumerate() {
+        let conn = &mut network.connections[index];
+        let share = partners.get(&presynaptic(conn)).copied().unwrap_or(1.0);
+        if share > 1.0 {
+            let removed = weight - weight / share;
+            conn.weight -= removed;
+            scaled.push((row, removed));
+        }
+    }
+    scaled
+}
+
+impl Network {
+    /// Load a dense weight matrix where row `i`, column `j` is the weight from
+    /// neuron `i` to neuron `j`, as written by `write_adjacency_csv`.
+    ///
+    /// Without `neuron_names` the first row names the columns after an empty
+    /// cell and every row starts with its neuron's name, which must match its
+    /// column. With it, the file holds only weights and the names file gives
+    /// one name per line. Each nonzero entry becomes an excitatory chemical
+    /// send; zero means no connection.
+    pub fn from_adjacency_csv<P: AsRef<Path>>(
+        path: P,
+        neuron_names: Option<&Path>,
+    ) -> Result<Self, FlymindError> {
+        Self::from_adjacency_csv_with(
+            path,
+            neuron_names,
+            SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+        )
+    }
+
+    /// Like `from_adjacency_csv`, making every nonzero entry a `synapse_type`
+    /// connection. For gap junctions both directions are read from the matrix
+    /// as they stand, so a symmetric matrix gives symmetric junctions.
+    pub fn from_adjacency_csv_with<P: AsRef<Path>>(
+        path: P,
+        neuron_names: Option<&Path>,
+        synapse_type: SynapseType,
+    ) -> Result<Self, FlymindError> {
+        let path = path.as_ref();
+        let names = match neuron_names {
+            Some(names_path) => Some(
+                std::fs::read_to_string(names_path)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+            None => None,
+        };
+        let file = File::open(path)?;
+        let mut network =
+            Self::from_adjacency_reader(BufReader::new(file), names.as_deref(), synapse_type)?;
+        let source: Option<Arc<str>> = path
+            .file_name()
+            .map(|name| Arc::from(name.to_string_lossy().as_ref()));
+        for conn in &mut network.connections {
+            conn.source = source.clone();
+        }
+        Ok(network)
+    }
+
+    /// Same as `from_adjacency_csv_with`, reading from any source; `neuron_names`
+    /// replaces the header and row names when given.
+    pub fn from_adjacency_reader<R: Read>(
+        reader: R,
+        neuron_names: Option<&[String]>,
+        synapse_type: SynapseType,
+    ) -> Result<Self, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(neuron_names.is_none())
+            .flexible(true) // row lengths are checked against the names
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let names: Vec<String> = match neuron_names {
+            Some(names) => names.to_vec(),
+            None => rdr.headers()?.iter().skip(1).map(str::to_string).collect(),
+        };
+
+        let mut network = Network::new();
+        for (index, name) in names.iter().enumerate() {
+            if network.get_or_create_neuron(name) != index {
+                return Err(LoadError::InvalidValue {
+                    line: 1,
+                    column: "name".to_string(),
+                    value: name.clone(),
+                }
+                .into());
+            }
+        }
+
+        let labelled = usize::from(neuron_names.is_none());
+        let mut record = csv::StringRecord::new();
+        let mut rows = 0;
+        while rdr.read_record(&mut record)? {
+            let line = record.position().map_or(0, |p| p.line());
+            if record.len() != names.len() + labelled {
+                return Err(LoadError::RowLength {
+                    line,
+                    expected: names.len(),
+                    found: record.len().saturating_sub(labelled),
+                }
+                .into());
+            }
+            if rows == names.len() {
+                return Err(LoadError::NotSquare {
+                    rows: rows + 1,
+                    columns: names.len(),
+                }
+                .into());
+            }
+            if labelled == 1 && record[0] != names[rows] {
+                return Err(LoadError::InvalidValue {
+                    line,
+                    column: "name".to_string(),
+                    value: record[0].to_string(),
+                }
+                .into());
+            }
+            for (to_id, cell) in record.iter().skip(labelled).enumerate() {
+                let weight = cell
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0)
+                    .ok_or_else(|| LoadError::InvalidValue {
+                        line,
+                        column: names[to_id].clone(),
+                        value: cell.to_string(),
+                    })?;
+                if weight != 0.0 {
+                    let contacts = cell.parse::<u32>().unwrap_or(0);
+                    network.connections.push(
+                        Connection::new(rows, to_id, synapse_type, weight).with_contacts(contacts),
+                    );
+                }
+            }
+            rows += 1;
+        }
+        if rows != names.len() {
+            return Err(LoadError::NotSquare {
+                rows,
+                columns: names.len(),
+            }
+            .into());
+        }
+        network.rebuild_indices();
+        Ok(network)
+    }
+}
+
+/// Give both directions of every gap junction the mean of the two directional
+/// weights, returning the number of neuron pairs that needed it.
+pub(crate) fn symmetrize_gap_junctions(network: &mut Network) -> usize {
+    let mut totals: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+    let mut contacts: BTreeMap<(usize, usize), u32> = BTreeMap::new();
+    let mut sources: BTreeMap<(usize, usize), Option<Arc<str>>> = BTreeMap::new();
+    for conn in &network.connections {
+        if conn.synapse_type == SynapseType::GapJunction && conn.from_id != conn.to_id {
+            let pair = (conn.from_id, conn.to_id);
+            *totals.entry(pair).or_default() += conn.weight;
+            *contacts.entry(pair).or_default() += conn.contacts;
+            sources.entry(pair).or_insert_with(|| conn.source.clone());
+        }
+    }
+
+    let mut changed = 0;
+    let mut mirrors = Vec::new();
+    for (&(a, b), &forward) in &totals {
+        match totals.get(&(b, a)) {
+            // Each pair is handled from its lower id when both directions exist.
+            Some(&backward) if a < b && forward != backward => changed += 1,
+            Some(_) => {}
+            None => {
+                let mirror = Connection::new(b, a, SynapseType::GapJunction, forward);
+                mirrors.push(
+                    mirror
+                        .with_contacts(contacts[&(a, b)])
+                        .with_source(sources[&(a, b)].clone()),
+                );
+                changed += 1;
+            }
+        }
+    }
+
+    for conn in &mut network.connections {
+        if conn.synapse_type != SynapseType::GapJunction || conn.from_id == conn.to_id {
+            continue;
+        }
+        let forward = totals[&(conn.from_id, conn.to_id)];
+        if let Some(&backward) = totals.get(&(conn.to_id, conn.from_id)) {
+            if forward != 0.0 {
+                conn.weight *= (forward + backward) / 2.0 / forward;
+            }
+        }
+    }
+    network.connections.extend(mirrors);
+    changed
+}
+
+fn maybe_report(
+    progress: &mut Option<ProgressCallback<'_>>,
+    network: &Network,
+    report: &LoadReport,
+    interval: usize,
+    bytes_read: &Cell<u64>,
+) -> Result<(), LoadError> {
+    if report.rows_read.is_multiple_of(interval) {
+        notify(progress, network, report.rows_read, bytes_read.get())?;
+    }
+    Ok(())
+}
+
+fn notify(
+    progress: &mut Option<ProgressCallback<'_>>,
+    network: &Network,
+    rows_read: usize,
+    bytes_read: u64,
+) -> Result<(), LoadError> {
+    let Some(callback) = progress else {
+        return Ok(());
+    };
+    let snapshot = LoadProgress {
+        rows_read,
+        bytes_read,
+        neurons_created: network.neurons.len(),
+        connections_added: network.connections.len(),
+    };
+    match callback(snapshot) {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(LoadError::Cancelled { rows_read }),
+    }
+}
+
+/// Pass-through reader that tallies how many bytes have been consumed.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic;
+
+    fn config(progress_interval: usize) -> LoaderConfig {
+        LoaderConfig {
+            progress_interval,
+            ..LoaderConfig::default()
+        }
+    }
+
+    #[test]
+    fn breaking_from_the_callback_cancels_the_load() {
+        let csv = synthetic::edge_list_csv(20, 500, 7);
+        let mut calls = 0;
+        let mut stop_third = |_: LoadProgress| {
+            calls += 1;
+            if calls == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        let Err(err) =
+            Network::load_connect_reader(csv.as_slice(), &config(50), Some(&mut stop_third))
+        else {
+            panic!("the load finished although the callback asked it to stop");
+        };
+        assert!(
+            matches!(
+                err,
+                FlymindError::Load(LoadError::Cancelled { rows_read: 150 })
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn progress_bytes_are_monotonic_and_end_at_the_input_size() {
+        let csv = synthetic::edge_list_csv(20, 500, 7);
+        let mut seen = Vec::new();
+        let mut record = |p: LoadProgress| {
+            seen.push(p);
+            ControlFlow::Continue(())
+        };
+        let (network, report) =
+            Network::load_connect_reader(csv.as_slice(), &config(64), Some(&mut record)).unwrap();
+
+        assert_eq!(report.rows_read, 500);
+        assert!(seen.len() > 2, "only {} callbacks", seen.len());
+        assert!(seen
+            .windows(2)
+            .all(|w| w[0].bytes_read <= w[1].bytes_read && w[0].rows_read <= w[1].rows_read));
+        let last = seen.last().unwrap();
+        assert_eq!(last.bytes_read, csv.len() as u64);
+        assert_eq!(last.rows_read, 500);
+        assert_eq!(last.neurons_created, network.neurons.len());
+    }
+
+    const FIRST: &str = "Neuron 1,Neuron 2,Type,Nbr\nA,B,S,2\nB,C,EJ,1\nC,B,EJ,1\nA,B,S,1\n";
+    const SECOND: &str = "Neuron 1,Neuron 2,Type,Nbr\nA,B,S,4\nC,D,Sp,3\nD,A,R,1\nC,D,Sp,2\n";
+
+    /// Every connection as names, type, weight and contacts, sorted.
+    fn edges(network: &Network) -> Vec<(String, String, String, f64, u32)> {
+        let mut edges: Vec<_> = network
+            .connections
+            .iter()
+            .map(|conn| {
+                (
+                    network.neurons[conn.from_id].name().to_string(),
+                    network.neurons[conn.to_id].name().to_string(),
+                    format!("{:?}", conn.synapse_type),
+                    conn.weight,
+                    conn.contacts,
+                )
+            })
+            .collect();
+        edges.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        edges
+    }
+
+    #[test]
+    fn appending_matches_loading_the_files_as_one() {
+        let both = format!("{FIRST}{}", SECOND.split_once('\n').unwrap().1);
+        for merge_mode in [MergeMode::KeepAll, MergeMode::Sum] {
+            let config = LoaderConfig {
+                merge_mode,
+                ..LoaderConfig::default()
+            };
+            let (mut appended, _) =
+                Network::load_connect_reader(FIRST.as_bytes(), &config, None).unwrap();
+            let report = appended
+                .append_from_reader(SECOND.as_bytes(), &config)
+                .unwrap();
+            let (whole, _) = Network::load_connect_reader(both.as_bytes(), &config, None).unwrap();
+            assert_eq!(edges(&appended), edges(&whole), "{merge_mode:?}");
+
+            if merge_mode == MergeMode::Sum {
+                // The second A->B row went into the first file's connection, the
+                // second C->D into the first C->D.
+                let ab = appended
+                    .connections
+                    .iter()
+                    .position(|conn| conn.from_id == 0 && conn.to_id == 1)
+                    .unwrap();
+                assert_eq!(report.merged_rows, 2);
+                assert_eq!(report.merged_into.get(&ab), Some(&1));
+                assert_eq!(report.merged_into.values().sum::<usize>(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn a_failed_append_leaves_the_network_as_it_was() {
+        let config = LoaderConfig {
+            merge_mode: MergeMode::Sum,
+            ..LoaderConfig::default()
+        };
+        let (mut network, _) =
+            Network::load_connect_reader(FIRST.as_bytes(), &config, None).unwrap();
+        let before = edges(&network);
+        let neurons = network.neurons.len();
+        network.start_edit_log();
+        let bad = "Neuron 1,Neuron 2,Type,Nbr\nA,B,S,4\nC,E,S,1\nE,A,S,x\n";
+        let result = network.append_from_reader(bad.as_bytes(), &config);
+        assert!(matches!(
+            result,
+            Err(FlymindError::Load(LoadError::InvalidValue { line: 4, .. }))
+        ));
+        assert_eq!(edges(&network), before);
+        assert_eq!(network.neurons.len(), neurons);
+        assert_eq!(network.id_of("E"), None);
+        assert_eq!(
+            network.outgoing_map.values().map(Vec::len).sum::<usize>(),
+            before.len()
+        );
+        assert!(network.edit_log().unwrap().is_empty());
+    }
+
+    #[test]
+    fn names_outside_a_fixed_roster_fail_the_append() {
+        let mut network = Network::with_canonical_roster();
+        let neurons = network.neurons.len();
+        let csv = "Neuron 1,Neuron 2,Type,Nbr\nAVAL,AVAR,EJ,2\nAVAL,AVXQ,S,1\n";
+        let mut calls = 0;
+        let mut count = |_: LoadProgress| {
+            calls += 1;
+            ControlFlow::Continue(())
+        };
+        let path = std::env::temp_dir().join(format!("flymind-roster-{}.csv", std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+        let result = network.append_from_csv_with(&path, &config(1), Some(&mut count));
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(FlymindError::Load(LoadError::NotInRoster { line, name })) => {
+                assert_eq!((line, name.as_str()), (3, "AVXQ"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(calls, 1);
+        assert_eq!(network.neurons.len(), neurons);
+        assert!(network.connections.is_empty());
+
+        // Muscle targets of NMJ rows are not neurons, so the roster allows them.
+        let nmj = "Neuron 1,Neuron 2,Type,Nbr\nDA01,BWM-DL01,NMJ,1\n";
+        let report = network
+            .append_from_reader(nmj.as_bytes(), &config(1))
+            .unwrap();
+        assert_eq!(report.connections_added, 1);
+    }
+
+    #[test]
+    fn the_transform_applies_once_per_connection() {
+        let csv =
+            "Neuron 1,Neuron 2,Type,Nbr\nA,B,S,3.0\nA,B,S,6\nB,C,S,1\nB,C,Sp,6\nC,A,S,\nC,A,S,4\n";
+        let load = |merge_mode: MergeMode| {
+            let config = LoaderConfig {
+                merge_mode,
+                polyadic: PolyadicScaling::PerSite(2.0),
+                weight_transform: NbrTransform::Sqrt,
+                default_weight: 0.5,
+                ..LoaderConfig::default()
+            };
+            let (network, _) = Network::load_connect_reader(csv.as_bytes(), &config, None).unwrap();
+            let weights: Vec<(f64, u32)> = network
+                .connections
+                .iter()
+                .map(|conn| (conn.weight, conn.contacts))
+                .collect();
+            weights
+        };
+        // Merged rows sum their Nbr, polyadic shares are scaled, and only then
+        // is the sum transformed; fallback weights are added as they are.
+        assert_eq!(load(MergeMode::Sum), vec![(3.0, 9), (2.0, 7), (2.5, 4)]);
+        let root = f64::sqrt;
+        assert_eq!(
+            load(MergeMode::KeepAll),
+            vec![
+                (root(3.0), 3),
+                (root(6.0), 6),
+                (1.0, 1),
+                (root(3.0), 6),
+                (0.5, 0),
+                (2.0, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn mapped_codes_decide_the_type_and_polyadicity() {
+        let csv = "Neuron 1,Neuron 2,Type,Nbr\nA,B,GJ,2\nA,C,Sx,4\nA,C,Sp,4\n";
+        let config = LoaderConfig {
+            polyadic: PolyadicScaling::PerSite(2.0),
+            ..LoaderConfig::default()
+        }
+        .with_synapse_code("GJ", SynapseType::GapJunction)
+        .with_synapse_code("Sx", SynapseCode::builtin("Sp").unwrap())
+        .with_synapse_code("Sp", SynapseType::ChemicalSend(ChemicalSubtype::Excitatory));
+        let (network, report) =
+            Network::load_connect_reader(csv.as_bytes(), &config, None).unwrap();
+        assert!(report.unknown_codes.is_empty());
+        let loaded: Vec<(SynapseType, f64)> = network
+            .connections
+            .iter()
+            .filter(|conn| conn.from_id == 0)
+            .map(|conn| (conn.synapse_type, conn.weight))
+            .collect();
+        assert_eq!(
+            loaded,
+            vec![
+                (SynapseType::GapJunction, 2.0),
+                (SynapseType::ChemicalSend(ChemicalSubtype::Excitatory), 2.0),
+                (SynapseType::ChemicalSend(ChemicalSubtype::Excitatory), 4.0)
+            ]
+        );
+    }
+}
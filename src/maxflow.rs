@@ -0,0 +1,281 @@
+//! Maximum flow and minimum cut between neuron groups (Dinic's algorithm).
+//!
+//! The flow network is the directed chemical graph: every `ChemicalSend` is an
+//! arc from presynaptic to postsynaptic neuron, parallel rows staying separate
+//! arcs. A super-source feeds every source and every sink drains into a
+//! super-sink without limit, so groups of any size work.
+
+use std::collections::VecDeque;
+
+use crate::network::{Network, SynapseType};
+
+/// Residual capacity below this counts as saturated.
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CapacityMode {
+    /// Each arc carries its weight; non-positive weights carry nothing.
+    #[default]
+    Weight,
+    /// Each arc carries 1, so the flow counts edge-disjoint paths.
+    Unit,
+}
+
+impl CapacityMode {
+    fn of(self, weight: f64) -> f64 {
+        match self {
+            CapacityMode::Weight => weight.max(0.0),
+            CapacityMode::Unit => 1.0,
+        }
+    }
+}
+
+/// A connection in a minimum cut.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CutEdge {
+    /// Index into `connections`.
+    pub connection: usize,
+    pub from: String,
+    pub to: String,
+    pub capacity: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowResult {
+    pub value: f64,
+    /// Saturated connections from the source side to the sink side, by index;
+    /// their capacities sum to `value`.
+    pub cut: Vec<CutEdge>,
+    /// Neurons still reachable from the sources in the residual graph,
+    /// ascending.
+    pub source_side: Vec<usize>,
+}
+
+struct FlowArc {
+    to: usize,
+    /// Position of the reverse arc in `arcs[to]`.
+    rev: usize,
+    residual: f64,
+    /// The connection this arc stands for; `None` for reverse and super arcs.
+    connection: Option<usize>,
+}
+
+impl Network {
+    /// Maximum flow from `sources` to `sinks` along chemical sends. Neurons in
+    /// both lists count as sources only, and ids out of range are ignored.
+    pub fn max_flow(
+        &self,
+        sources: &[usize],
+        sinks: &[usize],
+        capacity: CapacityMode,
+    ) -> FlowResult {
+        let n = self.neurons.len();
+        let (source, sink) = (n, n + 1);
+        let mut arcs: Vec<Vec<FlowArc>> = (0..n + 2).map(|_| Vec::new()).collect();
+        for (index, conn) in self.connections.iter().enumerate() {
+            if !matches!(conn.synapse_type, SynapseType::ChemicalSend(_))
+                || conn.from_id == conn.to_id
+            {
+                continue;
+            }
+            let cap = capacity.of(conn.weight);
+            add_arc(&mut arcs, conn.from_id, conn.to_id, cap, Some(index));
+        }
+        let mut is_source = vec![false; n];
+        for &s in sources.iter().filter(|&&s| s < n) {
+            if !is_source[s] {
+                is_source[s] = true;
+                add_arc(&mut arcs, source, s, f64::INFINITY, None);
+            }
+        }
+        let mut is_sink = vec![false; n];
+        for &t in sinks.iter().filter(|&&t| t < n && !is_source[t]) {
+            if !is_sink[t] {
+                is_sink[t] = true;
+                add_arc(&mut arcs, t, sink, f64::INFINITY, None);
+            }
+        }
+
+        let mut value = 0.0;
+        let mut level = vec![usize::MAX; n + 2];
+        let mut next = vec![0; n + 2];
+        loop {
+            levels(&arcs, source, &mut level);
+            if level[sink] == usize::MAX {
+                break;
+            }
+            next.fill(0);
+            loop {
+                let pushed = augment(&mut arcs, &level, &mut next, source, sink, f64::INFINITY);
+                if pushed <= EPSILON {
+                    break;
+                }
+                value += pushed;
+            }
+        }
+
+        // What the final level search reached is the source side of a minimum cut.
+        let reached: Vec<bool> = level.iter().map(|&l| l != usize::MAX).collect();
+        let mut cut = Vec::new();
+        for (v, list) in arcs.iter().enumerate().take(n) {
+            if !reached[v] {
+                continue;
+            }
+            for arc in list {
+                if let Some(index) = arc.connection {
+                    if !reached[arc.to] {
+                        let conn = &self.connections[index];
+                        cut.push(CutEdge {
+                            connection: index,
+                            from: self.neurons[conn.from_id].name().to_string(),
+                            to: self.neurons[conn.to_id].name().to_string(),
+                            capacity: capacity.of(conn.weight),
+                        });
+                    }
+                }
+            }
+        }
+        cut.retain(|edge| edge.capacity > 0.0);
+        cut.sort_by_key(|edge| edge.connection);
+        FlowResult {
+            value,
+            cut,
+            source_side: (0..n).filter(|&v| reached[v]).collect(),
+        }
+    }
+}
+
+fn add_arc(
+    arcs: &mut [Vec<FlowArc>],
+    from: usize,
+    to: usize,
+    capacity: f64,
+    connection: Option<usize>,
+) {
+    let (rev_from, rev_to) = (arcs[to].len(), arcs[from].len());
+    arcs[from].push(FlowArc {
+        to,
+        rev: rev_from,
+        residual: capacity,
+        connection,
+    });
+    arcs[to].push(FlowArc {
+        to: from,
+        rev: rev_to,
+        residual: 0.0,
+        connection: None,
+    });
+}
+
+/// Breadth-first levels over unsaturated arcs, `usize::MAX` where unreached.
+fn levels(arcs: &[Vec<FlowArc>], source: usize, level: &mut [usize]) {
+    level.fill(usize::MAX);
+    level[source] = 0;
+    let mut queue = VecDeque::from([source]);
+    while let Some(v) = queue.pop_front() {
+        for arc in &arcs[v] {
+            if arc.residual > EPSILON && level[arc.to] == usize::MAX {
+                level[arc.to] = level[v] + 1;
+                queue.push_back(arc.to);
+            }
+        }
+    }
+}
+
+/// Push up to `limit` along one level-increasing path from `v`, returning what
+/// was pushed. `next` skips arcs already found to be dead ends.
+fn augment(
+    arcs: &mut [Vec<FlowArc>],
+    level: &[usize],
+    next: &mut [usize],
+    v: usize,
+    sink: usize,
+    limit: f64,
+) -> f64 {
+    if v == sink {
+        return limit;
+    }
+    while next[v] < arcs[v].len() {
+        let (to, residual) = (arcs[v][next[v]].to, arcs[v][next[v]].residual);
+        if residual > EPSILON && level[to] == level[v] + 1 {
+            let pushed = augment(arcs, level, next, to, sink, limit.min(residual));
+            if pushed > EPSILON {
+                let rev = arcs[v][next[v]].rev;
+                arcs[v][next[v]].residual -= pushed;
+                arcs[to][rev].residual += pushed;
+                return pushed;
+            }
+        }
+        next[v] += 1;
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    /// The flow network of CLRS figure 26.1, s = 0 and t = 5.
+    fn clrs() -> Network {
+        let mut network = Network::with_neuron_roster(&["s", "v1", "v2", "v3", "v4", "t"]);
+        for (from, to, capacity) in [
+            (0, 1, 16.0),
+            (0, 2, 13.0),
+            (2, 1, 4.0),
+            (1, 3, 12.0),
+            (3, 2, 9.0),
+            (2, 4, 14.0),
+            (4, 3, 7.0),
+            (3, 5, 20.0),
+            (4, 5, 4.0),
+        ] {
+            network.add_connection(from, to, EXCITATORY, capacity);
+        }
+        network
+    }
+
+    /// `(connection, capacity)` of each cut edge.
+    fn cut(result: &FlowResult) -> Vec<(usize, f64)> {
+        result
+            .cut
+            .iter()
+            .map(|edge| (edge.connection, edge.capacity))
+            .collect()
+    }
+
+    #[test]
+    fn the_textbook_network_carries_23() {
+        let result = clrs().max_flow(&[0], &[5], CapacityMode::Weight);
+        assert_eq!(result.value, 23.0);
+        // v1 -> v3, v4 -> v3 and v4 -> t, the cut the book gives.
+        assert_eq!(cut(&result), vec![(3, 12.0), (6, 7.0), (8, 4.0)]);
+        assert_eq!(result.cut[2].from, "v4");
+        assert_eq!(result.cut[2].to, "t");
+        assert_eq!(result.source_side, vec![0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn unit_capacities_count_edge_disjoint_paths() {
+        let result = clrs().max_flow(&[0], &[5], CapacityMode::Unit);
+        assert_eq!(result.value, 2.0);
+        assert_eq!(cut(&result), vec![(0, 1.0), (1, 1.0)]);
+        assert_eq!(result.source_side, vec![0]);
+    }
+
+    #[test]
+    fn every_cut_weighs_the_flow() {
+        let network = clrs();
+        for mode in [CapacityMode::Weight, CapacityMode::Unit] {
+            for (sources, sinks) in [(&[0][..], &[5][..]), (&[0, 3], &[5]), (&[2], &[3, 5])] {
+                let result = network.max_flow(sources, sinks, mode);
+                let capacity: f64 = result.cut.iter().map(|edge| edge.capacity).sum();
+                assert_eq!(capacity, result.value, "{mode:?} {sources:?} -> {sinks:?}");
+            }
+        }
+        let none = network.max_flow(&[5], &[0], CapacityMode::Weight);
+        assert_eq!((none.value, none.cut.len()), (0.0, 0));
+    }
+}
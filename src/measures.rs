@@ -0,0 +1,233 @@
+//! Whole-network structural descriptors.
+
+use std::collections::HashMap;
+
+use crate::functional::pearson;
+use crate::network::{Network, Region, SynapseType};
+
+impl Network {
+    /// Rich-club coefficient for degree threshold `k`.
+    ///
+    /// Computed on the undirected simple graph: every connection type counts,
+    /// direction and parallel edges are ignored, and a neuron's degree is its
+    /// number of distinct partners. With `N_k` neurons of degree greater than `k`
+    /// and `E_k` edges among them, the coefficient is `2 E_k / (N_k (N_k - 1))`.
+    /// Returns 0.0 when fewer than two neurons qualify.
+    pub fn rich_club_coefficient(&self, k: usize) -> f64 {
+        rich_club(&self.undirected_adjacency(), k)
+    }
+
+    /// `(k, coefficient)` for every `k` that leaves at least two rich neurons.
+    pub fn rich_club_curve(&self) -> Vec<(usize, f64)> {
+        let adjacency = self.undirected_adjacency();
+        let mut degrees: Vec<usize> = adjacency.iter().map(Vec::len).collect();
+        degrees.sort_unstable();
+        // Largest k such that two neurons still have degree > k.
+        let Some(&second_largest) = degrees.iter().rev().nth(1) else {
+            return Vec::new();
+        };
+        (0..second_largest)
+            .map(|k| (k, rich_club(&adjacency, k)))
+            .collect()
+    }
+
+    /// Unweighted degree assortativity of the chemical graph: the Pearson
+    /// correlation, over every distinct `ChemicalSend` edge `u -> v`, of the
+    /// out-degree of `u` with the in-degree of `v` (the out-in pairing
+    /// standard for directed graphs). Degrees count distinct chemical
+    /// partners, as in `chemical_adjacency`, whatever their weights; see
+    /// `weighted_degree_assortativity` for strengths. Positive when
+    /// well-connected senders favour well-connected receivers. NaN when
+    /// undefined: without edges, or when either degree is the same on every
+    /// edge.
+    pub fn degree_assortativity(&self) -> f64 {
+        let adjacency = self.chemical_adjacency();
+        let mut in_degree = vec![0usize; adjacency.len()];
+        for &to in adjacency.iter().flatten() {
+            in_degree[to] += 1;
+        }
+        let (sources, targets): (Vec<f64>, Vec<f64>) = adjacency
+            .iter()
+            .flat_map(|targets| {
+                let out = targets.len() as f64;
+                targets.iter().map(move |&to| (out, to))
+            })
+            .map(|(out, to)| (out, in_degree[to] as f64))
+            .unzip();
+        pearson(&sources, &targets)
+    }
+
+    /// `degree_assortativity` with strengths in place of degrees: over the
+    /// same distinct chemical edges `u -> v`, the Pearson correlation of the
+    /// summed `ChemicalSend` weight leaving `u` with that arriving at `v`.
+    /// Parallel rows add to the strengths but the edge is still one sample.
+    /// NaN when undefined, as there.
+    pub fn weighted_degree_assortativity(&self) -> f64 {
+        let adjacency = self.chemical_adjacency();
+        let mut out_strength = vec![0.0; adjacency.len()];
+        let mut in_strength = vec![0.0; adjacency.len()];
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) && self.counts(conn) {
+                out_strength[conn.from_id] += conn.weight;
+                in_strength[conn.to_id] += conn.weight;
+            }
+        }
+        let (sources, targets): (Vec<f64>, Vec<f64>) = adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(from, targets)| targets.iter().map(move |&to| (from, to)))
+            .map(|(from, to)| (out_strength[from], in_strength[to]))
+            .unzip();
+        pearson(&sources, &targets)
+    }
+
+    /// Participation coefficient `1 - sum_s (k_s / k)^2` of every neuron listed
+    /// in `communities`, where `k` is its degree and `k_s` how many of its
+    /// partners lie in community `s`; near 1 for connector hubs spreading their
+    /// links evenly, 0 for neurons linked within one community only (or not at
+    /// all). Degrees are as in `rich_club_coefficient`. A neuron listed twice
+    /// belongs to its first community, and partners in no community each count
+    /// as a community of their own. Out-of-range ids are ignored.
+    pub fn participation_coefficient(&self, communities: &[Vec<usize>]) -> HashMap<usize, f64> {
+        let n = self.neurons.len();
+        let mut community = vec![None; n];
+        for (index, members) in communities.iter().enumerate() {
+            for &id in members.iter().filter(|&&id| id < n) {
+                community[id].get_or_insert(index);
+            }
+        }
+        let adjacency = self.undirected_adjacency();
+        let mut coefficients = HashMap::new();
+        for id in (0..n).filter(|&id| community[id].is_some()) {
+            let neighbours = &adjacency[id];
+            if neighbours.is_empty() {
+                coefficients.insert(id, 0.0);
+                continue;
+            }
+            let mut per_community: HashMap<usize, usize> = HashMap::new();
+            let mut unassigned = 0;
+            for &m in neighbours {
+                match community[m] {
+                    Some(c) => *per_community.entry(c).or_insert(0) += 1,
+                    None => unassigned += 1,
+                }
+            }
+            let k = neighbours.len() as f64;
+            let concentration: f64 = per_community
+                .values()
+                .map(|&k_s| (k_s as f64 / k).powi(2))
+                .sum::<f64>()
+                + unassigned as f64 / (k * k);
+            coefficients.insert(id, 1.0 - concentration);
+        }
+        coefficients
+    }
+}
+
+impl Network {
+    /// Summed connection weight for every ordered `(from region, to region)` pair
+    /// that has at least one connection. `ChemicalReceive` rows repeat the sends
+    /// and are left out; every other row counts. Neurons without an assigned
+    /// region appear under `Region::Unknown` rather than being folded into a
+    /// real region.
+    pub fn region_connectivity(&self) -> HashMap<(Region, Region), f64> {
+        let mut totals = HashMap::new();
+        for conn in self.connections.iter().filter(|conn| {
+            self.counts(conn) && !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_))
+        }) {
+            let key = (
+                self.neurons[conn.from_id].region,
+                self.neurons[conn.to_id].region,
+            );
+            *totals.entry(key).or_insert(0.0) += conn.weight;
+        }
+        totals
+    }
+
+    /// Like `region_connectivity`, counting connections instead of summing weights.
+    pub fn region_connection_counts(&self) -> HashMap<(Region, Region), usize> {
+        let mut counts = HashMap::new();
+        for conn in self.connections.iter().filter(|conn| {
+            self.counts(conn) && !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_))
+        }) {
+            let key = (
+                self.neurons[conn.from_id].region,
+                self.neurons[conn.to_id].region,
+            );
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+fn rich_club(adjacency: &[Vec<usize>], k: usize) -> f64 {
+    let rich: Vec<bool> = adjacency.iter().map(|n| n.len() > k).collect();
+    let count = rich.iter().filter(|&&r| r).count();
+    if count < 2 {
+        return 0.0;
+    }
+    let edges: usize = adjacency
+        .iter()
+        .enumerate()
+        .filter(|(id, _)| rich[*id])
+        .map(|(id, neighbours)| neighbours.iter().filter(|&&m| m > id && rich[m]).count())
+        .sum();
+    2.0 * edges as f64 / (count * (count - 1)) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, NeuronType};
+
+    #[test]
+    fn region_connectivity_counts_a_send_once() {
+        let mut network = Network::new();
+        let head = network.add_neuron("A", NeuronType::Sensory, Region::Head, 0.0);
+        let tail = network.add_neuron("B", NeuronType::Motor, Region::Tail, 1.0);
+        let excitatory = ChemicalSubtype::Excitatory;
+        network.add_connection(head, tail, SynapseType::ChemicalSend(excitatory), 3.0);
+        network.add_connection(tail, head, SynapseType::ChemicalReceive(excitatory), 3.0);
+        network.add_connection(tail, head, SynapseType::GapJunction, 2.0);
+
+        let weights = network.region_connectivity();
+        assert_eq!(weights[&(Region::Head, Region::Tail)], 3.0);
+        assert_eq!(weights[&(Region::Tail, Region::Head)], 2.0);
+        let counts = network.region_connection_counts();
+        assert_eq!(counts.values().sum::<usize>(), 2);
+    }
+
+    fn chemical(edges: &[(usize, usize, f64)]) -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C", "D"]);
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        for &(from, to, weight) in edges {
+            network.add_connection(from, to, send, weight);
+        }
+        network
+    }
+
+    #[test]
+    fn assortativity_pairs_sender_out_with_receiver_in() {
+        // Out-in degrees on A->B, A->C and D->B are (2, 2), (2, 1), (1, 2).
+        let network = chemical(&[(0, 1, 1.0), (0, 2, 1.0), (3, 1, 1.0)]);
+        assert!((network.degree_assortativity() + 0.5).abs() < 1e-12);
+        assert!(Network::with_neuron_roster(&["A"])
+            .degree_assortativity()
+            .is_nan());
+    }
+
+    #[test]
+    fn weighted_assortativity_uses_strengths() {
+        // Every degree is 1, so only the strengths tell A->B and C->D apart.
+        let mut network = chemical(&[(0, 1, 1.0), (2, 3, 2.0), (2, 3, 1.0)]);
+        assert!(network.degree_assortativity().is_nan());
+        assert!((network.weighted_degree_assortativity() - 1.0).abs() < 1e-12);
+
+        // Neither receive mirrors nor gap junctions are chemical sends.
+        let excitatory = ChemicalSubtype::Excitatory;
+        network.add_connection(1, 0, SynapseType::ChemicalReceive(excitatory), 9.0);
+        network.add_connection(0, 3, SynapseType::GapJunction, 9.0);
+        assert!((network.weighted_degree_assortativity() - 1.0).abs() < 1e-12);
+        assert!(chemical(&[]).weighted_degree_assortativity().is_nan());
+    }
+}
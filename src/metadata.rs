@@ -0,0 +1,321 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord};
+
+use crate::canonical::canonicalize_name;
+use crate::error::{FlymindError, LoadError};
+use crate::loader::LoaderConfig;
+use crate::network::{Network, NeuronType, Region};
+
+/// Accepted header names (case-insensitive) of each neuron table column.
+const NAME_COLUMNS: [&str; 2] = ["name", "neuron"];
+const TYPE_COLUMNS: [&str; 2] = ["type", "neuron_type"];
+const REGION_COLUMNS: [&str; 1] = ["region"];
+const POSITION_COLUMNS: [&str; 3] = ["position", "soma_position", "soma_pos"];
+const TRANSMITTER_COLUMNS: [&str; 3] = ["transmitter", "neurotransmitter", "nt"];
+
+/// What `Network::apply_neuron_table` found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NeuronTableReport {
+    pub rows_read: usize,
+    /// Neurons the table updated.
+    pub applied: usize,
+    /// Columns recognised in the header, among `type`, `region`, `position`
+    /// and `transmitter`.
+    pub columns: Vec<&'static str>,
+    /// Table names that matched no neuron, in table order.
+    pub unknown_neurons: Vec<String>,
+}
+
+/// What `Network::apply_type_probabilities` found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeProbabilityReport {
+    pub rows_read: usize,
+    /// Neurons given a type from the table, `Other` ones included.
+    pub assigned: usize,
+    /// Of those, the ones left `Other` for falling short of the threshold.
+    pub below_threshold: usize,
+    /// Table names that matched no neuron, in table order.
+    pub unknown_neurons: Vec<String>,
+}
+
+impl Network {
+    /// Apply a `name, type[, region]` CSV (with header) to neurons already in the
+    /// network. Returns the names that matched no neuron; unparseable labels leave
+    /// the existing value alone. A type set here clears `Neuron::type_confidence`.
+    pub fn apply_metadata_csv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Vec<String>, FlymindError> {
+        let file = File::open(path)?;
+        self.apply_metadata_reader(BufReader::new(file))
+    }
+
+    pub fn apply_metadata_reader<R: Read>(
+        &mut self,
+        reader: R,
+    ) -> Result<Vec<String>, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(reader);
+
+        let mut unmatched = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            let Some(name) = record.get(0).map(str::trim) else {
+                continue;
+            };
+            let Some(id) = self.id_of(name) else {
+                unmatched.push(name.to_string());
+                continue;
+            };
+            if let Some(neuron_type) = record.get(1).and_then(NeuronType::from_label) {
+                self.neurons[id].neuron_type = neuron_type;
+                self.neurons[id].type_confidence = None;
+            }
+            if let Some(region) = record.get(2).and_then(Region::from_label) {
+                self.neurons[id].region = region;
+            }
+        }
+        Ok(unmatched)
+    }
+
+    /// Apply a neuron table with a header naming its columns: `name` (required)
+    /// and any of `type`, `region`, `position` and `transmitter`, in any order
+    /// and under the aliases above; other columns are ignored. Names go through
+    /// `canonicalize_name` when `config.normalize_names` is set, as when loading.
+    /// Empty or unparseable cells leave the existing value alone; a type given
+    /// outright clears any `Neuron::type_confidence`.
+    pub fn apply_neuron_table<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+    ) -> Result<NeuronTableReport, FlymindError> {
+        let file = File::open(path)?;
+        self.apply_neuron_table_reader(BufReader::new(file), config)
+    }
+
+    pub fn apply_neuron_table_reader<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+    ) -> Result<NeuronTableReport, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .delimiter(config.delimiter)
+            .from_reader(reader);
+        let header = rdr.headers()?.clone();
+        let find = |aliases: &[&str]| {
+            header
+                .iter()
+                .position(|h| aliases.iter().any(|a| h.trim().eq_ignore_ascii_case(a)))
+        };
+        let name_column = find(&NAME_COLUMNS).ok_or_else(|| LoadError::MissingColumn {
+            column: "name".into(),
+        })?;
+        let type_column = find(&TYPE_COLUMNS);
+        let region_column = find(&REGION_COLUMNS);
+        let position_column = find(&POSITION_COLUMNS);
+        let transmitter_column = find(&TRANSMITTER_COLUMNS);
+
+        let mut report = NeuronTableReport::default();
+        for (label, column) in [
+            ("type", type_column),
+            ("region", region_column),
+            ("position", position_column),
+            ("transmitter", transmitter_column),
+        ] {
+            if column.is_some() {
+                report.columns.push(label);
+            }
+        }
+
+        for result in rdr.records() {
+            let record = result?;
+            report.rows_read += 1;
+            let Some(name) = cell(&record, Some(name_column)) else {
+                continue;
+            };
+            let id = if config.normalize_names {
+                self.id_of(&canonicalize_name(name))
+            } else {
+                self.id_of(name)
+            };
+            let Some(id) = id else {
+                report.unknown_neurons.push(name.to_string());
+                continue;
+            };
+            let neuron = &mut self.neurons[id];
+            if let Some(neuron_type) = cell(&record, type_column).and_then(NeuronType::from_label) {
+                neuron.neuron_type = neuron_type;
+                neuron.type_confidence = None;
+            }
+            if let Some(region) = cell(&record, region_column).and_then(Region::from_label) {
+                neuron.region = region;
+            }
+            if let Some(position) = cell(&record, position_column).and_then(|p| p.parse().ok()) {
+                neuron.soma_position = position;
+            }
+            if let Some(transmitter) = cell(&record, transmitter_column) {
+                neuron.transmitter = Some(transmitter.to_string());
+            }
+            report.applied += 1;
+        }
+        Ok(report)
+    }
+
+    /// Assign types from a table of type probabilities, as a classifier
+    /// gives them: a `name` column (as in `apply_neuron_table`) and one column
+    /// per type, headed by a label `NeuronType::from_label` accepts, e.g.
+    /// `name,sensory,interneuron,motor`. Each listed neuron takes its most
+    /// likely type, ties going to the leftmost column, and keeps that
+    /// probability as `Neuron::type_confidence`; one below `min_confidence`
+    /// becomes `Other` instead, still with its confidence, so analyses can
+    /// leave it out (e.g. with the `typeconf` selection). Probabilities are
+    /// read as given, without normalizing rows; empty cells count as zero and
+    /// rows with every cell empty are skipped. A cell that is not a number in
+    /// `[0, 1]` fails the load, leaving earlier rows applied.
+    pub fn apply_type_probabilities<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+        min_confidence: f64,
+    ) -> Result<TypeProbabilityReport, FlymindError> {
+        let file = File::open(path)?;
+        self.apply_type_probabilities_reader(BufReader::new(file), config, min_confidence)
+    }
+
+    pub fn apply_type_probabilities_reader<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+        min_confidence: f64,
+    ) -> Result<TypeProbabilityReport, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .delimiter(config.delimiter)
+            .from_reader(reader);
+        let header = rdr.headers()?.clone();
+        let name_column = header
+            .iter()
+            .position(|h| {
+                NAME_COLUMNS
+                    .iter()
+                    .any(|a| h.trim().eq_ignore_ascii_case(a))
+            })
+            .ok_or_else(|| LoadError::MissingColumn {
+                column: "name".into(),
+            })?;
+        let type_columns: Vec<(usize, NeuronType)> = header
+            .iter()
+            .enumerate()
+            .filter_map(|(column, h)| NeuronType::from_label(h).map(|t| (column, t)))
+            .collect();
+        if type_columns.is_empty() {
+            return Err(LoadError::MissingColumn {
+                column: "type probability".into(),
+            }
+            .into());
+        }
+
+        let mut report = TypeProbabilityReport::default();
+        for result in rdr.records() {
+            let record = result?;
+            report.rows_read += 1;
+            let Some(name) = cell(&record, Some(name_column)) else {
+                continue;
+            };
+            let mut best: Option<(NeuronType, f64)> = None;
+            let mut any = false;
+            for &(column, neuron_type) in &type_columns {
+                let p = match cell(&record, Some(column)) {
+                    None => 0.0,
+                    Some(value) => {
+                        any = true;
+                        value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|p| (0.0..=1.0).contains(p))
+                            .ok_or_else(|| LoadError::InvalidValue {
+                                line: record.position().map_or(0, |p| p.line()),
+                                column: header[column].trim().to_string(),
+                                value: value.to_string(),
+                            })?
+                    }
+                };
+                if best.is_none_or(|(_, most)| p > most) {
+                    best = Some((neuron_type, p));
+                }
+            }
+            if !any {
+                continue;
+            }
+            let id = if config.normalize_names {
+                self.id_of(&canonicalize_name(name))
+            } else {
+                self.id_of(name)
+            };
+            let Some(id) = id else {
+                report.unknown_neurons.push(name.to_string());
+                continue;
+            };
+            let (neuron_type, confidence) = best.expect("the table has a type column");
+            let neuron = &mut self.neurons[id];
+            if confidence < min_confidence {
+                neuron.neuron_type = NeuronType::Other;
+                report.below_threshold += 1;
+            } else {
+                neuron.neuron_type = neuron_type;
+            }
+            neuron.type_confidence = Some(confidence);
+            report.assigned += 1;
+        }
+        Ok(report)
+    }
+}
+
+/// The trimmed, non-empty value of `column`.
+fn cell(record: &StringRecord, column: Option<usize>) -> Option<&str> {
+    column
+        .and_then(|c| record.get(c))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_labelled_type_clears_the_confidence() {
+        let mut network = Network::example_touch_circuit();
+        let config = LoaderConfig::default();
+        let probabilities = "name,sensory,motor\nALML,0.9,0.1\nAVAL,0.4,0.6\nPVCL,0.3,0.3\n";
+        let report = network
+            .apply_type_probabilities_reader(probabilities.as_bytes(), &config, 0.5)
+            .unwrap();
+        assert_eq!((report.assigned, report.below_threshold), (3, 1));
+        let confidence = |network: &Network, name: &str| {
+            network.neurons[network.id_of(name).unwrap()].type_confidence
+        };
+        assert_eq!(confidence(&network, "ALML"), Some(0.9));
+        assert_eq!(confidence(&network, "PVCL"), Some(0.3));
+
+        let table = "name,type\nALML,sensory\nAVAL,\n";
+        network
+            .apply_neuron_table_reader(table.as_bytes(), &config)
+            .unwrap();
+        assert_eq!(confidence(&network, "ALML"), None);
+        assert_eq!(confidence(&network, "AVAL"), Some(0.6));
+
+        network
+            .apply_metadata_reader("name,type\nPVCL,interneuron\n".as_bytes())
+            .unwrap();
+        assert_eq!(confidence(&network, "PVCL"), None);
+    }
+}
@@ -0,0 +1,362 @@
+//! Three-neuron motifs of the chemical wiring and their significance against
+//! the degree-preserving null model of `crate::rewire`.
+//!
+//! A triad is three neurons with at least one chemical connection linking each
+//! to another, counted once whatever the weights or the number of rows
+//! between a pair; self-connections are ignored. Triads are told apart by
+//! their pattern of mutual and one-way links, in the 13 connected classes of
+//! the Holland-Leinhardt triad census, named by `Motif::code`.
+
+use std::collections::HashMap;
+
+use crate::network::Network;
+use crate::rewire::RewireConstraints;
+use crate::rng::Rng;
+
+/// Degree-preserving swaps made per chemical edge for each null network.
+const SWAPS_PER_EDGE: usize = 10;
+
+/// A connected triad class; the doc of each variant draws it with neurons
+/// A, B and C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Motif {
+    /// A <- B -> C.
+    OutStar,
+    /// A -> B <- C.
+    InStar,
+    /// A -> B -> C.
+    Chain,
+    /// A <-> B <- C.
+    MutualIn,
+    /// A <-> B -> C.
+    MutualOut,
+    /// A -> B <- C and A -> C: the feed-forward loop.
+    FeedForward,
+    /// A -> B -> C -> A.
+    Cycle,
+    /// A <-> B <-> C.
+    MutualChain,
+    /// A <- B -> C and A <-> C.
+    MutualOutStar,
+    /// A -> B <- C and A <-> C.
+    MutualInStar,
+    /// A -> B -> C and A <-> C.
+    MutualCycle,
+    /// A -> B <-> C and A <-> C.
+    NearClique,
+    /// Every pair mutually connected.
+    Clique,
+}
+
+impl Motif {
+    /// Every class, in census order.
+    pub const ALL: [Motif; 13] = [
+        Motif::OutStar,
+        Motif::InStar,
+        Motif::Chain,
+        Motif::MutualIn,
+        Motif::MutualOut,
+        Motif::FeedForward,
+        Motif::Cycle,
+        Motif::MutualChain,
+        Motif::MutualOutStar,
+        Motif::MutualInStar,
+        Motif::MutualCycle,
+        Motif::NearClique,
+        Motif::Clique,
+    ];
+
+    /// The class's name in the triad census: mutual, asymmetric and null
+    /// pair counts, then a letter telling classes with the same counts apart.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Motif::OutStar => "021D",
+            Motif::InStar => "021U",
+            Motif::Chain => "021C",
+            Motif::MutualIn => "111D",
+            Motif::MutualOut => "111U",
+            Motif::FeedForward => "030T",
+            Motif::Cycle => "030C",
+            Motif::MutualChain => "201",
+            Motif::MutualOutStar => "120D",
+            Motif::MutualInStar => "120U",
+            Motif::MutualCycle => "120C",
+            Motif::NearClique => "210",
+            Motif::Clique => "300",
+        }
+    }
+
+    /// Classify the triad whose links are `edge(x, y)`, for x and y among
+    /// 0, 1 and 2, or `None` when it is not connected.
+    fn classify(edge: impl Fn(usize, usize) -> bool) -> Option<Motif> {
+        let pairs = [(0, 1), (0, 2), (1, 2)];
+        let mutual = pairs
+            .iter()
+            .filter(|&&(x, y)| edge(x, y) && edge(y, x))
+            .count();
+        let one_way: Vec<(usize, usize)> = pairs
+            .iter()
+            .filter_map(|&(x, y)| match (edge(x, y), edge(y, x)) {
+                (true, false) => Some((x, y)),
+                (false, true) => Some((y, x)),
+                _ => None,
+            })
+            .collect();
+        let out_degree = |node: usize| one_way.iter().filter(|&&(from, _)| from == node).count();
+        let in_degree = |node: usize| one_way.iter().filter(|&&(_, to)| to == node).count();
+        let motif = match (mutual, one_way.len()) {
+            (0, 2) => {
+                if (0..3).any(|node| out_degree(node) == 2) {
+                    Motif::OutStar
+                } else if (0..3).any(|node| in_degree(node) == 2) {
+                    Motif::InStar
+                } else {
+                    Motif::Chain
+                }
+            }
+            (1, 1) => {
+                // The one-way link ends in the mutual pair, or leaves it.
+                let (x, y) = pairs
+                    .into_iter()
+                    .find(|&(x, y)| edge(x, y) && edge(y, x))
+                    .expect("one pair is mutual");
+                if one_way[0].1 == x || one_way[0].1 == y {
+                    Motif::MutualIn
+                } else {
+                    Motif::MutualOut
+                }
+            }
+            (0, 3) => {
+                if (0..3).any(|node| out_degree(node) == 2) {
+                    Motif::FeedForward
+                } else {
+                    Motif::Cycle
+                }
+            }
+            (2, 0) => Motif::MutualChain,
+            (1, 2) => {
+                if (0..3).any(|node| out_degree(node) == 2) {
+                    Motif::MutualOutStar
+                } else if (0..3).any(|node| in_degree(node) == 2) {
+                    Motif::MutualInStar
+                } else {
+                    Motif::MutualCycle
+                }
+            }
+            (2, 1) => Motif::NearClique,
+            (3, 0) => Motif::Clique,
+            _ => return None,
+        };
+        Some(motif)
+    }
+}
+
+impl Network {
+    /// How many triads of each class the chemical wiring has; every class is
+    /// listed, with zero where none occurs. Each triad is visited once, as in
+    /// Batagelj and Mrvar's triad census.
+    pub fn motif_counts(&self) -> HashMap<Motif, usize> {
+        let successors = self.chemical_adjacency();
+        let n = successors.len();
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (from, list) in successors.iter().enumerate() {
+            for &to in list.iter().filter(|&&to| to != from) {
+                neighbours[from].push(to);
+                neighbours[to].push(from);
+            }
+        }
+        for list in &mut neighbours {
+            list.sort_unstable();
+            list.dedup();
+        }
+        let edge = |from: usize, to: usize| successors[from].binary_search(&to).is_ok();
+        let adjacent = |a: usize, b: usize| neighbours[a].binary_search(&b).is_ok();
+
+        let mut counts: HashMap<Motif, usize> = Motif::ALL.iter().map(|&m| (m, 0)).collect();
+        let mut third: Vec<usize> = Vec::new();
+        for v in 0..n {
+            for &u in neighbours[v].iter().filter(|&&u| u > v) {
+                third.clear();
+                third.extend(neighbours[u].iter().chain(&neighbours[v]).copied());
+                third.sort_unstable();
+                third.dedup();
+                for &w in third.iter().filter(|&&w| w != u && w != v) {
+                    // Counted from its lowest-id link, so once.
+                    if u < w || (v < w && w < u && !adjacent(v, w)) {
+                        let triad = [v, u, w];
+                        if let Some(motif) = Motif::classify(|x, y| edge(triad[x], triad[y])) {
+                            *counts.entry(motif).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    /// Z-score of every class's count against `n_null` copies of the network
+    /// rewired with `Network::rewire`, ten swaps per chemical edge each, from
+    /// seeds drawn from `seed`: the count minus the null mean, over the null
+    /// standard deviation. Classes the nulls never vary on get NaN, as does
+    /// every class when `n_null` is below 2.
+    pub fn motif_zscores(&self, n_null: usize, seed: u64) -> HashMap<Motif, f64> {
+        let observed = self.motif_counts();
+        let ids: Vec<usize> = (0..self.neurons.len()).collect();
+        let edges: usize = self.chemical_adjacency().iter().map(Vec::len).sum();
+        let mut seeds = Rng::new(seed);
+        let nulls: Vec<HashMap<Motif, usize>> = (0..n_null)
+            .map(|_| {
+                let mut null = self.subnetwork(&ids);
+                null.rewire(
+                    edges * SWAPS_PER_EDGE,
+                    RewireConstraints::default(),
+                    seeds.next_u64(),
+                );
+                null.motif_counts()
+            })
+            .collect();
+
+        Motif::ALL
+            .iter()
+            .map(|&motif| {
+                let count = |counts: &HashMap<Motif, usize>| counts[&motif] as f64;
+                let z = if n_null < 2 {
+                    f64::NAN
+                } else {
+                    let mean = nulls.iter().map(count).sum::<f64>() / n_null as f64;
+                    let variance = nulls.iter().map(|c| (count(c) - mean).powi(2)).sum::<f64>()
+                        / (n_null - 1) as f64;
+                    let sd = variance.sqrt();
+                    if sd > 0.0 {
+                        (count(&observed) - mean) / sd
+                    } else {
+                        f64::NAN
+                    }
+                };
+                (motif, z)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, SynapseType};
+
+    fn wired(neurons: usize, edges: &[(usize, usize)]) -> Network {
+        let mut network = Network::new();
+        for id in 0..neurons {
+            network.get_or_create_neuron(&format!("N{id}"));
+        }
+        for &(from, to) in edges {
+            network.add_connection(
+                from,
+                to,
+                SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+                1.0,
+            );
+        }
+        network
+    }
+
+    fn only(counts: &HashMap<Motif, usize>) -> Vec<(Motif, usize)> {
+        let mut found: Vec<(Motif, usize)> = counts
+            .iter()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(&motif, &count)| (motif, count))
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    #[test]
+    fn each_drawn_triad_is_its_own_class() {
+        let (a, b, c) = (0, 1, 2);
+        let drawings: [(Motif, Vec<(usize, usize)>); 13] = [
+            (Motif::OutStar, vec![(b, a), (b, c)]),
+            (Motif::InStar, vec![(a, b), (c, b)]),
+            (Motif::Chain, vec![(a, b), (b, c)]),
+            (Motif::MutualIn, vec![(a, b), (b, a), (c, b)]),
+            (Motif::MutualOut, vec![(a, b), (b, a), (b, c)]),
+            (Motif::FeedForward, vec![(a, b), (c, b), (a, c)]),
+            (Motif::Cycle, vec![(a, b), (b, c), (c, a)]),
+            (Motif::MutualChain, vec![(a, b), (b, a), (b, c), (c, b)]),
+            (Motif::MutualOutStar, vec![(b, a), (b, c), (a, c), (c, a)]),
+            (Motif::MutualInStar, vec![(a, b), (c, b), (a, c), (c, a)]),
+            (Motif::MutualCycle, vec![(a, b), (b, c), (a, c), (c, a)]),
+            (
+                Motif::NearClique,
+                vec![(a, b), (b, c), (c, b), (a, c), (c, a)],
+            ),
+            (
+                Motif::Clique,
+                vec![(a, b), (b, a), (b, c), (c, b), (a, c), (c, a)],
+            ),
+        ];
+        for (motif, edges) in drawings {
+            // Every relabelling of the drawing is the same class.
+            for order in [
+                [0, 1, 2],
+                [0, 2, 1],
+                [1, 0, 2],
+                [1, 2, 0],
+                [2, 0, 1],
+                [2, 1, 0],
+            ] {
+                let relabelled: Vec<(usize, usize)> = edges
+                    .iter()
+                    .map(|&(from, to)| (order[from], order[to]))
+                    .collect();
+                let counts = wired(3, &relabelled).motif_counts();
+                assert_eq!(counts.len(), 13);
+                assert_eq!(only(&counts), vec![(motif, 1)], "{}", motif.code());
+            }
+        }
+    }
+
+    #[test]
+    fn the_census_visits_every_connected_triad_once() {
+        let mut rng = Rng::new(4);
+        let edges: Vec<(usize, usize)> = (0..60).map(|_| (rng.below(15), rng.below(15))).collect();
+        let network = wired(15, &edges);
+        let successors = network.chemical_adjacency();
+        let edge = |from: usize, to: usize| from != to && successors[from].contains(&to);
+        let mut expected: HashMap<Motif, usize> = HashMap::new();
+        for a in 0..15 {
+            for b in a + 1..15 {
+                for c in b + 1..15 {
+                    let triad = [a, b, c];
+                    if let Some(motif) = Motif::classify(|x, y| edge(triad[x], triad[y])) {
+                        *expected.entry(motif).or_default() += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(only(&network.motif_counts()), only(&expected));
+    }
+
+    #[test]
+    fn planted_feed_forward_loops_stand_out() {
+        // Twenty disjoint loops and a ring through their sources, so swaps
+        // have room to break the loops up.
+        let mut edges = Vec::new();
+        for k in 0..20 {
+            let (a, b, c) = (3 * k, 3 * k + 1, 3 * k + 2);
+            edges.extend([(a, b), (c, b), (a, c)]);
+            edges.push((c, (3 * k + 3) % 60));
+        }
+        let network = wired(60, &edges);
+        assert_eq!(network.motif_counts()[&Motif::FeedForward], 20);
+
+        let z = network.motif_zscores(20, 8);
+        assert_eq!(z.len(), 13);
+        assert!(z[&Motif::FeedForward] > 3.0, "{}", z[&Motif::FeedForward]);
+        assert!(z[&Motif::Clique].is_nan());
+        let again = network.motif_zscores(20, 8);
+        assert!(Motif::ALL
+            .iter()
+            .all(|motif| z[motif].to_bits() == again[motif].to_bits()));
+        assert!(network.motif_zscores(1, 8).values().all(|z| z.is_nan()));
+    }
+}
@@ -0,0 +1,358 @@
+//! Muscles driven by motor neurons.
+//!
+//! Muscles sit beside the neurons with ids of their own, `MuscleId`, so a
+//! muscle index cannot be mistaken for a neuron id. `Network::neuromuscular`
+//! junctions each link a neuron to a muscle. The NMJ rows of a
+//! NeuronConnect-style edge list, which lump a neuron's whole muscle output
+//! into one pseudo-neuron, stay in `connections` as they are and drive nothing.
+//!
+//! Each step, every muscle's activation decays with time constant
+//! `Network::muscle_tau` and takes in its NMJ drive. A presynaptic spike adds
+//! its junction weight times `NmjParams::gain` to the drive, which passes into
+//! the activation with time constant `NmjParams::tau`: all of it on the step
+//! of the spike when that is zero, the default, else spread over the steps
+//! after. Either way a spike adds `gain * weight` to the activation in all,
+//! before the activation's own decay. None of this touches the connections
+//! between neurons, so the motor output can be tuned on its own.
+//!
+//! `SimulationResult::body_bend` gives a coarser readout straight from motor
+//! neuron spikes, for runs without muscles.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use csv::ReaderBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::canonical::canonicalize_name;
+use crate::error::{FlymindError, LoadError};
+use crate::loader::LoaderConfig;
+use crate::network::{Network, SynapseType};
+use crate::simulation::SimulationResult;
+
+/// Index into `Network::muscles`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MuscleId(pub usize);
+
+/// What a neuron's output lands on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetId {
+    Neuron(usize),
+    Muscle(MuscleId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Muscle {
+    pub id: MuscleId,
+    pub name: String,
+    /// Simulation state, in units of junction weight.
+    pub activation: f64,
+    /// NMJ input not yet passed into `activation`.
+    pub drive: f64,
+}
+
+/// Transmission at neuromuscular junctions, apart from chemical synapses.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NmjParams {
+    /// Multiplies every junction weight.
+    pub gain: f64,
+    /// Time constant of the drive, in `lif.time_unit`; zero passes it on at
+    /// once.
+    pub tau: f64,
+}
+
+impl Default for NmjParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            tau: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NeuromuscularJunction {
+    pub from_id: usize,
+    pub muscle: MuscleId,
+    pub weight: f64,
+}
+
+/// What `Network::load_muscle_connectivity` found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MuscleLoadReport {
+    pub rows_read: usize,
+    pub junctions_added: usize,
+    pub muscles_added: usize,
+    /// Presynaptic names that matched no neuron, in file order; their rows
+    /// are skipped.
+    pub unknown_neurons: Vec<String>,
+}
+
+/// Accepted header names (case-insensitive) of muscle file columns.
+const NEURON_COLUMNS: [&str; 3] = ["neuron", "neuron1", "source"];
+const MUSCLE_COLUMNS: [&str; 3] = ["muscle", "neuron2", "target"];
+const WEIGHT_COLUMNS: [&str; 4] = ["weight", "nbr", "connections", "number of connections"];
+
+impl Network {
+    pub fn muscle_id(&self, name: &str) -> Option<MuscleId> {
+        self.muscles.iter().find(|m| m.name == name).map(|m| m.id)
+    }
+
+    /// The muscle called `name`, added at rest if new.
+    pub fn add_muscle(&mut self, name: &str) -> MuscleId {
+        if let Some(id) = self.muscle_id(name) {
+            return id;
+        }
+        let id = MuscleId(self.muscles.len());
+        self.muscles.push(Muscle {
+            id,
+            name: name.to_string(),
+            activation: 0.0,
+            drive: 0.0,
+        });
+        id
+    }
+
+    pub fn add_neuromuscular_junction(
+        &mut self,
+        from_id: usize,
+        muscle: MuscleId,
+        weight: f64,
+    ) -> Result<(), FlymindError> {
+        if from_id >= self.neurons.len() {
+            return Err(FlymindError::InvalidNeuronId {
+                position: 0,
+                id: from_id,
+                neuron_count: self.neurons.len(),
+            });
+        }
+        self.neuromuscular.push(NeuromuscularJunction {
+            from_id,
+            muscle,
+            weight,
+        });
+        Ok(())
+    }
+
+    /// Read neuron-to-muscle junctions from a CSV whose header names a
+    /// `neuron` column, a `muscle` column and optionally a `weight` column (see
+    /// the aliases above). Muscles are created as they first appear; rows
+    /// without a weight use `config.default_weight_for(SynapseType::NMJ)`.
+    /// Neuron names go through `canonicalize_name` when `config.normalize_names`
+    /// is set.
+    pub fn load_muscle_connectivity<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        config: &LoaderConfig,
+    ) -> Result<MuscleLoadReport, FlymindError> {
+        let file = File::open(path)?;
+        self.load_muscle_reader(BufReader::new(file), config)
+    }
+
+    pub fn load_muscle_reader<R: Read>(
+        &mut self,
+        reader: R,
+        config: &LoaderConfig,
+    ) -> Result<MuscleLoadReport, FlymindError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .delimiter(config.delimiter)
+            .from_reader(reader);
+        let header = rdr.headers()?.clone();
+        let find = |aliases: &[&str]| {
+            header
+                .iter()
+                .position(|h| aliases.iter().any(|a| h.trim().eq_ignore_ascii_case(a)))
+        };
+        let neuron_column = find(&NEURON_COLUMNS).ok_or_else(|| LoadError::MissingColumn {
+            column: "neuron".into(),
+        })?;
+        let muscle_column = find(&MUSCLE_COLUMNS).ok_or_else(|| LoadError::MissingColumn {
+            column: "muscle".into(),
+        })?;
+        let weight_column = find(&WEIGHT_COLUMNS);
+        let muscles_before = self.muscles.len();
+
+        let mut report = MuscleLoadReport::default();
+        for result in rdr.records() {
+            let record = result?;
+            report.rows_read += 1;
+            let field = |column: usize| record.get(column).map(str::trim).filter(|v| !v.is_empty());
+            let (Some(neuron), Some(muscle)) = (field(neuron_column), field(muscle_column)) else {
+                continue;
+            };
+            let id = if config.normalize_names {
+                self.id_of(&canonicalize_name(neuron))
+            } else {
+                self.id_of(neuron)
+            };
+            let Some(id) = id else {
+                report.unknown_neurons.push(neuron.to_string());
+                continue;
+            };
+            let weight = weight_column
+                .and_then(field)
+                .and_then(|w| w.parse().ok())
+                .unwrap_or_else(|| config.default_weight_for(SynapseType::NMJ));
+            let muscle = self.add_muscle(muscle);
+            self.neuromuscular.push(NeuromuscularJunction {
+                from_id: id,
+                muscle,
+                weight,
+            });
+            report.junctions_added += 1;
+        }
+        report.muscles_added = self.muscles.len() - muscles_before;
+        Ok(report)
+    }
+
+    /// Current activation of every muscle, indexed by `MuscleId`.
+    pub fn muscle_activation(&self) -> Vec<f64> {
+        self.muscles.iter().map(|m| m.activation).collect()
+    }
+
+    /// Everything neuron `id` connects to: its outgoing connections (except
+    /// `ChemicalReceive` mirrors) and its neuromuscular junctions, with weights.
+    pub fn targets_of(&self, id: usize) -> Vec<(TargetId, f64)> {
+        let mut targets: Vec<(TargetId, f64)> = self
+            .outgoing_map
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.connections[index])
+            .filter(|conn| {
+                !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) && self.counts(conn)
+            })
+            .map(|conn| (TargetId::Neuron(conn.to_id), conn.weight))
+            .collect();
+        targets.extend(
+            self.neuromuscular
+                .iter()
+                .filter(|j| j.from_id == id)
+                .map(|j| (TargetId::Muscle(j.muscle), j.weight)),
+        );
+        targets
+    }
+
+    /// Advance muscle activations after the neurons of a step have fired.
+    pub(crate) fn update_muscles(&mut self, dt: f64) {
+        if self.muscles.is_empty() {
+            return;
+        }
+        let decay = (-dt / self.muscle_tau).exp();
+        let drive_decay = if self.nmj.tau > 0.0 {
+            (-dt / self.nmj.tau).exp()
+        } else {
+            0.0
+        };
+        for junction in &self.neuromuscular {
+            if self.neurons[junction.from_id].just_fired {
+                if let Some(muscle) = self.muscles.get_mut(junction.muscle.0) {
+                    muscle.drive += self.nmj.gain * junction.weight;
+                }
+            }
+        }
+        for muscle in &mut self.muscles {
+            muscle.activation = muscle.activation * decay + muscle.drive * (1.0 - drive_decay);
+            muscle.drive *= drive_decay;
+        }
+    }
+}
+
+/// Time constant of the activations behind `SimulationResult::body_bend`,
+/// the default `Network::muscle_tau`.
+const BEND_TAU: f64 = 20.0;
+
+impl SimulationResult {
+    /// A 1D bend angle per step: the mean activation of the `dorsal` motor
+    /// neurons minus that of the `ventral` ones, positive bending dorsally.
+    /// A neuron's activation decays with a time constant of 20 time units and
+    /// rises by one each time it fires, which also smooths the bend. Ids
+    /// outside the run are ignored, and an empty side counts as zero.
+    pub fn body_bend(&self, dorsal: &[usize], ventral: &[usize]) -> Vec<f64> {
+        let dorsal = self.mean_activation(dorsal);
+        let ventral = self.mean_activation(ventral);
+        dorsal.iter().zip(&ventral).map(|(d, v)| d - v).collect()
+    }
+
+    /// Mean spike-driven activation of `ids` after every step.
+    fn mean_activation(&self, ids: &[usize]) -> Vec<f64> {
+        let mut fired = vec![0.0; self.steps];
+        let mut count = 0;
+        for train in ids.iter().filter_map(|&id| self.spike_trains.get(id)) {
+            count += 1;
+            for step in train.iter().filter(|&step| step < self.steps) {
+                fired[step] += 1.0;
+            }
+        }
+        if count == 0 {
+            return vec![0.0; self.steps];
+        }
+        let decay = (-self.dt / BEND_TAU).exp();
+        let mut activation = 0.0;
+        fired
+            .into_iter()
+            .map(|spikes| {
+                activation = activation * decay + spikes;
+                activation / count as f64
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{NeuronType, Region};
+
+    /// A motor neuron with a junction of weight 3 onto one muscle, whose
+    /// activation does not decay.
+    fn network(nmj: NmjParams) -> Network {
+        let mut network = Network::new();
+        let id = network.add_neuron("DA01", NeuronType::Motor, Region::MidBody, 0.0);
+        let muscle = network.add_muscle("mu_bod_DL01");
+        network.add_neuromuscular_junction(id, muscle, 3.0).unwrap();
+        network.muscle_tau = f64::INFINITY;
+        network.nmj = nmj;
+        network
+    }
+
+    /// Activation after each of `steps` steps of 0.1, the neuron firing on
+    /// the first.
+    fn activation(network: &mut Network, steps: usize) -> Vec<f64> {
+        (0..steps)
+            .map(|step| {
+                network.neurons[0].just_fired = step == 0;
+                network.update_muscles(0.1);
+                network.muscles[0].activation
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_tau_passes_the_drive_on_at_once() {
+        let mut network = network(NmjParams {
+            gain: 0.5,
+            tau: 0.0,
+        });
+        assert_eq!(activation(&mut network, 3), vec![1.5; 3]);
+        assert_eq!(network.muscles[0].drive, 0.0);
+    }
+
+    #[test]
+    fn a_spike_drives_gain_times_weight_in_all() {
+        let mut network = network(NmjParams {
+            gain: 0.5,
+            tau: 2.0,
+        });
+        let activation = activation(&mut network, 1000);
+        assert!(activation[0] < 0.1);
+        assert!(activation.windows(2).all(|w| w[0] <= w[1]));
+        let total = activation[999] + network.muscles[0].drive;
+        assert!((total - 1.5).abs() < 1e-12, "{total}");
+        assert!((activation[999] - 1.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,1321 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::annotations::Annotations;
+use crate::canonical::canonicalize_name;
+use crate::edits::EditLog;
+use crate::error::{FlymindError, IntegrityError};
+use crate::events::{EventVerbosity, SimEvent};
+use crate::kernel::{InputLimits, StepInputs};
+use crate::muscle::{Muscle, NeuromuscularJunction, NmjParams};
+use crate::propagation::{Propagation, Wavefront};
+use crate::rng::Rng;
+use crate::simulation::{LifParams, StdpParams, SynapticInput};
+use crate::spikes::SpikeStorage;
+use crate::symmetry::PairMismatch;
+use crate::synapses::{ConnectionParams, SynapseParams};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NeuronType {
+    Sensory,
+    Interneuron,
+    Motor,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChemicalSubtype {
+    Excitatory,
+    Inhibitory,
+    /// Neuromodulation: a spike raises the target's `Neuron::modulation`,
+    /// which scales its excitability, instead of moving its potential. See
+    /// `Network::update_step`.
+    Modulatory(Modulator),
+}
+
+/// The transmitter of a modulatory synapse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Modulator {
+    Dopamine,
+    Serotonin,
+    Octopamine,
+    Tyramine,
+    Neuropeptide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SynapseType {
+    ChemicalSend(ChemicalSubtype),
+    ChemicalReceive(ChemicalSubtype),
+    GapJunction,
+    NMJ,
+    /// Wireless signalling by a monoamine or neuropeptide released outside
+    /// synapses, from `Network::load_extrasynaptic_csv`. Directed from the
+    /// releasing neuron to a neuron expressing a receptor; the weight is the
+    /// edge's confidence.
+    Extrasynaptic(Modulator),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Region {
+    Head,
+    MidBody,
+    Tail,
+    Unknown,
+}
+
+impl NeuronType {
+    /// Stable lowercase label used by exporters.
+    pub fn label(&self) -> &'static str {
+        match self {
+            NeuronType::Sensory => "sensory",
+            NeuronType::Interneuron => "interneuron",
+            NeuronType::Motor => "motor",
+            NeuronType::Other => "other",
+        }
+    }
+}
+
+impl NeuronType {
+    /// Parse a label case-insensitively, accepting `label()` values and common
+    /// abbreviations ("inter", "moto").
+    pub fn from_label(label: &str) -> Option<NeuronType> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "sensory" | "s" => Some(NeuronType::Sensory),
+            "interneuron" | "inter" | "i" => Some(NeuronType::Interneuron),
+            "motor" | "moto" | "m" => Some(NeuronType::Motor),
+            "other" => Some(NeuronType::Other),
+            _ => None,
+        }
+    }
+}
+
+impl ChemicalSubtype {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChemicalSubtype::Excitatory => "excitatory",
+            ChemicalSubtype::Inhibitory => "inhibitory",
+            ChemicalSubtype::Modulatory(modulator) => modulator.label(),
+        }
+    }
+
+    /// Parse a `label()` value case-insensitively.
+    pub fn from_label(label: &str) -> Option<ChemicalSubtype> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "excitatory" => Some(ChemicalSubtype::Excitatory),
+            "inhibitory" => Some(ChemicalSubtype::Inhibitory),
+            "dopaminergic" => Some(ChemicalSubtype::Modulatory(Modulator::Dopamine)),
+            "serotonergic" => Some(ChemicalSubtype::Modulatory(Modulator::Serotonin)),
+            "octopaminergic" => Some(ChemicalSubtype::Modulatory(Modulator::Octopamine)),
+            "tyraminergic" => Some(ChemicalSubtype::Modulatory(Modulator::Tyramine)),
+            "peptidergic" => Some(ChemicalSubtype::Modulatory(Modulator::Neuropeptide)),
+            _ => None,
+        }
+    }
+
+    pub fn is_modulatory(&self) -> bool {
+        matches!(self, ChemicalSubtype::Modulatory(_))
+    }
+
+    /// Sign of the potential jump a spike delivers: 1.0 excitatory, -1.0
+    /// inhibitory and 0.0 modulatory, which delivers none.
+    pub fn sign(&self) -> f64 {
+        match self {
+            ChemicalSubtype::Excitatory => 1.0,
+            ChemicalSubtype::Inhibitory => -1.0,
+            ChemicalSubtype::Modulatory(_) => 0.0,
+        }
+    }
+}
+
+impl Modulator {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Modulator::Dopamine => "dopaminergic",
+            Modulator::Serotonin => "serotonergic",
+            Modulator::Octopamine => "octopaminergic",
+            Modulator::Tyramine => "tyraminergic",
+            Modulator::Neuropeptide => "peptidergic",
+        }
+    }
+
+    /// Parse a `label()` value or a transmitter name ("dopamine", "5-HT",
+    /// "peptide", ...) case-insensitively.
+    pub fn from_label(label: &str) -> Option<Modulator> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "dopaminergic" | "dopamine" | "da" => Some(Modulator::Dopamine),
+            "serotonergic" | "serotonin" | "5-ht" | "5ht" => Some(Modulator::Serotonin),
+            "octopaminergic" | "octopamine" | "oa" => Some(Modulator::Octopamine),
+            "tyraminergic" | "tyramine" | "ta" => Some(Modulator::Tyramine),
+            "peptidergic" | "neuropeptide" | "peptide" => Some(Modulator::Neuropeptide),
+            _ => None,
+        }
+    }
+}
+
+impl SynapseType {
+    /// Map a connectome file code to a synapse type: `S`/`Sp` (send, polyadic),
+    /// `R`/`Rp` (receive, polyadic), `EJ` (gap junction), `NMJ`.
+    pub fn from_code(code: &str) -> Option<SynapseType> {
+        match code {
+            "S" | "Sp" => Some(SynapseType::ChemicalSend(ChemicalSubtype::Excitatory)),
+            "R" | "Rp" => Some(SynapseType::ChemicalReceive(ChemicalSubtype::Excitatory)),
+            "EJ" => Some(SynapseType::GapJunction),
+            "NMJ" => Some(SynapseType::NMJ),
+            _ => None,
+        }
+    }
+
+    /// Short code in the style of the connectome files; `ES` for
+    /// extrasynaptic edges, which those files do not have.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SynapseType::ChemicalSend(_) => "S",
+            SynapseType::ChemicalReceive(_) => "R",
+            SynapseType::GapJunction => "EJ",
+            SynapseType::NMJ => "NMJ",
+            SynapseType::Extrasynaptic(_) => "ES",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SynapseType::ChemicalSend(_) => "chemical_send",
+            SynapseType::ChemicalReceive(_) => "chemical_receive",
+            SynapseType::GapJunction => "gap_junction",
+            SynapseType::NMJ => "nmj",
+            SynapseType::Extrasynaptic(_) => "extrasynaptic",
+        }
+    }
+
+    pub fn subtype(&self) -> Option<ChemicalSubtype> {
+        match self {
+            SynapseType::ChemicalSend(subtype) | SynapseType::ChemicalReceive(subtype) => {
+                Some(*subtype)
+            }
+            SynapseType::GapJunction | SynapseType::NMJ | SynapseType::Extrasynaptic(_) => None,
+        }
+    }
+}
+
+impl Region {
+    pub fn from_label(label: &str) -> Option<Region> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "head" => Some(Region::Head),
+            "midbody" | "mid_body" | "mid-body" | "body" => Some(Region::MidBody),
+            "tail" => Some(Region::Tail),
+            "unknown" => Some(Region::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Region::Head => "head",
+            Region::MidBody => "midbody",
+            Region::Tail => "tail",
+            Region::Unknown => "unknown",
+        }
+    }
+}
+
+/// What `Network::validate` found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The first problem `Network::check_integrity` found; the data checks
+    /// are skipped when there is one.
+    pub integrity: Option<IntegrityError>,
+    /// Homolog pairs whose sides disagree on type or region, as
+    /// `Network::bilateral_mismatches`.
+    pub bilateral_mismatches: Vec<PairMismatch>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.integrity.is_none() && self.bilateral_mismatches.is_empty()
+    }
+
+    /// Human-readable notes, one per problem found.
+    pub fn warnings(&self) -> Vec<String> {
+        self.integrity
+            .iter()
+            .map(|err| err.to_string())
+            .chain(
+                self.bilateral_mismatches
+                    .iter()
+                    .map(|mismatch| format!("bilateral pair {mismatch}")),
+            )
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Neuron {
+    pub id: usize,
+    /// Shared with the network's name index, so each name is stored once.
+    pub name: Arc<str>,
+    pub neuron_type: NeuronType,
+    pub region: Region,
+    pub soma_position: f64,
+    /// Neurotransmitter, when metadata gives one.
+    pub transmitter: Option<String>,
+    /// Probability of `neuron_type`, when it came from a type-probability
+    /// table (see `Network::apply_type_probabilities`); `None` for a label
+    /// given outright.
+    #[serde(default)]
+    pub type_confidence: Option<f64>,
+    // Simulation state
+    /// Absolute potential; `Neuron::new` starts it at the default
+    /// `LifParams::resting_potential`, and `Network::add_neuron` and
+    /// `Network::reset_state` at the neuron's own.
+    pub membrane_potential: f64,
+    pub just_fired: bool,
+    pub refractory_remaining: usize,
+    /// Spikes in a row each fired on the first step refractoriness allowed;
+    /// see `DepolarizationBlock`.
+    pub rapid_spikes: usize,
+    /// Whether the neuron went into depolarization block on the last step.
+    pub just_blocked: bool,
+    /// STDP traces, see `StdpParams`.
+    pub pre_trace: f64,
+    pub post_trace: f64,
+    /// Excitability change from modulatory synapses: input is scaled by
+    /// `1 + modulation` (not below zero). Decays with `LifParams::modulation_tau`.
+    pub modulation: f64,
+    /// Recent firing rate, in spikes per unit time, that chemical sends carry
+    /// under `SynapticInput::Rate`; zero under `Spikes`.
+    #[serde(default)]
+    pub rate_estimate: f64,
+}
+
+impl Neuron {
+    pub fn new(
+        id: usize,
+        name: &str,
+        neuron_type: NeuronType,
+        region: Region,
+        soma_pos: f64,
+    ) -> Self {
+        Self {
+            id,
+            name: Arc::from(name),
+            neuron_type,
+            region,
+            soma_position: soma_pos,
+            transmitter: None,
+            type_confidence: None,
+            membrane_potential: LifParams::default().resting_potential,
+            just_fired: false,
+            refractory_remaining: 0,
+            rapid_spikes: 0,
+            just_blocked: false,
+            pre_trace: 0.0,
+            post_trace: 0.0,
+            modulation: 0.0,
+            rate_estimate: 0.0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Whether connections from a neuron onto itself take part in degrees,
+/// adjacency, weight matrices and simulation input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SelfConnections {
+    #[default]
+    Include,
+    /// Keep them in `connections` but leave them out of every analysis and
+    /// every run.
+    Ignore,
+}
+
+/// Whether extrasynaptic edges take part in degrees, adjacency, weight
+/// matrices and the other measures of the whole network. Queries that name
+/// their synapse classes, such as paths and `graph_view`, follow those
+/// instead, and runs drive through the layer as `LifParams::extrasynaptic_gain`
+/// says either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExtrasynapticEdges {
+    /// Measure the wired network only.
+    #[default]
+    Ignore,
+    /// Count them as connections like any other.
+    Include,
+}
+
+/// Neurons combined by `Network::merge_duplicate_neurons`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeuronMerge {
+    /// Name of the merged neuron.
+    pub name: String,
+    /// Original names, in id order.
+    pub merged: Vec<String>,
+}
+
+/// How `Network::consolidate_edges` combines parallel connections.
+///
+/// Every policy but `KeepByType` treats excitatory and inhibitory chemical
+/// rows between two neurons as parallel and gives the merged connection the
+/// type of the first; no policy merges across chemical sends, mirrored
+/// receives, gap junctions and NMJs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsolidationPolicy {
+    /// The merged weight is the sum.
+    #[default]
+    Sum,
+    Max,
+    Mean,
+    /// Every weight, lone connections' included, becomes a count of synaptic
+    /// contacts: the summed `contacts`, a connection whose count is unknown
+    /// (0) counting as one. The merged connection keeps that total as its
+    /// `contacts`, so running it again changes nothing.
+    Count,
+    /// Sum, merging only connections of the same `SynapseType`, subtype
+    /// included, as `MergeMode::Sum` does on load.
+    KeepByType,
+}
+
+/// What `Network::consolidate_edges` merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsolidationReport {
+    pub connections_before: usize,
+    pub connections_after: usize,
+    /// For each `(from, to)` pair that lost connections, how many were folded
+    /// into another.
+    pub merged: BTreeMap<(usize, usize), usize>,
+    /// Connections left unmerged whose weight the policy changed; only `Count`
+    /// changes any.
+    pub reweighted: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Connection {
+    pub from_id: usize,
+    pub to_id: usize,
+    pub synapse_type: SynapseType,
+    pub weight: f64,
+    /// Synaptic contacts the row records (the source data's Nbr, rounded), kept apart
+    /// from `weight` so reweighting does not lose it; 0 when unknown.
+    pub contacts: u32,
+    /// Whether STDP may change `weight`. Only chemical sends are ever updated,
+    /// whatever this says.
+    pub plastic: bool,
+    /// Where the row came from: `LoaderConfig::source`, else the loaded file's
+    /// name. Shared between all connections of one load.
+    pub source: Option<Arc<str>>,
+}
+
+impl Connection {
+    pub fn new(from_id: usize, to_id: usize, synapse_type: SynapseType, weight: f64) -> Self {
+        Self {
+            from_id,
+            to_id,
+            synapse_type,
+            weight,
+            contacts: 0,
+            plastic: synapse_type.is_chemical(),
+            source: None,
+        }
+    }
+
+    pub fn with_contacts(mut self, contacts: u32) -> Self {
+        self.contacts = contacts;
+        self
+    }
+
+    pub fn with_source(mut self, source: Option<Arc<str>>) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+pub struct Network {
+    pub neurons: Vec<Neuron>,
+    pub connections: Vec<Connection>,
+    pub outgoing_map: HashMap<usize, Vec<usize>>,
+    pub incoming_map: HashMap<usize, Vec<usize>>,
+    name_index: HashMap<Arc<str>, usize>,
+
+    // LIF parameters and simulation clock
+    pub lif: LifParams,
+    /// Per-neuron parameters that replace `lif` for that neuron.
+    pub lif_overrides: HashMap<usize, LifParams>,
+    pub stimuli: Vec<crate::simulation::Stimulus>,
+    /// Sampled time courses, indexed by `StimulusShape::Trace` stimuli.
+    pub stimulus_traces: Vec<crate::trace::StimulusTrace>,
+    /// Constant current into each listed neuron on every step, on top of
+    /// `stimuli`; unlisted neurons get none.
+    pub bias_currents: HashMap<usize, f64>,
+    pub stdp: Option<StdpParams>,
+    /// How runs record spike trains.
+    pub spike_storage: SpikeStorage,
+    /// Which neurons `update_step` updates within a run; see
+    /// `crate::propagation`.
+    pub propagation: Propagation,
+    /// What chemical sends carry; see `SynapticInput`.
+    pub synaptic_input: SynapticInput,
+    /// The run in progress's wavefront, under `Propagation::EventDriven`.
+    pub(crate) wavefront: Option<Wavefront>,
+    /// What `update_step` logs; see `crate::events`.
+    pub event_verbosity: EventVerbosity,
+    pub self_connections: SelfConnections,
+    pub extrasynaptic_edges: ExtrasynapticEdges,
+    /// Synaptic parameters by connection; see `crate::synapses`.
+    pub connection_params: ConnectionParams,
+    /// `connection_params` resolved for the run in progress.
+    pub(crate) synapses: Option<Vec<SynapseParams>>,
+    /// The connections compiled for the run in progress; see `crate::kernel`.
+    pub(crate) step_inputs: Option<StepInputs>,
+    /// `max_inputs` and input normalization resolved for the run in
+    /// progress, refreshed when STDP or a state switch changes them.
+    pub(crate) input_limits: Option<InputLimits>,
+    /// See `crate::annotations`.
+    pub annotations: Annotations,
+    pub(crate) events: Vec<SimEvent>,
+    /// Structural edits to undo, while recording; see `crate::edits`.
+    pub(crate) edit_log: Option<EditLog>,
+    /// See `crate::muscle`.
+    pub muscles: Vec<Muscle>,
+    pub neuromuscular: Vec<NeuromuscularJunction>,
+    /// Decay time constant of muscle activation, in `lif.time_unit`.
+    pub muscle_tau: f64,
+    /// How motor neuron spikes drive muscles; see `crate::muscle`.
+    pub nmj: NmjParams,
+    pub current_step: usize,
+    pub rng: Rng,
+    /// Set by `with_neuron_roster`: edge-list loads then fail on names not
+    /// already in the network instead of adding them, except for the targets
+    /// of NMJ rows.
+    pub fixed_roster: bool,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self {
+            neurons: Vec::new(),
+            connections: Vec::new(),
+            outgoing_map: HashMap::new(),
+            incoming_map: HashMap::new(),
+            name_index: HashMap::new(),
+            lif: LifParams::default(),
+            lif_overrides: HashMap::new(),
+            stimuli: Vec::new(),
+            stimulus_traces: Vec::new(),
+            bias_currents: HashMap::new(),
+            stdp: None,
+            spike_storage: SpikeStorage::Steps,
+            propagation: Propagation::Dense,
+            synaptic_input: SynapticInput::Spikes,
+            wavefront: None,
+            event_verbosity: EventVerbosity::Off,
+            self_connections: SelfConnections::Include,
+            extrasynaptic_edges: ExtrasynapticEdges::Ignore,
+            connection_params: ConnectionParams::default(),
+            synapses: None,
+            step_inputs: None,
+            input_limits: None,
+            annotations: Annotations::default(),
+            events: Vec::new(),
+            edit_log: None,
+            muscles: Vec::new(),
+            neuromuscular: Vec::new(),
+            muscle_tau: 20.0,
+            nmj: NmjParams::default(),
+            current_step: 0,
+            rng: Rng::new(0),
+            fixed_roster: false,
+        }
+    }
+
+    /// A network with one unconnected neuron per name, ids in the order
+    /// given (a repeated name keeps its first id), and `fixed_roster` set, so
+    /// the id space stays as listed when edge lists are loaded into it with
+    /// `append_from_csv`. Neurons start as `NeuronType::Other` in
+    /// `Region::Unknown`, for metadata to fill in.
+    pub fn with_neuron_roster(names: &[&str]) -> Self {
+        let mut network = Network::new();
+        for name in names {
+            network.get_or_create_neuron(name);
+        }
+        network.fixed_roster = true;
+        network
+    }
+
+    pub fn add_neuron(
+        &mut self,
+        name: &str,
+        neuron_type: NeuronType,
+        region: Region,
+        soma_position: f64,
+    ) -> usize {
+        let id = self.neurons.len();
+        let mut neuron = Neuron::new(id, name, neuron_type, region, soma_position);
+        neuron.membrane_potential = self.lif_for(id).resting_potential;
+        self.name_index.entry(neuron.name.clone()).or_insert(id);
+        self.neurons.push(neuron);
+        self.record_added_neuron(id);
+        id
+    }
+
+    /// Recompute the name lookup from `neurons`, the first of a repeated name
+    /// winning as in `add_neuron`.
+    pub(crate) fn rebuild_name_index(&mut self) {
+        self.name_index.clear();
+        for neuron in &self.neurons {
+            self.name_index
+                .entry(neuron.name.clone())
+                .or_insert(neuron.id);
+        }
+    }
+
+    /// Whether `conn` counts under `self_connections` and
+    /// `extrasynaptic_edges`: always, unless it is a self-connection or an
+    /// extrasynaptic edge being ignored.
+    pub fn counts(&self, conn: &Connection) -> bool {
+        self.counts_in_any_layer(conn)
+            && (self.extrasynaptic_edges == ExtrasynapticEdges::Include
+                || !matches!(conn.synapse_type, SynapseType::Extrasynaptic(_)))
+    }
+
+    /// Whether `conn` counts under `self_connections` alone, for callers that
+    /// choose synapse types themselves: runs and class-filtered queries.
+    pub(crate) fn counts_in_any_layer(&self, conn: &Connection) -> bool {
+        self.self_connections == SelfConnections::Include || conn.from_id != conn.to_id
+    }
+
+    /// Look up a neuron id by name without allocating.
+    pub fn id_of(&self, name: &str) -> Option<usize> {
+        self.name_index.get(name).copied()
+    }
+
+    /// Ids of the neurons whose names match `pattern`, ascending.
+    ///
+    /// `*` matches any run of characters and `?` any single one, against the
+    /// whole name; a pattern without either is a prefix, so `"AVA"` and `"AVA*"`
+    /// both select AVAL and AVAR. Matching is case-sensitive.
+    pub fn find_neurons(&self, pattern: &str) -> Vec<usize> {
+        let pattern: Vec<char> = if pattern.contains(['*', '?']) {
+            pattern.chars().collect()
+        } else {
+            pattern.chars().chain(['*']).collect()
+        };
+        self.neurons
+            .iter()
+            .filter(|neuron| glob_match(&pattern, &neuron.name().chars().collect::<Vec<_>>()))
+            .map(|neuron| neuron.id)
+            .collect()
+    }
+
+    /// Merge neurons whose names have the same `canonicalize_name` form into
+    /// one neuron with that name, returning what was merged. Neurons whose form
+    /// is shared with no other keep their name.
+    ///
+    /// The merged neuron takes the lowest id of its group and the first known
+    /// type, region, position and transmitter; every connection, parameter
+    /// override, stimulus, bias current, neuromuscular junction and annotation
+    /// of the others moves to it, the lowest id's value winning where keys
+    /// clash and bias currents adding up.
+    /// Remaining neurons are renumbered in order, so ids held from before the
+    /// merge are stale.
+    pub fn merge_duplicate_neurons(&mut self) -> Vec<NeuronMerge> {
+        self.record_structural(Network::merge_duplicates_unrecorded, |merges| {
+            (!merges.is_empty()).then(|| format!("merge {} duplicate names", merges.len()))
+        })
+    }
+
+    fn merge_duplicates_unrecorded(&mut self) -> Vec<NeuronMerge> {
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        for neuron in &self.neurons {
+            let canonical = canonicalize_name(neuron.name());
+            let index = *group_of.entry(canonical.clone()).or_insert_with(|| {
+                groups.push((canonical, Vec::new()));
+                groups.len() - 1
+            });
+            groups[index].1.push(neuron.id);
+        }
+
+        let merges: Vec<NeuronMerge> = groups
+            .iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(name, ids)| NeuronMerge {
+                name: name.clone(),
+                merged: ids
+                    .iter()
+                    .map(|&id| self.neurons[id].name().to_string())
+                    .collect(),
+            })
+            .collect();
+        if merges.is_empty() {
+            return merges;
+        }
+
+        // Groups are in order of their lowest id, so group index is the new id.
+        let mut new_id = vec![0; self.neurons.len()];
+        for (index, (_, ids)) in groups.iter().enumerate() {
+            for &id in ids {
+                new_id[id] = index;
+            }
+        }
+
+        let mut old_neurons: Vec<Option<Neuron>> = std::mem::take(&mut self.neurons)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.name_index.clear();
+        for (index, (name, ids)) in groups.iter().enumerate() {
+            let mut neuron = old_neurons[ids[0]].take().expect("each id is in one group");
+            for &other in &ids[1..] {
+                let other = old_neurons[other].take().expect("each id is in one group");
+                if neuron.neuron_type == NeuronType::Other {
+                    neuron.neuron_type = other.neuron_type;
+                    neuron.type_confidence = other.type_confidence;
+                }
+                if neuron.region == Region::Unknown {
+                    neuron.region = other.region;
+                }
+                if neuron.soma_position == 0.0 {
+                    neuron.soma_position = other.soma_position;
+                }
+                if neuron.transmitter.is_none() {
+                    neuron.transmitter = other.transmitter;
+                }
+            }
+            neuron.id = index;
+            if ids.len() > 1 && *neuron.name != **name {
+                neuron.name = Arc::from(name.as_str());
+            }
+            self.name_index.insert(neuron.name.clone(), index);
+            self.neurons.push(neuron);
+        }
+
+        for conn in &mut self.connections {
+            conn.from_id = new_id[conn.from_id];
+            conn.to_id = new_id[conn.to_id];
+        }
+        for junction in &mut self.neuromuscular {
+            junction.from_id = new_id[junction.from_id];
+        }
+        let mut overrides: Vec<_> = std::mem::take(&mut self.lif_overrides)
+            .into_iter()
+            .collect();
+        overrides.sort_by_key(|&(id, _)| id);
+        for (id, params) in overrides {
+            self.lif_overrides.entry(new_id[id]).or_insert(params);
+        }
+        for stim in &mut self.stimuli {
+            if let Some(&id) = new_id.get(stim.neuron) {
+                stim.neuron = id;
+            }
+        }
+        for (id, bias) in std::mem::take(&mut self.bias_currents) {
+            if let Some(&id) = new_id.get(id) {
+                *self.bias_currents.entry(id).or_default() += bias;
+            }
+        }
+        self.annotations.remap_neurons(|id| new_id.get(id).copied());
+        self.rebuild_indices();
+        merges
+    }
+
+    /// Merge parallel connections (same endpoints and kind, see
+    /// `ConsolidationPolicy`) into one, at the position of the first, and
+    /// rebuild the adjacency maps. The merged connection sums the contacts,
+    /// is plastic if any part was and keeps the first's source, annotations
+    /// and parameter overrides. Running it again with the same policy changes
+    /// nothing.
+    pub fn consolidate_edges(&mut self, policy: ConsolidationPolicy) -> ConsolidationReport {
+        self.record_structural(
+            |network| network.consolidate_edges_unrecorded(policy),
+            |report| {
+                let merged = report.connections_before - report.connections_after;
+                if merged > 0 {
+                    Some(format!(
+                        "consolidate {merged} parallel connections ({policy:?})"
+                    ))
+                } else {
+                    (report.reweighted > 0)
+                        .then(|| format!("reweight {} connections ({policy:?})", report.reweighted))
+                }
+            },
+        )
+    }
+
+    fn consolidate_edges_unrecorded(&mut self, policy: ConsolidationPolicy) -> ConsolidationReport {
+        let mut report = ConsolidationReport {
+            connections_before: self.connections.len(),
+            ..ConsolidationReport::default()
+        };
+        let kind = |synapse_type: SynapseType| match policy {
+            ConsolidationPolicy::KeepByType => synapse_type,
+            _ => match synapse_type {
+                SynapseType::ChemicalSend(_) => {
+                    SynapseType::ChemicalSend(ChemicalSubtype::Excitatory)
+                }
+                SynapseType::ChemicalReceive(_) => {
+                    SynapseType::ChemicalReceive(ChemicalSubtype::Excitatory)
+                }
+                other => other,
+            },
+        };
+
+        let mut group_of: HashMap<(usize, usize, SynapseType), usize> = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (index, conn) in self.connections.iter().enumerate() {
+            let key = (conn.from_id, conn.to_id, kind(conn.synapse_type));
+            let group = *group_of.entry(key).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(index);
+        }
+        if groups.len() == self.connections.len() {
+            if policy == ConsolidationPolicy::Count {
+                report.reweighted = self
+                    .connections
+                    .iter_mut()
+                    .map(weigh_by_contacts)
+                    .filter(|&changed| changed)
+                    .count();
+            }
+            report.connections_after = self.connections.len();
+            return report;
+        }
+
+        let mut new_index = vec![None; self.connections.len()];
+        let mut old: Vec<Option<Connection>> = std::mem::take(&mut self.connections)
+            .into_iter()
+            .map(Some)
+            .collect();
+        for (position, members) in groups.iter().enumerate() {
+            let weights: Vec<f64> = members
+                .iter()
+                .map(|&i| old[i].as_ref().expect("each index is in one group").weight)
+                .collect();
+            let mut conn = old[members[0]].take().expect("each index is in one group");
+            new_index[members[0]] = Some(position);
+            if members.len() > 1 {
+                let known = |contacts: u32| match policy {
+                    ConsolidationPolicy::Count => contacts.max(1),
+                    _ => contacts,
+                };
+                conn.contacts = known(conn.contacts);
+                for &other in &members[1..] {
+                    let other = old[other].take().expect("each index is in one group");
+                    conn.contacts += known(other.contacts);
+                    conn.plastic |= other.plastic;
+                }
+                conn.weight = match policy {
+                    ConsolidationPolicy::Sum | ConsolidationPolicy::KeepByType => {
+                        weights.iter().sum()
+                    }
+                    ConsolidationPolicy::Max => weights.iter().copied().fold(f64::MIN, f64::max),
+                    ConsolidationPolicy::Mean => weights.iter().sum::<f64>() / weights.len() as f64,
+                    ConsolidationPolicy::Count => f64::from(conn.contacts),
+                };
+                *report.merged.entry((conn.from_id, conn.to_id)).or_default() += members.len() - 1;
+            } else if policy == ConsolidationPolicy::Count && weigh_by_contacts(&mut conn) {
+                report.reweighted += 1;
+            }
+            self.connections.push(conn);
+        }
+        self.annotations.remap_connections(&new_index);
+        self.connection_params.remap_connections(&new_index);
+        self.rebuild_indices();
+        report.connections_after = self.connections.len();
+        report
+    }
+
+    /// Every `(from, to, synapse type)` held by more than one connection, once
+    /// each, in order of its first connection. Types compare subtype included,
+    /// so an excitatory and an inhibitory send between one pair are distinct;
+    /// empty after `ConsolidationPolicy::KeepByType` or a `MergeMode::Sum` load.
+    pub fn find_duplicate_connections(&self) -> Vec<(usize, usize, SynapseType)> {
+        let mut seen: HashMap<(usize, usize, SynapseType), bool> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for conn in &self.connections {
+            let key = (conn.from_id, conn.to_id, conn.synapse_type);
+            match seen.get_mut(&key) {
+                Some(reported) => {
+                    if !*reported {
+                        *reported = true;
+                        duplicates.push(key);
+                    }
+                }
+                None => {
+                    seen.insert(key, false);
+                }
+            }
+        }
+        duplicates
+    }
+
+    /// Remove `isolated_neurons` and return their names, in id order.
+    ///
+    /// The remaining neurons are renumbered in order and every connection,
+    /// parameter override, stimulus, bias current, neuromuscular junction and
+    /// annotation is moved to the new ids, so ids held from before are stale.
+    /// Stimuli, bias currents and annotations of removed neurons are dropped.
+    pub fn remove_isolated(&mut self) -> Vec<String> {
+        self.record_structural(Network::remove_isolated_unrecorded, |removed| {
+            (!removed.is_empty()).then(|| format!("remove {} isolated neurons", removed.len()))
+        })
+    }
+
+    fn remove_isolated_unrecorded(&mut self) -> Vec<String> {
+        let isolated = self.isolated_neurons();
+        if isolated.is_empty() {
+            return Vec::new();
+        }
+        let mut keep = vec![true; self.neurons.len()];
+        for &id in &isolated {
+            keep[id] = false;
+        }
+        let mut new_id = vec![None; self.neurons.len()];
+        let mut removed = Vec::with_capacity(isolated.len());
+        let mut kept = Vec::with_capacity(self.neurons.len() - isolated.len());
+        self.name_index.clear();
+        for (mut neuron, keep) in std::mem::take(&mut self.neurons).into_iter().zip(keep) {
+            if !keep {
+                removed.push(neuron.name().to_string());
+                continue;
+            }
+            new_id[neuron.id] = Some(kept.len());
+            neuron.id = kept.len();
+            self.name_index.insert(neuron.name.clone(), neuron.id);
+            kept.push(neuron);
+        }
+        self.neurons = kept;
+
+        let remap = |id: usize| new_id[id].expect("connected neurons are kept");
+        for conn in &mut self.connections {
+            conn.from_id = remap(conn.from_id);
+            conn.to_id = remap(conn.to_id);
+        }
+        for junction in &mut self.neuromuscular {
+            junction.from_id = remap(junction.from_id);
+        }
+        self.lif_overrides = std::mem::take(&mut self.lif_overrides)
+            .into_iter()
+            .filter_map(|(id, params)| Some((new_id.get(id).copied().flatten()?, params)))
+            .collect();
+        self.bias_currents = std::mem::take(&mut self.bias_currents)
+            .into_iter()
+            .filter_map(|(id, bias)| Some((new_id.get(id).copied().flatten()?, bias)))
+            .collect();
+        self.stimuli
+            .retain_mut(|stim| match new_id.get(stim.neuron) {
+                Some(Some(id)) => {
+                    stim.neuron = *id;
+                    true
+                }
+                Some(None) => false,
+                None => true,
+            });
+        self.annotations
+            .remap_neurons(|id| new_id.get(id).copied().flatten());
+        self.rebuild_indices();
+        removed
+    }
+
+    /// Return the id for `name`, adding an untyped neuron if it is not present yet.
+    pub fn get_or_create_neuron(&mut self, name: &str) -> usize {
+        match self.id_of(name) {
+            Some(id) => id,
+            None => self.add_neuron(name, NeuronType::Other, Region::Unknown, 0.0),
+        }
+    }
+
+    pub fn add_connection(
+        &mut self,
+        from_id: usize,
+        to_id: usize,
+        synapse_type: SynapseType,
+        weight: f64,
+    ) {
+        let conn_index = self.connections.len();
+        let conn = Connection::new(from_id, to_id, synapse_type, weight);
+        self.connections.push(conn);
+
+        self.outgoing_map
+            .entry(from_id)
+            .or_default()
+            .push(conn_index);
+        self.incoming_map.entry(to_id).or_default().push(conn_index);
+        self.record_added_connections(conn_index);
+    }
+
+    /// Append many connections and build the adjacency maps once at the end.
+    ///
+    /// Returns the index range of the new connections. If any endpoint is out of
+    /// range nothing is added and the error carries the edge's position in `edges`.
+    pub fn add_connections_bulk(
+        &mut self,
+        edges: impl IntoIterator<Item = (usize, usize, SynapseType, f64)>,
+    ) -> Result<Range<usize>, FlymindError> {
+        let start = self.connections.len();
+        let neuron_count = self.neurons.len();
+
+        for (position, (from_id, to_id, synapse_type, weight)) in edges.into_iter().enumerate() {
+            if let Some(id) = [from_id, to_id].into_iter().find(|&id| id >= neuron_count) {
+                self.connections.truncate(start);
+                return Err(FlymindError::InvalidNeuronId {
+                    position,
+                    id,
+                    neuron_count,
+                });
+            }
+            self.connections
+                .push(Connection::new(from_id, to_id, synapse_type, weight));
+        }
+
+        self.rebuild_indices();
+        self.record_added_connections(start);
+        Ok(start..self.connections.len())
+    }
+
+    /// Recompute `outgoing_map` and `incoming_map` from `connections`.
+    ///
+    /// Call this after editing `connections` directly. Each neuron's list is sized
+    /// exactly from a degree count first, so the maps match per-edge insertion
+    /// (connection indices in ascending order) without repeated reallocation.
+    pub fn rebuild_indices(&mut self) {
+        let slots = self
+            .connections
+            .iter()
+            .map(|c| c.from_id.max(c.to_id) + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut out_degree = vec![0usize; slots];
+        let mut in_degree = vec![0usize; slots];
+        for conn in &self.connections {
+            out_degree[conn.from_id] += 1;
+            in_degree[conn.to_id] += 1;
+        }
+
+        let mut outgoing: Vec<Vec<usize>> =
+            out_degree.iter().map(|&d| Vec::with_capacity(d)).collect();
+        let mut incoming: Vec<Vec<usize>> =
+            in_degree.iter().map(|&d| Vec::with_capacity(d)).collect();
+        for (index, conn) in self.connections.iter().enumerate() {
+            outgoing[conn.from_id].push(index);
+            incoming[conn.to_id].push(index);
+        }
+
+        self.outgoing_map = outgoing
+            .into_iter()
+            .enumerate()
+            .filter(|(_, list)| !list.is_empty())
+            .collect();
+        self.incoming_map = incoming
+            .into_iter()
+            .enumerate()
+            .filter(|(_, list)| !list.is_empty())
+            .collect();
+    }
+
+    /// Check that neuron ids are their positions, every connection endpoint is
+    /// a neuron, the adjacency maps list exactly the connections at each
+    /// neuron and the name lookup agrees with `neurons`, returning the first
+    /// problem found.
+    ///
+    /// The loaders and the editing methods keep all of this true; a network
+    /// whose fields were filled or edited directly may not be, and indexing
+    /// into it can panic deep inside a run. `SimulationConfig::configure`
+    /// calls this first.
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        let neuron_count = self.neurons.len();
+        for (index, neuron) in self.neurons.iter().enumerate() {
+            if neuron.id != index {
+                return Err(IntegrityError::NeuronId {
+                    index,
+                    id: neuron.id,
+                });
+            }
+        }
+
+        let mut outgoing = vec![Vec::new(); neuron_count];
+        let mut incoming = vec![Vec::new(); neuron_count];
+        for (connection, conn) in self.connections.iter().enumerate() {
+            let endpoints = [conn.from_id, conn.to_id];
+            if let Some(id) = endpoints.into_iter().find(|&id| id >= neuron_count) {
+                return Err(IntegrityError::Endpoint {
+                    connection,
+                    id,
+                    neuron_count,
+                });
+            }
+            outgoing[conn.from_id].push(connection);
+            incoming[conn.to_id].push(connection);
+        }
+        for (map, stored, expected) in [
+            ("outgoing_map", &self.outgoing_map, &outgoing),
+            ("incoming_map", &self.incoming_map, &incoming),
+        ] {
+            let mut neurons: Vec<usize> = stored.keys().copied().collect();
+            neurons.extend((0..neuron_count).filter(|&id| !expected[id].is_empty()));
+            neurons.sort_unstable();
+            neurons.dedup();
+            for neuron in neurons {
+                let mut listed = stored.get(&neuron).cloned().unwrap_or_default();
+                listed.sort_unstable();
+                let wanted = expected.get(neuron).map_or(&[][..], Vec::as_slice);
+                let differs = listed.iter().zip(wanted).find(|(a, b)| a != b);
+                let connection = match differs {
+                    Some((&a, &b)) => Some(a.min(b)),
+                    None if listed.len() > wanted.len() => Some(listed[wanted.len()]),
+                    None => wanted.get(listed.len()).copied(),
+                };
+                if let Some(connection) = connection {
+                    return Err(IntegrityError::Adjacency {
+                        map,
+                        neuron,
+                        connection,
+                    });
+                }
+            }
+        }
+
+        let mut first: HashMap<&str, usize> = HashMap::new();
+        for neuron in &self.neurons {
+            first.entry(neuron.name()).or_insert(neuron.id);
+        }
+        let stale = self
+            .neurons
+            .iter()
+            .map(|neuron| neuron.name())
+            .find(|&name| self.name_index.get(name) != first.get(name))
+            .or_else(|| {
+                self.name_index
+                    .keys()
+                    .map(|name| &**name)
+                    .find(|&name| !first.contains_key(name))
+            });
+        match stale {
+            Some(name) => Err(IntegrityError::NameIndex {
+                name: name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// `check_integrity`, then checks of the data that do not stop a run but
+    /// usually mean it was mislabelled, such as `bilateral_mismatches`.
+    pub fn validate(&self) -> ValidationReport {
+        match self.check_integrity() {
+            Err(err) => ValidationReport {
+                integrity: Some(err),
+                ..ValidationReport::default()
+            },
+            Ok(()) => ValidationReport {
+                integrity: None,
+                bilateral_mismatches: self.bilateral_mismatches(),
+            },
+        }
+    }
+}
+
+/// Whether `name` matches the `*`/`?` glob `pattern` in full.
+pub(crate) fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    // Greedy match remembering the last `*`, which is retried one character
+    // further on a mismatch.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Give `conn` its contact count, unknown (0) counting as one, as weight under
+/// `ConsolidationPolicy::Count`; whether that changed anything.
+fn weigh_by_contacts(conn: &mut Connection) -> bool {
+    let contacts = conn.contacts.max(1);
+    let changed = conn.contacts != contacts || conn.weight != f64::from(contacts);
+    conn.contacts = contacts;
+    conn.weight = f64::from(contacts);
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+    const INHIBITORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Inhibitory);
+
+    /// Three parallel sends from A to B, one of them inhibitory, with 2, 3
+    /// and 5 contacts, and a lone gap junction with unknown contacts.
+    fn parallel_sends() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        for (synapse_type, weight, contacts) in [
+            (EXCITATORY, 1.0, 2),
+            (INHIBITORY, 4.0, 3),
+            (EXCITATORY, 2.5, 5),
+        ] {
+            network.add_connection(0, 1, synapse_type, weight);
+            network.connections.last_mut().unwrap().contacts = contacts;
+        }
+        network.add_connection(1, 0, SynapseType::GapJunction, 0.5);
+        network
+    }
+
+    fn weights(network: &Network) -> Vec<f64> {
+        network.connections.iter().map(|c| c.weight).collect()
+    }
+
+    #[test]
+    fn each_policy_merges_three_parallel_sends() {
+        for (policy, expected) in [
+            (ConsolidationPolicy::Sum, vec![7.5, 0.5]),
+            (ConsolidationPolicy::Max, vec![4.0, 0.5]),
+            (ConsolidationPolicy::Mean, vec![2.5, 0.5]),
+            (ConsolidationPolicy::Count, vec![10.0, 1.0]),
+            (ConsolidationPolicy::KeepByType, vec![3.5, 4.0, 0.5]),
+        ] {
+            let mut network = parallel_sends();
+            let report = network.consolidate_edges(policy);
+            assert_eq!(weights(&network), expected, "{policy:?}");
+            assert_eq!(report.connections_before, 4);
+            assert_eq!(report.connections_after, expected.len());
+            let folded = if policy == ConsolidationPolicy::KeepByType {
+                1
+            } else {
+                2
+            };
+            assert_eq!(report.merged, BTreeMap::from([((0, 1), folded)]));
+            assert_eq!(network.outgoing_map[&0].len(), expected.len() - 1);
+
+            let again = network.consolidate_edges(policy);
+            assert_eq!(weights(&network), expected, "{policy:?} twice");
+            assert!(again.merged.is_empty());
+            assert_eq!(again.reweighted, 0);
+        }
+    }
+
+    #[test]
+    fn count_weighs_lone_connections_by_contacts_too() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 1, EXCITATORY, 0.2);
+        network.connections[0].contacts = 6;
+        network.add_connection(1, 0, EXCITATORY, 3.0);
+        let report = network.consolidate_edges(ConsolidationPolicy::Count);
+        assert!(report.merged.is_empty());
+        assert_eq!(report.reweighted, 2);
+        assert_eq!(weights(&network), vec![6.0, 1.0]);
+        assert_eq!(network.connections[1].contacts, 1);
+    }
+
+    #[test]
+    fn validation_reports_mismatched_pairs_of_a_sound_network() {
+        let mut network = Network::new();
+        network.add_neuron("AVAL", NeuronType::Interneuron, Region::Head, 0.0);
+        network.add_neuron("AVAR", NeuronType::Motor, Region::Head, 0.0);
+        network.add_neuron("AIBL", NeuronType::Interneuron, Region::Head, 0.0);
+        network.add_neuron("AIBR", NeuronType::Interneuron, Region::Head, 0.0);
+        let report = network.validate();
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.warnings(),
+            vec!["bilateral pair AVAL/AVAR: type interneuron vs motor".to_string()]
+        );
+
+        network.neurons[1].id = 5;
+        let report = network.validate();
+        assert_eq!(
+            report.integrity,
+            Some(IntegrityError::NeuronId { index: 1, id: 5 })
+        );
+        assert!(report.bilateral_mismatches.is_empty());
+    }
+
+    #[test]
+    fn bulk_insertion_matches_one_at_a_time() {
+        let fixture = Network::example_touch_circuit();
+        let names: Vec<&str> = fixture.neurons.iter().map(|n| n.name()).collect();
+        let edges: Vec<(usize, usize, SynapseType, f64)> = fixture
+            .connections
+            .iter()
+            .map(|c| (c.from_id, c.to_id, c.synapse_type, c.weight))
+            .collect();
+
+        let mut single = Network::with_neuron_roster(&names);
+        for &(from, to, synapse_type, weight) in &edges {
+            single.add_connection(from, to, synapse_type, weight);
+        }
+        // Two batches, so the second is indexed after existing connections.
+        let mut bulk = Network::with_neuron_roster(&names);
+        let (first, rest) = edges.split_at(edges.len() / 2);
+        assert_eq!(
+            bulk.add_connections_bulk(first.to_vec()).unwrap(),
+            0..first.len()
+        );
+        assert_eq!(
+            bulk.add_connections_bulk(rest.to_vec()).unwrap(),
+            first.len()..edges.len()
+        );
+
+        assert_eq!(bulk.name_index, single.name_index);
+        assert_eq!(bulk.outgoing_map, single.outgoing_map);
+        assert_eq!(bulk.incoming_map, single.incoming_map);
+        let rows = |network: &Network| -> Vec<_> {
+            network
+                .connections
+                .iter()
+                .map(|c| (c.from_id, c.to_id, c.synapse_type, c.weight))
+                .collect()
+        };
+        assert_eq!(rows(&bulk), edges);
+        assert_eq!(rows(&single), edges);
+        assert!(bulk.check_integrity().is_ok());
+    }
+
+    #[test]
+    fn a_bad_endpoint_adds_nothing() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 1, EXCITATORY, 1.0);
+        let result =
+            network.add_connections_bulk([(1, 0, EXCITATORY, 1.0), (1, 2, EXCITATORY, 1.0)]);
+        assert!(matches!(
+            result,
+            Err(FlymindError::InvalidNeuronId {
+                position: 1,
+                id: 2,
+                neuron_count: 2
+            })
+        ));
+        assert_eq!(network.connections.len(), 1);
+        assert_eq!(network.outgoing_map, HashMap::from([(0, vec![0])]));
+        assert_eq!(network.incoming_map, HashMap::from([(1, vec![0])]));
+    }
+}
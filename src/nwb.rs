@@ -0,0 +1,192 @@
+//! Spike output laid out as a Neurodata Without Borders units table, for
+//! loading runs into NWB tooling without a bespoke reader.
+//!
+//! The table is columnar, as in NWB: one row per neuron with its `id`, name,
+//! type and region, and every spike time in one flat `spike_times` column,
+//! `spike_times_index[row]` being the end of the row's spikes there (a
+//! `VectorIndex`). Times are in seconds, as NWB requires, whatever
+//! `LifParams::time_unit` the run used; a spike on step `s` is at `s * dt`.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::Network;
+use crate::simulation::SimulationResult;
+
+/// A run as written by `SimulationResult::write_nwb_json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NwbExport {
+    pub session: NwbSession,
+    pub units: NwbUnits,
+}
+
+/// What NWB keeps at file level, and the run's clock.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NwbSession {
+    pub session_description: String,
+    /// Version of this crate that wrote the export.
+    pub source_software_version: String,
+    /// Step length, in seconds.
+    pub dt: f64,
+    pub steps: usize,
+    /// `steps * dt`, in seconds.
+    pub duration: f64,
+    /// The unit the run was configured in, `"ms"` or `"s"`.
+    pub model_time_unit: String,
+    pub seed: Option<u64>,
+}
+
+/// The units table; every per-unit column has one entry per neuron, in id
+/// order.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NwbUnits {
+    pub id: Vec<usize>,
+    pub name: Vec<String>,
+    pub neuron_type: Vec<String>,
+    pub region: Vec<String>,
+    /// Spike times of every unit, in seconds, one unit after another.
+    pub spike_times: Vec<f64>,
+    pub spike_times_index: Vec<usize>,
+}
+
+impl NwbUnits {
+    /// Spike times of the unit in `row`, empty past the end of the table.
+    pub fn spike_times_of(&self, row: usize) -> &[f64] {
+        let Some(&end) = self.spike_times_index.get(row) else {
+            return &[];
+        };
+        let start = row
+            .checked_sub(1)
+            .map_or(0, |prev| self.spike_times_index[prev]);
+        &self.spike_times[start..end]
+    }
+}
+
+impl SimulationResult {
+    /// This run as an NWB units table, with unit metadata from `network`, the
+    /// network it ran on. `seed` is recorded as given, since a result does not
+    /// keep its own.
+    pub fn to_nwb(&self, network: &Network, seed: Option<u64>) -> NwbExport {
+        let unit = network.lif.time_unit;
+        let dt = self.dt * unit.in_seconds();
+        let mut units = NwbUnits::default();
+        for neuron in &network.neurons {
+            units.id.push(neuron.id);
+            units.name.push(neuron.name().to_string());
+            units
+                .neuron_type
+                .push(neuron.neuron_type.label().to_string());
+            units.region.push(neuron.region.label().to_string());
+            if let Some(train) = self.spike_trains.get(neuron.id) {
+                units
+                    .spike_times
+                    .extend(train.iter().map(|step| step as f64 * dt));
+            }
+            units.spike_times_index.push(units.spike_times.len());
+        }
+        NwbExport {
+            session: NwbSession {
+                session_description: format!(
+                    "flymind simulation of {} neurons",
+                    network.neurons.len()
+                ),
+                source_software_version: env!("CARGO_PKG_VERSION").to_string(),
+                dt,
+                steps: self.steps,
+                duration: self.steps as f64 * dt,
+                model_time_unit: unit.label().to_string(),
+                seed,
+            },
+            units,
+        }
+    }
+
+    /// Write `to_nwb` as pretty-printed JSON.
+    pub fn write_nwb_json<W: Write>(
+        &self,
+        network: &Network,
+        seed: Option<u64>,
+        mut writer: W,
+    ) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, &self.to_nwb(network, seed))?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::TimeUnit;
+    use crate::spikes::{SpikeStorage, SpikeTrain};
+
+    fn result(dt: f64, trains: &[&[usize]]) -> SimulationResult {
+        let spike_trains = trains
+            .iter()
+            .map(|steps| {
+                let mut train = SpikeTrain::new(SpikeStorage::Steps);
+                for &step in *steps {
+                    train.push(step);
+                }
+                train
+            })
+            .collect();
+        SimulationResult {
+            dt,
+            steps: 100,
+            spike_trains,
+            ..SimulationResult::default()
+        }
+    }
+
+    #[test]
+    fn spike_times_are_indexed_per_unit_in_seconds() {
+        let mut network = Network::new();
+        for name in ["A", "B", "C"] {
+            network.get_or_create_neuron(name);
+        }
+        let export = result(0.5, &[&[2, 10], &[], &[4]]).to_nwb(&network, Some(3));
+        let units = &export.units;
+        assert_eq!(units.id, vec![0, 1, 2]);
+        assert_eq!(units.name, vec!["A", "B", "C"]);
+        assert_eq!(units.spike_times_index, vec![2, 2, 3]);
+        assert_eq!(units.spike_times_of(0), &[0.001, 0.005]);
+        assert!(units.spike_times_of(1).is_empty());
+        assert_eq!(units.spike_times_of(2), &[0.002]);
+        assert!(units.spike_times_of(3).is_empty());
+        assert_eq!(export.session.dt, 0.0005);
+        assert_eq!(export.session.duration, 0.05);
+        assert_eq!(export.session.model_time_unit, "ms");
+        assert_eq!(export.session.seed, Some(3));
+
+        network.lif.time_unit = TimeUnit::Seconds;
+        let export = result(0.5, &[&[2, 10], &[], &[4]]).to_nwb(&network, None);
+        assert_eq!(export.units.spike_times_of(0), &[1.0, 5.0]);
+        assert_eq!(export.session.model_time_unit, "s");
+    }
+
+    #[test]
+    fn the_json_reads_back() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network
+            .stimuli
+            .push(crate::simulation::Stimulus::new(alml, 10, 200, 2.0));
+        let run = network.run_simulation(300, 0.1);
+        let mut out = Vec::new();
+        run.write_nwb_json(&network, None, &mut out).unwrap();
+        let read: NwbExport = serde_json::from_slice(&out).unwrap();
+        let export = run.to_nwb(&network, None);
+        assert_eq!(read.units.name, export.units.name);
+        assert_eq!(read.units.spike_times_index, export.units.spike_times_index);
+        assert!(read
+            .units
+            .spike_times
+            .iter()
+            .zip(&export.units.spike_times)
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert_eq!(read.units.spike_times.len(), run.total_spikes());
+        assert_eq!(read.units.neuron_type[alml], "sensory");
+    }
+}
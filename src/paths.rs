@@ -0,0 +1,336 @@
+//! Path queries with configurable traversal rules.
+//!
+//! Results never depend on the order connections were loaded in, only on
+//! neuron ids and weights. Every traversal expands neighbours through
+//! `Network::traversal_steps`, in ascending neuron id, and parallel
+//! connections to one neighbour are taken by heaviest weight, then lowest
+//! connection index. Among equally good answers:
+//!
+//! - `shortest_path_with` returns the path whose id sequence is smallest;
+//! - `simple_paths` lists paths in ascending order of their id sequences;
+//! - `weighted_shortest_path` keeps, for each neuron, the predecessor that
+//!   first reached its final cost, settling equal costs lowest id first.
+//!
+//! Reachability (`crate::cone`) and `Network::assign_layers` return depths,
+//! on which ties have no effect.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::network::{Network, SynapseType};
+use crate::subnetwork::hop_distances;
+
+/// Coarse synapse classes used to choose which connections a traversal may follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SynapseClass {
+    /// `ChemicalSend` connections, followed presynaptic to postsynaptic.
+    Chemical,
+    /// Gap junctions, followed in either direction.
+    GapJunction,
+    Nmj,
+    /// Extrasynaptic edges, followed from the releasing neuron.
+    Extrasynaptic,
+}
+
+impl SynapseType {
+    /// The traversal class of this type, or `None` for `ChemicalReceive` rows,
+    /// which mirror a send and are never traversed.
+    pub fn class(&self) -> Option<SynapseClass> {
+        match self {
+            SynapseType::ChemicalSend(_) => Some(SynapseClass::Chemical),
+            SynapseType::ChemicalReceive(_) => None,
+            SynapseType::GapJunction => Some(SynapseClass::GapJunction),
+            SynapseType::NMJ => Some(SynapseClass::Nmj),
+            SynapseType::Extrasynaptic(_) => Some(SynapseClass::Extrasynaptic),
+        }
+    }
+}
+
+/// The standard traversal filters shared by path and reachability queries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathOptions {
+    /// Connection classes that may be followed; defaults to the chemical graph.
+    pub classes: Vec<SynapseClass>,
+    /// Longest path, in hops, that a query may return.
+    pub max_hops: Option<usize>,
+    /// Connections lighter than this are ignored.
+    pub min_weight: f64,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        Self {
+            classes: vec![SynapseClass::Chemical],
+            max_hops: None,
+            min_weight: 0.0,
+        }
+    }
+}
+
+/// A route through the network: `connections[i]` links `neurons[i]` to `neurons[i + 1]`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Path {
+    pub neurons: Vec<usize>,
+    pub connections: Vec<usize>,
+}
+
+impl Path {
+    pub fn hops(&self) -> usize {
+        self.connections.len()
+    }
+}
+
+/// One traversable step: `(next neuron, connection index)`.
+pub type Step = (usize, usize);
+
+impl Network {
+    /// Traversable steps out of every neuron under `opts`.
+    ///
+    /// Parallel connections to the same partner collapse to the heaviest one
+    /// (lowest index on ties), and each list is sorted by partner id so that
+    /// traversals expand neighbours in a fixed order.
+    pub fn traversal_steps(&self, opts: &PathOptions) -> Vec<Vec<Step>> {
+        let mut steps: Vec<Vec<Step>> = vec![Vec::new(); self.neurons.len()];
+        for (index, conn) in self.connections.iter().enumerate() {
+            let Some(class) = conn.synapse_type.class() else {
+                continue;
+            };
+            if !opts.classes.contains(&class)
+                || conn.weight < opts.min_weight
+                || !self.counts_in_any_layer(conn)
+            {
+                continue;
+            }
+            steps[conn.from_id].push((conn.to_id, index));
+            if class == SynapseClass::GapJunction {
+                steps[conn.to_id].push((conn.from_id, index));
+            }
+        }
+
+        for list in &mut steps {
+            list.sort_by(|a, b| {
+                a.0.cmp(&b.0)
+                    .then_with(|| {
+                        let (wa, wb) = (self.connections[a.1].weight, self.connections[b.1].weight);
+                        wb.total_cmp(&wa)
+                    })
+                    .then(a.1.cmp(&b.1))
+            });
+            list.dedup_by_key(|step| step.0);
+        }
+        steps
+    }
+
+    /// Fewest-hop path under `opts`, or `None` if `to` is unreachable within
+    /// `max_hops`. Of several, the one whose neuron ids compare smallest in
+    /// order, which is the first `simple_paths` lists among the shortest.
+    pub fn shortest_path_with(&self, from: usize, to: usize, opts: &PathOptions) -> Option<Path> {
+        let n = self.neurons.len();
+        if from >= n || to >= n {
+            return None;
+        }
+        if from == to {
+            return Some(Path {
+                neurons: vec![from],
+                connections: Vec::new(),
+            });
+        }
+
+        let steps = self.traversal_steps(opts);
+        let mut into: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (current, list) in steps.iter().enumerate() {
+            for &(next, _) in list {
+                into[next].push(current);
+            }
+        }
+        // Hops left to `to`; walking forward along the lowest id that is one
+        // hop closer gives the lexicographically smallest shortest path.
+        let remaining = hop_distances(n, &[to], |v| into[v].iter().copied());
+        let mut hops = remaining[from].filter(|&h| h <= opts.max_hops.unwrap_or(usize::MAX))?;
+        let mut path = Path {
+            neurons: vec![from],
+            connections: Vec::new(),
+        };
+        let mut current = from;
+        while current != to {
+            let &(next, conn) = steps[current]
+                .iter()
+                .find(|&&(next, _)| remaining[next] == Some(hops - 1))
+                .expect("a neuron short of `to` has a step one hop closer");
+            path.neurons.push(next);
+            path.connections.push(conn);
+            current = next;
+            hops -= 1;
+        }
+        Some(path)
+    }
+
+    /// Lowest-cost path where crossing a connection costs `1 / weight` (Dijkstra),
+    /// so strong connections are preferred. Connections with non-positive weight are
+    /// impassable. Returns the path and its total cost. `max_hops` stops expansion
+    /// past that depth along the cheapest routes found, so it bounds the result
+    /// rather than finding the cheapest path among all short ones.
+    pub fn weighted_shortest_path(
+        &self,
+        from: usize,
+        to: usize,
+        opts: &PathOptions,
+    ) -> Option<(Path, f64)> {
+        let n = self.neurons.len();
+        if from >= n || to >= n {
+            return None;
+        }
+
+        let steps = self.traversal_steps(opts);
+        let max_hops = opts.max_hops.unwrap_or(usize::MAX);
+        let mut cost = vec![f64::INFINITY; n];
+        let mut hops = vec![0usize; n];
+        let mut parent: Vec<Option<Step>> = vec![None; n];
+        let mut heap = BinaryHeap::new();
+        cost[from] = 0.0;
+        heap.push(Frontier {
+            cost: 0.0,
+            id: from,
+        });
+
+        while let Some(Frontier { cost: c, id }) = heap.pop() {
+            if c > cost[id] {
+                continue;
+            }
+            if id == to {
+                return Some((unwind(&parent, from, to), c));
+            }
+            if hops[id] >= max_hops {
+                continue;
+            }
+            for &(next, conn) in &steps[id] {
+                let weight = self.connections[conn].weight;
+                if weight <= 0.0 {
+                    continue;
+                }
+                let candidate = c + 1.0 / weight;
+                if candidate < cost[next] {
+                    cost[next] = candidate;
+                    hops[next] = hops[id] + 1;
+                    parent[next] = Some((id, conn));
+                    heap.push(Frontier {
+                        cost: candidate,
+                        id: next,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// The most strongly connected route from `from` to `to` over chemical sends
+    /// and gap junctions (either way), and its summed weight. This is
+    /// `weighted_shortest_path`, so it minimises the sum of `1 / weight`: a few
+    /// strong hops beat many weak ones even when the weak ones add up to more.
+    /// Zero-weight connections are impassable.
+    pub fn strongest_path(&self, from: usize, to: usize) -> Option<(Path, f64)> {
+        let opts = PathOptions {
+            classes: vec![SynapseClass::Chemical, SynapseClass::GapJunction],
+            ..PathOptions::default()
+        };
+        let (path, _) = self.weighted_shortest_path(from, to, &opts)?;
+        let strength = path
+            .connections
+            .iter()
+            .map(|&conn| self.connections[conn].weight)
+            .sum();
+        Some((path, strength))
+    }
+
+    /// Every simple path from `from` to `to` with at most `max_hops` hops (four
+    /// when unset), in depth-first order over ascending neighbour ids.
+    pub fn simple_paths(&self, from: usize, to: usize, opts: &PathOptions) -> Vec<Path> {
+        let n = self.neurons.len();
+        let mut found = Vec::new();
+        if from >= n || to >= n || from == to {
+            return found;
+        }
+
+        let steps = self.traversal_steps(opts);
+        let mut on_path = vec![false; n];
+        on_path[from] = true;
+        let mut path = Path {
+            neurons: vec![from],
+            connections: Vec::new(),
+        };
+        let max_hops = opts.max_hops.unwrap_or(4);
+        extend_simple_paths(&steps, to, max_hops, &mut path, &mut on_path, &mut found);
+        found
+    }
+}
+
+fn extend_simple_paths(
+    steps: &[Vec<Step>],
+    to: usize,
+    max_hops: usize,
+    path: &mut Path,
+    on_path: &mut [bool],
+    found: &mut Vec<Path>,
+) {
+    if path.hops() >= max_hops {
+        return;
+    }
+    let last = *path.neurons.last().expect("path always holds the source");
+    for &(next, conn) in &steps[last] {
+        if on_path[next] {
+            continue;
+        }
+        path.neurons.push(next);
+        path.connections.push(conn);
+        if next == to {
+            found.push(path.clone());
+        } else {
+            on_path[next] = true;
+            extend_simple_paths(steps, to, max_hops, path, on_path, found);
+            on_path[next] = false;
+        }
+        path.neurons.pop();
+        path.connections.pop();
+    }
+}
+
+fn unwind(parent: &[Option<Step>], from: usize, to: usize) -> Path {
+    let mut path = Path {
+        neurons: vec![to],
+        connections: Vec::new(),
+    };
+    let mut node = to;
+    while node != from {
+        let (prev, conn) = parent[node].expect("every reached neuron has a parent");
+        path.neurons.push(prev);
+        path.connections.push(conn);
+        node = prev;
+    }
+    path.neurons.reverse();
+    path.connections.reverse();
+    path
+}
+
+/// Min-heap entry for Dijkstra (ordering reversed for `BinaryHeap`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frontier {
+    cost: f64,
+    id: usize,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
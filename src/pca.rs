@@ -0,0 +1,221 @@
+//! Principal components of the recorded membrane potentials, for looking at
+//! population activity as a trajectory in a few dimensions.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::error::FlymindError;
+use crate::layout::symmetric_eigen;
+use crate::simulation::SimulationResult;
+
+/// Principal components of a run's potentials, from `pca`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PcaResult {
+    /// The watched neurons with a recorded trace, ascending; the variables of
+    /// the analysis.
+    pub neurons: Vec<usize>,
+    /// Time of each potential sample, as in `write_voltages_csv`.
+    pub times: Vec<f64>,
+    /// Unit loading vectors over `neurons`, by decreasing variance, each
+    /// signed so its largest loading is positive.
+    pub components: Vec<Vec<f64>>,
+    /// Variance along each component.
+    pub explained_variance: Vec<f64>,
+    /// `explained_variance` over the total variance of all components.
+    pub explained_ratio: Vec<f64>,
+    /// `projections[sample][k]`: the centred (and scaled) sample on component
+    /// `k`.
+    pub projections: Vec<Vec<f64>>,
+}
+
+/// The first `n_components` principal components of `result`'s recorded
+/// potentials, treating each watched neuron as a variable and each sample as
+/// an observation. Empty traces, as recorded for a watched id outside the
+/// network, are left out, and longer traces are cut to the shortest of the
+/// rest. Traces are mean-centred, and with `z_score` also divided by their
+/// standard deviation; constant traces are left at zero rather than divided
+/// by it. Empty without at least two samples.
+pub fn pca(result: &SimulationResult, n_components: usize, z_score: bool) -> PcaResult {
+    let mut neurons: Vec<usize> = result
+        .potentials
+        .iter()
+        .filter(|(_, trace)| !trace.is_empty())
+        .map(|(&id, _)| id)
+        .collect();
+    neurons.sort_unstable();
+    let samples = neurons
+        .iter()
+        .map(|id| result.potentials[id].len())
+        .min()
+        .unwrap_or(0);
+    if samples < 2 {
+        return PcaResult::default();
+    }
+
+    let data: Vec<Vec<f64>> = neurons
+        .iter()
+        .map(|id| {
+            let trace = &result.potentials[id][..samples];
+            let mean = trace.iter().sum::<f64>() / samples as f64;
+            let centred: Vec<f64> = trace.iter().map(|v| v - mean).collect();
+            let std = (centred.iter().map(|v| v * v).sum::<f64>() / (samples - 1) as f64).sqrt();
+            if z_score && std > 0.0 {
+                centred.iter().map(|v| v / std).collect()
+            } else {
+                centred
+            }
+        })
+        .collect();
+
+    let n = neurons.len();
+    let mut covariance = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let c = data[i]
+                .iter()
+                .zip(&data[j])
+                .map(|(a, b)| a * b)
+                .sum::<f64>()
+                / (samples - 1) as f64;
+            covariance[i][j] = c;
+            covariance[j][i] = c;
+        }
+    }
+    let (values, vectors) = symmetric_eigen(covariance);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| values[b].total_cmp(&values[a]).then(a.cmp(&b)));
+    let total: f64 = values.iter().map(|v| v.max(0.0)).sum();
+
+    let mut pca = PcaResult {
+        times: (0..samples)
+            .map(|sample| (sample * result.potential_stride) as f64 * result.dt)
+            .collect(),
+        ..PcaResult::default()
+    };
+    for &column in order.iter().take(n_components) {
+        let mut component: Vec<f64> = (0..n).map(|i| vectors[i][column]).collect();
+        let largest = component
+            .iter()
+            .copied()
+            .reduce(|best, x| {
+                if x.abs() > best.abs() + 1e-12 {
+                    x
+                } else {
+                    best
+                }
+            })
+            .unwrap_or(0.0);
+        if largest < 0.0 {
+            component.iter_mut().for_each(|x| *x = -*x);
+        }
+        let variance = values[column].max(0.0);
+        pca.explained_variance.push(variance);
+        pca.explained_ratio
+            .push(if total > 0.0 { variance / total } else { 0.0 });
+        pca.components.push(component);
+    }
+    pca.projections = (0..samples)
+        .map(|sample| {
+            pca.components
+                .iter()
+                .map(|component| (0..n).map(|i| component[i] * data[i][sample]).sum())
+                .collect()
+        })
+        .collect();
+    pca.neurons = neurons;
+    pca
+}
+
+impl PcaResult {
+    /// Write a `time,pc1,pc2,...` row per sample with the projections onto the
+    /// first `k` components (all of them if there are fewer).
+    pub fn write_projections_csv<W: Write>(&self, writer: W, k: usize) -> Result<(), FlymindError> {
+        let k = k.min(self.components.len());
+        let mut wtr = csv::Writer::from_writer(writer);
+        let mut header = vec!["time".to_string()];
+        header.extend((1..=k).map(|i| format!("pc{i}")));
+        wtr.write_record(&header)?;
+        for (time, projection) in self.times.iter().zip(&self.projections) {
+            let mut record = vec![time.to_string()];
+            record.extend(projection[..k].iter().map(|x| x.to_string()));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn result_of(potentials: Vec<(usize, Vec<f64>)>) -> SimulationResult {
+        SimulationResult {
+            dt: 0.1,
+            steps: 1000,
+            potentials: potentials.into_iter().collect(),
+            potential_stride: 1,
+            ..SimulationResult::default()
+        }
+    }
+
+    #[test]
+    fn recovers_a_two_dimensional_latent() {
+        let mixing = [[1.0, 0.0], [0.5, 2.0], [-1.0, 1.0], [0.2, -0.7], [2.0, 0.3]];
+        let mut rng = Rng::new(5);
+        let latent: Vec<[f64; 2]> = (0..1000)
+            .map(|t| {
+                let t = t as f64 * 0.01;
+                [(3.0 * t).sin(), (7.0 * t).cos()]
+            })
+            .collect();
+        let potentials = mixing
+            .iter()
+            .enumerate()
+            .map(|(id, row)| {
+                let trace = latent
+                    .iter()
+                    .map(|z| row[0] * z[0] + row[1] * z[1] + 0.001 * rng.normal())
+                    .collect();
+                (id, trace)
+            })
+            .collect();
+        let pca = pca(&result_of(potentials), 3, false);
+        assert_eq!(pca.neurons, vec![0, 1, 2, 3, 4]);
+        assert!(pca.explained_ratio[0] + pca.explained_ratio[1] > 0.9999);
+        assert!(pca.explained_ratio[2] < 1e-4);
+        for column in 0..2 {
+            let m: Vec<f64> = mixing.iter().map(|row| row[column]).collect();
+            let norm = m.iter().map(|x| x * x).sum::<f64>().sqrt();
+            let projected: f64 = pca.components[..2]
+                .iter()
+                .map(|c| c.iter().zip(&m).map(|(a, b)| a * b).sum::<f64>().powi(2))
+                .sum::<f64>()
+                .sqrt();
+            assert!(projected / norm > 0.9999, "column {column}");
+        }
+    }
+
+    #[test]
+    fn uneven_and_empty_traces_are_trimmed() {
+        let potentials = vec![
+            (0, (0..50).map(|t| t as f64).collect()),
+            (1, (0..30).map(|t| (t as f64).sin()).collect()),
+            (7, Vec::new()),
+        ];
+        let pca = pca(&result_of(potentials), 2, true);
+        assert_eq!(pca.neurons, vec![0, 1]);
+        assert_eq!(pca.times.len(), 30);
+        assert_eq!(pca.projections.len(), 30);
+        assert!(pca.explained_ratio.iter().all(|r| r.is_finite()));
+
+        let mut network = crate::network::Network::example_touch_circuit();
+        let result = network.run_simulation_recording(20, 0.1, &[0, 1, 999]);
+        assert_eq!(super::pca(&result, 1, false).neurons, vec![0, 1]);
+
+        let lonely = result_of(vec![(3, vec![1.0]), (4, Vec::new())]);
+        assert_eq!(super::pca(&lonely, 2, false), PcaResult::default());
+    }
+}
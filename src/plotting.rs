@@ -0,0 +1,459 @@
+//! Spike rasters and potential traces drawn to PNG with plotters, from the
+//! recorders of `crate::recording`. Built with the `plotting` feature, as is
+//! `Network::render_heatmap`.
+
+mod backend;
+mod heatmap;
+
+use std::io;
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters_backend::text_anchor::{HPos, Pos, VPos};
+use plotters_backend::DrawingErrorKind;
+
+pub(crate) use backend::PngBackend;
+
+use crate::network::NeuronType;
+use crate::recording::{RasterOrder, SpikeRecorder, VoltageRecorder};
+
+pub(crate) const FONT: (&str, u32) = ("sans-serif", 10);
+const GREY: RGBColor = RGBColor(160, 160, 160);
+/// Trace colours, cycled through in trace order.
+const PALETTE: [RGBColor; 6] = [
+    RGBColor(31, 119, 180),
+    RGBColor(255, 127, 14),
+    RGBColor(44, 160, 44),
+    RGBColor(214, 39, 40),
+    RGBColor(148, 103, 189),
+    RGBColor(140, 86, 75),
+];
+/// Pixels below the plot for the time axis, its labels and title.
+const TIME_AXIS: u32 = 32;
+const PAD: u32 = 8;
+/// Smallest image either plot is drawn in.
+const MIN_SIZE: (u32, u32) = (96, 64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterOptions {
+    /// Image size in pixels, labels included.
+    pub width: u32,
+    pub height: u32,
+    pub order: RasterOrder,
+    /// Colour spikes by neuron type (sensory red, interneuron blue, motor green,
+    /// other grey) instead of black.
+    pub color_by_type: bool,
+    /// Name every `label_every`-th row; 0 draws no names.
+    pub label_every: usize,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 640,
+            order: RasterOrder::default(),
+            color_by_type: false,
+            label_every: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceLayout {
+    /// All traces on one shared potential axis.
+    #[default]
+    Overlaid,
+    /// One band per neuron, each scaled to its own range.
+    Stacked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoltageOptions {
+    pub width: u32,
+    pub height: u32,
+    pub layout: TraceLayout,
+}
+
+impl Default for VoltageOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 480,
+            layout: TraceLayout::default(),
+        }
+    }
+}
+
+/// Draw the spikes of `recorder` as a raster, one row per neuron in
+/// `options.order` from the top, against time in the run's unit. A recording
+/// without spikes gives an empty, labelled plot. Fails with `InvalidInput` if
+/// the image is too small.
+pub fn raster_png<P: AsRef<Path>>(
+    recorder: &SpikeRecorder,
+    path: P,
+    options: &RasterOptions,
+) -> io::Result<()> {
+    check_size(options.width, options.height)?;
+    let rows = recorder.order(options.order);
+    let labelled: Vec<usize> = match options.label_every {
+        0 => Vec::new(),
+        k => (0..rows.len()).step_by(k).collect(),
+    };
+    let names: Vec<&str> = labelled.iter().map(|&i| recorder.name(rows[i])).collect();
+
+    let root = PngBackend::new(path.as_ref(), (options.width, options.height)).into_drawing_area();
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let n = rows.len().max(1) as f64;
+    let dt = recorder.dt();
+    let mut chart = ChartBuilder::on(&root)
+        .margin(PAD)
+        .x_label_area_size(TIME_AXIS)
+        .y_label_area_size(text_width(&names))
+        .build_cartesian_2d(0.0..time_span(recorder.duration()), 0.0..n)
+        .map_err(drawing_error)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .y_labels(0)
+        .x_label_formatter(&format_value)
+        .x_desc(format!("time ({})", recorder.time_unit().label()))
+        .label_style(FONT)
+        .axis_desc_style(FONT)
+        .draw()
+        .map_err(drawing_error)?;
+
+    if recorder.total_spikes() == 0 {
+        centred_text(&root, "no spikes")?;
+        return root.present().map_err(drawing_error);
+    }
+    let top = |i: usize| n - i as f64;
+    chart
+        .draw_series(rows.iter().enumerate().flat_map(|(i, &id)| {
+            let colour = if options.color_by_type {
+                type_colour(recorder.neuron_type(id))
+            } else {
+                BLACK
+            };
+            recorder.spikes(id).iter().map(move |&step| {
+                let t = step as f64 * dt;
+                Rectangle::new([(t, top(i)), (t + dt, top(i) - 1.0)], colour.filled())
+            })
+        }))
+        .map_err(drawing_error)?;
+    for (&i, name) in labelled.iter().zip(&names) {
+        let (x, y) = chart.backend_coord(&(0.0, top(i) - 0.5));
+        root.draw(&Text::new(*name, (x - 4, y), right_aligned()))
+            .map_err(drawing_error)?;
+    }
+    root.present().map_err(drawing_error)
+}
+
+/// Draw the traces of `recorder`, one line per watched neuron in the order
+/// watched, against time in the run's unit. Overlaid traces share a potential
+/// axis and are named in a legend; stacked ones are named beside their band.
+/// A recording without samples gives an empty, labelled plot. Fails with
+/// `InvalidInput` if the image is too small.
+pub fn voltage_png<P: AsRef<Path>>(
+    recorder: &VoltageRecorder,
+    path: P,
+    options: &VoltageOptions,
+) -> io::Result<()> {
+    check_size(options.width, options.height)?;
+    let traces: Vec<usize> = (0..recorder.ids().len())
+        .filter(|&k| !recorder.trace(k).is_empty())
+        .collect();
+    let names: Vec<&str> = traces
+        .iter()
+        .map(|&k| recorder.names()[k].as_str())
+        .collect();
+    let times = recorder.sample_times();
+    let (low, high) = padded_range(traces.iter().flat_map(|&k| recorder.trace(k)));
+    let bands = traces.len().max(1) as f64;
+    let (y_range, margin) = match options.layout {
+        TraceLayout::Overlaid => {
+            let labels = [format_value(&high), format_value(&low)];
+            (low..high, text_width(&labels))
+        }
+        TraceLayout::Stacked => (0.0..bands, text_width(&names)),
+    };
+
+    let root = PngBackend::new(path.as_ref(), (options.width, options.height)).into_drawing_area();
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(PAD)
+        .x_label_area_size(TIME_AXIS)
+        .y_label_area_size(margin)
+        .build_cartesian_2d(0.0..time_span(recorder.duration()), y_range)
+        .map_err(drawing_error)?;
+    let mut mesh = chart.configure_mesh();
+    mesh.disable_mesh()
+        .x_label_formatter(&format_value)
+        .y_label_formatter(&format_value)
+        .x_desc(format!("time ({})", recorder.time_unit().label()))
+        .label_style(FONT)
+        .axis_desc_style(FONT);
+    if options.layout == TraceLayout::Stacked {
+        mesh.y_labels(0);
+    }
+    mesh.draw().map_err(drawing_error)?;
+
+    if traces.is_empty() {
+        centred_text(&root, "no potentials")?;
+        return root.present().map_err(drawing_error);
+    }
+    for (band, (&k, name)) in traces.iter().zip(&names).enumerate() {
+        let colour = PALETTE[band % PALETTE.len()];
+        let trace = recorder.trace(k);
+        let y_of: Box<dyn Fn(f64) -> f64> = match options.layout {
+            TraceLayout::Overlaid => Box::new(|v| v),
+            TraceLayout::Stacked => {
+                let (lo, hi) = padded_range(trace);
+                let bottom = bands - 1.0 - band as f64;
+                Box::new(move |v| bottom + (v - lo) / (hi - lo))
+            }
+        };
+        let points: Vec<(f64, f64)> = times
+            .iter()
+            .zip(trace)
+            .map(|(&t, &v)| (t, y_of(v)))
+            .collect();
+        let series = chart
+            .draw_series(LineSeries::new(points.iter().copied(), colour))
+            .map_err(drawing_error)?;
+        match options.layout {
+            TraceLayout::Overlaid => {
+                series
+                    .label(*name)
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 12, y)], colour));
+            }
+            TraceLayout::Stacked => {
+                let (x, y) = chart.backend_coord(&(0.0, bands - 0.5 - band as f64));
+                root.draw(&Text::new(
+                    *name,
+                    (x - 4, y),
+                    right_aligned().color(&colour),
+                ))
+                .map_err(drawing_error)?;
+            }
+        }
+        if let [point] = points[..] {
+            chart
+                .draw_series([Circle::new(point, 1, colour.filled())])
+                .map_err(drawing_error)?;
+        }
+    }
+    if options.layout == TraceLayout::Overlaid {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .label_font(FONT)
+            .background_style(WHITE)
+            .border_style(GREY)
+            .draw()
+            .map_err(drawing_error)?;
+    }
+    root.present().map_err(drawing_error)
+}
+
+/// The backend's own error if it failed, otherwise plotters' as `Other`.
+pub(crate) fn drawing_error(err: DrawingAreaErrorKind<io::Error>) -> io::Error {
+    match err {
+        DrawingAreaErrorKind::BackendError(DrawingErrorKind::DrawingError(err)) => err,
+        other => io::Error::other(other.to_string()),
+    }
+}
+
+fn check_size(width: u32, height: u32) -> io::Result<()> {
+    if width < MIN_SIZE.0 || height < MIN_SIZE.1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a {width}x{height} image leaves no room for the plot"),
+        ));
+    }
+    Ok(())
+}
+
+/// The time axis runs to `duration`, or to 1 for an empty recording.
+fn time_span(duration: f64) -> f64 {
+    if duration > 0.0 {
+        duration
+    } else {
+        1.0
+    }
+}
+
+/// Label text anchored at its right end, centred vertically.
+pub(crate) fn right_aligned() -> TextStyle<'static> {
+    TextStyle::from(FONT.into_font()).pos(Pos::new(HPos::Right, VPos::Center))
+}
+
+fn centred_text(root: &DrawingArea<PngBackend, Shift>, text: &str) -> io::Result<()> {
+    let (width, height) = root.dim_in_pixel();
+    let style = TextStyle::from(FONT.into_font())
+        .color(&GREY)
+        .pos(Pos::new(HPos::Center, VPos::Center));
+    root.draw(&Text::new(
+        text,
+        (width as i32 / 2, height as i32 / 2),
+        style,
+    ))
+    .map_err(drawing_error)
+}
+
+fn type_colour(neuron_type: NeuronType) -> RGBColor {
+    match neuron_type {
+        NeuronType::Sensory => RGBColor(214, 39, 40),
+        NeuronType::Interneuron => RGBColor(31, 119, 180),
+        NeuronType::Motor => RGBColor(44, 160, 44),
+        NeuronType::Other => GREY,
+    }
+}
+
+/// Pixels taken by the longest of `labels` and a gap.
+pub(crate) fn text_width<S: AsRef<str>>(labels: &[S]) -> u32 {
+    labels
+        .iter()
+        .map(|label| label.as_ref().chars().count() as u32 * 6 + 8)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The range of `values` widened by 5% each way, or by 1 around a flat trace.
+fn padded_range<'a>(values: impl IntoIterator<Item = &'a f64>) -> (f64, f64) {
+    let (low, high) = values
+        .into_iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+    if !(low.is_finite() && high.is_finite()) {
+        (0.0, 1.0)
+    } else if high - low < 1e-12 {
+        (low - 1.0, high + 1.0)
+    } else {
+        let pad = (high - low) * 0.05;
+        (low - pad, high + pad)
+    }
+}
+
+/// `value` with three significant digits or so.
+fn format_value(value: &f64) -> String {
+    let value = *value;
+    let decimals = if value == 0.0 {
+        0
+    } else {
+        (2 - value.abs().log10().floor() as i32).clamp(0, 6) as usize
+    };
+    format!("{value:.decimals$}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+    use crate::simulation::Stimulus;
+    use std::path::PathBuf;
+
+    fn png_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("flymind-plot-{}-{name}.png", std::process::id()))
+    }
+
+    /// Read back a written PNG, checking its signature and that the pixel data
+    /// (stored uncompressed) is all there.
+    fn assert_png(path: &Path, width: u32, height: u32) {
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(bytes.len() as u64 > u64::from(width * height) * 3);
+    }
+
+    fn stimulated() -> Network {
+        let mut network = Network::example_touch_circuit();
+        for name in ["ALML", "ALMR", "AVM"] {
+            let id = network.id_of(name).unwrap();
+            network.stimuli.push(Stimulus::new(id, 20, 200, 0.15));
+        }
+        network
+    }
+
+    #[test]
+    fn rasters_draw_in_every_order() {
+        let mut network = stimulated();
+        let mut recorder = SpikeRecorder::new(&network);
+        network.run_simulation_observed(300, 0.1, &[], 1, &mut recorder);
+        assert!(recorder.total_spikes() > 0);
+        for (k, order) in [
+            RasterOrder::Id,
+            RasterOrder::SomaPosition,
+            RasterOrder::NeuronType,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let path = png_path(&format!("raster-{k}"));
+            let options = RasterOptions {
+                width: 400,
+                height: 300,
+                order,
+                color_by_type: k % 2 == 0,
+                label_every: 1,
+            };
+            raster_png(&recorder, &path, &options).unwrap();
+            assert_png(&path, 400, 300);
+        }
+    }
+
+    #[test]
+    fn traces_draw_overlaid_and_stacked() {
+        let mut network = stimulated();
+        let watch = [
+            network.id_of("ALML").unwrap(),
+            network.id_of("AVAL").unwrap(),
+        ];
+        let result = network.run_simulation_strided(300, 0.1, &watch, 2);
+        let recorder = VoltageRecorder::from_result(&network, &result);
+        assert_eq!(recorder.trace(0).len(), 150);
+        for layout in [TraceLayout::Overlaid, TraceLayout::Stacked] {
+            let path = png_path(&format!("trace-{layout:?}"));
+            let options = VoltageOptions {
+                width: 320,
+                height: 200,
+                layout,
+            };
+            voltage_png(&recorder, &path, &options).unwrap();
+            assert_png(&path, 320, 200);
+        }
+    }
+
+    #[test]
+    fn empty_recordings_still_draw() {
+        let network = Network::example_touch_circuit();
+        let path = png_path("empty-raster");
+        raster_png(
+            &SpikeRecorder::new(&network),
+            &path,
+            &RasterOptions::default(),
+        )
+        .unwrap();
+        assert_png(&path, 1024, 640);
+        let path = png_path("empty-trace");
+        let recorder = VoltageRecorder::new(&network, &[], 1);
+        voltage_png(&recorder, &path, &VoltageOptions::default()).unwrap();
+        assert_png(&path, 1024, 480);
+    }
+
+    #[test]
+    fn tiny_images_are_rejected() {
+        let network = Network::example_touch_circuit();
+        let options = RasterOptions {
+            width: 10,
+            height: 10,
+            ..RasterOptions::default()
+        };
+        let path = png_path("tiny");
+        let err = raster_png(&SpikeRecorder::new(&network), &path, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
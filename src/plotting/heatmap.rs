@@ -0,0 +1,286 @@
+//! `Network::render_heatmap`, the weight matrix as a PNG.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use plotters::prelude::*;
+use plotters_backend::text_anchor::{HPos, Pos, VPos};
+
+use super::{drawing_error, right_aligned, text_width, PngBackend, FONT};
+use crate::heatmap::{ColorScale, HeatmapLayers, HeatmapOptions, MatrixOrder};
+use crate::network::{Network, SynapseType};
+
+/// Pixels between the two panels of `HeatmapLayers::Panels`.
+const PANEL_GAP: u32 = 8;
+/// Yellow-orange-red ramp from lightest to heaviest weight.
+const HEAT_STOPS: [[f64; 3]; 4] = [
+    [255.0, 237.0, 160.0],
+    [254.0, 178.0, 76.0],
+    [240.0, 59.0, 32.0],
+    [128.0, 0.0, 38.0],
+];
+
+impl Network {
+    /// Render the weight matrix as a PNG at `path`, presynaptic neurons as rows.
+    /// Cells sum chemical-send and gap-junction weights (non-positive weights
+    /// draw nothing) and run from white for no weight through yellow to dark
+    /// red. Where neurons outnumber pixels, a pixel shows the heaviest cell it
+    /// covers. Fails with `InvalidInput` for a `Permutation` that is not one or
+    /// an image too small to hold the labels.
+    pub fn render_heatmap<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &HeatmapOptions,
+    ) -> io::Result<()> {
+        let order = self.matrix_order(&options.order)?;
+        let (width, height) = (options.width, options.height);
+
+        let labelled: Vec<usize> = match options.label_every {
+            0 => Vec::new(),
+            k => (0..order.len()).step_by(k).collect(),
+        };
+        let names: Vec<&str> = labelled
+            .iter()
+            .map(|&i| self.neurons[order[i]].name())
+            .collect();
+        let margin = text_width(&names).max(2);
+        let panels = if options.layers == HeatmapLayers::Panels {
+            2
+        } else {
+            1
+        };
+        let panel_width = width.saturating_sub(margin + 2 + PANEL_GAP * (panels - 1)) / panels;
+        let panel_height = height.saturating_sub(margin + 2);
+        if panel_width == 0 || panel_height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("a {width}x{height} image leaves no room for the matrix"),
+            ));
+        }
+
+        let n = order.len();
+        let mut position = vec![0; self.neurons.len()];
+        for (i, &id) in order.iter().enumerate() {
+            position[id] = i;
+        }
+        let mut chemical = HashMap::new();
+        let mut gap = HashMap::new();
+        for conn in self
+            .connections
+            .iter()
+            .filter(|c| c.weight > 0.0 && self.counts(c))
+        {
+            let cells = match conn.synapse_type {
+                SynapseType::ChemicalSend(_) => &mut chemical,
+                SynapseType::GapJunction => &mut gap,
+                _ => continue,
+            };
+            let cell = (position[conn.from_id], position[conn.to_id]);
+            *cells.entry(cell).or_insert(0.0) += conn.weight;
+        }
+        let (panel_width, panel_height) = (panel_width as usize, panel_height as usize);
+        let raster = |cells: &HashMap<(usize, usize), f64>| {
+            rasterize(cells, n, panel_width, panel_height, options.scale)
+        };
+        let heat = |t: f64| if t > 0.0 { heat_colour(t) } else { WHITE };
+        let layers = match options.layers {
+            HeatmapLayers::Combined => {
+                for (&cell, &w) in &gap {
+                    *chemical.entry(cell).or_insert(0.0) += w;
+                }
+                vec![raster(&chemical).iter().map(|&t| heat(t)).collect()]
+            }
+            HeatmapLayers::Channels => {
+                let (red, green) = (raster(&chemical), raster(&gap));
+                vec![red
+                    .iter()
+                    .zip(&green)
+                    .map(|(&r, &g)| RGBColor(channel(r), channel(g), 0))
+                    .collect()]
+            }
+            HeatmapLayers::Panels => [&chemical, &gap]
+                .iter()
+                .map(|cells| raster(cells).iter().map(|&t| heat(t)).collect())
+                .collect::<Vec<Vec<RGBColor>>>(),
+        };
+
+        let root = PngBackend::new(path.as_ref(), (width, height)).into_drawing_area();
+        root.fill(&WHITE).map_err(drawing_error)?;
+        let top = margin as i32;
+        let span =
+            |i: usize, extent: usize| ((i * extent / n) as i32, ((i + 1) * extent / n) as i32);
+        let up = TextStyle::from(FONT.into_font())
+            .transform(FontTransform::Rotate270)
+            .pos(Pos::new(HPos::Left, VPos::Center));
+        for (panel, pixels) in layers.iter().enumerate() {
+            let left = (margin + panel as u32 * (panel_width as u32 + PANEL_GAP)) as i32;
+            for (y, row) in pixels.chunks(panel_width).enumerate() {
+                for (x, colour) in row.iter().enumerate() {
+                    root.draw_pixel((left + x as i32, top + y as i32), colour)
+                        .map_err(drawing_error)?;
+                }
+            }
+            for (&i, name) in labelled.iter().zip(&names) {
+                let (start, end) = span(i, panel_width);
+                let x = left + (start + end) / 2;
+                root.draw(&Text::new(*name, (x, top - 3), up.clone()))
+                    .map_err(drawing_error)?;
+            }
+        }
+        for (&i, name) in labelled.iter().zip(&names) {
+            let (start, end) = span(i, panel_height);
+            let y = top + (start + end) / 2;
+            root.draw(&Text::new(*name, (margin as i32 - 3, y), right_aligned()))
+                .map_err(drawing_error)?;
+        }
+        root.present().map_err(drawing_error)
+    }
+
+    /// Neuron ids in axis order.
+    fn matrix_order(&self, order: &MatrixOrder) -> io::Result<Vec<usize>> {
+        let n = self.neurons.len();
+        let mut ids: Vec<usize> = (0..n).collect();
+        match order {
+            MatrixOrder::TypeThenName => ids.sort_by(|&a, &b| {
+                let (a, b) = (&self.neurons[a], &self.neurons[b]);
+                (a.neuron_type as usize, a.name()).cmp(&(b.neuron_type as usize, b.name()))
+            }),
+            MatrixOrder::SomaPosition => ids.sort_by(|&a, &b| {
+                self.neurons[a]
+                    .soma_position
+                    .total_cmp(&self.neurons[b].soma_position)
+            }),
+            MatrixOrder::Communities(communities) => {
+                let mut placed = vec![false; n];
+                ids = communities
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .filter(|&id| id < n && !std::mem::replace(&mut placed[id], true))
+                    .collect();
+                ids.extend((0..n).filter(|&id| !placed[id]));
+            }
+            MatrixOrder::Permutation(permutation) => {
+                let mut seen = vec![false; n];
+                let valid = permutation.len() == n
+                    && permutation
+                        .iter()
+                        .all(|&id| id < n && !std::mem::replace(&mut seen[id], true));
+                if !valid {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("matrix order is not a permutation of the {n} neuron ids"),
+                    ));
+                }
+                ids.clone_from(permutation);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Intensities in `[0, 1]` of a `width` x `height` raster of the `n` x `n`
+/// matrix `cells`, row by row; each pixel keeps the heaviest cell under it.
+fn rasterize(
+    cells: &HashMap<(usize, usize), f64>,
+    n: usize,
+    width: usize,
+    height: usize,
+    scale: ColorScale,
+) -> Vec<f64> {
+    let mut raster = vec![0.0; width * height];
+    let max = cells.values().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return raster;
+    }
+    let pixels = |i: usize, extent: usize| {
+        let start = i * extent / n;
+        start..((i + 1) * extent / n).max(start + 1)
+    };
+    for (&(row, column), &w) in cells {
+        let t = match scale {
+            ColorScale::Linear => w / max,
+            ColorScale::Log => w.ln_1p() / max.ln_1p(),
+        };
+        for y in pixels(row, height) {
+            for x in pixels(column, width) {
+                let pixel = &mut raster[y * width + x];
+                *pixel = f64::max(*pixel, t);
+            }
+        }
+    }
+    raster
+}
+
+fn heat_colour(t: f64) -> RGBColor {
+    let x = t.clamp(0.0, 1.0) * (HEAT_STOPS.len() - 1) as f64;
+    let i = (x as usize).min(HEAT_STOPS.len() - 2);
+    let f = x - i as f64;
+    let (a, b) = (HEAT_STOPS[i], HEAT_STOPS[i + 1]);
+    let [r, g, b] = [0, 1, 2].map(|c| (a[c] + (b[c] - a[c]) * f).round() as u8);
+    RGBColor(r, g, b)
+}
+
+fn channel(t: f64) -> u8 {
+    (t.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flymind-heatmap-{}-{name}.png", std::process::id()))
+    }
+
+    fn assert_png(path: &Path) {
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn fixture_renders_in_every_layer_mode() {
+        let network = Network::example_touch_circuit();
+        for layers in [
+            HeatmapLayers::Combined,
+            HeatmapLayers::Channels,
+            HeatmapLayers::Panels,
+        ] {
+            let path = png_path(&format!("{layers:?}"));
+            let options = HeatmapOptions {
+                width: 400,
+                height: 300,
+                label_every: 1,
+                layers,
+                ..HeatmapOptions::default()
+            };
+            network.render_heatmap(&path, &options).unwrap();
+            assert_png(&path);
+        }
+    }
+
+    #[test]
+    fn unwired_roster_renders() {
+        let network = Network::with_canonical_roster();
+        assert!(network.connections.is_empty());
+        let path = png_path("roster");
+        network
+            .render_heatmap(&path, &HeatmapOptions::default())
+            .unwrap();
+        assert_png(&path);
+    }
+
+    #[test]
+    fn bad_permutation_is_rejected() {
+        let network = Network::example_touch_circuit();
+        let options = HeatmapOptions {
+            order: MatrixOrder::Permutation(vec![0; network.neurons.len()]),
+            ..HeatmapOptions::default()
+        };
+        let path = png_path("perm");
+        let err = network.render_heatmap(&path, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
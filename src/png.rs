@@ -0,0 +1,109 @@
+//! Minimal PNG encoder for 8-bit RGB images.
+//!
+//! Pixel data goes into uncompressed deflate blocks, so files are about the
+//! size of the raw pixels but need no compression or image crates.
+
+use std::io::{self, Write};
+
+/// Largest payload of one stored deflate block.
+const STORED_BLOCK: usize = 65_535;
+
+/// Write `rgb`, `width * height` pixels of three bytes in rows top to bottom,
+/// as a PNG.
+pub fn write_rgb_png<W: Write>(
+    mut writer: W,
+    width: u32,
+    height: u32,
+    rgb: &[u8],
+) -> io::Result<()> {
+    let row_len = width as usize * 3;
+    if width == 0 || height == 0 || rgb.len() != row_len * height as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes do not make a {width}x{height} RGB image",
+                rgb.len()
+            ),
+        ));
+    }
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // Bit depth 8, colour type 2 (RGB), default compression, filter and no
+    // interlacing.
+    header.extend([8, 2, 0, 0, 0]);
+    write_chunk(&mut writer, b"IHDR", &header)?;
+
+    // Every scanline starts with filter type 0 (none).
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgb.chunks(row_len) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = Vec::with_capacity(raw.len() + raw.len() / STORED_BLOCK * 5 + 11);
+    zlib.extend([0x78, 0x01]);
+    let blocks = raw.len().div_ceil(STORED_BLOCK);
+    for (i, block) in raw.chunks(STORED_BLOCK).enumerate() {
+        let len = block.len() as u16;
+        zlib.push(u8::from(i + 1 == blocks));
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+    write_chunk(&mut writer, b"IDAT", &zlib)?;
+    write_chunk(&mut writer, b"IEND", &[])?;
+    writer.flush()
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = !crc32_update(crc32_update(u32::MAX, kind), data);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// CRC-32 (polynomial 0xEDB88320) without the final inversion.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = CRC_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` may overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
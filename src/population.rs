@@ -0,0 +1,337 @@
+//! Spike counts of groups of neurons per time bin, kept while a run goes.
+//!
+//! `PopulationRecorder` is a `StepObserver`; it stores one count per group and
+//! bin, never individual spikes. Bins follow the step index of each run, so
+//! observing several runs with one recorder sums them trial by trial.
+//!
+//! `Network::run_simulation_by_group` records one curve per neuron type and
+//! one per region alongside a run, as `GroupActivity`;
+//! `Network::run_group_curves` records the curves without the run's raster.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use crate::error::FlymindError;
+use crate::grouped::GroupBy;
+use crate::network::{Network, NeuronType, Region};
+use crate::select::Selection;
+use crate::simulation::{SimulationResult, StepInfo, StepObserver};
+
+/// What a curve of `PopulationRecorder::series` holds per bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Aggregate {
+    /// Spikes of the whole group.
+    Total,
+    /// Mean rate per member, as `PopulationRecorder::rates`.
+    #[default]
+    Mean,
+}
+
+/// Per-bin curves of a run by neuron type and by region, from
+/// `Network::run_simulation_by_group`. Types and regions without neurons
+/// have no curve.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupActivity {
+    pub by_type: HashMap<NeuronType, Vec<f64>>,
+    pub by_region: HashMap<Region, Vec<f64>>,
+}
+
+/// Records per-group spike counts during a run; see the module documentation.
+#[derive(Debug, Clone)]
+pub struct PopulationRecorder {
+    labels: Vec<String>,
+    /// Neuron ids of each group.
+    members: Vec<Vec<usize>>,
+    bin_steps: usize,
+    dt: f64,
+    /// `counts[bin][group]`.
+    counts: Vec<Vec<usize>>,
+    /// Steps observed in each bin, over every run.
+    observed: Vec<usize>,
+}
+
+impl PopulationRecorder {
+    /// Group `network`'s neurons by `group_by`, counting in bins of
+    /// `bin_steps` steps (at least one). Built-in groups without members, such
+    /// as `GroupBy::Region`'s `unknown` in a fully annotated network, are left
+    /// out.
+    pub fn new(network: &Network, group_by: &GroupBy, bin_steps: usize) -> Self {
+        let (labels, group) = group_by.assign(network);
+        let mut members = vec![Vec::new(); labels.len()];
+        for (id, &g) in group.iter().enumerate() {
+            members[g].push(id);
+        }
+        let (labels, members) = labels
+            .into_iter()
+            .zip(members)
+            .filter(|(_, ids)| !ids.is_empty())
+            .unzip();
+        Self::with_members(labels, members, bin_steps)
+    }
+
+    /// One group per `(label, selection)`, e.g. `("head sensory",
+    /// "region:head & type:sensory")`; groups may overlap. A selection that
+    /// matches no neuron is an `EmptyGroup` error.
+    pub fn from_selections(
+        network: &Network,
+        groups: &[(&str, Selection)],
+        bin_steps: usize,
+    ) -> Result<Self, FlymindError> {
+        let mut labels = Vec::new();
+        let mut members = Vec::new();
+        for (label, selection) in groups {
+            let ids = network.select(selection);
+            if ids.is_empty() {
+                return Err(FlymindError::EmptyGroup {
+                    label: label.to_string(),
+                });
+            }
+            labels.push(label.to_string());
+            members.push(ids);
+        }
+        Ok(Self::with_members(labels, members, bin_steps))
+    }
+
+    fn with_members(labels: Vec<String>, members: Vec<Vec<usize>>, bin_steps: usize) -> Self {
+        Self {
+            labels,
+            members,
+            bin_steps: bin_steps.max(1),
+            dt: 0.0,
+            counts: Vec::new(),
+            observed: Vec::new(),
+        }
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn members(&self, group: usize) -> &[usize] {
+        &self.members[group]
+    }
+
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Start time of each bin, in the run's time unit.
+    pub fn bin_times(&self) -> Vec<f64> {
+        (0..self.bins())
+            .map(|bin| (bin * self.bin_steps) as f64 * self.dt)
+            .collect()
+    }
+
+    /// Spikes of each group in each bin, `[bin][group]`.
+    pub fn counts(&self) -> &[Vec<usize>] {
+        &self.counts
+    }
+
+    /// Mean rate per member of each group in each bin, `[bin][group]`, in
+    /// spikes per unit time; a partly observed last bin is divided by the
+    /// steps it saw.
+    pub fn rates(&self) -> Vec<Vec<f64>> {
+        self.counts
+            .iter()
+            .zip(&self.observed)
+            .map(|(counts, &steps)| {
+                let duration = steps as f64 * self.dt;
+                counts
+                    .iter()
+                    .zip(&self.members)
+                    .map(|(&count, ids)| count as f64 / (ids.len() as f64 * duration))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Spikes of `group` over the whole recording.
+    pub fn total_spikes(&self, group: usize) -> usize {
+        self.counts.iter().map(|counts| counts[group]).sum()
+    }
+
+    /// Start time of the bin in which `group` fired most, the earliest on a
+    /// tie; `None` if it never fired.
+    pub fn peak_time(&self, group: usize) -> Option<f64> {
+        let (bin, _) = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, counts)| counts[group] > 0)
+            .max_by(|(a, x), (b, y)| x[group].cmp(&y[group]).then(b.cmp(a)))?;
+        Some((bin * self.bin_steps) as f64 * self.dt)
+    }
+
+    /// One curve per group, keyed by label, one value per bin.
+    pub fn series(&self, aggregate: Aggregate) -> HashMap<String, Vec<f64>> {
+        let values: Vec<Vec<f64>> = match aggregate {
+            Aggregate::Total => self
+                .counts
+                .iter()
+                .map(|counts| counts.iter().map(|&count| count as f64).collect())
+                .collect(),
+            Aggregate::Mean => self.rates(),
+        };
+        self.labels
+            .iter()
+            .enumerate()
+            .map(|(group, label)| (label.clone(), values.iter().map(|bin| bin[group]).collect()))
+            .collect()
+    }
+
+    /// `series` of the groups labelled as neuron types, as `GroupBy::NeuronType`
+    /// labels them; other groups are left out.
+    pub fn type_series(&self, aggregate: Aggregate) -> HashMap<NeuronType, Vec<f64>> {
+        self.series(aggregate)
+            .into_iter()
+            .filter_map(|(label, curve)| Some((NeuronType::from_label(&label)?, curve)))
+            .collect()
+    }
+
+    /// `series` of the groups labelled as regions, as `GroupBy::Region` labels
+    /// them; other groups are left out.
+    pub fn region_series(&self, aggregate: Aggregate) -> HashMap<Region, Vec<f64>> {
+        self.series(aggregate)
+            .into_iter()
+            .filter_map(|(label, curve)| Some((Region::from_label(&label)?, curve)))
+            .collect()
+    }
+
+    /// Write `rates` as a `time` column (bin start) and one column per group.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), FlymindError> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        let mut header = vec!["time".to_string()];
+        header.extend(self.labels.iter().cloned());
+        wtr.write_record(&header)?;
+        for (time, rates) in self.bin_times().iter().zip(self.rates()) {
+            let mut record = vec![time.to_string()];
+            record.extend(rates.iter().map(|rate| rate.to_string()));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl StepObserver for PopulationRecorder {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        self.dt = info.dt;
+        let bin = info.step / self.bin_steps;
+        if self.counts.len() <= bin {
+            self.counts.resize(bin + 1, vec![0; self.labels.len()]);
+            self.observed.resize(bin + 1, 0);
+        }
+        self.observed[bin] += 1;
+        for (count, ids) in self.counts[bin].iter_mut().zip(&self.members) {
+            *count += ids
+                .iter()
+                .filter(|&&id| network.neurons.get(id).is_some_and(|n| n.just_fired))
+                .count();
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl Network {
+    /// `run_simulation`, also recording per-type and per-region curves in
+    /// bins of `bin_steps` steps (at least one).
+    pub fn run_simulation_by_group(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        bin_steps: usize,
+        aggregate: Aggregate,
+    ) -> (SimulationResult, GroupActivity) {
+        let result = self.empty_result(steps, dt, &[], 1);
+        self.run_grouped(result, bin_steps, aggregate)
+    }
+
+    /// The curves of `run_simulation_by_group` alone. No spike trains are
+    /// kept, so memory does not grow with the spike count.
+    pub fn run_group_curves(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        bin_steps: usize,
+        aggregate: Aggregate,
+    ) -> GroupActivity {
+        let mut result = self.empty_result(steps, dt, &[], 1);
+        result.spike_trains.clear();
+        self.run_grouped(result, bin_steps, aggregate).1
+    }
+
+    fn run_grouped(
+        &mut self,
+        result: SimulationResult,
+        bin_steps: usize,
+        aggregate: Aggregate,
+    ) -> (SimulationResult, GroupActivity) {
+        let mut by_type = PopulationRecorder::new(self, &GroupBy::NeuronType, bin_steps);
+        let mut by_region = PopulationRecorder::new(self, &GroupBy::Region, bin_steps);
+        let mut observer = |info: &StepInfo, network: &Network| {
+            let _ = by_type.observe(info, network);
+            by_region.observe(info, network)
+        };
+        let result = self.run_into(result, &mut observer, &mut |_| {});
+        let activity = GroupActivity {
+            by_type: by_type.type_series(aggregate),
+            by_region: by_region.region_series(aggregate),
+        };
+        (result, activity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Stimulus;
+
+    fn groups() -> Vec<(&'static str, Selection)> {
+        [
+            ("head sensory", "region:head & type:sensory"),
+            ("motor", "type:motor"),
+        ]
+        .into_iter()
+        .map(|(label, expr)| (label, expr.parse().unwrap()))
+        .collect()
+    }
+
+    #[test]
+    fn head_sensory_activity_peaks_before_motor() {
+        let mut network = Network::example_touch_circuit();
+        for name in ["ASHL", "ASHR"] {
+            let id = network.id_of(name).unwrap();
+            network.stimuli.push(Stimulus::new(id, 100, 200, 2.0));
+        }
+        let mut recorder = PopulationRecorder::from_selections(&network, &groups(), 20).unwrap();
+        network.run_simulation_observed(1500, 0.1, &[], 1, &mut recorder);
+        assert_eq!(recorder.members(0).len(), 2);
+        assert!(recorder.total_spikes(1) > 0);
+        let sensory = recorder.peak_time(0).unwrap();
+        let motor = recorder.peak_time(1).unwrap();
+        assert!(sensory < motor, "sensory {sensory} motor {motor}");
+    }
+
+    #[test]
+    fn empty_selections_are_rejected() {
+        let network = Network::example_touch_circuit();
+        let mut groups = groups();
+        groups.push(("tail motor", "region:tail & type:motor".parse().unwrap()));
+        let err = PopulationRecorder::from_selections(&network, &groups, 10).unwrap_err();
+        assert!(matches!(err, FlymindError::EmptyGroup { label } if label == "tail motor"));
+    }
+
+    #[test]
+    fn curves_alone_match_the_full_run() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 200, 0.5));
+        let (result, full) = network.run_simulation_by_group(600, 0.1, 25, Aggregate::Total);
+        assert!(result.spike_trains.iter().any(|train| !train.is_empty()));
+        network.reset_state();
+        let curves = network.run_group_curves(600, 0.1, 25, Aggregate::Total);
+        assert_eq!(curves, full);
+        assert!(curves.by_type.values().flatten().sum::<f64>() > 0.0);
+    }
+}
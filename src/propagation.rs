@@ -0,0 +1,259 @@
+//! Event-driven stepping: updating only the neurons a step can change.
+//!
+//! A neuron's step is a function of its own state and its input alone, so a
+//! neuron whose last step left its state as it was, and whose input cannot
+//! have changed since, would step to the same state again. Under
+//! `Propagation::EventDriven`, runs keep a `Wavefront` of the neurons that
+//! changed on the last step; the next step updates only those, the neurons
+//! they drive through chemical sends, gap junctions and extrasynaptic edges
+//! (found through `outgoing_map`), and neurons whose stimulus and bias
+//! current add up differently from the last step's: where a stimulus starts
+//! or ends, every step of a shaped one, and wherever a run's `before_step`
+//! changed them. Every other neuron is left untouched, which gives the dense
+//! step's output bit for bit.
+//!
+//! Each step is a pure function only without noise or plasticity, and the
+//! `Inputs` event log reports every neuron, so networks with noise, `stdp` or
+//! that log set to `Inputs` or above step densely whatever the mode. So do
+//! networks under `SynapticInput::Rate`, whose rate estimates decay every step.
+//! Release failures are drawn as in the dense step, from every send.
+//!
+//! Changing neuron state or connections from outside `update_step` in the
+//! middle of a run is not seen; between runs it is, as each run starts with
+//! every neuron active.
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::EventVerbosity;
+use crate::network::{Network, Neuron, SynapseType};
+use crate::simulation::SynapticInput;
+
+/// How `update_step` picks the neurons it updates within a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Propagation {
+    /// Every neuron and every connection, every step.
+    #[default]
+    Dense,
+    /// Only the neurons a step can change; see the module documentation.
+    #[serde(rename = "event")]
+    EventDriven,
+}
+
+/// What an event-driven run carries from step to step.
+#[derive(Debug, Clone)]
+pub(crate) struct Wavefront {
+    /// Neurons each neuron drives, ascending.
+    targets: Vec<Vec<usize>>,
+    /// Neurons to update on the next step; `None` before the first, when all
+    /// are.
+    next: Option<Vec<bool>>,
+    /// Stimulus and bias current into each neuron on the last step, bitwise.
+    external: Vec<u64>,
+}
+
+/// The part of a neuron's state a step reads or writes, bitwise.
+pub(crate) fn dynamic_state(neuron: &Neuron) -> [u64; 8] {
+    [
+        neuron.membrane_potential.to_bits(),
+        u64::from(neuron.just_fired),
+        neuron.refractory_remaining as u64,
+        neuron.rapid_spikes as u64,
+        u64::from(neuron.just_blocked),
+        neuron.pre_trace.to_bits(),
+        neuron.post_trace.to_bits(),
+        neuron.modulation.to_bits(),
+    ]
+}
+
+impl Network {
+    /// A wavefront for a run starting now, or `None` when the run steps
+    /// densely.
+    pub(crate) fn start_wavefront(&self) -> Option<Wavefront> {
+        if self.propagation != Propagation::EventDriven {
+            return None;
+        }
+        let n = self.neurons.len();
+        let mut targets = vec![Vec::new(); n];
+        for (id, driven) in targets.iter_mut().enumerate() {
+            for &index in self.outgoing_map.get(&id).map(Vec::as_slice).unwrap_or(&[]) {
+                let conn = &self.connections[index];
+                let drives = match conn.synapse_type {
+                    SynapseType::ChemicalSend(_)
+                    | SynapseType::GapJunction
+                    | SynapseType::Extrasynaptic(_) => true,
+                    SynapseType::ChemicalReceive(_) | SynapseType::NMJ => false,
+                };
+                if drives && conn.to_id < n {
+                    driven.push(conn.to_id);
+                }
+            }
+        }
+        for list in &mut targets {
+            list.sort_unstable();
+            list.dedup();
+        }
+        Some(Wavefront {
+            targets,
+            next: None,
+            external: vec![0.0f64.to_bits(); n],
+        })
+    }
+
+    /// Whether this step may skip neurons: the run keeps a wavefront and
+    /// every step is a pure function of state and input.
+    pub(crate) fn steps_by_events(&self) -> bool {
+        self.wavefront.is_some()
+            && self.stdp.is_none()
+            && self.synaptic_input == SynapticInput::Spikes
+            && self.event_verbosity < EventVerbosity::Inputs
+            && self.lif.noise_std <= 0.0
+            && self
+                .lif_overrides
+                .values()
+                .all(|params| params.noise_std <= 0.0)
+    }
+
+    /// The neurons this step updates, `None` for all of them: those queued by
+    /// the last step and those whose external current changed since.
+    pub(crate) fn active_neurons(&mut self) -> Option<Vec<bool>> {
+        let n = self.neurons.len();
+        let mut external = vec![0.0f64; n];
+        for stim in &self.stimuli {
+            if stim.neuron < n {
+                external[stim.neuron] += stim.current_at(self.current_step, &self.stimulus_traces);
+            }
+        }
+        for (&id, &bias) in &self.bias_currents {
+            if id < n {
+                external[id] += bias;
+            }
+        }
+        let wavefront = self.wavefront.as_mut()?;
+        let mut active = wavefront.next.take();
+        for (id, (current, last)) in external.iter().zip(&mut wavefront.external).enumerate() {
+            if current.to_bits() != *last {
+                *last = current.to_bits();
+                if let Some(active) = active.as_mut() {
+                    active[id] = true;
+                }
+            }
+        }
+        active
+    }
+
+    /// Update every neuron on the next step, as after the parameters of the
+    /// step itself have changed.
+    pub(crate) fn restart_wavefront(&mut self) {
+        if let Some(wavefront) = self.wavefront.as_mut() {
+            wavefront.next = None;
+        }
+    }
+
+    /// Queue the neurons in `changed`, and those they drive, for the next step.
+    pub(crate) fn advance_wavefront(&mut self, changed: &[usize]) {
+        let n = self.neurons.len();
+        let Some(wavefront) = self.wavefront.as_mut() else {
+            return;
+        };
+        let mut next = vec![false; n];
+        for &id in changed {
+            next[id] = true;
+            for &target in &wavefront.targets[id] {
+                next[target] = true;
+            }
+        }
+        wavefront.next = Some(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use super::*;
+    use crate::network::ChemicalSubtype;
+    use crate::simulation::{SimulationResult, StepInfo, Stimulus, StimulusShape};
+
+    fn run(network: &mut Network, propagation: Propagation, steps: usize) -> SimulationResult {
+        network.propagation = propagation;
+        network.reset_state();
+        let watch: Vec<usize> = (0..network.neurons.len()).collect();
+        network.run_simulation_recording(steps, 0.1, &watch)
+    }
+
+    fn assert_same(dense: &SimulationResult, event: &SimulationResult) {
+        for (a, b) in dense.spike_trains.iter().zip(&event.spike_trains) {
+            assert_eq!(a.to_vec(), b.to_vec());
+        }
+        for (id, trace) in &dense.potentials {
+            let bits = |trace: &[f64]| trace.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(trace), bits(&event.potentials[id]), "neuron {id}");
+        }
+    }
+
+    #[test]
+    fn event_driven_runs_match_dense_ones() {
+        let mut network = Network::example_touch_circuit();
+        let (alml, plml, avm) = (
+            network.id_of("ALML").unwrap(),
+            network.id_of("PLML").unwrap(),
+            network.id_of("AVM").unwrap(),
+        );
+        network.stimuli.push(Stimulus::new(alml, 10, 300, 2.0));
+        network.stimuli.push(Stimulus {
+            shape: StimulusShape::Pulse {
+                period_steps: 50,
+                width_steps: 5,
+            },
+            ..Stimulus::new(plml, 200, 400, 3.0)
+        });
+        network.stimuli.push(Stimulus {
+            shape: StimulusShape::Ramp { end_amplitude: 0.0 },
+            ..Stimulus::new(avm, 500, 200, 1.5)
+        });
+        network.bias_currents.insert(alml, 0.01);
+        let dense = run(&mut network, Propagation::Dense, 1000);
+        let event = run(&mut network, Propagation::EventDriven, 1000);
+        assert!(dense.total_spikes() > 0);
+        assert_same(&dense, &event);
+    }
+
+    #[test]
+    fn a_stimulus_removed_mid_run_is_seen() {
+        let mut network = Network::new();
+        let a = network.get_or_create_neuron("A");
+        let b = network.get_or_create_neuron("B");
+        network.add_connection(
+            a,
+            b,
+            SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+            1.0,
+        );
+        let runs: Vec<SimulationResult> = [Propagation::Dense, Propagation::EventDriven]
+            .into_iter()
+            .map(|propagation| {
+                network.propagation = propagation;
+                network.reset_state();
+                network.stimuli = vec![Stimulus::new(a, 0, 5000, 0.05)];
+                network.run_simulation_driven(
+                    4000,
+                    0.5,
+                    &[a],
+                    1,
+                    &mut |_: &StepInfo, _: &Network| ControlFlow::Continue(()),
+                    &mut |network| {
+                        if network.current_step == 3000 {
+                            network.stimuli.clear();
+                        }
+                    },
+                )
+            })
+            .collect();
+        // The potential had settled bit for bit before the stimulus went.
+        let trace = &runs[0].potentials[&a];
+        assert_eq!(trace[2998].to_bits(), trace[2999].to_bits());
+        assert!(trace[3999] < trace[2999]);
+        assert_same(&runs[0], &runs[1]);
+    }
+}
@@ -0,0 +1,467 @@
+//! Firing-rate dynamics over the connectome.
+//!
+//! Each neuron carries a continuous activation `x` updated by the map
+//! `x' = (1 - leak) * x + leak * f(W x + input)`, where `W` is built the same way
+//! the spiking model couples neurons: chemical sends add `±chemical_gain * weight`
+//! from presynaptic to postsynaptic neuron (negative when inhibitory), and gap
+//! junctions add a diffusive `gap_gain * weight * (x_from - x_to)` term.
+//! `ChemicalReceive`, modulatory and NMJ rows do not couple neurons.
+//!
+//! With `Activation::Linear` the map is `x' = (1 - leak) * x + leak * (W x +
+//! input)`, whose fixed points solve `(I - W) x = input` whatever the leak;
+//! `RateModel::linear_steady_state` solves for it directly.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::{Network, SynapseType};
+use crate::walk::solve;
+
+/// Most squarings `spectral_radius_bound` makes.
+const SPECTRAL_SQUARINGS: usize = 16;
+
+/// Transfer function applied to a neuron's summed input.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    Linear,
+    /// `1 / (1 + exp(-gain * (u - threshold)))`.
+    Sigmoid {
+        gain: f64,
+        threshold: f64,
+    },
+    Tanh,
+}
+
+impl Activation {
+    pub fn apply(&self, u: f64) -> f64 {
+        match *self {
+            Activation::Linear => u,
+            Activation::Sigmoid { gain, threshold } => {
+                1.0 / (1.0 + (-gain * (u - threshold)).exp())
+            }
+            Activation::Tanh => u.tanh(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateParams {
+    pub activation: Activation,
+    pub chemical_gain: f64,
+    pub gap_gain: f64,
+    /// Fraction of the way each step moves toward `f(W x + input)`; 1 makes the
+    /// update a pure map, smaller values integrate with `leak = dt / tau`.
+    pub leak: f64,
+}
+
+impl Default for RateParams {
+    fn default() -> Self {
+        Self {
+            activation: Activation::Sigmoid {
+                gain: 4.0,
+                threshold: 0.5,
+            },
+            chemical_gain: 0.1,
+            gap_gain: 0.01,
+            leak: 1.0,
+        }
+    }
+}
+
+/// The coupling matrix of a network, fixed at construction.
+#[derive(Debug, Clone)]
+pub struct RateModel {
+    pub params: RateParams,
+    /// `inputs[to]` lists `(from, coefficient)`, merged per source and sorted by id.
+    inputs: Vec<Vec<(usize, f64)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttractorOptions {
+    /// Largest per-neuron difference at which two states count as equal.
+    pub tolerance: f64,
+    pub max_iterations: usize,
+    /// Longest limit cycle looked for.
+    pub max_period: usize,
+}
+
+impl Default for AttractorOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-9,
+            max_iterations: 10_000,
+            max_period: 64,
+        }
+    }
+}
+
+/// What `RateModel::find_attractor` settled into.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attractor {
+    FixedPoint {
+        state: Vec<f64>,
+        iterations: usize,
+    },
+    /// `states` holds one full period, in visiting order.
+    Cycle {
+        period: usize,
+        states: Vec<Vec<f64>>,
+        iterations: usize,
+    },
+    /// Neither within `max_iterations`; `state` is the last one reached.
+    NotConverged {
+        state: Vec<f64>,
+        iterations: usize,
+    },
+}
+
+impl RateModel {
+    pub fn from_network(network: &Network, params: RateParams) -> Self {
+        let mut inputs: Vec<Vec<(usize, f64)>> = vec![Vec::new(); network.neurons.len()];
+        for conn in network
+            .connections
+            .iter()
+            .filter(|conn| network.counts(conn))
+        {
+            match conn.synapse_type {
+                SynapseType::ChemicalSend(subtype) if !subtype.is_modulatory() => {
+                    inputs[conn.to_id].push((
+                        conn.from_id,
+                        subtype.sign() * params.chemical_gain * conn.weight,
+                    ));
+                }
+                SynapseType::GapJunction => {
+                    let g = params.gap_gain * conn.weight;
+                    inputs[conn.to_id].push((conn.from_id, g));
+                    inputs[conn.to_id].push((conn.to_id, -g));
+                }
+                SynapseType::ChemicalSend(_)
+                | SynapseType::ChemicalReceive(_)
+                | SynapseType::NMJ
+                | SynapseType::Extrasynaptic(_) => {}
+            }
+        }
+        for row in &mut inputs {
+            row.sort_unstable_by_key(|&(from, _)| from);
+            row.dedup_by(|next, kept| {
+                if next.0 == kept.0 {
+                    kept.1 += next.1;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+        Self { params, inputs }
+    }
+
+    pub fn neuron_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// One application of the update map. `input` is indexed by neuron id;
+    /// missing entries count as zero.
+    pub fn step(&self, state: &[f64], input: &[f64]) -> Vec<f64> {
+        let leak = self.params.leak;
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let drive: f64 = row.iter().map(|&(from, w)| w * state[from]).sum::<f64>()
+                    + input.get(i).copied().unwrap_or(0.0);
+                (1.0 - leak) * state[i] + leak * self.params.activation.apply(drive)
+            })
+            .collect()
+    }
+
+    /// Iterate from `initial` under constant `input` until the state repeats.
+    ///
+    /// A state within `tolerance` of the previous one is a fixed point; one
+    /// within `tolerance` of the state `p` steps back (`2 <= p <= max_period`),
+    /// and not of any closer one, is a cycle of period `p`.
+    pub fn find_attractor(
+        &self,
+        initial: &[f64],
+        input: &[f64],
+        options: &AttractorOptions,
+    ) -> Attractor {
+        let max_period = options.max_period.max(1);
+        let mut history: Vec<Vec<f64>> = vec![initial.to_vec()];
+        for iteration in 1..=options.max_iterations {
+            let next = self.step(history.last().expect("history is never empty"), input);
+            let period = (1..=max_period.min(history.len()))
+                .find(|&p| max_difference(&next, &history[history.len() - p]) <= options.tolerance);
+            if let Some(period) = period {
+                return if period == 1 {
+                    Attractor::FixedPoint {
+                        state: next,
+                        iterations: iteration,
+                    }
+                } else {
+                    let start = history.len() - period + 1;
+                    let mut states = history.split_off(start);
+                    states.push(next);
+                    Attractor::Cycle {
+                        period,
+                        states,
+                        iterations: iteration,
+                    }
+                };
+            }
+            if history.len() == max_period {
+                history.remove(0);
+            }
+            history.push(next);
+        }
+        Attractor::NotConverged {
+            state: history.pop().expect("history is never empty"),
+            iterations: options.max_iterations,
+        }
+    }
+}
+
+impl RateModel {
+    /// The coupling matrix `W`, `[to][from]`.
+    pub fn coupling_matrix(&self) -> Vec<Vec<f64>> {
+        let n = self.inputs.len();
+        let mut matrix = vec![vec![0.0; n]; n];
+        for (to, row) in self.inputs.iter().enumerate() {
+            for &(from, w) in row {
+                matrix[to][from] = w;
+            }
+        }
+        matrix
+    }
+
+    /// The fixed point of the linear model under constant `input` by neuron
+    /// id (missing ids zero, ids past the last neuron ignored):
+    /// `x = (I - W)^-1 input`, from
+    /// `coupling_matrix`, whatever `params.activation` is. `None` when the
+    /// linear map is not stable, its spectral radius (estimated from above
+    /// by `spectral_radius_bound`) being 1 or more, so that iterating would
+    /// not settle there; or when `I - W` is singular.
+    ///
+    /// A model with a nonlinear activation is only near this fixed point
+    /// while its drive stays where the activation is roughly linear with
+    /// slope 1, which a sigmoid's is nowhere; scale by the slope first for
+    /// a linearization around an operating point.
+    pub fn linear_steady_state(&self, input: &HashMap<usize, f64>) -> Option<Vec<f64>> {
+        let n = self.inputs.len();
+        let coupling = self.coupling_matrix();
+        if spectral_radius_bound(&coupling) >= 1.0 {
+            return None;
+        }
+        let mut system = coupling;
+        for (i, row) in system.iter_mut().enumerate() {
+            for w in row.iter_mut() {
+                *w = -*w;
+            }
+            row[i] += 1.0;
+        }
+        let mut b = vec![0.0; n];
+        for (&id, &value) in input {
+            if let Some(slot) = b.get_mut(id) {
+                *slot += value;
+            }
+        }
+        solve(system, b)
+    }
+}
+
+impl Network {
+    pub fn rate_model(&self, params: RateParams) -> RateModel {
+        RateModel::from_network(self, params)
+    }
+
+    /// Steady state of the linear rate model with the default gains, under
+    /// constant `input` by neuron id: `RateModel::linear_steady_state` with
+    /// `Activation::Linear`. `W` is the signed chemical matrix of
+    /// `to_signed_adjacency_matrix`, transposed and scaled by
+    /// `RateParams::chemical_gain`, plus the gap junctions' diffusive
+    /// coupling. This holds only as far as neurons integrate linearly with no
+    /// threshold or saturation, so rates may come out negative; `None` when
+    /// the linear system is unstable.
+    pub fn linear_steady_state(&self, input: &HashMap<usize, f64>) -> Option<Vec<f64>> {
+        let params = RateParams {
+            activation: Activation::Linear,
+            ..RateParams::default()
+        };
+        self.rate_model(params).linear_steady_state(input)
+    }
+}
+
+/// An upper bound on the spectral radius of `matrix`: the least
+/// `||M^k||^(1/k)`, in the infinity norm, for `k` doubling until the bound is
+/// below 1, which settles stability, or up to `2^SPECTRAL_SQUARINGS`, by when
+/// it is within about 1e-4 relative of the radius. Powers are rescaled as
+/// they are squared so they neither overflow nor underflow.
+pub(crate) fn spectral_radius_bound(matrix: &[Vec<f64>]) -> f64 {
+    let norm = |m: &[Vec<f64>]| {
+        m.iter()
+            .map(|row| row.iter().map(|w| w.abs()).sum::<f64>())
+            .fold(0.0, f64::max)
+    };
+    let n = matrix.len();
+    // `power` is M^k / exp(log_scale).
+    let mut power = matrix.to_vec();
+    let mut log_scale = 0.0;
+    let mut k = 1.0;
+    let mut log_bound = f64::INFINITY;
+    for squaring in 0..=SPECTRAL_SQUARINGS {
+        let current = norm(&power);
+        if current == 0.0 {
+            return 0.0;
+        }
+        log_bound = log_bound.min((log_scale + current.ln()) / k);
+        if log_bound < 0.0 || squaring == SPECTRAL_SQUARINGS {
+            break;
+        }
+        for row in &mut power {
+            for w in row.iter_mut() {
+                *w /= current;
+            }
+        }
+        log_scale = 2.0 * (log_scale + current.ln());
+        k *= 2.0;
+        let mut squared = vec![vec![0.0; n]; n];
+        for (out, row) in squared.iter_mut().zip(&power) {
+            for (&a, next) in row.iter().zip(&power) {
+                if a != 0.0 {
+                    for (o, &b) in out.iter_mut().zip(next) {
+                        *o += a * b;
+                    }
+                }
+            }
+        }
+        power = squared;
+    }
+    log_bound.exp()
+}
+
+fn max_difference(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, NeuronType, Region};
+
+    #[test]
+    fn the_steady_state_is_where_iteration_settles() {
+        let network = Network::example_touch_circuit();
+        let by_id: HashMap<usize, f64> = [("ALML", 1.0), ("PLMR", 0.5), ("AVM", -0.25)]
+            .into_iter()
+            .map(|(name, value)| (network.id_of(name).unwrap(), value))
+            .collect();
+        let mut input = vec![0.0; network.neurons.len()];
+        for (&id, &value) in &by_id {
+            input[id] = value;
+        }
+        // Weak enough coupling for the touch circuit to be stable.
+        let model = network.rate_model(RateParams {
+            activation: Activation::Linear,
+            chemical_gain: 0.01,
+            leak: 0.5,
+            ..RateParams::default()
+        });
+        let solved = model.linear_steady_state(&by_id).unwrap();
+
+        let start = vec![0.0; network.neurons.len()];
+        let options = AttractorOptions {
+            tolerance: 1e-13,
+            ..AttractorOptions::default()
+        };
+        let Attractor::FixedPoint { state, .. } = model.find_attractor(&start, &input, &options)
+        else {
+            panic!("the linear model did not settle");
+        };
+        assert!(max_difference(&solved, &state) < 1e-9);
+        assert!(solved.iter().any(|&x| x < 0.0));
+    }
+
+    #[test]
+    fn an_unstable_map_has_no_steady_state() {
+        let mut network = Network::new();
+        for name in ["A", "B"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        network.add_connection(0, 1, send, 20.0);
+        network.add_connection(1, 0, send, 20.0);
+        let input = HashMap::from([(0, 1.0)]);
+        assert_eq!(network.linear_steady_state(&input), None);
+
+        network.connections[1].weight = 2.0;
+        let state = network.linear_steady_state(&input).unwrap();
+        // x0 = 1 + 0.2 x1 and x1 = 2 x0.
+        assert!((state[0] - 1.0 / 0.6).abs() < 1e-12);
+        assert!((state[1] - 2.0 / 0.6).abs() < 1e-12);
+
+        // An id past the last neuron drives nothing.
+        let past_the_end = HashMap::from([(0, 1.0), (2, 5.0), (usize::MAX, 1.0)]);
+        assert_eq!(network.linear_steady_state(&past_the_end), Some(state));
+    }
+
+    #[test]
+    fn the_radius_bound_sees_past_the_norm() {
+        // Triangular, so the radius is the largest diagonal entry, though
+        // the first norm is 10.5.
+        let bound = spectral_radius_bound(&[vec![0.5, 10.0], vec![0.0, 0.25]]);
+        assert!((0.5..1.0).contains(&bound), "{bound}");
+        assert_eq!(
+            spectral_radius_bound(&[vec![0.0, 3.0], vec![0.0, 0.0]]),
+            0.0
+        );
+        let rotation = spectral_radius_bound(&[vec![0.0, -1.0], vec![1.0, 0.0]]);
+        assert!((rotation - 1.0).abs() < 1e-9, "{rotation}");
+    }
+
+    #[test]
+    fn rates_stay_in_range_whatever_the_in_degree() {
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        for leaves in [1, 2, 8, 32, 128, 512] {
+            // Every leaf excites the hub, already well past saturation at one.
+            let names: Vec<String> = (0..=leaves).map(|i| format!("N{i}")).collect();
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let mut network = Network::with_neuron_roster(&names);
+            for leaf in 1..=leaves {
+                network.add_connection(leaf, 0, send, 5.0);
+            }
+            let input = vec![10.0; leaves + 1];
+            for (activation, range) in [
+                (
+                    Activation::Sigmoid {
+                        gain: 4.0,
+                        threshold: 0.5,
+                    },
+                    0.0..=1.0,
+                ),
+                (Activation::Tanh, -1.0..=1.0),
+            ] {
+                for leak in [1.0, 0.3] {
+                    let model = network.rate_model(RateParams {
+                        activation,
+                        chemical_gain: 1.0,
+                        leak,
+                        ..RateParams::default()
+                    });
+                    let mut state = vec![0.0; leaves + 1];
+                    for _ in 0..50 {
+                        state = model.step(&state, &input);
+                        assert!(
+                            state.iter().all(|x| range.contains(x)),
+                            "{activation:?}, leak {leak}, {leaves} leaves: {}",
+                            state[0]
+                        );
+                    }
+                    assert!(state[0] > 0.99 * range.end());
+                }
+            }
+        }
+    }
+}
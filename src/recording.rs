@@ -0,0 +1,323 @@
+//! Spikes and potentials kept while a run goes, for rasters and traces.
+//!
+//! `SpikeRecorder` and `VoltageRecorder` are `StepObserver`s. Each takes the
+//! names and other labels it needs from the network when it is made, so what
+//! it recorded can be exported or drawn (see `crate::plotting`) without the
+//! network at hand. Times are in the network's `lif.time_unit`; a spike on
+//! step `s` is at `s * dt`.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use crate::error::FlymindError;
+use crate::network::{Network, NeuronType};
+use crate::simulation::{SimulationResult, StepInfo, StepObserver, TimeUnit};
+
+/// Row order of a spike raster, in `SpikeRecorder::write_csv` and
+/// `plotting::raster_png`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RasterOrder {
+    #[default]
+    Id,
+    /// Anterior to posterior by `soma_position`, ties broken by id.
+    SomaPosition,
+    /// Sensory, interneuron, motor, other, by id within each type.
+    NeuronType,
+}
+
+/// Every spike of every neuron in a run.
+#[derive(Debug, Clone)]
+pub struct SpikeRecorder {
+    names: Vec<String>,
+    types: Vec<NeuronType>,
+    positions: Vec<f64>,
+    time_unit: TimeUnit,
+    dt: f64,
+    steps: usize,
+    /// Spike steps of each neuron by id, ascending.
+    spikes: Vec<Vec<usize>>,
+}
+
+impl SpikeRecorder {
+    /// An empty recording of `network`'s neurons.
+    pub fn new(network: &Network) -> Self {
+        Self {
+            names: network
+                .neurons
+                .iter()
+                .map(|n| n.name().to_string())
+                .collect(),
+            types: network.neurons.iter().map(|n| n.neuron_type).collect(),
+            positions: network.neurons.iter().map(|n| n.soma_position).collect(),
+            time_unit: network.lif.time_unit,
+            dt: 0.0,
+            steps: 0,
+            spikes: vec![Vec::new(); network.neurons.len()],
+        }
+    }
+
+    /// The spikes of a finished run on `network`.
+    pub fn from_result(network: &Network, result: &SimulationResult) -> Self {
+        let mut recorder = Self::new(network);
+        recorder.dt = result.dt;
+        recorder.steps = result.steps;
+        for (spikes, train) in recorder.spikes.iter_mut().zip(&result.spike_trains) {
+            spikes.extend(train.iter());
+        }
+        recorder
+    }
+
+    /// Neurons recorded, by id.
+    pub fn len(&self) -> usize {
+        self.spikes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spikes.is_empty()
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn neuron_type(&self, id: usize) -> NeuronType {
+        self.types[id]
+    }
+
+    /// Spike steps of neuron `id`, ascending; empty past the last neuron.
+    pub fn spikes(&self, id: usize) -> &[usize] {
+        self.spikes.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn total_spikes(&self) -> usize {
+        self.spikes.iter().map(Vec::len).sum()
+    }
+
+    /// Steps observed.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    pub fn time_unit(&self) -> TimeUnit {
+        self.time_unit
+    }
+
+    /// `steps * dt`.
+    pub fn duration(&self) -> f64 {
+        self.steps as f64 * self.dt
+    }
+
+    /// Neuron ids in raster order.
+    pub fn order(&self, order: RasterOrder) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.len()).collect();
+        match order {
+            RasterOrder::Id => {}
+            RasterOrder::SomaPosition => {
+                ids.sort_by(|&a, &b| self.positions[a].total_cmp(&self.positions[b]))
+            }
+            RasterOrder::NeuronType => ids.sort_by_key(|&id| self.types[id] as usize),
+        }
+        ids
+    }
+
+    /// Write one `neuron,time` row per spike, neurons in `order` and each
+    /// neuron's spikes in time order.
+    pub fn write_csv<W: Write>(&self, writer: W, order: RasterOrder) -> Result<(), FlymindError> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["neuron", "time"])?;
+        for id in self.order(order) {
+            for &step in &self.spikes[id] {
+                let time = step as f64 * self.dt;
+                wtr.write_record([self.names[id].as_str(), &time.to_string()])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl StepObserver for SpikeRecorder {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        self.dt = info.dt;
+        self.steps = self.steps.max(info.step + 1);
+        for (spikes, neuron) in self.spikes.iter_mut().zip(&network.neurons) {
+            if neuron.just_fired {
+                spikes.push(info.step);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Membrane potentials of chosen neurons, sampled every `stride` steps.
+#[derive(Debug, Clone)]
+pub struct VoltageRecorder {
+    ids: Vec<usize>,
+    names: Vec<String>,
+    stride: usize,
+    time_unit: TimeUnit,
+    dt: f64,
+    steps: usize,
+    /// Step after which each sample was taken.
+    sample_steps: Vec<usize>,
+    /// One trace per entry of `ids`, a value per sample.
+    traces: Vec<Vec<f64>>,
+}
+
+impl VoltageRecorder {
+    /// Watch `ids` of `network`, in the order given, sampling after every
+    /// `stride`-th step (at least one), the first sample following step 0.
+    /// Ids outside the network are ignored.
+    pub fn new(network: &Network, ids: &[usize], stride: usize) -> Self {
+        let ids: Vec<usize> = ids
+            .iter()
+            .copied()
+            .filter(|&id| id < network.neurons.len())
+            .collect();
+        Self {
+            names: ids
+                .iter()
+                .map(|&id| network.neurons[id].name().to_string())
+                .collect(),
+            traces: vec![Vec::new(); ids.len()],
+            ids,
+            stride: stride.max(1),
+            time_unit: network.lif.time_unit,
+            dt: 0.0,
+            steps: 0,
+            sample_steps: Vec::new(),
+        }
+    }
+
+    /// The potentials a finished run on `network` recorded for its watched
+    /// neurons, in id order.
+    pub fn from_result(network: &Network, result: &SimulationResult) -> Self {
+        let mut ids: Vec<usize> = result.potentials.keys().copied().collect();
+        ids.sort_unstable();
+        let mut recorder = Self::new(network, &ids, result.potential_stride);
+        recorder.dt = result.dt;
+        recorder.steps = result.steps;
+        for (trace, id) in recorder.traces.iter_mut().zip(&recorder.ids) {
+            trace.clone_from(&result.potentials[id]);
+        }
+        let samples = recorder.traces.iter().map(Vec::len).max().unwrap_or(0);
+        recorder.sample_steps = (0..samples).map(|k| k * recorder.stride).collect();
+        recorder
+    }
+
+    /// Watched neuron ids, in trace order.
+    pub fn ids(&self) -> &[usize] {
+        &self.ids
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Potentials of the `k`-th watched neuron.
+    pub fn trace(&self, k: usize) -> &[f64] {
+        &self.traces[k]
+    }
+
+    /// Time of each sample.
+    pub fn sample_times(&self) -> Vec<f64> {
+        self.sample_steps
+            .iter()
+            .map(|&step| step as f64 * self.dt)
+            .collect()
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn time_unit(&self) -> TimeUnit {
+        self.time_unit
+    }
+
+    /// `steps * dt`.
+    pub fn duration(&self) -> f64 {
+        self.steps as f64 * self.dt
+    }
+}
+
+impl StepObserver for VoltageRecorder {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        self.dt = info.dt;
+        self.steps = self.steps.max(info.step + 1);
+        if info.step.is_multiple_of(self.stride) {
+            self.sample_steps.push(info.step);
+            for (trace, &id) in self.traces.iter_mut().zip(&self.ids) {
+                trace.push(network.neurons[id].membrane_potential);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Stimulus;
+
+    #[test]
+    fn observed_spikes_match_the_result() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 200, 0.2));
+        let mut recorder = SpikeRecorder::new(&network);
+        let result = network.run_simulation_observed(300, 0.1, &[], 1, &mut recorder);
+        let replayed = SpikeRecorder::from_result(&network, &result);
+        assert!(recorder.total_spikes() > 0);
+        assert_eq!(recorder.steps(), 300);
+        for id in 0..network.neurons.len() {
+            assert_eq!(recorder.spikes(id), replayed.spikes(id));
+        }
+    }
+
+    #[test]
+    fn csv_rows_follow_the_raster_order() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 200, 0.2));
+        let mut recorder = SpikeRecorder::new(&network);
+        network.run_simulation_observed(300, 0.1, &[], 1, &mut recorder);
+        let order = recorder.order(RasterOrder::NeuronType);
+        assert!(order
+            .windows(2)
+            .all(|w| recorder.neuron_type(w[0]) as usize <= recorder.neuron_type(w[1]) as usize));
+
+        let mut out = Vec::new();
+        recorder
+            .write_csv(&mut out, RasterOrder::NeuronType)
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let names: Vec<&str> = text
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(names.len(), recorder.total_spikes());
+        let expected: Vec<&str> = order
+            .iter()
+            .flat_map(|&id| std::iter::repeat_n(recorder.name(id), recorder.spikes(id).len()))
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn voltage_samples_follow_the_stride() {
+        let mut network = Network::example_touch_circuit();
+        let ids = [0, 3, network.neurons.len()];
+        let mut recorder = VoltageRecorder::new(&network, &ids, 4);
+        network.run_simulation_observed(10, 0.5, &[], 1, &mut recorder);
+        assert_eq!(recorder.ids(), &[0, 3]);
+        assert_eq!(recorder.sample_times(), vec![0.0, 2.0, 4.0]);
+        assert_eq!(recorder.trace(1).len(), 3);
+        assert_eq!(recorder.duration(), 5.0);
+    }
+}
@@ -0,0 +1,718 @@
+//! A run's outputs in one directory, with what is needed to reproduce them.
+//!
+//! `SimulationResult::write_bundle` writes `spikes.csv` and, when they were
+//! recorded, `voltages.csv`, `events.jsonl`, `weights.csv` and
+//! `population.csv`, the resolved config as `config.toml`, and a
+//! `manifest.json` last. The manifest keeps the crate version, seed and wall
+//! time, the network's `structural_hash`, source path and neuron names, and
+//! the size and FNV-1a checksum of every other file.
+//!
+//! `SimulationResult::read_bundle` checks every listed file against the
+//! manifest before reading spikes, potentials and the config back, so a
+//! bundle that was edited or copied only in part fails to load. Files the
+//! manifest does not list are ignored, and so is a manifest naming a file
+//! outside the bundle directory. Spike and voltage files name neurons, so a
+//! network with two neurons of one name cannot be written as a bundle.
+//!
+//! A `SimulationBundle` is the single-file alternative for sharing a run: the
+//! network's hash and names, the config as TOML (parameters, stimuli, seed)
+//! and the spike raster in one JSON document. `SimulationBundle::replay`
+//! runs the config again and fails unless every spike comes out the same.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compare::Fnv1a;
+use crate::config::SimulationConfig;
+use crate::error::FlymindError;
+use crate::network::Network;
+use crate::population::PopulationRecorder;
+use crate::simulation::SimulationResult;
+use crate::spikes::{SpikeStorage, SpikeTrain};
+use crate::weight_history::WeightRecorder;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+const SPIKES_FILE: &str = "spikes.csv";
+const VOLTAGES_FILE: &str = "voltages.csv";
+const CONFIG_FILE: &str = "config.toml";
+
+/// What a bundle records besides the result: the network it ran on is
+/// required, the rest is written when given.
+#[derive(Clone, Copy)]
+pub struct BundleInputs<'a> {
+    network: &'a Network,
+    source: Option<&'a Path>,
+    config: Option<&'a SimulationConfig>,
+    seed: Option<u64>,
+    wall_time: Option<Duration>,
+    weights: Option<&'a WeightRecorder>,
+    population: Option<&'a PopulationRecorder>,
+}
+
+impl<'a> BundleInputs<'a> {
+    pub fn new(network: &'a Network) -> Self {
+        Self {
+            network,
+            source: None,
+            config: None,
+            seed: None,
+            wall_time: None,
+            weights: None,
+            population: None,
+        }
+    }
+
+    /// The file the network was loaded from.
+    pub fn source(mut self, path: &'a Path) -> Self {
+        self.source = Some(path);
+        self
+    }
+
+    /// The config the run used; its seed is recorded unless `seed` is given.
+    pub fn config(mut self, config: &'a SimulationConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// How long the run took.
+    pub fn wall_time(mut self, wall_time: Duration) -> Self {
+        self.wall_time = Some(wall_time);
+        self
+    }
+
+    pub fn weights(mut self, recorder: &'a WeightRecorder) -> Self {
+        self.weights = Some(recorder);
+        self
+    }
+
+    pub fn population(mut self, recorder: &'a PopulationRecorder) -> Self {
+        self.population = Some(recorder);
+        self
+    }
+}
+
+/// The contents of `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of this crate that wrote the bundle.
+    pub crate_version: String,
+    pub seed: Option<u64>,
+    pub wall_time_seconds: Option<f64>,
+    pub dt: f64,
+    pub steps: usize,
+    pub potential_stride: usize,
+    pub network: NetworkRecord,
+    /// Every file of the bundle but the manifest, in the order written.
+    pub files: Vec<BundleFile>,
+}
+
+/// The network a bundle's run used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkRecord {
+    /// `Network::structural_hash`, as 16 hex digits.
+    pub structural_hash: String,
+    pub source: Option<String>,
+    /// Neuron names in id order, which spike and voltage files are read back
+    /// against.
+    pub neurons: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// Path relative to the bundle directory.
+    pub name: String,
+    pub bytes: u64,
+    /// 64-bit FNV-1a of the contents, as 16 hex digits.
+    pub fnv1a: String,
+}
+
+/// A bundle read back by `SimulationResult::read_bundle`.
+#[derive(Debug, Clone)]
+pub struct LoadedBundle {
+    pub manifest: Manifest,
+    /// Spike trains and potentials as written; events, activity totals and
+    /// depolarization blocks are not kept.
+    pub result: SimulationResult,
+    pub config: Option<SimulationConfig>,
+}
+
+impl LoadedBundle {
+    /// Whether `network` has the structural hash the bundle's run recorded.
+    pub fn matches_network(&self, network: &Network) -> bool {
+        self.manifest.network.structural_hash == hex(network.structural_hash())
+    }
+}
+
+/// A run and how it was produced, in one file; see the module documentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationBundle {
+    /// Version of this crate that wrote the bundle.
+    pub crate_version: String,
+    pub network: NetworkRecord,
+    /// `SimulationConfig::to_toml_string` of the config the run used.
+    pub config: String,
+    /// The config's seed, which a replay takes from `config`.
+    pub seed: u64,
+    pub dt: f64,
+    pub steps: usize,
+    /// Steps at which each neuron fired, indexed by neuron id.
+    pub spikes: Vec<Vec<usize>>,
+}
+
+impl SimulationBundle {
+    /// The bundle of `result`, produced by `config` on `network` as it was
+    /// before the run (the hash covers weights, so record a plastic network
+    /// before running it).
+    pub fn new(
+        network: &Network,
+        config: &SimulationConfig,
+        result: &SimulationResult,
+    ) -> Result<Self, FlymindError> {
+        Ok(Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            network: NetworkRecord {
+                structural_hash: hex(network.structural_hash()),
+                source: None,
+                neurons: network
+                    .neurons
+                    .iter()
+                    .map(|n| n.name().to_string())
+                    .collect(),
+            },
+            config: config.to_toml_string()?,
+            seed: config.seed,
+            dt: result.dt,
+            steps: result.steps,
+            spikes: result.spike_trains.iter().map(SpikeTrain::to_vec).collect(),
+        })
+    }
+
+    /// Record the file the network was loaded from.
+    pub fn with_source(mut self, path: &Path) -> Self {
+        self.network.source = Some(path.display().to_string());
+        self
+    }
+
+    /// Whether `network` has the structural hash the bundle's run recorded.
+    pub fn matches_network(&self, network: &Network) -> bool {
+        self.network.structural_hash == hex(network.structural_hash())
+    }
+
+    /// The config the run used, parsed back from `config`.
+    pub fn simulation_config(&self) -> Result<SimulationConfig, FlymindError> {
+        SimulationConfig::from_toml_str(&self.config)
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), FlymindError> {
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    pub fn read_json<R: Read>(reader: R) -> Result<Self, FlymindError> {
+        serde_json::from_reader(reader).map_err(|err| invalid("bundle", err.to_string()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), FlymindError> {
+        self.write_json(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Read a bundle written by `save`, failing with `FlymindError::Bundle`
+    /// if it is not valid bundle JSON.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FlymindError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|err| missing(&path.display().to_string(), err))?;
+        serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|err| invalid(&path.display().to_string(), err.to_string()))
+    }
+
+    /// Configure `network` with the stored config and run it again, returning
+    /// the new result if its spikes are exactly the recorded ones. Fails with
+    /// `FlymindError::Replay` if the network's structural hash differs or
+    /// the rasters do, naming the earliest spike that differs, which points
+    /// at nondeterminism or a change in the model.
+    pub fn replay(&self, network: &mut Network) -> Result<SimulationResult, FlymindError> {
+        if !self.matches_network(network) {
+            return Err(FlymindError::Replay {
+                message: format!(
+                    "the network has structural hash {}, the bundle {}",
+                    hex(network.structural_hash()),
+                    self.network.structural_hash
+                ),
+            });
+        }
+        let result = self.simulation_config()?.run(network)?;
+        let replayed: Vec<Vec<usize>> =
+            result.spike_trains.iter().map(SpikeTrain::to_vec).collect();
+        if result.steps != self.steps || replayed.len() != self.spikes.len() {
+            return Err(FlymindError::Replay {
+                message: format!(
+                    "it ran {} steps of {} neurons, the bundle {} of {}",
+                    result.steps,
+                    replayed.len(),
+                    self.steps,
+                    self.spikes.len()
+                ),
+            });
+        }
+        // The earliest differing spike, as (step, neuron, fired in the bundle).
+        let mut first: Option<(usize, usize, bool)> = None;
+        for (id, (recorded, again)) in self.spikes.iter().zip(&replayed).enumerate() {
+            let at = recorded.iter().zip(again).position(|(a, b)| a != b);
+            let difference = match at {
+                Some(i) => Some((recorded[i].min(again[i]), id, recorded[i] < again[i])),
+                None if recorded.len() > again.len() => Some((recorded[again.len()], id, true)),
+                None => again.get(recorded.len()).map(|&step| (step, id, false)),
+            };
+            if difference.is_some_and(|d| first.is_none_or(|f| (d.0, d.1) < (f.0, f.1))) {
+                first = difference;
+            }
+        }
+        match first {
+            None => Ok(result),
+            Some((step, id, recorded)) => {
+                let name = self.network.neurons.get(id).map_or("?", String::as_str);
+                let (fired, silent) = if recorded {
+                    ("bundle", "replay")
+                } else {
+                    ("replay", "bundle")
+                };
+                Err(FlymindError::Replay {
+                    message: format!(
+                        "{name} fires at step {step} in the {fired}, not the {silent}"
+                    ),
+                })
+            }
+        }
+    }
+}
+
+impl SimulationResult {
+    /// Write this run as a bundle in `dir`, creating it if needed and
+    /// overwriting files of the same names; see the module documentation.
+    pub fn write_bundle<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        inputs: &BundleInputs,
+    ) -> Result<Manifest, FlymindError> {
+        let dir = dir.as_ref();
+        let network = inputs.network;
+        let neurons: Vec<String> = network
+            .neurons
+            .iter()
+            .map(|n| n.name().to_string())
+            .collect();
+        if let Some(name) = duplicate(&neurons) {
+            return Err(invalid(
+                MANIFEST_FILE,
+                format!("would name neuron {name:?} twice"),
+            ));
+        }
+        fs::create_dir_all(dir)?;
+        let mut files = Vec::new();
+
+        let mut spikes = Vec::new();
+        self.write_spikes_csv(network, &mut spikes)?;
+        files.push(put(dir, SPIKES_FILE, &spikes)?);
+        if !self.potentials.is_empty() {
+            let mut voltages = Vec::new();
+            self.write_voltages_csv(network, &mut voltages)?;
+            files.push(put(dir, VOLTAGES_FILE, &voltages)?);
+        }
+        if !self.events.is_empty() {
+            let mut events = Vec::new();
+            self.write_events_jsonl(network, &mut events)?;
+            files.push(put(dir, "events.jsonl", &events)?);
+        }
+        if let Some(recorder) = inputs.weights {
+            let mut weights = Vec::new();
+            recorder.write_csv(&mut weights)?;
+            files.push(put(dir, "weights.csv", &weights)?);
+        }
+        if let Some(recorder) = inputs.population {
+            let mut population = Vec::new();
+            recorder.write_csv(&mut population)?;
+            files.push(put(dir, "population.csv", &population)?);
+        }
+        if let Some(config) = inputs.config {
+            files.push(put(dir, CONFIG_FILE, config.to_toml_string()?.as_bytes())?);
+        }
+
+        let manifest = Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: inputs.seed.or(inputs.config.map(|config| config.seed)),
+            wall_time_seconds: inputs.wall_time.map(|time| time.as_secs_f64()),
+            dt: self.dt,
+            steps: self.steps,
+            potential_stride: self.potential_stride,
+            network: NetworkRecord {
+                structural_hash: hex(network.structural_hash()),
+                source: inputs.source.map(|path| path.display().to_string()),
+                neurons,
+            },
+            files,
+        };
+        let json = serde_json::to_string_pretty(&manifest).map_err(io::Error::from)?;
+        fs::write(dir.join(MANIFEST_FILE), json + "\n")?;
+        Ok(manifest)
+    }
+
+    /// Read the bundle in `dir`, failing with `FlymindError::Bundle` if the
+    /// manifest or `spikes.csv` is missing, the manifest names a neuron twice
+    /// or a file outside `dir`, or any listed file is missing or differs from
+    /// what the manifest records.
+    pub fn read_bundle<P: AsRef<Path>>(dir: P) -> Result<LoadedBundle, FlymindError> {
+        let dir = dir.as_ref();
+        let manifest =
+            fs::read(dir.join(MANIFEST_FILE)).map_err(|err| missing(MANIFEST_FILE, err))?;
+        let manifest: Manifest = serde_json::from_slice(&manifest)
+            .map_err(|err| invalid(MANIFEST_FILE, err.to_string()))?;
+        if let Some(name) = duplicate(&manifest.network.neurons) {
+            return Err(invalid(
+                MANIFEST_FILE,
+                format!("names neuron {name:?} twice"),
+            ));
+        }
+
+        let mut contents = HashMap::new();
+        for file in &manifest.files {
+            let relative = Path::new(&file.name)
+                .components()
+                .all(|part| matches!(part, Component::Normal(_)));
+            if !relative || file.name.is_empty() {
+                return Err(invalid(
+                    &file.name,
+                    "is not a path inside the bundle".to_string(),
+                ));
+            }
+            let bytes = fs::read(dir.join(&file.name)).map_err(|err| missing(&file.name, err))?;
+            if bytes.len() as u64 != file.bytes {
+                let message = format!("has {} bytes, the manifest {}", bytes.len(), file.bytes);
+                return Err(invalid(&file.name, message));
+            }
+            if hex(checksum(&bytes)) != file.fnv1a {
+                let message = "does not match its checksum in the manifest".to_string();
+                return Err(invalid(&file.name, message));
+            }
+            contents.insert(file.name.as_str(), bytes);
+        }
+
+        let ids: HashMap<&str, usize> = manifest
+            .network
+            .neurons
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.as_str(), id))
+            .collect();
+        let spikes = contents
+            .get(SPIKES_FILE)
+            .ok_or_else(|| invalid(SPIKES_FILE, "is not in the manifest".to_string()))?;
+        let neurons = manifest.network.neurons.len();
+        let mut result = SimulationResult {
+            dt: manifest.dt,
+            steps: manifest.steps,
+            spike_trains: read_spikes(spikes, &ids, neurons, manifest.dt)?,
+            potential_stride: manifest.potential_stride,
+            depolarization_blocks: vec![0; neurons],
+            ..SimulationResult::default()
+        };
+        if let Some(voltages) = contents.get(VOLTAGES_FILE) {
+            result.potentials = read_voltages(voltages, &ids)?;
+        }
+        let config = match contents.get(CONFIG_FILE) {
+            Some(bytes) => {
+                let text = String::from_utf8(bytes.clone())
+                    .map_err(|err| invalid(CONFIG_FILE, err.to_string()))?;
+                Some(SimulationConfig::from_toml_str(&text)?)
+            }
+            None => None,
+        };
+        Ok(LoadedBundle {
+            manifest,
+            result,
+            config,
+        })
+    }
+}
+
+/// Write `bytes` to `name` in `dir`, returning its manifest entry.
+fn put(dir: &Path, name: &str, bytes: &[u8]) -> Result<BundleFile, FlymindError> {
+    fs::write(dir.join(name), bytes)?;
+    Ok(BundleFile {
+        name: name.to_string(),
+        bytes: bytes.len() as u64,
+        fnv1a: hex(checksum(bytes)),
+    })
+}
+
+/// The first name `names` holds twice.
+fn duplicate(names: &[String]) -> Option<&str> {
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .find(|name| !seen.insert(name.as_str()))
+        .map(String::as_str)
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv1a::default();
+    hash.write(bytes);
+    hash.finish()
+}
+
+fn hex(value: u64) -> String {
+    format!("{value:016x}")
+}
+
+fn missing(file: &str, err: io::Error) -> FlymindError {
+    if err.kind() == io::ErrorKind::NotFound {
+        invalid(file, "is missing".to_string())
+    } else {
+        FlymindError::Io(err)
+    }
+}
+
+fn invalid(file: &str, message: String) -> FlymindError {
+    FlymindError::Bundle {
+        file: file.to_string(),
+        message,
+    }
+}
+
+fn neuron_id(ids: &HashMap<&str, usize>, file: &str, name: &str) -> Result<usize, FlymindError> {
+    ids.get(name).copied().ok_or_else(|| {
+        invalid(
+            file,
+            format!("names neuron {name:?}, which the manifest lacks"),
+        )
+    })
+}
+
+/// Spike trains from `write_spikes_csv` output, steps recovered from times.
+fn read_spikes(
+    bytes: &[u8],
+    ids: &HashMap<&str, usize>,
+    neurons: usize,
+    dt: f64,
+) -> Result<Vec<SpikeTrain>, FlymindError> {
+    let mut trains = vec![SpikeTrain::new(SpikeStorage::Steps); neurons];
+    for record in csv::Reader::from_reader(bytes).records() {
+        let record = record?;
+        let (Some(time), Some(name)) = (record.get(0), record.get(1)) else {
+            return Err(invalid(
+                SPIKES_FILE,
+                "has a row without time and neuron".to_string(),
+            ));
+        };
+        let time: f64 = time
+            .parse()
+            .map_err(|_| invalid(SPIKES_FILE, format!("has time {time:?}")))?;
+        let id = neuron_id(ids, SPIKES_FILE, name)?;
+        trains[id].push((time / dt).round() as usize);
+    }
+    Ok(trains)
+}
+
+/// Potentials from `write_voltages_csv` output, by neuron id.
+fn read_voltages(
+    bytes: &[u8],
+    ids: &HashMap<&str, usize>,
+) -> Result<HashMap<usize, Vec<f64>>, FlymindError> {
+    let mut reader = csv::Reader::from_reader(bytes);
+    let columns = reader
+        .headers()?
+        .iter()
+        .skip(1)
+        .map(|name| neuron_id(ids, VOLTAGES_FILE, name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut potentials: HashMap<usize, Vec<f64>> =
+        columns.iter().map(|&id| (id, Vec::new())).collect();
+    for record in reader.records() {
+        let record = record?;
+        for (&id, value) in columns.iter().zip(record.iter().skip(1)) {
+            let value = value
+                .parse()
+                .map_err(|_| invalid(VOLTAGES_FILE, format!("has potential {value:?}")))?;
+            potentials.entry(id).or_default().push(value);
+        }
+    }
+    Ok(potentials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{NeuronType, Region};
+
+    const CONFIG: &str = r#"
+duration = 30.0
+dt = 0.1
+seed = 7
+
+[[stimulus]]
+neuron = "ALML"
+start = 1.0
+duration = 20.0
+amplitude = 2.0
+"#;
+
+    fn bundle_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flymind-bundle-{}-{name}", std::process::id()))
+    }
+
+    fn write_example(dir: &Path) -> (SimulationConfig, SimulationResult) {
+        let config = SimulationConfig::from_toml_str(CONFIG).unwrap();
+        let mut network = Network::example_touch_circuit();
+        let result = config.run(&mut network).unwrap();
+        let inputs = BundleInputs::new(&network).config(&config);
+        result.write_bundle(dir, &inputs).unwrap();
+        (config, result)
+    }
+
+    fn rewrite_manifest(dir: &Path, edit: impl FnOnce(&mut Manifest)) {
+        let mut manifest: Manifest =
+            serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILE)).unwrap()).unwrap();
+        edit(&mut manifest);
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn the_config_reproduces_the_spikes() {
+        let dir = bundle_dir("reproduce");
+        let (_, result) = write_example(&dir);
+        let loaded = SimulationResult::read_bundle(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.total_spikes() > 0);
+
+        let mut network = Network::example_touch_circuit();
+        assert!(loaded.matches_network(&network));
+        let again = loaded.config.unwrap().run(&mut network).unwrap();
+        let trains = |result: &SimulationResult| -> Vec<Vec<usize>> {
+            result.spike_trains.iter().map(SpikeTrain::to_vec).collect()
+        };
+        assert_eq!(trains(&loaded.result), trains(&result));
+        assert_eq!(trains(&again), trains(&result));
+    }
+
+    #[test]
+    fn a_corrupted_file_fails_its_checksum() {
+        let dir = bundle_dir("corrupt");
+        write_example(&dir);
+        let path = dir.join(SPIKES_FILE);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] = if bytes[last] == b'1' { b'2' } else { b'1' };
+        fs::write(&path, bytes).unwrap();
+        let err = SimulationResult::read_bundle(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        match err {
+            FlymindError::Bundle { file, message } => {
+                assert_eq!(file, SPIKES_FILE);
+                assert!(message.contains("checksum"), "{message}");
+            }
+            other => panic!("unexpected error {other}"),
+        }
+    }
+
+    #[test]
+    fn manifest_paths_stay_inside_the_bundle() {
+        for name in ["../spikes.csv", "/etc/passwd", "data/../../x"] {
+            let dir = bundle_dir("paths");
+            write_example(&dir);
+            rewrite_manifest(&dir, |manifest| manifest.files[0].name = name.to_string());
+            let err = SimulationResult::read_bundle(&dir).unwrap_err();
+            fs::remove_dir_all(&dir).unwrap();
+            assert!(
+                matches!(&err, FlymindError::Bundle { file, .. } if file == name),
+                "{name}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn repeated_neuron_names_are_rejected() {
+        let mut network = Network::new();
+        network.add_neuron("AVAL", NeuronType::Interneuron, Region::Head, 0.0);
+        network.add_neuron("AVAL", NeuronType::Interneuron, Region::Head, 0.0);
+        let result = network.run_simulation(10, 0.1);
+        let dir = bundle_dir("repeated");
+        let written = result.write_bundle(&dir, &BundleInputs::new(&network));
+        assert!(matches!(written, Err(FlymindError::Bundle { .. })));
+        assert!(!dir.exists());
+
+        write_example(&dir);
+        rewrite_manifest(&dir, |manifest| {
+            let first = manifest.network.neurons[0].clone();
+            manifest.network.neurons[1] = first;
+        });
+        let read = SimulationResult::read_bundle(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(read, Err(FlymindError::Bundle { .. })));
+    }
+
+    /// The example run as a single-file bundle. Nothing in it is plastic, so
+    /// a fresh example network still has the recorded hash.
+    fn single_file_example() -> SimulationBundle {
+        let config = SimulationConfig::from_toml_str(CONFIG).unwrap();
+        let mut network = Network::example_touch_circuit();
+        let result = config.run(&mut network).unwrap();
+        SimulationBundle::new(&network, &config, &result).unwrap()
+    }
+
+    #[test]
+    fn a_saved_bundle_replays() {
+        let bundle = single_file_example();
+        assert!(bundle.spikes.iter().any(|train| !train.is_empty()));
+        let path = bundle_dir("single.json");
+        bundle.save(&path).unwrap();
+        let loaded = SimulationBundle::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.spikes, bundle.spikes);
+        let result = loaded
+            .replay(&mut Network::example_touch_circuit())
+            .unwrap();
+        assert_eq!(result.steps, bundle.steps);
+    }
+
+    #[test]
+    fn replay_names_the_earliest_divergence() {
+        let mut bundle = single_file_example();
+        let mut network = Network::example_touch_circuit();
+        let (id, step) = bundle
+            .spikes
+            .iter()
+            .enumerate()
+            .filter_map(|(id, train)| train.first().map(|&step| (id, step)))
+            .min_by_key(|&(id, step)| (step, id))
+            .unwrap();
+        bundle.spikes[id].remove(0);
+        let name = network.neurons[id].name().to_string();
+        match bundle.replay(&mut network) {
+            Err(FlymindError::Replay { message }) => assert_eq!(
+                message,
+                format!("{name} fires at step {step} in the replay, not the bundle")
+            ),
+            other => panic!("unexpected {:?}", other.map(|r| r.steps)),
+        }
+
+        network.connections[0].weight += 1.0;
+        let replayed = bundle.replay(&mut network);
+        assert!(
+            matches!(&replayed, Err(FlymindError::Replay { message }) if message.contains("structural hash"))
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Degree-preserving rewiring of the chemical wiring, for null models.
+//!
+//! `Network::rewire` repeats Maslov-Sneppen swaps: two chemical edges a -> b
+//! and c -> d become a -> d and c -> b, unless that would make a
+//! self-connection or an edge that already exists. An edge is a connected
+//! pair of neurons: every `ChemicalSend` row from a to b moves with the
+//! `ChemicalReceive` rows mirroring it, so each neuron keeps its numbers of
+//! presynaptic and postsynaptic partners and its outgoing weight. Gap
+//! junctions, NMJ rows and self-connections stay as they are.
+//!
+//! With `RewireConstraints::preserve_bilateral_symmetry`, each swap is made
+//! together with its mirror image, left and right homologs exchanged (see
+//! [`crate::symmetry`]), so a network whose chemical edges mirror stays
+//! mirrored. Edges whose mirror image is missing never move and are reported.
+
+use std::collections::HashMap;
+
+use crate::network::{Network, SynapseType};
+use crate::rng::Rng;
+
+/// Attempts allowed per requested swap before `rewire` gives up.
+const ATTEMPTS_PER_SWAP: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewireConstraints {
+    /// Swap every edge together with its mirror image.
+    pub preserve_bilateral_symmetry: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RewireReport {
+    /// Swaps made, a swap and its mirror image counting once.
+    pub swaps: usize,
+    pub attempts: usize,
+    /// Edges left in place for want of a mirror image, by neuron names.
+    pub fixed: Vec<(String, String)>,
+}
+
+/// Edge index and the endpoints it moves to.
+type Move = (usize, (usize, usize));
+
+impl Network {
+    /// Make `swaps` swaps drawn with `seed`; see the module documentation.
+    /// Gives up after `100 * swaps` attempts, with fewer swaps in the report,
+    /// when few swaps are possible.
+    pub fn rewire(
+        &mut self,
+        swaps: usize,
+        constraints: RewireConstraints,
+        seed: u64,
+    ) -> RewireReport {
+        self.record_structural(
+            |network| network.rewire_edges(swaps, constraints, seed),
+            |report| (report.swaps > 0).then(|| format!("rewire {} edge pairs", report.swaps)),
+        )
+    }
+
+    fn rewire_edges(
+        &mut self,
+        swaps: usize,
+        constraints: RewireConstraints,
+        seed: u64,
+    ) -> RewireReport {
+        // Endpoints of each edge, the rows that make it up, and the edge at
+        // each pair of endpoints.
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        let mut rows: Vec<Vec<usize>> = Vec::new();
+        let mut index: HashMap<(usize, usize), usize> = HashMap::new();
+        for (row, conn) in self.connections.iter().enumerate() {
+            let pair = match conn.synapse_type {
+                SynapseType::ChemicalSend(_) => (conn.from_id, conn.to_id),
+                SynapseType::ChemicalReceive(_) => (conn.to_id, conn.from_id),
+                SynapseType::GapJunction | SynapseType::NMJ | SynapseType::Extrasynaptic(_) => {
+                    continue
+                }
+            };
+            if pair.0 == pair.1 {
+                continue;
+            }
+            let edge = *index.entry(pair).or_insert_with(|| {
+                edges.push(pair);
+                rows.push(Vec::new());
+                edges.len() - 1
+            });
+            rows[edge].push(row);
+        }
+
+        let mirror: Option<Vec<usize>> = constraints.preserve_bilateral_symmetry.then(|| {
+            let mut mirror: Vec<usize> = (0..self.neurons.len()).collect();
+            for (left, right) in self.homolog_pairs() {
+                mirror[left] = right;
+                mirror[right] = left;
+            }
+            mirror
+        });
+        let reflect = |mirror: &[usize], (from, to): (usize, usize)| (mirror[from], mirror[to]);
+
+        let mut report = RewireReport::default();
+        let movable: Vec<usize> = match &mirror {
+            None => (0..edges.len()).collect(),
+            Some(mirror) => {
+                let (movable, fixed): (Vec<usize>, Vec<usize>) = (0..edges.len())
+                    .partition(|&edge| index.contains_key(&reflect(mirror, edges[edge])));
+                report.fixed = fixed
+                    .into_iter()
+                    .map(|edge| {
+                        let (from, to) = edges[edge];
+                        let name = |id: usize| self.neurons[id].name().to_string();
+                        (name(from), name(to))
+                    })
+                    .collect();
+                movable
+            }
+        };
+
+        if movable.len() >= 2 {
+            let mut rng = Rng::new(seed);
+            let mut moves: Vec<Move> = Vec::with_capacity(4);
+            let max_attempts = swaps.saturating_mul(ATTEMPTS_PER_SWAP);
+            while report.swaps < swaps && report.attempts < max_attempts {
+                report.attempts += 1;
+                let first = movable[rng.below(movable.len())];
+                let second = movable[rng.below(movable.len())];
+                let ((a, b), (c, d)) = (edges[first], edges[second]);
+                moves.clear();
+                moves.push((first, (a, d)));
+                moves.push((second, (c, b)));
+                if let Some(mirror) = &mirror {
+                    let third = index[&reflect(mirror, (a, b))];
+                    let fourth = index[&reflect(mirror, (c, d))];
+                    // The mirror image is either this swap again or a swap
+                    // of two other edges; one sharing an edge with this swap
+                    // cannot be made alongside it.
+                    let itself =
+                        [third, fourth] == [first, second] || [third, fourth] == [second, first];
+                    let mut all = [first, second, third, fourth];
+                    all.sort_unstable();
+                    if !itself && all.windows(2).any(|pair| pair[0] == pair[1]) {
+                        continue;
+                    }
+                    moves.push((third, reflect(mirror, (a, d))));
+                    moves.push((fourth, reflect(mirror, (c, b))));
+                }
+                if !consistent(&mut moves, &index) {
+                    continue;
+                }
+                for &(edge, _) in &moves {
+                    index.remove(&edges[edge]);
+                }
+                for &(edge, pair) in &moves {
+                    edges[edge] = pair;
+                    index.insert(pair, edge);
+                }
+                report.swaps += 1;
+            }
+        }
+
+        for (edge, &(from, to)) in edges.iter().enumerate() {
+            for &row in &rows[edge] {
+                let conn = &mut self.connections[row];
+                if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) {
+                    (conn.from_id, conn.to_id) = (to, from);
+                } else {
+                    (conn.from_id, conn.to_id) = (from, to);
+                }
+            }
+        }
+        self.rebuild_indices();
+        report
+    }
+}
+
+/// Whether `moves`, once duplicates are dropped, send each edge to one new
+/// pair of distinct neurons that no edge has yet, and no two edges to the
+/// same pair.
+fn consistent(moves: &mut Vec<Move>, index: &HashMap<(usize, usize), usize>) -> bool {
+    moves.sort_unstable();
+    moves.dedup();
+    if moves.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return false;
+    }
+    let mut targets: Vec<(usize, usize)> = moves.iter().map(|&(_, pair)| pair).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets.len() == moves.len()
+        && targets
+            .iter()
+            .all(|&(from, to)| from != to && !index.contains_key(&(from, to)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const SEND: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    /// Six homolog pairs, `N<k>L` and `N<k>R` at ids `2k` and `2k + 1`, whose
+    /// chemical edges mirror, with a receive row under every send.
+    fn mirrored_network() -> Network {
+        let mut network = Network::new();
+        for k in 0..6 {
+            network.get_or_create_neuron(&format!("N{k}L"));
+            network.get_or_create_neuron(&format!("N{k}R"));
+        }
+        let mut rng = Rng::new(3);
+        let mut pairs = BTreeSet::new();
+        while pairs.len() < 30 {
+            let (from, to) = (rng.below(12), rng.below(12));
+            if from != to {
+                pairs.insert((from, to));
+                pairs.insert((from ^ 1, to ^ 1));
+            }
+        }
+        for (from, to) in pairs {
+            network.add_connection(from, to, SEND, 1.0 + from as f64);
+            network.add_connection(
+                to,
+                from,
+                SynapseType::ChemicalReceive(ChemicalSubtype::Excitatory),
+                1.0 + from as f64,
+            );
+        }
+        network
+    }
+
+    fn sends(network: &Network) -> BTreeSet<(usize, usize)> {
+        network
+            .connections
+            .iter()
+            .filter(|conn| conn.synapse_type == SEND)
+            .map(|conn| (conn.from_id, conn.to_id))
+            .collect()
+    }
+
+    /// Out- and in-degree and outgoing weight of every neuron.
+    fn degrees(network: &Network) -> Vec<(usize, usize, f64)> {
+        let mut degrees = vec![(0, 0, 0.0); network.neurons.len()];
+        for conn in &network.connections {
+            if conn.synapse_type == SEND {
+                degrees[conn.from_id].0 += 1;
+                degrees[conn.from_id].2 += conn.weight;
+                degrees[conn.to_id].1 += 1;
+            }
+        }
+        degrees
+    }
+
+    #[test]
+    fn swaps_keep_degrees_and_receive_mirrors() {
+        let mut network = mirrored_network();
+        let before = degrees(&network);
+        let edges = sends(&network);
+        let report = network.rewire(200, RewireConstraints::default(), 9);
+        assert_eq!(report.swaps, 200);
+        assert_eq!(degrees(&network), before);
+        let after = sends(&network);
+        assert_eq!(after.len(), edges.len());
+        assert_ne!(after, edges);
+        assert!(after.iter().all(|&(from, to)| from != to));
+        for conn in &network.connections {
+            if conn.synapse_type != SEND {
+                assert!(after.contains(&(conn.to_id, conn.from_id)));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_rewiring_keeps_the_network_mirrored() {
+        let mut network = mirrored_network();
+        let before = degrees(&network);
+        let edges = sends(&network);
+        let constraints = RewireConstraints {
+            preserve_bilateral_symmetry: true,
+        };
+        let report = network.rewire(100, constraints, 5);
+        assert!(report.swaps > 0);
+        assert!(report.fixed.is_empty());
+        assert_eq!(degrees(&network), before);
+        let after = sends(&network);
+        assert_ne!(after, edges);
+        assert!(after
+            .iter()
+            .all(|&(from, to)| after.contains(&(from ^ 1, to ^ 1))));
+    }
+
+    #[test]
+    fn edges_without_a_mirror_stay_put() {
+        let mut network = mirrored_network();
+        let odd = network.get_or_create_neuron("AVG");
+        network.add_connection(0, odd, SEND, 1.0);
+        let constraints = RewireConstraints {
+            preserve_bilateral_symmetry: true,
+        };
+        let report = network.rewire(100, constraints, 5);
+        assert_eq!(report.fixed, vec![("N0L".to_string(), "AVG".to_string())]);
+        assert!(sends(&network).contains(&(0, odd)));
+    }
+}
@@ -0,0 +1,476 @@
+//! Selecting neurons with small boolean expressions, e.g.
+//! `type:interneuron & region:head & outdeg>20 & !class:AVA`.
+//!
+//! Predicates:
+//!
+//! - `type:<label>` and `region:<label>`, labels as `NeuronType::from_label`
+//!   and `Region::from_label` accept them;
+//! - `name:<pattern>`, matched as `Network::find_neurons` matches, so `name:AVA`
+//!   and `name:AVA*` both select AVAL and AVAR;
+//! - `class:<class>`, comparing `classes::neuron_class` of the name;
+//! - `outdeg<op><n>` and `indeg<op><n>`, on `Network::out_degree` and
+//!   `in_degree`;
+//! - `typeconf<op><p>`, on `Neuron::type_confidence`, 1 for neurons whose
+//!   type was given outright;
+//! - `attr:<key>` for neurons with that annotation, and `attr:<key><op><value>`
+//!   comparing it: numerically when both sides are numbers, otherwise `=` only,
+//!   against the value's text.
+//!
+//! Operators `<op>` are `=`, `<`, `<=`, `>` and `>=`. Predicates combine with
+//! `!` (binding tightest), `&`, then `|`, and parentheses. A predicate runs up
+//! to the next space, parenthesis or operator, so values cannot contain them.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::annotations::AttrValue;
+use crate::classes::neuron_class;
+use crate::network::{glob_match, Network, NeuronType, Region};
+
+/// A parsed selection expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    expr: Expr,
+}
+
+/// Why an expression did not parse. `position` is the byte offset of the
+/// offending token (the expression's length at an unexpected end) and `token`
+/// its text, empty at the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectError {
+    pub position: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "{} at end of selection", self.message)
+        } else {
+            write!(
+                f,
+                "{} at column {} ({:?})",
+                self.message,
+                self.position + 1,
+                self.token
+            )
+        }
+    }
+}
+
+impl Error for SelectError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Type(NeuronType),
+    Region(Region),
+    Name(Vec<char>),
+    Class(String),
+    OutDegree(Comparison, f64),
+    InDegree(Comparison, f64),
+    TypeConfidence(Comparison, f64),
+    HasAttr(String),
+    Attr(String, Comparison, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// The operator `text` starts with and its length.
+    fn split(text: &str) -> Option<(Comparison, usize)> {
+        [
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("=", Comparison::Eq),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ]
+        .into_iter()
+        .find(|(op, _)| text.starts_with(op))
+        .map(|(op, comparison)| (comparison, op.len()))
+    }
+
+    fn holds(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+impl Selection {
+    pub fn parse(source: &str) -> Result<Selection, SelectError> {
+        let mut parser = Parser {
+            tokens: tokenize(source),
+            next: 0,
+            end: source.len(),
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Selection { expr }),
+            Some(token) => Err(token.error("expected `&`, `|` or the end")),
+        }
+    }
+
+    /// Whether neuron `id` of `network` is selected; false for an unknown id.
+    pub fn matches(&self, network: &Network, id: usize) -> bool {
+        id < network.neurons.len() && self.expr.matches(network, id)
+    }
+
+    /// The selection as a predicate, for APIs that take one.
+    pub fn into_predicate(self) -> impl Fn(&Network, usize) -> bool {
+        move |network, id| self.matches(network, id)
+    }
+}
+
+impl FromStr for Selection {
+    type Err = SelectError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Selection::parse(source)
+    }
+}
+
+impl Network {
+    /// Ids of the neurons `selection` matches, ascending.
+    pub fn select(&self, selection: &Selection) -> Vec<usize> {
+        (0..self.neurons.len())
+            .filter(|&id| selection.matches(self, id))
+            .collect()
+    }
+
+    /// `subnetwork` of the neurons `selection` matches.
+    pub fn select_subnetwork(&self, selection: &Selection) -> Network {
+        self.subnetwork(&self.select(selection))
+    }
+}
+
+impl Expr {
+    fn matches(&self, network: &Network, id: usize) -> bool {
+        let neuron = &network.neurons[id];
+        match self {
+            Expr::And(a, b) => a.matches(network, id) && b.matches(network, id),
+            Expr::Or(a, b) => a.matches(network, id) || b.matches(network, id),
+            Expr::Not(a) => !a.matches(network, id),
+            Expr::Type(neuron_type) => neuron.neuron_type == *neuron_type,
+            Expr::Region(region) => neuron.region == *region,
+            Expr::Name(pattern) => glob_match(pattern, &neuron.name().chars().collect::<Vec<_>>()),
+            Expr::Class(class) => neuron_class(neuron.name()) == *class,
+            Expr::OutDegree(op, n) => op.holds(network.out_degree(id) as f64, *n),
+            Expr::InDegree(op, n) => op.holds(network.in_degree(id) as f64, *n),
+            Expr::TypeConfidence(op, p) => op.holds(neuron.type_confidence.unwrap_or(1.0), *p),
+            Expr::HasAttr(key) => network.get_neuron_attr(id, key).is_some(),
+            Expr::Attr(key, op, value) => network
+                .get_neuron_attr(id, key)
+                .is_some_and(|attr| attr_holds(attr, *op, value)),
+        }
+    }
+}
+
+fn attr_holds(attr: &AttrValue, op: Comparison, value: &str) -> bool {
+    match (attr.as_f64(), value.parse::<f64>()) {
+        (Some(left), Ok(right)) => op.holds(left, right),
+        _ => op == Comparison::Eq && attr.to_string() == value,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Predicate,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    position: usize,
+}
+
+impl Token<'_> {
+    fn error(&self, message: &str) -> SelectError {
+        SelectError {
+            position: self.position,
+            token: self.text.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        let kind = match ch {
+            c if c.is_whitespace() => continue,
+            '&' => TokenKind::And,
+            '|' => TokenKind::Or,
+            '!' => TokenKind::Not,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            _ => {
+                let mut end = start + ch.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || "&|!()".contains(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Predicate,
+                    text: &source[start..end],
+                    position: start,
+                });
+                continue;
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &source[start..start + ch.len_utf8()],
+            position: start,
+        });
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        let found = self.peek().is_some_and(|token| token.kind == kind);
+        self.next += usize::from(found);
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, SelectError> {
+        let mut expr = self.and()?;
+        while self.eat(TokenKind::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, SelectError> {
+        let mut expr = self.unary()?;
+        while self.eat(TokenKind::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SelectError> {
+        if self.eat(TokenKind::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let Some(token) = self.peek().cloned() else {
+            return Err(SelectError {
+                position: self.end,
+                token: String::new(),
+                message: "expected a predicate".to_string(),
+            });
+        };
+        self.next += 1;
+        match token.kind {
+            TokenKind::Open => {
+                let expr = self.or()?;
+                if self.eat(TokenKind::Close) {
+                    Ok(expr)
+                } else {
+                    Err(match self.peek() {
+                        Some(token) => token.error("expected `)`"),
+                        None => SelectError {
+                            position: self.end,
+                            token: String::new(),
+                            message: format!("expected `)` to close column {}", token.position + 1),
+                        },
+                    })
+                }
+            }
+            TokenKind::Predicate => predicate(&token),
+            _ => Err(token.error("expected a predicate")),
+        }
+    }
+}
+
+fn predicate(token: &Token<'_>) -> Result<Expr, SelectError> {
+    let text = token.text;
+    for field in ["outdeg", "indeg", "typeconf"] {
+        if let Some(rest) = text.strip_prefix(field) {
+            let (op, len) = Comparison::split(rest)
+                .ok_or_else(|| token.error(&format!("expected a comparison after `{field}`")))?;
+            let n = rest[len..]
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| token.error("expected a number"))?;
+            return Ok(match field {
+                "outdeg" => Expr::OutDegree(op, n),
+                "indeg" => Expr::InDegree(op, n),
+                _ => Expr::TypeConfidence(op, n),
+            });
+        }
+    }
+
+    let Some((field, value)) = text.split_once(':') else {
+        return Err(token.error("expected `field:value` or a comparison"));
+    };
+    if value.is_empty() {
+        return Err(token.error(&format!("missing value after `{field}:`")));
+    }
+    match field {
+        "type" => NeuronType::from_label(value)
+            .map(Expr::Type)
+            .ok_or_else(|| token.error("unknown neuron type")),
+        "region" => Region::from_label(value)
+            .map(Expr::Region)
+            .ok_or_else(|| token.error("unknown region")),
+        "name" => Ok(Expr::Name(if value.contains(['*', '?']) {
+            value.chars().collect()
+        } else {
+            value.chars().chain(['*']).collect()
+        })),
+        "class" => Ok(Expr::Class(neuron_class(value))),
+        "attr" => match value.find(['=', '<', '>']) {
+            None => Ok(Expr::HasAttr(value.to_string())),
+            Some(0) => Err(token.error("missing annotation key")),
+            Some(at) => {
+                let (op, len) = Comparison::split(&value[at..]).expect("found an operator");
+                Ok(Expr::Attr(
+                    value[..at].to_string(),
+                    op,
+                    value[at + len..].to_string(),
+                ))
+            }
+        },
+        _ => Err(token.error(&format!("unknown field `{field}`"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names `expr` selects from the touch circuit, sorted.
+    fn selected(expr: &str) -> Vec<String> {
+        let network = Network::example_touch_circuit();
+        let selection = Selection::parse(expr).unwrap();
+        let mut names: Vec<String> = network
+            .select(&selection)
+            .into_iter()
+            .map(|id| network.neurons[id].name().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn not_binds_tightest_then_and_then_or() {
+        let parse = |expr: &str| Selection::parse(expr).unwrap();
+        assert_eq!(
+            parse("name:A | name:B & !name:C"),
+            parse("name:A | (name:B & (!name:C))")
+        );
+        assert_ne!(
+            parse("name:A | name:B & name:C"),
+            parse("(name:A | name:B) & name:C")
+        );
+
+        assert_eq!(
+            selected("region:tail | type:sensory & region:head"),
+            ["ASHL", "ASHR", "PLML", "PLMR", "PVCL", "PVCR"]
+        );
+        assert_eq!(
+            selected("(region:tail | type:sensory) & region:head"),
+            ["ASHL", "ASHR"]
+        );
+        assert_eq!(
+            selected("!type:motor & region:midbody"),
+            ["ALML", "ALMR", "AVM", "PVM"]
+        );
+        assert_eq!(selected("!(type:motor | region:head)").len(), 8);
+    }
+
+    #[test]
+    fn names_match_as_globs_and_classes_by_class() {
+        assert_eq!(selected("name:AVA"), ["AVAL", "AVAR"]);
+        assert_eq!(selected("name:AVA*"), ["AVAL", "AVAR"]);
+        assert_eq!(selected("name:AV?L"), ["AVAL", "AVBL", "AVDL"]);
+        assert_eq!(selected("class:AVA"), ["AVAL", "AVAR"]);
+        assert!(selected("name:ZZZ*").is_empty());
+    }
+
+    #[test]
+    fn degrees_compare_numerically() {
+        assert_eq!(
+            selected("outdeg>20"),
+            ["AVAL", "AVAR", "AVBL", "AVBR", "PVCL", "PVCR"]
+        );
+        assert_eq!(selected("indeg<=2"), ["ALMR", "PLML", "PVM"]);
+        assert_eq!(selected("outdeg=38"), ["AVAL"]);
+        assert_eq!(selected("outdeg>=38"), ["AVAL"]);
+        assert!(selected("outdeg<1").is_empty());
+    }
+
+    #[test]
+    fn a_whole_selection_picks_from_the_fixture() {
+        assert_eq!(
+            selected("type:interneuron & region:head & outdeg>20 & !class:AVA"),
+            ["AVBL", "AVBR"]
+        );
+        let network = Network::example_touch_circuit();
+        let motor: Selection = "type:motor".parse().unwrap();
+        let sub = network.select_subnetwork(&motor);
+        assert_eq!(sub.neurons.len(), 10);
+    }
+
+    #[test]
+    fn malformed_expressions_fail_with_their_position() {
+        let error = |expr: &str| Selection::parse(expr).unwrap_err();
+        assert_eq!(
+            error("name:A name:B"),
+            SelectError {
+                position: 7,
+                token: "name:B".to_string(),
+                message: "expected `&`, `|` or the end".to_string(),
+            }
+        );
+        assert_eq!(error("name:A &").position, 8);
+        assert_eq!(error("name:A &").token, "");
+        assert_eq!(error("(name:A").message, "expected `)` to close column 1");
+        assert_eq!(error("outdeg>x").message, "expected a number");
+        assert_eq!(error("type:alien").message, "unknown neuron type");
+        assert_eq!(
+            error("& x").to_string(),
+            "expected a predicate at column 1 (\"&\")"
+        );
+        assert!("type:".parse::<Selection>().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! How concentrated each neuron's outputs (or inputs) are on a few partners.
+//!
+//! Weight is summed per partner over every connection except `ChemicalReceive`
+//! mirrors, so parallel rows to one partner count as one. Neurons without
+//! partners in that direction have no entry.
+
+use std::collections::HashMap;
+
+use crate::network::{Network, SynapseType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Selectivity {
+    /// Share of the weight on the strongest partner: 1.0 for a single partner,
+    /// `1 / k` when spread evenly over `k`.
+    TopFraction,
+    /// Shannon entropy of the weight shares divided by `ln k`: 0.0 for a single
+    /// partner (taken as fully specific), 1.0 when spread evenly. Low values mean
+    /// specific, high values broadcast.
+    NormalizedEntropy,
+}
+
+impl Network {
+    /// Selectivity of each neuron's targets, keyed by neuron id.
+    pub fn output_selectivity(&self, measure: Selectivity) -> HashMap<usize, f64> {
+        self.selectivity(true, measure)
+    }
+
+    /// Selectivity of each neuron's sources, keyed by neuron id.
+    pub fn input_selectivity(&self, measure: Selectivity) -> HashMap<usize, f64> {
+        self.selectivity(false, measure)
+    }
+
+    fn selectivity(&self, outgoing: bool, measure: Selectivity) -> HashMap<usize, f64> {
+        let map = if outgoing {
+            &self.outgoing_map
+        } else {
+            &self.incoming_map
+        };
+        let mut result = HashMap::with_capacity(map.len());
+        let mut weights: HashMap<usize, f64> = HashMap::new();
+        for (&id, indices) in map {
+            weights.clear();
+            for &index in indices {
+                let conn = &self.connections[index];
+                if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) {
+                    continue;
+                }
+                let partner = if outgoing { conn.to_id } else { conn.from_id };
+                *weights.entry(partner).or_default() += conn.weight;
+            }
+            let total: f64 = weights.values().sum();
+            if weights.is_empty() || total <= 0.0 {
+                continue;
+            }
+            let value = match measure {
+                Selectivity::TopFraction => weights.values().copied().fold(0.0, f64::max) / total,
+                Selectivity::NormalizedEntropy if weights.len() == 1 => 0.0,
+                Selectivity::NormalizedEntropy => {
+                    let entropy: f64 = weights
+                        .values()
+                        .map(|&w| w / total)
+                        .filter(|&p| p > 0.0)
+                        .map(|p| -p * p.ln())
+                        .sum();
+                    entropy / (weights.len() as f64).ln()
+                }
+            };
+            result.insert(id, value);
+        }
+        result
+    }
+}
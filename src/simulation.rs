@@ -0,0 +1,1757 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::FlymindError;
+use crate::events::{EventKind, EventVerbosity, SimEvent};
+use crate::kernel::{Delivery, InputLimits, StepInputs};
+use crate::network::{Connection, Network, Neuron, SynapseType};
+use crate::propagation::dynamic_state;
+use crate::rng::Rng;
+use crate::spikes::SpikeTrain;
+use crate::synapses::SynapseParams;
+use crate::trace::StimulusTrace;
+
+/// Unit in which `dt` and every time constant of a run are expressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TimeUnit {
+    #[default]
+    #[serde(rename = "ms")]
+    Milliseconds,
+    #[serde(rename = "s")]
+    Seconds,
+}
+
+impl TimeUnit {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Seconds => "s",
+        }
+    }
+
+    pub fn in_seconds(&self) -> f64 {
+        match self {
+            TimeUnit::Milliseconds => 1e-3,
+            TimeUnit::Seconds => 1.0,
+        }
+    }
+
+    /// Express `value` (in this unit) in unit `to`.
+    pub fn convert(&self, value: f64, to: TimeUnit) -> f64 {
+        value * self.in_seconds() / to.in_seconds()
+    }
+}
+
+/// Scheme for the subthreshold membrane equation `dV/dt = -V / tau + I`.
+///
+/// Synaptic and stimulus currents are held at their start-of-step values for
+/// the whole step under either scheme; only the leak integration differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrationMethod {
+    #[default]
+    Euler,
+    /// Classical fourth-order Runge-Kutta; accurate at much larger `dt`.
+    Rk4,
+}
+
+impl IntegrationMethod {
+    /// Largest `dt`, as a multiple of the smallest time constant, that the
+    /// scheme integrates stably (Euler is stable below 2, RK4 below about 2.78;
+    /// both limits keep a margin).
+    pub fn stability_limit(&self) -> f64 {
+        match self {
+            IntegrationMethod::Euler => 1.0,
+            IntegrationMethod::Rk4 => 2.5,
+        }
+    }
+
+    /// `dt`, as a multiple of the smallest time constant, up to which the
+    /// scheme stays accurate.
+    pub fn accuracy_limit(&self) -> f64 {
+        match self {
+            IntegrationMethod::Euler => 0.1,
+            IntegrationMethod::Rk4 => 0.5,
+        }
+    }
+
+    /// Advance `v` by `dt` under constant input `current`.
+    pub fn integrate(&self, v: f64, current: f64, tau: f64, dt: f64) -> f64 {
+        let f = |v: f64| -v / tau + current;
+        match self {
+            IntegrationMethod::Euler => v + dt * f(v),
+            IntegrationMethod::Rk4 => {
+                let k1 = f(v);
+                let k2 = f(v + 0.5 * dt * k1);
+                let k3 = f(v + 0.5 * dt * k2);
+                let k4 = f(v + dt * k3);
+                v + dt / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4)
+            }
+        }
+    }
+}
+
+/// What a neuron's summed synaptic input (chemical jumps and gap-junction
+/// current, not stimuli) is divided by before integration, so densely
+/// innervated neurons are not driven harder just for having more inputs.
+/// Counts are of distinct presynaptic partners over chemical sends and gap
+/// junctions; neurons without any are left as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputNormalization {
+    #[default]
+    None,
+    ByCount,
+    BySqrtCount,
+    /// Summed absolute weight of those incoming connections.
+    ByWeight,
+}
+
+/// What a chemical send carries to its target, network-wide, as
+/// `Network::synaptic_input`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SynapticInput {
+    /// Each presynaptic spike, whole, on the step after it.
+    #[default]
+    Spikes,
+    /// The presynaptic `Neuron::rate_estimate`, its spike train low-pass
+    /// filtered with time constant `tau` (in `time_unit`), times `dt`: the
+    /// drive of each spike in all, spread out over the steps after it. The
+    /// neurons still spike; only their coupling is smoothed. Release
+    /// probabilities scale the drive instead of failing spikes, and runs
+    /// step densely whatever `Network::propagation` says.
+    Rate { tau: f64 },
+}
+
+impl SynapticInput {
+    /// Fold the spikes of the last step into every neuron's rate estimate:
+    /// a spike adds `(1 - decay) / dt`, so that over the steps after it the
+    /// estimate sums to one spike.
+    pub(crate) fn update_rates(self, neurons: &mut [Neuron], dt: f64) {
+        let SynapticInput::Rate { tau } = self else {
+            return;
+        };
+        let decay = if tau > 0.0 { (-dt / tau).exp() } else { 0.0 };
+        for neuron in neurons {
+            neuron.rate_estimate *= decay;
+            if neuron.just_fired {
+                neuron.rate_estimate += (1.0 - decay) / dt;
+            }
+        }
+    }
+}
+
+/// How the membrane potential is compared with `LifParams::threshold`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdCrossing {
+    /// Fire when the potential strictly exceeds the threshold (`>`).
+    #[default]
+    Above,
+    /// Fire when the potential reaches the threshold (`>=`), for models
+    /// written as `v >= v_th`.
+    AtOrAbove,
+}
+
+impl ThresholdCrossing {
+    pub fn crosses(self, potential: f64, threshold: f64) -> bool {
+        match self {
+            ThresholdCrossing::Above => potential > threshold,
+            ThresholdCrossing::AtOrAbove => potential >= threshold,
+        }
+    }
+}
+
+/// Silences a neuron driven to fire at its maximum rate for too long, as
+/// sustained depolarization inactivates real spiking.
+///
+/// A run counts spikes each fired on the first step the refractory period
+/// allowed (every step with `refractory_steps` 0); any step the neuron could
+/// have fired and did not ends it. The spike that takes the run past
+/// `max_rapid_spikes` puts the neuron in block: it is held at
+/// `reset_potential` for `recovery_steps` steps instead of the refractory
+/// period, and the run starts over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepolarizationBlock {
+    pub max_rapid_spikes: usize,
+    pub recovery_steps: usize,
+}
+
+/// Leaky integrate-and-fire parameters, shared by every neuron unless overridden
+/// in `Network::lif_overrides`.
+///
+/// The membrane leaks towards `resting_potential`, and `threshold` and
+/// `reset_potential` are measured from it, so moving the rest (e.g. to -65
+/// for millivolts) shifts the whole model without changing how it behaves.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LifParams {
+    /// Potential the membrane leaks towards and starts from.
+    pub resting_potential: f64,
+    /// Firing threshold, above `resting_potential`.
+    pub threshold: f64,
+    pub threshold_crossing: ThresholdCrossing,
+    /// Potential after a spike, relative to `resting_potential`.
+    pub reset_potential: f64,
+    /// Membrane time constant, in `time_unit`.
+    pub tau: f64,
+    /// Steps a neuron is held at `reset_potential` after firing.
+    pub refractory_steps: usize,
+    /// Potential jump per unit weight delivered by a presynaptic chemical spike.
+    pub chemical_gain: f64,
+    /// Coupling current per unit weight and unit potential difference across a gap junction.
+    pub gap_gain: f64,
+    /// Standard deviation of the Gaussian noise current; its effect on the
+    /// potential scales with `sqrt(dt)`. Zero disables noise.
+    pub noise_std: f64,
+    /// Unit of `tau`, and of the `dt` passed to the simulation.
+    pub time_unit: TimeUnit,
+    pub integration: IntegrationMethod,
+    pub input_normalization: InputNormalization,
+    /// Most fast inputs (non-modulatory chemical sends and gap-junction rows)
+    /// that reach this neuron each step: only the strongest by `|weight|`
+    /// count, ties going to the earlier connection. `None` for no cap.
+    /// Normalization still counts every input.
+    pub max_inputs: Option<usize>,
+    /// Off unless set.
+    pub depolarization_block: Option<DepolarizationBlock>,
+    /// Rise in `Neuron::modulation` per unit weight delivered by a presynaptic
+    /// modulatory spike; negative to make modulation suppress this neuron.
+    pub modulation_gain: f64,
+    /// Decay time constant of `Neuron::modulation`, in `time_unit`; usually
+    /// far longer than `tau`.
+    pub modulation_tau: f64,
+    /// Rise in `Neuron::modulation` per unit weight and unit of time from an
+    /// extrasynaptic edge whose source is fully depolarized, graded by
+    /// `Neuron::graded_activity` rather than gated by spikes. Zero, the
+    /// default, leaves extrasynaptic edges out of runs.
+    pub extrasynaptic_gain: f64,
+}
+
+impl Default for LifParams {
+    fn default() -> Self {
+        Self {
+            resting_potential: 0.0,
+            threshold: 1.0,
+            threshold_crossing: ThresholdCrossing::Above,
+            reset_potential: 0.0,
+            tau: 10.0,
+            refractory_steps: 2,
+            chemical_gain: 0.1,
+            gap_gain: 0.01,
+            noise_std: 0.0,
+            time_unit: TimeUnit::Milliseconds,
+            integration: IntegrationMethod::Euler,
+            input_normalization: InputNormalization::None,
+            max_inputs: None,
+            depolarization_block: None,
+            modulation_gain: 0.002,
+            modulation_tau: 500.0,
+            extrasynaptic_gain: 0.0,
+        }
+    }
+}
+
+impl LifParams {
+    /// The absolute potential at which a neuron fires.
+    pub fn absolute_threshold(&self) -> f64 {
+        self.resting_potential + self.threshold
+    }
+
+    /// The absolute potential a neuron is reset to.
+    pub fn absolute_reset(&self) -> f64 {
+        self.resting_potential + self.reset_potential
+    }
+
+    /// The largest step at which `integration` resolves `tau` accurately.
+    pub fn suggest_dt(&self) -> f64 {
+        self.tau * self.integration.accuracy_limit()
+    }
+}
+
+/// Pair-based spike-timing-dependent plasticity on chemical sends.
+///
+/// Each neuron keeps a presynaptic and a postsynaptic trace that jump by one
+/// on a spike and decay with `tau_plus` and `tau_minus`. A postsynaptic spike
+/// potentiates every incoming send by `a_plus` times the presynaptic trace; a
+/// presynaptic spike depresses every outgoing send by `a_minus` times the
+/// postsynaptic trace. Weights are clamped to `[w_min, w_max]`. Time constants
+/// share the unit of `LifParams::time_unit`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StdpParams {
+    pub a_plus: f64,
+    pub a_minus: f64,
+    pub tau_plus: f64,
+    pub tau_minus: f64,
+    pub w_min: f64,
+    pub w_max: f64,
+}
+
+impl Default for StdpParams {
+    fn default() -> Self {
+        Self {
+            a_plus: 0.01,
+            a_minus: 0.012,
+            tau_plus: 20.0,
+            tau_minus: 20.0,
+            w_min: 0.0,
+            w_max: 50.0,
+        }
+    }
+}
+
+impl StdpParams {
+    /// Decay every neuron's traces over a step of `dt`.
+    pub(crate) fn decay_traces(&self, neurons: &mut [Neuron], dt: f64) {
+        let pre_decay = (-dt / self.tau_plus).exp();
+        let post_decay = (-dt / self.tau_minus).exp();
+        for neuron in neurons {
+            neuron.pre_trace *= pre_decay;
+            neuron.post_trace *= post_decay;
+        }
+    }
+
+    /// The change this step's spikes make to a plastic send from `pre` to
+    /// `post`, before clamping; call between `decay_traces` and `mark_spikes`.
+    pub(crate) fn weight_change(&self, pre: &Neuron, post: &Neuron) -> f64 {
+        let mut dw = 0.0;
+        if post.just_fired {
+            dw += self.a_plus * pre.pre_trace;
+        }
+        if pre.just_fired {
+            dw -= self.a_minus * post.post_trace;
+        }
+        dw
+    }
+
+    /// The weight bounds of a connection with parameters `synapse`.
+    pub(crate) fn bounds(&self, synapse: Option<&SynapseParams>) -> (f64, f64) {
+        (
+            synapse.and_then(|s| s.w_min).unwrap_or(self.w_min),
+            synapse.and_then(|s| s.w_max).unwrap_or(self.w_max),
+        )
+    }
+
+    /// Bump the traces of the neurons that fired.
+    pub(crate) fn mark_spikes(neurons: &mut [Neuron]) {
+        for neuron in neurons {
+            if neuron.just_fired {
+                neuron.pre_trace += 1.0;
+                neuron.post_trace += 1.0;
+            }
+        }
+    }
+}
+
+/// Time course of a stimulus within its window.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum StimulusShape {
+    /// Constant `amplitude` for the whole window.
+    #[default]
+    Step,
+    /// `amplitude` for the first `width_steps` of every `period_steps`, zero otherwise.
+    Pulse {
+        period_steps: usize,
+        width_steps: usize,
+    },
+    /// Linear from `amplitude` on the first step of the window to
+    /// `end_amplitude` on the last.
+    Ramp { end_amplitude: f64 },
+    /// `amplitude` times `Network::stimulus_traces[index]`, read at the offset
+    /// into the window; see `Stimulus::from_csv`. Zero if there is no such trace.
+    Trace(usize),
+}
+
+/// A current injected into one neuron for a window of steps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stimulus {
+    pub neuron: usize,
+    pub start_step: usize,
+    pub duration_steps: usize,
+    pub amplitude: f64,
+    pub shape: StimulusShape,
+}
+
+impl Stimulus {
+    /// A constant (step) stimulus.
+    pub fn new(neuron: usize, start_step: usize, duration_steps: usize, amplitude: f64) -> Self {
+        Self {
+            neuron,
+            start_step,
+            duration_steps,
+            amplitude,
+            shape: StimulusShape::Step,
+        }
+    }
+
+    pub fn is_active(&self, step: usize) -> bool {
+        step >= self.start_step && step - self.start_step < self.duration_steps
+    }
+
+    /// Current delivered on `step`; zero outside the window. `traces` are the
+    /// network's `stimulus_traces`, which a `Trace` shape indexes.
+    pub fn current_at(&self, step: usize, traces: &[StimulusTrace]) -> f64 {
+        if !self.is_active(step) {
+            return 0.0;
+        }
+        let offset = step - self.start_step;
+        match self.shape {
+            StimulusShape::Step => self.amplitude,
+            StimulusShape::Pulse {
+                period_steps,
+                width_steps,
+            } => {
+                if offset % period_steps.max(1) < width_steps {
+                    self.amplitude
+                } else {
+                    0.0
+                }
+            }
+            StimulusShape::Ramp { end_amplitude } => {
+                let span = self.duration_steps.saturating_sub(1).max(1) as f64;
+                self.amplitude + (end_amplitude - self.amplitude) * offset as f64 / span
+            }
+            StimulusShape::Trace(index) => traces
+                .get(index)
+                .map_or(0.0, |trace| self.amplitude * trace.value_at(offset as f64)),
+        }
+    }
+}
+
+/// The spread of membrane time constants over a network's neurons, from
+/// `Network::time_constant_summary`, in `lif.time_unit`; all zero without
+/// neurons.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TimeConstantSummary {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// Neurons whose `tau` comes from `Network::lif_overrides`.
+    pub overridden: usize,
+    /// The neuron with the smallest `tau`, ties to the lowest id.
+    pub fastest: Option<usize>,
+    /// `Network::suggest_dt`, which also allows for STDP trace constants.
+    pub suggested_dt: f64,
+}
+
+/// Progress of a run, passed to a `StepObserver` after each step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepInfo {
+    /// Index of the step just completed.
+    pub step: usize,
+    /// Steps the run was asked for.
+    pub steps: usize,
+    pub dt: f64,
+    /// Neurons that fired on this step.
+    pub spikes: usize,
+    pub total_spikes: usize,
+}
+
+impl StepInfo {
+    /// Simulated time at the end of this step, in the model's time unit.
+    pub fn time(&self) -> f64 {
+        (self.step + 1) as f64 * self.dt
+    }
+
+    pub fn total_time(&self) -> f64 {
+        self.steps as f64 * self.dt
+    }
+}
+
+/// Watches a run step by step, for progress reporting or early stopping.
+pub trait StepObserver {
+    /// Return `ControlFlow::Break` to end the run after this step.
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()>;
+}
+
+impl<F: FnMut(&StepInfo, &Network) -> ControlFlow<()>> StepObserver for F {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        self(info, network)
+    }
+}
+
+/// Network-wide activity of a run, cheap scalars for comparing dynamical
+/// regimes across parameter sweeps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivityTotals {
+    /// Spikes fired by all neurons, as `SimulationResult::total_spikes`.
+    pub spikes: usize,
+    /// Synaptic transmission events: every spike counts once for each chemical
+    /// send leaving the neuron that fired, whatever its weight.
+    pub transmissions: usize,
+    /// Sum over steps and neurons of `|membrane potential - resting potential|
+    /// * dt`, taking the potential after each step.
+    pub potential_integral: f64,
+    /// Spikes fired on each step, for telling silent stretches from runaway
+    /// ones.
+    pub spikes_per_step: Vec<usize>,
+}
+
+/// Spike trains (and optionally potential traces) produced by a run.
+#[derive(Debug, Clone, Default)]
+pub struct SimulationResult {
+    pub dt: f64,
+    pub steps: usize,
+    /// Steps at which each neuron fired, indexed by neuron id, stored as
+    /// `Network::spike_storage` selects.
+    pub spike_trains: Vec<SpikeTrain>,
+    /// Membrane potential of the watched neurons, sampled after every
+    /// `potential_stride`-th step (the first sample follows step 0).
+    pub potentials: HashMap<usize, Vec<f64>>,
+    pub potential_stride: usize,
+    /// The event log of the run, empty unless `Network::event_verbosity` was set.
+    pub events: Vec<SimEvent>,
+    /// How many times each neuron went into depolarization block, indexed by
+    /// neuron id; all zero unless `LifParams::depolarization_block` is set.
+    pub depolarization_blocks: Vec<usize>,
+    pub activity: ActivityTotals,
+}
+
+impl SimulationResult {
+    /// Record the spikes and activity of `neurons`, governed by `lif`, after
+    /// `step`, returning how many fired.
+    pub(crate) fn record_step<'a>(
+        &mut self,
+        step: usize,
+        neurons: &[Neuron],
+        lif: impl Fn(usize) -> &'a LifParams,
+        fan_out: &[usize],
+    ) -> usize {
+        let mut spikes = 0;
+        let mut potential = 0.0;
+        for (id, neuron) in neurons.iter().enumerate() {
+            potential += (neuron.membrane_potential - lif(id).resting_potential).abs();
+            if neuron.just_fired {
+                if let Some(train) = self.spike_trains.get_mut(id) {
+                    train.push(step);
+                }
+                self.activity.transmissions += fan_out[id];
+                spikes += 1;
+            }
+            if neuron.just_blocked {
+                self.depolarization_blocks[id] += 1;
+            }
+        }
+        self.activity.spikes += spikes;
+        self.activity.potential_integral += potential * self.dt;
+        self.activity.spikes_per_step.push(spikes);
+        spikes
+    }
+
+    pub fn spike_count(&self, id: usize) -> usize {
+        self.spike_trains.get(id).map_or(0, SpikeTrain::len)
+    }
+
+    /// Spikes per unit time over the whole run.
+    pub fn firing_rate(&self, id: usize) -> f64 {
+        let duration = self.steps as f64 * self.dt;
+        if duration > 0.0 {
+            self.spike_count(id) as f64 / duration
+        } else {
+            0.0
+        }
+    }
+
+    /// Steps from `onset_step` (e.g. a `Stimulus::start_step`) to each
+    /// neuron's first spike on or after it, keyed by every neuron id of the
+    /// run; `None` for neurons silent from the onset on.
+    pub fn response_latencies(&self, onset_step: usize) -> HashMap<usize, Option<usize>> {
+        self.spike_trains
+            .iter()
+            .enumerate()
+            .map(|(id, train)| {
+                let first = train.iter().find(|&step| step >= onset_step);
+                (id, first.map(|step| step - onset_step))
+            })
+            .collect()
+    }
+
+    pub fn total_spikes(&self) -> usize {
+        self.spike_trains.iter().map(SpikeTrain::len).sum()
+    }
+
+    /// Spikes of `neuron` in a window of `window` steps starting every
+    /// `stride` steps from step 0, one value per start before the end of the
+    /// run. Windows cut short by the end are shrunk, their count scaled up to
+    /// a full window, so steady firing reads the same to the last value.
+    /// Zero `window` or `stride` counts as 1.
+    pub fn windowed_rates(&self, neuron: usize, window: usize, stride: usize) -> Vec<f64> {
+        self.windowed_mean(&[neuron], window, stride)
+    }
+
+    /// `windowed_rates` averaged over `neurons`; empty without neurons.
+    pub fn population_windowed_rates(
+        &self,
+        neurons: &[usize],
+        window: usize,
+        stride: usize,
+    ) -> Vec<f64> {
+        if neurons.is_empty() {
+            return Vec::new();
+        }
+        self.windowed_mean(neurons, window, stride)
+    }
+
+    fn windowed_mean(&self, neurons: &[usize], window: usize, stride: usize) -> Vec<f64> {
+        let (window, stride) = (window.max(1), stride.max(1));
+        // Spikes before each step, over `neurons`.
+        let mut before = vec![0usize; self.steps + 1];
+        for train in neurons.iter().filter_map(|&id| self.spike_trains.get(id)) {
+            for step in train.iter().filter(|&step| step < self.steps) {
+                before[step + 1] += 1;
+            }
+        }
+        for step in 0..self.steps {
+            before[step + 1] += before[step];
+        }
+        (0..self.steps)
+            .step_by(stride)
+            .map(|start| {
+                let end = (start + window).min(self.steps);
+                let spikes = (before[end] - before[start]) as f64;
+                spikes * window as f64 / (end - start) as f64 / neurons.len() as f64
+            })
+            .collect()
+    }
+
+    /// Write one `time,neuron` row per spike, ordered by time then neuron id.
+    pub fn write_spikes_csv<W: Write>(
+        &self,
+        network: &Network,
+        writer: W,
+    ) -> Result<(), FlymindError> {
+        let mut events: Vec<(usize, usize)> = self
+            .spike_trains
+            .iter()
+            .enumerate()
+            .flat_map(|(id, train)| train.iter().map(move |step| (step, id)))
+            .collect();
+        events.sort_unstable();
+
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["time", "neuron"])?;
+        for (step, id) in events {
+            let name = network.neurons.get(id).map_or("", |n| n.name());
+            wtr.write_record([(step as f64 * self.dt).to_string().as_str(), name])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write the recorded potentials as a `time` column followed by one column
+    /// per watched neuron, in id order.
+    pub fn write_voltages_csv<W: Write>(
+        &self,
+        network: &Network,
+        writer: W,
+    ) -> Result<(), FlymindError> {
+        network.write_potential_traces_csv(
+            &self.potentials,
+            self.dt,
+            self.potential_stride,
+            &TraceColumns::Id,
+            writer,
+        )
+    }
+}
+
+/// Column order of `Network::write_potential_traces_csv`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TraceColumns {
+    /// By neuron id.
+    #[default]
+    Id,
+    /// Head to tail by `soma_position`, ties by id.
+    SomaPosition,
+    /// As listed; ids without a trace, or listed twice, are skipped.
+    Given(Vec<usize>),
+}
+
+impl Network {
+    /// Write potential traces, such as `SimulationResult::potentials`, in
+    /// wide form: a `time` column, then one column per traced neuron headed
+    /// by its name (its id if not in this network), ordered by `columns`.
+    /// Sample `i` is a row at `i * stride` steps of `dt`. A trace shorter than
+    /// the longest leaves its cells empty past its end.
+    pub fn write_potential_traces_csv<W: Write>(
+        &self,
+        traces: &HashMap<usize, Vec<f64>>,
+        dt: f64,
+        stride: usize,
+        columns: &TraceColumns,
+        writer: W,
+    ) -> Result<(), FlymindError> {
+        let mut ids: Vec<usize> = match columns {
+            TraceColumns::Given(ids) => {
+                let mut seen = Vec::new();
+                for &id in ids {
+                    if traces.contains_key(&id) && !seen.contains(&id) {
+                        seen.push(id);
+                    }
+                }
+                seen
+            }
+            _ => traces.keys().copied().collect(),
+        };
+        match columns {
+            TraceColumns::Id => ids.sort_unstable(),
+            TraceColumns::SomaPosition => {
+                let position =
+                    |id: usize| self.neurons.get(id).map_or(f64::MAX, |n| n.soma_position);
+                ids.sort_unstable_by(|&a, &b| position(a).total_cmp(&position(b)).then(a.cmp(&b)));
+            }
+            TraceColumns::Given(_) => {}
+        }
+
+        let mut wtr = csv::Writer::from_writer(writer);
+        let mut header = vec!["time".to_string()];
+        header.extend(ids.iter().map(|&id| {
+            self.neurons
+                .get(id)
+                .map_or_else(|| id.to_string(), |n| n.name().to_string())
+        }));
+        wtr.write_record(&header)?;
+
+        let samples = ids.iter().map(|id| traces[id].len()).max().unwrap_or(0);
+        let stride = stride.max(1);
+        for sample in 0..samples {
+            let mut record = vec![((sample * stride) as f64 * dt).to_string()];
+            record.extend(ids.iter().map(|id| {
+                traces[id]
+                    .get(sample)
+                    .map_or_else(String::new, ToString::to_string)
+            }));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Steps a network one at a time, from `Network::step_iter`.
+pub struct StepIter<'a> {
+    network: &'a mut Network,
+    dt: f64,
+}
+
+impl StepIter<'_> {
+    /// The network as of the last step, e.g. for reading potentials.
+    pub fn network(&self) -> &Network {
+        self.network
+    }
+}
+
+impl Iterator for StepIter<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        self.network.update_step(self.dt);
+        Some(
+            self.network
+                .neurons
+                .iter()
+                .enumerate()
+                .filter(|(_, neuron)| neuron.just_fired)
+                .map(|(id, _)| id)
+                .collect(),
+        )
+    }
+}
+
+impl Drop for StepIter<'_> {
+    fn drop(&mut self) {
+        self.network.clear_run_caches();
+    }
+}
+
+/// Dynamic state of a network, from `Network::snapshot_state`.
+#[derive(Debug, Clone)]
+pub struct NetworkState {
+    pub current_step: usize,
+    neurons: Vec<NeuronState>,
+    /// Activation and drive of every muscle.
+    muscles: Vec<(f64, f64)>,
+    weights: Option<Vec<f64>>,
+    rng: Rng,
+}
+
+#[derive(Debug, Clone)]
+struct NeuronState {
+    membrane_potential: f64,
+    just_fired: bool,
+    refractory_remaining: usize,
+    rapid_spikes: usize,
+    just_blocked: bool,
+    pre_trace: f64,
+    post_trace: f64,
+    modulation: f64,
+    rate_estimate: f64,
+}
+
+/// How a neuron fired, from `Neuron::integrate`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Firing {
+    /// The potential that crossed threshold, before the reset.
+    pub potential: f64,
+    /// Steps the neuron is now held at reset.
+    pub silent_steps: usize,
+    /// Whether it went into depolarization block.
+    pub blocked: bool,
+}
+
+impl Neuron {
+    /// How far the potential has moved from rest towards threshold, clamped
+    /// to `[0, 1]`: the release level of a graded extrasynaptic edge.
+    pub fn graded_activity(&self, params: &LifParams) -> f64 {
+        if params.threshold == 0.0 {
+            return 0.0;
+        }
+        ((self.membrane_potential - params.resting_potential) / params.threshold).clamp(0.0, 1.0)
+    }
+
+    /// Decay `modulation` over a step of `dt` and add `drive` to it, returning
+    /// the factor this step's input is scaled by.
+    pub(crate) fn modulate(&mut self, params: &LifParams, drive: f64, dt: f64) -> f64 {
+        if self.modulation != 0.0 || drive != 0.0 {
+            self.modulation = self.modulation * (-dt / params.modulation_tau).exp() + drive;
+        }
+        (1.0 + self.modulation).max(0.0)
+    }
+
+    /// Advance this neuron one step of length `dt` under `params`, with
+    /// continuous `current` and instantaneous `jump` input. Returns how it
+    /// fired, if it did.
+    pub(crate) fn integrate(
+        &mut self,
+        params: &LifParams,
+        current: f64,
+        jump: f64,
+        dt: f64,
+        rng: &mut Rng,
+    ) -> Option<Firing> {
+        self.just_blocked = false;
+        if self.refractory_remaining > 0 {
+            self.refractory_remaining -= 1;
+            self.membrane_potential = params.absolute_reset();
+            self.just_fired = false;
+            return None;
+        }
+
+        let rest = params.resting_potential;
+        let v = self.membrane_potential - rest;
+        let mut next = rest + params.integration.integrate(v, current, params.tau, dt) + jump;
+        if params.noise_std > 0.0 {
+            next += params.noise_std * dt.sqrt() * rng.normal();
+        }
+        self.membrane_potential = next;
+
+        if !params
+            .threshold_crossing
+            .crosses(self.membrane_potential, params.absolute_threshold())
+        {
+            self.just_fired = false;
+            self.rapid_spikes = 0;
+            return None;
+        }
+        self.rapid_spikes += 1;
+        let block = params
+            .depolarization_block
+            .filter(|block| self.rapid_spikes > block.max_rapid_spikes);
+        let firing = Firing {
+            potential: self.membrane_potential,
+            silent_steps: block.map_or(params.refractory_steps, |b| b.recovery_steps),
+            blocked: block.is_some(),
+        };
+        self.just_fired = true;
+        self.membrane_potential = params.absolute_reset();
+        self.refractory_remaining = firing.silent_steps;
+        if firing.blocked {
+            self.just_blocked = true;
+            self.rapid_spikes = 0;
+        }
+        Some(firing)
+    }
+}
+
+impl Network {
+    /// Parameters governing neuron `id`: its override if any, otherwise `lif`.
+    pub fn lif_for(&self, id: usize) -> &LifParams {
+        self.lif_overrides.get(&id).unwrap_or(&self.lif)
+    }
+
+    /// Advance every neuron by one Euler step of length `dt`.
+    ///
+    /// Chemical sends from neurons that fired on the previous step deliver an
+    /// instantaneous jump (negative for inhibitory synapses); gap junctions,
+    /// active stimuli and `bias_currents` contribute continuous current, each
+    /// gap-junction row conducting into its `to_id` only (see
+    /// `GapJunctionMode`). `ChemicalReceive` rows are the mirrored record of a
+    /// send and NMJ rows target muscle, so neither drives input.
+    /// Modulatory sends instead raise the target's `Neuron::modulation` by
+    /// `modulation_gain * weight`; it decays with `modulation_tau`, and all of
+    /// a neuron's input, stimuli included, is scaled by `1 + modulation`.
+    /// Synaptic gains and `InputNormalization` are those of the postsynaptic
+    /// neuron, the gains scaled by each connection's `SynapseParams::gain`;
+    /// modulatory sends do not count towards normalization.
+    /// A send whose `SynapseParams::release_probability` is below 1 transmits
+    /// each spike only with that probability, drawn from the seeded generator.
+    /// Extrasynaptic edges raise it too, continuously, by
+    /// `extrasynaptic_gain * weight * dt` times the source's
+    /// `Neuron::graded_activity`.
+    /// A neuron with `LifParams::max_inputs` set takes only that many of its
+    /// fast inputs, ranked by the weights at the start of the step.
+    /// With `stdp` set, the weights of plastic sends are updated after the
+    /// spikes of this step are known, within their `SynapseParams` bounds.
+    /// Neurons kept firing at their maximum rate go into
+    /// `LifParams::depolarization_block`, if set.
+    /// Muscles then integrate the step's spikes (see `crate::muscle`), and events
+    /// are logged as `event_verbosity` asks.
+    pub fn update_step(&mut self, dt: f64) {
+        let cached = self.step_inputs.is_some();
+        let inputs = self
+            .step_inputs
+            .take()
+            .unwrap_or_else(|| StepInputs::compile(self, self.resolve_synapses().as_deref()));
+        let cached_limits = self.input_limits.is_some();
+        let limits = self
+            .input_limits
+            .take()
+            .unwrap_or_else(|| self.limits_of(&inputs));
+        let by_rate = self.synaptic_input != SynapticInput::Spikes;
+        self.synaptic_input.update_rates(&mut self.neurons, dt);
+        let failed = if by_rate {
+            None
+        } else {
+            inputs.release_failures(&self.neurons, &mut self.rng)
+        };
+        let by_events = self.steps_by_events();
+        let active = if by_events {
+            self.active_neurons()
+        } else {
+            None
+        };
+        let lif = |id: usize| self.lif_overrides.get(&id).unwrap_or(&self.lif);
+        let delivery = Delivery {
+            limits: &limits,
+            failed: failed.as_deref(),
+            by_rate,
+            active: active.as_deref(),
+        };
+        let connections = &self.connections;
+        let mut drive = inputs.drive(
+            &self.neurons,
+            lif,
+            |index| connections[index].weight,
+            delivery,
+            dt,
+        );
+        drive.add_external(
+            &self.stimuli,
+            &self.stimulus_traces,
+            self.bias_currents.iter().map(|(&id, &bias)| (id, bias)),
+            self.current_step,
+        );
+        let before: Vec<(usize, [u64; 8])> = if by_events {
+            (0..self.neurons.len())
+                .filter(|&id| active.as_ref().is_none_or(|active| active[id]))
+                .map(|id| (id, dynamic_state(&self.neurons[id])))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        drive.modulate(&mut self.neurons, lif, dt, active.as_deref());
+
+        let step = self.current_step;
+        let log_spikes = self.event_verbosity >= EventVerbosity::Spikes;
+        if self.event_verbosity >= EventVerbosity::Inputs {
+            for (neuron, (&jump, &current)) in drive.jump.iter().zip(&drive.current).enumerate() {
+                if jump != 0.0 || current != 0.0 {
+                    self.events.push(SimEvent {
+                        step,
+                        neuron,
+                        kind: EventKind::Input { jump, current },
+                    });
+                }
+            }
+        }
+
+        let events = &mut self.events;
+        drive.integrate(
+            &mut self.neurons,
+            lif,
+            dt,
+            &mut self.rng,
+            active.as_deref(),
+            |neuron, firing| {
+                if !log_spikes {
+                    return;
+                }
+                events.push(SimEvent {
+                    step,
+                    neuron,
+                    kind: EventKind::Threshold {
+                        potential: firing.potential,
+                    },
+                });
+                if firing.blocked {
+                    events.push(SimEvent {
+                        step,
+                        neuron,
+                        kind: EventKind::DepolarizationBlock {
+                            steps: firing.silent_steps,
+                        },
+                    });
+                } else if firing.silent_steps > 0 {
+                    events.push(SimEvent {
+                        step,
+                        neuron,
+                        kind: EventKind::Refractory {
+                            steps: firing.silent_steps,
+                        },
+                    });
+                }
+            },
+        );
+
+        if by_events {
+            let changed: Vec<usize> = before
+                .into_iter()
+                .filter(|&(id, state)| {
+                    let neuron = &self.neurons[id];
+                    neuron.just_fired || neuron.just_blocked || dynamic_state(neuron) != state
+                })
+                .map(|(id, _)| id)
+                .collect();
+            self.advance_wavefront(&changed);
+        }
+
+        self.update_muscles(dt);
+        let mut reweighted = false;
+        if let Some(stdp) = self.stdp {
+            let connections = &self.connections;
+            let changed = inputs.apply_stdp(&stdp, &mut self.neurons, dt, |index| {
+                connections[index].weight
+            });
+            reweighted = !changed.is_empty();
+            for (index, weight) in changed {
+                self.connections[index].weight = weight;
+            }
+        }
+        if cached_limits {
+            self.input_limits = Some(if reweighted {
+                self.limits_of(&inputs)
+            } else {
+                limits
+            });
+        }
+        if cached {
+            self.step_inputs = Some(inputs);
+        }
+
+        self.current_step += 1;
+    }
+
+    /// `max_inputs` and normalization of `inputs` for the weights and
+    /// parameters as they are now.
+    fn limits_of(&self, inputs: &StepInputs) -> InputLimits {
+        inputs.limits(
+            |id| self.lif_for(id),
+            |index| self.connections[index].weight,
+        )
+    }
+
+    /// Compile the inputs and resolve the limits and synapses a run keeps
+    /// from step to step; see `clear_run_caches`.
+    pub(crate) fn start_run_caches(&mut self) {
+        self.synapses = self.resolve_synapses();
+        let inputs = StepInputs::compile(self, self.synapses.as_deref());
+        self.input_limits = Some(self.limits_of(&inputs));
+        self.step_inputs = Some(inputs);
+        self.wavefront = self.start_wavefront();
+    }
+
+    /// Drop what `start_run_caches` kept, as a run ends.
+    pub(crate) fn clear_run_caches(&mut self) {
+        self.synapses = None;
+        self.step_inputs = None;
+        self.input_limits = None;
+        self.wavefront = None;
+    }
+
+    /// Resolve `input_limits` again if a run is keeping them, after its
+    /// weights or parameters changed.
+    pub(crate) fn refresh_input_limits(&mut self) {
+        if let (Some(inputs), Some(_)) = (&self.step_inputs, &self.input_limits) {
+            self.input_limits = Some(self.limits_of(inputs));
+        }
+    }
+
+    /// Counted chemical sends leaving each neuron, for
+    /// `ActivityTotals::transmissions`.
+    pub(crate) fn fan_out(&self) -> Vec<usize> {
+        let mut fan_out = vec![0; self.neurons.len()];
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalSend(_)) && self.counts(conn) {
+                fan_out[conn.from_id] += 1;
+            }
+        }
+        fan_out
+    }
+
+    /// Time constants the integration has to resolve: every `tau` in use and,
+    /// with plasticity on, the STDP trace constants.
+    fn integration_time_constants(&self) -> Vec<f64> {
+        let mut constants: Vec<f64> = std::iter::once(&self.lif)
+            .chain(self.lif_overrides.values())
+            .map(|p| p.tau)
+            .collect();
+        if let Some(stdp) = &self.stdp {
+            constants.extend([stdp.tau_plus, stdp.tau_minus]);
+        }
+        constants
+    }
+
+    /// Each neuron's membrane time constant, by id, in `lif.time_unit`: its
+    /// override's `tau`, or the shared `lif.tau` for neurons without one.
+    pub fn time_constants(&self) -> HashMap<usize, f64> {
+        self.neurons
+            .iter()
+            .map(|neuron| (neuron.id, self.lif_for(neuron.id).tau))
+            .collect()
+    }
+
+    /// The spread of `time_constants`, with the step it allows, for checking
+    /// the timescales before a long run.
+    pub fn time_constant_summary(&self) -> TimeConstantSummary {
+        let mut taus: Vec<(f64, usize)> = self
+            .neurons
+            .iter()
+            .map(|n| (self.lif_for(n.id).tau, n.id))
+            .collect();
+        taus.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let mut summary = TimeConstantSummary {
+            overridden: self
+                .neurons
+                .iter()
+                .filter(|n| self.lif_overrides.contains_key(&n.id))
+                .count(),
+            suggested_dt: self.suggest_dt(),
+            ..TimeConstantSummary::default()
+        };
+        let n = taus.len();
+        if n == 0 {
+            return summary;
+        }
+        summary.min = taus[0].0;
+        summary.max = taus[n - 1].0;
+        summary.mean = taus.iter().map(|&(tau, _)| tau).sum::<f64>() / n as f64;
+        summary.median = if n % 2 == 1 {
+            taus[n / 2].0
+        } else {
+            (taus[n / 2 - 1].0 + taus[n / 2].0) / 2.0
+        };
+        summary.fastest = Some(taus[0].1);
+        summary
+    }
+
+    /// The largest accurate step for the smallest time constant in use under
+    /// `lif.integration`, in `lif.time_unit`.
+    pub fn suggest_dt(&self) -> f64 {
+        let smallest = self
+            .integration_time_constants()
+            .into_iter()
+            .fold(f64::INFINITY, f64::min);
+        smallest * self.lif.integration.accuracy_limit()
+    }
+
+    /// Check `dt` (in `lif.time_unit`) before a run.
+    ///
+    /// Fails if `dt` is not positive or is past the stability limit of
+    /// `lif.integration` for the smallest time constant; returns a warning if it
+    /// is more than `suggest_dt()`, where it is stable but inaccurate.
+    pub fn check_dt(&self, dt: f64) -> Result<Vec<String>, FlymindError> {
+        let smallest = self
+            .integration_time_constants()
+            .into_iter()
+            .fold(f64::INFINITY, f64::min);
+        let method = self.lif.integration;
+        if !(dt.is_finite() && dt > 0.0) {
+            return Err(FlymindError::InvalidTimeStep { dt, max_dt: None });
+        }
+        let max_dt = smallest * method.stability_limit();
+        if dt > max_dt {
+            return Err(FlymindError::InvalidTimeStep {
+                dt,
+                max_dt: Some(max_dt),
+            });
+        }
+        let mut warnings = Vec::new();
+        let suggested = self.suggest_dt();
+        if dt > suggested {
+            let unit = self.lif.time_unit.label();
+            warnings.push(format!(
+                "dt = {dt} {unit} is coarse for the smallest time constant ({smallest} {unit}) \
+                 under {method:?} integration; consider dt <= {suggested} {unit}"
+            ));
+        }
+        Ok(warnings)
+    }
+
+    /// Reseed the generator behind noise currents.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Return every neuron to rest and rewind the simulation clock.
+    pub fn reset_state(&mut self) {
+        for neuron in &mut self.neurons {
+            neuron.membrane_potential = self
+                .lif_overrides
+                .get(&neuron.id)
+                .unwrap_or(&self.lif)
+                .resting_potential;
+            neuron.just_fired = false;
+            neuron.refractory_remaining = 0;
+            neuron.rapid_spikes = 0;
+            neuron.just_blocked = false;
+            neuron.pre_trace = 0.0;
+            neuron.post_trace = 0.0;
+            neuron.modulation = 0.0;
+            neuron.rate_estimate = 0.0;
+        }
+        for muscle in &mut self.muscles {
+            muscle.activation = 0.0;
+            muscle.drive = 0.0;
+        }
+        self.current_step = 0;
+        self.events.clear();
+    }
+
+    /// Set `Connection::plastic` on the connections `filter` selects and return
+    /// how many changed. Only chemical connections can be made plastic, so gap
+    /// junctions and NMJs are left alone even if selected.
+    pub fn set_plasticity<F>(&mut self, mut filter: F, plastic: bool) -> usize
+    where
+        F: FnMut(&Network, &Connection) -> bool,
+    {
+        let selected: Vec<bool> = self
+            .connections
+            .iter()
+            .map(|conn| filter(self, conn))
+            .collect();
+        let mut changed = Vec::new();
+        for (index, (conn, selected)) in self.connections.iter_mut().zip(selected).enumerate() {
+            let allowed = !plastic || conn.synapse_type.is_chemical();
+            if selected && allowed && conn.plastic != plastic {
+                conn.plastic = plastic;
+                changed.push(index);
+            }
+        }
+        let count = changed.len();
+        self.record_plasticity(changed, plastic);
+        count
+    }
+
+    /// Capture the dynamic state (potentials, refractory counters, STDP traces,
+    /// muscle activations, the clock and the noise generator) so a run can be forked from here.
+    /// Weights are included only while `stdp` is set, since nothing else changes
+    /// them during a run.
+    pub fn snapshot_state(&self) -> NetworkState {
+        NetworkState {
+            current_step: self.current_step,
+            neurons: self
+                .neurons
+                .iter()
+                .map(|neuron| NeuronState {
+                    membrane_potential: neuron.membrane_potential,
+                    just_fired: neuron.just_fired,
+                    refractory_remaining: neuron.refractory_remaining,
+                    rapid_spikes: neuron.rapid_spikes,
+                    just_blocked: neuron.just_blocked,
+                    pre_trace: neuron.pre_trace,
+                    post_trace: neuron.post_trace,
+                    modulation: neuron.modulation,
+                    rate_estimate: neuron.rate_estimate,
+                })
+                .collect(),
+            muscles: self
+                .muscles
+                .iter()
+                .map(|m| (m.activation, m.drive))
+                .collect(),
+            weights: self
+                .stdp
+                .is_some()
+                .then(|| self.connections.iter().map(|conn| conn.weight).collect()),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Return to a state from `snapshot_state` on this network (or one with the
+    /// same neurons, muscles and connections). Stimuli and parameters are left as they
+    /// are, so a restored run can continue under a different protocol.
+    pub fn restore_state(&mut self, state: &NetworkState) -> Result<(), FlymindError> {
+        if state.neurons.len() != self.neurons.len() {
+            return Err(FlymindError::StateMismatch {
+                what: "neurons",
+                state: state.neurons.len(),
+                network: self.neurons.len(),
+            });
+        }
+        if state.muscles.len() != self.muscles.len() {
+            return Err(FlymindError::StateMismatch {
+                what: "muscles",
+                state: state.muscles.len(),
+                network: self.muscles.len(),
+            });
+        }
+        if let Some(weights) = &state.weights {
+            if weights.len() != self.connections.len() {
+                return Err(FlymindError::StateMismatch {
+                    what: "connections",
+                    state: weights.len(),
+                    network: self.connections.len(),
+                });
+            }
+            for (conn, &weight) in self.connections.iter_mut().zip(weights) {
+                conn.weight = weight;
+            }
+        }
+        for (neuron, saved) in self.neurons.iter_mut().zip(&state.neurons) {
+            neuron.membrane_potential = saved.membrane_potential;
+            neuron.just_fired = saved.just_fired;
+            neuron.refractory_remaining = saved.refractory_remaining;
+            neuron.rapid_spikes = saved.rapid_spikes;
+            neuron.just_blocked = saved.just_blocked;
+            neuron.pre_trace = saved.pre_trace;
+            neuron.post_trace = saved.post_trace;
+            neuron.modulation = saved.modulation;
+            neuron.rate_estimate = saved.rate_estimate;
+        }
+        for (muscle, &(activation, drive)) in self.muscles.iter_mut().zip(&state.muscles) {
+            muscle.activation = activation;
+            muscle.drive = drive;
+        }
+        self.current_step = state.current_step;
+        self.rng = state.rng.clone();
+        Ok(())
+    }
+
+    /// Step the network one `update_step` of `dt` per `next()`, yielding the
+    /// ids of the neurons that fired, ascending. Nothing is recorded and the
+    /// iterator never ends, so take as many steps as needed and drop it.
+    /// Logged events pile up as under `update_step`; see `take_events`.
+    pub fn step_iter(&mut self, dt: f64) -> StepIter<'_> {
+        self.start_run_caches();
+        StepIter { network: self, dt }
+    }
+
+    pub fn run_simulation(&mut self, steps: usize, dt: f64) -> SimulationResult {
+        self.run_simulation_recording(steps, dt, &[])
+    }
+
+    /// Like `run_simulation`, also recording the potential of each `watch` neuron.
+    pub fn run_simulation_recording(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+    ) -> SimulationResult {
+        self.run_simulation_strided(steps, dt, watch, 1)
+    }
+
+    /// Like `run_simulation_recording`, keeping one potential sample every `stride` steps.
+    pub fn run_simulation_strided(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+        stride: usize,
+    ) -> SimulationResult {
+        self.run_simulation_observed(
+            steps,
+            dt,
+            watch,
+            stride,
+            &mut |_: &StepInfo, _: &Network| ControlFlow::Continue(()),
+        )
+    }
+
+    /// Like `run_simulation_strided`, calling `observer` after every step.
+    ///
+    /// If the observer breaks, the run stops there and the result covers the
+    /// steps completed so far (`steps` is reduced to match). Events logged
+    /// before the run and not taken are discarded.
+    pub fn run_simulation_observed(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+        stride: usize,
+        observer: &mut dyn StepObserver,
+    ) -> SimulationResult {
+        self.run_simulation_driven(steps, dt, watch, stride, observer, &mut |_| {})
+    }
+
+    /// Like `run_simulation_observed`, calling `before_step` ahead of every
+    /// step so it can change the network's input.
+    pub(crate) fn run_simulation_driven(
+        &mut self,
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+        stride: usize,
+        observer: &mut dyn StepObserver,
+        before_step: &mut dyn FnMut(&mut Network),
+    ) -> SimulationResult {
+        let result = self.empty_result(steps, dt, watch, stride);
+        self.run_into(result, observer, before_step)
+    }
+
+    /// A result for a run of `steps` steps of `dt` with no steps recorded
+    /// yet, keeping the potentials of `watch` every `stride` steps.
+    pub(crate) fn empty_result(
+        &self,
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+        stride: usize,
+    ) -> SimulationResult {
+        let stride = stride.max(1);
+        SimulationResult {
+            dt,
+            steps,
+            spike_trains: vec![SpikeTrain::new(self.spike_storage); self.neurons.len()],
+            potentials: watch
+                .iter()
+                .map(|&id| (id, Vec::with_capacity(steps.div_ceil(stride))))
+                .collect(),
+            potential_stride: stride,
+            events: Vec::new(),
+            depolarization_blocks: vec![0; self.neurons.len()],
+            activity: ActivityTotals {
+                spikes_per_step: Vec::with_capacity(steps),
+                ..ActivityTotals::default()
+            },
+        }
+    }
+
+    /// Run `result.steps` steps of `result.dt`, recording into `result`. A
+    /// result with no spike trains keeps no raster, only its totals.
+    pub(crate) fn run_into(
+        &mut self,
+        mut result: SimulationResult,
+        observer: &mut dyn StepObserver,
+        before_step: &mut dyn FnMut(&mut Network),
+    ) -> SimulationResult {
+        let (steps, dt, stride) = (result.steps, result.dt, result.potential_stride);
+        self.events.clear();
+        self.start_run_caches();
+        let fan_out = self.fan_out();
+
+        let mut total_spikes = 0;
+        for step in 0..steps {
+            before_step(self);
+            self.update_step(dt);
+            let spikes = result.record_step(step, &self.neurons, |id| self.lif_for(id), &fan_out);
+            total_spikes += spikes;
+            if step.is_multiple_of(stride) {
+                for (&id, trace) in result.potentials.iter_mut() {
+                    if let Some(neuron) = self.neurons.get(id) {
+                        trace.push(neuron.membrane_potential);
+                    }
+                }
+            }
+
+            let info = StepInfo {
+                step,
+                steps,
+                dt,
+                spikes,
+                total_spikes,
+            };
+            if observer.observe(&info, self).is_break() {
+                result.steps = step + 1;
+                break;
+            }
+        }
+
+        self.clear_run_caches();
+        result.events = self.take_events();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{DiagnosticsObserver, DiagnosticsOptions};
+    use crate::network::{NeuronType, Region};
+    use crate::spikes::SpikeStorage;
+    use crate::states::BehavioralState;
+    use crate::synapses::SynapseOverride;
+
+    /// The touch circuit with plastic sends, driven hard enough that STDP
+    /// moves weights ranked by `max_inputs` and summed by normalization.
+    fn capped_plastic_circuit() -> Network {
+        let mut network = Network::example_touch_circuit();
+        for conn in &mut network.connections {
+            conn.plastic = true;
+        }
+        network.stdp = Some(StdpParams {
+            a_plus: 2.0,
+            a_minus: 2.0,
+            ..StdpParams::default()
+        });
+        network.lif.max_inputs = Some(4);
+        network.lif.input_normalization = InputNormalization::ByWeight;
+        network.lif.chemical_gain = 10.0;
+        for touch in ["ALML", "ALMR", "AVM", "PLML"] {
+            let id = network.id_of(touch).unwrap();
+            network.stimuli.push(Stimulus::new(id, 0, 300, 0.4));
+        }
+        network
+    }
+
+    fn trains(network: &mut Network, steps: usize) -> Vec<Vec<usize>> {
+        let mut trains = vec![Vec::new(); network.neurons.len()];
+        for step in 0..steps {
+            network.update_step(0.1);
+            for (id, neuron) in network.neurons.iter().enumerate() {
+                if neuron.just_fired {
+                    trains[id].push(step);
+                }
+            }
+        }
+        trains
+    }
+
+    #[test]
+    fn cached_input_limits_follow_stdp() {
+        let mut run = capped_plastic_circuit();
+        let result = run.run_simulation(300, 0.1);
+        let mut stepped = capped_plastic_circuit();
+        let expected = trains(&mut stepped, 300);
+        assert!(result.total_spikes() > 0);
+        for (id, train) in expected.iter().enumerate() {
+            assert_eq!(result.spike_trains[id].to_vec(), *train, "neuron {id}");
+        }
+        let weights = |network: &Network| -> Vec<f64> {
+            network.connections.iter().map(|c| c.weight).collect()
+        };
+        assert_eq!(weights(&run), weights(&stepped));
+        assert!(run.input_limits.is_none());
+    }
+
+    #[test]
+    fn cached_input_limits_follow_a_state_switch() {
+        let mut run = capped_plastic_circuit();
+        run.stdp = None;
+        run.lif.max_inputs = None;
+        run.lif.input_normalization = InputNormalization::None;
+        let mut capped = BehavioralState::of("capped", &run);
+        capped.lif.max_inputs = Some(1);
+        let result = run
+            .run_with_states(&[(150, capped.clone())], 300, 0.1)
+            .unwrap();
+
+        let mut stepped = capped_plastic_circuit();
+        stepped.stdp = None;
+        stepped.lif.max_inputs = None;
+        stepped.lif.input_normalization = InputNormalization::None;
+        let mut expected = trains(&mut stepped, 150);
+        stepped.lif = capped.lif;
+        for (id, later) in trains(&mut stepped, 150).into_iter().enumerate() {
+            expected[id].extend(later.into_iter().map(|step| step + 150));
+        }
+        for (id, train) in expected.iter().enumerate() {
+            assert_eq!(result.spike_trains[id].to_vec(), *train, "neuron {id}");
+        }
+    }
+
+    #[test]
+    fn potentials_are_measured_from_rest() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.lif.resting_potential = -65.0;
+        network.reset_state();
+        let quiet = network.run_simulation(200, 0.1);
+        assert_eq!(quiet.activity.potential_integral, 0.0);
+
+        network.reset_state();
+        network.stimuli.push(Stimulus::new(0, 0, 200, 0.05));
+        let mut diagnostics = DiagnosticsObserver::new(DiagnosticsOptions {
+            check_every: 1,
+            max_abs_potential: 1.0,
+            ..DiagnosticsOptions::default()
+        });
+        let driven = network.run_simulation_observed(200, 0.1, &[], 1, &mut diagnostics);
+        assert!(diagnostics.error().is_none());
+        let offset = network.neurons[0].membrane_potential + 65.0;
+        assert!(offset > 0.0 && offset < 1.0);
+        assert!(driven.activity.potential_integral > 0.0);
+        assert!(driven.activity.potential_integral < 200.0 * 0.1 * offset);
+    }
+
+    #[test]
+    fn certain_release_draws_nothing() {
+        let run = |probability: Option<f64>| {
+            let mut network = Network::example_touch_circuit();
+            network.lif.noise_std = 0.05;
+            network.seed(11);
+            // An override on every connection, so even p = 1 resolves
+            // per-synapse parameters for the run.
+            if let Some(probability) = probability {
+                for index in 0..network.connections.len() {
+                    let layer = SynapseOverride {
+                        release_probability: Some(probability),
+                        ..SynapseOverride::default()
+                    };
+                    network.connection_params.by_connection.insert(index, layer);
+                }
+            }
+            let alml = network.id_of("ALML").unwrap();
+            network.stimuli.push(Stimulus::new(alml, 10, 800, 2.0));
+            let result = network.run_simulation(1000, 0.1);
+            let trains: Vec<Vec<usize>> =
+                result.spike_trains.iter().map(SpikeTrain::to_vec).collect();
+            trains
+        };
+        let plain = run(None);
+        assert!(plain.iter().map(Vec::len).sum::<usize>() > 0);
+        assert_eq!(run(Some(1.0)), plain);
+        assert_ne!(run(Some(0.5)), plain);
+    }
+
+    #[test]
+    fn latencies_count_from_the_onset() {
+        let train = |steps: &[usize]| {
+            let mut train = SpikeTrain::new(SpikeStorage::Steps);
+            for &step in steps {
+                train.push(step);
+            }
+            train
+        };
+        let result = SimulationResult {
+            dt: 0.1,
+            steps: 100,
+            spike_trains: vec![train(&[5, 30, 40]), train(&[20]), train(&[3]), train(&[])],
+            ..SimulationResult::default()
+        };
+        let latencies = result.response_latencies(20);
+        assert_eq!(latencies.len(), 4);
+        assert_eq!(latencies[&0], Some(10));
+        assert_eq!(latencies[&1], Some(0));
+        assert_eq!(latencies[&2], None);
+        assert_eq!(latencies[&3], None);
+    }
+
+    #[test]
+    fn trace_columns_follow_the_order() {
+        let mut network = Network::new();
+        for (name, position) in [("A", 0.5), ("B", 0.1), ("C", 0.5)] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, position);
+        }
+        let traces: HashMap<usize, Vec<f64>> = [
+            (0, vec![1.0, 2.0]),
+            (1, vec![3.0]),
+            (2, vec![4.0, 5.0]),
+            (7, vec![6.0, 7.0]),
+        ]
+        .into_iter()
+        .collect();
+        let csv = |columns: TraceColumns| {
+            let mut out = Vec::new();
+            network
+                .write_potential_traces_csv(&traces, 0.5, 2, &columns, &mut out)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(csv(TraceColumns::Id), "time,A,B,C,7\n0,1,3,4,6\n1,2,,5,7\n");
+        assert_eq!(
+            csv(TraceColumns::SomaPosition).lines().next(),
+            Some("time,B,A,C,7")
+        );
+        assert_eq!(
+            csv(TraceColumns::Given(vec![2, 5, 0, 2])),
+            "time,C,A\n0,4,1\n1,5,2\n"
+        );
+    }
+
+    #[test]
+    fn time_constants_take_overrides_per_neuron() {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C", "D"]);
+        for (id, tau) in [(2, 1.0), (3, 4.0)] {
+            let lif = LifParams { tau, ..network.lif };
+            network.lif_overrides.insert(id, lif);
+        }
+        let taus = network.time_constants();
+        assert_eq!(taus.len(), 4);
+        assert_eq!((taus[&0], taus[&2], taus[&3]), (10.0, 1.0, 4.0));
+
+        let fastest = LifParams {
+            tau: 1.0,
+            ..network.lif
+        };
+        assert_eq!(
+            network.time_constant_summary(),
+            TimeConstantSummary {
+                min: 1.0,
+                max: 10.0,
+                mean: 6.25,
+                median: 7.0,
+                overridden: 2,
+                fastest: Some(2),
+                suggested_dt: fastest.suggest_dt(),
+            }
+        );
+        assert_eq!(Network::new().time_constant_summary().fastest, None);
+    }
+
+    #[test]
+    fn a_rate_estimate_carries_one_spike_in_all() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        let input = SynapticInput::Rate { tau: 5.0 };
+        let dt = 0.25;
+        network.neurons[0].just_fired = true;
+        let mut delivered = 0.0;
+        for _ in 0..2000 {
+            input.update_rates(&mut network.neurons, dt);
+            delivered += network.neurons[0].rate_estimate * dt;
+            assert_eq!(network.neurons[1].rate_estimate, 0.0);
+            network.neurons[0].just_fired = false;
+        }
+        assert!((delivered - 1.0).abs() < 1e-9);
+
+        SynapticInput::Spikes.update_rates(&mut network.neurons, dt);
+        assert!(network.neurons[0].rate_estimate > 0.0);
+    }
+
+    #[test]
+    fn rk4_tracks_exponential_decay_far_closer_than_euler() {
+        // dV/dt = -V / tau + I from V = 1: V(t) = I tau + (1 - I tau) e^(-t / tau).
+        let (tau, current, dt, steps) = (10.0, 0.05, 1.0, 50);
+        let exact = |t: f64| current * tau + (1.0 - current * tau) * (-t / tau).exp();
+        let error = |method: IntegrationMethod| {
+            let mut v = 1.0;
+            let mut worst: f64 = 0.0;
+            for step in 1..=steps {
+                v = method.integrate(v, current, tau, dt);
+                worst = worst.max((v - exact(step as f64 * dt)).abs());
+            }
+            worst
+        };
+        let (euler, rk4) = (
+            error(IntegrationMethod::Euler),
+            error(IntegrationMethod::Rk4),
+        );
+        assert!(euler > 1e-3, "Euler error {euler}");
+        assert!(rk4 * 1e4 < euler, "RK4 error {rk4} against Euler {euler}");
+    }
+}
@@ -0,0 +1,90 @@
+//! A network's structure saved as one JSON document and read back.
+//!
+//! `Network::save_structure` keeps the neurons with their metadata, the
+//! connections, neuromuscular junctions, per-neuron and per-connection
+//! parameters, stimuli (with their traces), bias currents and annotations,
+//! all by id and connection index. Model parameters such as `lif` and `stdp`
+//! belong to a `SimulationConfig`, and run state to `snapshot_state`; neither
+//! is saved.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::edits::Structure;
+use crate::error::FlymindError;
+use crate::network::Network;
+
+impl Network {
+    pub fn write_structure_json<W: Write>(&self, writer: W) -> Result<(), FlymindError> {
+        serde_json::to_writer(writer, &Structure::of(self)).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// A network with the structure `write_structure_json` wrote and default
+    /// settings otherwise. Fails on JSON that is not such a structure, and
+    /// with `FlymindError::Integrity` if its ids and indices disagree.
+    pub fn read_structure_json<R: Read>(reader: R) -> Result<Network, FlymindError> {
+        let structure: Structure = serde_json::from_reader(reader).map_err(io::Error::from)?;
+        let mut network = Network::new();
+        structure.restore(&mut network);
+        network.check_integrity()?;
+        Ok(network)
+    }
+
+    pub fn save_structure<P: AsRef<Path>>(&self, path: P) -> Result<(), FlymindError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_structure_json(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_structure<P: AsRef<Path>>(path: P) -> Result<Network, FlymindError> {
+        Self::read_structure_json(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::AttrValue;
+
+    #[test]
+    fn annotations_survive_a_save_and_load() {
+        let mut network = Network::example_touch_circuit();
+        let avm = network.id_of("AVM").unwrap();
+        network.set_neuron_attr(avm, "lineage", AttrValue::from("QR.pa"));
+        network.set_neuron_attr(avm, "score", AttrValue::from(0.75));
+        network.set_neuron_attr(0, "flagged", AttrValue::from(true));
+        network.set_connection_attr(3, "contacts", AttrValue::from(7_i64));
+
+        let mut json = Vec::new();
+        network.write_structure_json(&mut json).unwrap();
+        let loaded = Network::read_structure_json(json.as_slice()).unwrap();
+
+        assert_eq!(loaded.annotations, network.annotations);
+        assert_eq!(
+            loaded.get_connection_attr(3, "contacts"),
+            Some(&AttrValue::I64(7))
+        );
+        assert_eq!(loaded.id_of("AVM"), Some(avm));
+        assert_eq!(loaded.connections.len(), network.connections.len());
+        assert_eq!(loaded.outgoing_map, network.outgoing_map);
+        assert_eq!(loaded.structural_hash(), network.structural_hash());
+        assert_eq!(
+            loaded.neurons_with_attr("lineage", |v| v.as_str() == Some("QR.pa")),
+            vec![avm]
+        );
+    }
+
+    #[test]
+    fn out_of_range_connections_are_rejected() {
+        let network = Network::example_touch_circuit();
+        let mut json = Vec::new();
+        network.write_structure_json(&mut json).unwrap();
+        let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        value["connections"][0]["to_id"] = 999.into();
+        let result = Network::read_structure_json(value.to_string().as_bytes());
+        assert!(matches!(result, Err(FlymindError::Integrity(_))));
+    }
+}
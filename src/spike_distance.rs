@@ -0,0 +1,175 @@
+//! Distances between spike trains, for telling apart the responses of two
+//! conditions, e.g. a run before and after an ablation.
+//!
+//! Trains are spike times in the run's time unit, as given by
+//! `SimulationResult::spike_times`.
+
+use serde::Serialize;
+
+use crate::simulation::SimulationResult;
+
+/// Victor-Purpura distance: the cheapest way to turn `a` into `b` by adding
+/// or removing spikes (cost 1 each) and moving them (cost `cost_q` per unit
+/// time). Small `cost_q` compares spike counts, large `cost_q` exact timing:
+/// a spike moved by `dt` costs `min(cost_q * dt, 2)`.
+pub fn victor_purpura_distance(a: &[f64], b: &[f64], cost_q: f64) -> f64 {
+    let mut previous: Vec<f64> = (0..=b.len()).map(|j| j as f64).collect();
+    let mut row = vec![0.0; b.len() + 1];
+    for (i, &ta) in a.iter().enumerate() {
+        row[0] = (i + 1) as f64;
+        for (j, &tb) in b.iter().enumerate() {
+            row[j + 1] = (previous[j + 1] + 1.0)
+                .min(row[j] + 1.0)
+                .min(previous[j] + cost_q * (ta - tb).abs());
+        }
+        std::mem::swap(&mut previous, &mut row);
+    }
+    previous[b.len()]
+}
+
+/// Van Rossum distance: the trains are filtered with a causal exponential of
+/// time constant `tau` and compared as `sqrt(integral((f - g)^2) / tau)`. One
+/// train with a single spike against an empty one is `sqrt(1/2)` apart.
+pub fn van_rossum_distance(a: &[f64], b: &[f64], tau: f64) -> f64 {
+    let overlap = |xs: &[f64], ys: &[f64]| -> f64 {
+        xs.iter()
+            .flat_map(|x| ys.iter().map(move |y| (-(x - y).abs() / tau).exp()))
+            .sum()
+    };
+    let squared = (overlap(a, a) + overlap(b, b) - 2.0 * overlap(a, b)) / 2.0;
+    squared.max(0.0).sqrt()
+}
+
+/// A per-neuron spike-train distance.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SpikeDistance {
+    VictorPurpura { cost_q: f64 },
+    VanRossum { tau: f64 },
+}
+
+impl SpikeDistance {
+    pub fn between(&self, a: &[f64], b: &[f64]) -> f64 {
+        match *self {
+            SpikeDistance::VictorPurpura { cost_q } => victor_purpura_distance(a, b, cost_q),
+            SpikeDistance::VanRossum { tau } => van_rossum_distance(a, b, tau),
+        }
+    }
+}
+
+/// Distances between the same neurons in two runs, from
+/// `SimulationResult::distance_to`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PopulationDistance {
+    /// `(neuron id, distance)` for neurons recorded in both runs.
+    pub per_neuron: Vec<(usize, f64)>,
+    /// Requested neurons missing from either run, left out of the totals.
+    pub missing: Vec<usize>,
+}
+
+impl PopulationDistance {
+    pub fn total(&self) -> f64 {
+        self.per_neuron.iter().map(|&(_, d)| d).sum()
+    }
+
+    /// Mean over the compared neurons, or `None` if there were none.
+    pub fn mean(&self) -> Option<f64> {
+        if self.per_neuron.is_empty() {
+            return None;
+        }
+        Some(self.total() / self.per_neuron.len() as f64)
+    }
+}
+
+impl SimulationResult {
+    /// Times of `neuron`'s spikes, empty for neurons outside the run.
+    pub fn spike_times(&self, neuron: usize) -> Vec<f64> {
+        self.spike_trains
+            .get(neuron)
+            .map(|train| train.iter().map(|step| step as f64 * self.dt).collect())
+            .unwrap_or_default()
+    }
+
+    /// `metric` between each of `neurons` here and in `other`, the same
+    /// function either way round. Neurons beyond either run's spike trains are
+    /// listed as missing rather than compared against silence.
+    pub fn distance_to(
+        &self,
+        other: &SimulationResult,
+        neurons: &[usize],
+        metric: SpikeDistance,
+    ) -> PopulationDistance {
+        let mut distance = PopulationDistance::default();
+        for &id in neurons {
+            if id < self.spike_trains.len() && id < other.spike_trains.len() {
+                let d = metric.between(&self.spike_times(id), &other.spike_times(id));
+                distance.per_neuron.push((id, d));
+            } else {
+                distance.missing.push(id);
+            }
+        }
+        distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spikes::{SpikeStorage, SpikeTrain};
+
+    fn result_of(trains: &[&[usize]]) -> SimulationResult {
+        let spike_trains = trains
+            .iter()
+            .map(|spikes| {
+                let mut train = SpikeTrain::new(SpikeStorage::Steps);
+                spikes.iter().for_each(|&step| train.push(step));
+                train
+            })
+            .collect();
+        SimulationResult {
+            dt: 0.5,
+            steps: 100,
+            spike_trains,
+            ..SimulationResult::default()
+        }
+    }
+
+    #[test]
+    fn identical_trains_are_zero_apart() {
+        let train = [1.0, 4.5, 9.0];
+        assert_eq!(victor_purpura_distance(&train, &train, 3.0), 0.0);
+        assert_eq!(van_rossum_distance(&train, &train, 2.0), 0.0);
+    }
+
+    #[test]
+    fn a_shifted_spike_costs_the_known_amount() {
+        let (a, b) = ([2.0, 10.0], [2.0, 11.5]);
+        assert!((victor_purpura_distance(&a, &b, 0.4) - 0.6).abs() < 1e-12);
+        assert_eq!(victor_purpura_distance(&a, &b, 10.0), 2.0);
+        assert_eq!(victor_purpura_distance(&a, &[2.0], 0.4), 1.0);
+
+        let tau = 3.0;
+        let expected = (1.0 - (-1.5_f64 / tau).exp()).sqrt();
+        assert!((van_rossum_distance(&[10.0], &[11.5], tau) - expected).abs() < 1e-12);
+        assert!((van_rossum_distance(&[10.0], &[], tau) - 0.5_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn population_distance_is_symmetric() {
+        let a = result_of(&[&[1, 20, 40], &[5], &[]]);
+        let b = result_of(&[&[2, 20], &[5, 60]]);
+        for metric in [
+            SpikeDistance::VictorPurpura { cost_q: 1.0 },
+            SpikeDistance::VanRossum { tau: 2.0 },
+        ] {
+            let ab = a.distance_to(&b, &[0, 1, 2], metric);
+            let ba = b.distance_to(&a, &[0, 1, 2], metric);
+            assert_eq!(ab, ba);
+            assert_eq!(ab.missing, vec![2]);
+            assert_eq!(ab.per_neuron.len(), 2);
+            assert!(ab.total() > 0.0);
+        }
+        let vp = a.distance_to(&b, &[0, 1], SpikeDistance::VictorPurpura { cost_q: 1.0 });
+        assert_eq!(vp.per_neuron, vec![(0, 1.5), (1, 1.0)]);
+        assert_eq!(vp.mean(), Some(1.25));
+    }
+}
@@ -0,0 +1,186 @@
+//! Spike-train storage for simulation results.
+//!
+//! A train is either a list of step indices (8 bytes per spike) or a bitset
+//! with one bit per simulated step (a fixed `steps / 8` bytes per neuron). The
+//! bitset is the smaller form once a neuron fires on more than one step in 64,
+//! as in long runs with dense firing. Both present the same interface.
+
+use std::iter::FusedIterator;
+use std::slice;
+
+use serde::{Deserialize, Serialize};
+
+/// Which representation a run records spike trains in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpikeStorage {
+    #[default]
+    Steps,
+    Bitset,
+}
+
+/// The steps at which one neuron fired, in ascending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpikeTrain {
+    Steps(Vec<usize>),
+    /// Bit `s % 64` of `words[s / 64]` is set if the neuron fired on step `s`;
+    /// `count` is the number of set bits.
+    Bitset {
+        words: Vec<u64>,
+        count: usize,
+    },
+}
+
+impl Default for SpikeTrain {
+    fn default() -> Self {
+        SpikeTrain::new(SpikeStorage::Steps)
+    }
+}
+
+impl SpikeTrain {
+    pub fn new(storage: SpikeStorage) -> Self {
+        match storage {
+            SpikeStorage::Steps => SpikeTrain::Steps(Vec::new()),
+            SpikeStorage::Bitset => SpikeTrain::Bitset {
+                words: Vec::new(),
+                count: 0,
+            },
+        }
+    }
+
+    pub fn storage(&self) -> SpikeStorage {
+        match self {
+            SpikeTrain::Steps(_) => SpikeStorage::Steps,
+            SpikeTrain::Bitset { .. } => SpikeStorage::Bitset,
+        }
+    }
+
+    /// Record a spike at `step`, which must not precede the last one recorded.
+    /// Recording the same step twice has no effect.
+    pub fn push(&mut self, step: usize) {
+        match self {
+            SpikeTrain::Steps(steps) => {
+                if steps.last() != Some(&step) {
+                    steps.push(step);
+                }
+            }
+            SpikeTrain::Bitset { words, count } => {
+                let (word, bit) = (step / 64, 1u64 << (step % 64));
+                if words.len() <= word {
+                    words.resize(word + 1, 0);
+                }
+                if words[word] & bit == 0 {
+                    words[word] |= bit;
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SpikeTrain::Steps(steps) => steps.len(),
+            SpikeTrain::Bitset { count, .. } => *count,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, step: usize) -> bool {
+        match self {
+            SpikeTrain::Steps(steps) => steps.binary_search(&step).is_ok(),
+            SpikeTrain::Bitset { words, .. } => words
+                .get(step / 64)
+                .is_some_and(|word| word & (1 << (step % 64)) != 0),
+        }
+    }
+
+    pub fn iter(&self) -> SpikeIter<'_> {
+        match self {
+            SpikeTrain::Steps(steps) => SpikeIter::Steps(steps.iter()),
+            SpikeTrain::Bitset { words, count } => SpikeIter::Bits {
+                words,
+                word: 0,
+                bits: words.first().copied().unwrap_or(0),
+                remaining: *count,
+            },
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<usize> {
+        self.iter().collect()
+    }
+
+    /// The same train in `storage` form.
+    pub fn converted(&self, storage: SpikeStorage) -> SpikeTrain {
+        let mut train = SpikeTrain::new(storage);
+        for step in self {
+            train.push(step);
+        }
+        train
+    }
+}
+
+impl<'a> IntoIterator for &'a SpikeTrain {
+    type Item = usize;
+    type IntoIter = SpikeIter<'a>;
+
+    fn into_iter(self) -> SpikeIter<'a> {
+        self.iter()
+    }
+}
+
+/// Spike steps of a `SpikeTrain`, in ascending order.
+#[derive(Debug, Clone)]
+pub enum SpikeIter<'a> {
+    Steps(slice::Iter<'a, usize>),
+    Bits {
+        words: &'a [u64],
+        word: usize,
+        /// Unvisited bits of `words[word]`.
+        bits: u64,
+        remaining: usize,
+    },
+}
+
+impl Iterator for SpikeIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            SpikeIter::Steps(steps) => steps.next().copied(),
+            SpikeIter::Bits {
+                words,
+                word,
+                bits,
+                remaining,
+            } => {
+                if *remaining == 0 {
+                    return None;
+                }
+                while *bits == 0 {
+                    *word += 1;
+                    *bits = words[*word];
+                }
+                let bit = bits.trailing_zeros() as usize;
+                *bits &= *bits - 1;
+                *remaining -= 1;
+                Some(*word * 64 + bit)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            SpikeIter::Steps(steps) => steps.len(),
+            SpikeIter::Bits { remaining, .. } => *remaining,
+        };
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for SpikeIter<'_> {}
+
+impl FusedIterator for SpikeIter<'_> {}
@@ -0,0 +1,234 @@
+//! Behavioral states: whole parameter sets switched in during a run.
+//!
+//! The worm's forward and reverse locomotion, dwelling and roaming run on the
+//! same wiring with different effective connectivity. A `BehavioralState`
+//! holds the parameters that differ, the shared `LifParams` and the
+//! per-neuron overrides, with multipliers on the chemical, gap-junction and
+//! neuromodulatory gains on top. `Network::run_with_states` swaps them in at
+//! the given steps; the neurons, connections and the state of every neuron
+//! carry over, so a transition shows how the same activity evolves under the
+//! new parameters.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::FlymindError;
+use crate::network::Network;
+use crate::simulation::{LifParams, SimulationResult, StepInfo, StepObserver};
+
+/// A named parameter set for `Network::run_with_states`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BehavioralState {
+    pub name: String,
+    /// Stands in for `Network::lif`.
+    pub lif: LifParams,
+    /// Stands in for `Network::lif_overrides`.
+    pub lif_overrides: HashMap<usize, LifParams>,
+    /// Multiplies `chemical_gain` in `lif` and every override.
+    pub chemical_scale: f64,
+    /// Multiplies `gap_gain` likewise.
+    pub gap_scale: f64,
+    /// Multiplies `modulation_gain` and `extrasynaptic_gain` likewise.
+    pub modulation_scale: f64,
+}
+
+impl Default for BehavioralState {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            lif: LifParams::default(),
+            lif_overrides: HashMap::new(),
+            chemical_scale: 1.0,
+            gap_scale: 1.0,
+            modulation_scale: 1.0,
+        }
+    }
+}
+
+impl BehavioralState {
+    /// The parameters `network` runs with now, unscaled, as a starting point
+    /// for a state that changes only some of them.
+    pub fn of(name: impl Into<String>, network: &Network) -> Self {
+        Self {
+            name: name.into(),
+            lif: network.lif,
+            lif_overrides: network.lif_overrides.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// `params` with this state's multipliers applied.
+    pub fn scaled(&self, params: &LifParams) -> LifParams {
+        LifParams {
+            chemical_gain: params.chemical_gain * self.chemical_scale,
+            gap_gain: params.gap_gain * self.gap_scale,
+            modulation_gain: params.modulation_gain * self.modulation_scale,
+            extrasynaptic_gain: params.extrasynaptic_gain * self.modulation_scale,
+            ..*params
+        }
+    }
+
+    /// Make this state's parameters the network's, scaled.
+    fn apply(&self, network: &mut Network) {
+        network.lif = self.scaled(&self.lif);
+        network.lif_overrides = self
+            .lif_overrides
+            .iter()
+            .map(|(&id, params)| (id, self.scaled(params)))
+            .collect();
+    }
+}
+
+impl Network {
+    /// Run `steps` steps of `dt`, switching to each state of `schedule` ahead
+    /// of its onset step, counted from the start of this run. Until the first
+    /// onset the network runs with its own parameters; on a shared onset the
+    /// later entry wins, and onsets at or past `steps` never take effect.
+    /// Neuron state, connections and stimuli carry over every switch.
+    ///
+    /// Every state is checked before the run starts: a time constant that is
+    /// not a positive number, or a `dt` that `check_dt` rejects under the
+    /// state's parameters, fails it with `FlymindError::InvalidState`. The
+    /// network's `lif` and `lif_overrides` are put back as they were once the
+    /// run ends.
+    pub fn run_with_states(
+        &mut self,
+        schedule: &[(usize, BehavioralState)],
+        steps: usize,
+        dt: f64,
+    ) -> Result<SimulationResult, FlymindError> {
+        self.run_with_states_observed(
+            schedule,
+            steps,
+            dt,
+            &[],
+            1,
+            &mut |_: &StepInfo, _: &Network| ControlFlow::Continue(()),
+        )
+    }
+
+    /// `run_with_states`, recording the potentials of `watch` every `stride`
+    /// steps and calling `observer` after each step, as
+    /// `run_simulation_observed` does.
+    pub fn run_with_states_observed(
+        &mut self,
+        schedule: &[(usize, BehavioralState)],
+        steps: usize,
+        dt: f64,
+        watch: &[usize],
+        stride: usize,
+        observer: &mut dyn StepObserver,
+    ) -> Result<SimulationResult, FlymindError> {
+        let saved = (self.lif, self.lif_overrides.clone());
+        let checked = schedule
+            .iter()
+            .try_for_each(|(_, state)| self.check_state(state, dt));
+        (self.lif, self.lif_overrides) = saved.clone();
+        checked?;
+
+        let mut order: Vec<usize> = (0..schedule.len()).collect();
+        order.sort_by_key(|&i| schedule[i].0);
+        let mut next = 0;
+        let mut step = 0;
+        let result =
+            self.run_simulation_driven(steps, dt, watch, stride, observer, &mut |network| {
+                let mut switched = false;
+                while let Some(&(onset, ref state)) = order.get(next).map(|&i| &schedule[i]) {
+                    if onset > step {
+                        break;
+                    }
+                    state.apply(network);
+                    switched = true;
+                    next += 1;
+                }
+                if switched {
+                    network.restart_wavefront();
+                    network.refresh_input_limits();
+                }
+                step += 1;
+            });
+
+        (self.lif, self.lif_overrides) = saved;
+        Ok(result)
+    }
+
+    /// Apply `state` and check that it can run with steps of `dt`.
+    fn check_state(&mut self, state: &BehavioralState, dt: f64) -> Result<(), FlymindError> {
+        let invalid = |message: String| FlymindError::InvalidState {
+            state: state.name.clone(),
+            message,
+        };
+        let mut params: Vec<(Option<usize>, &LifParams)> = vec![(None, &state.lif)];
+        params.extend(state.lif_overrides.iter().map(|(&id, p)| (Some(id), p)));
+        params.sort_by_key(|&(id, _)| id);
+        for (id, p) in params {
+            if !(p.tau.is_finite() && p.tau > 0.0) {
+                let whose = id.map_or_else(|| "lif".to_string(), |id| format!("neuron {id}"));
+                return Err(invalid(format!(
+                    "has tau = {} for {whose}, expected a positive number",
+                    p.tau
+                )));
+            }
+        }
+        state.apply(self);
+        self.check_dt(dt)
+            .map(|_| ())
+            .map_err(|err| invalid(format!("cannot run: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Stimulus;
+
+    #[test]
+    fn unrunnable_states_fail_before_the_run() {
+        let mut network = Network::example_touch_circuit();
+        let lif = network.lif;
+        let mut still = BehavioralState::of("still", &network);
+        still.lif_overrides.insert(3, LifParams { tau: 0.0, ..lif });
+        let mut fast = BehavioralState::of("fast", &network);
+        fast.lif.tau = 0.01;
+        let plain = BehavioralState::of("plain", &network);
+
+        for (state, expected) in [(still, "still"), (fast, "fast")] {
+            let result = network.run_with_states(&[(0, plain.clone()), (50, state)], 100, 0.1);
+            assert!(
+                matches!(&result, Err(FlymindError::InvalidState { state, .. }) if state == expected),
+                "{expected}"
+            );
+        }
+        assert_eq!(network.lif, lif);
+        assert!(network.lif_overrides.is_empty());
+        assert_eq!(network.current_step, 0);
+    }
+
+    #[test]
+    fn observed_runs_watch_and_see_every_step() {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 200, 0.5));
+        let mut quiet = BehavioralState::of("quiet", &network);
+        quiet.chemical_scale = 0.0;
+        let schedule = [(100, quiet)];
+
+        let mut seen = 0;
+        let mut observer = |_: &StepInfo, _: &Network| {
+            seen += 1;
+            ControlFlow::Continue(())
+        };
+        let observed = network
+            .run_with_states_observed(&schedule, 300, 0.1, &[alml], 10, &mut observer)
+            .unwrap();
+        assert_eq!(seen, 300);
+        assert_eq!(observed.potentials[&alml].len(), 30);
+
+        network.reset_state();
+        let plain = network.run_with_states(&schedule, 300, 0.1).unwrap();
+        assert_eq!(plain.spike_trains, observed.spike_trains);
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::network::{Network, SynapseType};
+
+/// Min/max/mean/median of a per-neuron quantity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DegreeSummary {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl DegreeSummary {
+    pub fn from_values(mut values: Vec<usize>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_unstable();
+        let n = values.len();
+        let median = if n % 2 == 1 {
+            values[n / 2] as f64
+        } else {
+            (values[n / 2 - 1] + values[n / 2]) as f64 / 2.0
+        };
+        Self {
+            min: values[0],
+            max: values[n - 1],
+            mean: values.iter().sum::<usize>() as f64 / n as f64,
+            median,
+        }
+    }
+}
+
+/// Synaptic contact counts (`Connection::contacts`), in the units of the source
+/// data rather than of the model weights. `ChemicalReceive` rows are left out
+/// since they record the contacts of a send a second time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ContactSummary {
+    pub total: u64,
+    /// Connections counted, including those with no recorded contacts.
+    pub connections: usize,
+    pub mean_per_connection: f64,
+    pub median_per_connection: f64,
+    pub max_per_connection: u32,
+    pub total_by_synapse: BTreeMap<String, u64>,
+    /// Number of connections with each contact count.
+    pub distribution: BTreeMap<u32, usize>,
+}
+
+/// One-call structural summary of a network.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NetworkStats {
+    pub neuron_count: usize,
+    pub connection_count: usize,
+    pub total_weight: f64,
+    /// See `Network::contact_summary`.
+    pub total_contacts: u64,
+    pub neurons_by_type: BTreeMap<String, usize>,
+    pub neurons_by_region: BTreeMap<String, usize>,
+    pub connections_by_synapse: BTreeMap<String, usize>,
+    pub out_degree: DegreeSummary,
+    pub in_degree: DegreeSummary,
+    /// See `Network::reciprocity`.
+    pub reciprocity: f64,
+    /// Weakly connected component sizes, largest first.
+    pub component_sizes: Vec<usize>,
+}
+
+impl Network {
+    pub fn stats(&self) -> NetworkStats {
+        let mut stats = NetworkStats {
+            neuron_count: self.neurons.len(),
+            connection_count: self.connections.len(),
+            ..NetworkStats::default()
+        };
+
+        for neuron in &self.neurons {
+            *stats
+                .neurons_by_type
+                .entry(neuron.neuron_type.label().to_string())
+                .or_default() += 1;
+            *stats
+                .neurons_by_region
+                .entry(neuron.region.label().to_string())
+                .or_default() += 1;
+        }
+        for conn in &self.connections {
+            stats.total_weight += conn.weight;
+            *stats
+                .connections_by_synapse
+                .entry(conn.synapse_type.label().to_string())
+                .or_default() += 1;
+        }
+
+        let ids = 0..self.neurons.len();
+        stats.out_degree =
+            DegreeSummary::from_values(ids.clone().map(|i| self.out_degree(i)).collect());
+        stats.in_degree = DegreeSummary::from_values(ids.map(|i| self.in_degree(i)).collect());
+        stats.total_contacts = self.contact_summary().total;
+        stats.reciprocity = self.reciprocity();
+        stats.component_sizes = self
+            .weakly_connected_components()
+            .iter()
+            .map(Vec::len)
+            .collect();
+        stats
+    }
+}
+
+impl Network {
+    pub fn contact_summary(&self) -> ContactSummary {
+        let mut summary = ContactSummary::default();
+        let mut counts = Vec::new();
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) || !self.counts(conn) {
+                continue;
+            }
+            counts.push(conn.contacts);
+            summary.total += u64::from(conn.contacts);
+            *summary
+                .total_by_synapse
+                .entry(conn.synapse_type.label().to_string())
+                .or_default() += u64::from(conn.contacts);
+            *summary.distribution.entry(conn.contacts).or_default() += 1;
+        }
+        if counts.is_empty() {
+            return summary;
+        }
+
+        counts.sort_unstable();
+        let n = counts.len();
+        summary.connections = n;
+        summary.mean_per_connection = summary.total as f64 / n as f64;
+        summary.median_per_connection = if n % 2 == 1 {
+            f64::from(counts[n / 2])
+        } else {
+            (f64::from(counts[n / 2 - 1]) + f64::from(counts[n / 2])) / 2.0
+        };
+        summary.max_per_connection = counts[n - 1];
+        summary
+    }
+}
+
+/// How `Network::weight_outliers_by` measures distance from typical weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutlierMethod {
+    /// Standard deviations from the mean.
+    #[default]
+    MeanStd,
+    /// Scaled median absolute deviations from the median (MAD times 1.4826,
+    /// matching the standard deviation for normal data), which the outliers
+    /// themselves barely move. Where more than half the weights are equal and
+    /// the MAD is zero, the mean absolute deviation from the median (times
+    /// 1.2533) stands in.
+    MedianMad,
+}
+
+impl Network {
+    /// `weight_outliers_by` with `OutlierMethod::MeanStd`.
+    pub fn weight_outliers(&self, z_threshold: f64) -> Vec<usize> {
+        self.weight_outliers_by(z_threshold, OutlierMethod::MeanStd)
+    }
+
+    /// Indices of the connections whose weight is more than `z_threshold`
+    /// spreads from the centre of the weights of the same synapse type,
+    /// ascending. Chemical subtypes are pooled and `ChemicalReceive` rows are
+    /// left out, as the send they mirror is already judged. A type whose
+    /// weights do not vary has no outliers.
+    pub fn weight_outliers_by(&self, z_threshold: f64, method: OutlierMethod) -> Vec<usize> {
+        let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, conn) in self.connections.iter().enumerate() {
+            if !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) {
+                groups
+                    .entry(conn.synapse_type.label())
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut outliers = Vec::new();
+        for indices in groups.values() {
+            let weights: Vec<f64> = indices
+                .iter()
+                .map(|&i| self.connections[i].weight)
+                .collect();
+            let (centre, spread) = match method {
+                OutlierMethod::MeanStd => {
+                    let n = weights.len() as f64;
+                    let mean = weights.iter().sum::<f64>() / n;
+                    let variance = weights.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / n;
+                    (mean, variance.sqrt())
+                }
+                OutlierMethod::MedianMad => {
+                    let centre = median(weights.clone());
+                    let deviations: Vec<f64> = weights.iter().map(|w| (w - centre).abs()).collect();
+                    let mean_deviation = deviations.iter().sum::<f64>() / deviations.len() as f64;
+                    let mad = median(deviations);
+                    let spread = if mad > 0.0 {
+                        1.4826 * mad
+                    } else {
+                        1.2533 * mean_deviation
+                    };
+                    (centre, spread)
+                }
+            };
+            if !(spread.is_finite() && spread > 0.0) {
+                continue;
+            }
+            outliers.extend(
+                indices
+                    .iter()
+                    .zip(&weights)
+                    .filter(|&(_, w)| (w - centre).abs() > z_threshold * spread)
+                    .map(|(&index, _)| index),
+            );
+        }
+        outliers.sort_unstable();
+        outliers
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_unstable_by(f64::total_cmp);
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    }
+}
+
+impl fmt::Display for NetworkStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<24}{}", "neurons", self.neuron_count)?;
+        writeln!(f, "{:<24}{}", "connections", self.connection_count)?;
+        writeln!(f, "{:<24}{}", "total weight", self.total_weight)?;
+        writeln!(f, "{:<24}{}", "total contacts", self.total_contacts)?;
+
+        writeln!(f, "\nneurons by type")?;
+        for (label, count) in &self.neurons_by_type {
+            writeln!(f, "  {label:<22}{count}")?;
+        }
+        writeln!(f, "\nneurons by region")?;
+        for (label, count) in &self.neurons_by_region {
+            writeln!(f, "  {label:<22}{count}")?;
+        }
+        writeln!(f, "\nconnections by synapse")?;
+        for (label, count) in &self.connections_by_synapse {
+            writeln!(f, "  {label:<22}{count}")?;
+        }
+
+        writeln!(
+            f,
+            "\n{:<24}{:>6}{:>6}{:>9}{:>9}",
+            "degree", "min", "max", "mean", "median"
+        )?;
+        for (label, d) in [("  out", &self.out_degree), ("  in", &self.in_degree)] {
+            writeln!(
+                f,
+                "{label:<24}{:>6}{:>6}{:>9.2}{:>9.1}",
+                d.min, d.max, d.mean, d.median
+            )?;
+        }
+
+        writeln!(f, "\n{:<24}{:.3}", "reciprocity", self.reciprocity)?;
+        let shown: Vec<String> = self
+            .component_sizes
+            .iter()
+            .take(10)
+            .map(usize::to_string)
+            .collect();
+        let more = if self.component_sizes.len() > 10 {
+            ", ..."
+        } else {
+            ""
+        };
+        write!(
+            f,
+            "{:<24}{} [{}{more}]",
+            "components",
+            self.component_sizes.len(),
+            shown.join(", ")
+        )
+    }
+}
@@ -0,0 +1,212 @@
+//! Extracting and pruning parts of a network; reweighting is in `crate::weights`.
+
+use std::collections::VecDeque;
+
+use crate::muscle::{Muscle, NeuromuscularJunction};
+use crate::network::{Connection, Network, NeuronType};
+use crate::paths::PathOptions;
+
+impl Network {
+    /// The subgraph induced by `ids`: those neurons (renumbered in ascending id
+    /// order, metadata and annotations kept), every connection between two of
+    /// them, and every muscle with the junctions from kept neurons.
+    /// Duplicate and out-of-range ids are ignored; simulation settings are not copied.
+    pub fn subnetwork(&self, ids: &[usize]) -> Network {
+        let mut keep: Vec<usize> = ids
+            .iter()
+            .copied()
+            .filter(|&id| id < self.neurons.len())
+            .collect();
+        keep.sort_unstable();
+        keep.dedup();
+
+        let mut new_id = vec![None; self.neurons.len()];
+        let mut sub = Network::new();
+        for &id in &keep {
+            let neuron = &self.neurons[id];
+            let sub_id = sub.add_neuron(
+                neuron.name(),
+                neuron.neuron_type,
+                neuron.region,
+                neuron.soma_position,
+            );
+            sub.neurons[sub_id]
+                .transmitter
+                .clone_from(&neuron.transmitter);
+            sub.neurons[sub_id].type_confidence = neuron.type_confidence;
+            new_id[id] = Some(sub_id);
+        }
+
+        let mut new_index = vec![None; self.connections.len()];
+        let (edges, extras): (Vec<_>, Vec<_>) = self
+            .connections
+            .iter()
+            .enumerate()
+            .filter_map(|(index, conn)| {
+                let edge = (
+                    new_id[conn.from_id]?,
+                    new_id[conn.to_id]?,
+                    conn.synapse_type,
+                    conn.weight,
+                );
+                Some((
+                    edge,
+                    (index, conn.contacts, conn.plastic, conn.source.clone()),
+                ))
+            })
+            .unzip();
+        let range = sub
+            .add_connections_bulk(edges)
+            .expect("renumbered endpoints are in range");
+        for (new, &(old, ..)) in range.clone().zip(&extras) {
+            new_index[old] = Some(new);
+        }
+        for (conn, (_, contacts, plastic, source)) in sub.connections[range].iter_mut().zip(extras)
+        {
+            conn.contacts = contacts;
+            conn.plastic = plastic;
+            conn.source = source;
+        }
+        sub.muscles = self
+            .muscles
+            .iter()
+            .map(|muscle| Muscle {
+                activation: 0.0,
+                drive: 0.0,
+                ..muscle.clone()
+            })
+            .collect();
+        sub.neuromuscular = self
+            .neuromuscular
+            .iter()
+            .filter_map(|junction| {
+                Some(NeuromuscularJunction {
+                    from_id: new_id[junction.from_id]?,
+                    ..*junction
+                })
+            })
+            .collect();
+        sub.annotations = self.annotations.clone();
+        sub.annotations
+            .remap_neurons(|id| new_id.get(id).copied().flatten());
+        sub.annotations.remap_connections(&new_index);
+        sub
+    }
+
+    /// Keep only the connections for which `keep` returns true, preserving their
+    /// order and moving their annotations and parameter overrides along.
+    /// Returns how many were removed.
+    pub fn retain_connections<F: FnMut(&Connection) -> bool>(&mut self, keep: F) -> usize {
+        self.record_structural(
+            |network| network.retain_connections_unrecorded(keep),
+            |&removed| (removed > 0).then(|| format!("remove {removed} connections")),
+        )
+    }
+
+    fn retain_connections_unrecorded<F>(&mut self, mut keep: F) -> usize
+    where
+        F: FnMut(&Connection) -> bool,
+    {
+        let before = self.connections.len();
+        let mut kept = 0;
+        let new_index: Vec<Option<usize>> = self
+            .connections
+            .iter()
+            .map(|conn| {
+                keep(conn).then(|| {
+                    kept += 1;
+                    kept - 1
+                })
+            })
+            .collect();
+        let mut flags = new_index.iter();
+        self.connections
+            .retain(|_| flags.next().is_some_and(Option::is_some));
+        self.annotations.remap_connections(&new_index);
+        self.connection_params.remap_connections(&new_index);
+        self.rebuild_indices();
+        before - self.connections.len()
+    }
+
+    /// Indices of the connections whose `source` is `tag`, ascending.
+    pub fn connections_from_source(&self, tag: &str) -> Vec<usize> {
+        self.connections
+            .iter()
+            .enumerate()
+            .filter(|(_, conn)| conn.source.as_deref() == Some(tag))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Remove every connection whose `source` is `tag`, backing out one file of
+    /// a network assembled from several. Neurons stay. Returns how many were
+    /// removed.
+    pub fn remove_source(&mut self, tag: &str) -> usize {
+        self.retain_connections(|conn| conn.source.as_deref() != Some(tag))
+    }
+
+    /// Remove connections lighter than `min_weight`. Returns how many were removed.
+    pub fn prune(&mut self, min_weight: f64) -> usize {
+        self.retain_connections(|conn| conn.weight >= min_weight)
+    }
+
+    /// The feedforward sensory-to-motor circuit: every sensory and motor neuron,
+    /// plus each interneuron on a chemical path of at most `max_depth` hops from
+    /// a sensory neuron to a motor neuron. Built with `subnetwork`, so names and
+    /// metadata are kept.
+    pub fn sensorimotor_subnetwork(&self, max_depth: usize) -> Network {
+        let n = self.neurons.len();
+        let forward = self.traversal_steps(&PathOptions::default());
+        let mut backward = vec![Vec::new(); n];
+        for (from, steps) in forward.iter().enumerate() {
+            for &(to, _) in steps {
+                backward[to].push(from);
+            }
+        }
+        let of_type = |t: NeuronType| -> Vec<usize> {
+            self.neurons
+                .iter()
+                .filter(|neuron| neuron.neuron_type == t)
+                .map(|neuron| neuron.id)
+                .collect()
+        };
+        let sensory = of_type(NeuronType::Sensory);
+        let motor = of_type(NeuronType::Motor);
+        let from_sensory = hop_distances(n, &sensory, |v| forward[v].iter().map(|&(w, _)| w));
+        let to_motor = hop_distances(n, &motor, |v| backward[v].iter().copied());
+
+        let mut ids: Vec<usize> = sensory.into_iter().chain(motor).collect();
+        ids.extend((0..n).filter(|&id| {
+            self.neurons[id].neuron_type == NeuronType::Interneuron
+                && from_sensory[id]
+                    .zip(to_motor[id])
+                    .is_some_and(|(a, b)| a + b <= max_depth)
+        }));
+        self.subnetwork(&ids)
+    }
+}
+
+/// Multi-source breadth-first hop counts over `n` neurons from `sources`, `None`
+/// where unreachable.
+pub(crate) fn hop_distances<I, F>(n: usize, sources: &[usize], mut next: F) -> Vec<Option<usize>>
+where
+    F: FnMut(usize) -> I,
+    I: Iterator<Item = usize>,
+{
+    let mut dist = vec![None; n];
+    let mut queue = VecDeque::new();
+    for &s in sources {
+        dist[s] = Some(0);
+        queue.push_back(s);
+    }
+    while let Some(v) = queue.pop_front() {
+        let d = dist[v].unwrap_or(0) + 1;
+        for w in next(v) {
+            if dist[w].is_none() {
+                dist[w] = Some(d);
+                queue.push_back(w);
+            }
+        }
+    }
+    dist
+}
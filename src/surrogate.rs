@@ -0,0 +1,303 @@
+//! Surrogate spike data: copies of a run with some structure destroyed and
+//! the rest kept, for null distributions of correlation-type statistics.
+//!
+//! `surrogates` yields the copies one at a time, so a thousand of them never
+//! sit in memory together. The same seed gives the same sequence. A
+//! surrogate carries only `dt`, `steps` and the spike trains, stored as the
+//! run stored them.
+
+use crate::network::Network;
+use crate::rng::Rng;
+use crate::simulation::SimulationResult;
+use crate::spikes::SpikeTrain;
+
+/// How a surrogate is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurrogateMethod {
+    /// Move every spike uniformly within `window_steps` steps either way,
+    /// staying inside the run. Keeps each neuron's spike count and its rate
+    /// on time scales longer than the window; a spike landing on a step
+    /// already taken moves to the nearest free one.
+    Jitter { window_steps: usize },
+    /// Hand each neuron the whole train of a random neuron of the same
+    /// `NeuronType`. Keeps every train and the rates of each type, breaks
+    /// which neuron fired them.
+    ShuffleTrains,
+    /// Reorder each neuron's inter-spike intervals, keeping its first spike.
+    /// Keeps the count and the interval distribution, breaks their order and
+    /// the timing against other neurons.
+    ShuffleIsis,
+}
+
+/// Iterator over the surrogates of one run, from `surrogates`.
+#[derive(Debug, Clone)]
+pub struct Surrogates<'a> {
+    result: &'a SimulationResult,
+    method: SurrogateMethod,
+    /// Neuron ids grouped by type, for `ShuffleTrains`.
+    groups: Vec<Vec<usize>>,
+    remaining: usize,
+    rng: Rng,
+}
+
+/// `n` surrogates of `result` by `method`, drawn from `seed`. `network` is the
+/// network that produced `result`; only `ShuffleTrains` reads it.
+pub fn surrogates<'a>(
+    result: &'a SimulationResult,
+    network: &Network,
+    method: SurrogateMethod,
+    n: usize,
+    seed: u64,
+) -> Surrogates<'a> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    if method == SurrogateMethod::ShuffleTrains {
+        let mut types = Vec::new();
+        for id in 0..result.spike_trains.len() {
+            let neuron_type = network.neurons.get(id).map(|n| n.neuron_type);
+            match types.iter().position(|&t| t == neuron_type) {
+                Some(group) => groups[group].push(id),
+                None => {
+                    types.push(neuron_type);
+                    groups.push(vec![id]);
+                }
+            }
+        }
+    }
+    Surrogates {
+        result,
+        method,
+        groups,
+        remaining: n,
+        rng: Rng::new(seed),
+    }
+}
+
+impl Iterator for Surrogates<'_> {
+    type Item = SimulationResult;
+
+    fn next(&mut self) -> Option<SimulationResult> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let result = self.result;
+        let spike_trains = match self.method {
+            SurrogateMethod::Jitter { window_steps } => result
+                .spike_trains
+                .iter()
+                .map(|train| jitter(train, window_steps, result.steps, &mut self.rng))
+                .collect(),
+            SurrogateMethod::ShuffleTrains => {
+                let mut trains = result.spike_trains.clone();
+                for group in &self.groups {
+                    let mut order = group.clone();
+                    shuffle(&mut order, &mut self.rng);
+                    for (&to, &from) in group.iter().zip(&order) {
+                        trains[to] = result.spike_trains[from].clone();
+                    }
+                }
+                trains
+            }
+            SurrogateMethod::ShuffleIsis => result
+                .spike_trains
+                .iter()
+                .map(|train| shuffle_isis(train, &mut self.rng))
+                .collect(),
+        };
+        Some(SimulationResult {
+            dt: result.dt,
+            steps: result.steps,
+            spike_trains,
+            ..SimulationResult::default()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Surrogates<'_> {}
+
+fn jitter(train: &SpikeTrain, window_steps: usize, steps: usize, rng: &mut Rng) -> SpikeTrain {
+    let mut taken = vec![false; steps];
+    let mut moved = Vec::with_capacity(train.len());
+    for step in train.iter().filter(|&step| step < steps) {
+        let low = step.saturating_sub(window_steps);
+        let high = (step + window_steps).min(steps - 1);
+        let mut target = low + rng.below(high - low + 1);
+        if taken[target] {
+            target = (1..steps)
+                .flat_map(|d| [target.checked_sub(d), Some(target + d)])
+                .flatten()
+                .find(|&t| t < steps && !taken[t])
+                .unwrap_or(target);
+        }
+        taken[target] = true;
+        moved.push(target);
+    }
+    moved.sort_unstable();
+    collect_train(train, moved)
+}
+
+fn shuffle_isis(train: &SpikeTrain, rng: &mut Rng) -> SpikeTrain {
+    let steps = train.to_vec();
+    let Some(&first) = steps.first() else {
+        return train.clone();
+    };
+    let mut intervals: Vec<usize> = steps.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    shuffle(&mut intervals, rng);
+    let mut step = first;
+    let mut shuffled = vec![first];
+    for interval in intervals {
+        step += interval;
+        shuffled.push(step);
+    }
+    collect_train(train, shuffled)
+}
+
+/// `steps` (ascending) in a train stored like `like`.
+fn collect_train(like: &SpikeTrain, steps: Vec<usize>) -> SpikeTrain {
+    let mut train = SpikeTrain::new(like.storage());
+    for step in steps {
+        train.push(step);
+    }
+    train
+}
+
+/// Fisher-Yates shuffle.
+fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+/// One-sided empirical p-value of `observed` against `null` draws of the same
+/// statistic: `(1 + draws at or above observed) / (1 + draws)`, so never
+/// zero. NaN draws are skipped; a NaN `observed` gives NaN.
+pub fn empirical_p_value(observed: f64, null: impl IntoIterator<Item = f64>) -> f64 {
+    if observed.is_nan() {
+        return f64::NAN;
+    }
+    let (mut draws, mut extreme) = (0usize, 0usize);
+    for value in null.into_iter().filter(|value| !value.is_nan()) {
+        draws += 1;
+        if value >= observed {
+            extreme += 1;
+        }
+    }
+    (1 + extreme) as f64 / (1 + draws) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::NeuronType;
+    use crate::spikes::SpikeStorage;
+
+    /// Four neurons, the first two sensory, with trains of differing density;
+    /// the third fires on every other step so jittered spikes collide.
+    fn recorded() -> (Network, SimulationResult) {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C", "D"]);
+        for (neuron, neuron_type) in network.neurons.iter_mut().zip([
+            NeuronType::Sensory,
+            NeuronType::Sensory,
+            NeuronType::Motor,
+            NeuronType::Motor,
+        ]) {
+            neuron.neuron_type = neuron_type;
+        }
+        let mut rng = Rng::new(9);
+        let spike_trains = [0.01, 0.03, 0.5, 0.0]
+            .into_iter()
+            .map(|rate| {
+                let mut train = SpikeTrain::new(SpikeStorage::Steps);
+                if rate == 0.5 {
+                    (0..5_000).step_by(2).for_each(|step| train.push(step));
+                } else {
+                    (0..5_000)
+                        .filter(|_| rng.next_f64() < rate)
+                        .for_each(|step| train.push(step));
+                }
+                train
+            })
+            .collect();
+        let result = SimulationResult {
+            dt: 0.1,
+            steps: 5_000,
+            spike_trains,
+            ..SimulationResult::default()
+        };
+        (network, result)
+    }
+
+    #[test]
+    fn jitter_keeps_every_spike_count() {
+        let (network, result) = recorded();
+        let method = SurrogateMethod::Jitter { window_steps: 20 };
+        for surrogate in surrogates(&result, &network, method, 20, 4) {
+            for (train, original) in surrogate.spike_trains.iter().zip(&result.spike_trains) {
+                assert_eq!(train.len(), original.len());
+                assert!(train.iter().all(|step| step < result.steps));
+            }
+            assert_ne!(surrogate.spike_trains[1], result.spike_trains[1]);
+        }
+        let first: Vec<SimulationResult> = surrogates(&result, &network, method, 3, 4).collect();
+        let again: Vec<SimulationResult> = surrogates(&result, &network, method, 3, 4).collect();
+        for (a, b) in first.iter().zip(&again) {
+            assert_eq!(a.spike_trains, b.spike_trains);
+        }
+    }
+
+    #[test]
+    fn shuffles_keep_their_marginals() {
+        let (network, result) = recorded();
+        let method = SurrogateMethod::ShuffleIsis;
+        for surrogate in surrogates(&result, &network, method, 5, 1) {
+            for (train, original) in surrogate.spike_trains.iter().zip(&result.spike_trains) {
+                let intervals = |train: &SpikeTrain| {
+                    let steps = train.to_vec();
+                    let mut isis: Vec<usize> = steps.windows(2).map(|w| w[1] - w[0]).collect();
+                    isis.sort_unstable();
+                    (steps.first().copied(), isis)
+                };
+                assert_eq!(intervals(train), intervals(original));
+            }
+        }
+        for surrogate in surrogates(&result, &network, SurrogateMethod::ShuffleTrains, 5, 1) {
+            for group in [[0, 1], [2, 3]] {
+                let mut counts: Vec<usize> =
+                    group.map(|id| surrogate.spike_trains[id].len()).to_vec();
+                let mut expected: Vec<usize> =
+                    group.map(|id| result.spike_trains[id].len()).to_vec();
+                counts.sort_unstable();
+                expected.sort_unstable();
+                assert_eq!(counts, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn p_values_of_a_surrogate_are_uniform() {
+        let (network, result) = recorded();
+        let method = SurrogateMethod::Jitter { window_steps: 200 };
+        let statistic = |surrogate: &SimulationResult| {
+            let times = surrogate.spike_times(1);
+            times.iter().sum::<f64>() / times.len() as f64
+        };
+        let mut draws = surrogates(&result, &network, method, 800, 12).map(|s| statistic(&s));
+        let null: Vec<f64> = draws.by_ref().take(500).collect();
+        let p_values: Vec<f64> = draws
+            .map(|observed| empirical_p_value(observed, null.iter().copied()))
+            .collect();
+        assert_eq!(p_values.len(), 300);
+        let mean = p_values.iter().sum::<f64>() / p_values.len() as f64;
+        assert!((mean - 0.5).abs() < 0.07, "mean p {mean}");
+        for cut in [0.1, 0.25, 0.5, 0.75] {
+            let below = p_values.iter().filter(|&&p| p <= cut).count() as f64 / 300.0;
+            assert!((below - cut).abs() < 0.08, "{below} at or below {cut}");
+        }
+        assert!(empirical_p_value(f64::NAN, null.iter().copied()).is_nan());
+        assert_eq!(empirical_p_value(f64::INFINITY, [1.0, f64::NAN]), 0.5);
+    }
+}
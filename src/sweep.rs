@@ -0,0 +1,297 @@
+//! Many runs of one network without copying it per run.
+//!
+//! `Network::freeze` takes a read-only `FrozenTopology`: the connections the
+//! simulator reads, with gains and bounds resolved, the neurons' parameters,
+//! stimuli and bias currents. Each `SimulationState` shares it through an
+//! `Arc` and owns only what a run changes: neuron state, the noise generator,
+//! the clock and, once plasticity changes one, its own copy of the weights.
+//! Both types are `Send + Sync`, so states can step on any thread; `run`
+//! spreads seeded runs across rayon's thread pool with the `parallel` feature.
+//!
+//! A state steps through the same kernel as `Network::update_step`, on the
+//! connections compiled the same way, starting from the network as it was
+//! frozen; it only drives no muscles and logs no events.
+
+use std::sync::Arc;
+
+use crate::kernel::{Delivery, InputLimits, StepInputs};
+use crate::network::{Network, Neuron};
+use crate::rng::Rng;
+use crate::simulation::{LifParams, SimulationResult, StdpParams, Stimulus, SynapticInput};
+use crate::spikes::{SpikeStorage, SpikeTrain};
+use crate::trace::StimulusTrace;
+
+/// The read-only part of a simulation, from `Network::freeze`.
+#[derive(Debug, Clone)]
+pub struct FrozenTopology {
+    /// The neurons as frozen, which every state starts from.
+    neurons: Vec<Neuron>,
+    lif: Vec<LifParams>,
+    /// The connections compiled as `Network::update_step` compiles them.
+    inputs: StepInputs,
+    /// One per connection of the frozen network.
+    weights: Vec<f64>,
+    /// `max_inputs` and normalization under `weights`.
+    limits: InputLimits,
+    stimuli: Vec<Stimulus>,
+    stimulus_traces: Vec<StimulusTrace>,
+    bias_currents: Vec<(usize, f64)>,
+    stdp: Option<StdpParams>,
+    synaptic_input: SynapticInput,
+    fan_out: Vec<usize>,
+    spike_storage: SpikeStorage,
+    start_step: usize,
+    seed_rng: Rng,
+}
+
+/// One run's mutable state over a shared `FrozenTopology`.
+#[derive(Debug, Clone)]
+pub struct SimulationState {
+    topology: Arc<FrozenTopology>,
+    neurons: Vec<Neuron>,
+    /// Private weights, copied from the topology when plasticity first
+    /// changes one, with the limits they give.
+    weights: Option<(Vec<f64>, InputLimits)>,
+    rng: Rng,
+    current_step: usize,
+}
+
+impl Network {
+    /// Freeze this network for `SimulationState`s, as `run_simulation` would
+    /// resolve it now. Later changes to the network do not reach the topology.
+    pub fn freeze(&self) -> Arc<FrozenTopology> {
+        let lif: Vec<LifParams> = (0..self.neurons.len())
+            .map(|id| *self.lif_for(id))
+            .collect();
+        let inputs = StepInputs::compile(self, self.resolve_synapses().as_deref());
+        let weights: Vec<f64> = self.connections.iter().map(|conn| conn.weight).collect();
+        let limits = inputs.limits(|id| &lif[id], |index| weights[index]);
+        Arc::new(FrozenTopology {
+            neurons: self.neurons.clone(),
+            lif,
+            inputs,
+            weights,
+            limits,
+            stimuli: self.stimuli.clone(),
+            stimulus_traces: self.stimulus_traces.clone(),
+            bias_currents: self
+                .bias_currents
+                .iter()
+                .map(|(&id, &bias)| (id, bias))
+                .collect(),
+            stdp: self.stdp,
+            synaptic_input: self.synaptic_input,
+            fan_out: self.fan_out(),
+            spike_storage: self.spike_storage,
+            start_step: self.current_step,
+            seed_rng: self.rng.clone(),
+        })
+    }
+}
+
+impl FrozenTopology {
+    pub fn neuron_count(&self) -> usize {
+        self.neurons.len()
+    }
+
+    /// Weights as frozen, indexed as the network's connections.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+}
+
+impl SimulationState {
+    /// A state starting from the network as frozen, its noise generator seeded
+    /// with `seed`.
+    pub fn new(topology: Arc<FrozenTopology>, seed: u64) -> Self {
+        let mut state = Self::unseeded(topology);
+        state.rng = Rng::new(seed);
+        state
+    }
+
+    /// A state that also carries on with the frozen network's generator, so
+    /// it runs exactly as the network would have.
+    pub fn unseeded(topology: Arc<FrozenTopology>) -> Self {
+        Self {
+            neurons: topology.neurons.clone(),
+            weights: None,
+            rng: topology.seed_rng.clone(),
+            current_step: topology.start_step,
+            topology,
+        }
+    }
+
+    pub fn topology(&self) -> &Arc<FrozenTopology> {
+        &self.topology
+    }
+
+    pub fn neurons(&self) -> &[Neuron] {
+        &self.neurons
+    }
+
+    /// Current weights, indexed as the network's connections.
+    pub fn weights(&self) -> &[f64] {
+        self.weights
+            .as_ref()
+            .map_or(&self.topology.weights, |(weights, _)| weights)
+    }
+
+    pub fn current_step(&self) -> usize {
+        self.current_step
+    }
+
+    /// Advance one step, as `Network::update_step`.
+    pub fn step(&mut self, dt: f64) {
+        let topology = &*self.topology;
+        let lif = |id: usize| &topology.lif[id];
+        let (weights, limits) = self.weights.as_ref().map_or(
+            (&topology.weights, &topology.limits),
+            |(weights, limits)| (weights, limits),
+        );
+        let by_rate = topology.synaptic_input != SynapticInput::Spikes;
+        topology.synaptic_input.update_rates(&mut self.neurons, dt);
+        let failed = if by_rate {
+            None
+        } else {
+            topology
+                .inputs
+                .release_failures(&self.neurons, &mut self.rng)
+        };
+        let delivery = Delivery {
+            limits,
+            failed: failed.as_deref(),
+            by_rate,
+            active: None,
+        };
+        let mut drive =
+            topology
+                .inputs
+                .drive(&self.neurons, lif, |index| weights[index], delivery, dt);
+        drive.add_external(
+            &topology.stimuli,
+            &topology.stimulus_traces,
+            topology.bias_currents.iter().copied(),
+            self.current_step,
+        );
+        drive.modulate(&mut self.neurons, lif, dt, None);
+        drive.integrate(&mut self.neurons, lif, dt, &mut self.rng, None, |_, _| {});
+
+        if let Some(stdp) = &topology.stdp {
+            let changed = topology
+                .inputs
+                .apply_stdp(stdp, &mut self.neurons, dt, |index| weights[index]);
+            if !changed.is_empty() {
+                let (weights, limits) = self
+                    .weights
+                    .get_or_insert_with(|| (topology.weights.clone(), InputLimits::default()));
+                for (index, weight) in changed {
+                    weights[index] = weight;
+                }
+                *limits = topology.inputs.limits(lif, |index| weights[index]);
+            }
+        }
+        self.current_step += 1;
+    }
+
+    /// Run `steps` steps, recording spikes and activity as `run_simulation`
+    /// does; no potentials or events are recorded.
+    pub fn run(&mut self, steps: usize, dt: f64) -> SimulationResult {
+        let n = self.neurons.len();
+        let mut result = SimulationResult {
+            dt,
+            steps,
+            spike_trains: vec![SpikeTrain::new(self.topology.spike_storage); n],
+            potential_stride: 1,
+            depolarization_blocks: vec![0; n],
+            ..SimulationResult::default()
+        };
+        result.activity.spikes_per_step.reserve(steps);
+        for step in 0..steps {
+            self.step(dt);
+            let topology = &self.topology;
+            result.record_step(
+                step,
+                &self.neurons,
+                |id| &topology.lif[id],
+                &topology.fan_out,
+            );
+        }
+        result
+    }
+}
+
+/// One run of `steps` steps per seed, each a `SimulationState` over `topology`,
+/// results in seed order. With the `parallel` feature, runs go on rayon's
+/// thread pool; the results are the same either way.
+pub fn run(
+    topology: &Arc<FrozenTopology>,
+    seeds: &[u64],
+    steps: usize,
+    dt: f64,
+) -> Vec<SimulationResult> {
+    let one = |&seed: &u64| SimulationState::new(Arc::clone(topology), seed).run(steps, dt);
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        seeds.par_iter().map(one).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        seeds.iter().map(one).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noisy_plastic_circuit() -> Network {
+        let mut network = Network::example_touch_circuit();
+        let alml = network.id_of("ALML").unwrap();
+        network.stimuli.push(Stimulus::new(alml, 10, 1800, 2.0));
+        network.lif.noise_std = 0.05;
+        network.stdp = Some(StdpParams::default());
+        network.connection_params.default.release_probability = 0.7;
+        network
+    }
+
+    #[test]
+    fn concurrent_runs_match_sequential_ones() {
+        let seeds = [1, 2, 3, 4];
+        let topology = noisy_plastic_circuit().freeze();
+        let concurrent: Vec<(SimulationResult, Vec<f64>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = seeds
+                .iter()
+                .map(|&seed| {
+                    let topology = Arc::clone(&topology);
+                    scope.spawn(move || {
+                        let mut state = SimulationState::new(topology, seed);
+                        let result = state.run(2000, 0.1);
+                        (result, state.weights().to_vec())
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut distinct = false;
+        for (&seed, (result, weights)) in seeds.iter().zip(&concurrent) {
+            let mut network = noisy_plastic_circuit();
+            network.seed(seed);
+            let expected = network.run_simulation(2000, 0.1);
+            assert!(expected.total_spikes() > 0);
+            for (id, train) in expected.spike_trains.iter().enumerate() {
+                assert_eq!(
+                    result.spike_trains[id].to_vec(),
+                    train.to_vec(),
+                    "seed {seed}"
+                );
+            }
+            let expected_weights: Vec<f64> = network.connections.iter().map(|c| c.weight).collect();
+            assert_eq!(*weights, expected_weights, "seed {seed}");
+            assert_ne!(*weights, topology.weights(), "seed {seed}");
+            distinct |= result.total_spikes() != concurrent[0].0.total_spikes();
+        }
+        assert!(distinct, "seeds should give different runs");
+    }
+}
@@ -0,0 +1,496 @@
+//! How alike each left neuron is to its right homolog.
+//!
+//! A neuron `<stem>L` pairs with `<stem>R` (or `<stem>l` with `<stem>r`) when
+//! both are present. Partners are compared by class (see [`crate::classes`]),
+//! so AVAL -> AIBL mirrors AVAR -> AIBR and AVAL -> AVAR mirrors AVAR -> AVAL.
+//! `ChemicalReceive` rows repeat the sends and are left out.
+//!
+//! `Network::bilateral_mismatches` lists the pairs whose sides disagree on
+//! type or region.
+//!
+//! `Network::lateralize` splits the neurons by side, and `Network::laterality`
+//! counts the wiring within and across sides.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::classes::{neuron_class, UNPAIRED_LR};
+use crate::functional::pearson;
+use crate::network::{Network, NeuronType, Region, SynapseType};
+
+/// Pairs shown at each end of the `Display` summary.
+const REPORT_ROWS: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SymmetryReport {
+    /// Most symmetric first, ties by left name.
+    pub pairs: Vec<PairSymmetry>,
+    /// Neurons without a homolog in the network, by id: unilateral neurons such
+    /// as DVA and AQR, and any side whose partner is missing.
+    pub unpaired: Vec<String>,
+    /// Mean pair score; NaN without pairs.
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairSymmetry {
+    pub class: String,
+    pub left: String,
+    pub right: String,
+    /// Jaccard similarity of the classes each side connects to.
+    pub out_jaccard: f64,
+    /// Jaccard similarity of the classes connecting to each side.
+    pub in_jaccard: f64,
+    /// Pearson correlation of the summed weights per partner class and
+    /// direction, over the partners of either side; NaN when undefined.
+    pub weight_correlation: f64,
+    /// Mean of the two Jaccard similarities; 1.0 for a perfect mirror.
+    pub score: f64,
+}
+
+/// A homolog pair assigned different types or regions, from
+/// `Network::bilateral_mismatches`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PairMismatch {
+    pub left: String,
+    pub right: String,
+    /// Left and right `neuron_type`, when they differ.
+    pub types: Option<(NeuronType, NeuronType)>,
+    /// Left and right `region`, when they differ.
+    pub regions: Option<(Region, Region)>,
+}
+
+impl fmt::Display for PairMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}:", self.left, self.right)?;
+        if let Some((left, right)) = self.types {
+            write!(f, " type {} vs {}", left.label(), right.label())?;
+        }
+        if let Some((left, right)) = self.regions {
+            if self.types.is_some() {
+                write!(f, ",")?;
+            }
+            write!(f, " region {} vs {}", left.label(), right.label())?;
+        }
+        Ok(())
+    }
+}
+
+/// Summed weight per partner class.
+type Profile = BTreeMap<String, f64>;
+
+impl Network {
+    pub fn bilateral_symmetry_report(&self) -> SymmetryReport {
+        let classes: Vec<String> = self
+            .neurons
+            .iter()
+            .map(|n| neuron_class(n.name()))
+            .collect();
+        let mut outputs = vec![Profile::new(); self.neurons.len()];
+        let mut inputs = vec![Profile::new(); self.neurons.len()];
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) || !self.counts(conn) {
+                continue;
+            }
+            *outputs[conn.from_id]
+                .entry(classes[conn.to_id].clone())
+                .or_default() += conn.weight;
+            *inputs[conn.to_id]
+                .entry(classes[conn.from_id].clone())
+                .or_default() += conn.weight;
+        }
+
+        let mut paired = vec![false; self.neurons.len()];
+        let mut pairs = Vec::new();
+        for (l, r) in self.homolog_pairs() {
+            paired[l] = true;
+            paired[r] = true;
+
+            let out_jaccard = jaccard(&outputs[l], &outputs[r]);
+            let in_jaccard = jaccard(&inputs[l], &inputs[r]);
+            let (mut xs, mut ys) = (Vec::new(), Vec::new());
+            for (a, b) in [(&outputs[l], &outputs[r]), (&inputs[l], &inputs[r])] {
+                for class in a.keys().chain(b.keys()).collect::<BTreeSet<_>>() {
+                    xs.push(a.get(class).copied().unwrap_or(0.0));
+                    ys.push(b.get(class).copied().unwrap_or(0.0));
+                }
+            }
+            pairs.push(PairSymmetry {
+                class: classes[l].clone(),
+                left: self.neurons[l].name().to_string(),
+                right: self.neurons[r].name().to_string(),
+                out_jaccard,
+                in_jaccard,
+                weight_correlation: pearson(&xs, &ys),
+                score: (out_jaccard + in_jaccard) / 2.0,
+            });
+        }
+        pairs.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.left.cmp(&b.left))
+        });
+
+        let score = pairs.iter().map(|p| p.score).sum::<f64>() / pairs.len() as f64;
+        SymmetryReport {
+            unpaired: self
+                .neurons
+                .iter()
+                .filter(|n| !paired[n.id])
+                .map(|n| n.name().to_string())
+                .collect(),
+            pairs,
+            score,
+        }
+    }
+
+    /// How far each homolog pair is from mirroring the other, `"left/right"`
+    /// with `1 - score` of `bilateral_symmetry_report`: the mean Jaccard
+    /// distance between the partner classes of the two sides, inputs and
+    /// outputs. Most asymmetric first, ties by left name. The connectome is
+    /// mostly mirror-symmetric, so pairs near the top point at real
+    /// lateralization or at errors in the data.
+    pub fn bilateral_asymmetry(&self) -> Vec<(String, f64)> {
+        let mut pairs = self.bilateral_symmetry_report().pairs;
+        pairs.sort_by(|a, b| {
+            a.score
+                .total_cmp(&b.score)
+                .then_with(|| a.left.cmp(&b.left))
+        });
+        pairs
+            .into_iter()
+            .map(|pair| (format!("{}/{}", pair.left, pair.right), 1.0 - pair.score))
+            .collect()
+    }
+
+    /// Homolog pairs whose two sides have a different `NeuronType` or
+    /// `Region`, by left id. The sides of a pair are the same cell type, so
+    /// any difference is almost always a classification error in the data;
+    /// a side left as `Other` or `Unknown` while its partner is assigned
+    /// counts too.
+    pub fn bilateral_mismatches(&self) -> Vec<PairMismatch> {
+        self.homolog_pairs()
+            .into_iter()
+            .filter_map(|(l, r)| {
+                let (left, right) = (&self.neurons[l], &self.neurons[r]);
+                let types = (left.neuron_type != right.neuron_type)
+                    .then_some((left.neuron_type, right.neuron_type));
+                let regions = (left.region != right.region).then_some((left.region, right.region));
+                (types.is_some() || regions.is_some()).then(|| PairMismatch {
+                    left: left.name().to_string(),
+                    right: right.name().to_string(),
+                    types,
+                    regions,
+                })
+            })
+            .collect()
+    }
+
+    /// `(left, right)` ids of every homolog pair present, by left id.
+    pub(crate) fn homolog_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for left in &self.neurons {
+            let right_name = match left.name().strip_suffix('L') {
+                Some(stem) => format!("{stem}R"),
+                None => match left.name().strip_suffix('l') {
+                    Some(stem) => format!("{stem}r"),
+                    None => continue,
+                },
+            };
+            if UNPAIRED_LR.contains(&left.name().to_ascii_uppercase().as_str()) {
+                continue;
+            }
+            if let Some(right) = self.id_of(&right_name) {
+                pairs.push((left.id, right));
+            }
+        }
+        pairs
+    }
+}
+
+/// Connections by the sides of their ends, from `Network::laterality`.
+/// `ChemicalReceive` rows are left out; gap junctions count once per row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Laterality {
+    /// Both ends on the same side.
+    pub ipsilateral: usize,
+    /// Ends on opposite sides.
+    pub contralateral: usize,
+    /// At least one end on the midline.
+    pub midline: usize,
+    pub ipsilateral_weight: f64,
+    pub contralateral_weight: f64,
+    pub midline_weight: f64,
+}
+
+impl Laterality {
+    /// Share of the connections between lateral neurons that stay on one
+    /// side; NaN without any.
+    pub fn ipsilateral_fraction(&self) -> f64 {
+        self.ipsilateral as f64 / (self.ipsilateral + self.contralateral) as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// The side a neuron's name puts it on: a final `L`/`l` or `R`/`r` after at
+/// least two characters, except for the unilateral `UNPAIRED_LR` neurons.
+fn side_of(name: &str) -> Option<Side> {
+    let upper = name.trim().to_ascii_uppercase();
+    if upper.len() < 3 || UNPAIRED_LR.contains(&upper.as_str()) {
+        return None;
+    }
+    match upper.as_bytes()[upper.len() - 1] {
+        b'L' => Some(Side::Left),
+        b'R' => Some(Side::Right),
+        _ => None,
+    }
+}
+
+impl Network {
+    /// Neuron ids on the left, on the right and on the midline (no side
+    /// suffix), each ascending. A neuron keeps its side whether or not its
+    /// homolog is in the network.
+    pub fn lateralize(&self) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+        let (mut left, mut right, mut midline) = (Vec::new(), Vec::new(), Vec::new());
+        for neuron in &self.neurons {
+            match side_of(neuron.name()) {
+                Some(Side::Left) => left.push(neuron.id),
+                Some(Side::Right) => right.push(neuron.id),
+                None => midline.push(neuron.id),
+            }
+        }
+        (left, right, midline)
+    }
+
+    /// How many connections, and how much weight, stay on one side of the
+    /// body or cross it, by the sides `lateralize` assigns.
+    pub fn laterality(&self) -> Laterality {
+        let sides: Vec<Option<Side>> = self.neurons.iter().map(|n| side_of(n.name())).collect();
+        let mut laterality = Laterality::default();
+        for conn in &self.connections {
+            if matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) || !self.counts(conn) {
+                continue;
+            }
+            let (count, weight) = match (sides[conn.from_id], sides[conn.to_id]) {
+                (Some(a), Some(b)) if a == b => (
+                    &mut laterality.ipsilateral,
+                    &mut laterality.ipsilateral_weight,
+                ),
+                (Some(_), Some(_)) => (
+                    &mut laterality.contralateral,
+                    &mut laterality.contralateral_weight,
+                ),
+                _ => (&mut laterality.midline, &mut laterality.midline_weight),
+            };
+            *count += 1;
+            *weight += conn.weight;
+        }
+        laterality
+    }
+}
+
+/// 1.0 when both sides have no partners.
+fn jaccard(a: &Profile, b: &Profile) -> f64 {
+    let union = a.keys().chain(b.keys()).collect::<BTreeSet<_>>().len();
+    if union == 0 {
+        return 1.0;
+    }
+    let shared = a.keys().filter(|class| b.contains_key(*class)).count();
+    shared as f64 / union as f64
+}
+
+impl SymmetryReport {
+    /// One row per pair, in `pairs` order.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record([
+            "class",
+            "left",
+            "right",
+            "out_jaccard",
+            "in_jaccard",
+            "weight_correlation",
+            "score",
+        ])?;
+        for pair in &self.pairs {
+            wtr.write_record([
+                &pair.class,
+                &pair.left,
+                &pair.right,
+                &pair.out_jaccard.to_string(),
+                &pair.in_jaccard.to_string(),
+                &pair.weight_correlation.to_string(),
+                &pair.score.to_string(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SymmetryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<24}{}", "pairs", self.pairs.len())?;
+        writeln!(f, "{:<24}{:.3}", "score", self.score)?;
+        let rows = REPORT_ROWS.min(self.pairs.len());
+        let least = self.pairs[self.pairs.len() - rows..].iter().rev();
+        for (label, pairs) in [
+            (
+                "most symmetric",
+                self.pairs[..rows].iter().collect::<Vec<_>>(),
+            ),
+            ("least symmetric", least.collect()),
+        ] {
+            writeln!(
+                f,
+                "\n{:<24}{:>8}{:>8}{:>8}{:>8}",
+                label, "score", "out", "in", "r"
+            )?;
+            for pair in pairs {
+                writeln!(
+                    f,
+                    "  {:<22}{:>8.3}{:>8.3}{:>8.3}{:>8.3}",
+                    format!("{}/{}", pair.left, pair.right),
+                    pair.score,
+                    pair.out_jaccard,
+                    pair.in_jaccard,
+                    pair.weight_correlation
+                )?;
+            }
+        }
+        writeln!(f, "\nunpaired ({})", self.unpaired.len())?;
+        write!(f, "  {}", self.unpaired.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    #[test]
+    fn the_least_mirrored_pairs_come_first() {
+        let mut network = Network::new();
+        for name in [
+            "AVAL", "AVAR", "ASHL", "ASHR", "AIBL", "AIBR", "RIML", "RIMR",
+        ] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        for (from, to) in [
+            ("AVAL", "AIBL"),
+            ("AVAR", "AIBR"),
+            ("ASHL", "AVAL"),
+            ("ASHR", "AIBR"),
+            ("RIML", "RIMR"),
+            ("RIMR", "RIML"),
+        ] {
+            let (from, to) = (network.id_of(from).unwrap(), network.id_of(to).unwrap());
+            network.add_connection(from, to, send, 1.0);
+        }
+        let asymmetry = network.bilateral_asymmetry();
+        assert_eq!(
+            asymmetry,
+            [
+                ("ASHL/ASHR", 0.5),
+                ("AVAL/AVAR", 0.5),
+                ("AIBL/AIBR", 0.25),
+                ("RIML/RIMR", 0.0),
+            ]
+            .map(|(pair, value)| (pair.to_string(), value))
+        );
+        let report = network.bilateral_symmetry_report();
+        for pair in &report.pairs {
+            let label = format!("{}/{}", pair.left, pair.right);
+            assert!(asymmetry.contains(&(label, 1.0 - pair.score)));
+        }
+    }
+
+    /// ASH onto AVA onto AIB on both sides, with reciprocal sends between
+    /// AVAL and AVAR, mirrored weight for weight.
+    fn mirrored() -> Network {
+        let mut network = Network::new();
+        for name in ["AVAL", "AVAR", "ASHL", "ASHR", "AIBL", "AIBR"] {
+            network.add_neuron(name, NeuronType::Interneuron, Region::Head, 0.0);
+        }
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        for (from, to, weight) in [
+            ("ASHL", "AVAL", 2.0),
+            ("ASHR", "AVAR", 2.0),
+            ("AVAL", "AIBL", 3.0),
+            ("AVAR", "AIBR", 3.0),
+            ("AVAL", "AVAR", 1.0),
+            ("AVAR", "AVAL", 1.0),
+        ] {
+            let (from, to) = (network.id_of(from).unwrap(), network.id_of(to).unwrap());
+            network.add_connection(from, to, send, weight);
+        }
+        network
+    }
+
+    #[test]
+    fn a_perfect_mirror_scores_one_until_an_edge_breaks() {
+        let mut network = mirrored();
+        let report = network.bilateral_symmetry_report();
+        assert_eq!(report.score, 1.0);
+        assert_eq!(report.pairs.len(), 3);
+        assert!(report.pairs.iter().all(|pair| pair.score == 1.0));
+        assert!(report.unpaired.is_empty());
+        let ava = report
+            .pairs
+            .iter()
+            .find(|pair| pair.class == "AVA")
+            .unwrap();
+        assert!((ava.weight_correlation - 1.0).abs() < 1e-12);
+        assert!(network.bilateral_mismatches().is_empty());
+
+        // AVAR -> AVAL gone: AVAL loses an AVA input, AVAR an AVA output.
+        let (avar, aval) = (
+            network.id_of("AVAR").unwrap(),
+            network.id_of("AVAL").unwrap(),
+        );
+        network.retain_connections(|conn| !(conn.from_id == avar && conn.to_id == aval));
+        let report = network.bilateral_symmetry_report();
+        let dropped: Vec<_> = report
+            .pairs
+            .iter()
+            .filter(|pair| pair.score < 1.0)
+            .map(|pair| {
+                (
+                    pair.left.as_str(),
+                    pair.out_jaccard,
+                    pair.in_jaccard,
+                    pair.score,
+                )
+            })
+            .collect();
+        assert_eq!(dropped, [("AVAL", 0.5, 0.5, 0.5)]);
+        assert!((report.score - 2.5 / 3.0).abs() < 1e-12);
+        assert!(network.bilateral_mismatches().is_empty());
+    }
+
+    #[test]
+    fn one_retyped_side_is_one_mismatch() {
+        let mut network = mirrored();
+        let avar = network.id_of("AVAR").unwrap();
+        network.neurons[avar].neuron_type = NeuronType::Motor;
+        assert_eq!(
+            network.bilateral_mismatches(),
+            [PairMismatch {
+                left: "AVAL".to_string(),
+                right: "AVAR".to_string(),
+                types: Some((NeuronType::Interneuron, NeuronType::Motor)),
+                regions: None,
+            }]
+        );
+        assert_eq!(network.bilateral_symmetry_report().score, 1.0);
+    }
+}
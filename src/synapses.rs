@@ -0,0 +1,261 @@
+//! Synaptic parameters kept beside the connection list instead of on
+//! `Connection`, as `Network::connection_params`.
+//!
+//! A connection's parameters are resolved in layers, each later layer
+//! replacing the fields it sets:
+//!
+//! 1. `ConnectionParams::default`;
+//! 2. the override for the connection's synapse class;
+//! 3. every class-pair override whose `from` and `to` selections match the
+//!    connection's endpoints, in order;
+//! 4. the override for the connection's index.
+//!
+//! Runs resolve the table once, at their start, into one `SynapseParams` per
+//! connection. Gap junction rows are matched as stored, so a class-pair
+//! override applies to the mirrored row only if it matches that direction too.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::Network;
+use crate::paths::SynapseClass;
+use crate::select::Selection;
+
+/// The parameters of one connection, as the simulator uses them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SynapseParams {
+    /// Multiplies the postsynaptic neuron's `chemical_gain` or `gap_gain`.
+    pub gain: f64,
+    /// Weight bounds under STDP; unset bounds are those of `StdpParams`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_max: Option<f64>,
+    /// Chance that a presynaptic spike is transmitted through a chemical
+    /// send, drawn from the run's generator; 1 transmits every spike without
+    /// a draw. Other connections ignore it.
+    pub release_probability: f64,
+}
+
+impl Default for SynapseParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            w_min: None,
+            w_max: None,
+            release_probability: 1.0,
+        }
+    }
+}
+
+/// Replacement parameters for a layer; unset fields keep the value below.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SynapseOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_probability: Option<f64>,
+}
+
+impl SynapseOverride {
+    pub fn apply(&self, base: SynapseParams) -> SynapseParams {
+        SynapseParams {
+            gain: self.gain.unwrap_or(base.gain),
+            w_min: self.w_min.or(base.w_min),
+            w_max: self.w_max.or(base.w_max),
+            release_probability: self.release_probability.unwrap_or(base.release_probability),
+        }
+    }
+
+    /// This override with the fields `later` sets replaced.
+    pub fn merged(&self, later: &SynapseOverride) -> SynapseOverride {
+        SynapseOverride {
+            gain: later.gain.or(self.gain),
+            w_min: later.w_min.or(self.w_min),
+            w_max: later.w_max.or(self.w_max),
+            release_probability: later.release_probability.or(self.release_probability),
+        }
+    }
+}
+
+/// An override for the connections from neurons `from` selects to neurons `to`
+/// selects; an unset side matches every neuron.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PairOverride {
+    pub from: Option<Selection>,
+    pub to: Option<Selection>,
+    pub params: SynapseOverride,
+}
+
+/// The layered parameter table; see the module documentation for the order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectionParams {
+    pub default: SynapseParams,
+    pub by_class: HashMap<SynapseClass, SynapseOverride>,
+    pub by_pair: Vec<PairOverride>,
+    /// Overrides by connection index. `retain_connections` moves them along;
+    /// other edits to `Network::connections` leave them where they were.
+    pub by_connection: HashMap<usize, SynapseOverride>,
+}
+
+impl ConnectionParams {
+    /// Whether every connection resolves to `SynapseParams::default()`.
+    pub fn is_default(&self) -> bool {
+        self.default == SynapseParams::default()
+            && self
+                .by_class
+                .values()
+                .all(|o| *o == SynapseOverride::default())
+            && self
+                .by_pair
+                .iter()
+                .all(|o| o.params == SynapseOverride::default())
+            && self
+                .by_connection
+                .values()
+                .all(|o| *o == SynapseOverride::default())
+    }
+
+    /// The parameters of every connection of `network`, in index order.
+    pub fn resolve(&self, network: &Network) -> Vec<SynapseParams> {
+        let side = |selection: &Option<Selection>| {
+            selection.as_ref().map(|selection| {
+                (0..network.neurons.len())
+                    .map(|id| selection.matches(network, id))
+                    .collect::<Vec<_>>()
+            })
+        };
+        let pairs: Vec<_> = self
+            .by_pair
+            .iter()
+            .map(|pair| (side(&pair.from), side(&pair.to), &pair.params))
+            .collect();
+        let matches = |side: &Option<Vec<bool>>, id: usize| side.as_ref().is_none_or(|m| m[id]);
+
+        network
+            .connections
+            .iter()
+            .enumerate()
+            .map(|(index, conn)| {
+                let mut params = self.default;
+                if let Some(class) = conn.synapse_type.class() {
+                    if let Some(layer) = self.by_class.get(&class) {
+                        params = layer.apply(params);
+                    }
+                }
+                for (from, to, layer) in &pairs {
+                    if matches(from, conn.from_id) && matches(to, conn.to_id) {
+                        params = layer.apply(params);
+                    }
+                }
+                if let Some(layer) = self.by_connection.get(&index) {
+                    params = layer.apply(params);
+                }
+                params
+            })
+            .collect()
+    }
+
+    /// Move connection overrides to `new_index[old]`, as
+    /// `Annotations::remap_connections` does.
+    pub(crate) fn remap_connections(&mut self, new_index: &[Option<usize>]) {
+        self.by_connection = std::mem::take(&mut self.by_connection)
+            .into_iter()
+            .filter_map(|(index, layer)| Some((new_index.get(index).copied().flatten()?, layer)))
+            .collect();
+    }
+}
+
+impl Network {
+    /// The parameters `connection_params` gives connection `index`.
+    pub fn synapse_params(&self, index: usize) -> SynapseParams {
+        match &self.synapses {
+            Some(resolved) => resolved.get(index).copied().unwrap_or_default(),
+            None if self.connection_params.is_default() => SynapseParams::default(),
+            None => self
+                .connection_params
+                .resolve(self)
+                .get(index)
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Resolve `connection_params` for a run, or `None` when every connection
+    /// has the defaults.
+    pub(crate) fn resolve_synapses(&self) -> Option<Vec<SynapseParams>> {
+        (!self.connection_params.is_default()).then(|| self.connection_params.resolve(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{ChemicalSubtype, SynapseType};
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    fn gain(gain: f64) -> SynapseOverride {
+        SynapseOverride {
+            gain: Some(gain),
+            ..SynapseOverride::default()
+        }
+    }
+
+    #[test]
+    fn later_layers_replace_what_they_set() {
+        let mut network = Network::with_neuron_roster(&["AVAL", "AVBL", "AVBR", "AVDL"]);
+        network.add_connection(0, 1, EXCITATORY, 1.0);
+        network.add_connection(0, 2, EXCITATORY, 1.0);
+        network.add_connection(3, 0, EXCITATORY, 1.0);
+        network.add_connection(0, 1, SynapseType::GapJunction, 1.0);
+        network.connection_params = ConnectionParams {
+            default: SynapseParams {
+                gain: 0.5,
+                ..SynapseParams::default()
+            },
+            by_class: HashMap::from([(
+                SynapseClass::Chemical,
+                SynapseOverride {
+                    gain: Some(0.8),
+                    release_probability: Some(0.9),
+                    ..SynapseOverride::default()
+                },
+            )]),
+            by_pair: vec![PairOverride {
+                from: Some("class:AVA".parse().unwrap()),
+                to: Some("class:AVB".parse().unwrap()),
+                params: gain(2.0),
+            }],
+            by_connection: HashMap::from([(1, gain(3.0))]),
+        };
+
+        let resolved = network.connection_params.resolve(&network);
+        let gains: Vec<f64> = resolved.iter().map(|p| p.gain).collect();
+        // AVA -> AVB beats the class default, and loses to AVAL -> AVBR's own
+        // override; AVDL -> AVAL only matches the class, the gap junction the
+        // class-pair override alone.
+        assert_eq!(gains, vec![2.0, 3.0, 0.8, 2.0]);
+        assert_eq!(resolved[0].release_probability, 0.9);
+        assert_eq!(resolved[3].release_probability, 1.0);
+        assert_eq!(network.synapse_params(1), resolved[1]);
+        assert_eq!(network.synapse_params(9), SynapseParams::default());
+    }
+
+    #[test]
+    fn only_untouched_tables_are_default() {
+        let mut params = ConnectionParams::default();
+        assert!(params.is_default());
+        params.by_connection.insert(0, SynapseOverride::default());
+        assert!(params.is_default());
+        params.by_connection.insert(0, gain(1.0));
+        assert!(!params.is_default());
+    }
+}
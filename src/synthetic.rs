@@ -0,0 +1,110 @@
+//! Seeded synthetic networks and edge lists for benchmarks and tests.
+//!
+//! Output depends only on the arguments, so sizes and seeds fully identify an input.
+
+use std::io::{self, Write};
+
+use crate::network::{ChemicalSubtype, Network, NeuronType, Region, SynapseType};
+use crate::rng::Rng;
+
+const TYPES: [NeuronType; 3] = [
+    NeuronType::Sensory,
+    NeuronType::Interneuron,
+    NeuronType::Motor,
+];
+
+/// Name of the `i`-th synthetic neuron.
+pub fn neuron_name(i: usize) -> String {
+    format!("N{i:06}")
+}
+
+/// A random network with `neurons` typed neurons and `edges` connections.
+///
+/// Roughly 70% of edges are chemical sends (one in ten inhibitory) and the rest
+/// gap junctions, with integer weights in `1..=10`.
+pub fn random_network(neurons: usize, edges: usize, seed: u64) -> Network {
+    let mut rng = Rng::new(seed);
+    let mut network = Network::new();
+    for i in 0..neurons {
+        let position = rng.next_f64();
+        let region = if position < 0.33 {
+            Region::Head
+        } else if position < 0.66 {
+            Region::MidBody
+        } else {
+            Region::Tail
+        };
+        network.add_neuron(
+            &neuron_name(i),
+            TYPES[rng.below(TYPES.len())],
+            region,
+            position,
+        );
+    }
+    if neurons == 0 {
+        return network;
+    }
+
+    let batch: Vec<_> = (0..edges)
+        .map(|_| {
+            let from = rng.below(neurons);
+            let to = rng.below(neurons);
+            let synapse_type = random_synapse_type(&mut rng);
+            let weight = 1.0 + rng.below(10) as f64;
+            (from, to, synapse_type, weight)
+        })
+        .collect();
+    network
+        .add_connections_bulk(batch)
+        .expect("generated endpoints are in range");
+    network
+}
+
+/// Write `rows` random rows in the `NeuronConnect.csv` format over `neurons` names.
+pub fn write_edge_list<W: Write>(
+    writer: W,
+    neurons: usize,
+    rows: usize,
+    seed: u64,
+) -> io::Result<()> {
+    let mut rng = Rng::new(seed);
+    let mut out = io::BufWriter::new(writer);
+    writeln!(out, "Neuron 1,Neuron 2,Type,Nbr")?;
+    for _ in 0..rows {
+        let from = rng.below(neurons.max(1));
+        let to = rng.below(neurons.max(1));
+        let code = match rng.below(10) {
+            0..=4 => "Sp",
+            5..=6 => "R",
+            _ => "EJ",
+        };
+        let nbr = 1 + rng.below(10);
+        writeln!(
+            out,
+            "{},{},{code},{nbr}",
+            neuron_name(from),
+            neuron_name(to)
+        )?;
+    }
+    out.flush()
+}
+
+/// In-memory form of `write_edge_list`.
+pub fn edge_list_csv(neurons: usize, rows: usize, seed: u64) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_edge_list(&mut buf, neurons, rows, seed).expect("writing to a Vec cannot fail");
+    buf
+}
+
+fn random_synapse_type(rng: &mut Rng) -> SynapseType {
+    if rng.below(10) < 7 {
+        let subtype = if rng.below(10) == 0 {
+            ChemicalSubtype::Inhibitory
+        } else {
+            ChemicalSubtype::Excitatory
+        };
+        SynapseType::ChemicalSend(subtype)
+    } else {
+        SynapseType::GapJunction
+    }
+}
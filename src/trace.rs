@@ -0,0 +1,257 @@
+//! Stimuli that follow a sampled time course, such as a measured odour
+//! concentration, read from CSV.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{FlymindError, LoadError};
+use crate::network::Network;
+use crate::simulation::{Stimulus, StimulusShape};
+
+/// How a trace is read between two samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TraceInterpolation {
+    /// Straight line between neighbouring samples.
+    #[default]
+    Linear,
+    /// Each sample's value until the next one (zero-order hold).
+    Hold,
+}
+
+/// How a trace is read before its first sample and after its last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TraceBoundary {
+    /// The nearest sample's value.
+    #[default]
+    Hold,
+    Zero,
+}
+
+/// A sampled current time course, for `StimulusShape::Trace`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StimulusTrace {
+    /// `(time, value)` samples with strictly increasing times, in steps from
+    /// the start of the stimulus window (fractions allowed).
+    pub points: Vec<(f64, f64)>,
+    pub interpolation: TraceInterpolation,
+    pub boundary: TraceBoundary,
+}
+
+impl StimulusTrace {
+    /// The trace at `time`, in steps; zero for a trace without samples.
+    pub fn value_at(&self, time: f64) -> f64 {
+        let (Some(&(first_time, first)), Some(&(last_time, last))) =
+            (self.points.first(), self.points.last())
+        else {
+            return 0.0;
+        };
+        if time < first_time || time > last_time {
+            return match self.boundary {
+                TraceBoundary::Hold if time < first_time => first,
+                TraceBoundary::Hold => last,
+                TraceBoundary::Zero => 0.0,
+            };
+        }
+        let next = self.points.partition_point(|&(t, _)| t <= time);
+        if next == self.points.len() {
+            return last;
+        }
+        let (t0, v0) = self.points[next - 1];
+        let (t1, v1) = self.points[next];
+        match self.interpolation {
+            TraceInterpolation::Linear => v0 + (v1 - v0) * (time - t0) / (t1 - t0),
+            TraceInterpolation::Hold => v0,
+        }
+    }
+}
+
+/// How `Stimulus::from_csv` reads its table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceOptions {
+    /// Header of the time column, in the model's time unit.
+    pub time_column: String,
+    pub interpolation: TraceInterpolation,
+    pub boundary: TraceBoundary,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            time_column: "time".to_string(),
+            interpolation: TraceInterpolation::default(),
+            boundary: TraceBoundary::default(),
+        }
+    }
+}
+
+impl Stimulus {
+    /// One stimulus per entry of `column_map` (neuron name to column header),
+    /// each following its column of a CSV table of samples against
+    /// `options.time_column`. Times are converted to steps of `dt`, so a run
+    /// at that `dt` samples the trace on every step, whatever the table's
+    /// resolution. The traces are added to `network.stimulus_traces`; the
+    /// stimuli, which the caller adds to `network.stimuli` (or another network
+    /// with the same traces), cover every step from 0 with amplitude 1 scaling
+    /// the trace, and come in neuron id order.
+    ///
+    /// Fails on an unknown neuron, a missing column, a cell that is not a
+    /// number or times that do not increase.
+    pub fn from_csv<R: Read>(
+        reader: R,
+        network: &mut Network,
+        column_map: &HashMap<String, String>,
+        dt: f64,
+        options: &TraceOptions,
+    ) -> Result<Vec<Stimulus>, FlymindError> {
+        if !(dt.is_finite() && dt > 0.0) {
+            return Err(FlymindError::InvalidTimeStep { dt, max_dt: None });
+        }
+        let mut targets = Vec::with_capacity(column_map.len());
+        for (name, column) in column_map {
+            let id = network
+                .id_of(name)
+                .ok_or_else(|| FlymindError::UnknownNeuron { name: name.clone() })?;
+            targets.push((id, column.as_str()));
+        }
+        targets.sort_unstable();
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let header = rdr.headers()?.clone();
+        let position = |column: &str| {
+            header
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| LoadError::MissingColumn {
+                    column: column.to_string(),
+                })
+        };
+        let time_column = position(&options.time_column)?;
+        let columns = targets
+            .iter()
+            .map(|&(_, column)| position(column))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut traces = vec![Vec::new(); targets.len()];
+        let mut previous = f64::NEG_INFINITY;
+        let mut record = csv::StringRecord::new();
+        while rdr.read_record(&mut record)? {
+            let line = record.position().map_or(0, |p| p.line());
+            let cell = |index: usize| {
+                let value = record.get(index).unwrap_or("");
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| LoadError::InvalidValue {
+                        line,
+                        column: header[index].to_string(),
+                        value: value.to_string(),
+                    })
+            };
+            let time = cell(time_column)?;
+            if time <= previous {
+                return Err(LoadError::InvalidValue {
+                    line,
+                    column: options.time_column.clone(),
+                    value: record[time_column].to_string(),
+                }
+                .into());
+            }
+            previous = time;
+            for (trace, &index) in traces.iter_mut().zip(&columns) {
+                trace.push((time / dt, cell(index)?));
+            }
+        }
+
+        let first = network.stimulus_traces.len();
+        network
+            .stimulus_traces
+            .extend(traces.into_iter().map(|points| StimulusTrace {
+                points,
+                interpolation: options.interpolation,
+                boundary: options.boundary,
+            }));
+        Ok(targets
+            .iter()
+            .enumerate()
+            .map(|(k, &(neuron, _))| Stimulus {
+                neuron,
+                start_step: 0,
+                duration_steps: usize::MAX,
+                amplitude: 1.0,
+                shape: StimulusShape::Trace(first + k),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch_map(column: &str) -> HashMap<String, String> {
+        HashMap::from([("ALML".to_string(), column.to_string())])
+    }
+
+    #[test]
+    fn coarse_triangle_is_interpolated_at_fine_dt() {
+        let mut network = Network::example_touch_circuit();
+        let csv = "time,odour\n0,0\n1,1\n2,0\n";
+        let stimuli = Stimulus::from_csv(
+            csv.as_bytes(),
+            &mut network,
+            &touch_map("odour"),
+            0.25,
+            &TraceOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(stimuli.len(), 1);
+        let stim = stimuli[0];
+        let traces = &network.stimulus_traces;
+        let expected = [0.0, 0.25, 0.5, 0.75, 1.0, 0.75, 0.5, 0.25, 0.0];
+        for (step, want) in expected.into_iter().enumerate() {
+            assert!((stim.current_at(step, traces) - want).abs() < 1e-12);
+        }
+        // Past the last sample the boundary value holds.
+        assert_eq!(stim.current_at(20, traces), 0.0);
+
+        let held = Stimulus::from_csv(
+            csv.as_bytes(),
+            &mut network,
+            &touch_map("odour"),
+            0.25,
+            &TraceOptions {
+                interpolation: TraceInterpolation::Hold,
+                ..TraceOptions::default()
+            },
+        )
+        .unwrap()[0];
+        assert_eq!(held.shape, StimulusShape::Trace(1));
+        let traces = &network.stimulus_traces;
+        assert_eq!(held.current_at(3, traces), 0.0);
+        assert_eq!(held.current_at(4, traces), 1.0);
+        assert_eq!(held.current_at(7, traces), 1.0);
+    }
+
+    #[test]
+    fn missing_column_errors_without_adding_traces() {
+        let mut network = Network::example_touch_circuit();
+        let err = Stimulus::from_csv(
+            "time,odour\n0,1\n".as_bytes(),
+            &mut network,
+            &touch_map("salt"),
+            0.1,
+            &TraceOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            FlymindError::Load(LoadError::MissingColumn { ref column }) if column == "salt"
+        ));
+        assert!(network.stimulus_traces.is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+//! Repeated runs of a stochastic network, aggregated per neuron.
+
+use serde::Serialize;
+
+use crate::network::Network;
+
+/// Firing rates across the trials of `Network::run_trials`, indexed by neuron
+/// id, in spikes per unit time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TrialStats {
+    pub trials: usize,
+    pub steps: usize,
+    pub dt: f64,
+    pub mean_rate: Vec<f64>,
+    /// Sample standard deviation; 0.0 with fewer than two trials.
+    pub std_rate: Vec<f64>,
+    /// Spikes of each trial, network-wide.
+    pub total_spikes: Vec<usize>,
+}
+
+impl TrialStats {
+    /// `std_rate / mean_rate`, 0.0 for neurons that never fired: low for
+    /// neurons that respond reliably, high for variable ones.
+    pub fn coefficient_of_variation(&self) -> Vec<f64> {
+        self.mean_rate
+            .iter()
+            .zip(&self.std_rate)
+            .map(|(&mean, &std)| if mean > 0.0 { std / mean } else { 0.0 })
+            .collect()
+    }
+}
+
+impl Network {
+    /// Run `trials` simulations of `steps` steps, each from rest after
+    /// `reset_state`. Trial `i` is seeded with `seeds[i]`; trials past the end of
+    /// `seeds` carry on with the generator where the previous trial left it, so
+    /// they still differ and are reproducible. Stimuli and parameters carry
+    /// over between trials as they are. With STDP on, every trial starts from
+    /// the weights the network had before the first, and they are restored
+    /// afterwards; the rest of the network is left as the last trial ended.
+    pub fn run_trials(
+        &mut self,
+        trials: usize,
+        steps: usize,
+        dt: f64,
+        seeds: &[u64],
+    ) -> TrialStats {
+        let n = self.neurons.len();
+        let mut stats = TrialStats {
+            trials,
+            steps,
+            dt,
+            mean_rate: vec![0.0; n],
+            std_rate: vec![0.0; n],
+            total_spikes: Vec::with_capacity(trials),
+        };
+        // Welford's running mean and sum of squared deviations.
+        let mut squares = vec![0.0; n];
+        let weights: Option<Vec<f64>> = self
+            .stdp
+            .is_some()
+            .then(|| self.connections.iter().map(|conn| conn.weight).collect());
+        for trial in 0..trials {
+            if let Some(weights) = &weights {
+                self.restore_weights(weights);
+            }
+            self.reset_state();
+            if let Some(&seed) = seeds.get(trial) {
+                self.seed(seed);
+            }
+            let result = self.run_simulation(steps, dt);
+            stats.total_spikes.push(result.total_spikes());
+            let count = (trial + 1) as f64;
+            for (id, (mean, square)) in stats.mean_rate.iter_mut().zip(&mut squares).enumerate() {
+                let rate = result.firing_rate(id);
+                let delta = rate - *mean;
+                *mean += delta / count;
+                *square += delta * (rate - *mean);
+            }
+        }
+        if let Some(weights) = &weights {
+            self.restore_weights(weights);
+        }
+        if trials > 1 {
+            for (std, square) in stats.std_rate.iter_mut().zip(squares) {
+                *std = (square / (trials - 1) as f64).sqrt();
+            }
+        }
+        stats
+    }
+
+    fn restore_weights(&mut self, weights: &[f64]) {
+        for (conn, &weight) in self.connections.iter_mut().zip(weights) {
+            conn.weight = weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{StdpParams, Stimulus};
+
+    fn plastic_touch_circuit() -> Network {
+        let mut network = Network::example_touch_circuit();
+        for conn in &mut network.connections {
+            conn.plastic = true;
+        }
+        network.stdp = Some(StdpParams::default());
+        for touch in ["ALML", "ALMR", "AVM"] {
+            let id = network.id_of(touch).unwrap();
+            network.stimuli.push(Stimulus::new(id, 0, 400, 2.0));
+        }
+        network
+    }
+
+    #[test]
+    fn stdp_trials_start_from_the_same_weights() {
+        let mut network = plastic_touch_circuit();
+        let before: Vec<f64> = network.connections.iter().map(|c| c.weight).collect();
+        let stats = network.run_trials(3, 400, 0.1, &[]);
+        let after: Vec<f64> = network.connections.iter().map(|c| c.weight).collect();
+        assert_eq!(before, after);
+        assert!(stats.total_spikes[0] > 0);
+        assert!(stats
+            .total_spikes
+            .iter()
+            .all(|&n| n == stats.total_spikes[0]));
+
+        let mut single = plastic_touch_circuit();
+        single.run_simulation(400, 0.1);
+        let learned: Vec<f64> = single.connections.iter().map(|c| c.weight).collect();
+        assert_ne!(before, learned, "the protocol should change some weight");
+    }
+}
@@ -0,0 +1,454 @@
+//! Random walks on the weighted chemical graph, a cheap proxy for how signals
+//! spread.
+//!
+//! From each neuron the walk takes one of its chemical sends with probability
+//! proportional to weight; parallel rows to one partner add up and
+//! non-positive weights are never taken. A neuron without such sends is a dead
+//! end: the walk stops there. `Network::random_walk_visits` also crosses gap
+//! junctions, either way, and starts over from a dead end.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::network::{Network, SynapseType};
+use crate::rng::Rng;
+
+/// Most neurons lying on walks between the pair that
+/// `Network::exact_hitting_time` will solve for.
+pub const EXACT_HITTING_LIMIT: usize = 2_000;
+
+/// Most steps of power iteration spent on a stationary distribution or a
+/// centrality.
+pub const MAX_POWER_ITERATIONS: usize = 1_000;
+
+/// Expected first-passage time from one neuron to another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HittingTime {
+    /// Mean number of steps to first arrival.
+    Finite(f64),
+    /// The target is reachable, but the walk can also end at a dead end or
+    /// wander where the target cannot be reached, so the mean is infinite;
+    /// `probability` is the chance of arriving at all.
+    Absorbed { probability: f64 },
+    /// No chemical path leads from one to the other.
+    Unreachable,
+}
+
+/// Outcome of the sampled walks of `Network::hitting_time`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HittingEstimate {
+    pub walks: usize,
+    pub arrived: usize,
+    /// Walks that stopped at a dead end first.
+    pub absorbed: usize,
+    /// Walks still under way after `max_steps`.
+    pub timed_out: usize,
+    /// Mean steps over the walks that arrived, `None` if none did. Only an
+    /// estimate of the hitting time when every walk arrived.
+    pub mean_steps: Option<f64>,
+    /// Whether any chemical path leads to the target; no walks are run if not.
+    pub reachable: bool,
+}
+
+impl HittingEstimate {
+    pub fn arrival_fraction(&self) -> f64 {
+        if self.walks == 0 {
+            0.0
+        } else {
+            self.arrived as f64 / self.walks as f64
+        }
+    }
+}
+
+impl Network {
+    /// Long-run share of time the walk spends at each neuron, indexed by id and
+    /// summing to 1. Each step teleports to a uniformly random neuron with
+    /// probability `teleport` (clamped to 0–1), and always from a dead end, so
+    /// this is PageRank with damping `1 - teleport`.
+    pub fn random_walk_stationary(&self, teleport: f64) -> Vec<f64> {
+        self.stationary_iteration(teleport, 1e-12).0
+    }
+
+    /// Power iteration behind `random_walk_stationary`, run until the L1
+    /// change of a step falls below `tol` or for `MAX_POWER_ITERATIONS`
+    /// steps: the ranks, the steps taken and the last step's change.
+    pub(crate) fn stationary_iteration(&self, teleport: f64, tol: f64) -> (Vec<f64>, usize, f64) {
+        let n = self.neurons.len();
+        if n == 0 {
+            return (Vec::new(), 0, 0.0);
+        }
+        let teleport = teleport.clamp(0.0, 1.0);
+        let transitions = self.walk_transitions();
+        let uniform = 1.0 / n as f64;
+        let mut rank = vec![uniform; n];
+        let mut next = vec![0.0; n];
+        let mut iterations = 0;
+        let mut change = f64::INFINITY;
+        while iterations < MAX_POWER_ITERATIONS {
+            let dangling: f64 = (0..n)
+                .filter(|&v| transitions[v].is_empty())
+                .map(|v| rank[v])
+                .sum();
+            next.fill((teleport + (1.0 - teleport) * dangling) * uniform);
+            for (v, list) in transitions.iter().enumerate() {
+                for &(w, p) in list {
+                    next[w] += (1.0 - teleport) * rank[v] * p;
+                }
+            }
+            change = rank.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+            std::mem::swap(&mut rank, &mut next);
+            iterations += 1;
+            if change < tol {
+                break;
+            }
+        }
+        (rank, iterations, change)
+    }
+
+    /// Monte Carlo estimate of the first-passage time from `from` to `to` over
+    /// `n_walks` walks of at most `max_steps` steps, reproducible for a given
+    /// `seed`. A walk from `to` to itself arrives at step 0.
+    pub fn hitting_time(
+        &self,
+        from: usize,
+        to: usize,
+        n_walks: usize,
+        max_steps: usize,
+        seed: u64,
+    ) -> HittingEstimate {
+        let n = self.neurons.len();
+        let transitions = self.walk_transitions();
+        let mut estimate = HittingEstimate {
+            reachable: from < n && to < n && walk_reach(&transitions, from)[to],
+            ..HittingEstimate::default()
+        };
+        if !estimate.reachable {
+            return estimate;
+        }
+        let mut rng = Rng::new(seed);
+        let mut total_steps = 0usize;
+        for _ in 0..n_walks {
+            let mut at = from;
+            let mut steps = 0;
+            loop {
+                if at == to {
+                    estimate.arrived += 1;
+                    total_steps += steps;
+                    break;
+                }
+                if transitions[at].is_empty() {
+                    estimate.absorbed += 1;
+                    break;
+                }
+                if steps == max_steps {
+                    estimate.timed_out += 1;
+                    break;
+                }
+                at = sample(&transitions[at], rng.next_f64());
+                steps += 1;
+            }
+        }
+        estimate.walks = n_walks;
+        if estimate.arrived > 0 {
+            estimate.mean_steps = Some(total_steps as f64 / estimate.arrived as f64);
+        }
+        estimate
+    }
+
+    /// How often a walk of `steps` steps from `start` visits each neuron,
+    /// counting `start` itself before the first step; only visited neurons
+    /// are listed, and the counts sum to `steps + 1`. Besides chemical sends
+    /// the walk crosses gap junctions in either direction, a junction
+    /// weighing the mean of its two rows (or the one there is), and on a
+    /// dead end it goes back to `start`. As `steps` grows the visit shares
+    /// settle to the walk's long-run occupancy, a PageRank-like importance
+    /// far cheaper to sample than exact centrality on large networks.
+    /// Reproducible for a given `seed`; empty if `start` is out of range.
+    pub fn random_walk_visits(
+        &self,
+        start: usize,
+        steps: usize,
+        seed: u64,
+    ) -> HashMap<usize, usize> {
+        let mut visits = HashMap::new();
+        if start >= self.neurons.len() {
+            return visits;
+        }
+        let transitions = self.walk_transitions_with(true);
+        let mut rng = Rng::new(seed);
+        let mut at = start;
+        *visits.entry(at).or_default() += 1;
+        for _ in 0..steps {
+            at = if transitions[at].is_empty() {
+                start
+            } else {
+                sample(&transitions[at], rng.next_f64())
+            };
+            *visits.entry(at).or_default() += 1;
+        }
+        visits
+    }
+
+    /// Exact first-passage time from `from` to `to` by dense linear solves, or
+    /// `None` when more than `EXACT_HITTING_LIMIT` neurons lie on walks between
+    /// them. Out-of-range ids are `Unreachable`.
+    pub fn exact_hitting_time(&self, from: usize, to: usize) -> Option<HittingTime> {
+        let n = self.neurons.len();
+        if from >= n || to >= n {
+            return Some(HittingTime::Unreachable);
+        }
+        if from == to {
+            return Some(HittingTime::Finite(0.0));
+        }
+        let transitions = self.walk_transitions();
+        let reached = walk_reach(&transitions, from);
+        if !reached[to] {
+            return Some(HittingTime::Unreachable);
+        }
+        // Unknowns are the neurons on some walk from `from` to `to`; stepping
+        // anywhere else is a walk that never arrives.
+        let mut backward: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+        for (v, list) in transitions.iter().enumerate() {
+            for &(w, p) in list {
+                backward[w].push((v, p));
+            }
+        }
+        let leads_to = walk_reach(&backward, to);
+        let states: Vec<usize> = (0..n)
+            .filter(|&v| reached[v] && leads_to[v] && v != to)
+            .collect();
+        if states.len() > EXACT_HITTING_LIMIT {
+            return None;
+        }
+        let mut index = vec![usize::MAX; n];
+        for (i, &v) in states.iter().enumerate() {
+            index[v] = i;
+        }
+        // (I - Q) x = b, with Q the transitions among the unknowns.
+        let system = |constant: &dyn Fn(usize) -> f64| {
+            let m = states.len();
+            let mut a = vec![vec![0.0; m]; m];
+            let mut b = vec![0.0; m];
+            for (i, &v) in states.iter().enumerate() {
+                a[i][i] += 1.0;
+                b[i] = constant(v);
+                for &(w, p) in &transitions[v] {
+                    if index[w] != usize::MAX {
+                        a[i][index[w]] -= p;
+                    }
+                }
+            }
+            (a, b)
+        };
+
+        let arrival = |v: usize| {
+            transitions[v]
+                .iter()
+                .filter(|&&(w, _)| w == to)
+                .map(|&(_, p)| p)
+                .sum()
+        };
+        let (a, b) = system(&arrival);
+        let probability = solve(a, b)?[index[from]];
+        if probability < 1.0 - 1e-9 {
+            return Some(HittingTime::Absorbed {
+                probability: probability.max(0.0),
+            });
+        }
+        let (a, b) = system(&|_| 1.0);
+        Some(HittingTime::Finite(solve(a, b)?[index[from]]))
+    }
+
+    /// Outgoing walk probabilities of every neuron, by partner id.
+    fn walk_transitions(&self) -> Vec<Vec<(usize, f64)>> {
+        self.walk_transitions_with(false)
+    }
+
+    /// `walk_transitions`, with each gap junction taken both ways when
+    /// `gap_junctions` is set.
+    fn walk_transitions_with(&self, gap_junctions: bool) -> Vec<Vec<(usize, f64)>> {
+        let mut transitions: Vec<Vec<(usize, f64)>> = vec![Vec::new(); self.neurons.len()];
+        let mut gaps: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for conn in &self.connections {
+            if conn.weight <= 0.0 || !self.counts(conn) {
+                continue;
+            }
+            match conn.synapse_type {
+                SynapseType::ChemicalSend(_) => {
+                    transitions[conn.from_id].push((conn.to_id, conn.weight));
+                }
+                SynapseType::GapJunction if gap_junctions && conn.from_id != conn.to_id => {
+                    *gaps.entry((conn.from_id, conn.to_id)).or_insert(0.0) += conn.weight;
+                }
+                _ => {}
+            }
+        }
+        for (&(a, b), &forward) in &gaps {
+            match gaps.get(&(b, a)) {
+                Some(&backward) => transitions[a].push((b, (forward + backward) / 2.0)),
+                None => {
+                    transitions[a].push((b, forward));
+                    transitions[b].push((a, forward));
+                }
+            }
+        }
+        for list in &mut transitions {
+            list.sort_by_key(|&(w, _)| w);
+            list.dedup_by(|next, kept| {
+                let same = next.0 == kept.0;
+                if same {
+                    kept.1 += next.1;
+                }
+                same
+            });
+            let total: f64 = list.iter().map(|&(_, w)| w).sum();
+            for step in list.iter_mut() {
+                step.1 /= total;
+            }
+        }
+        transitions
+    }
+}
+
+fn walk_reach(transitions: &[Vec<(usize, f64)>], from: usize) -> Vec<bool> {
+    let mut reached = vec![false; transitions.len()];
+    reached[from] = true;
+    let mut queue = VecDeque::from([from]);
+    while let Some(v) = queue.pop_front() {
+        for &(w, _) in &transitions[v] {
+            if !reached[w] {
+                reached[w] = true;
+                queue.push_back(w);
+            }
+        }
+    }
+    reached
+}
+
+/// The partner that `u`, uniform in `[0, 1)`, falls on.
+fn sample(steps: &[(usize, f64)], u: f64) -> usize {
+    let mut cumulative = 0.0;
+    for &(w, p) in steps {
+        cumulative += p;
+        if u < cumulative {
+            return w;
+        }
+    }
+    steps[steps.len() - 1].0
+}
+
+/// Gaussian elimination with partial pivoting; `None` if `a` is singular.
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let m = b.len();
+    for col in 0..m {
+        let pivot = (col..m).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            if factor == 0.0 {
+                continue;
+            }
+            for (x, &p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; m];
+    for row in (0..m).rev() {
+        let tail: f64 = (row + 1..m).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    const EXCITATORY: SynapseType = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+
+    #[test]
+    fn visits_start_over_from_a_dead_end() {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        network.add_connection(0, 1, EXCITATORY, 1.0);
+        let visits = network.random_walk_visits(0, 5, 7);
+        assert_eq!(visits, HashMap::from([(0, 3), (1, 3)]));
+        assert!(network.random_walk_visits(2, 5, 7).is_empty());
+    }
+
+    #[test]
+    fn visits_cross_gap_junctions_either_way() {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C"]);
+        network.add_connection(0, 1, EXCITATORY, 1.0);
+        network.add_connection(2, 1, SynapseType::GapJunction, 1.0);
+        let visits = network.random_walk_visits(0, 4, 7);
+        assert_eq!(visits, HashMap::from([(0, 1), (1, 2), (2, 2)]));
+        assert_eq!(
+            network
+                .random_walk_visits(1, 100, 3)
+                .values()
+                .sum::<usize>(),
+            101
+        );
+    }
+
+    /// 0 - 1 - 2, sends both ways along the chain.
+    fn chain() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C"]);
+        for (from, to) in [(0, 1), (1, 0), (1, 2), (2, 1)] {
+            network.add_connection(from, to, EXCITATORY, 1.0);
+        }
+        network
+    }
+
+    #[test]
+    fn the_chain_hitting_time_is_solved_exactly() {
+        // h(B) = 1 + h(A) / 2 and h(A) = 1 + h(B), so h(A) = 4 and h(B) = 3.
+        let network = chain();
+        let Some(HittingTime::Finite(steps)) = network.exact_hitting_time(0, 2) else {
+            panic!("every walk from A arrives at C");
+        };
+        assert!((steps - 4.0).abs() < 1e-9);
+        match network.exact_hitting_time(1, 2) {
+            Some(HittingTime::Finite(steps)) => assert!((steps - 3.0).abs() < 1e-9),
+            other => panic!("{other:?}"),
+        }
+
+        let mut forked = Network::with_neuron_roster(&["A", "B", "C"]);
+        forked.add_connection(0, 1, EXCITATORY, 1.0);
+        forked.add_connection(0, 2, EXCITATORY, 1.0);
+        assert_eq!(
+            forked.exact_hitting_time(0, 2),
+            Some(HittingTime::Absorbed { probability: 0.5 })
+        );
+        assert_eq!(
+            forked.exact_hitting_time(2, 0),
+            Some(HittingTime::Unreachable)
+        );
+    }
+
+    #[test]
+    fn sampled_walks_agree_with_the_exact_solve() {
+        let network = chain();
+        let estimate = network.hitting_time(0, 2, 20_000, 1_000, 11);
+        assert!(estimate.reachable);
+        assert_eq!(estimate.arrived, estimate.walks);
+        assert_eq!(estimate.arrival_fraction(), 1.0);
+        let mean = estimate.mean_steps.unwrap();
+        assert!((mean - 4.0).abs() < 0.15, "sampled mean {mean}");
+
+        let mut forked = Network::with_neuron_roster(&["A", "B", "C"]);
+        forked.add_connection(0, 1, EXCITATORY, 1.0);
+        forked.add_connection(0, 2, EXCITATORY, 1.0);
+        let estimate = forked.hitting_time(0, 2, 20_000, 1_000, 11);
+        assert_eq!(estimate.arrived + estimate.absorbed, estimate.walks);
+        assert!((estimate.arrival_fraction() - 0.5).abs() < 0.02);
+    }
+}
@@ -0,0 +1,206 @@
+//! JavaScript bindings (feature `wasm`) for exploring a connectome in the
+//! browser: loading from CSV text, graph queries and step-wise simulation.
+//!
+//! Structured results come back as plain JS objects; every error becomes a JS
+//! `Error` carrying the `FlymindError` message. Build the library with
+//! `--no-default-features --features wasm` for `wasm32-unknown-unknown`.
+
+use std::fmt::Display;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::config::SimulationConfig;
+use crate::export::ExportFormat;
+use crate::graph::Direction;
+use crate::network::Network;
+use crate::paths::PathOptions;
+
+fn js_err(err: impl Display) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    let json = serde_json::to_string(value).map_err(js_err)?;
+    js_sys::JSON::parse(&json).map_err(|_| JsError::new("result is not valid JSON"))
+}
+
+/// A connectome and the queries that run on it.
+#[wasm_bindgen(js_name = Network)]
+pub struct WasmNetwork {
+    network: Network,
+}
+
+/// Load `NeuronConnect.csv`-formatted text.
+#[wasm_bindgen(js_name = loadNetworkFromString)]
+pub fn load_network_from_string(csv_text: &str) -> Result<WasmNetwork, JsError> {
+    let network = Network::from_connect_reader(csv_text.as_bytes()).map_err(js_err)?;
+    Ok(WasmNetwork { network })
+}
+
+#[derive(Serialize)]
+struct Neighbor<'a> {
+    partner: &'a str,
+    direction: &'static str,
+    synapse: &'static str,
+    weight: f64,
+}
+
+#[derive(Serialize)]
+struct SpikeEvent<'a> {
+    time: f64,
+    neuron: &'a str,
+}
+
+impl WasmNetwork {
+    fn id(&self, name: &str) -> Result<usize, JsError> {
+        self.network
+            .id_of(name)
+            .ok_or_else(|| JsError::new(&format!("unknown neuron {name:?}")))
+    }
+}
+
+#[wasm_bindgen(js_class = Network)]
+impl WasmNetwork {
+    #[wasm_bindgen(getter, js_name = neuronCount)]
+    pub fn neuron_count(&self) -> usize {
+        self.network.neurons.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = connectionCount)]
+    pub fn connection_count(&self) -> usize {
+        self.network.connections.len()
+    }
+
+    /// `Network::stats` as an object.
+    pub fn stats(&self) -> Result<JsValue, JsError> {
+        to_js(&self.network.stats())
+    }
+
+    /// `[{partner, direction, synapse, weight}]` for every connection of `name`,
+    /// heaviest first.
+    pub fn neighbors(&self, name: &str) -> Result<JsValue, JsError> {
+        let id = self.id(name)?;
+        let mut rows: Vec<Neighbor> = self
+            .network
+            .connections_of(id, Direction::Both)
+            .into_iter()
+            .map(|c| {
+                let conn = &self.network.connections[c];
+                let (partner, direction) = if conn.from_id == id {
+                    (conn.to_id, "out")
+                } else {
+                    (conn.from_id, "in")
+                };
+                Neighbor {
+                    partner: self.network.neurons[partner].name(),
+                    direction,
+                    synapse: conn.synapse_type.label(),
+                    weight: conn.weight,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.weight
+                .total_cmp(&a.weight)
+                .then_with(|| a.partner.cmp(b.partner))
+        });
+        to_js(&rows)
+    }
+
+    /// Names along the fewest-hop chemical path from `from` to `to`, or `null`.
+    #[wasm_bindgen(js_name = shortestPath)]
+    pub fn shortest_path(&self, from: &str, to: &str) -> Result<JsValue, JsError> {
+        let path =
+            self.network
+                .shortest_path_with(self.id(from)?, self.id(to)?, &PathOptions::default());
+        let names: Option<Vec<&str>> = path.map(|path| {
+            path.neurons
+                .iter()
+                .map(|&id| self.network.neurons[id].name())
+                .collect()
+        });
+        to_js(&names)
+    }
+
+    /// The network as Cytoscape.js `elements` JSON text.
+    #[wasm_bindgen(js_name = toCytoscapeJson)]
+    pub fn to_cytoscape_json(&self) -> Result<String, JsError> {
+        let mut out = Vec::new();
+        self.network
+            .export(ExportFormat::Cytoscape, &mut out)
+            .map_err(js_err)?;
+        String::from_utf8(out).map_err(js_err)
+    }
+
+    /// A simulation of a copy of this network, set up from a `SimulationConfig`
+    /// written as JSON.
+    #[wasm_bindgen(js_name = createSim)]
+    pub fn create_sim(&self, config_json: &str) -> Result<WasmSim, JsError> {
+        let config = SimulationConfig::from_json_str(config_json).map_err(js_err)?;
+        let all: Vec<usize> = (0..self.network.neurons.len()).collect();
+        let mut network = self.network.subnetwork(&all);
+        config.configure(&mut network).map_err(js_err)?;
+        Ok(WasmSim {
+            network,
+            dt: *config.dt.get_ref(),
+            steps: config.steps(),
+            pending: Vec::new(),
+        })
+    }
+}
+
+/// A configured run advanced a few steps at a time, e.g. once per animation frame.
+#[wasm_bindgen(js_name = Simulation)]
+pub struct WasmSim {
+    network: Network,
+    dt: f64,
+    /// Steps in the configured duration.
+    steps: usize,
+    /// `(step, neuron)` spikes not yet taken.
+    pending: Vec<(usize, usize)>,
+}
+
+#[wasm_bindgen(js_class = Simulation)]
+impl WasmSim {
+    /// Advance up to `n` steps, stopping at the configured duration. Returns the
+    /// number of steps taken.
+    pub fn step(&mut self, n: usize) -> usize {
+        let taken = n.min(self.steps.saturating_sub(self.network.current_step));
+        for _ in 0..taken {
+            self.network.update_step(self.dt);
+            let step = self.network.current_step - 1;
+            for neuron in self.network.neurons.iter().filter(|n| n.just_fired) {
+                self.pending.push((step, neuron.id));
+            }
+        }
+        taken
+    }
+
+    /// Simulated time so far, in the model's time unit.
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.network.current_step as f64 * self.dt
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.network.current_step >= self.steps
+    }
+
+    /// Spikes since the last call as `[{time, neuron}]`, in time order.
+    #[wasm_bindgen(js_name = takeSpikes)]
+    pub fn take_spikes(&mut self) -> Result<JsValue, JsError> {
+        let events: Vec<SpikeEvent> = self
+            .pending
+            .iter()
+            .map(|&(step, id)| SpikeEvent {
+                time: step as f64 * self.dt,
+                neuron: self.network.neurons[id].name(),
+            })
+            .collect();
+        let value = to_js(&events)?;
+        self.pending.clear();
+        Ok(value)
+    }
+}
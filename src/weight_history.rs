@@ -0,0 +1,322 @@
+//! Weight trajectories of chosen connections during a plastic run.
+//!
+//! `WeightRecorder` is a `StepObserver` that samples the weights of the
+//! connections it tracks every `stride` steps and after the last step. The
+//! first sample is taken when connections are tracked, at time 0. Samples are
+//! stored as one flat row per sample, so memory grows with tracked
+//! connections times samples and nothing else.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use crate::error::FlymindError;
+use crate::network::{Network, SynapseType};
+use crate::select::Selection;
+use crate::simulation::{StepInfo, StepObserver};
+
+/// Records the weights of tracked connections as a run goes; see the module
+/// documentation.
+#[derive(Debug, Clone)]
+pub struct WeightRecorder {
+    stride: usize,
+    /// Tracked connection indices, ascending.
+    connections: Vec<usize>,
+    times: Vec<f64>,
+    /// `times.len()` rows of `connections.len()` weights.
+    weights: Vec<f64>,
+}
+
+impl WeightRecorder {
+    /// Track nothing yet, sampling every `stride` steps (at least one).
+    pub fn new(stride: usize) -> Self {
+        Self {
+            stride: stride.max(1),
+            connections: Vec::new(),
+            times: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// Track connection `index`; out-of-range indices are ignored.
+    pub fn track_connection(mut self, network: &Network, index: usize) -> Self {
+        if index < network.connections.len() {
+            self.track(network, [index]);
+        }
+        self
+    }
+
+    /// Track every connection from `from` to `to`, by name, except
+    /// `ChemicalReceive` mirrors.
+    pub fn track_pair(
+        mut self,
+        network: &Network,
+        from: &str,
+        to: &str,
+    ) -> Result<Self, FlymindError> {
+        let id = |name: &str| {
+            network
+                .id_of(name)
+                .ok_or_else(|| FlymindError::UnknownNeuron {
+                    name: name.to_string(),
+                })
+        };
+        let (from, to) = (id(from)?, id(to)?);
+        self.track(network, tracked(network, |a, b| a == from && b == to));
+        Ok(self)
+    }
+
+    /// Track every connection from a neuron `from` selects to one `to`
+    /// selects, except `ChemicalReceive` mirrors, e.g. `class:AVA` to
+    /// `type:motor`.
+    pub fn track_selection(mut self, network: &Network, from: &Selection, to: &Selection) -> Self {
+        let from: Vec<bool> = (0..network.neurons.len())
+            .map(|id| from.matches(network, id))
+            .collect();
+        let to: Vec<bool> = (0..network.neurons.len())
+            .map(|id| to.matches(network, id))
+            .collect();
+        self.track(network, tracked(network, |a, b| from[a] && to[b]));
+        self
+    }
+
+    /// Add `indices`. Before the run, this retakes the time-0 sample of
+    /// `network`; once the recording has gone further, its earlier samples
+    /// are kept and read as NaN for the connections added, whose weights they
+    /// did not record.
+    fn track(&mut self, network: &Network, indices: impl IntoIterator<Item = usize>) {
+        let old = std::mem::take(&mut self.connections);
+        self.connections = old.iter().copied().chain(indices).collect();
+        self.connections.sort_unstable();
+        self.connections.dedup();
+        if self.times.len() <= 1 {
+            self.times.clear();
+            self.weights.clear();
+            self.sample(network, 0.0);
+            return;
+        }
+        let width = old.len();
+        let rows = std::mem::take(&mut self.weights);
+        for sample in 0..self.times.len() {
+            let row = &rows[sample * width..(sample + 1) * width];
+            let mut kept = old.iter().zip(row).peekable();
+            for index in &self.connections {
+                let weight = match kept.peek() {
+                    Some(&(&tracked, &weight)) if tracked == *index => {
+                        kept.next();
+                        weight
+                    }
+                    _ => f64::NAN,
+                };
+                self.weights.push(weight);
+            }
+        }
+    }
+
+    /// Sample the tracked weights; a connection `network` no longer has
+    /// reads as NaN.
+    fn sample(&mut self, network: &Network, time: f64) {
+        self.times.push(time);
+        let weight = |&index: &usize| {
+            network
+                .connections
+                .get(index)
+                .map_or(f64::NAN, |c| c.weight)
+        };
+        self.weights.extend(self.connections.iter().map(weight));
+    }
+
+    /// Tracked connection indices, ascending.
+    pub fn connections(&self) -> &[usize] {
+        &self.connections
+    }
+
+    /// Time of each sample, in the run's time unit; the first is 0.
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    /// The weights of every tracked connection at sample `sample`, in the
+    /// order of `connections`, or `None` past the last sample.
+    pub fn sample_weights(&self, sample: usize) -> Option<&[f64]> {
+        if sample >= self.times.len() {
+            return None;
+        }
+        let width = self.connections.len();
+        Some(&self.weights[sample * width..(sample + 1) * width])
+    }
+
+    /// The sampled weights of connection `index`, or `None` if it is not
+    /// tracked.
+    pub fn trajectory(&self, index: usize) -> Option<Vec<f64>> {
+        let column = self.connections.binary_search(&index).ok()?;
+        Some(
+            self.weights
+                .chunks(self.connections.len())
+                .map(|row| row[column])
+                .collect(),
+        )
+    }
+
+    /// Last sampled weight minus the first, per tracked connection.
+    pub fn final_minus_initial(&self) -> Vec<(usize, f64)> {
+        let last = self.times.len().saturating_sub(1);
+        self.columns(|column| self.at(last, column) - self.at(0, column))
+    }
+
+    /// Least-squares slope of each tracked connection's weight against time,
+    /// in weight per unit time; 0.0 with fewer than two samples.
+    pub fn drift_per_connection(&self) -> Vec<(usize, f64)> {
+        let count = self.times.len() as f64;
+        let mean_time = self.times.iter().sum::<f64>() / count;
+        let spread: f64 = self.times.iter().map(|t| (t - mean_time).powi(2)).sum();
+        self.columns(|column| {
+            if spread == 0.0 {
+                return 0.0;
+            }
+            let mean = (0..self.times.len())
+                .map(|sample| self.at(sample, column))
+                .sum::<f64>()
+                / count;
+            let covariance: f64 = self
+                .times
+                .iter()
+                .enumerate()
+                .map(|(sample, t)| (t - mean_time) * (self.at(sample, column) - mean))
+                .sum();
+            covariance / spread
+        })
+    }
+
+    fn at(&self, sample: usize, column: usize) -> f64 {
+        self.weights[sample * self.connections.len() + column]
+    }
+
+    fn columns(&self, value: impl Fn(usize) -> f64) -> Vec<(usize, f64)> {
+        if self.times.is_empty() {
+            return Vec::new();
+        }
+        self.connections
+            .iter()
+            .enumerate()
+            .map(|(column, &index)| (index, value(column)))
+            .collect()
+    }
+
+    /// Write one `time,connection,weight` row per sample and tracked
+    /// connection, ordered by time then connection index.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), FlymindError> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["time", "connection", "weight"])?;
+        for (sample, time) in self.times.iter().enumerate() {
+            for (column, index) in self.connections.iter().enumerate() {
+                wtr.write_record([
+                    time.to_string(),
+                    index.to_string(),
+                    self.at(sample, column).to_string(),
+                ])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Indices of the connections whose endpoints satisfy `matches`, without
+/// `ChemicalReceive` mirrors.
+fn tracked(network: &Network, matches: impl Fn(usize, usize) -> bool) -> Vec<usize> {
+    network
+        .connections
+        .iter()
+        .enumerate()
+        .filter(|(_, conn)| !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)))
+        .filter(|(_, conn)| matches(conn.from_id, conn.to_id))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+impl StepObserver for WeightRecorder {
+    fn observe(&mut self, info: &StepInfo, network: &Network) -> ControlFlow<()> {
+        let last = info.step + 1 == info.steps;
+        if (info.step + 1).is_multiple_of(self.stride) || last {
+            self.sample(network, info.time());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+    use crate::simulation::{StdpParams, Stimulus};
+
+    /// `A -> B` and `B -> A`, both plastic, with A made to fire 5 steps
+    /// before B every 200 steps: `A -> B` is potentiated, `B -> A` depressed.
+    fn pairing_protocol() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B"]);
+        let send = SynapseType::ChemicalSend(ChemicalSubtype::Excitatory);
+        network.add_connection(0, 1, send, 1.0);
+        network.add_connection(1, 0, send, 1.0);
+        for conn in &mut network.connections {
+            conn.plastic = true;
+        }
+        network.stdp = Some(StdpParams::default());
+        for pairing in 0..10 {
+            let start = 50 + pairing * 200;
+            network.stimuli.push(Stimulus::new(0, start, 1, 20.0));
+            network.stimuli.push(Stimulus::new(1, start + 5, 1, 20.0));
+        }
+        network
+    }
+
+    #[test]
+    fn pairing_potentiates_the_forward_synapse() {
+        let mut network = pairing_protocol();
+        let mut recorder = WeightRecorder::new(200)
+            .track_pair(&network, "A", "B")
+            .unwrap()
+            .track_connection(&network, 1);
+        let result = network.run_simulation_observed(2000, 0.1, &[], 1, &mut recorder);
+        assert_eq!(result.spike_trains[0].len(), 10);
+        assert_eq!(result.spike_trains[1].len(), 10);
+
+        let forward = recorder.trajectory(0).unwrap();
+        assert_eq!(forward.len(), 11);
+        assert!(forward.windows(2).all(|w| w[1] > w[0]), "{forward:?}");
+        assert_eq!(*forward.last().unwrap(), network.connections[0].weight);
+        let backward = recorder.trajectory(1).unwrap();
+        assert!(backward.last().unwrap() < &1.0);
+        assert_eq!(*backward.last().unwrap(), network.connections[1].weight);
+
+        let change = recorder.final_minus_initial();
+        assert_eq!(change[0], (0, network.connections[0].weight - 1.0));
+        assert!(recorder.drift_per_connection()[0].1 > 0.0);
+    }
+
+    #[test]
+    fn tracking_more_keeps_the_recording() {
+        let mut network = pairing_protocol();
+        let mut recorder = WeightRecorder::new(500).track_connection(&network, 0);
+        network.run_simulation_observed(1000, 0.1, &[], 1, &mut recorder);
+        let before = recorder.trajectory(0).unwrap();
+        assert_eq!(before.len(), 3);
+
+        let mut recorder = recorder.track_connection(&network, 1);
+        network.run_simulation_observed(1000, 0.1, &[], 1, &mut recorder);
+        let forward = recorder.trajectory(0).unwrap();
+        assert_eq!(forward[..3], before[..]);
+        assert_eq!(forward.len(), 5);
+        let backward = recorder.trajectory(1).unwrap();
+        assert!(backward[..3].iter().all(|w| w.is_nan()));
+        assert_eq!(backward[4], network.connections[1].weight);
+    }
+
+    #[test]
+    fn samples_past_the_last_are_none() {
+        let network = pairing_protocol();
+        let recorder = WeightRecorder::new(1).track_connection(&network, 1);
+        assert_eq!(recorder.sample_weights(0), Some(&[1.0][..]));
+        assert_eq!(recorder.sample_weights(1), None);
+        assert!(WeightRecorder::new(1).sample_weights(0).is_none());
+    }
+}
@@ -0,0 +1,312 @@
+//! Reweighting connections in bulk: binarizing, log-scaling, normalizing.
+//!
+//! `ChemicalReceive` rows count as chemical. They are left out of every sum,
+//! as elsewhere, so no synapse is counted twice, and every built-in transform
+//! gives them the weight their send gets, so mirrors stay equal to what they
+//! mirror. `WeightTransform::Custom` makes no such promise.
+
+use crate::error::FlymindError;
+use crate::network::{Connection, Network, SynapseType};
+use crate::paths::SynapseClass;
+
+pub(crate) const ALL_CLASSES: [SynapseClass; 4] = [
+    SynapseClass::Chemical,
+    SynapseClass::GapJunction,
+    SynapseClass::Nmj,
+    SynapseClass::Extrasynaptic,
+];
+
+/// `ALL_CLASSES` but the extrasynaptic layer.
+pub(crate) const WIRED_CLASSES: [SynapseClass; 3] = [
+    SynapseClass::Chemical,
+    SynapseClass::GapJunction,
+    SynapseClass::Nmj,
+];
+
+/// A new weight for every transformed connection.
+pub enum WeightTransform {
+    /// 1 for weights of at least `threshold`, else 0; applying it twice changes
+    /// nothing for thresholds in `(0, 1]`.
+    Binarize {
+        threshold: f64,
+    },
+    /// `ln(1 + w)`, e.g. to compress contact counts.
+    Log1p,
+    /// Each neuron's outgoing weights sum to 1. Neurons whose outgoing weights
+    /// sum to zero keep them. Applied to gap junctions, each direction of a
+    /// junction is normalized by its own neuron, so the two can differ.
+    NormalizeBySource,
+    /// All weights are scaled to sum to `target_sum`; fails if they sum to
+    /// zero.
+    NormalizeGlobal {
+        target_sum: f64,
+    },
+    Scale(f64),
+    /// The closure's value for each connection, called once per row, receive
+    /// mirrors included. A closure that looks at more than the weight, such
+    /// as the endpoints or the synapse type, can give a mirror a different
+    /// weight from its send.
+    Custom(Box<dyn Fn(&Connection) -> f64>),
+}
+
+impl Network {
+    /// Apply `transform` to every connection. Fails, leaving every weight as it
+    /// was, if any new weight would be negative, NaN or infinite.
+    pub fn transform_weights(&mut self, transform: WeightTransform) -> Result<(), FlymindError> {
+        self.transform_weights_of(&ALL_CLASSES, transform)
+    }
+
+    /// Like `transform_weights`, only for connections in `classes`; sums are
+    /// over those connections too.
+    pub fn transform_weights_of(
+        &mut self,
+        classes: &[SynapseClass],
+        transform: WeightTransform,
+    ) -> Result<(), FlymindError> {
+        let selected: Vec<usize> = (0..self.connections.len())
+            .filter(|&index| classes.contains(&weight_class(&self.connections[index])))
+            .collect();
+        let counted = |index: &&usize| {
+            !matches!(
+                self.connections[**index].synapse_type,
+                SynapseType::ChemicalReceive(_)
+            )
+        };
+        let weights: Vec<f64> = match &transform {
+            WeightTransform::Binarize { threshold } => selected
+                .iter()
+                .map(|&index| f64::from(u8::from(self.connections[index].weight >= *threshold)))
+                .collect(),
+            WeightTransform::Log1p => selected
+                .iter()
+                .map(|&index| self.connections[index].weight.ln_1p())
+                .collect(),
+            WeightTransform::NormalizeBySource => {
+                let mut totals = vec![0.0; self.neurons.len()];
+                for &index in selected.iter().filter(counted) {
+                    let conn = &self.connections[index];
+                    totals[presynaptic(conn)] += conn.weight;
+                }
+                selected
+                    .iter()
+                    .map(|&index| {
+                        let conn = &self.connections[index];
+                        let total = totals[presynaptic(conn)];
+                        if total == 0.0 {
+                            conn.weight
+                        } else {
+                            conn.weight / total
+                        }
+                    })
+                    .collect()
+            }
+            WeightTransform::NormalizeGlobal { target_sum } => {
+                let total: f64 = selected
+                    .iter()
+                    .filter(counted)
+                    .map(|&index| self.connections[index].weight)
+                    .sum();
+                selected
+                    .iter()
+                    .map(|&index| self.connections[index].weight * target_sum / total)
+                    .collect()
+            }
+            WeightTransform::Scale(factor) => selected
+                .iter()
+                .map(|&index| self.connections[index].weight * factor)
+                .collect(),
+            WeightTransform::Custom(f) => selected
+                .iter()
+                .map(|&index| f(&self.connections[index]))
+                .collect(),
+        };
+        if let Some((&index, &weight)) = selected
+            .iter()
+            .zip(&weights)
+            .find(|(_, &w)| !(w.is_finite() && w >= 0.0))
+        {
+            return Err(FlymindError::InvalidWeight {
+                connection: index,
+                weight,
+            });
+        }
+        let before = if self.edit_log.is_some() {
+            selected
+                .iter()
+                .map(|&index| self.connections[index].weight)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        for (&index, &weight) in selected.iter().zip(&weights) {
+            self.connections[index].weight = weight;
+        }
+        let description = format!("transform {} weights", selected.len());
+        self.record_weights(description, selected, before);
+        Ok(())
+    }
+
+    /// Clear out the near-zero weights quantizing or thresholding can leave,
+    /// which cost the simulation loop without moving any potential: remove
+    /// every connection lighter than `floor`, as `prune` does, or with
+    /// `clamp` raise its weight to `floor` instead, keeping the connection.
+    /// `ChemicalReceive` mirrors share their send's weight, so the two go
+    /// together. Returns how many connections were removed or raised.
+    pub fn apply_weight_floor(&mut self, floor: f64, clamp: bool) -> usize {
+        if !clamp {
+            return self.prune(floor);
+        }
+        let raised: Vec<usize> = (0..self.connections.len())
+            .filter(|&index| self.connections[index].weight < floor)
+            .collect();
+        let before = raised
+            .iter()
+            .map(|&index| self.connections[index].weight)
+            .collect();
+        for &index in &raised {
+            self.connections[index].weight = floor;
+        }
+        let count = raised.len();
+        if count > 0 {
+            self.record_weights(format!("raise {count} weights to {floor}"), raised, before);
+        }
+        count
+    }
+
+    /// Multiply every gap-junction weight by `factor`, leaving other
+    /// connections alone, e.g. to sweep the electrical-to-chemical ratio.
+    /// Fails, changing nothing, for a negative or non-finite `factor`.
+    pub fn scale_gap_junctions(&mut self, factor: f64) -> Result<(), FlymindError> {
+        self.transform_weights_of(&[SynapseClass::GapJunction], WeightTransform::Scale(factor))
+    }
+
+    /// Multiply every chemical weight, receive mirrors included, by `factor`,
+    /// as `scale_gap_junctions` does for gap junctions.
+    pub fn scale_chemical(&mut self, factor: f64) -> Result<(), FlymindError> {
+        self.transform_weights_of(&[SynapseClass::Chemical], WeightTransform::Scale(factor))
+    }
+
+    /// A copy with `transform` applied, this network untouched. The copy is
+    /// taken as `subnetwork` takes one of every neuron, so simulation settings
+    /// are not carried over.
+    pub fn with_transformed_weights(
+        &self,
+        transform: WeightTransform,
+    ) -> Result<Network, FlymindError> {
+        let ids: Vec<usize> = (0..self.neurons.len()).collect();
+        let mut copy = self.subnetwork(&ids);
+        copy.transform_weights(transform)?;
+        Ok(copy)
+    }
+}
+
+fn weight_class(conn: &Connection) -> SynapseClass {
+    match conn.synapse_type {
+        SynapseType::ChemicalSend(_) | SynapseType::ChemicalReceive(_) => SynapseClass::Chemical,
+        SynapseType::GapJunction => SynapseClass::GapJunction,
+        SynapseType::NMJ => SynapseClass::Nmj,
+        SynapseType::Extrasynaptic(_) => SynapseClass::Extrasynaptic,
+    }
+}
+
+/// The neuron a connection leaves; a receive row runs postsynaptic to
+/// presynaptic.
+fn presynaptic(conn: &Connection) -> usize {
+    match conn.synapse_type {
+        SynapseType::ChemicalReceive(_) => conn.to_id,
+        _ => conn.from_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ChemicalSubtype;
+
+    fn network() -> Network {
+        let mut network = Network::with_neuron_roster(&["A", "B", "C"]);
+        network.add_connection(
+            0,
+            1,
+            SynapseType::ChemicalSend(ChemicalSubtype::Excitatory),
+            0.05,
+        );
+        network.add_connection(1, 2, SynapseType::GapJunction, 2.0);
+        network
+    }
+
+    #[test]
+    fn the_floor_removes_light_connections() {
+        let mut network = network();
+        assert_eq!(network.apply_weight_floor(0.1, false), 1);
+        assert_eq!(network.connections.len(), 1);
+        assert_eq!(network.connections[0].weight, 2.0);
+    }
+
+    #[test]
+    fn a_clamped_floor_raises_weights_and_can_be_undone() {
+        let mut network = network();
+        network.start_edit_log();
+        assert_eq!(network.apply_weight_floor(0.1, true), 1);
+        let weights: Vec<f64> = network.connections.iter().map(|c| c.weight).collect();
+        assert_eq!(weights, vec![0.1, 2.0]);
+        assert_eq!(network.edit_log().unwrap().len(), 1);
+
+        network.undo();
+        assert_eq!(network.connections[0].weight, 0.05);
+        assert_eq!(network.apply_weight_floor(0.01, true), 0);
+    }
+
+    fn weights(network: &Network) -> Vec<f64> {
+        network.connections.iter().map(|c| c.weight).collect()
+    }
+
+    #[test]
+    fn normalizing_by_source_makes_each_row_sum_to_one() {
+        let mut network = Network::example_touch_circuit();
+        network
+            .transform_weights(WeightTransform::NormalizeBySource)
+            .unwrap();
+        let mut rows = vec![0.0; network.neurons.len()];
+        for conn in &network.connections {
+            if !matches!(conn.synapse_type, SynapseType::ChemicalReceive(_)) {
+                rows[conn.from_id] += conn.weight;
+            }
+        }
+        for (neuron, row) in network.neurons.iter().zip(rows) {
+            assert!(
+                row == 0.0 || (row - 1.0).abs() < 1e-12,
+                "{}: {row}",
+                neuron.name()
+            );
+        }
+        assert!(network.neurons.iter().any(|n| network.out_degree(n.id) > 1));
+    }
+
+    #[test]
+    fn binarizing_twice_is_binarizing_once() {
+        // Scaled down so some weights fall below each threshold.
+        let scaled = || {
+            let mut network = Network::example_touch_circuit();
+            network
+                .transform_weights(WeightTransform::Scale(0.3))
+                .unwrap();
+            network
+        };
+        for threshold in [0.5, 1.0] {
+            let mut once = scaled();
+            once.transform_weights(WeightTransform::Binarize { threshold })
+                .unwrap();
+            let binary = weights(&once);
+            assert!(binary.contains(&0.0) && binary.contains(&1.0));
+            assert!(binary.iter().all(|&w| w == 0.0 || w == 1.0));
+            let mut twice = scaled();
+            for _ in 0..2 {
+                twice
+                    .transform_weights(WeightTransform::Binarize { threshold })
+                    .unwrap();
+            }
+            assert_eq!(weights(&twice), binary, "threshold {threshold}");
+        }
+    }
+}
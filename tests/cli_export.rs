@@ -0,0 +1,124 @@
+//! `flymind export` on the fixture, once per format: each written file is
+//! parsed back and its neurons and edges counted.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::flymind;
+
+const NEURONS: usize = 17;
+const EDGES: usize = 20;
+
+/// Export the fixture as `format` and read back what was written.
+fn export(format: &str) -> String {
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("fixture.{format}"));
+    flymind("export", &["--format", format, "--out"])
+        .arg(&out)
+        .assert()
+        .success();
+    fs::read_to_string(&out).unwrap()
+}
+
+fn count(text: &str, pattern: &str) -> usize {
+    text.matches(pattern).count()
+}
+
+#[test]
+fn gml_holds_every_neuron_and_edge() {
+    let text = export("gml");
+    assert_eq!(count(&text, "node ["), NEURONS);
+    assert_eq!(count(&text, "edge ["), EDGES);
+}
+
+#[test]
+fn dot_holds_every_neuron_and_edge() {
+    let text = export("dot");
+    let (edges, nodes): (Vec<&str>, Vec<&str>) = text
+        .lines()
+        .filter(|line| line.trim_start().starts_with('"'))
+        .partition(|line| line.contains(" -> "));
+    assert_eq!(nodes.len(), NEURONS);
+    assert_eq!(edges.len(), EDGES);
+}
+
+#[test]
+fn graphml_holds_every_neuron_and_edge() {
+    let text = export("graphml");
+    assert_eq!(count(&text, "<node "), NEURONS);
+    assert_eq!(count(&text, "<edge "), EDGES);
+}
+
+#[test]
+fn gexf_holds_every_neuron_and_edge() {
+    let text = export("gexf");
+    assert_eq!(count(&text, "<node "), NEURONS);
+    assert_eq!(count(&text, "<edge "), EDGES);
+}
+
+#[test]
+fn json_holds_every_neuron_and_edge() {
+    let graph: serde_json::Value = serde_json::from_str(&export("json")).unwrap();
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), NEURONS);
+    assert_eq!(graph["links"].as_array().unwrap().len(), EDGES);
+}
+
+#[test]
+fn edge_csv_holds_every_neuron_and_edge() {
+    let text = export("edge-csv");
+    let rows: Vec<Vec<&str>> = text
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').collect())
+        .collect();
+    let mut names: Vec<&str> = rows.iter().flat_map(|row| [row[0], row[1]]).collect();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), NEURONS);
+    assert_eq!(rows.len(), EDGES);
+}
+
+#[test]
+fn adjacency_holds_every_neuron_and_edge() {
+    let text = export("adjacency");
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').skip(1).collect();
+    assert_eq!(header.len(), NEURONS);
+    let rows: Vec<Vec<f64>> = lines
+        .map(|line| {
+            line.split(',')
+                .skip(1)
+                .map(|cell| cell.parse().unwrap())
+                .collect()
+        })
+        .collect();
+    assert_eq!(rows.len(), NEURONS);
+    // No pair of the fixture has parallel connections, so each edge is one cell.
+    let nonzero = rows.iter().flatten().filter(|&&w| w != 0.0).count();
+    assert_eq!(nonzero, EDGES);
+}
+
+#[test]
+fn mtx_holds_every_neuron_and_edge() {
+    let text = export("mtx");
+    let mut lines = text.lines().filter(|line| !line.starts_with('%'));
+    let size: Vec<usize> = lines
+        .next()
+        .unwrap()
+        .split_whitespace()
+        .map(|n| n.parse().unwrap())
+        .collect();
+    assert_eq!(size, vec![NEURONS, NEURONS, EDGES]);
+    assert_eq!(lines.count(), EDGES);
+}
+
+#[test]
+fn cytoscape_holds_every_neuron_and_edge() {
+    let graph: serde_json::Value = serde_json::from_str(&export("cytoscape")).unwrap();
+    assert_eq!(
+        graph["elements"]["nodes"].as_array().unwrap().len(),
+        NEURONS
+    );
+    assert_eq!(graph["elements"]["edges"].as_array().unwrap().len(), EDGES);
+}
@@ -0,0 +1,63 @@
+//! `flymind path` on the fixture.
+
+mod common;
+
+use common::{flymind, stdout};
+
+/// `flymind path`'s exit code when the target cannot be reached.
+const UNREACHABLE: i32 = 3;
+
+#[test]
+fn the_two_hop_path_runs_through_avdl() {
+    let text = stdout(&mut flymind("path", &["--from", "ALML", "--to", "AVAL"]));
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("ALML -S-> AVDL -S-> AVAL"));
+    let hops: Vec<Vec<&str>> = lines
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        hops,
+        [
+            ["ALML", "->", "AVDL", "chemical_send", "3"],
+            ["AVDL", "->", "AVAL", "chemical_send", "5"],
+        ]
+    );
+}
+
+#[test]
+fn the_two_hop_path_as_json() {
+    let text = stdout(&mut flymind(
+        "path",
+        &["--from", "ALML", "--to", "AVAL", "--json"],
+    ));
+    let paths: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(paths.as_array().unwrap().len(), 1);
+    assert_eq!(
+        paths[0]["neurons"],
+        serde_json::json!(["ALML", "AVDL", "AVAL"])
+    );
+    assert_eq!(paths[0]["hops"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn an_unreachable_pair_has_its_own_exit_code() {
+    for json in [false, true] {
+        let mut command = flymind("path", &["--from", "VA08", "--to", "ALML"]);
+        if json {
+            command.arg("--json");
+        }
+        let output = command.assert().code(UNREACHABLE).get_output().clone();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr, "no path from VA08 to ALML\n");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout, if json { "[]\n" } else { "" });
+    }
+}
+
+#[test]
+fn an_unknown_neuron_is_an_error_not_unreachable() {
+    flymind("path", &["--from", "VA08", "--to", "NOPE"])
+        .assert()
+        .failure()
+        .code(1);
+}
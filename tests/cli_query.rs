@@ -0,0 +1,70 @@
+//! `flymind query` on the fixture.
+
+mod common;
+
+use common::{flymind, stdout};
+
+/// `(partner, synapse, weight)` of each table row, in printed order.
+fn rows(text: &str) -> Vec<(String, String, String)> {
+    text.lines()
+        .skip(1)
+        .filter(|line| !line.ends_with("connections"))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (
+                fields[0].to_string(),
+                fields[2].to_string(),
+                fields[fields.len() - 1].to_string(),
+            )
+        })
+        .collect()
+}
+
+fn row(partner: &str, synapse: &str, weight: &str) -> (String, String, String) {
+    (partner.to_string(), synapse.to_string(), weight.to_string())
+}
+
+#[test]
+fn aval_lists_its_out_neighbours_heaviest_first() {
+    let text = stdout(&mut flymind("query", &["--neuron", "AVAL"]));
+    assert_eq!(
+        rows(&text),
+        [
+            row("VA08", "chemical_send", "7"),
+            row("DA05", "chemical_send", "3"),
+            row("AVAR", "gap_junction", "2"),
+        ]
+    );
+    assert!(text.ends_with("3 connections\n"), "{text}");
+}
+
+#[test]
+fn aval_json_names_the_same_partners() {
+    let text = stdout(&mut flymind("query", &["--neuron", "AVAL", "--json"]));
+    let rows: serde_json::Value = serde_json::from_str(&text).unwrap();
+    let partners: Vec<&str> = rows
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["partner"].as_str().unwrap())
+        .collect();
+    assert_eq!(partners, ["VA08", "DA05", "AVAR"]);
+    assert!(rows
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|row| row["from"] == "AVAL"));
+}
+
+#[test]
+fn an_unknown_neuron_suggests_near_names() {
+    let output = flymind("query", &["--neuron", "AVLA"])
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .stderr
+        .clone();
+    let stderr = String::from_utf8(output).unwrap();
+    assert!(stderr.contains("did you mean AVAL"), "{stderr}");
+}
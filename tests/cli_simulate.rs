@@ -0,0 +1,129 @@
+//! `flymind simulate` on the fixture, from a TOML config to its recordings.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assert_cmd::Command;
+
+use common::fixture;
+
+/// ALML driven hard for 150 ms, its voltage and AVDL's sampled every 1 ms.
+const CONFIG: &str = r#"duration = 200.0
+dt = 0.5
+seed = 1
+
+[[stimulus]]
+neuron = "ALML"
+start = 10.0
+duration = 150.0
+amplitude = 40.0
+
+[recorders.voltages]
+neurons = ["ALML", "AVDL"]
+stride = 2
+"#;
+
+/// A fresh directory under the target dir, holding `config` as config.toml.
+fn workspace(name: &str, config: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), config).unwrap();
+    dir
+}
+
+fn simulate(dir: &Path) -> Command {
+    let mut command = Command::cargo_bin("flymind").expect("the flymind binary is built");
+    command
+        .arg("simulate")
+        .arg("--network")
+        .arg(fixture())
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .arg("--out")
+        .arg(dir.join("out"));
+    command
+}
+
+#[test]
+fn a_config_run_writes_its_recordings() {
+    let dir = workspace("simulate-run", CONFIG);
+    simulate(&dir).assert().success();
+    let out = dir.join("out");
+
+    let summary: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(out.join("summary.json")).unwrap()).unwrap();
+    assert_eq!(summary["steps"], 400);
+    assert_eq!(summary["interrupted"], false);
+    assert_eq!(summary["spike_counts"]["ALML"], 100);
+    assert_eq!(summary["spike_counts"]["AVDL"], 20);
+    assert_eq!(summary["spike_counts"]["VA08"], 0);
+    assert_eq!(summary["total_spikes"], 120);
+
+    let spikes = fs::read_to_string(out.join("spikes.csv")).unwrap();
+    let mut lines = spikes.lines();
+    assert_eq!(lines.next(), Some("time,neuron"));
+    assert_eq!(lines.count(), 120);
+
+    let voltages = fs::read_to_string(out.join("voltages.csv")).unwrap();
+    let mut lines = voltages.lines();
+    assert_eq!(lines.next(), Some("time,ALML,AVDL"));
+    assert_eq!(lines.count(), 200);
+
+    // The config written alongside parses back with the same settings.
+    let written = fs::read_to_string(out.join("config.toml")).unwrap();
+    let written: toml::Table = toml::from_str(&written).unwrap();
+    assert_eq!(written["duration"].as_float(), Some(200.0));
+    assert_eq!(written["stimulus"][0]["neuron"].as_str(), Some("ALML"));
+}
+
+/// What `simulate` printed to stderr on failing with `config`, having written
+/// nothing.
+fn config_error(name: &str, config: &str) -> String {
+    let dir = workspace(name, config);
+    let output = simulate(&dir)
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .clone();
+    assert!(!dir.join("out").exists());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn a_misspelled_key_is_reported_with_its_line() {
+    let config = CONFIG.replace("duration = 150.0", "duraton = 150.0");
+    let stderr = config_error("simulate-misspelled", &config);
+    assert!(
+        stderr
+            .starts_with("error: config key `stimulus.duraton` (line 8): unknown field `duraton`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn a_mistyped_value_is_reported_with_its_line() {
+    let config = CONFIG.replace("dt = 0.5", "dt = \"fast\"");
+    let stderr = config_error("simulate-mistyped", &config);
+    assert!(
+        stderr.starts_with("error: config key `dt` (line 2): invalid type: string \"fast\""),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn an_unknown_neuron_is_reported_with_its_line() {
+    let config = CONFIG.replace("\"ALML\"\n", "\"ALMX\"\n");
+    let stderr = config_error("simulate-unknown-neuron", &config);
+    assert!(
+        stderr.starts_with(
+            "error: config key `stimulus[0].neuron` (line 6): unknown neuron \"ALMX\""
+        ),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,44 @@
+//! `flymind stats` on the fixture.
+
+mod common;
+
+use common::{flymind, stdout};
+
+#[test]
+fn stats_prints_the_fixture_counts() {
+    let text = stdout(&mut flymind("stats", &[]));
+    let row = |label: &str| {
+        text.lines()
+            .find(|line| line.trim_start().starts_with(label))
+            .and_then(|line| line.split_whitespace().last())
+            .unwrap_or_else(|| panic!("no {label:?} row in\n{text}"))
+            .to_string()
+    };
+    assert_eq!(row("neurons "), "17");
+    assert_eq!(row("connections "), "20");
+    assert_eq!(row("chemical_send"), "14");
+    assert_eq!(row("gap_junction"), "6");
+    assert!(text.contains("components              2 [9, 8]"));
+}
+
+#[test]
+fn stats_json_parses_back() {
+    let text = stdout(&mut flymind("stats", &["--json"]));
+    let stats: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(stats["neuron_count"], 17);
+    assert_eq!(stats["connection_count"], 20);
+}
+
+#[test]
+fn a_missing_file_fails() {
+    let mut command = assert_cmd::Command::cargo_bin("flymind").unwrap();
+    let output = command
+        .args(["stats", "no/such/file.csv"])
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(output).unwrap().starts_with("error: "));
+}
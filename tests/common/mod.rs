@@ -0,0 +1,27 @@
+//! Shared by the CLI tests: the `flymind` binary and the fixture it reads.
+
+// Each test crate uses only some of these.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use assert_cmd::Command;
+
+/// `tests/fixtures/touch_circuit.csv`: 17 neurons of the touch circuit, 14
+/// chemical sends and 3 gap junctions listed both ways.
+pub fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/touch_circuit.csv")
+}
+
+/// `flymind <subcommand> <fixture> <args>`.
+pub fn flymind(subcommand: &str, args: &[&str]) -> Command {
+    let mut command = Command::cargo_bin("flymind").expect("the flymind binary is built");
+    command.arg(subcommand).arg(fixture()).args(args);
+    command
+}
+
+/// What `command` printed to stdout, once it has exited successfully.
+pub fn stdout(command: &mut Command) -> String {
+    let output = command.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).expect("output is UTF-8")
+}
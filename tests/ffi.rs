@@ -0,0 +1,93 @@
+//! The C ABI seen from C: `include/flymind.h` must declare exactly the
+//! functions `src/ffi.rs` exports, and `tests/ffi_smoke.c` must build against
+//! it and the cdylib and pass.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Every `flymind_*` identifier followed by `(` on the lines `keep` accepts.
+fn function_names(text: &str, keep: impl Fn(&str) -> bool) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for line in text.lines().filter(|line| keep(line.trim_start())) {
+        let mut rest = line;
+        while let Some(at) = rest.find("flymind_") {
+            let tail = &rest[at..];
+            let end = tail
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(tail.len());
+            if tail[end..].starts_with('(') {
+                names.insert(tail[..end].to_string());
+            }
+            rest = &tail[end..];
+        }
+    }
+    names
+}
+
+#[test]
+fn header_declares_every_exported_function() {
+    let source = fs::read_to_string(root().join("src/ffi.rs")).unwrap();
+    let header = fs::read_to_string(root().join("include/flymind.h")).unwrap();
+    let exported = function_names(&source, |line| line.contains("extern \"C\" fn"));
+    let declared = function_names(&header, |line| {
+        !line.starts_with("//") && !line.starts_with("/*") && !line.starts_with('*')
+    });
+    assert!(!exported.is_empty());
+    assert_eq!(
+        exported, declared,
+        "include/flymind.h is stale; regenerate it as cbindgen.toml describes"
+    );
+}
+
+/// The directory holding this test binary and the crate's cdylib.
+fn artifact_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn c_smoke_test_passes() {
+    let libs = artifact_dir();
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi_smoke");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let built = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(root().join("include"))
+        .arg(root().join("tests/ffi_smoke.c"))
+        .arg("-L")
+        .arg(&libs)
+        .arg("-lcelegans_connectome")
+        .arg(format!("-Wl,-rpath,{}", libs.display()))
+        .arg("-o")
+        .arg(&out)
+        .status();
+    match built {
+        Ok(status) => assert!(status.success(), "{compiler} failed on tests/ffi_smoke.c"),
+        Err(err) => {
+            eprintln!("skipping the C smoke test: cannot run {compiler}: {err}");
+            return;
+        }
+    }
+    // Cargo's library path may list an uplifted copy of the cdylib built
+    // without `ffi`; the rpath points at the one this test links.
+    let run = Command::new(&out)
+        .env_remove("LD_LIBRARY_PATH")
+        .output()
+        .unwrap();
+    assert!(
+        run.status.success(),
+        "ffi_smoke failed:\n{}{}",
+        String::from_utf8_lossy(&run.stdout),
+        String::from_utf8_lossy(&run.stderr)
+    );
+}
@@ -0,0 +1,78 @@
+/* Smoke test of include/flymind.h against the cdylib, built and run by
+ * tests/ffi.rs. Exits nonzero naming the first check that failed. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "flymind.h"
+
+#define CHECK(cond)                                                            \
+    do {                                                                       \
+        if (!(cond)) {                                                         \
+            const char *error = flymind_last_error_message();                  \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n",      \
+                    __FILE__, __LINE__, #cond, error ? error : "none");        \
+            return 1;                                                          \
+        }                                                                      \
+    } while (0)
+
+static const char EDGES[] = "Neuron 1,Neuron 2,Type,Nbr\n"
+                            "ASHL,AVAL,Sp,20\n"
+                            "AVAL,ASHL,R,20\n"
+                            "AVAL,VA01,S,10\n"
+                            "VA01,AVAL,R,10\n";
+
+int main(void) {
+    flymind_network_t *network =
+        flymind_network_load_buffer((const uint8_t *)EDGES, strlen(EDGES));
+    CHECK(network != NULL);
+    CHECK(flymind_network_neuron_count(network) == 3);
+    CHECK(flymind_network_connection_count(network) == 4);
+
+    ptrdiff_t ashl = flymind_network_neuron_id(network, "ASHL");
+    CHECK(ashl >= 0);
+    CHECK(flymind_network_neuron_id(network, "NOPE") == -1);
+    CHECK(flymind_last_error_message() != NULL);
+
+    ptrdiff_t len = flymind_network_neuron_name(network, (size_t)ashl, NULL, 0);
+    CHECK(len == 4);
+    char name[8];
+    CHECK(flymind_network_neuron_name(network, (size_t)ashl, name, sizeof name) == len);
+    CHECK(strcmp(name, "ASHL") == 0);
+
+    CHECK(flymind_network_load_path("does/not/exist.csv") == NULL);
+    CHECK(flymind_sim_new(network, 0.0) == NULL);
+
+    flymind_sim_t *sim = flymind_sim_new(network, 0.5);
+    CHECK(sim != NULL);
+    double currents[3] = {0.0, 0.0, 0.0};
+    currents[ashl] = 3.0;
+    CHECK(flymind_sim_run(sim, 400, currents, 2) == -1);
+    CHECK(flymind_sim_run(sim, 400, currents, 3) == 0);
+    CHECK(flymind_sim_time(sim) == 200.0);
+
+    ptrdiff_t pending = flymind_sim_take_spikes(sim, NULL, NULL, 0);
+    CHECK(pending > 0);
+    double *times = malloc((size_t)pending * sizeof *times);
+    size_t *neurons = malloc((size_t)pending * sizeof *neurons);
+    CHECK(times != NULL && neurons != NULL);
+    CHECK(flymind_sim_take_spikes(sim, times, neurons, (size_t)pending) == pending);
+    int stimulated_fired = 0;
+    for (ptrdiff_t k = 0; k < pending; k++) {
+        CHECK(k == 0 || times[k - 1] <= times[k]);
+        CHECK(times[k] < 200.0);
+        stimulated_fired |= neurons[k] == (size_t)ashl;
+    }
+    CHECK(stimulated_fired);
+    CHECK(flymind_sim_take_spikes(sim, NULL, NULL, 0) == 0);
+    free(times);
+    free(neurons);
+
+    flymind_sim_free(sim);
+    flymind_network_free(network);
+    flymind_sim_free(NULL);
+    flymind_network_free(NULL);
+    printf("ffi smoke test passed\n");
+    return 0;
+}
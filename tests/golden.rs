@@ -0,0 +1,121 @@
+//! Golden results for the bundled touch circuit. Every quantity pinned in
+//! `tests/golden/touch_circuit.txt` is recomputed here; a change to the
+//! loader, the graph algorithms or the simulator that moves one fails with
+//! the quantity's key, the pinned value and the new one.
+//!
+//! After an intended change, rewrite the file with
+//! `FLYMIND_BLESS=1 cargo test --test golden` and review its diff.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use celegans_connectome::{Network, Stimulus};
+
+const SEED: u64 = 1;
+const STEPS: usize = 1000;
+const DT: f64 = 0.1;
+
+fn golden_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/touch_circuit.txt")
+}
+
+/// Each pinned quantity as `key -> value`, both as written in the golden file.
+fn quantities() -> Vec<(String, String)> {
+    let mut network = Network::example_touch_circuit();
+    let name = |id: usize| network.neurons[id].name().to_string();
+    let mut out = vec![
+        ("neurons".to_string(), network.neurons.len().to_string()),
+        (
+            "connections".to_string(),
+            network.connections.len().to_string(),
+        ),
+    ];
+    for neuron in &network.neurons {
+        out.push((
+            format!("degree {}", neuron.name()),
+            format!(
+                "out {} in {}",
+                network.out_degree(neuron.id),
+                network.in_degree(neuron.id)
+            ),
+        ));
+    }
+    let ashl = network.id_of("ASHL").unwrap();
+    let aval = network.id_of("AVAL").unwrap();
+    let path = network
+        .shortest_path(ashl, aval)
+        .map_or("none".to_string(), |path| {
+            path.into_iter().map(name).collect::<Vec<_>>().join(" ")
+        });
+    out.push(("shortest_path ASHL AVAL".to_string(), path));
+    out.push(("reciprocity".to_string(), network.reciprocity().to_string()));
+    for (k, component) in network.strongly_connected_components().iter().enumerate() {
+        let members: Vec<_> = component.iter().map(|&id| name(id)).collect();
+        out.push((format!("scc {k}"), members.join(" ")));
+    }
+
+    network.lif.noise_std = 0.5;
+    network.seed(SEED);
+    for touch in ["ALML", "ALMR", "AVM"] {
+        let id = network.id_of(touch).unwrap();
+        network.stimuli.push(Stimulus::new(id, 100, 500, 0.15));
+    }
+    let result = network.run_simulation(STEPS, DT);
+    out.push((
+        format!("spikes seed {SEED} steps {STEPS}"),
+        result.total_spikes().to_string(),
+    ));
+    out
+}
+
+fn render(quantities: &[(String, String)]) -> String {
+    let mut text =
+        String::from("# Golden results for Network::example_touch_circuit; see tests/golden.rs.\n");
+    for (key, value) in quantities {
+        text.push_str(&format!("{key}: {value}\n"));
+    }
+    text
+}
+
+fn parse(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, value) = line
+                .split_once(": ")
+                .unwrap_or_else(|| panic!("malformed golden line {line:?}"));
+            (key.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn touch_circuit_matches_its_golden_results() {
+    let current = quantities();
+    if env::var_os("FLYMIND_BLESS").is_some() {
+        fs::write(golden_path(), render(&current)).unwrap();
+        return;
+    }
+    let golden = parse(&fs::read_to_string(golden_path()).unwrap());
+    let mut moved = Vec::new();
+    for (key, value) in &current {
+        match golden.get(key) {
+            Some(pinned) if pinned == value => {}
+            Some(pinned) => moved.push(format!("{key}: golden {pinned}, now {value}")),
+            None => moved.push(format!("{key}: not in the golden file, now {value}")),
+        }
+    }
+    for key in golden.keys() {
+        if !current.iter().any(|(k, _)| k == key) {
+            moved.push(format!("{key}: in the golden file, no longer computed"));
+        }
+    }
+    assert!(
+        moved.is_empty(),
+        "touch circuit results moved from tests/golden/touch_circuit.txt:\n  {}\n\
+         rerun with FLYMIND_BLESS=1 if the change is intended",
+        moved.join("\n  ")
+    );
+}
@@ -0,0 +1,69 @@
+//! The JavaScript bindings as JavaScript sees them, run in node by
+//! `wasm-bindgen-test`:
+//! `cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --test wasm`
+//! with `wasm-bindgen-test-runner` as the target's runner.
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use celegans_connectome::wasm::load_network_from_string;
+
+const FIXTURE: &str = include_str!("fixtures/touch_circuit.csv");
+
+/// ALML driven hard for 150 ms, as in the `simulate` CLI test.
+const CONFIG: &str = r#"{
+    "duration": 200.0,
+    "dt": 0.5,
+    "seed": 1,
+    "stimulus": [{"neuron": "ALML", "start": 10.0, "duration": 150.0, "amplitude": 40.0}]
+}"#;
+
+#[derive(Deserialize)]
+struct Spike {
+    time: f64,
+    neuron: String,
+}
+
+/// A JS result read back through its JSON text.
+fn from_js<T: for<'de> Deserialize<'de>>(value: JsValue) -> T {
+    let json: String = js_sys::JSON::stringify(&value).unwrap().into();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn the_fixture_loads_with_its_counts() {
+    let network = load_network_from_string(FIXTURE).unwrap();
+    assert_eq!(network.neuron_count(), 17);
+    assert_eq!(network.connection_count(), 20);
+    let path: Option<Vec<String>> = from_js(network.shortest_path("ALML", "AVAL").unwrap());
+    assert_eq!(path.unwrap(), ["ALML", "AVDL", "AVAL"]);
+}
+
+#[wasm_bindgen_test]
+fn a_simulation_round_trips_its_spikes() {
+    let network = load_network_from_string(FIXTURE).unwrap();
+    let mut sim = network.create_sim(CONFIG).unwrap();
+    let mut spikes: Vec<Spike> = Vec::new();
+    let mut steps = 0;
+    while !sim.finished() {
+        steps += sim.step(64);
+        spikes.extend(from_js::<Vec<Spike>>(sim.take_spikes().unwrap()));
+    }
+    assert_eq!(steps, 400);
+    assert_eq!(sim.time(), 200.0);
+    assert_eq!(sim.step(1), 0);
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for spike in &spikes {
+        *counts.entry(spike.neuron.as_str()).or_default() += 1;
+    }
+    assert_eq!(spikes.len(), 120);
+    assert_eq!(counts, BTreeMap::from([("ALML", 100), ("AVDL", 20)]));
+    assert!(spikes.windows(2).all(|w| w[0].time <= w[1].time));
+    assert_eq!(spikes[0].time, 10.0);
+}